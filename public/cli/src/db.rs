@@ -0,0 +1,596 @@
+use std::{error::Error, io::Write, path::Path};
+
+use prjcombine_entity::EntityVec;
+use prjcombine_interconnect::{
+    decode::DecodedBitstream,
+    grid::{DieId, ExpandedGrid},
+};
+use prjcombine_types::{
+    bsdata::BsData,
+    db::DumpFlags,
+    pinout::{ConstraintFormat, Pinout},
+};
+use prjcombine_xilinx_bitstream::{BitstreamMode, KeyData, emit, parse_strict};
+
+/// All known targets, with the family crate that handles each of them.
+pub const TARGETS: &[(&str, &str)] = &[
+    ("xc2000", "xc2000"),
+    ("xc3000", "xc2000"),
+    ("xc3000a", "xc2000"),
+    ("xc4000", "xc2000"),
+    ("xc4000a", "xc2000"),
+    ("xc4000h", "xc2000"),
+    ("xc4000e", "xc2000"),
+    ("xc4000ex", "xc2000"),
+    ("xc4000xla", "xc2000"),
+    ("xc4000xv", "xc2000"),
+    ("spartanxl", "xc2000"),
+    ("xc5200", "xc2000"),
+    ("virtex", "virtex"),
+    ("virtex2", "virtex2"),
+    ("spartan3", "virtex2"),
+    ("fpgacore", "virtex2"),
+    ("spartan6", "spartan6"),
+    ("virtex4", "virtex4"),
+    ("virtex5", "virtex4"),
+    ("virtex6", "virtex4"),
+    ("virtex7", "virtex4"),
+    ("ultrascale", "ultrascale"),
+    ("ultrascaleplus", "ultrascale"),
+    ("versal", "versal"),
+    ("siliconblue", "siliconblue"),
+    ("ecp", "ecp"),
+    ("xp", "ecp"),
+    ("machxo", "ecp"),
+    ("ecp2", "ecp"),
+    ("ecp2m", "ecp"),
+    ("xp2", "ecp"),
+    ("ecp3", "ecp"),
+    ("machxo2", "ecp"),
+    ("ecp4", "ecp"),
+    ("scm", "ecp"),
+    ("ecp5", "ecp"),
+    ("crosslink", "ecp"),
+    ("xc9500", "xc9500"),
+    ("xc9500xl", "xc9500"),
+    ("xc9500xv", "xc9500"),
+    ("xpla3", "xpla3"),
+    ("coolrunner2", "coolrunner2"),
+];
+
+pub fn target_family(target: &str) -> Result<&'static str, Box<dyn Error>> {
+    match TARGETS.iter().find(|&&(name, _)| name == target) {
+        Some(&(_, family)) => Ok(family),
+        None => Err(format!("unknown target {target} (see the targets subcommand)").into()),
+    }
+}
+
+pub enum Db {
+    Xc2000(prjcombine_xc2000::db::Database),
+    Virtex(prjcombine_virtex::db::Database),
+    Virtex2(prjcombine_virtex2::db::Database),
+    Spartan6(prjcombine_spartan6::db::Database),
+    Virtex4(prjcombine_virtex4::db::Database),
+    Ultrascale(prjcombine_ultrascale::db::Database),
+    Siliconblue(prjcombine_siliconblue::db::Database),
+    Ecp(prjcombine_ecp::db::Database),
+    Xc9500(prjcombine_xc9500::Database),
+    Xpla3(prjcombine_xpla3::Database),
+    Coolrunner2(prjcombine_coolrunner2::Database),
+}
+
+pub enum Edev<'a> {
+    Xc2000(prjcombine_xc2000::expanded::ExpandedDevice<'a>),
+    Virtex(prjcombine_virtex::expanded::ExpandedDevice<'a>),
+    Virtex2(prjcombine_virtex2::expanded::ExpandedDevice<'a>),
+    Spartan6(prjcombine_spartan6::expanded::ExpandedDevice<'a>),
+    Virtex4(prjcombine_virtex4::expanded::ExpandedDevice<'a>),
+    Ultrascale(prjcombine_ultrascale::expanded::ExpandedDevice<'a>),
+    Siliconblue(prjcombine_siliconblue::expanded::ExpandedDevice<'a>),
+    Ecp(prjcombine_ecp::expanded::ExpandedDevice<'a>),
+}
+
+/// The names of a device and its packages and speed grades.
+pub struct DeviceInfo {
+    pub name: String,
+    pub packages: Vec<String>,
+    pub speeds: Vec<String>,
+}
+
+macro_rules! find_dev {
+    ($db:expr, $device:expr) => {
+        $db.devices
+            .iter()
+            .find(|dev| dev.name == $device)
+            .ok_or_else(|| format!("unknown device {device}", device = $device))?
+    };
+}
+
+impl Db {
+    /// Loads the database for a target from `<dir>/<target>.zstd`.
+    pub fn load(dir: &Path, target: &str) -> Result<Db, Box<dyn Error>> {
+        let family = target_family(target)?;
+        if family == "versal" {
+            return Err("no Versal database is available yet".into());
+        }
+        let path = dir.join(format!("{target}.zstd"));
+        if !path.exists() {
+            return Err(format!(
+                "database {path} not found (set --db-dir or PRJCOMBINE_DB_DIR)",
+                path = path.display()
+            )
+            .into());
+        }
+        let wrap = |e: Box<dyn Error>| format!("{path}: {e}", path = path.display());
+        Ok(match family {
+            "xc2000" => {
+                Db::Xc2000(prjcombine_xc2000::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "virtex" => {
+                Db::Virtex(prjcombine_virtex::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "virtex2" => {
+                Db::Virtex2(prjcombine_virtex2::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "spartan6" => {
+                Db::Spartan6(prjcombine_spartan6::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "virtex4" => {
+                Db::Virtex4(prjcombine_virtex4::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "ultrascale" => {
+                Db::Ultrascale(prjcombine_ultrascale::db::Database::from_file(&path).map_err(wrap)?)
+            }
+            "siliconblue" => Db::Siliconblue(
+                prjcombine_siliconblue::db::Database::from_file(&path).map_err(wrap)?,
+            ),
+            "ecp" => Db::Ecp(prjcombine_ecp::db::Database::from_file(&path).map_err(wrap)?),
+            "xc9500" => Db::Xc9500(prjcombine_xc9500::Database::from_file(&path).map_err(wrap)?),
+            "xpla3" => Db::Xpla3(prjcombine_xpla3::Database::from_file(&path).map_err(wrap)?),
+            "coolrunner2" => {
+                Db::Coolrunner2(prjcombine_coolrunner2::Database::from_file(&path).map_err(wrap)?)
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn dump(&self, o: &mut dyn Write, flags: DumpFlags) -> std::io::Result<()> {
+        match self {
+            Db::Xc2000(db) => db.dump(o, flags),
+            Db::Virtex(db) => db.dump(o, flags),
+            Db::Virtex2(db) => db.dump(o, flags),
+            Db::Spartan6(db) => db.dump(o, flags),
+            Db::Virtex4(db) => db.dump(o, flags),
+            Db::Ultrascale(db) => db.dump(o, flags),
+            Db::Siliconblue(db) => db.dump(o, flags),
+            Db::Ecp(db) => db.dump(o, flags),
+            Db::Xc9500(db) => db.dump(o, flags),
+            Db::Xpla3(db) => db.dump(o, flags),
+            Db::Coolrunner2(db) => db.dump(o, flags),
+        }
+    }
+
+    pub fn write_json(&self, o: &mut dyn Write, pretty: bool) -> serde_json::Result<()> {
+        macro_rules! write_json {
+            ($db:expr) => {
+                if pretty {
+                    serde_json::to_writer_pretty(o, $db)
+                } else {
+                    serde_json::to_writer(o, $db)
+                }
+            };
+        }
+        match self {
+            Db::Xc2000(db) => write_json!(db),
+            Db::Virtex(db) => write_json!(db),
+            Db::Virtex2(db) => write_json!(db),
+            Db::Spartan6(db) => write_json!(db),
+            Db::Virtex4(db) => write_json!(db),
+            Db::Ultrascale(db) => write_json!(db),
+            Db::Siliconblue(db) => write_json!(db),
+            Db::Ecp(db) => write_json!(db),
+            Db::Xc9500(db) => write_json!(db),
+            Db::Xpla3(db) => write_json!(db),
+            Db::Coolrunner2(db) => write_json!(db),
+        }
+    }
+
+    pub fn devices(&self) -> Vec<DeviceInfo> {
+        macro_rules! devices {
+            ($db:expr, $speeds:ident) => {
+                $db.devices
+                    .iter()
+                    .map(|dev| DeviceInfo {
+                        name: dev.name.clone(),
+                        packages: dev.bonds.keys().cloned().collect(),
+                        speeds: dev.speeds.$speeds().cloned().collect(),
+                    })
+                    .collect()
+            };
+        }
+        match self {
+            Db::Xc2000(db) => devices!(db, values),
+            Db::Virtex(db) => devices!(db, values),
+            Db::Virtex2(db) => devices!(db, values),
+            Db::Spartan6(db) => devices!(db, values),
+            Db::Virtex4(db) => devices!(db, values),
+            Db::Ultrascale(db) => devices!(db, values),
+            Db::Siliconblue(db) => devices!(db, keys),
+            Db::Ecp(db) => devices!(db, values),
+            Db::Xc9500(db) => devices!(db, keys),
+            Db::Xpla3(db) => devices!(db, keys),
+            Db::Coolrunner2(db) => devices!(db, keys),
+        }
+    }
+
+    /// Writes the pinout of a device in the given package.
+    pub fn write_bond(
+        &self,
+        o: &mut dyn Write,
+        device: &str,
+        package: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        macro_rules! bond {
+            ($db:expr, $dev:expr, $bond:expr) => {{
+                let Some(&bond) = $bond else {
+                    return Err(format!("unknown package {package} for device {device}").into());
+                };
+                writeln!(o, "bond {device}-{package} {{", device = $dev.name)?;
+                &$db.bonds[bond]
+            }};
+        }
+        match self {
+            Db::Xc2000(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Virtex(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Virtex2(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Spartan6(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Virtex4(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Ultrascale(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Siliconblue(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package)).dump(o, &db.int)?;
+            }
+            Db::Ecp(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package).map(|(_, b)| b)).dump(o)?;
+            }
+            Db::Xc9500(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package)).dump(o)?;
+            }
+            Db::Xpla3(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package)).dump(o)?;
+            }
+            Db::Coolrunner2(db) => {
+                let dev = find_dev!(db, device);
+                bond!(db, dev, dev.bonds.get(package)).dump(o)?;
+            }
+        }
+        writeln!(o, "}}")?;
+        Ok(())
+    }
+
+    /// Writes the timing values of a device in the given speed grade.
+    pub fn write_speed(
+        &self,
+        o: &mut dyn Write,
+        device: &str,
+        grade: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        macro_rules! speed {
+            ($db:expr) => {{
+                let dev = find_dev!($db, device);
+                let Some(&speed) = dev.speeds.get(grade) else {
+                    return Err(format!("unknown speed grade {grade} for device {device}").into());
+                };
+                writeln!(o, "speed {device}{grade} {{")?;
+                write!(o, "{speed}", speed = $db.speeds[speed])?;
+                writeln!(o, "}}")?;
+            }};
+        }
+        match self {
+            Db::Siliconblue(db) => speed!(db),
+            Db::Xc9500(db) => speed!(db),
+            Db::Xpla3(db) => speed!(db),
+            Db::Coolrunner2(db) => speed!(db),
+            _ => {
+                return Err(format!(
+                    "the {family} database has no speed data",
+                    family = self.family()
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the pinout table of a device in the given package.
+    pub fn make_pinout(&self, device: &str, package: &str) -> Result<Pinout, Box<dyn Error>> {
+        macro_rules! bond {
+            ($db:expr, $bond:expr) => {{
+                let Some(&bond) = $bond else {
+                    return Err(format!("unknown package {package} for device {device}").into());
+                };
+                &$db.bonds[bond]
+            }};
+        }
+        Ok(match self {
+            Db::Xc2000(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                db.chips[dev.chip].make_pinout(bond, device, package)
+            }
+            Db::Virtex(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                db.chips[dev.chip].make_pinout(bond, device, package)
+            }
+            Db::Virtex2(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                db.chips[dev.chip].make_pinout(bond, device, package)
+            }
+            Db::Spartan6(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                db.chips[dev.chip].make_pinout(bond, device, package)
+            }
+            Db::Virtex4(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                let Edev::Virtex4(edev) = self.expand(device)? else {
+                    unreachable!()
+                };
+                edev.make_pinout(bond, device, package)
+            }
+            Db::Ultrascale(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                let Edev::Ultrascale(edev) = self.expand(device)? else {
+                    unreachable!()
+                };
+                edev.make_pinout(bond, device, package)
+            }
+            Db::Siliconblue(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package));
+                db.chips[dev.chip].make_pinout(bond, &db.int, device, package)
+            }
+            Db::Ecp(db) => {
+                let dev = find_dev!(db, device);
+                let bond = bond!(db, dev.bonds.get(package).map(|(_, b)| b));
+                db.chips[dev.chip].make_pinout(bond, device, package)
+            }
+            _ => {
+                return Err(format!(
+                    "pinout export is not supported for the {family} family",
+                    family = self.family()
+                )
+                .into());
+            }
+        })
+    }
+
+    /// The constraint file formats native to the family's vendor tools.
+    pub fn constraint_formats(&self) -> &'static [ConstraintFormat] {
+        match self {
+            Db::Xc2000(_) | Db::Virtex(_) | Db::Virtex2(_) | Db::Spartan6(_) => {
+                &[ConstraintFormat::Ucf]
+            }
+            Db::Virtex4(_) => &[ConstraintFormat::Ucf, ConstraintFormat::Xdc],
+            Db::Ultrascale(_) => &[ConstraintFormat::Xdc],
+            Db::Siliconblue(_) => &[ConstraintFormat::Pcf],
+            Db::Ecp(_) => &[ConstraintFormat::Lpf],
+            Db::Xc9500(_) | Db::Xpla3(_) | Db::Coolrunner2(_) => &[],
+        }
+    }
+
+    /// The legacy bitstream data of the database, if it has any.
+    pub fn bsdata(&self) -> Option<&BsData> {
+        match self {
+            Db::Virtex(db) => Some(&db.bsdata),
+            Db::Virtex4(db) => Some(&db.bsdata),
+            Db::Ultrascale(db) => Some(&db.bsdata),
+            Db::Ecp(db) => Some(&db.bsdata),
+            _ => None,
+        }
+    }
+
+    pub fn family(&self) -> &'static str {
+        match self {
+            Db::Xc2000(_) => "xc2000",
+            Db::Virtex(_) => "virtex",
+            Db::Virtex2(_) => "virtex2",
+            Db::Spartan6(_) => "spartan6",
+            Db::Virtex4(_) => "virtex4",
+            Db::Ultrascale(_) => "ultrascale",
+            Db::Siliconblue(_) => "siliconblue",
+            Db::Ecp(_) => "ecp",
+            Db::Xc9500(_) => "xc9500",
+            Db::Xpla3(_) => "xpla3",
+            Db::Coolrunner2(_) => "coolrunner2",
+        }
+    }
+
+    pub fn expand(&self, device: &str) -> Result<Edev<'_>, Box<dyn Error>> {
+        Ok(match self {
+            Db::Xc2000(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Xc2000(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Virtex(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Virtex(db.chips[dev.chip].expand_grid(&dev.disabled, &db.int))
+            }
+            Db::Virtex2(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Virtex2(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Spartan6(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Spartan6(db.chips[dev.chip].expand_grid(&db.int, &dev.disabled))
+            }
+            Db::Virtex4(db) => {
+                let dev = find_dev!(db, device);
+                let chips: EntityVec<DieId, _> =
+                    dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+                let interposer = dev.interposer.map(|ip| &db.interposers[ip]);
+                Edev::Virtex4(prjcombine_virtex4::expand_grid(
+                    &chips,
+                    interposer,
+                    &dev.disabled,
+                    &db.int,
+                    &db.gtz,
+                ))
+            }
+            Db::Ultrascale(db) => {
+                let dev = find_dev!(db, device);
+                let chips: EntityVec<DieId, _> =
+                    dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+                Edev::Ultrascale(prjcombine_ultrascale::expand_grid(
+                    &chips,
+                    &db.interposers[dev.interposer],
+                    &dev.disabled,
+                    &db.int,
+                ))
+            }
+            Db::Siliconblue(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Siliconblue(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Ecp(db) => {
+                let dev = find_dev!(db, device);
+                Edev::Ecp(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Xc9500(_) | Db::Xpla3(_) | Db::Coolrunner2(_) => {
+                return Err("CPLD databases have no interconnect grid".into());
+            }
+        })
+    }
+}
+
+impl<'a> Edev<'a> {
+    pub fn egrid(&self) -> &ExpandedGrid<'a> {
+        match self {
+            Edev::Xc2000(edev) => &edev.egrid,
+            Edev::Virtex(edev) => &edev.egrid,
+            Edev::Virtex2(edev) => &edev.egrid,
+            Edev::Spartan6(edev) => &edev.egrid,
+            Edev::Virtex4(edev) => &edev.egrid,
+            Edev::Ultrascale(edev) => &edev.egrid,
+            Edev::Siliconblue(edev) => &edev.egrid,
+            Edev::Ecp(edev) => &edev.egrid,
+        }
+    }
+
+    /// Parses a raw bitstream and decodes it tile by tile.
+    pub fn decode(&self, bsdata: &BsData, data: &[u8]) -> Result<DecodedBitstream, Box<dyn Error>> {
+        macro_rules! decode {
+            ($edev:expr) => {{
+                let bitstream = parse_strict(&$edev.bs_geom, data, &KeyData::None)?;
+                Ok($edev.decode(bsdata, &bitstream)?)
+            }};
+        }
+        match self {
+            Edev::Virtex(edev) => decode!(edev),
+            Edev::Virtex2(edev) => decode!(edev),
+            Edev::Spartan6(edev) => decode!(edev),
+            Edev::Virtex4(edev) => decode!(edev),
+            Edev::Siliconblue(edev) => {
+                let bitstream = prjcombine_siliconblue::bitstream::Bitstream::parse(data)?;
+                Ok(edev.decode(bsdata, &bitstream)?)
+            }
+            Edev::Ecp(edev) => {
+                let geom = ecp_geom(edev)?;
+                let bitstream = prjcombine_ecp::bitstream::Bitstream::parse(&geom, data)?;
+                Ok(edev.decode(bsdata, &bitstream)?)
+            }
+            _ => Err("bitstream decoding is not supported for this family".into()),
+        }
+    }
+
+    /// Encodes a tile configuration into a raw bitstream.
+    pub fn encode(
+        &self,
+        bsdata: &BsData,
+        config: &DecodedBitstream,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        macro_rules! encode {
+            ($edev:expr) => {{
+                let bitstream = $edev.encode(bsdata, config)?;
+                Ok(emit(&$edev.bs_geom, &bitstream, BitstreamMode::Plain)?)
+            }};
+        }
+        match self {
+            Edev::Virtex(edev) => encode!(edev),
+            Edev::Virtex2(edev) => encode!(edev),
+            Edev::Spartan6(edev) => encode!(edev),
+            Edev::Virtex4(edev) => encode!(edev),
+            Edev::Siliconblue(edev) => Ok(edev.encode(bsdata, config)?.emit()?),
+            Edev::Ecp(edev) => {
+                let geom = ecp_geom(edev)?;
+                Ok(edev.encode(&geom, bsdata, config)?.emit(&geom)?)
+            }
+            _ => Err("bitstream encoding is not supported for this family".into()),
+        }
+    }
+
+    /// Writes the tile map: the size of every die, followed by every tile with its class.
+    pub fn write_tiles(&self, o: &mut dyn Write) -> std::io::Result<()> {
+        let egrid = self.egrid();
+        for die in egrid.die.ids() {
+            writeln!(
+                o,
+                "// {die}: {cols} columns, {rows} rows",
+                cols = egrid.cols(die).len(),
+                rows = egrid.rows(die).len()
+            )?;
+        }
+        for (tcrd, tile) in egrid.tiles() {
+            write!(
+                o,
+                "{tcrd} {kind}",
+                tcrd = tcrd.to_string(egrid.db),
+                kind = egrid.db.tile_classes.key(tile.class)
+            )?;
+            if tile.cells.len() > 1 {
+                for cell in tile.cells.values() {
+                    write!(o, " {cell}")?;
+                }
+            }
+            writeln!(o)?;
+        }
+        Ok(())
+    }
+}
+
+fn ecp_geom(
+    edev: &prjcombine_ecp::expanded::ExpandedDevice,
+) -> Result<prjcombine_ecp::bitstream::BitstreamGeom, Box<dyn Error>> {
+    edev.bitstream_geom().ok_or_else(|| {
+        format!(
+            "the bitstream geometry of {kind} devices is not known yet",
+            kind = edev.chip.kind
+        )
+        .into()
+    })
+}
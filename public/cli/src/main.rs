@@ -1,10 +1,91 @@
 use clap::{Arg, ArgAction, Command, value_parser};
-use prjcombine_types::db::DumpFlags;
-use std::error::Error;
+use prjcombine_entity::EntityPartVec;
+use prjcombine_interconnect::{
+    db::{DeviceDataId, IntDb, TableValue},
+    decode::DecodedBitstream,
+};
+use prjcombine_types::{
+    bsdata::BsData,
+    bsdl::Bsdl,
+    db::DumpFlags,
+    kicad::{KicadSymbol, emit_library},
+    pinout::ConstraintFormat,
+};
+use std::{
+    error::Error,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-fn main() -> Result<(), Box<dyn Error>> {
+mod db;
+
+use db::{Db, TARGETS};
+
+fn parse_idcode(s: &str) -> Result<u32, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u32::from_str_radix(s, 16).map_err(|e| e.to_string())
+}
+
+fn get_idcode(int: &IntDb, data: &EntityPartVec<DeviceDataId, TableValue>) -> Option<u32> {
+    let (ddid, _) = int.devdata.get("IDCODE")?;
+    match data.get(ddid)? {
+        TableValue::BitVec(val) => Some(val.as_u32()),
+        _ => None,
+    }
+}
+
+fn open_out(path: Option<&PathBuf>) -> Result<BufWriter<Box<dyn Write>>, Box<dyn Error>> {
+    let out: Box<dyn Write> = match path {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .map_err(|e| format!("{path}: {e}", path = path.display()))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    Ok(BufWriter::new(out))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(std::fs::read(path).map_err(|e| format!("{path}: {e}", path = path.display()))?)
+}
+
+macro_rules! find_bond {
+    ($db:expr, $device:expr, $package:expr) => {{
+        let Some(dev) = $db.devices.iter().find(|dev| dev.name == *$device) else {
+            return Err(format!("unknown device {device}", device = $device).into());
+        };
+        let Some((_, &bond)) = dev.bonds.get($package) else {
+            return Err(format!(
+                "unknown package {package} for device {device}",
+                package = $package,
+                device = $device
+            )
+            .into());
+        };
+        (dev, &$db.chips[dev.chip], &$db.bonds[bond])
+    }};
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     let m = Command::new("prjcombine-cli")
         .subcommand_required(true)
+        .arg(
+            Arg::new("db-dir")
+                .long("db-dir")
+                .env("PRJCOMBINE_DB_DIR")
+                .default_value("../databases")
+                .global(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .subcommand(Command::new("targets"))
+        .subcommand(
+            Command::new("devices").arg(
+                Arg::new("target")
+                    .required(true)
+                    .value_parser(value_parser!(String)),
+            ),
+        )
         .subcommand(
             Command::new("dumpdb")
                 .arg(
@@ -49,8 +130,235 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .action(ArgAction::SetTrue),
                 ),
         )
+        .subcommand(
+            Command::new("diffdb")
+                .arg(
+                    Arg::new("old")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("new")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("target")
+                        .short('t')
+                        .long("target")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("json")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("export-json")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf)))
+                .arg(
+                    Arg::new("pretty")
+                        .short('p')
+                        .long("pretty")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("expand")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("bond")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("speed")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("grade")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("pinout")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf)))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["csv", "json", "ucf", "xdc", "pcf", "lpf"])
+                        .default_value("csv"),
+                ),
+        )
+        .subcommand(
+            Command::new("kicad")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("decode")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("bitstream")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf))),
+        )
+        .subcommand(
+            Command::new("encode")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("config")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("out")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("bsdl")
+                .arg(
+                    Arg::new("target")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("device")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("package")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(Arg::new("out").value_parser(value_parser!(PathBuf)))
+                .arg(Arg::new("idcode").long("idcode").value_parser(parse_idcode)),
+        )
         .get_matches();
+    let db_dir = m.get_one::<PathBuf>("db-dir").unwrap();
     match m.subcommand() {
+        Some(("targets", _)) => {
+            let mut out = open_out(None)?;
+            for &(target, family) in TARGETS {
+                let status = if db_dir.join(format!("{target}.zstd")).exists() {
+                    ""
+                } else {
+                    " (no database)"
+                };
+                writeln!(out, "{target:16} {family}{status}")?;
+            }
+            out.flush()?;
+        }
+        Some(("devices", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let mut out = open_out(None)?;
+            for dev in db.devices() {
+                writeln!(out, "{name}", name = dev.name)?;
+                if !dev.packages.is_empty() {
+                    writeln!(out, "\tpackages: {}", dev.packages.join(", "))?;
+                }
+                if !dev.speeds.is_empty() {
+                    writeln!(out, "\tspeeds: {}", dev.speeds.join(", "))?;
+                }
+            }
+            out.flush()?;
+        }
         Some(("dumpdb", m)) => {
             let arg_target = m.get_one::<String>("target").unwrap();
             let flag_intdb = m.get_flag("intdb");
@@ -77,78 +385,205 @@ fn main() -> Result<(), Box<dyn Error>> {
             {
                 flags = DumpFlags::all();
             }
-            match arg_target.as_str() {
-                "xc2000" | "xc3000" | "xc3000a" | "xc4000" | "xc4000a" | "xc4000h" | "xc4000e"
-                | "xc4000ex" | "xc4000xla" | "xc4000xv" | "spartanxl" | "xc5200" => {
-                    let db = prjcombine_xc2000::db::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
-                }
-                "virtex" => {
-                    let db =
-                        prjcombine_virtex::db::Database::from_file("../databases/virtex.zstd")?;
-                    db.dump(&mut std::io::stdout(), flags)?;
-                }
-                "virtex2" | "spartan3" | "fpgacore" => {
-                    let db = prjcombine_virtex2::db::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
-                }
-                "spartan6" => {
-                    let db =
-                        prjcombine_spartan6::db::Database::from_file("../databases/spartan6.zstd")?;
-                    db.dump(&mut std::io::stdout(), flags)?;
-                }
-                "virtex4" | "virtex5" | "virtex6" | "virtex7" => {
-                    let db = prjcombine_virtex4::db::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+            let db = Db::load(db_dir, arg_target)?;
+            let mut out = open_out(None)?;
+            db.dump(&mut out, flags)?;
+            out.flush()?;
+        }
+        Some(("diffdb", m)) => {
+            let arg_old = m.get_one::<PathBuf>("old").unwrap();
+            let arg_new = m.get_one::<PathBuf>("new").unwrap();
+            let target = match m.get_one::<String>("target") {
+                Some(target) => target.clone(),
+                None => arg_old
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .ok_or("cannot determine target from file name; use --target")?
+                    .to_string(),
+            };
+            macro_rules! diff {
+                ($db:ty) => {{
+                    let old = <$db>::from_file(arg_old)?;
+                    let new = <$db>::from_file(arg_new)?;
+                    old.diff(&new)
+                }};
+            }
+            let diff = match db::target_family(&target)? {
+                "xc2000" => diff!(prjcombine_xc2000::db::Database),
+                "virtex" => diff!(prjcombine_virtex::db::Database),
+                "virtex2" => diff!(prjcombine_virtex2::db::Database),
+                "spartan6" => diff!(prjcombine_spartan6::db::Database),
+                "virtex4" => diff!(prjcombine_virtex4::db::Database),
+                "ultrascale" => diff!(prjcombine_ultrascale::db::Database),
+                "versal" => return Err("no Versal database is available yet".into()),
+                "siliconblue" => diff!(prjcombine_siliconblue::db::Database),
+                "ecp" => diff!(prjcombine_ecp::db::Database),
+                "xc9500" => diff!(prjcombine_xc9500::Database),
+                "xpla3" => diff!(prjcombine_xpla3::Database),
+                "coolrunner2" => diff!(prjcombine_coolrunner2::Database),
+                _ => unreachable!(),
+            };
+            if m.get_flag("json") {
+                let mut out = std::io::stdout().lock();
+                for entry in &diff.entries {
+                    serde_json::to_writer(&mut out, entry)?;
+                    writeln!(out)?;
                 }
-                "ultrascale" | "ultrascaleplus" => {
-                    let db = prjcombine_ultrascale::db::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+            } else {
+                diff.write_text(&mut std::io::stdout())?;
+            }
+        }
+        Some(("export-json", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            db.write_json(&mut out, m.get_flag("pretty"))?;
+            writeln!(out)?;
+            out.flush()?;
+        }
+        Some(("expand", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let edev = db.expand(arg_device)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            edev.write_tiles(&mut out)?;
+            out.flush()?;
+        }
+        Some(("bond", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_package = m.get_one::<String>("package").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            db.write_bond(&mut out, arg_device, arg_package)?;
+            out.flush()?;
+        }
+        Some(("speed", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_grade = m.get_one::<String>("grade").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            db.write_speed(&mut out, arg_device, arg_grade)?;
+            out.flush()?;
+        }
+        Some(("pinout", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_package = m.get_one::<String>("package").unwrap();
+            let arg_format = m.get_one::<String>("format").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let pinout = db.make_pinout(arg_device, arg_package)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            match arg_format.as_str() {
+                "csv" => pinout.emit_csv(&mut out)?,
+                "json" => {
+                    serde_json::to_writer_pretty(&mut out, &pinout)?;
+                    writeln!(out)?;
                 }
-                "versal" => {
-                    todo!()
+                _ => {
+                    let format: ConstraintFormat = arg_format.parse()?;
+                    if !db.constraint_formats().contains(&format) {
+                        return Err(format!(
+                            "{format} constraints are not supported for target {arg_target}"
+                        )
+                        .into());
+                    }
+                    pinout.emit_constraints(&mut out, format)?;
                 }
-                "siliconblue" => {
-                    let db = prjcombine_siliconblue::db::Database::from_file(
-                        "../databases/siliconblue.zstd",
-                    )?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+            }
+            out.flush()?;
+        }
+        Some(("kicad", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_package = m.get_one::<String>("package").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let pinout = db.make_pinout(arg_device, arg_package)?;
+            let symbol = KicadSymbol::from_pinout(&pinout);
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            emit_library(&mut out, &[symbol])?;
+            out.flush()?;
+        }
+        Some(("decode", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_bitstream = m.get_one::<PathBuf>("bitstream").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let edev = db.expand(arg_device)?;
+            let data = read_file(arg_bitstream)?;
+            let empty = BsData::new();
+            let decoded = edev.decode(db.bsdata().unwrap_or(&empty), &data)?;
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            decoded.dump(edev.egrid(), &mut out)?;
+            out.flush()?;
+        }
+        Some(("encode", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_config = m.get_one::<PathBuf>("config").unwrap();
+            let arg_out = m.get_one::<PathBuf>("out").unwrap();
+            let db = Db::load(db_dir, arg_target)?;
+            let edev = db.expand(arg_device)?;
+            let text = String::from_utf8(read_file(arg_config)?)
+                .map_err(|_| format!("{path}: not UTF-8", path = arg_config.display()))?;
+            let config = DecodedBitstream::parse(edev.egrid(), &text)
+                .map_err(|e| format!("{path}: {e}", path = arg_config.display()))?;
+            let empty = BsData::new();
+            let data = edev.encode(db.bsdata().unwrap_or(&empty), &config)?;
+            std::fs::write(arg_out, data)
+                .map_err(|e| format!("{path}: {e}", path = arg_out.display()))?;
+        }
+        Some(("bsdl", m)) => {
+            let arg_target = m.get_one::<String>("target").unwrap();
+            let arg_device = m.get_one::<String>("device").unwrap();
+            let arg_package = m.get_one::<String>("package").unwrap();
+            let arg_idcode = m.get_one::<u32>("idcode").copied();
+            let entity = format!("{arg_device}_{arg_package}");
+            let db = Db::load(db_dir, arg_target)?;
+            let bsdl: Bsdl = match db {
+                Db::Xc2000(ref db)
+                    if !matches!(arg_target.as_str(), "xc2000" | "xc3000" | "xc3000a") =>
+                {
+                    let (_, chip, bond) = find_bond!(db, arg_device, arg_package);
+                    chip.make_bsdl(bond, &entity, arg_package, arg_idcode)
                 }
-                "ecp" | "xp" | "machxo" | "ecp2" | "ecp2m" | "xp2" | "ecp3" | "machxo2"
-                | "ecp4" | "scm" | "ecp5" | "crosslink" => {
-                    let db = prjcombine_ecp::db::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+                Db::Virtex(ref db) => {
+                    let (_, chip, bond) = find_bond!(db, arg_device, arg_package);
+                    chip.make_bsdl(bond, &entity, arg_package, arg_idcode)
                 }
-                "xc9500" | "xc9500xl" | "xc9500xv" => {
-                    let db = prjcombine_xc9500::Database::from_file(format!(
-                        "../databases/{arg_target}.zstd"
-                    ))?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+                Db::Virtex2(ref db) => {
+                    let (dev, chip, bond) = find_bond!(db, arg_device, arg_package);
+                    let idcode = arg_idcode.or_else(|| get_idcode(&db.int, &dev.data));
+                    chip.make_bsdl(bond, &entity, arg_package, idcode)
                 }
-                "xpla3" => {
-                    let db = prjcombine_xpla3::Database::from_file("../databases/xpla3.zstd")?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+                Db::Spartan6(ref db) => {
+                    let (dev, chip, bond) = find_bond!(db, arg_device, arg_package);
+                    let idcode = arg_idcode.or_else(|| get_idcode(&db.int, &dev.data));
+                    chip.make_bsdl(bond, &entity, arg_package, idcode)
                 }
-                "coolrunner2" => {
-                    let db = prjcombine_coolrunner2::Database::from_file(
-                        "../databases/coolrunner2.zstd",
-                    )?;
-                    db.dump(&mut std::io::stdout(), flags)?;
+                Db::Ecp(ref db) if arg_target != "crosslink" => {
+                    let (_, chip, bond) = find_bond!(db, arg_device, arg_package);
+                    chip.make_bsdl(bond, &entity, arg_package, arg_idcode)
                 }
-                _ => panic!("unknown target {arg_target}"),
-            }
+                _ => return Err(format!("no BSDL support for target {arg_target}").into()),
+            };
+            let mut out = open_out(m.get_one::<PathBuf>("out"))?;
+            bsdl.emit(&mut out)?;
+            out.flush()?;
         }
         _ => unreachable!(),
     }
     Ok(())
 }
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::process::Command;
+
+const DB_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases");
+
+struct Output {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+fn run(args: &[&str]) -> Output {
+    let out = Command::new(env!("CARGO_BIN_EXE_prjcombine-cli"))
+        .env_remove("PRJCOMBINE_DB_DIR")
+        .arg("--db-dir")
+        .arg(DB_DIR)
+        .args(args)
+        .output()
+        .unwrap();
+    Output {
+        success: out.status.success(),
+        stdout: String::from_utf8(out.stdout).unwrap(),
+        stderr: String::from_utf8(out.stderr).unwrap(),
+    }
+}
+
+/// Runs the CLI and checks that it fails cleanly with the given message.
+fn check_error(args: &[&str], msg: &str) {
+    let out = run(args);
+    assert!(!out.success, "{args:?} succeeded");
+    assert!(
+        !out.stderr.contains("panicked"),
+        "{args:?} panicked: {stderr}",
+        stderr = out.stderr
+    );
+    assert_eq!(out.stderr.trim_end(), format!("error: {msg}"), "{args:?}");
+}
+
+#[test]
+fn cli_ok() {
+    let out = run(&["devices", "xc9500"]);
+    assert!(out.success, "{stderr}", stderr = out.stderr);
+    assert!(
+        out.stdout
+            .starts_with("xc9536\n\tpackages: cs48, pc44, vq44\n")
+    );
+
+    let out = run(&["pinout", "virtex", "xc2s15", "vq100"]);
+    assert!(out.success, "{stderr}", stderr = out.stderr);
+    assert!(out.stdout.starts_with(
+        "pin,pad,name,kind,bank,io,diff,diff_pin,functions\nP1,GND,GND,ground,,,,,\n"
+    ));
+}
+
+#[test]
+fn cli_unknown_target() {
+    let msg = "unknown target xc1234 (see the targets subcommand)";
+    check_error(&["devices", "xc1234"], msg);
+    check_error(&["dumpdb", "xc1234"], msg);
+    check_error(&["export-json", "xc1234"], msg);
+    check_error(&["expand", "xc1234", "xc1234a"], msg);
+    check_error(&["pinout", "xc1234", "xc1234a", "pq208"], msg);
+    check_error(&["kicad", "xc1234", "xc1234a", "pq208"], msg);
+    check_error(&["bsdl", "xc1234", "xc1234a", "pq208"], msg);
+    check_error(
+        &["diffdb", "--target", "xc1234", "old.zstd", "new.zstd"],
+        msg,
+    );
+    check_error(
+        &["devices", "versal"],
+        "no Versal database is available yet",
+    );
+}
+
+#[test]
+fn cli_unknown_device() {
+    let msg = "unknown device xcv9999";
+    check_error(&["expand", "virtex", "xcv9999"], msg);
+    check_error(&["bond", "virtex", "xcv9999", "pq240"], msg);
+    check_error(&["pinout", "virtex", "xcv9999", "pq240"], msg);
+    check_error(&["kicad", "virtex", "xcv9999", "pq240"], msg);
+    check_error(&["bsdl", "virtex", "xcv9999", "pq240"], msg);
+    check_error(&["speed", "xc9500", "xcv9999", "-10"], msg);
+}
+
+#[test]
+fn cli_unknown_package() {
+    let msg = "unknown package pq240 for device xc2s15";
+    check_error(&["bond", "virtex", "xc2s15", "pq240"], msg);
+    check_error(&["pinout", "virtex", "xc2s15", "pq240"], msg);
+    check_error(&["kicad", "virtex", "xc2s15", "pq240"], msg);
+    check_error(&["bsdl", "virtex", "xc2s15", "pq240"], msg);
+    check_error(
+        &["speed", "xc9500", "xc9536", "-99"],
+        "unknown speed grade -99 for device xc9536",
+    );
+}
+
+#[test]
+fn cli_unsupported() {
+    check_error(
+        &["bsdl", "xc9500", "xc9536", "pc44"],
+        "no BSDL support for target xc9500",
+    );
+    check_error(
+        &["pinout", "xc9500", "xc9536", "pc44"],
+        "pinout export is not supported for the xc9500 family",
+    );
+    check_error(
+        &[
+            "pinout",
+            "siliconblue",
+            "iCE65L04",
+            "VQ100",
+            "--format",
+            "xdc",
+        ],
+        "xdc constraints are not supported for target siliconblue",
+    );
+    let out = Command::new(env!("CARGO_BIN_EXE_prjcombine-cli"))
+        .args(["--db-dir", "/nonexistent", "devices", "virtex"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap().trim_end(),
+        "error: database /nonexistent/virtex.zstd not found (set --db-dir or PRJCOMBINE_DB_DIR)"
+    );
+}
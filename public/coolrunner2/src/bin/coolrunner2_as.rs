@@ -1,143 +1,11 @@
-use std::{collections::BTreeMap, error::Error, fs::read_to_string, path::PathBuf};
+use std::{error::Error, fs::read_to_string, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
-use prjcombine_coolrunner2::{Chip, Database};
-use prjcombine_entity::EntityId;
-use prjcombine_jed::JedFile;
-use prjcombine_types::{
-    bitvec::BitVec,
-    bsdata::{Tile, TileItemKind},
-    cpld::{BlockId, IoCoord, MacrocellCoord, MacrocellId},
+use prjcombine_coolrunner2::{
+    Database,
+    bitstream::{Bitstream, set_tile_item},
 };
 
-struct Bitstream {
-    fbs: Vec<FbData>,
-    globals: BTreeMap<String, BitVec>,
-}
-
-struct FbData {
-    imux: BTreeMap<String, BitVec>,
-    mcs: [BTreeMap<String, BitVec>; 16],
-    pla_and: [PTermData; 56],
-    pla_or: [BitVec; 16],
-}
-
-struct PTermData {
-    im_t: BitVec,
-    im_f: BitVec,
-}
-
-fn init_tile(tile: &Tile) -> BTreeMap<String, BitVec> {
-    tile.items
-        .iter()
-        .map(|(k, v)| (k.clone(), BitVec::repeat(true, v.bits.len())))
-        .collect()
-}
-
-impl Bitstream {
-    fn new(chip: &Chip) -> Self {
-        let fbs = (0..(chip.block_rows * chip.block_cols.len() * 2))
-            .map(|_| FbData {
-                imux: init_tile(&chip.imux_bits),
-                mcs: core::array::from_fn(|_| init_tile(&chip.mc_bits)),
-                pla_and: core::array::from_fn(|_| PTermData {
-                    im_t: BitVec::repeat(false, 40),
-                    im_f: BitVec::repeat(false, 40),
-                }),
-                pla_or: core::array::from_fn(|_| BitVec::repeat(false, 56)),
-            })
-            .collect();
-        Bitstream {
-            fbs,
-            globals: init_tile(&chip.global_bits),
-        }
-    }
-
-    fn to_jed(&self, chip: &Chip, db: &Database, device: &str) -> JedFile {
-        let mut res = BitVec::new();
-        for (fb, fbd) in self.fbs.iter().enumerate() {
-            for i in 0..40 {
-                let n = format!("IM[{i}].MUX");
-                let val = &fbd.imux[&n];
-                res.extend(val);
-            }
-            for i in 0..56 {
-                let pt = &fbd.pla_and[i];
-                for j in 0..40 {
-                    res.push(!pt.im_t[j]);
-                    res.push(!pt.im_f[j]);
-                }
-            }
-            for i in 0..56 {
-                for j in 0..16 {
-                    res.push(!fbd.pla_or[j][i]);
-                }
-            }
-            for mc in 0..16 {
-                let iobful = chip
-                    .io
-                    .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
-                        BlockId::from_idx(fb),
-                        MacrocellId::from_idx(mc),
-                    )));
-                let mcd = &fbd.mcs[mc];
-                let jed_bits = if !chip.has_vref {
-                    &db.jed_mc_bits_small
-                } else if iobful {
-                    &db.jed_mc_bits_large_iob
-                } else {
-                    &db.jed_mc_bits_large_buried
-                };
-                for (bn, bi) in jed_bits {
-                    res.push(mcd[bn][*bi]);
-                }
-            }
-        }
-        for (bn, bi) in &chip.jed_global_bits {
-            res.push(self.globals[bn][*bi]);
-        }
-        JedFile::new()
-            .with_fuses(res)
-            .with_note(format!(" DEVICE {device}"))
-    }
-}
-
-fn set_tile_item(data: &mut BTreeMap<String, BitVec>, tile: &Tile, item: &str) {
-    if let Some((name, val)) = item.split_once('=') {
-        let item = &tile.items[name];
-        let val = match &item.kind {
-            TileItemKind::Enum { values } => values[val].clone(),
-            TileItemKind::BitVec { invert } => {
-                assert_eq!(val.len(), item.bits.len());
-                val.chars()
-                    .rev()
-                    .enumerate()
-                    .map(|(i, x)| match x {
-                        '0' => invert[i],
-                        '1' => !invert[i],
-                        _ => unreachable!(),
-                    })
-                    .collect()
-            }
-        };
-        data.insert(name.to_string(), val);
-    } else {
-        let (name, val) = if let Some(name) = item.strip_prefix('!') {
-            (name, false)
-        } else {
-            (item, true)
-        };
-        let item = &tile.items[name];
-        match item.kind {
-            TileItemKind::Enum { .. } => unreachable!(),
-            TileItemKind::BitVec { ref invert } => {
-                assert_eq!(item.bits.len(), 1);
-                data.insert(name.to_string(), BitVec::repeat(val ^ invert[0], 1));
-            }
-        }
-    }
-}
-
 pub fn main() -> Result<(), Box<dyn Error>> {
     let m = Command::new("coolrunner2_as")
         .arg(
@@ -1,106 +1,13 @@
 use std::{collections::BTreeMap, error::Error, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
-use prjcombine_coolrunner2::{Chip, Database};
-use prjcombine_entity::EntityId;
+use prjcombine_coolrunner2::{Database, bitstream::Bitstream};
 use prjcombine_jed::{JedFile, JedParserOptions};
 use prjcombine_types::{
     bitvec::BitVec,
     bsdata::{Tile, TileItemKind},
-    cpld::{BlockId, IoCoord, MacrocellCoord, MacrocellId},
 };
 
-struct Bitstream {
-    fbs: Vec<FbData>,
-    globals: BTreeMap<String, BitVec>,
-}
-
-struct FbData {
-    imux: BTreeMap<String, BitVec>,
-    mcs: [BTreeMap<String, BitVec>; 16],
-    pla_and: [PTermData; 56],
-    pla_or: [BitVec; 16],
-}
-
-struct PTermData {
-    im_t: BitVec,
-    im_f: BitVec,
-}
-
-impl Bitstream {
-    fn from_jed(jed: &JedFile, chip: &Chip, db: &Database) -> Self {
-        let fuses = jed.fuses.as_ref().unwrap();
-        let mut fbs = vec![];
-        let mut pos = 0;
-        for fb in 0..(chip.block_cols.len() * chip.block_rows * 2) {
-            let mut fbd = FbData {
-                imux: BTreeMap::new(),
-                mcs: core::array::from_fn(|_| BTreeMap::new()),
-                pla_and: core::array::from_fn(|_| PTermData {
-                    im_t: BitVec::new(),
-                    im_f: BitVec::new(),
-                }),
-                pla_or: core::array::from_fn(|_| BitVec::new()),
-            };
-            for i in 0..40 {
-                let n = format!("IM[{i}].MUX");
-                let data = fuses.slice(pos..(pos + chip.imux_width));
-                pos += chip.imux_width;
-                fbd.imux.insert(n, data);
-            }
-            for i in 0..56 {
-                let pt = &mut fbd.pla_and[i];
-                for _ in 0..40 {
-                    pt.im_t.push(!fuses[pos]);
-                    pos += 1;
-                    pt.im_f.push(!fuses[pos]);
-                    pos += 1;
-                }
-            }
-            for _ in 0..56 {
-                for j in 0..16 {
-                    fbd.pla_or[j].push(!fuses[pos]);
-                    pos += 1;
-                }
-            }
-            for mc in 0..16 {
-                let iobful = chip
-                    .io
-                    .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
-                        BlockId::from_idx(fb),
-                        MacrocellId::from_idx(mc),
-                    )));
-                let mcd = &mut fbd.mcs[mc];
-                let jed_bits = if !chip.has_vref {
-                    &db.jed_mc_bits_small
-                } else if iobful {
-                    &db.jed_mc_bits_large_iob
-                } else {
-                    &db.jed_mc_bits_large_buried
-                };
-                for (bn, bi) in jed_bits {
-                    let bits = mcd.entry(bn.clone()).or_insert_with(|| {
-                        BitVec::repeat(false, chip.mc_bits.items[bn].bits.len())
-                    });
-                    bits.set(*bi, fuses[pos]);
-                    pos += 1;
-                }
-            }
-            fbs.push(fbd);
-        }
-        let mut globals = BTreeMap::new();
-        for (bn, bi) in &chip.jed_global_bits {
-            let bits = globals
-                .entry(bn.clone())
-                .or_insert_with(|| BitVec::repeat(false, chip.global_bits.items[bn].bits.len()));
-            bits.set(*bi, fuses[pos]);
-            pos += 1;
-        }
-        assert_eq!(pos, fuses.len());
-        Bitstream { fbs, globals }
-    }
-}
-
 fn print_tile(data: &BTreeMap<String, BitVec>, tile: &Tile) {
     for (k, v) in data {
         let item = &tile.items[k];
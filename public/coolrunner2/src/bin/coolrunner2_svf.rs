@@ -0,0 +1,66 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+
+use prjcombine_coolrunner2::{Database, svf::program};
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_svf::ProgramOptions;
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("coolrunner2_svf")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("out")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(Arg::new("xsvf").long("xsvf").action(ArgAction::SetTrue))
+        .arg(
+            Arg::new("no-erase")
+                .long("no-erase")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-verify")
+                .long("no-verify")
+                .action(ArgAction::SetTrue),
+        )
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_out = m.get_one::<PathBuf>("out").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let device = device.unwrap();
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let db = Database::from_file(arg_db)?;
+    let options = ProgramOptions {
+        erase: !m.get_flag("no-erase"),
+        verify: !m.get_flag("no-verify"),
+    };
+    let prog = program(&db, dev, &jed, &options)?;
+    if m.get_flag("xsvf") {
+        prog.emit_xsvf_to_file(arg_out)?;
+    } else {
+        prog.emit_svf_to_file(arg_out)?;
+    }
+    Ok(())
+}
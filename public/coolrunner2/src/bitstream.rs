@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{Tile, TileItemKind},
+    cpld::{BlockId, IoCoord, MacrocellCoord, MacrocellId},
+};
+
+use crate::{Chip, Database};
+
+pub struct Bitstream {
+    pub fbs: Vec<FbData>,
+    pub globals: BTreeMap<String, BitVec>,
+}
+
+pub struct FbData {
+    pub imux: BTreeMap<String, BitVec>,
+    pub mcs: [BTreeMap<String, BitVec>; 16],
+    pub pla_and: [PTermData; 56],
+    pub pla_or: [BitVec; 16],
+}
+
+pub struct PTermData {
+    pub im_t: BitVec,
+    pub im_f: BitVec,
+}
+
+fn init_tile(tile: &Tile) -> BTreeMap<String, BitVec> {
+    tile.items
+        .iter()
+        .map(|(k, v)| (k.clone(), BitVec::repeat(true, v.bits.len())))
+        .collect()
+}
+
+impl Bitstream {
+    pub fn new(chip: &Chip) -> Self {
+        let fbs = (0..(chip.block_rows * chip.block_cols.len() * 2))
+            .map(|_| FbData {
+                imux: init_tile(&chip.imux_bits),
+                mcs: core::array::from_fn(|_| init_tile(&chip.mc_bits)),
+                pla_and: core::array::from_fn(|_| PTermData {
+                    im_t: BitVec::repeat(false, 40),
+                    im_f: BitVec::repeat(false, 40),
+                }),
+                pla_or: core::array::from_fn(|_| BitVec::repeat(false, 56)),
+            })
+            .collect();
+        Bitstream {
+            fbs,
+            globals: init_tile(&chip.global_bits),
+        }
+    }
+
+    pub fn from_jed(jed: &JedFile, chip: &Chip, db: &Database) -> Self {
+        let fuses = jed.fuses.as_ref().unwrap();
+        let mut fbs = vec![];
+        let mut pos = 0;
+        for fb in 0..(chip.block_cols.len() * chip.block_rows * 2) {
+            let mut fbd = FbData {
+                imux: BTreeMap::new(),
+                mcs: core::array::from_fn(|_| BTreeMap::new()),
+                pla_and: core::array::from_fn(|_| PTermData {
+                    im_t: BitVec::new(),
+                    im_f: BitVec::new(),
+                }),
+                pla_or: core::array::from_fn(|_| BitVec::new()),
+            };
+            for i in 0..40 {
+                let n = format!("IM[{i}].MUX");
+                let data = fuses.slice(pos..(pos + chip.imux_width));
+                pos += chip.imux_width;
+                fbd.imux.insert(n, data);
+            }
+            for i in 0..56 {
+                let pt = &mut fbd.pla_and[i];
+                for _ in 0..40 {
+                    pt.im_t.push(!fuses[pos]);
+                    pos += 1;
+                    pt.im_f.push(!fuses[pos]);
+                    pos += 1;
+                }
+            }
+            for _ in 0..56 {
+                for j in 0..16 {
+                    fbd.pla_or[j].push(!fuses[pos]);
+                    pos += 1;
+                }
+            }
+            for mc in 0..16 {
+                let iobful = chip
+                    .io
+                    .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
+                        BlockId::from_idx(fb),
+                        MacrocellId::from_idx(mc),
+                    )));
+                let mcd = &mut fbd.mcs[mc];
+                let jed_bits = if !chip.has_vref {
+                    &db.jed_mc_bits_small
+                } else if iobful {
+                    &db.jed_mc_bits_large_iob
+                } else {
+                    &db.jed_mc_bits_large_buried
+                };
+                for (bn, bi) in jed_bits {
+                    let bits = mcd.entry(bn.clone()).or_insert_with(|| {
+                        BitVec::repeat(false, chip.mc_bits.items[bn].bits.len())
+                    });
+                    bits.set(*bi, fuses[pos]);
+                    pos += 1;
+                }
+            }
+            fbs.push(fbd);
+        }
+        let mut globals = BTreeMap::new();
+        for (bn, bi) in &chip.jed_global_bits {
+            let bits = globals
+                .entry(bn.clone())
+                .or_insert_with(|| BitVec::repeat(false, chip.global_bits.items[bn].bits.len()));
+            bits.set(*bi, fuses[pos]);
+            pos += 1;
+        }
+        assert_eq!(pos, fuses.len());
+        Bitstream { fbs, globals }
+    }
+
+    pub fn to_jed(&self, chip: &Chip, db: &Database, device: &str) -> JedFile {
+        let mut res = BitVec::new();
+        for (fb, fbd) in self.fbs.iter().enumerate() {
+            for i in 0..40 {
+                let n = format!("IM[{i}].MUX");
+                let val = &fbd.imux[&n];
+                res.extend(val);
+            }
+            for i in 0..56 {
+                let pt = &fbd.pla_and[i];
+                for j in 0..40 {
+                    res.push(!pt.im_t[j]);
+                    res.push(!pt.im_f[j]);
+                }
+            }
+            for i in 0..56 {
+                for j in 0..16 {
+                    res.push(!fbd.pla_or[j][i]);
+                }
+            }
+            for mc in 0..16 {
+                let iobful = chip
+                    .io
+                    .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
+                        BlockId::from_idx(fb),
+                        MacrocellId::from_idx(mc),
+                    )));
+                let mcd = &fbd.mcs[mc];
+                let jed_bits = if !chip.has_vref {
+                    &db.jed_mc_bits_small
+                } else if iobful {
+                    &db.jed_mc_bits_large_iob
+                } else {
+                    &db.jed_mc_bits_large_buried
+                };
+                for (bn, bi) in jed_bits {
+                    res.push(mcd[bn][*bi]);
+                }
+            }
+        }
+        for (bn, bi) in &chip.jed_global_bits {
+            res.push(self.globals[bn][*bi]);
+        }
+        JedFile::new()
+            .with_fuses(res)
+            .with_note(format!(" DEVICE {device}"))
+    }
+}
+
+pub fn set_tile_item(data: &mut BTreeMap<String, BitVec>, tile: &Tile, item: &str) {
+    if let Some((name, val)) = item.split_once('=') {
+        let item = &tile.items[name];
+        let val = match &item.kind {
+            TileItemKind::Enum { values } => values[val].clone(),
+            TileItemKind::BitVec { invert } => {
+                assert_eq!(val.len(), item.bits.len());
+                val.chars()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, x)| match x {
+                        '0' => invert[i],
+                        '1' => !invert[i],
+                        _ => unreachable!(),
+                    })
+                    .collect()
+            }
+        };
+        data.insert(name.to_string(), val);
+    } else {
+        let (name, val) = if let Some(name) = item.strip_prefix('!') {
+            (name, false)
+        } else {
+            (item, true)
+        };
+        let item = &tile.items[name];
+        match item.kind {
+            TileItemKind::Enum { .. } => unreachable!(),
+            TileItemKind::BitVec { ref invert } => {
+                assert_eq!(item.bits.len(), 1);
+                data.insert(name.to_string(), BitVec::repeat(val ^ invert[0], 1));
+            }
+        }
+    }
+}
+
+/// Returns the current value of an enum item, or `None` if the item is not present or
+/// its bits don't match any value.
+pub fn get_tile_enum<'a>(
+    data: &BTreeMap<String, BitVec>,
+    tile: &'a Tile,
+    name: &str,
+) -> Option<&'a str> {
+    let bits = data.get(name)?;
+    let TileItemKind::Enum { ref values } = tile.items[name].kind else {
+        unreachable!()
+    };
+    values
+        .iter()
+        .find(|(_, val)| *val == bits)
+        .map(|(vn, _)| vn.as_str())
+}
+
+/// Returns the current value of a bitvec item, or `None` if the item is not present.
+pub fn get_tile_bitvec(data: &BTreeMap<String, BitVec>, tile: &Tile, name: &str) -> Option<BitVec> {
+    let bits = data.get(name)?;
+    let TileItemKind::BitVec { ref invert } = tile.items[name].kind else {
+        unreachable!()
+    };
+    Some(
+        bits.iter()
+            .enumerate()
+            .map(|(i, bit)| bit ^ invert[i])
+            .collect(),
+    )
+}
@@ -0,0 +1,628 @@
+//! Compiles a [`Design`] into a JED file.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bsdata::TileItemKind,
+    cpld::{
+        BlockId, IoCoord, MacrocellCoord, MacrocellId,
+        design::{
+            CompileError, ControlKind, Design, Placement, ProductTerm, RegisterKind, Signal, Slew,
+            assign_slots,
+        },
+    },
+};
+
+use crate::{
+    BondPad, Chip, Database,
+    bitstream::{Bitstream, set_tile_item},
+};
+
+const MCS_PER_FB: usize = 16;
+const PTS_PER_FB: usize = 56;
+const IMUX_PER_FB: usize = 40;
+
+fn pta(mc: usize) -> usize {
+    8 + 3 * mc
+}
+
+fn ptb(mc: usize) -> usize {
+    9 + 3 * mc
+}
+
+fn ptc(mc: usize) -> usize {
+    10 + 3 * mc
+}
+
+#[derive(Debug, Default)]
+struct McPlan {
+    items: Vec<String>,
+    sum: Vec<ProductTerm>,
+    // product terms with a fixed position: the per-macrocell PTA/PTB/PTC, and the
+    // FB-wide control terms CT4-CT7
+    pts: Vec<(usize, ProductTerm)>,
+}
+
+struct Compiler<'a> {
+    chip: &'a Chip,
+    design: &'a Design,
+    pins: &'a BTreeMap<String, BondPad>,
+    pad_pins: BTreeMap<IoCoord, &'a str>,
+    used_pads: BTreeSet<IoCoord>,
+    input_pads: BTreeMap<&'a str, IoCoord>,
+    mc_place: BTreeMap<&'a str, MacrocellCoord>,
+    mc_names: BTreeMap<MacrocellCoord, &'a str>,
+    fb_signals: Vec<BTreeSet<&'a Signal>>,
+    fb_terms: Vec<BTreeSet<&'a ProductTerm>>,
+    fclk: [bool; 3],
+    foe: [Option<bool>; 4],
+    fsr_inv: Option<bool>,
+    bs: Bitstream,
+}
+
+impl<'a> Compiler<'a> {
+    fn special_index(&self, pad: IoCoord, prefix: &str) -> Option<usize> {
+        let IoCoord::Macrocell(pad) = pad else {
+            return None;
+        };
+        self.chip.io_special.iter().find_map(|(name, &crd)| {
+            if crd != pad {
+                return None;
+            }
+            let suffix = name.strip_prefix(prefix)?;
+            if suffix.is_empty() {
+                Some(0)
+            } else {
+                suffix.parse().ok()
+            }
+        })
+    }
+
+    fn is_special(&self, pad: IoCoord) -> bool {
+        matches!(pad, IoCoord::Macrocell(pad) if self.chip.io_special.values().any(|&crd| crd == pad))
+    }
+
+    fn pad_of(&self, signal: &Signal) -> Option<IoCoord> {
+        match signal {
+            Signal::Input(name) => self.input_pads.get(name.as_str()).copied(),
+            Signal::Pad(name) => self
+                .mc_place
+                .get(name.as_str())
+                .map(|&crd| IoCoord::Macrocell(crd)),
+            Signal::Macrocell(_) => None,
+        }
+    }
+
+    fn global_source(&self, term: &ProductTerm, prefix: &str) -> Option<(usize, bool)> {
+        let lit = term.single()?;
+        let pad = self.pad_of(&lit.signal)?;
+        Some((self.special_index(pad, prefix)?, lit.inv))
+    }
+
+    fn set_global(&mut self, item: &str) {
+        set_tile_item(&mut self.bs.globals, &self.chip.global_bits, item);
+    }
+
+    fn set_fb(&mut self, fb: usize, item: &str) {
+        set_tile_item(&mut self.bs.fbs[fb].imux, &self.chip.imux_bits, item);
+    }
+
+    fn set_mc(&mut self, crd: MacrocellCoord, item: &str) {
+        set_tile_item(
+            &mut self.bs.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()],
+            &self.chip.mc_bits,
+            item,
+        );
+    }
+
+    fn resolve_pin(&mut self, pin: &str, output: bool) -> Result<IoCoord, CompileError> {
+        let Some(&bpad) = self.pins.get(pin) else {
+            return Err(CompileError::UnknownPin(pin.to_string()));
+        };
+        let pad = match bpad {
+            BondPad::Iob(crd) => IoCoord::Macrocell(crd),
+            BondPad::Ipad(ipad) if !output => IoCoord::Ipad(ipad),
+            _ => return Err(CompileError::PinNotUsable(pin.to_string())),
+        };
+        if !self.used_pads.insert(pad) {
+            return Err(CompileError::PinConflict(pin.to_string()));
+        }
+        Ok(pad)
+    }
+
+    fn mc_terms(&self, name: &str) -> Vec<&'a ProductTerm> {
+        let mc = &self.design.macrocells[name];
+        let mut terms: Vec<&ProductTerm> = mc.sum.iter().collect();
+        if let Some(ref reg) = mc.reg {
+            if self.global_source(&reg.clk, "GCLK").is_none() {
+                terms.push(&reg.clk);
+            }
+            terms.extend(reg.ce.iter());
+            for term in [&reg.rst, &reg.set].into_iter().flatten() {
+                if self.global_source(term, "GSR").is_none() {
+                    terms.push(term);
+                }
+            }
+        }
+        if let Some(oe) = mc.output.as_ref().and_then(|out| out.oe.as_ref())
+            && self.global_source(oe, "GOE").is_none()
+        {
+            terms.push(oe);
+        }
+        terms
+    }
+
+    fn account_mc(&mut self, name: &str, crd: MacrocellCoord) {
+        let terms = self.mc_terms(name);
+        let fb = crd.block.to_idx();
+        self.fb_signals[fb].extend(terms.iter().flat_map(|term| term.signals()));
+        self.fb_terms[fb].extend(terms);
+    }
+
+    fn place(&mut self) -> Result<(), CompileError> {
+        let design = self.design;
+        for (name, inp) in &design.inputs {
+            if let Some(ref pin) = inp.pin {
+                let pad = self.resolve_pin(pin, false)?;
+                self.input_pads.insert(name, pad);
+            }
+        }
+        for (name, mc) in &design.macrocells {
+            if let Some(pin) = mc.output.as_ref().and_then(|out| out.pin.as_ref()) {
+                let IoCoord::Macrocell(crd) = self.resolve_pin(pin, true)? else {
+                    unreachable!()
+                };
+                self.mc_place.insert(name, crd);
+                self.mc_names.insert(crd, name);
+            }
+        }
+
+        let clocks = design.control_signals(ControlKind::Clock);
+        let srs = design.control_signals(ControlKind::SetReset);
+        let oes = design.control_signals(ControlKind::OutputEnable);
+        for (name, inp) in &design.inputs {
+            if inp.pin.is_some() {
+                continue;
+            }
+            let signal = Signal::Input(name.clone());
+            let prefix = if clocks.contains(&signal) {
+                Some("GCLK")
+            } else if srs.contains(&signal) {
+                Some("GSR")
+            } else if oes.contains(&signal) {
+                Some("GOE")
+            } else {
+                None
+            };
+            let free: Vec<IoCoord> = self
+                .pad_pins
+                .keys()
+                .copied()
+                .filter(|pad| {
+                    !self.used_pads.contains(pad)
+                        && !matches!(pad, IoCoord::Macrocell(crd) if self.mc_names.contains_key(crd))
+                })
+                .collect();
+            let pad = prefix
+                .and_then(|prefix| {
+                    free.iter()
+                        .copied()
+                        .find(|&pad| self.special_index(pad, prefix).is_some())
+                })
+                .or_else(|| free.iter().copied().find(|&pad| !self.is_special(pad)))
+                .or_else(|| free.first().copied())
+                .ok_or_else(|| CompileError::NoFreePin(name.clone()))?;
+            self.used_pads.insert(pad);
+            self.input_pads.insert(name, pad);
+        }
+
+        for (&crd, &name) in &self.mc_names.clone() {
+            self.account_mc(name, crd);
+        }
+
+        for with_output in [true, false] {
+            for (name, mc) in &design.macrocells {
+                if self.mc_place.contains_key(name.as_str()) || mc.output.is_some() != with_output {
+                    continue;
+                }
+                let terms = self.mc_terms(name);
+                let signals: BTreeSet<&Signal> =
+                    terms.iter().flat_map(|term| term.signals()).collect();
+                let mut candidates = vec![];
+                for fb in self.chip.blocks() {
+                    for mc in 0..MCS_PER_FB {
+                        let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                        if self.mc_names.contains_key(&crd) {
+                            continue;
+                        }
+                        let pad = IoCoord::Macrocell(crd);
+                        let has_pad =
+                            self.pad_pins.contains_key(&pad) && !self.used_pads.contains(&pad);
+                        if with_output && !has_pad {
+                            continue;
+                        }
+                        let cost = if with_output {
+                            usize::from(self.is_special(pad))
+                        } else {
+                            usize::from(has_pad)
+                        };
+                        candidates.push((cost, crd));
+                    }
+                }
+                candidates.sort();
+                let fits = |crd: MacrocellCoord| {
+                    let fb = crd.block.to_idx();
+                    self.fb_signals[fb].union(&signals).count() <= IMUX_PER_FB
+                        && self.fb_terms[fb]
+                            .union(&terms.iter().copied().collect())
+                            .count()
+                            <= PTS_PER_FB
+                };
+                let crd = candidates
+                    .iter()
+                    .map(|&(_, crd)| crd)
+                    .find(|&crd| fits(crd))
+                    .or_else(|| candidates.first().map(|&(_, crd)| crd))
+                    .ok_or_else(|| {
+                        if with_output {
+                            CompileError::NoFreePin(name.clone())
+                        } else {
+                            CompileError::NoFreeMacrocell(name.clone())
+                        }
+                    })?;
+                if with_output {
+                    self.used_pads.insert(IoCoord::Macrocell(crd));
+                }
+                self.mc_place.insert(name, crd);
+                self.mc_names.insert(crd, name);
+                self.account_mc(name, crd);
+            }
+        }
+        Ok(())
+    }
+
+    fn alloc_fsr(&mut self, term: &ProductTerm) -> bool {
+        let Some((_, inv)) = self.global_source(term, "GSR") else {
+            return false;
+        };
+        if self.fsr_inv.is_some_and(|cur| cur != inv) {
+            return false;
+        }
+        self.fsr_inv = Some(inv);
+        true
+    }
+
+    fn alloc_foe(&mut self, term: &ProductTerm) -> Option<usize> {
+        let (goe, inv) = self.global_source(term, "GOE")?;
+        if goe >= self.foe.len() || self.foe[goe].is_some_and(|cur| cur != inv) {
+            return None;
+        }
+        self.foe[goe] = Some(inv);
+        Some(goe)
+    }
+
+    fn plan_mc(&mut self, name: &str, mc: usize) -> Result<McPlan, CompileError> {
+        let lmc = &self.design.macrocells[name];
+        let mut plan = McPlan {
+            sum: lmc.sum.clone(),
+            ..Default::default()
+        };
+        let items = &mut plan.items;
+        items.push(
+            if lmc.invert {
+                "XOR_MUX=VCC"
+            } else {
+                "XOR_MUX=GND"
+            }
+            .into(),
+        );
+        items.push("REG_D_MUX=XOR".into());
+        items.push("!CLK_DDR".into());
+        match lmc.reg {
+            None => {
+                items.push("MC_ZIA_MUX=XOR".into());
+                items.push("MC_IOB_MUX=XOR".into());
+            }
+            Some(ref reg) => {
+                items.push("MC_ZIA_MUX=REG".into());
+                items.push("MC_IOB_MUX=REG".into());
+                items.push(if reg.init { "REG_INIT" } else { "!REG_INIT" }.into());
+                let mode = match (reg.kind, reg.ce.is_some()) {
+                    (RegisterKind::Dff, false) => "DFF",
+                    (RegisterKind::Dff, true) => "DFFCE",
+                    (RegisterKind::Tff, false) => "TFF",
+                    (RegisterKind::Latch, false) => "LATCH",
+                    (_, true) => {
+                        return Err(CompileError::Unsupported {
+                            signal: name.to_string(),
+                            reason: "clock enable is only supported on D flip-flops on CoolRunner-II",
+                        });
+                    }
+                };
+                items.push(format!("REG_MODE={mode}"));
+
+                // the clock enable can only come from PTC, which the clock then cannot use
+                if let Some((gclk, inv)) = self.global_source(&reg.clk, "GCLK")
+                    && gclk < self.fclk.len()
+                {
+                    self.fclk[gclk] = true;
+                    items.push(format!("CLK_MUX=FCLK{gclk}"));
+                    items.push(
+                        if inv ^ reg.clk_inv {
+                            "CLK_INV"
+                        } else {
+                            "!CLK_INV"
+                        }
+                        .into(),
+                    );
+                } else {
+                    items.push(if reg.clk_inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                    if reg.ce.is_some() {
+                        items.push("CLK_MUX=CT4".into());
+                        plan.pts.push((4, reg.clk.clone()));
+                    } else {
+                        items.push("CLK_MUX=PT".into());
+                        plan.pts.push((ptc(mc), reg.clk.clone()));
+                    }
+                }
+                if let Some(ref ce) = reg.ce {
+                    plan.pts.push((ptc(mc), ce.clone()));
+                }
+
+                let mut pta_used = false;
+                for (term, mux, ct) in [(&reg.rst, "RST_MUX", 5), (&reg.set, "SET_MUX", 6)] {
+                    match term {
+                        None => items.push(format!("{mux}=GND")),
+                        Some(term) if self.alloc_fsr(term) => items.push(format!("{mux}=FSR")),
+                        Some(term) if !pta_used => {
+                            pta_used = true;
+                            items.push(format!("{mux}=PT"));
+                            plan.pts.push((pta(mc), term.clone()));
+                        }
+                        Some(term) => {
+                            items.push(format!("{mux}=CT{ct}"));
+                            plan.pts.push((ct, term.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(ref out) = lmc.output {
+            items.push(
+                match out.slew {
+                    Slew::Slow => "IOB_SLEW=SLOW",
+                    Slew::Fast => "IOB_SLEW=FAST",
+                }
+                .into(),
+            );
+            match out.oe {
+                None => items.push("OE_MUX=VCC".into()),
+                Some(ref oe) => {
+                    if let Some(foe) = self.alloc_foe(oe) {
+                        items.push(format!("OE_MUX=FOE{foe}"));
+                    } else {
+                        items.push("OE_MUX=PT".into());
+                        plan.pts.push((ptb(mc), oe.clone()));
+                    }
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    fn emit_fb(
+        &mut self,
+        fb: usize,
+        plans: BTreeMap<usize, McPlan>,
+        names: &BTreeMap<usize, &str>,
+    ) -> Result<(), CompileError> {
+        let fbid = BlockId::from_idx(fb);
+        let mut pts: [Option<ProductTerm>; PTS_PER_FB] = core::array::from_fn(|_| None);
+        let mut sums = BTreeMap::new();
+        for (mc, plan) in plans {
+            for (pt, term) in plan.pts {
+                if pts[pt].as_ref().is_some_and(|cur| *cur != term) {
+                    return Err(CompileError::ControlTermsExhausted(names[&mc].to_string()));
+                }
+                pts[pt] = Some(term);
+            }
+            let crd = MacrocellCoord::simple(fbid, MacrocellId::from_idx(mc));
+            for item in &plan.items {
+                self.set_mc(crd, item);
+            }
+            sums.insert(mc, plan.sum);
+        }
+
+        // sum terms can use any product term not taken by a control function, and are
+        // shared between macrocells
+        let mut sum_pts: HashMap<ProductTerm, usize> = HashMap::new();
+        for (mc, sum) in sums {
+            for term in sum {
+                let pt = match sum_pts.get(&term) {
+                    Some(&pt) => pt,
+                    None => {
+                        let Some(pt) = (0..PTS_PER_FB).find(|&pt| pts[pt].is_none()) else {
+                            return Err(CompileError::ProductTermsExhausted(
+                                names[&mc].to_string(),
+                            ));
+                        };
+                        pts[pt] = Some(term.clone());
+                        sum_pts.insert(term, pt);
+                        pt
+                    }
+                };
+                self.bs.fbs[fb].pla_or[mc].set(pt, true);
+            }
+        }
+
+        let signals: Vec<&Signal> = pts
+            .iter()
+            .flatten()
+            .flat_map(|term| term.signals())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut candidates = vec![];
+        let mut sources = vec![];
+        for signal in &signals {
+            let src = match signal {
+                Signal::Macrocell(name) => format!("MC_{}", self.mc_place[name.as_str()]),
+                _ => self.pad_of(signal).unwrap().to_string(),
+            };
+            let mut cands = vec![];
+            for im in 0..IMUX_PER_FB {
+                if let TileItemKind::Enum { ref values } =
+                    self.chip.imux_bits.items[&format!("IM[{im}].MUX")].kind
+                    && values.contains_key(&src)
+                {
+                    cands.push(im);
+                }
+            }
+            candidates.push(cands);
+            sources.push(src);
+        }
+        let Some(assignment) = assign_slots(&candidates, IMUX_PER_FB) else {
+            return Err(CompileError::RoutingFailed(format!("FB {fb}")));
+        };
+        let mut signal_im = HashMap::new();
+        for (i, &im) in assignment.iter().enumerate() {
+            self.set_fb(fb, &format!("IM[{im}].MUX={src}", src = sources[i]));
+            signal_im.insert(signals[i], im);
+        }
+        for (pt, term) in pts.iter().enumerate() {
+            let Some(term) = term else { continue };
+            let ptd = &mut self.bs.fbs[fb].pla_and[pt];
+            for lit in &term.literals {
+                let im = signal_im[&lit.signal];
+                if lit.inv {
+                    ptd.im_f.set(im, true);
+                } else {
+                    ptd.im_t.set(im, true);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self) -> Result<(), CompileError> {
+        let nfb = self.chip.blocks().len();
+        let mut plans: Vec<BTreeMap<usize, McPlan>> = (0..nfb).map(|_| BTreeMap::new()).collect();
+        let mut names: Vec<BTreeMap<usize, &str>> = vec![BTreeMap::new(); nfb];
+        for (crd, name) in self.mc_names.clone() {
+            let mc = crd.macrocell.to_idx();
+            let plan = self.plan_mc(name, mc)?;
+            plans[crd.block.to_idx()].insert(mc, plan);
+            names[crd.block.to_idx()].insert(mc, name);
+        }
+
+        // all used pads feed the ZIA; input-only pads also need their output disabled
+        let mut ibuf_mcs = BTreeSet::new();
+        for pad in self.input_pads.clone().into_values() {
+            if let IoCoord::Macrocell(crd) = pad {
+                ibuf_mcs.insert(crd);
+                self.set_mc(crd, "OE_MUX=GND");
+            }
+        }
+        for (&name, &crd) in &self.mc_place {
+            if self.design.macrocells[name].output.is_some() {
+                ibuf_mcs.insert(crd);
+            }
+        }
+        for crd in ibuf_mcs {
+            self.set_mc(crd, "IOB_ZIA_MUX=IBUF");
+        }
+
+        for (fb, plans) in plans.into_iter().enumerate() {
+            if !plans.is_empty() {
+                self.emit_fb(fb, plans, &names[fb])?;
+            }
+        }
+
+        for i in 0..self.fclk.len() {
+            if self.fclk[i] {
+                self.set_global(&format!("FCLK{i}_ENABLE"));
+            }
+        }
+        for i in 0..self.foe.len() {
+            if let Some(inv) = self.foe[i] {
+                self.set_global(&format!(
+                    "FOE{i}_MUX={}",
+                    if inv { "IBUF_INV" } else { "IBUF" }
+                ));
+            }
+        }
+        if let Some(inv) = self.fsr_inv {
+            self.set_global("FSR_ENABLE");
+            self.set_global(if inv { "FSR_INV" } else { "!FSR_INV" });
+        }
+        self.set_global("DONE");
+        Ok(())
+    }
+}
+
+/// Compiles a design for the given device and package.  Returns the JED file and the
+/// chosen placement.
+pub fn compile(
+    db: &Database,
+    device: &str,
+    package: &str,
+    design: &Design,
+) -> Result<(JedFile, Placement), CompileError> {
+    design.check()?;
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(CompileError::UnknownDevice(device.to_string()));
+    };
+    let Some(&bond) = dev.bonds.get(package) else {
+        return Err(CompileError::UnknownPackage(package.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[bond];
+    let mut pad_pins = BTreeMap::new();
+    for (pin, &pad) in &bond.pins {
+        let pad = match pad {
+            BondPad::Iob(crd) => IoCoord::Macrocell(crd),
+            BondPad::Ipad(ipad) => IoCoord::Ipad(ipad),
+            _ => continue,
+        };
+        pad_pins.entry(pad).or_insert(pin.as_str());
+    }
+    let nfb = chip.blocks().len();
+    let mut compiler = Compiler {
+        chip,
+        design,
+        pins: &bond.pins,
+        pad_pins,
+        used_pads: BTreeSet::new(),
+        input_pads: BTreeMap::new(),
+        mc_place: BTreeMap::new(),
+        mc_names: BTreeMap::new(),
+        fb_signals: vec![BTreeSet::new(); nfb],
+        fb_terms: vec![BTreeSet::new(); nfb],
+        fclk: [false; 3],
+        foe: [None; 4],
+        fsr_inv: None,
+        bs: Bitstream::new(chip),
+    };
+    compiler.place()?;
+    compiler.emit()?;
+
+    let mut placement = Placement::default();
+    for (&name, &pad) in &compiler.input_pads {
+        placement
+            .pins
+            .insert(name.to_string(), compiler.pad_pins[&pad].to_string());
+    }
+    for (&name, &crd) in &compiler.mc_place {
+        placement.macrocells.insert(name.to_string(), crd);
+        if design.macrocells[name].output.is_some() {
+            placement.pins.insert(
+                name.to_string(),
+                compiler.pad_pins[&IoCoord::Macrocell(crd)].to_string(),
+            );
+        }
+    }
+    Ok((compiler.bs.to_jed(chip, db, device), placement))
+}
@@ -3,16 +3,24 @@ use std::{collections::BTreeMap, error::Error, fs::File, path::Path};
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_entity::{
-    EntityRange, EntityVec,
+    EntityId, EntityRange, EntityVec,
     id::{EntityIdU8, EntityTag},
 };
 use prjcombine_types::{
     bsdata::Tile,
-    cpld::{BlockId, IoCoord, IpadId, MacrocellCoord},
-    db::{BondId, ChipId, DumpFlags, SpeedId},
+    cpld::{BlockId, IoCoord, IpadId, IpadTag, MacrocellCoord, jed_bits_diff_node},
+    db::{BondId, BondTag, ChipId, ChipTag, DumpFlags, SpeedId, SpeedTag},
+    diff::{DbDiff, DiffNode},
     speed::Speed,
+    text::{TextError, TextParser, expect_next_id, parse_hex, parse_id, parse_num, split_assign},
 };
 
+pub mod bitstream;
+pub mod compile;
+pub mod sim;
+pub mod svf;
+pub mod timing;
+
 pub struct BankTag;
 impl EntityTag for BankTag {
     const PREFIX: &'static str = "BANK";
@@ -20,9 +28,11 @@ impl EntityTag for BankTag {
 pub type BankId = EntityIdU8<BankTag>;
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub idcode_part: u32,
     pub ipads: usize,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub io: BTreeMap<IoCoord, Io>,
     pub banks: usize,
     pub has_vref: bool,
@@ -107,9 +117,146 @@ impl Chip {
 
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("idcode_part", format!("0x{:04x}", self.idcode_part));
+        res.insert_leaf("ipads", self.ipads);
+        for (k, v) in &self.io {
+            res.insert_leaf(
+                format!("io {k}"),
+                format!("bank {bank} pad {pad}", bank = v.bank, pad = v.pad_distance),
+            );
+        }
+        res.insert_leaf("banks", self.banks);
+        res.insert_leaf("has_vref", self.has_vref);
+        res.insert_leaf("bs_layout", self.bs_layout);
+        res.insert_leaf("bs_cols", self.bs_cols);
+        res.insert_leaf("imux_width", self.imux_width);
+        res.insert_leaf(
+            "xfer_cols",
+            self.xfer_cols.iter().map(|x| x.to_string()).join(", "),
+        );
+        res.insert_leaf("mc_width", self.mc_width);
+        res.insert_leaf("block_rows", self.block_rows);
+        res.insert_leaf(
+            "block_cols",
+            self.block_cols.iter().map(|x| x.to_string()).join(", "),
+        );
+        for (k, v) in &self.io_special {
+            res.insert_leaf(format!("io_special {k}"), v);
+        }
+        res.insert("bstile MC_BITS", self.mc_bits.diff_node());
+        res.insert("bstile GLOBAL_BITS", self.global_bits.diff_node());
+        res.insert(
+            "jedtile GLOBAL_BITS",
+            jed_bits_diff_node(&self.jed_global_bits),
+        );
+        res.insert("bstile IMUX_BITS", self.imux_bits.diff_node());
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut idcode_part = None;
+        let mut ipads = None;
+        let mut io = BTreeMap::new();
+        let mut banks = None;
+        let mut has_vref = false;
+        let mut bs_layout = None;
+        let mut bs_cols = None;
+        let mut imux_width = None;
+        let mut xfer_cols = vec![];
+        let mut mc_width = None;
+        let mut block_rows = None;
+        let mut block_cols = None;
+        let mut io_special = BTreeMap::new();
+        let mut mc_bits = None;
+        let mut global_bits = None;
+        let mut jed_global_bits = None;
+        let mut imux_bits = None;
+        let parse_list = |val: &str| -> Result<Vec<usize>, String> {
+            val.split(", ")
+                .filter(|x| !x.is_empty())
+                .map(parse_num)
+                .collect()
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("bstile", "MC_BITS")) => mc_bits = Some(Tile::parse(p)?),
+                Some(("bstile", "GLOBAL_BITS")) => global_bits = Some(Tile::parse(p)?),
+                Some(("jedtile", "GLOBAL_BITS")) => jed_global_bits = Some(p.parse_jed_bits()?),
+                Some(("bstile", "IMUX_BITS")) => imux_bits = Some(Tile::parse(p)?),
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "idcode_part" => idcode_part = Some(line.check(parse_hex(val))?),
+                        "ipads" => ipads = Some(line.check(parse_num(val))?),
+                        "io" => {
+                            let (crd, val) = line.check(split_assign(val))?;
+                            let Some((bank, pad)) = val
+                                .strip_prefix("bank ")
+                                .and_then(|v| v.split_once(" pad "))
+                            else {
+                                return Err(line.error("invalid io"));
+                            };
+                            io.insert(
+                                line.check(crd.parse())?,
+                                Io {
+                                    bank: line.check(parse_id(bank, BankTag::PREFIX))?,
+                                    pad_distance: line.check(parse_num(pad))?,
+                                },
+                            );
+                        }
+                        "banks" => banks = Some(line.check(parse_num(val))?),
+                        "has_vref" => has_vref = true,
+                        "bs_layout" => {
+                            bs_layout = Some(match val {
+                                "narrow" => BsLayout::Narrow,
+                                "wide" => BsLayout::Wide,
+                                _ => return Err(line.error(format!("unknown layout {val}"))),
+                            })
+                        }
+                        "bs_cols" => bs_cols = Some(line.check(parse_num(val))?),
+                        "imux_width" => imux_width = Some(line.check(parse_num(val))?),
+                        "xfer_cols" => xfer_cols = line.check(parse_list(val))?,
+                        "mc_width" => mc_width = Some(line.check(parse_num(val))?),
+                        "block_rows" => block_rows = Some(line.check(parse_num(val))?),
+                        "block_cols" => block_cols = Some(line.check(parse_list(val))?),
+                        "io_special" => {
+                            let (name, mc) = line.check(split_assign(val))?;
+                            io_special.insert(name.to_string(), line.check(mc.parse())?);
+                        }
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        Ok(Chip {
+            idcode_part: idcode_part.ok_or_else(|| p.missing("idcode_part"))?,
+            ipads: ipads.ok_or_else(|| p.missing("ipads"))?,
+            io,
+            banks: banks.ok_or_else(|| p.missing("banks"))?,
+            has_vref,
+            bs_layout: bs_layout.ok_or_else(|| p.missing("bs_layout"))?,
+            bs_cols: bs_cols.ok_or_else(|| p.missing("bs_cols"))?,
+            imux_width: imux_width.ok_or_else(|| p.missing("imux_width"))?,
+            xfer_cols,
+            mc_width: mc_width.ok_or_else(|| p.missing("mc_width"))?,
+            block_rows: block_rows.ok_or_else(|| p.missing("block_rows"))?,
+            block_cols: block_cols.ok_or_else(|| p.missing("block_cols"))?,
+            io_special,
+            mc_bits: mc_bits.ok_or_else(|| p.missing("MC_BITS"))?,
+            global_bits: global_bits.ok_or_else(|| p.missing("GLOBAL_BITS"))?,
+            jed_global_bits: jed_global_bits.ok_or_else(|| p.missing("GLOBAL_BITS jedtile"))?,
+            imux_bits: imux_bits.ok_or_else(|| p.missing("IMUX_BITS"))?,
+        })
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BsLayout {
     Narrow,
     Wide,
@@ -125,12 +272,14 @@ impl std::fmt::Display for BsLayout {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Io {
     pub bank: BankId,
     pub pad_distance: u32,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Nc,
     Gnd,
@@ -163,7 +312,36 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "TCK" => BondPad::Tck,
+            "TMS" => BondPad::Tms,
+            "TDI" => BondPad::Tdi,
+            "TDO" => BondPad::Tdo,
+            _ => {
+                if let Some(mc) = s.strip_prefix("IOB_") {
+                    BondPad::Iob(mc.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCIO") {
+                    BondPad::VccIo(BankId::from_idx(parse_num(bank)?))
+                } else if s.starts_with(IpadTag::PREFIX) {
+                    BondPad::Ipad(parse_id(s, IpadTag::PREFIX)?)
+                } else {
+                    return Err(format!("unknown bond pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub idcode_part: u32,
     pub pins: BTreeMap<String, BondPad>,
@@ -182,9 +360,76 @@ impl Bond {
         }
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("idcode_part", format!("0x{:04x}", self.idcode_part));
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut idcode_part = None;
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "idcode_part" => idcode_part = Some(line.check(parse_hex(val))?),
+                "pin" => {
+                    let (name, pad) = line.check(split_assign(val))?;
+                    pins.insert(name.to_string(), line.check(pad.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(Bond {
+            idcode_part: idcode_part.ok_or_else(|| p.missing("idcode_part"))?,
+            pins,
+        })
+    }
+}
+
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut bonds = BTreeMap::new();
+        let mut speeds = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "bond" => {
+                    let (pkg, bond) = line.check(split_assign(val))?;
+                    bonds.insert(
+                        pkg.to_string(),
+                        line.check(parse_id(bond, BondTag::PREFIX))?,
+                    );
+                }
+                "speed" => {
+                    let (sname, speed) = line.check(split_assign(val))?;
+                    speeds.insert(
+                        sname.to_string(),
+                        line.check(parse_id(speed, SpeedTag::PREFIX))?,
+                    );
+                }
+                _ => return Err(line.error(format!("unknown device statement {kw}"))),
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds,
+            speeds,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -193,6 +438,7 @@ pub struct Device {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -220,6 +466,98 @@ impl Database {
         Ok(bincode::decode_from_std_read(&mut cf, config)?)
     }
 
+    /// Reads back a database from the full text dump written by [`Database::dump`].
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut speeds = EntityVec::new();
+        let mut devices = vec![];
+        let mut jed_mc_bits_small = None;
+        let mut jed_mc_bits_large_iob = None;
+        let mut jed_mc_bits_large_buried = None;
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match (kw, name) {
+                ("chip", _) => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                ("bond", _) => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                ("speed", _) => {
+                    line.check(expect_next_id(name, speeds.next_id()))?;
+                    speeds.push(Speed::parse(&mut p)?);
+                }
+                ("device", _) => devices.push(Device::parse(&mut p, name)?),
+                ("jedtile", "MC_BITS_SMALL") => jed_mc_bits_small = Some(p.parse_jed_bits()?),
+                ("jedtile", "MC_BITS_LARGE_IOB") => {
+                    jed_mc_bits_large_iob = Some(p.parse_jed_bits()?)
+                }
+                ("jedtile", "MC_BITS_LARGE_BURIED") => {
+                    jed_mc_bits_large_buried = Some(p.parse_jed_bits()?)
+                }
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            speeds,
+            devices,
+            jed_mc_bits_small: jed_mc_bits_small
+                .ok_or_else(|| p.missing("MC_BITS_SMALL jedtile"))?,
+            jed_mc_bits_large_iob: jed_mc_bits_large_iob
+                .ok_or_else(|| p.missing("MC_BITS_LARGE_IOB jedtile"))?,
+            jed_mc_bits_large_buried: jed_mc_bits_large_buried
+                .ok_or_else(|| p.missing("MC_BITS_LARGE_BURIED jedtile"))?,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips, bonds and speeds are compared through
+    /// the devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node());
+            for (pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for (sname, &speed) in &dev.speeds {
+                node.insert(format!("speed {sname}"), self.speeds[speed].diff_node());
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert(
+            "jedtile MC_BITS_SMALL",
+            jed_bits_diff_node(&self.jed_mc_bits_small),
+        );
+        res.insert(
+            "jedtile MC_BITS_LARGE_IOB",
+            jed_bits_diff_node(&self.jed_mc_bits_large_iob),
+        );
+        res.insert(
+            "jedtile MC_BITS_LARGE_BURIED",
+            jed_bits_diff_node(&self.jed_mc_bits_large_buried),
+        );
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
@@ -301,8 +639,8 @@ impl Database {
                 for (pkg, bond) in &dev.bonds {
                     writeln!(o, "\tbond {pkg} = {bond};")?;
                 }
-                for speed in dev.speeds.values() {
-                    writeln!(o, "\tspeed {speed};")?;
+                for (sname, speed) in &dev.speeds {
+                    writeln!(o, "\tspeed {sname} = {speed};")?;
                 }
                 writeln!(o, "}}")?;
                 writeln!(o)?;
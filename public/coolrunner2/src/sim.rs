@@ -0,0 +1,624 @@
+//! Functional simulation of a configured device.
+//!
+//! The simulator evaluates the configuration as decoded from the bitstream.  It is
+//! cycle-based: all combinatorial logic is re-evaluated until it is stable, and registers
+//! are updated when their clock changes during that process.  Timing is not modelled, so
+//! inputs that change together with a clock are assumed to meet setup time.  Pins that are
+//! not driven by either side keep their last value, like with a bus keeper, unless the
+//! termination is enabled in pull-up mode.
+//!
+//! The clock divider is modelled without its optional delay, and the data gate latches
+//! are transparent while the `DGE` pad is high.
+
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::TestTarget;
+use prjcombine_types::cpld::{BlockId, IoCoord, MacrocellCoord, MacrocellId};
+
+use crate::{
+    Bond, BondPad, Chip,
+    bitstream::{Bitstream, get_tile_bitvec, get_tile_enum},
+};
+
+pub(crate) const MCS_PER_FB: usize = 16;
+const PTS_PER_FB: usize = 56;
+const IMUX_PER_FB: usize = 40;
+const MAX_SETTLE_STEPS: usize = 1000;
+
+pub(crate) fn pta(mc: usize) -> usize {
+    8 + 3 * mc
+}
+
+pub(crate) fn ptb(mc: usize) -> usize {
+    9 + 3 * mc
+}
+
+pub(crate) fn ptc(mc: usize) -> usize {
+    10 + 3 * mc
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImuxSource {
+    Const(bool),
+    Mc(MacrocellCoord),
+    Iob(IoCoord),
+}
+
+/// A control input of a macrocell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CtlSource {
+    Const(bool),
+    Pt(usize),
+    Fclk(usize),
+    Foe(usize),
+    Fsr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegMode {
+    Dff,
+    Tff,
+    Latch,
+    Dffce,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ZiaMux {
+    None,
+    Reg,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OeMode {
+    Normal,
+    IsGnd,
+    OpenDrain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FoeSource {
+    None,
+    Pad(IoCoord, bool),
+    Mc(MacrocellCoord),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct McConfig {
+    pub(crate) xor: CtlSource,
+    pub(crate) xor_inv: bool,
+    pub(crate) reg_d_ibuf: bool,
+    pub(crate) mode: RegMode,
+    pub(crate) init: bool,
+    pub(crate) clk: CtlSource,
+    pub(crate) clk_inv: bool,
+    pub(crate) clk_ddr: bool,
+    pub(crate) rst: CtlSource,
+    pub(crate) set: CtlSource,
+    pub(crate) oe: CtlSource,
+    pub(crate) oe_mode: OeMode,
+    pub(crate) mc_zia: ZiaMux,
+    pub(crate) iob_zia: ZiaMux,
+    pub(crate) mc_iob_reg: bool,
+    pub(crate) pullup: bool,
+    pub(crate) dge: bool,
+    pub(crate) ibuf_schmitt: bool,
+    pub(crate) slew_fast: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FbConfig {
+    pub(crate) imux: Vec<ImuxSource>,
+    // the (FB input, true polarity) pairs included in each product term
+    pub(crate) pts: Vec<Vec<(usize, bool)>>,
+    pub(crate) sums: Vec<Vec<usize>>,
+    pub(crate) mcs: Vec<McConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct McState {
+    reg: bool,
+    clk: bool,
+    xor: bool,
+    mc_zia: bool,
+    mc_iob: bool,
+    oe: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ClkDivState {
+    clk: bool,
+    count: u8,
+    out: bool,
+}
+
+/// The configuration of a device, as decoded from the bitstream.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) pins: BTreeMap<String, IoCoord>,
+    pub(crate) fclk: Vec<Option<IoCoord>>,
+    pub(crate) foe: Vec<FoeSource>,
+    pub(crate) fsr: Option<(IoCoord, bool)>,
+    pub(crate) clkdiv: Option<u8>,
+    pub(crate) cdr: Option<IoCoord>,
+    pub(crate) dge: Option<IoCoord>,
+    pub(crate) done: bool,
+    pub(crate) ipad_schmitt: bool,
+    pub(crate) fbs: Vec<FbConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    cfg: Config,
+    ext: BTreeMap<IoCoord, Option<bool>>,
+    ibuf: BTreeMap<IoCoord, bool>,
+    clkdiv_state: ClkDivState,
+    mcs: Vec<[McState; MCS_PER_FB]>,
+}
+
+fn parse_ctl(val: Option<&str>, pt: usize) -> CtlSource {
+    let Some(val) = val else {
+        return CtlSource::Const(false);
+    };
+    if val == "PT" || val == "PT_INV" {
+        CtlSource::Pt(pt)
+    } else if val == "VCC" {
+        CtlSource::Const(true)
+    } else if val == "FSR" {
+        CtlSource::Fsr
+    } else if let Some(idx) = val.strip_prefix("CT") {
+        CtlSource::Pt(idx.parse().unwrap())
+    } else if let Some(idx) = val.strip_prefix("FCLK") {
+        CtlSource::Fclk(idx.parse().unwrap())
+    } else if let Some(idx) = val.strip_prefix("FOE") {
+        CtlSource::Foe(idx.parse().unwrap())
+    } else {
+        CtlSource::Const(false)
+    }
+}
+
+fn parse_zia_mux(val: Option<&str>) -> ZiaMux {
+    match val {
+        Some("REG") => ZiaMux::Reg,
+        Some("NONE") | None => ZiaMux::None,
+        _ => ZiaMux::Other,
+    }
+}
+
+impl Config {
+    pub(crate) fn new(chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let mut io_names = BTreeMap::new();
+        for &io in chip.io.keys() {
+            io_names.insert(io.to_string(), io);
+        }
+        let mut mc_names = BTreeMap::new();
+        for fb in chip.blocks() {
+            for mc in 0..MCS_PER_FB {
+                let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                mc_names.insert(crd.to_string(), crd);
+            }
+        }
+        let special = |name: &str| {
+            chip.io_special
+                .get(name)
+                .map(|&crd| IoCoord::Macrocell(crd))
+        };
+        let get_global = |name: &str| get_tile_enum(&bs.globals, &chip.global_bits, name);
+        let get_global_bool = |name: &str| {
+            get_tile_bitvec(&bs.globals, &chip.global_bits, name).is_some_and(|val| val[0])
+        };
+
+        let fclk = (0..3)
+            .map(|i| {
+                if get_global_bool(&format!("FCLK{i}_ENABLE")) {
+                    special(&format!("GCLK{i}"))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let foe = (0..4)
+            .map(|i| {
+                let Some(pad) = chip.io_special.get(&format!("GOE{i}")) else {
+                    return FoeSource::None;
+                };
+                match get_global(&format!("FOE{i}_MUX")) {
+                    Some("IBUF") => FoeSource::Pad(IoCoord::Macrocell(*pad), false),
+                    Some("IBUF_INV") => FoeSource::Pad(IoCoord::Macrocell(*pad), true),
+                    Some("MC") => FoeSource::Mc(*pad),
+                    _ => FoeSource::None,
+                }
+            })
+            .collect();
+        let fsr = if get_global_bool("FSR_ENABLE") {
+            special("GSR").map(|pad| (pad, get_global_bool("FSR_INV")))
+        } else {
+            None
+        };
+        let clkdiv = if get_global_bool("CLKDIV_ENABLE") {
+            get_global("CLKDIV_DIV").map(|val| val.parse().unwrap())
+        } else {
+            None
+        };
+        let dge = if get_global_bool("DGE_ENABLE") {
+            special("DGE")
+        } else {
+            None
+        };
+        // DONE is set by the programming sequence rather than stored in the JED file
+        let done = get_tile_bitvec(&bs.globals, &chip.global_bits, "DONE").is_none_or(|val| val[0]);
+        let term_pullup = get_global("TERM_MODE") == Some("PULLUP");
+
+        let mut fbs = vec![];
+        for fbd in &bs.fbs {
+            let mut imux = vec![];
+            for im in 0..IMUX_PER_FB {
+                let val = get_tile_enum(&fbd.imux, &chip.imux_bits, &format!("IM[{im}].MUX"));
+                imux.push(match val {
+                    Some("VCC") => ImuxSource::Const(true),
+                    Some(val) => {
+                        if let Some(crd) = val.strip_prefix("MC_") {
+                            ImuxSource::Mc(mc_names[crd])
+                        } else if let Some(&io) = io_names.get(val) {
+                            ImuxSource::Iob(io)
+                        } else {
+                            ImuxSource::Const(false)
+                        }
+                    }
+                    None => ImuxSource::Const(false),
+                });
+            }
+            let pts = fbd
+                .pla_and
+                .iter()
+                .map(|ptd| {
+                    let mut inputs = vec![];
+                    for im in 0..IMUX_PER_FB {
+                        if ptd.im_t[im] {
+                            inputs.push((im, true));
+                        }
+                        if ptd.im_f[im] {
+                            inputs.push((im, false));
+                        }
+                    }
+                    inputs
+                })
+                .collect();
+            let sums = fbd
+                .pla_or
+                .iter()
+                .map(|st| (0..PTS_PER_FB).filter(|&pt| st[pt]).collect())
+                .collect();
+            let mut mcs = vec![];
+            for (mc, mcd) in fbd.mcs.iter().enumerate() {
+                let get = |name: &str| get_tile_enum(mcd, &chip.mc_bits, name);
+                let get_bool = |name: &str| {
+                    get_tile_bitvec(mcd, &chip.mc_bits, name).is_some_and(|val| val[0])
+                };
+                let oe_mux = get("OE_MUX");
+                mcs.push(McConfig {
+                    xor: parse_ctl(get("XOR_MUX"), ptc(mc)),
+                    xor_inv: get("XOR_MUX") == Some("PT_INV"),
+                    reg_d_ibuf: get("REG_D_MUX") == Some("IBUF"),
+                    mode: match get("REG_MODE") {
+                        Some("TFF") => RegMode::Tff,
+                        Some("LATCH") => RegMode::Latch,
+                        Some("DFFCE") => RegMode::Dffce,
+                        _ => RegMode::Dff,
+                    },
+                    init: get_bool("REG_INIT"),
+                    clk: parse_ctl(get("CLK_MUX"), ptc(mc)),
+                    clk_inv: get_bool("CLK_INV"),
+                    clk_ddr: get_bool("CLK_DDR"),
+                    rst: parse_ctl(get("RST_MUX"), pta(mc)),
+                    set: parse_ctl(get("SET_MUX"), pta(mc)),
+                    oe: parse_ctl(oe_mux, ptb(mc)),
+                    oe_mode: match oe_mux {
+                        Some("IS_GND") => OeMode::IsGnd,
+                        Some("OPEN_DRAIN") => OeMode::OpenDrain,
+                        _ => OeMode::Normal,
+                    },
+                    mc_zia: parse_zia_mux(get("MC_ZIA_MUX")),
+                    iob_zia: parse_zia_mux(get("IOB_ZIA_MUX")),
+                    mc_iob_reg: get("MC_IOB_MUX") == Some("REG"),
+                    pullup: term_pullup && get_bool("IOB_TERM_ENABLE"),
+                    dge: get_bool("DGE_ENABLE"),
+                    ibuf_schmitt: get("IBUF_MODE") == Some("SCHMITT"),
+                    slew_fast: get("IOB_SLEW") == Some("FAST"),
+                });
+            }
+            fbs.push(FbConfig {
+                imux,
+                pts,
+                sums,
+                mcs,
+            });
+        }
+
+        let mut pins = BTreeMap::new();
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Iob(crd) => {
+                    pins.insert(pin.clone(), IoCoord::Macrocell(crd));
+                }
+                BondPad::Ipad(ipad) => {
+                    pins.insert(pin.clone(), IoCoord::Ipad(ipad));
+                }
+                _ => (),
+            }
+        }
+        Config {
+            pins,
+            fclk,
+            foe,
+            fsr,
+            clkdiv,
+            cdr: special("CDR"),
+            dge,
+            done,
+            ipad_schmitt: get_global("IPAD0_IBUF_MODE") == Some("SCHMITT"),
+            fbs,
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new(chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let cfg = Config::new(chip, bond, bs);
+        let mcs = cfg
+            .fbs
+            .iter()
+            .map(|fbc| {
+                core::array::from_fn(|mc| McState {
+                    reg: fbc.mcs[mc].init,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut res = Simulator {
+            cfg,
+            ext: chip.io.keys().map(|&io| (io, None)).collect(),
+            ibuf: chip.io.keys().map(|&io| (io, false)).collect(),
+            clkdiv_state: ClkDivState::default(),
+            mcs,
+        };
+        res.settle_inner(false);
+        res
+    }
+
+    /// Drives a package pin from the outside, or leaves it undriven if `None`.
+    pub fn set_pin(&mut self, pin: &str, val: Option<bool>) {
+        if let Some(&io) = self.cfg.pins.get(pin) {
+            self.ext.insert(io, val);
+        }
+    }
+
+    /// Returns the value driven by the device on a package pin, or `None` if the output
+    /// buffer is disabled.
+    pub fn get_pin(&self, pin: &str) -> Option<bool> {
+        self.cfg.pins.get(pin).and_then(|&io| self.pad_output(io))
+    }
+
+    /// Returns the AIM output of a macrocell.
+    pub fn get_mc(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].mc_zia
+    }
+
+    /// Returns the state of a macrocell's register.
+    pub fn get_reg(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].reg
+    }
+
+    /// Re-evaluates the device until it is stable.
+    pub fn settle(&mut self) {
+        self.settle_inner(true);
+    }
+
+    fn settle_inner(&mut self, clocked: bool) {
+        for _ in 0..MAX_SETTLE_STEPS {
+            if !self.step(clocked) {
+                break;
+            }
+        }
+    }
+
+    fn mc_state(&self, crd: MacrocellCoord) -> &McState {
+        &self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()]
+    }
+
+    fn mc_config(&self, crd: MacrocellCoord) -> &McConfig {
+        &self.cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()]
+    }
+
+    fn pad_output(&self, io: IoCoord) -> Option<bool> {
+        let IoCoord::Macrocell(crd) = io else {
+            return None;
+        };
+        if !self.cfg.done {
+            return None;
+        }
+        let state = self.mc_state(crd);
+        match self.mc_config(crd).oe_mode {
+            OeMode::IsGnd => Some(false),
+            OeMode::OpenDrain => (!state.mc_iob).then_some(false),
+            OeMode::Normal => state.oe.then_some(state.mc_iob),
+        }
+    }
+
+    fn pad_input(&self, pad: Option<IoCoord>) -> bool {
+        pad.is_some_and(|io| self.ibuf[&io])
+    }
+
+    fn iob_zia(&self, io: IoCoord) -> bool {
+        match io {
+            IoCoord::Ipad(_) => self.ibuf[&io],
+            IoCoord::Macrocell(crd) => match self.mc_config(crd).iob_zia {
+                ZiaMux::None => false,
+                ZiaMux::Reg => self.mc_state(crd).reg,
+                ZiaMux::Other => self.ibuf[&io],
+            },
+        }
+    }
+
+    /// Performs a single evaluation step.  Returns true if anything changed.
+    fn step(&mut self, clocked: bool) -> bool {
+        let mut changed = false;
+        let dge = self.cfg.dge.is_none_or(|io| self.ibuf[&io]);
+        let pads: Vec<IoCoord> = self.ibuf.keys().copied().collect();
+        for io in pads {
+            if let IoCoord::Macrocell(crd) = io
+                && self.mc_config(crd).dge
+                && !dge
+            {
+                continue;
+            }
+            let pullup = matches!(io, IoCoord::Macrocell(crd) if self.mc_config(crd).pullup);
+            let val = self
+                .pad_output(io)
+                .or(self.ext[&io])
+                .or(pullup.then_some(true));
+            if let Some(val) = val
+                && self.ibuf[&io] != val
+            {
+                self.ibuf.insert(io, val);
+                changed = true;
+            }
+        }
+
+        let mut fclk: Vec<bool> = self
+            .cfg
+            .fclk
+            .iter()
+            .map(|&pad| self.pad_input(pad))
+            .collect();
+        if let Some(div) = self.cfg.clkdiv {
+            let mut state = self.clkdiv_state;
+            let clk = fclk[2];
+            if self.pad_input(self.cfg.cdr) {
+                state = ClkDivState::default();
+            } else if clocked && clk && !state.clk {
+                state.count += 1;
+                if state.count == div / 2 {
+                    state.count = 0;
+                    state.out = !state.out;
+                }
+            }
+            state.clk = clk;
+            fclk[2] = state.out;
+            if state != self.clkdiv_state {
+                self.clkdiv_state = state;
+                changed = true;
+            }
+        }
+        let foe: Vec<bool> = self
+            .cfg
+            .foe
+            .iter()
+            .map(|&src| match src {
+                FoeSource::None => false,
+                FoeSource::Pad(io, inv) => self.ibuf[&io] ^ inv,
+                FoeSource::Mc(crd) => self.mc_state(crd).mc_iob,
+            })
+            .collect();
+        let fsr = self.cfg.fsr.is_some_and(|(io, inv)| self.ibuf[&io] ^ inv);
+
+        let mut new_mcs = self.mcs.clone();
+        for (fb, fbc) in self.cfg.fbs.iter().enumerate() {
+            let inputs: Vec<bool> = fbc
+                .imux
+                .iter()
+                .map(|src| match *src {
+                    ImuxSource::Const(val) => val,
+                    ImuxSource::Mc(crd) => self.get_mc(crd),
+                    ImuxSource::Iob(io) => self.iob_zia(io),
+                })
+                .collect();
+            let pts: Vec<bool> = fbc
+                .pts
+                .iter()
+                .map(|lits| lits.iter().all(|&(im, pol)| inputs[im] == pol))
+                .collect();
+            let ctl = |src: CtlSource| match src {
+                CtlSource::Const(val) => val,
+                CtlSource::Pt(pt) => pts[pt],
+                CtlSource::Fclk(idx) => fclk[idx],
+                CtlSource::Foe(idx) => foe[idx],
+                CtlSource::Fsr => fsr,
+            };
+            for mc in 0..MCS_PER_FB {
+                let mcc = &fbc.mcs[mc];
+                let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+                let sum = fbc.sums[mc].iter().any(|&pt| pts[pt]);
+                let xor = sum ^ ctl(mcc.xor) ^ mcc.xor_inv;
+                let d = if mcc.reg_d_ibuf {
+                    self.ibuf
+                        .get(&IoCoord::Macrocell(crd))
+                        .copied()
+                        .unwrap_or(false)
+                } else {
+                    xor
+                };
+                let clk = ctl(mcc.clk) ^ mcc.clk_inv;
+
+                let state = &mut new_mcs[fb][mc];
+                let edge = clocked && clk != state.clk && (clk || mcc.clk_ddr);
+                if ctl(mcc.rst) {
+                    state.reg = false;
+                } else if ctl(mcc.set) {
+                    state.reg = true;
+                } else if mcc.mode == RegMode::Latch {
+                    if clk {
+                        state.reg = d;
+                    }
+                } else if edge {
+                    match mcc.mode {
+                        RegMode::Dff => state.reg = d,
+                        RegMode::Tff => state.reg ^= d,
+                        RegMode::Dffce => {
+                            if pts[ptc(mc)] {
+                                state.reg = d;
+                            }
+                        }
+                        RegMode::Latch => unreachable!(),
+                    }
+                }
+                state.clk = clk;
+                state.xor = xor;
+                state.mc_zia = match mcc.mc_zia {
+                    ZiaMux::None => false,
+                    ZiaMux::Reg => state.reg,
+                    ZiaMux::Other => xor,
+                };
+                state.mc_iob = if mcc.mc_iob_reg { state.reg } else { xor };
+                state.oe = ctl(mcc.oe);
+            }
+        }
+        for (old, new) in self.mcs.iter().zip(&new_mcs) {
+            for (old, new) in old.iter().zip(new) {
+                if (old.reg, old.xor, old.mc_zia, old.mc_iob, old.oe)
+                    != (new.reg, new.xor, new.mc_zia, new.mc_iob, new.oe)
+                {
+                    changed = true;
+                }
+            }
+        }
+        self.mcs = new_mcs;
+        changed
+    }
+}
+
+/// JEDEC pin numbers refer to the `P<n>` pins of the package.
+impl TestTarget for Simulator {
+    fn set_pin(&mut self, pin: u32, val: Option<bool>) {
+        Simulator::set_pin(self, &format!("P{pin}"), val);
+    }
+
+    fn get_pin(&self, pin: u32) -> Option<bool> {
+        Simulator::get_pin(self, &format!("P{pin}"))
+    }
+
+    fn settle(&mut self) {
+        Simulator::settle(self);
+    }
+}
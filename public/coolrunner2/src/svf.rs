@@ -0,0 +1,380 @@
+//! Generates JTAG programming sequences (SVF / XSVF) from JED files.
+//!
+//! The fuse array is programmed one row at a time, using the `ISC_*` instructions.
+//! The `DONE` and read protection fuses are programmed in a separate final pass, after
+//! the (optional) verification, as the device cannot be read back once they are set.
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagProgram, ProgramError, ProgramOptions, bits_from_u64};
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{TileBit, TileItemKind},
+    cpld::{IoCoord, MacrocellCoord, MacrocellId},
+};
+
+use crate::{BsLayout, Chip, Database};
+
+const IR_LEN: usize = 8;
+const IR_IDCODE: u64 = 0x01;
+const IR_ISC_DISABLE: u64 = 0xc0;
+const IR_ISC_ENABLE: u64 = 0xe8;
+const IR_ISC_PROGRAM: u64 = 0xea;
+const IR_ISC_ERASE: u64 = 0xed;
+const IR_ISC_READ: u64 = 0xee;
+const IR_ISC_INIT: u64 = 0xf0;
+const IR_BYPASS: u64 = 0xff;
+
+const ENABLE_TIME: u32 = 800;
+const DISABLE_TIME: u32 = 100;
+const ERASE_TIME: u32 = 100000;
+const PROGRAM_TIME: u32 = 10000;
+const INIT_TIME: u32 = 800;
+
+const FINAL_ITEMS: [&str; 2] = ["READ_PROT", "DONE"];
+
+fn row_height(chip: &Chip) -> usize {
+    match chip.bs_layout {
+        BsLayout::Narrow => 40,
+        BsLayout::Wide => 48,
+    }
+}
+
+/// Returns the number of rows in the fuse array.
+pub fn num_rows(chip: &Chip) -> usize {
+    chip.block_rows * row_height(chip) + 2
+}
+
+/// Returns the width of the row address in the ISC data register.
+pub fn addr_len(chip: &Chip) -> usize {
+    (usize::BITS - (num_rows(chip) - 1).leading_zeros()) as usize
+}
+
+/// Returns the row address as shifted into the ISC data register: gray-coded, with
+/// the MSB shifted first.
+fn row_addr(chip: &Chip, row: usize) -> BitVec {
+    let alen = addr_len(chip);
+    let gray = row ^ row >> 1;
+    (0..alen)
+        .map(|i| (gray >> (alen - 1 - i) & 1) != 0)
+        .collect()
+}
+
+/// Converts a JED fuse list into physical fuse array rows, in shift order.
+fn jed_to_rows(chip: &Chip, db: &Database, fuses: &BitVec) -> Vec<BitVec> {
+    let mut rows = vec![BitVec::repeat(true, chip.bs_cols); num_rows(chip)];
+    for row in &mut rows {
+        for &col in &chip.xfer_cols {
+            row.set(col, false);
+        }
+    }
+    let mut pos = 0;
+    let mut put = |col: usize, row: usize| {
+        rows[row].set(chip.bs_cols - 1 - col, fuses[pos]);
+        pos += 1;
+    };
+    let narrow = chip.bs_layout == BsLayout::Narrow;
+    let fb_row = |fbr: usize, idx: usize| match chip.bs_layout {
+        BsLayout::Narrow => fbr * 40 + idx,
+        BsLayout::Wide => fbr * 48 + if idx < 20 { idx } else { idx + 8 },
+    };
+    for fb in chip.blocks() {
+        let fbc = fb.to_idx() / (chip.block_rows * 2);
+        let fbr = fb.to_idx() / 2 % chip.block_rows;
+        let fb_odd = fb.to_idx() % 2 == 1;
+        let mc_a_col = chip.block_cols[fbc];
+        let pla_or_a_col = mc_a_col + chip.mc_width;
+        let pla_and_a_col = if narrow {
+            pla_or_a_col + 32
+        } else {
+            pla_or_a_col
+        };
+        let imux_col = pla_and_a_col + 112;
+        let pla_and_b_col = imux_col + chip.imux_width * 2;
+        let pla_or_b_col = if narrow {
+            pla_and_b_col + 112
+        } else {
+            pla_and_b_col
+        };
+        let mc_b_col = if narrow {
+            pla_or_b_col + 32
+        } else {
+            pla_and_b_col + 112
+        };
+        for i in 0..40 {
+            for j in 0..chip.imux_width {
+                let col = imux_col + (chip.imux_width - 1 - j) * 2 + usize::from(fb_odd);
+                put(col, fb_row(fbr, i));
+            }
+        }
+        for pt in 0..56 {
+            let xpt = if narrow {
+                match pt {
+                    0..=7 => pt,
+                    8..=31 => 8 + (pt - 8) % 3 + (pt - 8) / 3 * 6,
+                    _ => 55 - (pt - 32) % 3 - (pt - 32) / 3 * 6,
+                }
+            } else {
+                pt
+            };
+            let (col_t, col_f) = if fb_odd {
+                (pla_and_b_col + 110 - xpt * 2, pla_and_b_col + 111 - xpt * 2)
+            } else {
+                (pla_and_a_col + xpt * 2 + 1, pla_and_a_col + xpt * 2)
+            };
+            for imux in 0..40 {
+                put(col_t, fb_row(fbr, imux));
+                put(col_f, fb_row(fbr, imux));
+            }
+        }
+        for pt in 0..56 {
+            for mc in 0..16 {
+                let (col, row) = if narrow {
+                    let idx = mc * 2 + if pt < 32 { pt % 2 } else { 1 - pt % 2 };
+                    (
+                        if fb_odd {
+                            pla_or_b_col + 31 - idx
+                        } else {
+                            pla_or_a_col + idx
+                        },
+                        fbr * 40
+                            + [
+                                17, 19, 22, 20, 0, 1, 3, 4, 5, 7, 8, 11, 12, 13, 15, 16, 23, 24,
+                                26, 27, 28, 31, 32, 34, 35, 36, 38, 39,
+                            ][pt / 2],
+                    )
+                } else {
+                    let idx = pt * 2 + mc % 2;
+                    (
+                        if fb_odd {
+                            pla_and_b_col + 111 - idx
+                        } else {
+                            pla_and_a_col + idx
+                        },
+                        fbr * 48 + 20 + mc / 2,
+                    )
+                };
+                put(col, row);
+            }
+        }
+        for mc in 0..16 {
+            let iobful = chip
+                .io
+                .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
+                    fb,
+                    MacrocellId::from_idx(mc),
+                )));
+            let jed_bits = if !chip.has_vref {
+                &db.jed_mc_bits_small
+            } else if iobful {
+                &db.jed_mc_bits_large_iob
+            } else {
+                &db.jed_mc_bits_large_buried
+            };
+            for (name, bit) in jed_bits {
+                let crd = chip.mc_bits.items[name].bits[*bit];
+                let col = if fb_odd {
+                    mc_b_col + chip.mc_width - 1 - crd.bit.to_idx()
+                } else {
+                    mc_a_col + crd.bit.to_idx()
+                };
+                let row = if narrow {
+                    fbr * 40 + mc / 2 * 5 + mc % 2 * 3
+                } else {
+                    fbr * 48 + mc * 3
+                } + crd.frame.to_idx();
+                put(col, row);
+            }
+        }
+    }
+    for (name, bit) in &chip.jed_global_bits {
+        let crd = chip.global_bits.items[name].bits[*bit];
+        put(crd.bit.to_idx(), crd.frame.to_idx());
+    }
+    rows
+}
+
+/// Returns the (row, shift position) of a global fuse.
+fn global_pos(chip: &Chip, crd: TileBit) -> (usize, usize) {
+    (crd.frame.to_idx(), chip.bs_cols - 1 - crd.bit.to_idx())
+}
+
+struct Programmer<'a> {
+    chip: &'a Chip,
+    prog: JtagProgram,
+}
+
+impl Programmer<'_> {
+    fn check_idcode(&mut self) {
+        self.prog.comment("check IDCODE");
+        self.prog.sir(IR_LEN, IR_IDCODE);
+        self.prog.sdr_check(
+            BitVec::repeat(false, 32),
+            bits_from_u64(u64::from(self.chip.idcode_part) << 12 | 0x093, 32),
+            bits_from_u64(0x0fff_8fff, 32),
+        );
+    }
+
+    fn isc_enable(&mut self) {
+        self.prog.comment("enter ISC mode");
+        self.prog.sir(IR_LEN, IR_ISC_ENABLE);
+        self.prog.run_test(1, ENABLE_TIME);
+    }
+
+    fn isc_disable(&mut self) {
+        self.prog.comment("exit ISC mode");
+        self.prog.sir(IR_LEN, IR_ISC_DISABLE);
+        self.prog.run_test(1, DISABLE_TIME);
+    }
+
+    fn program(&mut self, rows: &[(usize, BitVec)]) {
+        self.prog.sir(IR_LEN, IR_ISC_PROGRAM);
+        for (row, data) in rows {
+            let mut tdi = data.clone();
+            tdi.extend(row_addr(self.chip, *row).iter());
+            self.prog.sdr(tdi);
+            self.prog.run_test(1, PROGRAM_TIME);
+        }
+    }
+
+    fn verify(&mut self, rows: &[(usize, BitVec)]) {
+        self.prog.sir(IR_LEN, IR_ISC_READ);
+        let mut mask = BitVec::repeat(true, self.chip.bs_cols);
+        for &col in &self.chip.xfer_cols {
+            mask.set(col, false);
+        }
+        for (row, data) in rows {
+            self.prog.sdr(row_addr(self.chip, *row));
+            self.prog.run_test(20, 0);
+            self.prog.sdr_check(
+                BitVec::repeat(false, self.chip.bs_cols),
+                data.clone(),
+                mask.clone(),
+            );
+        }
+    }
+}
+
+/// Generates a JTAG sequence programming the given JED file into a device.
+///
+/// The `DONE` fuse is always programmed.  The read protection fuses are programmed
+/// if the JED file has the security fuse set, and the `USERCODE` is taken from the
+/// JED user field, if present.
+pub fn program(
+    db: &Database,
+    device: &str,
+    jed: &JedFile,
+    options: &ProgramOptions,
+) -> Result<JtagProgram, ProgramError> {
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(ProgramError::UnknownDevice(device.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let Some(ref fuses) = jed.fuses else {
+        return Err(ProgramError::MissingFuses);
+    };
+    let expected = jed_fuse_count(chip, db);
+    if fuses.len() != expected {
+        return Err(ProgramError::FuseCountMismatch {
+            expected,
+            got: fuses.len(),
+        });
+    }
+    let mut rows = jed_to_rows(chip, db, fuses);
+
+    let usercode = &chip.global_bits.items["USERCODE"];
+    if !jed.user.is_empty() {
+        if jed.user.len() != usercode.bits.len() {
+            return Err(ProgramError::UserCodeLengthMismatch {
+                expected: usercode.bits.len(),
+                got: jed.user.len(),
+            });
+        }
+        let TileItemKind::BitVec { ref invert } = usercode.kind else {
+            unreachable!()
+        };
+        for (i, &crd) in usercode.bits.iter().enumerate() {
+            let (row, bit) = global_pos(chip, crd);
+            rows[row].set(bit, jed.user[i] ^ invert[i]);
+        }
+    }
+
+    let mut main_rows: Vec<_> = rows.iter().cloned().enumerate().collect();
+    let mut final_rows = vec![];
+    for name in FINAL_ITEMS {
+        let item = &chip.global_bits.items[name];
+        let TileItemKind::BitVec { ref invert } = item.kind else {
+            unreachable!()
+        };
+        let val = name == "DONE" || jed.security == Some(true);
+        for (i, &crd) in item.bits.iter().enumerate() {
+            let (row, bit) = global_pos(chip, crd);
+            rows[row].set(bit, val ^ invert[i]);
+            main_rows[row].1.set(bit, true);
+            final_rows.push(row);
+        }
+    }
+    final_rows.sort_unstable();
+    final_rows.dedup();
+    let final_rows: Vec<_> = final_rows
+        .into_iter()
+        .map(|row| (row, rows[row].clone()))
+        .collect();
+
+    let mut p = Programmer {
+        chip,
+        prog: JtagProgram::new(),
+    };
+    p.prog.comment(format!("device {device}"));
+    p.check_idcode();
+    p.isc_enable();
+    if options.erase {
+        p.prog.comment("erase the device");
+        p.prog.sir(IR_LEN, IR_ISC_ERASE);
+        p.prog.run_test(1, ERASE_TIME);
+        // read protection is only reevaluated when entering ISC mode
+        p.isc_disable();
+        p.isc_enable();
+    }
+    p.prog.comment("program main fuses");
+    p.program(&main_rows);
+    if options.verify {
+        p.prog.comment("verify main fuses");
+        p.verify(&main_rows);
+    }
+    p.prog.comment("program protection and DONE fuses");
+    p.program(&final_rows);
+    p.prog.comment("load the configuration");
+    p.prog.sir(IR_LEN, IR_ISC_INIT);
+    p.prog.run_test(20, 0);
+    p.prog.sir(IR_LEN, IR_ISC_INIT);
+    p.prog.sdr(BitVec::repeat(false, 8));
+    p.prog.run_test(1, INIT_TIME);
+    p.isc_disable();
+    p.prog.sir(IR_LEN, IR_BYPASS);
+    Ok(p.prog)
+}
+
+/// Returns the number of fuses in a JED file for the given chip.
+pub fn jed_fuse_count(chip: &Chip, db: &Database) -> usize {
+    let mut res = chip.jed_global_bits.len();
+    for fb in chip.blocks() {
+        res += 40 * chip.imux_width + 56 * 80 + 56 * 16;
+        for mc in 0..16 {
+            let iobful = chip
+                .io
+                .contains_key(&IoCoord::Macrocell(MacrocellCoord::simple(
+                    fb,
+                    MacrocellId::from_idx(mc),
+                )));
+            res += if !chip.has_vref {
+                db.jed_mc_bits_small.len()
+            } else if iobful {
+                db.jed_mc_bits_large_iob.len()
+            } else {
+                db.jed_mc_bits_large_buried.len()
+            };
+        }
+    }
+    res
+}
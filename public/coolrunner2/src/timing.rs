@@ -0,0 +1,291 @@
+//! Static timing analysis of a configured device.
+//!
+//! The timing graph is built from the configuration as decoded for the simulator, using
+//! the delays of the chosen speed grade.  The I/O standard is not stored in the bitstream,
+//! so it is given by the caller and applies to all pads; it selects the input and output
+//! buffer delays.  Control terms pay `DEL_IMUX_CT`, while product terms feeding the XOR gate
+//! directly pay `DEL_IMUX_PT`.  The clock divider is treated as a plain connection from its
+//! input pad, and the data gate latches are assumed to be transparent.
+
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_types::{
+    cpld::{
+        BlockId, IoCoord, MacrocellCoord, MacrocellId,
+        timing::{MacrocellTiming, RegisterNodes},
+    },
+    speed::{EdgeDelay, Speed, SpeedError},
+    timing::{ClockEdge, TimingGraph, TimingNodeId, TimingNodeKind},
+};
+
+use crate::{
+    Bond, Chip,
+    bitstream::Bitstream,
+    sim::{Config, CtlSource, FoeSource, ImuxSource, MCS_PER_FB, OeMode, RegMode, ZiaMux, ptc},
+};
+
+#[derive(Clone, Copy)]
+struct McNodes {
+    xor: TimingNodeId,
+    comb: TimingNodeId,
+    d: TimingNodeId,
+    clk: TimingNodeId,
+    ce: TimingNodeId,
+    sr: TimingNodeId,
+    oe: TimingNodeId,
+    q: TimingNodeId,
+    out: TimingNodeId,
+    zia: TimingNodeId,
+}
+
+/// Builds the timing graph of a configured device for the given speed grade and I/O
+/// standard.
+pub fn timing_graph(
+    chip: &Chip,
+    bond: &Bond,
+    bs: &Bitstream,
+    speed: &Speed,
+    iostd: &str,
+) -> Result<TimingGraph, SpeedError> {
+    let cfg = Config::new(chip, bond, bs);
+    let mut mt = MacrocellTiming::new(speed);
+
+    let del_ibuf_schmitt = speed.delay(&format!("DEL_IBUF_SCHMITT.{iostd}"))?;
+    // LVCMOS15 inputs always use the Schmitt trigger
+    let del_ibuf_plain = match speed.delay(&format!("DEL_IBUF_PLAIN.{iostd}")) {
+        Err(SpeedError::Missing(_)) => del_ibuf_schmitt,
+        res => res?,
+    };
+    let (pads, pad_pins) = mt
+        .add_input_pads(cfg.pins.iter().map(|(pin, &io)| (pin.as_str(), io)), |io| {
+            format!("{io}.PAD")
+        });
+    let mut ibufs = BTreeMap::new();
+    for (&io, &pad) in &pads {
+        let schmitt = match io {
+            IoCoord::Ipad(_) => cfg.ipad_schmitt,
+            IoCoord::Macrocell(crd) => {
+                cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()].ibuf_schmitt
+            }
+        };
+        let ibuf = mt
+            .graph
+            .add_node(format!("{io}.I"), TimingNodeKind::Internal);
+        mt.graph.add_edge(
+            pad,
+            ibuf,
+            if schmitt {
+                del_ibuf_schmitt
+            } else {
+                del_ibuf_plain
+            },
+        );
+        ibufs.insert(io, ibuf);
+    }
+
+    let mut mcs: Vec<Vec<McNodes>> = vec![];
+    for fb in 0..cfg.fbs.len() {
+        let mut fb_mcs = vec![];
+        for mc in 0..MCS_PER_FB {
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            let mut node = |name: &str| {
+                mt.graph
+                    .add_node(format!("{crd}.{name}"), TimingNodeKind::Internal)
+            };
+            fb_mcs.push(McNodes {
+                xor: node("XOR"),
+                comb: node("COMB"),
+                d: node("D"),
+                clk: node("CLK"),
+                ce: node("CE"),
+                sr: node("SR"),
+                oe: node("OE"),
+                q: node("Q"),
+                out: node("OUT"),
+                zia: node("ZIA"),
+            });
+        }
+        mcs.push(fb_mcs);
+    }
+    let mc_nodes = |crd: MacrocellCoord| mcs[crd.block.to_idx()][crd.macrocell.to_idx()];
+
+    let del_ibuf_imux = speed.delay("DEL_IBUF_IMUX")?;
+    let del_uim_imux = speed.delay("DEL_UIM_IMUX")?;
+    let del_ibuf_d = speed.delay("DEL_IBUF_D")?;
+    let del_ibuf_fclk = speed.delay("DEL_IBUF_FCLK")?;
+    let del_ibuf_foe = speed.delay("DEL_IBUF_FOE")?;
+    let del_ibuf_fsr = speed.delay("DEL_IBUF_FSR")?;
+    let del_mc_foe = speed.delay("DEL_MC_FOE")?;
+    let del_imux_pt = speed.delay("DEL_IMUX_PT")?;
+    let del_imux_ct = speed.delay("DEL_IMUX_CT")?;
+    let del_imux_or = speed.delay("DEL_IMUX_OR")?;
+    let del_obuf_oe = speed.delay("DEL_OBUF_OE")?;
+
+    for (fb, fbc) in cfg.fbs.iter().enumerate() {
+        let imux: Vec<TimingNodeId> = (0..fbc.imux.len())
+            .map(|im| {
+                mt.graph
+                    .add_node(format!("FB{fb}.IM{im}"), TimingNodeKind::Internal)
+            })
+            .collect();
+        for (im, &src) in fbc.imux.iter().enumerate() {
+            match src {
+                ImuxSource::Const(_) => (),
+                ImuxSource::Mc(crd) => mt.graph.add_edge(mc_nodes(crd).zia, imux[im], del_uim_imux),
+                ImuxSource::Iob(io) => {
+                    let iob_zia = match io {
+                        IoCoord::Ipad(_) => ZiaMux::Other,
+                        IoCoord::Macrocell(crd) => {
+                            cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()].iob_zia
+                        }
+                    };
+                    match iob_zia {
+                        ZiaMux::None => (),
+                        ZiaMux::Reg => {
+                            let IoCoord::Macrocell(crd) = io else {
+                                unreachable!()
+                            };
+                            mt.graph.add_edge(mc_nodes(crd).q, imux[im], del_uim_imux);
+                        }
+                        ZiaMux::Other => {
+                            if let Some(&ibuf) = ibufs.get(&io) {
+                                mt.graph.add_edge(ibuf, imux[im], del_ibuf_imux);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let pt_inputs = |pt: usize| -> Vec<TimingNodeId> {
+            fbc.pts[pt].iter().map(|&(im, _)| imux[im]).collect()
+        };
+
+        let ctl = |graph: &mut TimingGraph, src: CtlSource, dst: TimingNodeId, pt_del| match src {
+            CtlSource::Const(_) => (),
+            CtlSource::Pt(pt) => {
+                for src in pt_inputs(pt) {
+                    graph.add_edge(src, dst, pt_del);
+                }
+            }
+            CtlSource::Fclk(idx) => {
+                if let Some(io) = cfg.fclk[idx]
+                    && let Some(&ibuf) = ibufs.get(&io)
+                {
+                    graph.add_edge(ibuf, dst, del_ibuf_fclk);
+                }
+            }
+            CtlSource::Foe(idx) => match cfg.foe[idx] {
+                FoeSource::None => (),
+                FoeSource::Pad(io, _) => {
+                    if let Some(&ibuf) = ibufs.get(&io) {
+                        graph.add_edge(ibuf, dst, del_ibuf_foe);
+                    }
+                }
+                FoeSource::Mc(crd) => graph.add_edge(mc_nodes(crd).out, dst, del_mc_foe),
+            },
+            CtlSource::Fsr => {
+                if let Some((io, _)) = cfg.fsr
+                    && let Some(&ibuf) = ibufs.get(&io)
+                {
+                    graph.add_edge(ibuf, dst, del_ibuf_fsr);
+                }
+            }
+        };
+
+        for (mc, mcc) in fbc.mcs.iter().enumerate() {
+            let nodes = mcs[fb][mc];
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            for &pt in &fbc.sums[mc] {
+                for src in pt_inputs(pt) {
+                    mt.graph.add_edge(src, nodes.xor, del_imux_or);
+                }
+            }
+            ctl(&mut mt.graph, mcc.xor, nodes.xor, del_imux_pt);
+            mt.graph
+                .add_edge(nodes.xor, nodes.comb, speed.delay("DEL_D_Q_COMB")?);
+            if mcc.reg_d_ibuf {
+                if let Some(&ibuf) = ibufs.get(&IoCoord::Macrocell(crd)) {
+                    mt.graph.add_edge(ibuf, nodes.d, del_ibuf_d);
+                }
+            } else {
+                mt.graph.add_edge(nodes.xor, nodes.d, EdgeDelay::ZERO);
+            }
+            ctl(&mut mt.graph, mcc.clk, nodes.clk, del_imux_ct);
+            ctl(&mut mt.graph, mcc.rst, nodes.sr, del_imux_ct);
+            ctl(&mut mt.graph, mcc.set, nodes.sr, del_imux_ct);
+            ctl(&mut mt.graph, mcc.oe, nodes.oe, del_imux_ct);
+
+            let fclk = matches!(mcc.clk, CtlSource::Fclk(_));
+            if mcc.mode == RegMode::Dffce {
+                ctl(&mut mt.graph, CtlSource::Pt(ptc(mc)), nodes.ce, del_imux_ct);
+            }
+            mt.add_register(
+                crd.to_string(),
+                RegisterNodes {
+                    clk: nodes.clk,
+                    d: nodes.d,
+                    ce: (mcc.mode == RegMode::Dffce).then_some(nodes.ce),
+                    sr: nodes.sr,
+                    q: nodes.q,
+                },
+                if mcc.clk_ddr {
+                    ClockEdge::Both
+                } else if mcc.clk_inv {
+                    ClockEdge::Falling
+                } else {
+                    ClockEdge::Rising
+                },
+                &format!(
+                    "SETUPHOLD_D_CLK_{d}_{clk}",
+                    d = if mcc.reg_d_ibuf { "IBUF" } else { "PT" },
+                    clk = if fclk { "FCLK" } else { "PT" },
+                ),
+                if fclk { "WIDTH_CLK" } else { "WIDTH_CLK_PT" },
+            )?;
+            if mcc.mode == RegMode::Latch {
+                mt.graph
+                    .add_edge(nodes.d, nodes.q, speed.delay("DEL_D_Q_LATCH")?);
+            }
+            mt.graph.add_edge(
+                if mcc.mc_iob_reg { nodes.q } else { nodes.comb },
+                nodes.out,
+                EdgeDelay::ZERO,
+            );
+            match mcc.mc_zia {
+                ZiaMux::None => (),
+                ZiaMux::Reg => mt.graph.add_edge(nodes.q, nodes.zia, EdgeDelay::ZERO),
+                ZiaMux::Other => mt.graph.add_edge(nodes.comb, nodes.zia, EdgeDelay::ZERO),
+            }
+
+            if !cfg.done
+                || mcc.oe_mode == OeMode::IsGnd
+                || (mcc.oe_mode == OeMode::Normal && mcc.oe == CtlSource::Const(false))
+            {
+                continue;
+            }
+            let Some(&pin) = pad_pins.get(&IoCoord::Macrocell(crd)) else {
+                continue;
+            };
+            match mcc.oe_mode {
+                OeMode::OpenDrain => {
+                    let obuf = mt
+                        .graph
+                        .add_node(format!("{crd}.O"), TimingNodeKind::Output(pin.into()));
+                    mt.graph.add_edge(nodes.out, obuf, del_obuf_oe);
+                }
+                _ => {
+                    mt.add_output_pad(
+                        format!("{crd}.O"),
+                        pin,
+                        nodes.out,
+                        mcc.slew_fast,
+                        &format!(".{iostd}"),
+                        Some((nodes.oe, del_obuf_oe)),
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(mt.graph)
+}
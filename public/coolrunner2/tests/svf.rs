@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+
+use prjcombine_coolrunner2::{
+    BsLayout, Chip, Database,
+    svf::{addr_len, jed_fuse_count, num_rows, program},
+};
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagOp, JtagProgram, ProgramError, ProgramOptions};
+use prjcombine_types::bitvec::BitVec;
+
+const IR_IDCODE: u64 = 0x01;
+const IR_ISC_DISABLE: u64 = 0xc0;
+const IR_ISC_ENABLE: u64 = 0xe8;
+const IR_ISC_PROGRAM: u64 = 0xea;
+const IR_ISC_ERASE: u64 = 0xed;
+const IR_ISC_READ: u64 = 0xee;
+const IR_ISC_INIT: u64 = 0xf0;
+const IR_BYPASS: u64 = 0xff;
+
+const DEVICE: &str = "xc2c32a";
+
+fn db() -> Database {
+    Database::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../databases/coolrunner2.zstd"
+    ))
+    .unwrap()
+}
+
+fn get_chip<'a>(db: &'a Database, device: &str) -> &'a Chip {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    &db.chips[dev.chip]
+}
+
+fn to_u64(bits: &BitVec) -> u64 {
+    bits.iter()
+        .enumerate()
+        .map(|(i, bit)| u64::from(bit) << i)
+        .sum()
+}
+
+fn ir_sequence(prog: &JtagProgram) -> Vec<u64> {
+    prog.ops
+        .iter()
+        .filter_map(|op| match op {
+            JtagOp::ShiftIr(val) => {
+                assert_eq!(val.len(), 8);
+                Some(to_u64(val))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Decodes a gray-coded row address, MSB first.
+fn decode_addr(addr: &BitVec) -> usize {
+    let mut gray = 0;
+    for bit in addr.iter() {
+        gray = gray << 1 | usize::from(bit);
+    }
+    let mut row = 0;
+    while gray != 0 {
+        row ^= gray;
+        gray >>= 1;
+    }
+    row
+}
+
+struct Programmed {
+    /// The data of the main programming pass, by row.
+    main: BTreeMap<usize, BitVec>,
+    /// The data last programmed into every row.
+    last: BTreeMap<usize, BitVec>,
+    /// The data checked by the verification pass, and its mask.
+    verified: BTreeMap<usize, (BitVec, BitVec)>,
+}
+
+fn programmed_rows(prog: &JtagProgram, chip: &Chip) -> Programmed {
+    let mut res = Programmed {
+        main: BTreeMap::new(),
+        last: BTreeMap::new(),
+        verified: BTreeMap::new(),
+    };
+    let mut ir = 0;
+    let mut num_program = 0;
+    let mut addr = None;
+    for op in &prog.ops {
+        match op {
+            JtagOp::ShiftIr(val) => {
+                ir = to_u64(val);
+                if ir == IR_ISC_PROGRAM {
+                    num_program += 1;
+                }
+            }
+            JtagOp::ShiftDr(shift) if ir == IR_ISC_PROGRAM => {
+                assert_eq!(shift.tdi.len(), chip.bs_cols + addr_len(chip));
+                let row = decode_addr(&shift.tdi.slice(chip.bs_cols..));
+                let data = shift.tdi.slice(..chip.bs_cols);
+                if num_program == 1 {
+                    assert!(res.main.insert(row, data.clone()).is_none());
+                }
+                res.last.insert(row, data);
+            }
+            JtagOp::ShiftDr(shift) if ir == IR_ISC_READ => match shift.check {
+                None => addr = Some(decode_addr(&shift.tdi)),
+                Some(ref check) => {
+                    res.verified.insert(addr.take().unwrap(), check.clone());
+                }
+            },
+            _ => (),
+        }
+    }
+    res
+}
+
+#[test]
+fn svf_sequence() {
+    let db = db();
+    let chip = get_chip(&db, DEVICE);
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, jed_fuse_count(chip, &db)));
+    let prog = program(&db, DEVICE, &jed, &ProgramOptions::default()).unwrap();
+    assert_eq!(
+        ir_sequence(&prog),
+        [
+            IR_IDCODE,
+            IR_ISC_ENABLE,
+            IR_ISC_ERASE,
+            IR_ISC_DISABLE,
+            IR_ISC_ENABLE,
+            IR_ISC_PROGRAM,
+            IR_ISC_READ,
+            IR_ISC_PROGRAM,
+            IR_ISC_INIT,
+            IR_ISC_INIT,
+            IR_ISC_DISABLE,
+            IR_BYPASS
+        ]
+    );
+
+    let mut runtests = vec![];
+    let mut ir = 0;
+    for op in &prog.ops {
+        match *op {
+            JtagOp::ShiftIr(ref val) => ir = to_u64(val),
+            JtagOp::RunTest { tck, usecs } => match ir {
+                IR_ISC_PROGRAM => assert_eq!((tck, usecs), (1, 10000)),
+                IR_ISC_READ => assert_eq!((tck, usecs), (20, 0)),
+                _ => runtests.push((ir, tck, usecs)),
+            },
+            _ => (),
+        }
+    }
+    assert_eq!(
+        runtests,
+        [
+            (IR_ISC_ENABLE, 1, 800),
+            (IR_ISC_ERASE, 1, 100000),
+            (IR_ISC_DISABLE, 1, 100),
+            (IR_ISC_ENABLE, 1, 800),
+            (IR_ISC_INIT, 20, 0),
+            (IR_ISC_INIT, 1, 800),
+            (IR_ISC_DISABLE, 1, 100),
+        ]
+    );
+
+    let idcode = chip.idcode_part << 12 | 0x093;
+    let svf = prog.emit_svf();
+    let lines: Vec<_> = svf.lines().skip(5).take(5).collect();
+    assert_eq!(
+        lines,
+        [
+            format!("// device {DEVICE}"),
+            "// check IDCODE".to_string(),
+            "SIR 8 TDI (01);".to_string(),
+            format!("SDR 32 TDI (00000000) TDO ({idcode:08x}) MASK (0fff8fff);"),
+            "// enter ISC mode".to_string(),
+        ]
+    );
+    assert!(svf.lines().any(|line| line == "RUNTEST 1 TCK 1.00E-1 SEC;"));
+
+    let xsvf = prog.emit_xsvf();
+    let mut expected = vec![
+        0x12, 0x00, 0x12, 0x01, 0x13, 0x00, 0x14, 0x00, 0x07, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+    ];
+    expected.push(0x16);
+    expected.extend(format!("device {DEVICE}\0").bytes());
+    expected.push(0x16);
+    expected.extend(b"check IDCODE\0");
+    expected.extend([0x02, 8, 0x01]);
+    expected.extend([0x08, 0, 0, 0, 32]);
+    expected.extend([0x01, 0x0f, 0xff, 0x8f, 0xff]);
+    expected.extend([0x09, 0, 0, 0, 0]);
+    expected.extend(idcode.to_be_bytes());
+    expected.push(0x16);
+    expected.extend(b"enter ISC mode\0");
+    expected.extend([0x02, 8, 0xe8]);
+    expected.extend([0x17, 0x01, 0x01, 0x00, 0x00, 0x03, 0x20]);
+    assert_eq!(xsvf[..expected.len()], expected);
+}
+
+#[test]
+fn svf_rows() {
+    let db = db();
+    let mut layouts = vec![];
+    for device in [DEVICE, "xc2c128"] {
+        let chip = get_chip(&db, device);
+        layouts.push(chip.bs_layout);
+        let count = jed_fuse_count(chip, &db);
+        let mut rows = vec![];
+        for val in [false, true] {
+            let jed = JedFile::new().with_fuses(BitVec::repeat(val, count));
+            let prog = program(&db, device, &jed, &ProgramOptions::default()).unwrap();
+            let programmed = programmed_rows(&prog, chip);
+            // every row is programmed in the main pass, and verified against it with
+            // the transfer columns masked out
+            assert_eq!(
+                programmed.main.keys().copied().collect::<Vec<_>>(),
+                (0..num_rows(chip)).collect::<Vec<_>>(),
+                "{device}"
+            );
+            assert_eq!(programmed.verified.len(), num_rows(chip), "{device}");
+            for (row, (tdo, mask)) in &programmed.verified {
+                assert_eq!(tdo, &programmed.main[row], "{device}");
+                for col in 0..chip.bs_cols {
+                    assert_eq!(mask[col], !chip.xfer_cols.contains(&col), "{device}");
+                }
+            }
+            for data in programmed.last.values() {
+                for &col in &chip.xfer_cols {
+                    assert!(!data[col], "{device}");
+                }
+            }
+            rows.push(programmed.last);
+        }
+        // every fuse lands in its own bit of the array
+        let diff: usize = rows[0]
+            .iter()
+            .map(|(row, data)| (data ^ &rows[1][row]).iter().filter(|&bit| bit).count())
+            .sum();
+        assert_eq!(diff, count, "{device}");
+    }
+    assert!(layouts.contains(&BsLayout::Narrow));
+    assert!(layouts.contains(&BsLayout::Wide));
+}
+
+#[test]
+fn svf_errors() {
+    let db = db();
+    let chip = get_chip(&db, DEVICE);
+    let options = ProgramOptions::default();
+    let count = jed_fuse_count(chip, &db);
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, count));
+    assert!(matches!(
+        program(&db, "xc2c999", &jed, &options),
+        Err(ProgramError::UnknownDevice(ref device)) if device == "xc2c999"
+    ));
+    assert!(matches!(
+        program(&db, DEVICE, &JedFile::new(), &options),
+        Err(ProgramError::MissingFuses)
+    ));
+    let mut jed_user = jed.clone();
+    jed_user.user = BitVec::repeat(true, 3);
+    assert!(matches!(
+        program(&db, DEVICE, &jed_user, &options),
+        Err(ProgramError::UserCodeLengthMismatch { got: 3, .. })
+    ));
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, count - 1));
+    assert!(matches!(
+        program(&db, DEVICE, &jed, &options),
+        Err(ProgramError::FuseCountMismatch { expected, got }) if expected == count && got == count - 1
+    ));
+}
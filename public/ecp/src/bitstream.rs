@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+
+use bitvec::prelude::*;
+use prjcombine_entity::EntityId;
+pub use prjcombine_types::bitstream::BitstreamError;
+use prjcombine_types::bsdata::{RectBitId, RectFrameId};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum BitPos {
+    // frame, bit
+    Main(usize, usize),
+    // EBR, word, bit
+    Ebr(usize, usize, usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum BitRect {
+    // frame, width, bit, height
+    Main(usize, usize, usize, usize),
+    // EBR index; frames are 9-bit words
+    Ebr(usize),
+}
+
+impl prjcombine_types::bitrect::BitRect for BitRect {
+    type BitPos = BitPos;
+
+    fn xlat_pos_rev(&self, bit: BitPos) -> Option<(RectFrameId, RectBitId)> {
+        let (rframe, rbit) = match (*self, bit) {
+            (BitRect::Main(frame, width, bit, height), BitPos::Main(bframe, bbit))
+                if bframe >= frame
+                    && bframe < frame + width
+                    && bbit >= bit
+                    && bbit < bit + height =>
+            {
+                (bframe - frame, bbit - bit)
+            }
+            (BitRect::Ebr(ebr), BitPos::Ebr(bebr, word, bit)) if bebr == ebr => (word, bit),
+            _ => return None,
+        };
+        Some((RectFrameId::from_idx(rframe), RectBitId::from_idx(rbit)))
+    }
+
+    fn xlat_pos_fwd(&self, bit: (RectFrameId, RectBitId)) -> BitPos {
+        let (rframe, rbit) = bit;
+        let rframe = rframe.to_idx();
+        let rbit = rbit.to_idx();
+        match *self {
+            BitRect::Main(frame, width, bit, height) => {
+                assert!(rframe < width);
+                assert!(rbit < height);
+                BitPos::Main(frame + rframe, bit + rbit)
+            }
+            BitRect::Ebr(ebr) => {
+                assert!(rbit < EBR_WORD_BITS);
+                BitPos::Ebr(ebr, rframe, rbit)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitstreamGeom {
+    pub frame_len: usize,
+    pub frames_num: usize,
+    pub ebr_num: usize,
+    // 9-bit words per EBR
+    pub ebr_words: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitstream {
+    pub frame_len: usize,
+    pub frame_data: BitVec,
+    pub frame_present: BitVec,
+    pub idcode: Option<u32>,
+    pub ctrl0: Option<u32>,
+    pub usercode: Option<u32>,
+    pub sed_crc: Option<u32>,
+    pub security: bool,
+    pub ebr_words: usize,
+    pub ebr_data: BitVec,
+    pub ebr_present: BitVec,
+}
+
+const PREAMBLE: [u8; 4] = [0xff, 0xff, 0xbd, 0xb3];
+
+const LSC_RESET_CRC: u8 = 0x3b;
+const VERIFY_ID: u8 = 0xe2;
+const LSC_PROG_CNTRL0: u8 = 0x22;
+const LSC_INIT_ADDRESS: u8 = 0x46;
+const LSC_WRITE_ADDRESS: u8 = 0xb4;
+const LSC_PROG_INCR_RTI: u8 = 0x82;
+const LSC_PROG_SED_CRC: u8 = 0xa2;
+const ISC_PROGRAM_SECURITY: u8 = 0xce;
+const ISC_PROGRAM_USERCODE: u8 = 0xc2;
+const LSC_EBR_ADDRESS: u8 = 0xf6;
+const LSC_EBR_WRITE: u8 = 0xb2;
+const ISC_PROGRAM_DONE: u8 = 0x5e;
+const ISC_NOOP: u8 = 0xff;
+
+// param byte flag: a CRC16 follows the command data (or every frame)
+const FLAG_CRC: u8 = 0x80;
+
+// a single EBR write frame is 72 bits, holding eight 9-bit words
+const EBR_FRAME_BYTES: usize = 9;
+const EBR_FRAME_WORDS: usize = 8;
+const EBR_WORD_BITS: usize = 9;
+
+// LSC_EBR_ADDRESS: EBR index in the upper bits, word address in the low 11 bits
+const EBR_ADDR_SHIFT: u32 = 11;
+
+struct Crc {
+    state: u16,
+}
+
+impl Crc {
+    fn new() -> Self {
+        Self { state: 0 }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            for i in (0..8).rev() {
+                let top = (self.state >> 15) != 0;
+                self.state = self.state << 1 | u16::from((byte >> i) & 1);
+                if top {
+                    self.state ^= 0x8005;
+                }
+            }
+        }
+    }
+
+    // the CRC is defined over the data followed by 16 zero bits
+    fn get(&self) -> u16 {
+        let mut state = self.state;
+        for _ in 0..16 {
+            let top = (state >> 15) != 0;
+            state <<= 1;
+            if top {
+                state ^= 0x8005;
+            }
+        }
+        state
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    start: usize,
+    crc: Crc,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, n: usize) -> Result<&[u8], BitstreamError> {
+        if self.pos + n > self.data.len() {
+            return Err(BitstreamError::Truncated { offset: self.start });
+        }
+        let res = &self.data[self.pos..self.pos + n];
+        self.crc.feed(res);
+        self.pos += n;
+        Ok(res)
+    }
+
+    fn u8(&mut self) -> Result<u8, BitstreamError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, BitstreamError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn params(&mut self) -> Result<[u8; 3], BitstreamError> {
+        let b = self.bytes(3)?;
+        Ok([b[0], b[1], b[2]])
+    }
+
+    fn check_crc(&mut self) -> Result<(), BitstreamError> {
+        let actual = self.crc.get();
+        let b = self.bytes(2)?;
+        let expected = u16::from_be_bytes([b[0], b[1]]);
+        if expected != actual {
+            return Err(BitstreamError::CrcMismatch {
+                offset: self.pos - 2,
+                expected: expected.into(),
+                actual: actual.into(),
+            });
+        }
+        self.crc = Crc::new();
+        Ok(())
+    }
+
+    fn malformed(&self, reason: &'static str) -> BitstreamError {
+        BitstreamError::Malformed {
+            offset: self.start,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+struct Writer {
+    data: Vec<u8>,
+    crc: Crc,
+}
+
+impl Writer {
+    fn bytes(&mut self, data: &[u8]) {
+        self.crc.feed(data);
+        self.data.extend(data);
+    }
+
+    fn cmd(&mut self, opcode: u8, params: [u8; 3]) {
+        self.bytes(&[opcode]);
+        self.bytes(&params);
+    }
+
+    fn crc(&mut self) {
+        let crc = self.crc.get();
+        self.data.extend(crc.to_be_bytes());
+        self.crc = Crc::new();
+    }
+}
+
+impl Bitstream {
+    pub fn new(geom: &BitstreamGeom) -> Self {
+        Self {
+            frame_len: geom.frame_len,
+            frame_data: BitVec::repeat(false, geom.frame_len * geom.frames_num),
+            frame_present: BitVec::repeat(false, geom.frames_num),
+            idcode: None,
+            ctrl0: None,
+            usercode: None,
+            sed_crc: None,
+            security: false,
+            ebr_words: geom.ebr_words,
+            ebr_data: BitVec::repeat(false, geom.ebr_num * geom.ebr_words * EBR_WORD_BITS),
+            ebr_present: BitVec::repeat(false, geom.ebr_num),
+        }
+    }
+
+    pub fn frame(&self, idx: usize) -> &BitSlice {
+        &self.frame_data[idx * self.frame_len..(idx + 1) * self.frame_len]
+    }
+
+    pub fn frame_mut(&mut self, idx: usize) -> &mut BitSlice {
+        &mut self.frame_data[idx * self.frame_len..(idx + 1) * self.frame_len]
+    }
+
+    pub fn ebr(&self, idx: usize) -> &BitSlice {
+        let len = self.ebr_words * EBR_WORD_BITS;
+        &self.ebr_data[idx * len..(idx + 1) * len]
+    }
+
+    pub fn ebr_mut(&mut self, idx: usize) -> &mut BitSlice {
+        let len = self.ebr_words * EBR_WORD_BITS;
+        &mut self.ebr_data[idx * len..(idx + 1) * len]
+    }
+
+    pub fn get(&self, bit: BitPos) -> bool {
+        match bit {
+            BitPos::Main(frame, bit) => self.frame(frame)[bit],
+            BitPos::Ebr(ebr, word, bit) => self.ebr(ebr)[word * EBR_WORD_BITS + bit],
+        }
+    }
+
+    pub fn set(&mut self, bit: BitPos, val: bool) {
+        match bit {
+            BitPos::Main(frame, bit) => self.frame_mut(frame).set(bit, val),
+            BitPos::Ebr(ebr, word, bit) => {
+                self.ebr_mut(ebr).set(word * EBR_WORD_BITS + bit, val);
+                self.ebr_present.set(ebr, true);
+            }
+        }
+    }
+
+    pub fn diff(a: &Bitstream, b: &Bitstream) -> HashMap<BitPos, bool> {
+        assert_eq!(a.frame_len, b.frame_len);
+        assert_eq!(a.frame_present.len(), b.frame_present.len());
+        let mut res = HashMap::new();
+        for i in 0..a.frame_present.len() {
+            let fa = a.frame(i);
+            let fb = b.frame(i);
+            if fa == fb {
+                continue;
+            }
+            for j in 0..a.frame_len {
+                if fa[j] != fb[j] {
+                    res.insert(BitPos::Main(i, j), fb[j]);
+                }
+            }
+        }
+        assert_eq!(a.ebr_words, b.ebr_words);
+        assert_eq!(a.ebr_present.len(), b.ebr_present.len());
+        for i in 0..a.ebr_present.len() {
+            let ea = a.ebr(i);
+            let eb = b.ebr(i);
+            if ea == eb {
+                continue;
+            }
+            for j in 0..ea.len() {
+                if ea[j] != eb[j] {
+                    res.insert(BitPos::Ebr(i, j / EBR_WORD_BITS, j % EBR_WORD_BITS), eb[j]);
+                }
+            }
+        }
+        res
+    }
+
+    pub fn parse(geom: &BitstreamGeom, data: &[u8]) -> Result<Self, BitstreamError> {
+        let mut res = Bitstream::new(geom);
+        let mut pos = 0;
+        // optional comment block: ff 00 <strings> 00 ff
+        if data.starts_with(&[0xff, 0x00]) {
+            let Some(end) = data.windows(2).skip(2).position(|w| w == [0x00, 0xff]) else {
+                return Err(BitstreamError::Malformed {
+                    offset: 0,
+                    reason: "unterminated comment block".to_string(),
+                });
+            };
+            pos = end + 4;
+        }
+        while pos < data.len()
+            && data[pos..].starts_with(&[0xff])
+            && !data[pos..].starts_with(&PREAMBLE)
+        {
+            pos += 1;
+        }
+        if !data[pos..].starts_with(&PREAMBLE) {
+            return Err(BitstreamError::Malformed {
+                offset: pos,
+                reason: "missing preamble".to_string(),
+            });
+        }
+        let mut r = Reader {
+            data,
+            pos: pos + PREAMBLE.len(),
+            start: pos,
+            crc: Crc::new(),
+        };
+        let bytes_per_frame = geom.frame_len.div_ceil(8);
+        let mut ebr_addr = None;
+        loop {
+            r.start = r.pos;
+            if r.pos == data.len() {
+                return Err(r.malformed("missing ISC_PROGRAM_DONE"));
+            }
+            let opcode = r.u8()?;
+            match opcode {
+                ISC_NOOP => (),
+                LSC_RESET_CRC => {
+                    r.params()?;
+                    r.crc = Crc::new();
+                }
+                VERIFY_ID => {
+                    r.params()?;
+                    res.idcode = Some(r.u32()?);
+                }
+                LSC_PROG_CNTRL0 => {
+                    r.params()?;
+                    res.ctrl0 = Some(r.u32()?);
+                }
+                LSC_INIT_ADDRESS => {
+                    r.params()?;
+                }
+                LSC_WRITE_ADDRESS => {
+                    r.params()?;
+                    let addr = r.u32()?;
+                    if addr != 0 {
+                        return Err(r.malformed("unsupported nonzero frame address"));
+                    }
+                }
+                LSC_PROG_INCR_RTI => {
+                    let params = r.params()?;
+                    let crc_check = (params[0] & FLAG_CRC) != 0;
+                    let num = usize::from(u16::from_be_bytes([params[1], params[2]]));
+                    if num != geom.frames_num {
+                        return Err(r.malformed("frame count mismatch"));
+                    }
+                    for fi in 0..num {
+                        let fdata = r.bytes(bytes_per_frame)?.to_vec();
+                        let frame = res.frame_mut(fi);
+                        for j in 0..geom.frame_len {
+                            let byte = fdata[bytes_per_frame - 1 - j / 8];
+                            frame.set(j, ((byte >> (j % 8)) & 1) != 0);
+                        }
+                        res.frame_present.set(fi, true);
+                        if crc_check {
+                            r.check_crc()?;
+                        }
+                        if r.u8()? != 0xff {
+                            return Err(r.malformed("missing frame trailer"));
+                        }
+                    }
+                }
+                LSC_PROG_SED_CRC => {
+                    r.params()?;
+                    res.sed_crc = Some(r.u32()?);
+                }
+                ISC_PROGRAM_USERCODE => {
+                    let params = r.params()?;
+                    res.usercode = Some(r.u32()?);
+                    if (params[0] & FLAG_CRC) != 0 {
+                        r.check_crc()?;
+                    }
+                }
+                ISC_PROGRAM_SECURITY => {
+                    r.params()?;
+                    res.security = true;
+                }
+                LSC_EBR_ADDRESS => {
+                    r.params()?;
+                    let addr = r.u32()?;
+                    let ebr = (addr >> EBR_ADDR_SHIFT) as usize;
+                    let word = (addr & ((1 << EBR_ADDR_SHIFT) - 1)) as usize;
+                    if ebr >= geom.ebr_num || word >= geom.ebr_words {
+                        return Err(BitstreamError::InvalidFrameAddress {
+                            offset: r.start,
+                            far: addr,
+                        });
+                    }
+                    ebr_addr = Some((ebr, word));
+                }
+                LSC_EBR_WRITE => {
+                    let params = r.params()?;
+                    let Some((ebr, mut word)) = ebr_addr else {
+                        return Err(r.malformed("EBR write without address"));
+                    };
+                    let num = usize::from(u16::from_be_bytes([params[1], params[2]]));
+                    if word + num * EBR_FRAME_WORDS > geom.ebr_words {
+                        return Err(r.malformed("EBR write past the end of the block"));
+                    }
+                    for _ in 0..num {
+                        let fdata = r.bytes(EBR_FRAME_BYTES)?;
+                        let mut val = [0; 16];
+                        val[16 - EBR_FRAME_BYTES..].copy_from_slice(fdata);
+                        let val = u128::from_be_bytes(val);
+                        for k in 0..EBR_FRAME_WORDS {
+                            let shift = (EBR_FRAME_WORDS - 1 - k) * EBR_WORD_BITS;
+                            let wdata = &mut res.ebr_mut(ebr)
+                                [word * EBR_WORD_BITS..(word + 1) * EBR_WORD_BITS];
+                            for b in 0..EBR_WORD_BITS {
+                                wdata.set(b, ((val >> (shift + b)) & 1) != 0);
+                            }
+                            word += 1;
+                        }
+                    }
+                    res.ebr_present.set(ebr, true);
+                    ebr_addr = Some((ebr, word));
+                    if (params[0] & FLAG_CRC) != 0 {
+                        r.check_crc()?;
+                    }
+                }
+                ISC_PROGRAM_DONE => {
+                    r.params()?;
+                    break;
+                }
+                _ => {
+                    return Err(BitstreamError::UnknownCommand {
+                        offset: r.start,
+                        opcode: opcode.into(),
+                        payload: None,
+                    });
+                }
+            }
+        }
+        if !res.frame_present.all() {
+            return Err(r.malformed("not all frames written"));
+        }
+        Ok(res)
+    }
+
+    pub fn emit(&self, geom: &BitstreamGeom) -> Result<Vec<u8>, BitstreamError> {
+        let mismatch = |what: &str, have: usize, expected: usize| {
+            if have == expected {
+                Ok(())
+            } else {
+                Err(BitstreamError::Malformed {
+                    offset: 0,
+                    reason: format!("bitstream has {have} {what}, geometry has {expected}"),
+                })
+            }
+        };
+        mismatch("bits per frame", self.frame_len, geom.frame_len)?;
+        mismatch("frames", self.frame_present.len(), geom.frames_num)?;
+        mismatch("words per EBR", self.ebr_words, geom.ebr_words)?;
+        mismatch("EBRs", self.ebr_present.len(), geom.ebr_num)?;
+        let Ok(num) = u16::try_from(geom.frames_num) else {
+            return Err(BitstreamError::Unsupported {
+                offset: 0,
+                reason: format!("{num} frames in one write", num = geom.frames_num),
+            });
+        };
+        if !geom.ebr_words.is_multiple_of(EBR_FRAME_WORDS) {
+            return Err(BitstreamError::Unsupported {
+                offset: 0,
+                reason: format!("EBRs of {num} words", num = geom.ebr_words),
+            });
+        }
+        let mut w = Writer {
+            data: vec![0xff; 16],
+            crc: Crc::new(),
+        };
+        w.data.extend(PREAMBLE);
+        w.cmd(LSC_RESET_CRC, [0; 3]);
+        w.crc = Crc::new();
+        if let Some(idcode) = self.idcode {
+            w.cmd(VERIFY_ID, [0; 3]);
+            w.bytes(&idcode.to_be_bytes());
+        }
+        if let Some(ctrl0) = self.ctrl0 {
+            w.cmd(LSC_PROG_CNTRL0, [0; 3]);
+            w.bytes(&ctrl0.to_be_bytes());
+        }
+        w.cmd(LSC_INIT_ADDRESS, [0; 3]);
+        let [nh, nl] = num.to_be_bytes();
+        w.cmd(LSC_PROG_INCR_RTI, [FLAG_CRC | 0x11, nh, nl]);
+        let bytes_per_frame = geom.frame_len.div_ceil(8);
+        for i in 0..geom.frames_num {
+            let frame = self.frame(i);
+            let mut fdata = vec![0; bytes_per_frame];
+            for j in 0..geom.frame_len {
+                if frame[j] {
+                    fdata[bytes_per_frame - 1 - j / 8] |= 1 << (j % 8);
+                }
+            }
+            w.bytes(&fdata);
+            w.crc();
+            w.bytes(&[0xff]);
+        }
+        if let Some(sed_crc) = self.sed_crc {
+            w.cmd(LSC_PROG_SED_CRC, [0; 3]);
+            w.bytes(&sed_crc.to_be_bytes());
+        }
+        if let Some(usercode) = self.usercode {
+            w.cmd(ISC_PROGRAM_USERCODE, [FLAG_CRC, 0, 0]);
+            w.bytes(&usercode.to_be_bytes());
+            w.crc();
+        }
+        for ebr in self.ebr_present.iter_ones() {
+            let addr = (ebr as u32) << EBR_ADDR_SHIFT;
+            w.cmd(LSC_EBR_ADDRESS, [0; 3]);
+            w.bytes(&addr.to_be_bytes());
+            let num = geom.ebr_words / EBR_FRAME_WORDS;
+            let [nh, nl] = (num as u16).to_be_bytes();
+            w.cmd(LSC_EBR_WRITE, [FLAG_CRC | 0x10, nh, nl]);
+            let data = self.ebr(ebr);
+            for i in 0..num {
+                let mut val = 0u128;
+                for k in 0..EBR_FRAME_WORDS {
+                    let word = i * EBR_FRAME_WORDS + k;
+                    let shift = (EBR_FRAME_WORDS - 1 - k) * EBR_WORD_BITS;
+                    for b in 0..EBR_WORD_BITS {
+                        if data[word * EBR_WORD_BITS + b] {
+                            val |= 1 << (shift + b);
+                        }
+                    }
+                }
+                w.bytes(&val.to_be_bytes()[16 - EBR_FRAME_BYTES..]);
+            }
+            w.crc();
+        }
+        if self.security {
+            w.cmd(ISC_PROGRAM_SECURITY, [0; 3]);
+        }
+        w.cmd(ISC_PROGRAM_DONE, [0; 3]);
+        w.data.extend([0xff; 4]);
+        Ok(w.data)
+    }
+}
@@ -2,12 +2,18 @@ use std::collections::BTreeMap;
 
 use bincode::{Decode, Encode};
 use itertools::Itertools;
+use prjcombine_entity::EntityId;
 use prjcombine_interconnect::{
     dir::{DirH, DirHV, DirV},
     grid::{ColId, EdgeIoCoord},
 };
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_num, split_assign},
+};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -69,7 +75,45 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "M2" => CfgPad::M2,
+            "M3" => CfgPad::M3,
+            "PROG_B" => CfgPad::ProgB,
+            "INIT_B" => CfgPad::InitB,
+            "RESET_B" => CfgPad::ResetB,
+            "RDCFG_B" => CfgPad::RdCfgB,
+            "MPIIRQ_B" => CfgPad::MpiIrqB,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            "SLEEP_B" => CfgPad::SleepB,
+            "TOE" => CfgPad::Toe,
+            "HFP" => CfgPad::Hfp,
+            "WRITE_N" => CfgPad::WriteN,
+            "CS_N" => CfgPad::CsN,
+            "CS1_N" => CfgPad::Cs1N,
+            "DOUT" => CfgPad::Dout,
+            "DI" => CfgPad::Di,
+            "BUSY" => CfgPad::Busy,
+            _ => match s.strip_prefix('D') {
+                Some(idx) => CfgPad::D(parse_num(idx)?),
+                None => return Err(format!("unknown config pad {s}")),
+            },
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PllSet {
     All,
     Side(DirH),
@@ -86,7 +130,22 @@ impl std::fmt::Display for PllSet {
     }
 }
 
+impl std::str::FromStr for PllSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "ALL" {
+            Ok(PllSet::All)
+        } else if let Ok(side) = s.parse() {
+            Ok(PllSet::Side(side))
+        } else {
+            Ok(PllSet::Quad(s.parse()?))
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SerdesPad {
     InP(u8),
     InN(u8),
@@ -147,7 +206,53 @@ impl std::fmt::Display for SerdesPad {
     }
 }
 
+impl std::str::FromStr for SerdesPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLK_P" => SerdesPad::ClkP,
+            "CLK_N" => SerdesPad::ClkN,
+            "RXCLK_P" => SerdesPad::RxClkP,
+            "RXCLK_N" => SerdesPad::RxClkN,
+            "VCCP" => SerdesPad::VccP,
+            "VCCAUX25" => SerdesPad::VccAux25,
+            "VCCAUX33" => SerdesPad::VccAux33,
+            "RXANTOUTP" => SerdesPad::RxantOutP,
+            "RXANTOUTN" => SerdesPad::RxantOutN,
+            "AUXTSTPADOUTP" => SerdesPad::AuxTstPadOutP,
+            "AUXTSTPADOUTN" => SerdesPad::AuxTstPadOutN,
+            "VCCA" => SerdesPad::VccA,
+            "VCCAUXA" => SerdesPad::VccAuxA,
+            "VCCTX_COMMON" => SerdesPad::VccTxCommon,
+            "RESP" => SerdesPad::ResP,
+            "RESPN" => SerdesPad::ResPN,
+            _ => {
+                let err = || format!("unknown SERDES pad {s}");
+                let (ch, pad) = s
+                    .strip_prefix("CH")
+                    .and_then(|s| s.split_once('_'))
+                    .ok_or_else(err)?;
+                let ch = parse_num(ch)?;
+                match pad {
+                    "IN_P" => SerdesPad::InP(ch),
+                    "IN_N" => SerdesPad::InN(ch),
+                    "OUT_P" => SerdesPad::OutP(ch),
+                    "OUT_N" => SerdesPad::OutN(ch),
+                    "VCCTX" => SerdesPad::VccTx(ch),
+                    "VCCRX" => SerdesPad::VccRx(ch),
+                    "VCCIB" => SerdesPad::VccIB(ch),
+                    "VCCOB" => SerdesPad::VccOB(ch),
+                    _ if pad == format!("CH{ch1}_VCCRX", ch1 = ch + 1) => SerdesPad::VccRxPair(ch),
+                    _ => return Err(err()),
+                }
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MipiPad {
     DataP(u8),
     DataN(u8),
@@ -178,7 +283,36 @@ impl std::fmt::Display for MipiPad {
     }
 }
 
+impl std::str::FromStr for MipiPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLK_P" => MipiPad::ClkP,
+            "CLK_N" => MipiPad::ClkN,
+            "GND" => MipiPad::Gnd,
+            "VCC" => MipiPad::Vcc,
+            "GNDPLL" => MipiPad::GndPll,
+            "VCCPLL" => MipiPad::VccPll,
+            "GNDMU" => MipiPad::GndMu,
+            "VCCMU" => MipiPad::VccMu,
+            _ => {
+                for (prefix, pad) in [
+                    ("DATA_P", MipiPad::DataP as fn(u8) -> MipiPad),
+                    ("DATA_N", MipiPad::DataN),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown MIPI pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AscPad {
     HvOut(u8),
     HviMonP,
@@ -239,7 +373,53 @@ impl std::fmt::Display for AscPad {
     }
 }
 
+impl std::str::FromStr for AscPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "HVIMONP" => AscPad::HviMonP,
+            "HIMONN" => AscPad::HiMonN,
+            "LDRV" => AscPad::Ldrv,
+            "HDRV" => AscPad::Hdrv,
+            "RDAT" => AscPad::RDat,
+            "WDATA" => AscPad::WDat,
+            "WRCLK" => AscPad::WrClk,
+            "ASCCLK" => AscPad::AscClk,
+            "SCL" => AscPad::Scl,
+            "SDA" => AscPad::Sda,
+            "I2C_ADDR" => AscPad::I2cAddr,
+            "RESET_B" => AscPad::ResetB,
+            "VSSA" => AscPad::VssA,
+            "VDDA" => AscPad::VddA,
+            "VDDD" => AscPad::VddD,
+            "VDC" => AscPad::Vdc,
+            _ => {
+                for (prefix, suffix, pad) in [
+                    ("HVOUT", "", AscPad::HvOut as fn(u8) -> AscPad),
+                    ("IMON", "P", AscPad::IMonP),
+                    ("IMON", "N", AscPad::IMonN),
+                    ("TMON", "P", AscPad::TMonP),
+                    ("TMON", "N", AscPad::TMonN),
+                    ("VMON", "GS", AscPad::VMonGs),
+                    ("VMON", "", AscPad::VMon),
+                    ("TRIM", "", AscPad::Trim),
+                    ("GPIO", "", AscPad::Gpio),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix).and_then(|s| s.strip_suffix(suffix))
+                        && let Ok(idx) = parse_num(idx)
+                    {
+                        return Ok(pad(idx));
+                    }
+                }
+                return Err(format!("unknown ASC pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PfrPad {
     Io(EdgeIoCoord),
     JtagEn,
@@ -278,7 +458,37 @@ impl std::fmt::Display for PfrPad {
     }
 }
 
+impl std::str::FromStr for PfrPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "JTAG_EN" => PfrPad::JtagEn,
+            "ADC_REFP0" => PfrPad::AdcRefP0,
+            "ADC_REFP1" => PfrPad::AdcRefP1,
+            "ADC_DP0" => PfrPad::AdcDp0,
+            "ADC_DP1" => PfrPad::AdcDp1,
+            "VCCINT" => PfrPad::VccInt,
+            "VCCAUX" => PfrPad::VccAux,
+            "VCCAUXA" => PfrPad::VccAuxA,
+            "VCCECLK" => PfrPad::VccEclk,
+            "VCCADC18" => PfrPad::VccAdc18,
+            "VSSADC" => PfrPad::VssAdc,
+            _ => {
+                if let Some(bank) = s.strip_prefix("VCCIO") {
+                    PfrPad::VccIo(parse_num(bank)?)
+                } else if let Some(bank) = s.strip_prefix("VCCAUXH") {
+                    PfrPad::VccAuxH(parse_num(bank)?)
+                } else {
+                    PfrPad::Io(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Io(EdgeIoCoord),
     Serdes(DirV, ColId, SerdesPad),
@@ -353,7 +563,90 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "GNDA" => BondPad::GndA,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "VCCAUXA" => BondPad::VccAuxA,
+            "VCC_JTAG" => BondPad::VccJtag,
+            "VCC12" => BondPad::Vcc12,
+            "VPP" => BondPad::Vpp,
+            "VCCA" => BondPad::VccA,
+            "XRES" => BondPad::XRes,
+            "OTHER" => BondPad::Other,
+            "TEMP_VSS" => BondPad::TempVss,
+            "TEMP_SENSE" => BondPad::TempSense,
+            "PROBE_VCC" => BondPad::ProbeVcc,
+            "PROBE_GND" => BondPad::ProbeGnd,
+            _ => {
+                if s.starts_with("IOB_") {
+                    // The IO coordinate itself is made of three parts.
+                    let (io, rest) = match s.match_indices('_').nth(2) {
+                        Some((pos, _)) => (&s[..pos], Some(&s[pos + 1..])),
+                        None => (s, None),
+                    };
+                    let io = io.parse()?;
+                    match rest {
+                        None => BondPad::Io(io),
+                        Some("CDONE") => BondPad::IoCdone(io),
+                        Some(rest) => {
+                            if let Some(pad) = rest.strip_prefix("ASC_") {
+                                BondPad::IoAsc(io, pad.parse()?)
+                            } else if let Some(pad) = rest.strip_prefix("PFR_") {
+                                BondPad::IoPfr(io, pad.parse()?)
+                            } else {
+                                return Err(format!("unknown bond pad {s}"));
+                            }
+                        }
+                    }
+                } else if let Some(pad) = s.strip_prefix("SERDES_CORNER_") {
+                    BondPad::SerdesCorner(pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("SERDES_") {
+                    let err = || format!("unknown bond pad {s}");
+                    let (pos, pad) = rest.split_once('_').ok_or_else(err)?;
+                    let (edge, col) = pos.split_at_checked(1).ok_or_else(err)?;
+                    BondPad::Serdes(
+                        edge.parse()?,
+                        ColId::from_idx(parse_num(col)?),
+                        pad.parse()?,
+                    )
+                } else if let Some(pad) = s.strip_prefix("MIPI_COMMON_") {
+                    BondPad::MipiCommon(pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("MIPI_N") {
+                    let (col, pad) = rest
+                        .split_once('_')
+                        .ok_or_else(|| format!("unknown bond pad {s}"))?;
+                    BondPad::Mipi(ColId::from_idx(parse_num(col)?), pad.parse()?)
+                } else if let Some(pad) = s.strip_prefix("ASC_") {
+                    BondPad::Asc(pad.parse()?)
+                } else if let Some(pad) = s.strip_prefix("PFR_") {
+                    BondPad::Pfr(pad.parse()?)
+                } else if let Some(set) = s.strip_prefix("VCCPLL_") {
+                    BondPad::VccPll(set.parse()?)
+                } else if let Some(set) = s.strip_prefix("GNDPLL_") {
+                    BondPad::GndPll(set.parse()?)
+                } else if let Some(set) = s.strip_prefix("PLLCAP_") {
+                    BondPad::PllCap(set.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCIO") {
+                    BondPad::VccIo(parse_num(bank)?)
+                } else if let Some(bank) = s.strip_prefix("VTT") {
+                    BondPad::Vtt(parse_num(bank)?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondKind {
     Single,
     Asc,
@@ -370,11 +663,26 @@ impl std::fmt::Display for BondKind {
     }
 }
 
+impl std::str::FromStr for BondKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "single" => BondKind::Single,
+            "ASC" => BondKind::Asc,
+            "Mach-NX" => BondKind::MachNx,
+            _ => return Err(format!("unknown bond kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub kind: BondKind,
     pub pins: BTreeMap<String, BondPad>,
     // MachNX: XO5 IO -> XO3 IO
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub pfr_io: BTreeMap<PfrPad, EdgeIoCoord>,
 }
 
@@ -399,4 +707,44 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name, plus the PFR IO mapping.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::leaf(self.kind);
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        for (pfr, io) in &self.pfr_io {
+            res.insert_leaf(format!("pfr {pfr}"), io);
+        }
+        res
+    }
+
+    /// Parses the body of a `bond` block written by [`Bond::dump`].
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut pins = BTreeMap::new();
+        let mut pfr_io = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "pin" => {
+                    let (pin, pad) = line.check(split_assign(val))?;
+                    pins.insert(pin.to_string(), line.check(pad.parse())?);
+                }
+                "pfr" => {
+                    let (pfr, io) = line.check(split_assign(val))?;
+                    pfr_io.insert(line.check(pfr.parse())?, line.check(io.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(Bond {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            pins,
+            pfr_io,
+        })
+    }
 }
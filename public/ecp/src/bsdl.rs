@@ -0,0 +1,85 @@
+//! BSDL file generation.
+
+use std::collections::BTreeMap;
+
+use prjcombine_types::bsdl::{Bsdl, InstructionRegister, PortKind};
+
+use crate::{
+    bond::{Bond, BondPad, CfgPad},
+    chip::{Chip, ChipKind, SpecialIoKey},
+};
+
+impl Chip {
+    /// Panics on CrossLink, which has no JTAG port.
+    pub fn make_bsdl(&self, bond: &Bond, entity: &str, package: &str, idcode: Option<u32>) -> Bsdl {
+        assert_ne!(
+            self.kind,
+            ChipKind::Crosslink,
+            "no boundary scan on CrossLink"
+        );
+        let bscan = self.get_bscan();
+        let mut res = Bsdl::new(entity, package, 8, bscan.bits);
+        // on MachXO2 the TAP pins are shared with IOBs
+        let mut tap = BTreeMap::new();
+        for (key, kind) in [
+            (SpecialIoKey::Tck, PortKind::In),
+            (SpecialIoKey::Tms, PortKind::In),
+            (SpecialIoKey::Tdi, PortKind::In),
+            (SpecialIoKey::Tdo, PortKind::Out),
+        ] {
+            if let Some(&crd) = self.special_io.get(&key) {
+                tap.insert(BondPad::Io(crd), (key, kind));
+            }
+        }
+        for (pin, &pad) in &bond.pins {
+            if let Some(&(key, kind)) = tap.get(&pad) {
+                res.add_pin(&key.to_string(), kind, pin);
+                continue;
+            }
+            let port = match pad {
+                BondPad::Io(_) | BondPad::IoAsc(..) | BondPad::IoPfr(..) | BondPad::IoCdone(_) => {
+                    format!("IO_{pin}")
+                }
+                _ => pad.to_string(),
+            };
+            match pad {
+                BondPad::Cfg(CfgPad::Tck | CfgPad::Tms | CfgPad::Tdi) => {
+                    res.add_pin(&port, PortKind::In, pin);
+                }
+                BondPad::Cfg(CfgPad::Tdo) => {
+                    res.add_pin(&port, PortKind::Out, pin);
+                }
+                BondPad::Nc => (),
+                _ => {
+                    if let Some(&bpad) = bscan.pads.get(&pad) {
+                        res.add_pad(&port, pin, bpad);
+                    } else {
+                        res.add_pin(&port, PortKind::Linkage, pin);
+                    }
+                }
+            }
+        }
+        // the newer families moved IDCODE into the configuration instruction space
+        let op_idcode = match self.kind {
+            ChipKind::Scm
+            | ChipKind::Ecp
+            | ChipKind::Xp
+            | ChipKind::MachXo
+            | ChipKind::Ecp2
+            | ChipKind::Ecp2M
+            | ChipKind::Xp2 => 0x16,
+            _ => 0xe0,
+        };
+        for (name, opcode, register) in [
+            ("EXTEST", 0x15, InstructionRegister::Standard),
+            ("SAMPLE", 0x1c, InstructionRegister::Standard),
+            ("HIGHZ", 0x18, InstructionRegister::Standard),
+            ("IDCODE", op_idcode, InstructionRegister::Standard),
+            ("BYPASS", 0xff, InstructionRegister::Standard),
+        ] {
+            res.add_instruction(name, opcode, register);
+        }
+        res.idcode = idcode.map(|idcode| (idcode, 0x0fff_ffff));
+        res
+    }
+}
@@ -1,15 +1,22 @@
 use std::{collections::BTreeMap, fmt::Display};
 
 use bincode::{Decode, Encode};
-use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_entity::{EntityId, EntityVec, id::EntityTag};
 use prjcombine_interconnect::{
     dir::{Dir, DirH, DirHV, DirV},
-    grid::{BelCoord, CellCoord, ColId, DieId, DieIdExt, EdgeIoCoord, RowId, TileIobId},
+    grid::{
+        BelCoord, CellCoord, ColId, ColTag, DieId, DieIdExt, EdgeIoCoord, RowId, RowTag, TileIobId,
+    },
+};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign},
 };
 
 use crate::bels;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Scm,
     Ecp,
@@ -27,6 +34,7 @@ pub enum ChipKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MachXo2Kind {
     MachXo2,
     MachXo3L,
@@ -122,15 +130,46 @@ impl Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "scm" => ChipKind::Scm,
+            "ecp" => ChipKind::Ecp,
+            "xp" => ChipKind::Xp,
+            "machxo" => ChipKind::MachXo,
+            "ecp2" => ChipKind::Ecp2,
+            "ecp2m" => ChipKind::Ecp2M,
+            "xp2" => ChipKind::Xp2,
+            "ecp3" => ChipKind::Ecp3,
+            "ecp3a" => ChipKind::Ecp3A,
+            "machxo2" => ChipKind::MachXo2(MachXo2Kind::MachXo2),
+            "machxo3l" => ChipKind::MachXo2(MachXo2Kind::MachXo3L),
+            "machxo3lfp" => ChipKind::MachXo2(MachXo2Kind::MachXo3Lfp),
+            "machxo3d" => ChipKind::MachXo2(MachXo2Kind::MachXo3D),
+            "machnx" => ChipKind::MachXo2(MachXo2Kind::MachNx),
+            "ecp4" => ChipKind::Ecp4,
+            "ecp5" => ChipKind::Ecp5,
+            "crosslink" => ChipKind::Crosslink,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: EntityVec<ColId, Column>,
     pub rows: EntityVec<RowId, Row>,
     pub col_clk: ColId,
     pub row_clk: RowId,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub special_loc: BTreeMap<SpecialLocKey, CellCoord>,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub special_io: BTreeMap<SpecialIoKey, EdgeIoCoord>,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub io_direct_plc: BTreeMap<EdgeIoCoord, (CellCoord, u8)>,
     pub extra_frames_w: usize,
     pub extra_frames_e: usize,
@@ -138,6 +177,7 @@ pub struct Chip {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RowKind {
     Plc,
     Fplc,
@@ -158,7 +198,23 @@ impl Display for RowKind {
     }
 }
 
+impl std::str::FromStr for RowKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "plc" => RowKind::Plc,
+            "fplc" => RowKind::Fplc,
+            "io" => RowKind::Io,
+            "ebr" => RowKind::Ebr,
+            "dsp" => RowKind::Dsp,
+            _ => return Err(format!("unknown row kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoGroupKind {
     None,
     Single,
@@ -211,7 +267,39 @@ impl Display for IoGroupKind {
     }
 }
 
+impl std::str::FromStr for IoGroupKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => IoGroupKind::None,
+            "single" => IoGroupKind::Single,
+            "double" => IoGroupKind::Double,
+            "double_a" => IoGroupKind::DoubleA,
+            "double_b" => IoGroupKind::DoubleB,
+            "double_dummy" => IoGroupKind::DoubleDummy,
+            "double_dqs" => IoGroupKind::DoubleDqs,
+            "quad" => IoGroupKind::Quad,
+            "quad_reverse" => IoGroupKind::QuadReverse,
+            "quad_dqs" => IoGroupKind::QuadDqs,
+            "quad_dqs_dummy" => IoGroupKind::QuadDqsDummy,
+            "quad_ebr_dqs" => IoGroupKind::QuadEbrDqs,
+            "ebr_dqs" => IoGroupKind::EbrDqs,
+            "quad_i3c" => IoGroupKind::QuadI3c,
+            "hex" => IoGroupKind::Hex,
+            "hex_reverse" => IoGroupKind::HexReverse,
+            "octal" => IoGroupKind::Octal,
+            "dozen" => IoGroupKind::Dozen,
+            "serdes" => IoGroupKind::Serdes,
+            "ebr" => IoGroupKind::Ebr,
+            "mipi" => IoGroupKind::Mipi,
+            _ => return Err(format!("unknown IO group kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoKind {
     Dummy,
     Io,
@@ -223,6 +311,7 @@ pub enum IoKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     pub io_s: IoGroupKind,
     pub io_n: IoGroupKind,
@@ -236,6 +325,7 @@ pub struct Column {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Row {
     pub kind: RowKind,
     pub io_w: IoGroupKind,
@@ -248,6 +338,7 @@ pub struct Row {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PllLoc {
     pub quad: DirHV,
     pub idx: u8,
@@ -272,7 +363,19 @@ impl Display for PllLoc {
     }
 }
 
+impl std::str::FromStr for PllLoc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((quad, idx)) = s.split_at_checked(2) else {
+            return Err(format!("invalid PLL location {s}"));
+        };
+        Ok(PllLoc::new(quad.parse()?, parse_num(idx)?))
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PllPad {
     PllIn0,
     PllIn1,
@@ -299,7 +402,34 @@ impl Display for PllPad {
     }
 }
 
+impl std::str::FromStr for PllPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "PLL_IN0" => PllPad::PllIn0,
+            "PLL_IN1" => PllPad::PllIn1,
+            "PLL_FB" => PllPad::PllFb,
+            "DLL_IN0" => PllPad::DllIn0,
+            "DLL_IN1" => PllPad::DllIn1,
+            "DLL_IN2" => PllPad::DllIn2,
+            "DLL_IN3" => PllPad::DllIn3,
+            "DLL_FB" => PllPad::DllFb,
+            _ => return Err(format!("unknown PLL pad {s}")),
+        })
+    }
+}
+
+/// Parses a direction followed by an index, such as `W0`.
+fn parse_dir_idx(s: &str) -> Result<(Dir, u8), String> {
+    let Some((dir, idx)) = s.split_at_checked(1) else {
+        return Err(format!("invalid direction and index {s}"));
+    };
+    Ok((dir.parse()?, parse_num(idx)?))
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialIoKey {
     Clock(Dir, u8),
     Pll(PllPad, PllLoc),
@@ -406,7 +536,83 @@ impl Display for SpecialIoKey {
     }
 }
 
+impl std::str::FromStr for SpecialIoKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GSR" => SpecialIoKey::Gsr,
+            "TSALL" => SpecialIoKey::TsAll,
+            "WRITE_N" => SpecialIoKey::WriteN,
+            "READ_N" => SpecialIoKey::ReadN,
+            "CS_N" => SpecialIoKey::CsN,
+            "CS1_N" => SpecialIoKey::Cs1N,
+            "CS1" => SpecialIoKey::Cs1,
+            "DOUT" => SpecialIoKey::Dout,
+            "QOUT" => SpecialIoKey::Qout,
+            "DI" => SpecialIoKey::Di,
+            "BUSY" => SpecialIoKey::Busy,
+            "MCLK" => SpecialIoKey::MClk,
+            "SLEEP_N" => SpecialIoKey::SleepN,
+            "MPI_CLK" => SpecialIoKey::MpiClk,
+            "MPI_ACK_N" => SpecialIoKey::MpiAckN,
+            "MPI_RETRY_N" => SpecialIoKey::MpiRetryN,
+            "MPI_TEA_N" => SpecialIoKey::MpiTeaN,
+            "HDC" => SpecialIoKey::Hdc,
+            "LDC" => SpecialIoKey::Ldc,
+            "EXT_DONE_I" => SpecialIoKey::ExtDoneI,
+            "EXT_DONE_O" => SpecialIoKey::ExtDoneO,
+            "INIT_B" => SpecialIoKey::InitB,
+            "CCLK" => SpecialIoKey::Cclk,
+            "SPI_C_CS_B" => SpecialIoKey::SpiCCsB,
+            "SPI_P_CS_B" => SpecialIoKey::SpiPCsB,
+            "M1" => SpecialIoKey::M1,
+            "DONE" => SpecialIoKey::Done,
+            "PROG_B" => SpecialIoKey::ProgB,
+            "TCK" => SpecialIoKey::Tck,
+            "TMS" => SpecialIoKey::Tms,
+            "TDI" => SpecialIoKey::Tdi,
+            "TDO" => SpecialIoKey::Tdo,
+            "JTAG_EN" => SpecialIoKey::JtagEn,
+            "PMU_WAKEUP_N" => SpecialIoKey::PmuWakeupN,
+            _ => {
+                if let Some((pad, loc)) = s.rsplit_once('_')
+                    && let Ok(pad) = pad.parse()
+                {
+                    SpecialIoKey::Pll(pad, loc.parse()?)
+                } else if let Some(rest) = s.strip_prefix("CLOCK_") {
+                    let (dir, idx) = parse_dir_idx(rest)?;
+                    SpecialIoKey::Clock(dir, idx)
+                } else if let Some(side) = s.strip_prefix("MIPI_CLK_") {
+                    SpecialIoKey::MipiClk(side.parse()?)
+                } else if let Some(idx) = s.strip_prefix("DQS_E") {
+                    SpecialIoKey::DqsE(parse_num(idx)?)
+                } else if let Some(bank) = s.strip_prefix("VREF1_") {
+                    SpecialIoKey::Vref1(parse_num(bank)?)
+                } else if let Some(bank) = s.strip_prefix("VREF2_") {
+                    SpecialIoKey::Vref2(parse_num(bank)?)
+                } else if let Some(bank) = s.strip_prefix("DIFFR_") {
+                    SpecialIoKey::DiffR(parse_num(bank)?)
+                } else if let Some(idx) = s.strip_prefix("EXT_CLK_I") {
+                    SpecialIoKey::ExtClkI(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("EXT_CLK_O") {
+                    SpecialIoKey::ExtClkO(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("DP") {
+                    SpecialIoKey::DP(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("D") {
+                    SpecialIoKey::D(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("A") {
+                    SpecialIoKey::A(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown special IO {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialLocKey {
     Pll(PllLoc),
     Ebr(u8),
@@ -449,6 +655,47 @@ impl Display for SpecialLocKey {
     }
 }
 
+impl std::str::FromStr for SpecialLocKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CONFIG" => SpecialLocKey::Config,
+            "CONFIG_BITS" => SpecialLocKey::ConfigBits,
+            "OSC" => SpecialLocKey::Osc,
+            "SERDES_SINGLE" => SpecialLocKey::SerdesSingle,
+            "SERDES_DOUBLE" => SpecialLocKey::SerdesDouble,
+            "SERDES_TRIPLE" => SpecialLocKey::SerdesTriple,
+            "PMU" => SpecialLocKey::Pmu,
+            _ => {
+                if let Some(loc) = s.strip_prefix("PLL_") {
+                    SpecialLocKey::Pll(loc.parse()?)
+                } else if let Some(idx) = s.strip_prefix("PCLK_IN_M") {
+                    SpecialLocKey::PclkInMid(parse_num(idx)?)
+                } else if let Some(rest) = s.strip_prefix("PCLK_IN_") {
+                    let (dir, idx) = parse_dir_idx(rest)?;
+                    SpecialLocKey::PclkIn(dir, idx)
+                } else if let Some(rest) = s.strip_prefix("SCLK_IN_") {
+                    let (dir, idx) = parse_dir_idx(rest)?;
+                    SpecialLocKey::SclkIn(dir, idx)
+                } else if let Some(dir) = s.strip_prefix("DQSDLL_") {
+                    SpecialLocKey::DqsDll(dir.parse()?)
+                } else if let Some(dir) = s.strip_prefix("DDRDLL_") {
+                    SpecialLocKey::DdrDll(dir.parse()?)
+                } else if let Some(dir) = s.strip_prefix("CLK_QUARTER_") {
+                    SpecialLocKey::ClkQuarter(dir.parse()?)
+                } else if let Some(idx) = s.strip_prefix("EBR") {
+                    SpecialLocKey::Ebr(parse_num(idx)?)
+                } else if let Some(bank) = s.strip_prefix("BC") {
+                    SpecialLocKey::Bc(parse_num(bank)?)
+                } else {
+                    return Err(format!("unknown special location {s}"));
+                }
+            }
+        })
+    }
+}
+
 impl Chip {
     // always single die
     pub const DIE: DieId = DieId::from_idx_const(0);
@@ -848,79 +1095,80 @@ impl Chip {
         Self::DIE.cell(col, self.row_n()).bel(bels::MIPI)
     }
 
-    pub fn btile_term_width(&self, _col: ColId) -> usize {
+    // the btile_* sizes are None for the kinds whose frame geometry is not known yet
+    pub fn btile_term_width(&self, _col: ColId) -> Option<usize> {
         match self.kind {
-            ChipKind::Scm => todo!(),
-            ChipKind::Ecp | ChipKind::Xp => 2,
-            ChipKind::MachXo => 0,
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Scm => None,
+            ChipKind::Ecp | ChipKind::Xp => Some(2),
+            ChipKind::MachXo => Some(0),
+            ChipKind::Ecp2 => None,
+            ChipKind::Ecp2M => None,
+            ChipKind::Xp2 => None,
+            ChipKind::Ecp3 => None,
+            ChipKind::Ecp3A => None,
+            ChipKind::MachXo2(_) => None,
+            ChipKind::Ecp4 => None,
+            ChipKind::Ecp5 => None,
+            ChipKind::Crosslink => None,
         }
     }
 
-    pub fn btile_clk_width(&self) -> usize {
+    pub fn btile_clk_width(&self) -> Option<usize> {
         match self.kind {
-            ChipKind::Scm => todo!(),
-            ChipKind::Ecp | ChipKind::Xp => 6,
-            ChipKind::MachXo => 1,
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Scm => None,
+            ChipKind::Ecp | ChipKind::Xp => Some(6),
+            ChipKind::MachXo => Some(1),
+            ChipKind::Ecp2 => None,
+            ChipKind::Ecp2M => None,
+            ChipKind::Xp2 => None,
+            ChipKind::Ecp3 => None,
+            ChipKind::Ecp3A => None,
+            ChipKind::MachXo2(_) => None,
+            ChipKind::Ecp4 => None,
+            ChipKind::Ecp5 => None,
+            ChipKind::Crosslink => None,
         }
     }
 
-    pub fn btile_width(&self, col: ColId) -> usize {
+    pub fn btile_width(&self, col: ColId) -> Option<usize> {
         match self.kind {
-            ChipKind::Scm => todo!(),
-            ChipKind::Ecp | ChipKind::Xp => 64,
+            ChipKind::Scm => None,
+            ChipKind::Ecp | ChipKind::Xp => Some(64),
             ChipKind::MachXo => {
                 let has_ebr = self.special_loc.contains_key(&SpecialLocKey::Ebr(0));
-                if col == self.col_w() {
+                Some(if col == self.col_w() {
                     if has_ebr { 135 } else { 19 }
                 } else if col == self.col_e() {
                     if has_ebr { 20 } else { 19 }
                 } else {
                     64
-                }
+                })
             }
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Ecp2 => None,
+            ChipKind::Ecp2M => None,
+            ChipKind::Xp2 => None,
+            ChipKind::Ecp3 => None,
+            ChipKind::Ecp3A => None,
+            ChipKind::MachXo2(_) => None,
+            ChipKind::Ecp4 => None,
+            ChipKind::Ecp5 => None,
+            ChipKind::Crosslink => None,
         }
     }
 
-    pub fn btile_height(&self, row: RowId) -> usize {
+    pub fn btile_height(&self, row: RowId) -> Option<usize> {
         let rd = &self.rows[row];
         match self.kind {
-            ChipKind::Scm => todo!(),
-            ChipKind::Ecp | ChipKind::Xp => match rd.kind {
+            ChipKind::Scm => None,
+            ChipKind::Ecp | ChipKind::Xp => Some(match rd.kind {
                 RowKind::Plc | RowKind::Fplc => 22,
                 RowKind::Io => 21,
                 RowKind::Dsp => 20,
                 RowKind::Ebr => 20,
-            },
+            }),
             ChipKind::MachXo => {
                 let has_ebr = self.special_loc.contains_key(&SpecialLocKey::Ebr(0));
-                match rd.kind {
+                Some(match rd.kind {
                     RowKind::Plc | RowKind::Fplc => 22,
                     RowKind::Io => {
                         if has_ebr {
@@ -930,22 +1178,85 @@ impl Chip {
                         }
                     }
                     _ => unreachable!(),
-                }
+                })
             }
-            ChipKind::Ecp2 => todo!(),
-            ChipKind::Ecp2M => todo!(),
-            ChipKind::Xp2 => todo!(),
-            ChipKind::Ecp3 => todo!(),
-            ChipKind::Ecp3A => todo!(),
-            ChipKind::MachXo2(_) => todo!(),
-            ChipKind::Ecp4 => todo!(),
-            ChipKind::Ecp5 => todo!(),
-            ChipKind::Crosslink => todo!(),
+            ChipKind::Ecp2 => None,
+            ChipKind::Ecp2M => None,
+            ChipKind::Xp2 => None,
+            ChipKind::Ecp3 => None,
+            ChipKind::Ecp3A => None,
+            ChipKind::MachXo2(_) => None,
+            ChipKind::Ecp4 => None,
+            ChipKind::Ecp5 => None,
+            ChipKind::Crosslink => None,
         }
     }
 }
 
 impl Chip {
+    fn dump_column(cd: &Column) -> String {
+        let mut parts = vec![];
+        if cd.io_s != IoGroupKind::None || cd.bank_s.is_some() {
+            parts.push(format!(
+                "io_s[{bank_s}, {io_s}]",
+                bank_s = cd.bank_s.unwrap(),
+                io_s = cd.io_s
+            ));
+        }
+        if cd.io_n != IoGroupKind::None || cd.bank_n.is_some() {
+            parts.push(format!(
+                "io_n[{bank_n}, {io_n}]",
+                bank_n = cd.bank_n.unwrap(),
+                io_n = cd.io_n
+            ));
+        }
+        for (flag, name) in [
+            (cd.eclk_tap_s, "eclk_tap_s"),
+            (cd.eclk_tap_n, "eclk_tap_n"),
+            (cd.pclk_drive, "pclk_drive"),
+            (cd.pclk_break, "pclk_break"),
+            (cd.sdclk_break, "sdclk_break"),
+        ] {
+            if flag {
+                parts.push(name.to_string());
+            }
+        }
+        if parts.is_empty() {
+            "null".to_string()
+        } else {
+            parts.join(" + ")
+        }
+    }
+
+    fn dump_row(rd: &Row) -> String {
+        let mut res = rd.kind.to_string();
+        if rd.io_w != IoGroupKind::None || rd.bank_w.is_some() {
+            res += &format!(
+                " + io_w[{bank_w}, {io_w}]",
+                bank_w = rd.bank_w.unwrap(),
+                io_w = rd.io_w
+            );
+        }
+        if rd.io_e != IoGroupKind::None || rd.bank_e.is_some() {
+            res += &format!(
+                " + io_e[{bank_e}, {io_e}]",
+                bank_e = rd.bank_e.unwrap(),
+                io_e = rd.io_e
+            );
+        }
+        for (flag, name) in [
+            (rd.pclk_drive, "pclk_drive"),
+            (rd.pclk_break, "pclk_break"),
+            (rd.sclk_break, "sclk_break"),
+        ] {
+            if flag {
+                res += " + ";
+                res += name;
+            }
+        }
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         writeln!(o, "\tkind {};", self.kind)?;
         writeln!(o, "\tcolumns {{")?;
@@ -953,73 +1264,7 @@ impl Chip {
             if self.col_clk == col {
                 writeln!(o, "\t\t// clock")?;
             }
-            write!(o, "\t\t")?;
-            let mut first = true;
-            if cd.io_s != IoGroupKind::None {
-                first = false;
-                write!(
-                    o,
-                    "io_s[{bank_s}, {io_s}]",
-                    bank_s = cd.bank_s.unwrap(),
-                    io_s = cd.io_s
-                )?;
-            }
-            if cd.io_n != IoGroupKind::None {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(
-                    o,
-                    "io_n[{bank_n}, {io_n}]",
-                    bank_n = cd.bank_n.unwrap(),
-                    io_n = cd.io_n
-                )?;
-            }
-            if cd.eclk_tap_s {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(o, "eclk_tap_s")?;
-            }
-            if cd.eclk_tap_n {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(o, "eclk_tap_n")?;
-            }
-            if cd.pclk_drive {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(o, "pclk_drive")?;
-            }
-            if cd.pclk_break {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(o, "pclk_break")?;
-            }
-            if cd.sdclk_break {
-                if first {
-                    first = false;
-                } else {
-                    write!(o, " + ")?;
-                }
-                write!(o, "sdclk_break")?;
-            }
-            if first {
-                write!(o, "null")?;
-            }
+            write!(o, "\t\t{cd}", cd = Self::dump_column(cd))?;
             writeln!(o, ", // {col}")?;
         }
         writeln!(o, "\t}}")?;
@@ -1029,36 +1274,20 @@ impl Chip {
             if self.row_clk == row {
                 writeln!(o, "\t\t// clock")?;
             }
-            write!(o, "\t\t{kind}", kind = rd.kind)?;
-            if rd.io_w != IoGroupKind::None {
-                write!(
-                    o,
-                    " + io_w[{bank_w}, {io_w}]",
-                    bank_w = rd.bank_w.unwrap(),
-                    io_w = rd.io_w
-                )?;
-            }
-            if rd.io_e != IoGroupKind::None {
-                write!(
-                    o,
-                    " + io_e[{bank_e}, {io_e}]",
-                    bank_e = rd.bank_e.unwrap(),
-                    io_e = rd.io_e
-                )?;
-            }
-            if rd.pclk_drive {
-                write!(o, " + pclk_drive")?;
-            }
-            if rd.pclk_break {
-                write!(o, " + pclk_break")?;
-            }
-            if rd.sclk_break {
-                write!(o, " + sclk_break")?;
-            }
+            write!(o, "\t\t{rd}", rd = Self::dump_row(rd))?;
             writeln!(o, ", // {row}")?;
         }
         writeln!(o, "\t}}")?;
         writeln!(o, "\trow_clk {};", self.row_clk)?;
+        if self.extra_frames_w != 0 {
+            writeln!(o, "\textra_frames_w {};", self.extra_frames_w)?;
+        }
+        if self.extra_frames_e != 0 {
+            writeln!(o, "\textra_frames_e {};", self.extra_frames_e)?;
+        }
+        if self.double_frames {
+            writeln!(o, "\tdouble_frames;")?;
+        }
 
         for (k, v) in &self.special_loc {
             writeln!(o, "\tspecial_loc {k} = {v};")?;
@@ -1071,4 +1300,187 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns, rows and
+    /// special locations keyed by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        for (col, cd) in &self.columns {
+            res.insert_leaf(format!("column {col}"), Self::dump_column(cd));
+        }
+        res.insert_leaf("col_clk", self.col_clk);
+        for (row, rd) in &self.rows {
+            res.insert_leaf(format!("row {row}"), Self::dump_row(rd));
+        }
+        res.insert_leaf("row_clk", self.row_clk);
+        res.insert_leaf("extra_frames_w", self.extra_frames_w);
+        res.insert_leaf("extra_frames_e", self.extra_frames_e);
+        res.insert_leaf("double_frames", self.double_frames);
+        for (k, v) in &self.special_loc {
+            res.insert_leaf(format!("special_loc {k}"), v);
+        }
+        for (k, v) in &self.special_io {
+            res.insert_leaf(format!("special_io {k}"), v);
+        }
+        for (k, (cell, lut)) in &self.io_direct_plc {
+            res.insert_leaf(format!("io_direct {k}"), format!("{cell}_{lut}"));
+        }
+        res
+    }
+
+    /// Parses an `io_x[bank, kind]` column or row part.
+    fn parse_io_group(s: &str) -> Result<(u32, IoGroupKind), String> {
+        let Some((bank, kind)) = s.strip_suffix(']').and_then(|s| s.split_once(", ")) else {
+            return Err(format!("invalid IO group \"{s}\""));
+        };
+        Ok((parse_num(bank)?, kind.parse()?))
+    }
+
+    fn parse_column(s: &str) -> Result<Column, String> {
+        let mut res = Column {
+            io_s: IoGroupKind::None,
+            io_n: IoGroupKind::None,
+            bank_s: None,
+            bank_n: None,
+            eclk_tap_s: false,
+            eclk_tap_n: false,
+            pclk_break: false,
+            pclk_drive: false,
+            sdclk_break: false,
+        };
+        if s == "null" {
+            return Ok(res);
+        }
+        for part in s.split(" + ") {
+            if let Some(io) = part.strip_prefix("io_s[") {
+                let (bank, kind) = Self::parse_io_group(io)?;
+                res.bank_s = Some(bank);
+                res.io_s = kind;
+            } else if let Some(io) = part.strip_prefix("io_n[") {
+                let (bank, kind) = Self::parse_io_group(io)?;
+                res.bank_n = Some(bank);
+                res.io_n = kind;
+            } else {
+                match part {
+                    "eclk_tap_s" => res.eclk_tap_s = true,
+                    "eclk_tap_n" => res.eclk_tap_n = true,
+                    "pclk_drive" => res.pclk_drive = true,
+                    "pclk_break" => res.pclk_break = true,
+                    "sdclk_break" => res.sdclk_break = true,
+                    _ => return Err(format!("unknown column part \"{part}\"")),
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    fn parse_row(s: &str) -> Result<Row, String> {
+        let mut parts = s.split(" + ");
+        let mut res = Row {
+            kind: parts.next().unwrap().parse()?,
+            io_w: IoGroupKind::None,
+            io_e: IoGroupKind::None,
+            bank_w: None,
+            bank_e: None,
+            sclk_break: false,
+            pclk_break: false,
+            pclk_drive: false,
+        };
+        for part in parts {
+            if let Some(io) = part.strip_prefix("io_w[") {
+                let (bank, kind) = Self::parse_io_group(io)?;
+                res.bank_w = Some(bank);
+                res.io_w = kind;
+            } else if let Some(io) = part.strip_prefix("io_e[") {
+                let (bank, kind) = Self::parse_io_group(io)?;
+                res.bank_e = Some(bank);
+                res.io_e = kind;
+            } else {
+                match part {
+                    "pclk_drive" => res.pclk_drive = true,
+                    "pclk_break" => res.pclk_break = true,
+                    "sclk_break" => res.sclk_break = true,
+                    _ => return Err(format!("unknown row part \"{part}\"")),
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Parses the body of a `chip` block written by [`Chip::dump`].
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = EntityVec::new();
+        let mut rows = EntityVec::new();
+        let mut col_clk = None;
+        let mut row_clk = None;
+        let mut special_loc = BTreeMap::new();
+        let mut special_io = BTreeMap::new();
+        let mut io_direct_plc = BTreeMap::new();
+        let mut extra_frames_w = 0;
+        let mut extra_frames_e = 0;
+        let mut double_frames = false;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("columns", "")) => {
+                    while !p.try_block_end() {
+                        let cline = p.expect_line()?;
+                        columns.push(cline.check(Self::parse_column(cline.list_item()?))?);
+                    }
+                }
+                Some(("rows", "")) => {
+                    while !p.try_block_end() {
+                        let rline = p.expect_line()?;
+                        rows.push(rline.check(Self::parse_row(rline.list_item()?))?);
+                    }
+                }
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "kind" => kind = Some(line.check(val.parse())?),
+                        "col_clk" => col_clk = Some(line.check(parse_id(val, ColTag::PREFIX))?),
+                        "row_clk" => row_clk = Some(line.check(parse_id(val, RowTag::PREFIX))?),
+                        "extra_frames_w" => extra_frames_w = line.check(parse_num(val))?,
+                        "extra_frames_e" => extra_frames_e = line.check(parse_num(val))?,
+                        "double_frames" => double_frames = true,
+                        "special_loc" => {
+                            let (k, v) = line.check(split_assign(val))?;
+                            special_loc.insert(line.check(k.parse())?, line.check(v.parse())?);
+                        }
+                        "special_io" => {
+                            let (k, v) = line.check(split_assign(val))?;
+                            special_io.insert(line.check(k.parse())?, line.check(v.parse())?);
+                        }
+                        "io_direct" => {
+                            let (k, v) = line.check(split_assign(val))?;
+                            let Some((cell, lut)) = v.rsplit_once('_') else {
+                                return Err(line.error(format!("invalid direct IO \"{v}\"")));
+                            };
+                            io_direct_plc.insert(
+                                line.check(k.parse())?,
+                                (line.check(cell.parse())?, line.check(parse_num(lut))?),
+                            );
+                        }
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns,
+            rows,
+            col_clk: col_clk.ok_or_else(|| p.missing("col_clk"))?,
+            row_clk: row_clk.ok_or_else(|| p.missing("row_clk"))?,
+            special_loc,
+            special_io,
+            io_direct_plc,
+            extra_frames_w,
+            extra_frames_e,
+            double_frames,
+        })
+    }
 }
@@ -3119,34 +3119,48 @@ impl Expander<'_, '_> {
         }
     }
 
-    fn fill_bs_ecp(&mut self) {
-        self.row_bit = self.chip.rows.map_values(|_| 0);
+    // leaves the frame geometry empty for the kinds whose layout is not known yet
+    fn fill_bs_ecp(&mut self) -> Option<()> {
+        let mut frame_len = 0;
+        let mut row_bit = self.chip.rows.map_values(|_| 0);
+        let mut row_ebr_bit = EntityPartVec::new();
         for row in self.chip.rows.ids().rev() {
-            self.row_bit[row] = self.frame_len;
-            self.frame_len += self.chip.btile_height(row);
+            row_bit[row] = frame_len;
+            frame_len += self.chip.btile_height(row)?;
             if self.chip.rows[row].kind == RowKind::Ebr {
-                self.row_ebr_bit.insert(row, self.frame_len);
-                self.frame_len += 144;
+                row_ebr_bit.insert(row, frame_len);
+                frame_len += 144;
             }
         }
-        self.frames_num += self.chip.extra_frames_w;
+        let mut frames_num = self.chip.extra_frames_w;
+        let mut clk_frame = 0;
+        let mut col_frame = EntityVec::new();
+        let mut col_term_frame = EntityPartVec::new();
         for col in self.chip.columns.ids() {
             if col == self.chip.col_w() {
-                self.col_term_frame.insert(col, self.frames_num);
-                self.frames_num += self.chip.btile_term_width(col);
+                col_term_frame.insert(col, frames_num);
+                frames_num += self.chip.btile_term_width(col)?;
             }
             if col == self.chip.col_clk {
-                self.clk_frame = self.frames_num;
-                self.frames_num += self.chip.btile_clk_width();
+                clk_frame = frames_num;
+                frames_num += self.chip.btile_clk_width()?;
             }
-            self.col_frame.push(self.frames_num);
-            self.frames_num += self.chip.btile_width(col);
+            col_frame.push(frames_num);
+            frames_num += self.chip.btile_width(col)?;
             if col == self.chip.col_e() {
-                self.col_term_frame.insert(col, self.frames_num);
-                self.frames_num += self.chip.btile_term_width(col);
-            }
-        }
-        self.frames_num += self.chip.extra_frames_e;
+                col_term_frame.insert(col, frames_num);
+                frames_num += self.chip.btile_term_width(col)?;
+            }
+        }
+        frames_num += self.chip.extra_frames_e;
+        self.frame_len = frame_len;
+        self.frames_num = frames_num;
+        self.clk_frame = clk_frame;
+        self.row_bit = row_bit;
+        self.row_ebr_bit = row_ebr_bit;
+        self.col_frame = col_frame;
+        self.col_term_frame = col_term_frame;
+        Some(())
     }
 }
 
@@ -3187,7 +3201,6 @@ impl Chip {
                 expander.fill_dsp_ecp();
                 expander.fill_io_ecp();
                 expander.fill_clk_ecp();
-                expander.fill_bs_ecp();
             }
             ChipKind::Xp => {
                 expander.fill_pll_xp();
@@ -3196,14 +3209,12 @@ impl Chip {
                 expander.fill_config_xp();
                 expander.fill_io_ecp();
                 expander.fill_clk_ecp();
-                expander.fill_bs_ecp();
             }
             ChipKind::MachXo => {
                 expander.fill_plc();
                 expander.fill_io_machxo();
                 expander.fill_special_machxo();
                 expander.fill_clk_machxo();
-                expander.fill_bs_ecp();
             }
             ChipKind::Ecp2 | ChipKind::Ecp2M => {
                 expander.fill_config_ecp2();
@@ -3271,6 +3282,7 @@ impl Chip {
                 expander.fill_clk_crosslink();
             }
         }
+        expander.fill_bs_ecp();
         expander.fill_conns();
 
         let bel_holes = expander.bel_holes;
@@ -3284,6 +3296,13 @@ impl Chip {
         let row_ebr_bit = expander.row_ebr_bit;
 
         let egrid = egrid.finish();
+        // numbered row by row from the top, like the frame bits
+        let mut ebrs: Vec<_> = egrid
+            .die_cells(Self::DIE)
+            .map(|cell| cell.bel(bels::EBR0))
+            .filter(|&bel| egrid.has_bel(bel))
+            .collect();
+        ebrs.sort_by_key(|bel| (std::cmp::Reverse(bel.row), bel.col));
         ExpandedDevice {
             chip: self,
             egrid,
@@ -3296,6 +3315,7 @@ impl Chip {
             col_term_frame,
             row_bit,
             row_ebr_bit,
+            ebrs,
         }
     }
 }
@@ -1,9 +1,18 @@
 use std::collections::BTreeMap;
 
 use prjcombine_entity::{EntityPartVec, EntityVec};
-use prjcombine_interconnect::grid::{CellCoord, ColId, ExpandedGrid, Rect, RowId};
+use prjcombine_interconnect::{
+    decode::{DecodedBitstream, decode_tiles},
+    encode::{EncodeError, encode_tiles},
+    grid::{BelCoord, CellCoord, ColId, ExpandedGrid, Rect, RowId, TileCoord},
+};
+use prjcombine_types::bsdata::{BitRectId, BsData};
 
-use crate::{bels, chip::Chip};
+use crate::{
+    bels,
+    bitstream::{BitRect, Bitstream, BitstreamGeom},
+    chip::Chip,
+};
 
 pub struct ExpandedDevice<'a> {
     pub chip: &'a Chip,
@@ -17,6 +26,8 @@ pub struct ExpandedDevice<'a> {
     pub col_term_frame: EntityPartVec<ColId, usize>,
     pub row_bit: EntityVec<RowId, usize>,
     pub row_ebr_bit: EntityPartVec<RowId, usize>,
+    // EBRs in bitstream index order
+    pub ebrs: Vec<BelCoord>,
 }
 
 impl ExpandedDevice<'_> {
@@ -32,6 +43,106 @@ impl ExpandedDevice<'_> {
         }
         false
     }
+
+    // None for the kinds whose frame geometry is not known yet
+    pub fn bitstream_geom(&self) -> Option<BitstreamGeom> {
+        if self.frames_num == 0 {
+            return None;
+        }
+        Some(BitstreamGeom {
+            frame_len: self.frame_len,
+            frames_num: self.frames_num,
+            ebr_num: self.ebrs.len(),
+            // 9-kbit blocks
+            ebr_words: 1024,
+        })
+    }
+
+    // the bit tiles are None for the kinds whose frame geometry is not known yet
+    pub fn btile_main(&self, cell: CellCoord) -> Option<BitRect> {
+        Some(BitRect::Main(
+            self.col_frame[cell.col],
+            self.chip.btile_width(cell.col)?,
+            self.row_bit[cell.row],
+            self.chip.btile_height(cell.row)?,
+        ))
+    }
+
+    pub fn btile_term(&self, cell: CellCoord) -> Option<BitRect> {
+        Some(BitRect::Main(
+            self.col_term_frame[cell.col],
+            self.chip.btile_term_width(cell.col)?,
+            self.row_bit[cell.row],
+            self.chip.btile_height(cell.row)?,
+        ))
+    }
+
+    pub fn btile_clk(&self, row: RowId) -> Option<BitRect> {
+        Some(BitRect::Main(
+            self.clk_frame,
+            self.chip.btile_clk_width()?,
+            self.row_bit[row],
+            self.chip.btile_height(row)?,
+        ))
+    }
+
+    pub fn btile_ebr(&self, cell: CellCoord) -> Option<BitRect> {
+        Some(BitRect::Main(
+            self.col_frame[cell.col],
+            self.chip.btile_width(cell.col)?,
+            self.row_ebr_bit[cell.row],
+            144,
+        ))
+    }
+
+    pub fn btile_ebr_data(&self, bel: BelCoord) -> BitRect {
+        BitRect::Ebr(self.ebrs.iter().position(|&ebr| ebr == bel).unwrap())
+    }
+
+    // the tile classes do not describe their bit rects yet; until they do, a tile
+    // covers the main bit tile of each of its cells.  A tile has no bits at all while
+    // the frame geometry of its kind is not known.
+    pub fn tile_bits(&self, tcrd: TileCoord) -> EntityVec<BitRectId, BitRect> {
+        let tile = &self[tcrd];
+        if self.db[tile.class].bitrects.is_empty() {
+            return EntityVec::new();
+        }
+        tile.cells
+            .values()
+            .map(|&cell| self.btile_main(cell))
+            .collect::<Option<_>>()
+            .unwrap_or_default()
+    }
+
+    pub fn decode(
+        &self,
+        bsdata: &BsData,
+        bitstream: &Bitstream,
+    ) -> Result<DecodedBitstream, EncodeError> {
+        decode_tiles(
+            &self.egrid,
+            bsdata,
+            |tcrd| self.tile_bits(tcrd),
+            |bit| bitstream.get(bit),
+        )
+    }
+
+    pub fn encode(
+        &self,
+        geom: &BitstreamGeom,
+        bsdata: &BsData,
+        config: &DecodedBitstream,
+    ) -> Result<Bitstream, EncodeError> {
+        let mut bitstream = Bitstream::new(geom);
+        encode_tiles(
+            &self.egrid,
+            bsdata,
+            config,
+            |tcrd| self.tile_bits(tcrd),
+            |bit, val| bitstream.set(bit, val),
+        )?;
+        Ok(bitstream)
+    }
 }
 
 impl<'a> std::ops::Deref for ExpandedDevice<'a> {
@@ -1,12 +1,15 @@
 #![recursion_limit = "1024"]
 
 pub mod bels;
+pub mod bitstream;
 pub mod bond;
 pub mod bscan;
+pub mod bsdl;
 pub mod chip;
 pub mod cslots;
 pub mod db;
 mod expand;
 pub mod expanded;
+pub mod pinout;
 pub mod regions;
 pub mod tslots;
@@ -0,0 +1,100 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::grid::TileIobId;
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad},
+    chip::{Chip, IoKind},
+};
+
+impl Chip {
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let mut special_io: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (&key, &io) in &self.special_io {
+            special_io.entry(io).or_default().push(key);
+        }
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(_) | BondPad::IoAsc(..) | BondPad::IoPfr(..) | BondPad::IoCdone(_) => {
+                    PinKind::Io
+                }
+                BondPad::Serdes(..)
+                | BondPad::SerdesCorner(_)
+                | BondPad::Mipi(..)
+                | BondPad::MipiCommon(_) => PinKind::Gt,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::VccInt
+                | BondPad::VccAux
+                | BondPad::VccAuxA
+                | BondPad::VccJtag
+                | BondPad::Vcc12
+                | BondPad::VccIo(_)
+                | BondPad::Vtt(_)
+                | BondPad::VccPll(_)
+                | BondPad::VccA
+                | BondPad::Vpp => PinKind::Power,
+                BondPad::Gnd | BondPad::GndA | BondPad::GndPll(_) => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::PllCap(_)
+                | BondPad::TempVss
+                | BondPad::TempSense
+                | BondPad::ProbeVcc
+                | BondPad::ProbeGnd
+                | BondPad::XRes
+                | BondPad::Other
+                | BondPad::Asc(_)
+                | BondPad::Pfr(_) => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Io(io)
+                | BondPad::IoAsc(io, _)
+                | BondPad::IoPfr(io, _)
+                | BondPad::IoCdone(io) => {
+                    ppin = ppin.with_bank(self.get_io_bank(io)).with_io(io);
+                    let io_kind = self.get_io_kind(io);
+                    if io_kind != IoKind::Dummy {
+                        // pads come in true (A/C, P) and complement (B/D, N) pairs
+                        let idx = io.iob().to_idx();
+                        ppin.diff = Some(if idx % 2 == 0 {
+                            DiffPolarity::P
+                        } else {
+                            DiffPolarity::N
+                        });
+                        partners.insert(
+                            io.to_string(),
+                            io.with_iob(TileIobId::from_idx(idx ^ 1)).to_string(),
+                        );
+                    }
+                    if matches!(io_kind, IoKind::Dqs | IoKind::SDqs) {
+                        ppin = ppin.with_function("DQS");
+                    }
+                    if let Some(keys) = special_io.get(&io) {
+                        for key in keys {
+                            ppin = ppin.with_function(key);
+                        }
+                    }
+                    match pad {
+                        BondPad::IoAsc(_, apad) => ppin = ppin.with_function(format!("ASC_{apad}")),
+                        BondPad::IoPfr(_, ppad) => ppin = ppin.with_function(format!("PFR_{ppad}")),
+                        BondPad::IoCdone(_) => ppin = ppin.with_function("CDONE"),
+                        _ => (),
+                    }
+                }
+                BondPad::VccIo(bank) | BondPad::Vtt(bank) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
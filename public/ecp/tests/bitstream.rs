@@ -0,0 +1,159 @@
+use prjcombine_ecp::{
+    bitstream::{BitPos, Bitstream, BitstreamError, BitstreamGeom},
+    db::Database,
+};
+use prjcombine_test_util::Scrambler;
+
+const DBS: [&str; 3] = [
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/ecp.zstd"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/xp.zstd"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/machxo.zstd"),
+];
+
+// Fills every frame and EBR with pseudo-random data, so that the round trip covers every bit.
+fn scramble(bitstream: &mut Bitstream) {
+    let mut scrambler = Scrambler::new();
+    scrambler.fill(&mut bitstream.frame_data);
+    scrambler.fill(&mut bitstream.ebr_data);
+    bitstream.ebr_present.fill(true);
+}
+
+#[test]
+fn emit_roundtrip() {
+    for path in DBS {
+        let db = Database::from_file(path).unwrap();
+        for (chip_id, chip) in &db.chips {
+            let edev = chip.expand_grid(&db.int);
+            let geom = edev.bitstream_geom().unwrap();
+            let mut bitstream = Bitstream::new(&geom);
+            bitstream.frame_present.fill(true);
+            bitstream.idcode = Some(0x01234043);
+            bitstream.usercode = Some(0xdeadbeef);
+            let mut scrambled = bitstream.clone();
+            scramble(&mut scrambled);
+            for bitstream in [bitstream, scrambled] {
+                let data = bitstream.emit(&geom).unwrap();
+                let parsed = Bitstream::parse(&geom, &data).unwrap();
+                assert_eq!(bitstream, parsed, "{path} {chip_id}");
+            }
+        }
+    }
+}
+
+#[test]
+fn ebr_words() {
+    let db = Database::from_file(DBS[0]).unwrap();
+    let chip = db.chips.values().next().unwrap();
+    let edev = chip.expand_grid(&db.int);
+    let geom = edev.bitstream_geom().unwrap();
+    assert!(geom.ebr_num > 1);
+    let mut bitstream = Bitstream::new(&geom);
+    bitstream.frame_present.fill(true);
+    bitstream.set(BitPos::Ebr(1, 0, 8), true);
+    bitstream.set(BitPos::Ebr(1, 7, 0), true);
+    let data = bitstream.emit(&geom).unwrap();
+    // LSC_EBR_ADDRESS selects EBR 1, word 0; the first frame packs words 0..8 MSB first.
+    let pos = data
+        .windows(8)
+        .position(|w| w == [0xf6, 0, 0, 0, 0, 0, 0x08, 0x00])
+        .unwrap();
+    assert_eq!(data[pos + 8], 0xb2);
+    assert_eq!(data[pos + 12..pos + 21], [0x80, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+    let parsed = Bitstream::parse(&geom, &data).unwrap();
+    assert!(parsed.get(BitPos::Ebr(1, 0, 8)));
+    assert!(parsed.get(BitPos::Ebr(1, 7, 0)));
+    assert_eq!(parsed.ebr_present.count_ones(), 1);
+    assert_eq!(Bitstream::diff(&bitstream, &parsed).len(), 0);
+}
+
+#[test]
+fn parse_errors() {
+    let db = Database::from_file(DBS[0]).unwrap();
+    let chip = db.chips.values().next().unwrap();
+    let edev = chip.expand_grid(&db.int);
+    let geom = edev.bitstream_geom().unwrap();
+    let mut bitstream = Bitstream::new(&geom);
+    bitstream.frame_present.fill(true);
+    bitstream.set(BitPos::Ebr(0, 0, 0), true);
+    let data = bitstream.emit(&geom).unwrap();
+
+    let err = Bitstream::parse(&geom, &data[..data.len() / 2]).unwrap_err();
+    assert!(matches!(err, BitstreamError::Truncated { .. }), "{err}");
+
+    let pos = data.windows(2).position(|w| w == [0x82, 0x91]).unwrap();
+    let mut bad = data.clone();
+    bad[pos + 4] ^= 1;
+    let err = Bitstream::parse(&geom, &bad).unwrap_err();
+    assert!(matches!(err, BitstreamError::CrcMismatch { .. }), "{err}");
+
+    let pos = data.windows(4).position(|w| w == [0xf6, 0, 0, 0]).unwrap();
+    let mut bad = data.clone();
+    bad[pos + 4..pos + 8].copy_from_slice(&((geom.ebr_num as u32) << 11).to_be_bytes());
+    let err = Bitstream::parse(&geom, &bad).unwrap_err();
+    assert!(
+        matches!(err, BitstreamError::InvalidFrameAddress { .. }),
+        "{err}"
+    );
+}
+
+#[test]
+fn emit_errors() {
+    let db = Database::from_file(DBS[0]).unwrap();
+    let mut chips = db.chips.values();
+    let geom = chips
+        .next()
+        .unwrap()
+        .expand_grid(&db.int)
+        .bitstream_geom()
+        .unwrap();
+    let mut bitstream = Bitstream::new(&geom);
+    bitstream.frame_present.fill(true);
+    // a bitstream of another device
+    let other = chips
+        .map(|chip| chip.expand_grid(&db.int).bitstream_geom().unwrap())
+        .find(|other| other.frame_len != geom.frame_len)
+        .unwrap();
+    let err = bitstream.emit(&other).unwrap_err();
+    assert!(
+        matches!(err, BitstreamError::Malformed { ref reason, .. } if reason.contains("bits per frame")),
+        "{err}"
+    );
+    let err = bitstream
+        .emit(&BitstreamGeom {
+            ebr_num: geom.ebr_num + 1,
+            ..geom
+        })
+        .unwrap_err();
+    assert!(
+        matches!(err, BitstreamError::Malformed { ref reason, .. } if reason.contains("EBRs")),
+        "{err}"
+    );
+    // the frame count does not fit the write command
+    let mut bitstream = Bitstream::new(&BitstreamGeom {
+        frames_num: 0x10000,
+        ..geom
+    });
+    bitstream.frame_present.fill(true);
+    let err = bitstream
+        .emit(&BitstreamGeom {
+            frames_num: 0x10000,
+            ..geom
+        })
+        .unwrap_err();
+    assert!(matches!(err, BitstreamError::Unsupported { .. }), "{err}");
+}
+
+#[test]
+fn geom_unknown() {
+    // the frame geometry of ECP2 and later has not been reverse engineered yet
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/ecp5.zstd");
+    let db = Database::from_file(path).unwrap();
+    for chip in db.chips.values() {
+        let edev = chip.expand_grid(&db.int);
+        assert_eq!(edev.bitstream_geom(), None);
+        assert_eq!(chip.btile_clk_width(), None);
+        for (tcrd, _) in edev.tiles() {
+            assert!(edev.tile_bits(tcrd).is_empty());
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use prjcombine_ecp::{
+    bond::{BondPad, CfgPad},
+    chip::SpecialIoKey,
+    db::Database,
+};
+use prjcombine_test_util::check_device_bsdl;
+
+// CrossLink has no boundary scan
+const DBS: [&str; 11] = [
+    "scm", "ecp", "xp", "machxo", "ecp2", "ecp2m", "xp2", "ecp3", "machxo2", "ecp4", "ecp5",
+];
+
+#[test]
+fn bsdl() {
+    for name in DBS {
+        let path = format!("{}/../../databases/{name}.zstd", env!("CARGO_MANIFEST_DIR"));
+        let db = Database::from_file(path).unwrap();
+        for dev in &db.devices {
+            let chip = &db.chips[dev.chip];
+            let bscan = chip.get_bscan();
+            // the TAP pins shared with IOBs are left out of the description
+            let tap: Vec<_> = [
+                SpecialIoKey::Tck,
+                SpecialIoKey::Tms,
+                SpecialIoKey::Tdi,
+                SpecialIoKey::Tdo,
+            ]
+            .into_iter()
+            .filter_map(|key| chip.special_io.get(&key).copied())
+            .map(BondPad::Io)
+            .collect();
+            check_device_bsdl(
+                &dev.name,
+                bscan.bits,
+                dev.bonds.iter().map(|(_, package, &bond)| {
+                    let bond = &db.bonds[bond];
+                    (package.as_str(), bond, bond.pins.values().copied())
+                }),
+                |bond, package, idcode| chip.make_bsdl(bond, &dev.name, package, idcode),
+                |pad| match pad {
+                    _ if tap.contains(&pad) => None,
+                    BondPad::Cfg(CfgPad::Tck | CfgPad::Tms | CfgPad::Tdi | CfgPad::Tdo) => None,
+                    _ => bscan.pads.get(&pad).copied(),
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use prjcombine_ecp::db::Database;
+use prjcombine_test_util::check_pinout;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/ecp5.zstd");
+
+// The IO names are made of the functions and the bank.
+#[test]
+fn pinout() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db
+        .devices
+        .iter()
+        .find(|dev| dev.name == "LFE5U-25F")
+        .unwrap();
+    let (_, &bond) = dev.bonds.get("CABGA381").unwrap();
+    let pinout = db.chips[dev.chip].make_pinout(&db.bonds[bond], "LFE5U-25F", "CABGA381");
+    check_pinout(
+        &pinout,
+        &[
+            ("A4", "IO_7", Some("7"), Some("A5")),
+            ("A10", "IO_CLOCK_N1_0", Some("0"), Some("A11")),
+            ("P3", "IO_PLL_IN0_SW0_6", Some("6"), Some("P4")),
+            ("R1", "IO_D7_8", Some("8"), Some("T1")),
+            ("U16", "IO_PLL_IN0_SE0_3", Some("3"), Some("T17")),
+        ],
+    );
+}
@@ -178,3 +178,6 @@ impl<I: EntityId, T> core::ops::IndexMut<I> for EntityBundleMap<I, T> {
 
 #[cfg(feature = "bincode")]
 mod bincode;
+
+#[cfg(feature = "serde")]
+mod serde;
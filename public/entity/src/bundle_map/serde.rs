@@ -0,0 +1,64 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use crate::{EntityBundleIndices, EntityBundleMap, EntityId};
+
+impl<I: EntityId, T: Serialize> Serialize for EntityBundleMap<I, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(self.bundles.len()))?;
+        for (_, key, (idx, val)) in &self.bundles {
+            let num = match idx {
+                EntityBundleIndices::Single(_) => None,
+                EntityBundleIndices::Array(range) => Some(range.len()),
+            };
+            seq.serialize_element(&(key, num, val))?;
+        }
+        seq.end()
+    }
+}
+
+struct DeserializeVisitor<I: EntityId, T> {
+    marker: PhantomData<fn() -> EntityBundleMap<I, T>>,
+}
+
+impl<'de, I: EntityId, T: Deserialize<'de>> Visitor<'de> for DeserializeVisitor<I, T> {
+    type Value = EntityBundleMap<I, T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("entity bundle map")
+    }
+
+    fn visit_seq<S>(self, mut access: S) -> Result<Self::Value, S::Error>
+    where
+        S: SeqAccess<'de>,
+    {
+        let mut res = EntityBundleMap::new();
+        while let Some((key, num, val)) = access.next_element::<(String, Option<usize>, T)>()? {
+            let ok = match num {
+                None => res.insert(key, val).is_some(),
+                Some(num) => res.insert_array(key, num, val).is_some(),
+            };
+            if !ok {
+                return Err(S::Error::custom("duplicate key"));
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl<'de, I: EntityId, T: Deserialize<'de>> Deserialize<'de> for EntityBundleMap<I, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(DeserializeVisitor {
+            marker: PhantomData,
+        })
+    }
+}
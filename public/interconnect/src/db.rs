@@ -12,6 +12,7 @@ use std::collections::{BTreeMap, BTreeSet, HashSet};
 // region: top
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntDb {
     pub enum_classes: EntityMap<EnumClassId, String, EnumClass>,
     pub bel_classes: EntityMap<BelClassId, String, BelClass>,
@@ -172,6 +173,7 @@ pub type EnumClassId = EntityIdU16<EnumClass>;
 pub type EnumValueId = EntityIdU16<EnumValueTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumClass {
     pub values: EntitySet<EnumValueId, String>,
 }
@@ -192,12 +194,14 @@ impl EntityTag for TableRowTag {
 pub type TableRowId = EntityIdU16<TableRowTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Table {
     pub fields: EntityMap<TableFieldId, String, BelAttributeType>,
     pub rows: EntityMap<TableRowId, String, EntityPartVec<TableFieldId, TableValue>>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TableValue {
     BitVec(BitVec),
     Enum(EnumValueId),
@@ -240,6 +244,7 @@ pub type BelPadId = EntityIdU16<BelClassPad>;
 pub type BelAttributeId = EntityIdU16<BelClassAttribute>;
 
 #[derive(Default, Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClass {
     pub inputs: EntityBundleMap<BelInputId, BelClassInput>,
     pub outputs: EntityBundleMap<BelOutputId, BelClassOutput>,
@@ -249,24 +254,28 @@ pub struct BelClass {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClassInput {
     pub nonroutable: bool,
     pub indexing: BelPinIndexing,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClassOutput {
     pub nonroutable: bool,
     pub indexing: BelPinIndexing,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClassBidir {
     pub nonroutable: bool,
     pub indexing: BelPinIndexing,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelPinIndexing {
     pub lsb_index: usize,
     pub wrong_endian: bool,
@@ -315,16 +324,19 @@ impl BelPinIndexing {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClassPad {
     pub kind: PadKind,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelClassAttribute {
     pub typ: BelAttributeType,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BelAttributeType {
     Enum(EnumClassId),
     Bool,
@@ -335,6 +347,7 @@ pub enum BelAttributeType {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PadKind {
     In,
     Out,
@@ -350,12 +363,14 @@ impl EntityTag for BelSlot {
 pub type BelSlotId = EntityIdU16<BelSlot>;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelSlot {
     pub tile_slot: TileSlotId,
     pub kind: BelKind,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BelKind {
     Routing,
     Class(BelClassId),
@@ -388,6 +403,7 @@ impl WireSlotIdExt for WireSlotId {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WireKind {
     Tie0,
     Tie1,
@@ -439,6 +455,7 @@ pub type ConnectorSlotId = EntityIdU8<ConnectorSlot>;
 pub type ConnectorClassId = EntityIdU16<ConnectorClass>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectorClass {
     pub slot: ConnectorSlotId,
     pub wires: EntityPartVec<WireSlotId, ConnectorWire>,
@@ -454,6 +471,7 @@ impl ConnectorClass {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConnectorWire {
     BlackHole,
     Reflect(WireSlotId),
@@ -461,6 +479,7 @@ pub enum ConnectorWire {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectorSlot {
     pub opposite: ConnectorSlotId,
 }
@@ -485,6 +504,7 @@ pub type CellSlotId = EntityIdU16<CellSlotTag>;
 pub type TileSlotId = EntityIdU8<TileSlotTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileClass {
     pub slot: TileSlotId,
     pub cells: EntityVec<CellSlotId, String>,
@@ -504,12 +524,14 @@ impl TileClass {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitRectInfo {
     pub name: String,
     pub geometry: BitRectGeometry,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileWireCoord {
     pub cell: CellSlotId,
     pub wire: WireSlotId,
@@ -551,6 +573,7 @@ impl TileWireCoord {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PolTileWireCoord {
     pub tw: TileWireCoord,
     pub inv: bool,
@@ -599,6 +622,7 @@ impl std::ops::DerefMut for PolTileWireCoord {
 /// - a [`LegacyBel`], which is a deprecated variant of [`Bel`] that is stringly-typed instead of
 ///   being described by a schema; is being slowly removed from the codebase
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BelInfo {
     SwitchBox(SwitchBox),
     Bel(Bel),
@@ -620,6 +644,7 @@ pub enum BelInfo {
 /// bel class that is used to describe both LUTs with LUTRAM functionality and ones without it).
 /// The meaning of this and circumstances when this happens are all target-dependent.
 #[derive(Default, Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bel {
     /// The wires connected to bel inputs.
     pub inputs: EntityPartVec<BelInputId, BelInput>,
@@ -634,6 +659,7 @@ pub struct Bel {
 
 /// Describes the connection of a [`Bel`] input pin.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BelInput {
     /// The bel input is connected directly to this interconnect wire.
     Fixed(PolTileWireCoord),
@@ -668,6 +694,7 @@ impl BelInput {
 ///   the case that some values of the enum are missing (non-encodeable).  Once again, the usage
 ///   and meaning of this is target-specific.
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BelAttribute {
     BitVec(Vec<PolTileBit>),
     Enum(BelAttributeEnum),
@@ -675,6 +702,7 @@ pub enum BelAttribute {
 
 /// Describes the bitstream encoding of an enum-typed [`Bel`] attribute.
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelAttributeEnum {
     /// The bitstream bits encoding this attribute.
     pub bits: Vec<TileBit>,
@@ -684,11 +712,13 @@ pub struct BelAttributeEnum {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LegacyBel {
     pub pins: BTreeMap<String, BelPin>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelPin {
     pub wires: BTreeSet<TileWireCoord>,
     pub dir: PinDir,
@@ -718,6 +748,7 @@ impl BelPin {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PinDir {
     Input,
     Output,
@@ -732,12 +763,14 @@ pub enum PinDir {
 ///
 /// Describes all interconnect that logically belongs to a particular tile.
 #[derive(Clone, Debug, Eq, PartialEq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SwitchBox {
     pub items: Vec<SwitchBoxItem>,
 }
 
 /// A single routing element contained in a [`SwitchBox`].
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SwitchBoxItem {
     /// A programmable multiplexer (drives one of several selectable source wires onto the destination wire).
     Mux(Mux),
@@ -770,12 +803,14 @@ pub enum SwitchBoxItem {
 ///
 /// The connection may be buffered or not; we do not store that information here.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mux {
     /// The destination wire.
     pub dst: TileWireCoord,
     /// The bitstream bits controlling the multiplexer.
     pub bits: Vec<TileBit>,
     /// The selectable source wires, with associated bit patterns.
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub src: BTreeMap<PolTileWireCoord, BitVec>,
     /// If specified, the bit pattern that turns off the multiplexer (and allows other multiplexers
     /// or programmable buffers to drive the wire).
@@ -787,6 +822,7 @@ pub struct Mux {
 /// Drives the value of the source wire onto the destination wire when enabled.
 /// Otherwise, the destination wire can be driven by another buffer or other interconnect element.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgBuf {
     /// The destination wire.
     pub dst: TileWireCoord,
@@ -803,6 +839,7 @@ pub struct ProgBuf {
 /// Can be used to represent actual interconnect buffers or wire aliasing in cases not covered by
 /// other tools.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PermaBuf {
     /// The destination wire.
     pub dst: TileWireCoord,
@@ -819,6 +856,7 @@ pub struct PermaBuf {
 /// the reverse connection is useless because of interconnect topology, or the signal strength is
 /// too low to be useful).
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pass {
     /// The destination wire.
     pub dst: TileWireCoord,
@@ -833,6 +871,7 @@ pub struct Pass {
 /// Connects the two wires together using a pass gate iff the bitstream bit is set.  The connection
 /// works in both directions at once, and is unbuffered by nature.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BiPass {
     /// The first wire.
     pub a: TileWireCoord,
@@ -850,6 +889,7 @@ pub struct BiPass {
 /// This is a permanent connection (it is not possible to not drive the destination wire), only
 /// the polarity of the connection is programmable.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgInv {
     /// The destination wire.
     pub dst: TileWireCoord,
@@ -865,6 +905,7 @@ pub struct ProgInv {
 /// the connection is programmable and can be set to one of predefined "steps" via bitstream bits.
 /// The exact values of the delays are specified elsewhere (as are interconnect delays in general).
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProgDelay {
     /// The destination wire.
     pub dst: TileWireCoord,
@@ -899,6 +940,7 @@ pub struct ProgDelay {
 /// if reaching the segment of the wire that is driven from the specified cell requires traversing
 /// the specified connector.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bidi {
     /// The connector slot used to determine whether the driver is upstream or downstream of
     /// the buffer.  This connector slot is relative to the cell specified in `wire`.
@@ -917,6 +959,7 @@ pub struct Bidi {
 ///
 /// The connection may be buffered or not.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PairMux {
     /// The destination wires.
     pub dst: [TileWireCoord; 2],
@@ -926,6 +969,7 @@ pub struct PairMux {
     /// One or both of the source wires may be `None`, which means that the value driven on
     /// the corresponding destination wire is undefined for this selection.  A `[None, None]`
     /// selection may be used to turn off the mux.
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub src: BTreeMap<[Option<PolTileWireCoord>; 2], BitVec>,
 }
 
@@ -941,6 +985,7 @@ pub struct PairMux {
 /// It is mostly unknown what these bits do.  They may control power to interconnect circuitry, or
 /// termination for long-distance differential transmission lines.
 #[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WireSupport {
     /// The associated wires.
     pub wires: BTreeSet<TileWireCoord>,
@@ -958,6 +1003,7 @@ pub struct WireSupport {
 /// and the test mode inputs ignored.  The test mode settings are only used for the purpose
 /// of testing the interconnect circuitry itself (at the factory).
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TestMux {
     /// The bits controlling the multiplexer.
     pub bits: Vec<TileBit>,
@@ -968,11 +1014,13 @@ pub struct TestMux {
     pub bits_primary: BitVec,
     /// The wires driven by this multiplexer and their sources.  The keys are destination wires,
     /// and the values describe the source wires.
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub wires: BTreeMap<TileWireCoord, TestMuxWire>,
 }
 
 /// A set of source wires corresponding to a particular destination wire of a [`TestMux`].
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TestMuxWire {
     /// The source wire used for primary mode.
     pub primary_src: PolTileWireCoord,
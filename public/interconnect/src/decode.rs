@@ -0,0 +1,606 @@
+//! Tile-level bitstream decoding.
+//!
+//! Walks every tile of an [`ExpandedGrid`], extracts its bits from the bitstream, and matches
+//! them against the bitstream encodings stored in the interconnect database (switchboxes,
+//! bel attributes, programmable inverters) and in the legacy [`BsData`] items.  The result
+//! is a per-tile report of active settings and PIPs, along with the bits that no database item
+//! accounts for.
+//!
+//! An item is reported when its raw bits differ from the state [`crate::encode`] writes for
+//! an unconfigured tile: for bit-vector attributes that is all bits clear after applying the
+//! bit polarity, for enum attributes and multiplexers all raw bits clear, except where items
+//! share bits.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use prjcombine_entity::{EntityBundleItemIndex, EntityId, EntityVec};
+use prjcombine_types::{
+    bitrect::BitRect,
+    bitvec::BitVec,
+    bsdata::{BitRectId, BsData, DbValue, PolTileBit, TileBit, TileItemKind},
+    text::{TextError, TextParser, parse_num},
+};
+
+use crate::{
+    db::{
+        BelAttribute, BelAttributeType, BelClass, BelInfo, BelInput, BelInputId, BelKind,
+        CellSlotId, IntDb, PolTileWireCoord, SwitchBoxItem, TileClass, TileWireCoord,
+    },
+    encode::{EncodeError, encode_tiles},
+    grid::{CellCoord, ExpandedGrid, TileCoord},
+};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodedSetting {
+    pub bel: String,
+    pub attr: String,
+    pub value: DbValue,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DecodedTile {
+    /// Attributes set to a known value.
+    pub settings: Vec<DecodedSetting>,
+    /// Enabled programmable connections, as `(destination, source)` pairs.
+    pub pips: Vec<(TileWireCoord, PolTileWireCoord)>,
+    /// Attributes whose bits match none of the known values; the value is the raw bit pattern.
+    pub invalid: Vec<DecodedSetting>,
+    /// Set bits not covered by any database item of this or any other tile.
+    pub unexplained: Vec<TileBit>,
+}
+
+impl DecodedTile {
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+            && self.pips.is_empty()
+            && self.invalid.is_empty()
+            && self.unexplained.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DecodedBitstream {
+    pub tiles: BTreeMap<TileCoord, DecodedTile>,
+}
+
+struct TileDecoder<'a, R: BitRect, F: Fn(R::BitPos) -> bool> {
+    tcls: &'a TileClass,
+    rects: &'a EntityVec<BitRectId, R>,
+    get_bit: &'a F,
+    unexplained: &'a mut HashMap<R::BitPos, (TileCoord, TileBit)>,
+    // the bits written by the encoder for an empty configuration
+    defaults: &'a HashMap<R::BitPos, bool>,
+    res: DecodedTile,
+}
+
+impl<R: BitRect, F: Fn(R::BitPos) -> bool> TileDecoder<'_, R, F> {
+    fn raw(&mut self, bit: TileBit) -> bool {
+        // Placeholder bits (such as `TileBit::DUMMY`) are never set.
+        let Some(rect) = self.rects.get(bit.rect) else {
+            return false;
+        };
+        let pos = rect.xlat_pos_fwd((bit.frame, bit.bit));
+        let val = (self.get_bit)(pos);
+        if val {
+            self.unexplained.remove(&pos);
+        }
+        val
+    }
+
+    fn raw_bits(&mut self, bits: &[TileBit]) -> BitVec {
+        bits.iter().map(|&bit| self.raw(bit)).collect()
+    }
+
+    fn default_raw(&self, bit: TileBit) -> bool {
+        let Some(rect) = self.rects.get(bit.rect) else {
+            return false;
+        };
+        let pos = rect.xlat_pos_fwd((bit.frame, bit.bit));
+        self.defaults.get(&pos).copied().unwrap_or(false)
+    }
+
+    /// Returns true if the raw bits are in the state the encoder writes for an unconfigured
+    /// tile.  Items can share bits, so this is not always all-clear after polarity.
+    fn is_default(&self, bits: &[TileBit], val: &BitVec) -> bool {
+        bits.iter()
+            .zip(val)
+            .all(|(&bit, v)| self.default_raw(bit) == v)
+    }
+
+    /// Reads a single polarized bit, returning true only if it is set and differs from
+    /// the unconfigured state.
+    fn pol_set(&mut self, bit: PolTileBit) -> bool {
+        let raw = self.raw(bit.bit);
+        raw != bit.inv && raw != self.default_raw(bit.bit)
+    }
+
+    /// Reads polarized bits, returning `None` if they are in the unconfigured state.
+    fn pol_bits_set(&mut self, bits: &[PolTileBit]) -> Option<BitVec> {
+        let raw: BitVec = bits.iter().map(|&bit| self.raw(bit.bit)).collect();
+        if bits
+            .iter()
+            .zip(&raw)
+            .all(|(&bit, v)| self.default_raw(bit.bit) == v)
+        {
+            return None;
+        }
+        Some(bits.iter().zip(&raw).map(|(&bit, v)| v ^ bit.inv).collect())
+    }
+
+    fn setting(&mut self, bel: &str, attr: impl Into<String>, value: impl Into<DbValue>) {
+        self.res.settings.push(DecodedSetting {
+            bel: bel.to_string(),
+            attr: attr.into(),
+            value: value.into(),
+        });
+    }
+
+    fn invalid(&mut self, bel: &str, attr: impl Into<String>, value: BitVec) {
+        self.res.invalid.push(DecodedSetting {
+            bel: bel.to_string(),
+            attr: attr.into(),
+            value: value.into(),
+        });
+    }
+
+    fn decode_switchbox(&mut self, egrid: &ExpandedGrid, bel: &str, items: &[SwitchBoxItem]) {
+        let db = egrid.db;
+        for item in items {
+            match item {
+                SwitchBoxItem::Mux(mux) => {
+                    if mux.bits.is_empty() {
+                        continue;
+                    }
+                    let val = self.raw_bits(&mux.bits);
+                    if self.is_default(&mux.bits, &val) || mux.bits_off.as_ref() == Some(&val) {
+                        continue;
+                    }
+                    match mux.src.iter().find(|&(_, v)| *v == val) {
+                        Some((&src, _)) => self.res.pips.push((mux.dst, src)),
+                        None => self.invalid(
+                            bel,
+                            format!("MUX.{dst}", dst = mux.dst.to_string(db, self.tcls)),
+                            val,
+                        ),
+                    }
+                }
+                SwitchBoxItem::ProgBuf(buf) => {
+                    if self.pol_set(buf.bit) {
+                        self.res.pips.push((buf.dst, buf.src));
+                    }
+                }
+                SwitchBoxItem::PermaBuf(_) => (),
+                SwitchBoxItem::Pass(pass) => {
+                    if self.pol_set(pass.bit) {
+                        self.res.pips.push((pass.dst, pass.src.pos()));
+                    }
+                }
+                SwitchBoxItem::BiPass(pass) => {
+                    if self.pol_set(pass.bit) {
+                        self.res.pips.push((pass.a, pass.b.pos()));
+                        self.res.pips.push((pass.b, pass.a.pos()));
+                    }
+                }
+                SwitchBoxItem::ProgInv(inv) => {
+                    if self.pol_set(inv.bit) {
+                        self.setting(
+                            bel,
+                            format!("INV.{dst}", dst = inv.dst.to_string(db, self.tcls)),
+                            BitVec::from_iter([true]),
+                        );
+                    }
+                }
+                SwitchBoxItem::ProgDelay(delay) => {
+                    let val = self.raw_bits(&delay.bits);
+                    if self.is_default(&delay.bits, &val) {
+                        continue;
+                    }
+                    let attr = format!("DELAY.{dst}", dst = delay.dst.to_string(db, self.tcls));
+                    match delay.steps.iter().position(|v| *v == val) {
+                        Some(step) => self.setting(bel, attr, step as u32),
+                        None => self.invalid(bel, attr, val),
+                    }
+                }
+                SwitchBoxItem::Bidi(bidi) => {
+                    if self.pol_set(bidi.bit_upstream) {
+                        self.setting(
+                            bel,
+                            format!(
+                                "BIDI.{conn}.{wire}",
+                                conn = db.conn_slots.key(bidi.conn),
+                                wire = bidi.wire.to_string(db, self.tcls)
+                            ),
+                            BitVec::from_iter([true]),
+                        );
+                    }
+                }
+                SwitchBoxItem::PairMux(mux) => {
+                    let val = self.raw_bits(&mux.bits);
+                    if self.is_default(&mux.bits, &val) {
+                        continue;
+                    }
+                    match mux.src.iter().find(|&(_, v)| *v == val) {
+                        Some((src, _)) => {
+                            for (&dst, &src) in mux.dst.iter().zip(src) {
+                                if let Some(src) = src {
+                                    self.res.pips.push((dst, src));
+                                }
+                            }
+                        }
+                        None => self.invalid(
+                            bel,
+                            format!(
+                                "MUX.{dst0}.{dst1}",
+                                dst0 = mux.dst[0].to_string(db, self.tcls),
+                                dst1 = mux.dst[1].to_string(db, self.tcls)
+                            ),
+                            val,
+                        ),
+                    }
+                }
+                SwitchBoxItem::WireSupport(support) => {
+                    if let Some(val) = self.pol_bits_set(&support.bits) {
+                        self.setting(bel, support_attr(db, self.tcls, &support.wires), val);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn input_name(bcls: &BelClass, pid: BelInputId) -> String {
+    let (pname, idx) = bcls.inputs.key(pid);
+    match idx {
+        EntityBundleItemIndex::Single => pname.to_string(),
+        EntityBundleItemIndex::Array { index, .. } => {
+            let index = bcls.inputs[pid].indexing.phys_to_virt(index);
+            format!("{pname}[{index}]")
+        }
+    }
+}
+
+pub(crate) fn support_attr(
+    db: &IntDb,
+    tcls: &TileClass,
+    wires: &BTreeSet<TileWireCoord>,
+) -> String {
+    format!(
+        "SUPPORT.{wires}",
+        wires = wires
+            .iter()
+            .map(|w| w.to_string(db, tcls))
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+/// Decodes the bitstream tile by tile.
+///
+/// `tile_bits` gives the bit rectangles of a tile (as returned by the `tile_bits` method of
+/// the family's `ExpandedDevice`), and `get_bit` reads a single bit of the bitstream.
+///
+/// Fails if the default value of some tile item cannot be placed in the bitstream, e.g.
+/// because it has a bit outside the rectangles of its tile.
+pub fn decode_tiles<R: BitRect>(
+    egrid: &ExpandedGrid,
+    bsdata: &BsData,
+    tile_bits: impl Fn(TileCoord) -> EntityVec<BitRectId, R>,
+    get_bit: impl Fn(R::BitPos) -> bool,
+) -> Result<DecodedBitstream, EncodeError> {
+    let db = egrid.db;
+    let mut res = DecodedBitstream::default();
+    let mut defaults = HashMap::new();
+    encode_tiles(
+        egrid,
+        bsdata,
+        &DecodedBitstream::default(),
+        &tile_bits,
+        |pos, val| {
+            defaults.insert(pos, val);
+        },
+    )?;
+    let mut unexplained: HashMap<R::BitPos, (TileCoord, TileBit)> = HashMap::new();
+    for (tcrd, tile) in egrid.tiles() {
+        let tcls = &db[tile.class];
+        for (rid, rect) in tile_bits(tcrd) {
+            // Legacy bitstream data may use more rects than the tile class describes; their
+            // geometry is unknown here, so their bits are not tracked as unexplained.
+            let Some(info) = tcls.bitrects.get(rid) else {
+                continue;
+            };
+            let geom = &info.geometry;
+            for frame in 0..geom.frames {
+                for bit in 0..geom.bits {
+                    let tbit = TileBit::new(rid.to_idx(), frame, bit);
+                    if !rect.contains((tbit.frame, tbit.bit)) {
+                        continue;
+                    }
+                    let pos = rect.xlat_pos_fwd((tbit.frame, tbit.bit));
+                    if get_bit(pos) {
+                        unexplained.entry(pos).or_insert((tcrd, tbit));
+                    }
+                }
+            }
+        }
+    }
+    for (tcrd, tile) in egrid.tiles() {
+        let tcls = &db[tile.class];
+        if tcls.bitrects.is_empty() {
+            continue;
+        }
+        let rects = tile_bits(tcrd);
+        let mut decoder = TileDecoder {
+            tcls,
+            rects: &rects,
+            get_bit: &get_bit,
+            unexplained: &mut unexplained,
+            defaults: &defaults,
+            res: DecodedTile::default(),
+        };
+        for (slot, bel) in &tcls.bels {
+            let bname = db.bel_slots.key(slot);
+            match bel {
+                BelInfo::SwitchBox(sb) => decoder.decode_switchbox(egrid, bname, &sb.items),
+                BelInfo::Bel(bel) => {
+                    let BelKind::Class(bcid) = db.bel_slots[slot].kind else {
+                        unreachable!()
+                    };
+                    let bcls = &db.bel_classes[bcid];
+                    for (pid, inp) in &bel.inputs {
+                        let BelInput::Invertible(_, bit) = *inp else {
+                            continue;
+                        };
+                        if decoder.pol_set(bit) {
+                            let pname = input_name(bcls, pid);
+                            decoder.setting(
+                                bname,
+                                format!("INV.{pname}"),
+                                BitVec::from_iter([true]),
+                            );
+                        }
+                    }
+                    for (aid, attr) in &bel.attributes {
+                        let aname = bcls.attributes.key(aid);
+                        match attr {
+                            BelAttribute::BitVec(bits) => {
+                                if let Some(val) = decoder.pol_bits_set(bits) {
+                                    decoder.setting(bname, aname, val);
+                                }
+                            }
+                            BelAttribute::Enum(ebits) => {
+                                let val = decoder.raw_bits(&ebits.bits);
+                                if decoder.is_default(&ebits.bits, &val) {
+                                    continue;
+                                }
+                                let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                                    unreachable!()
+                                };
+                                match ebits.values.iter().find(|&(_, v)| *v == val) {
+                                    Some((vid, _)) => decoder.setting(
+                                        bname,
+                                        aname,
+                                        db.enum_classes[eid].values[vid].clone(),
+                                    ),
+                                    None => decoder.invalid(bname, aname, val),
+                                }
+                            }
+                        }
+                    }
+                }
+                BelInfo::TestMux(tmux) => {
+                    let val = decoder.raw_bits(&tmux.bits);
+                    if !decoder.is_default(&tmux.bits, &val) {
+                        match tmux.groups.iter().position(|v| *v == val) {
+                            Some(group) => decoder.setting(bname, "TEST_GROUP", group as u32),
+                            None => decoder.invalid(bname, "TEST_GROUP", val),
+                        }
+                    }
+                }
+                BelInfo::OldTestMux | BelInfo::Legacy(_) => (),
+            }
+        }
+        if let Some(btile) = bsdata.tiles.get(db.tile_classes.key(tile.class)) {
+            for (name, item) in &btile.items {
+                let (bel, attr) = name.split_once(':').unwrap_or(("", name));
+                let raw = decoder.raw_bits(&item.bits);
+                match item.kind {
+                    TileItemKind::BitVec { ref invert } => {
+                        if !decoder.is_default(&item.bits, &raw) {
+                            decoder.setting(bel, attr, &raw ^ invert);
+                        }
+                    }
+                    TileItemKind::Enum { ref values } => {
+                        if decoder.is_default(&item.bits, &raw) {
+                            continue;
+                        }
+                        match values.iter().find(|&(_, v)| *v == raw) {
+                            Some((vname, _)) => decoder.setting(bel, attr, vname.clone()),
+                            None => decoder.invalid(bel, attr, raw),
+                        }
+                    }
+                }
+            }
+        }
+        if !decoder.res.is_empty() {
+            res.tiles.insert(tcrd, decoder.res);
+        }
+    }
+    for (tcrd, bit) in unexplained.into_values() {
+        res.tiles.entry(tcrd).or_default().unexplained.push(bit);
+    }
+    for tile in res.tiles.values_mut() {
+        tile.unexplained.sort();
+    }
+    Ok(res)
+}
+
+impl DecodedBitstream {
+    pub fn dump(&self, egrid: &ExpandedGrid, o: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let db = egrid.db;
+        for (&tcrd, tile) in &self.tiles {
+            let tcls = &db[egrid[tcrd].class];
+            writeln!(
+                o,
+                "tile {tcrd} {kind} {{",
+                tcrd = tcrd.to_string(db),
+                kind = db.tile_classes.key(egrid[tcrd].class)
+            )?;
+            for setting in &tile.settings {
+                writeln!(
+                    o,
+                    "\t{bel}:{attr} = {value};",
+                    bel = setting.bel,
+                    attr = setting.attr,
+                    value = setting.value
+                )?;
+            }
+            for &(dst, src) in &tile.pips {
+                writeln!(
+                    o,
+                    "\tpip {dst} = {src};",
+                    dst = dst.to_string(db, tcls),
+                    src = src.to_string(db, tcls)
+                )?;
+            }
+            for setting in &tile.invalid {
+                writeln!(
+                    o,
+                    "\tinvalid {bel}:{attr} = {value};",
+                    bel = setting.bel,
+                    attr = setting.attr,
+                    value = setting.value
+                )?;
+            }
+            for &bit in &tile.unexplained {
+                writeln!(o, "\tunexplained {bit};", bit = tcls.dump_bit(bit))?;
+            }
+            writeln!(o, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+impl DecodedBitstream {
+    /// Parses the text written by [`DecodedBitstream::dump`], resolving tile, wire and bit
+    /// names against the given grid.
+    pub fn parse(egrid: &ExpandedGrid, text: &str) -> Result<Self, TextError> {
+        let db = egrid.db;
+        let mut p = TextParser::new(text);
+        let mut res = DecodedBitstream::default();
+        while !p.is_done() {
+            let line = p.expect_line()?;
+            let head = line.expect_block("tile")?;
+            let (tname, kind) = head.split_once(' ').ok_or_else(|| {
+                line.error(format!("expected tile name and kind, got \"{head}\""))
+            })?;
+            let (cell, slot) = tname
+                .split_once('.')
+                .ok_or_else(|| line.error(format!("invalid tile \"{tname}\"")))?;
+            let cell: CellCoord = line.check(cell.parse())?;
+            let slot = db
+                .tile_slots
+                .get(slot)
+                .ok_or_else(|| line.error(format!("unknown tile slot {slot}")))?;
+            let tcrd = cell.tile(slot);
+            let tile = if egrid.die.get(cell.die).is_some()
+                && egrid.cols(cell.die).contains(cell.col)
+                && egrid.rows(cell.die).contains(cell.row)
+            {
+                egrid.get_tile(tcrd)
+            } else {
+                None
+            };
+            let Some(tile) = tile else {
+                return Err(line.error(format!("no tile {tname} in this device")));
+            };
+            if db.tile_classes.key(tile.class) != kind {
+                return Err(line.error(format!(
+                    "tile {tname} is {actual}, not {kind}",
+                    actual = db.tile_classes.key(tile.class)
+                )));
+            }
+            let tcls = &db[tile.class];
+            let parse_wire = |s: &str| -> Result<TileWireCoord, String> {
+                let (cell, wire) = if tcls.cells.len() == 1 {
+                    (CellSlotId::from_idx(0), s)
+                } else {
+                    let (cell, wire) = s
+                        .split_once('.')
+                        .ok_or_else(|| format!("invalid tile wire \"{s}\""))?;
+                    let cell = tcls
+                        .cells
+                        .iter()
+                        .find(|(_, name)| *name == cell)
+                        .ok_or_else(|| format!("unknown tile cell {cell}"))?
+                        .0;
+                    (cell, wire)
+                };
+                let (wire, _) = db
+                    .wires
+                    .get(wire)
+                    .ok_or_else(|| format!("unknown wire {wire}"))?;
+                Ok(TileWireCoord { cell, wire })
+            };
+            let parse_bit = |s: &str| -> Result<TileBit, String> {
+                let parsed = s.strip_suffix(']').and_then(|s| {
+                    let (rest, bit) = s.rsplit_once("][")?;
+                    let (rect, frame) = rest.rsplit_once('[')?;
+                    Some((rect, parse_num(frame).ok()?, parse_num(bit).ok()?))
+                });
+                let Some((rect, frame, bit)) = parsed else {
+                    return Err(format!("invalid tile bit \"{s}\""));
+                };
+                let rect = match rect.strip_prefix("XXX") {
+                    Some(idx) => parse_num(idx)?,
+                    None => tcls
+                        .bitrects
+                        .iter()
+                        .find(|(_, info)| info.name == rect)
+                        .ok_or_else(|| format!("unknown bit rect {rect}"))?
+                        .0
+                        .to_idx(),
+                };
+                Ok(TileBit::new(rect, frame, bit))
+            };
+            let parse_setting = |s: &str| -> Result<DecodedSetting, String> {
+                let (name, value) = s
+                    .split_once(" = ")
+                    .ok_or_else(|| format!("expected setting, got \"{s}\""))?;
+                let (bel, attr) = name
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid setting name \"{name}\""))?;
+                Ok(DecodedSetting {
+                    bel: bel.to_string(),
+                    attr: attr.to_string(),
+                    value: value.parse()?,
+                })
+            };
+            let mut dtile = DecodedTile::default();
+            while !p.try_block_end() {
+                let line = p.expect_line()?;
+                let stmt = line.statement()?;
+                let (kw, rest) = stmt.split_once(' ').unwrap_or((stmt, ""));
+                match kw {
+                    "pip" => {
+                        let (dst, src) = rest
+                            .split_once(" = ")
+                            .ok_or_else(|| line.error(format!("invalid pip \"{rest}\"")))?;
+                        let dst = line.check(parse_wire(dst))?;
+                        let src = match src.strip_prefix('~') {
+                            Some(src) => line.check(parse_wire(src))?.neg(),
+                            None => line.check(parse_wire(src))?.pos(),
+                        };
+                        dtile.pips.push((dst, src));
+                    }
+                    "invalid" => dtile.invalid.push(line.check(parse_setting(rest))?),
+                    "unexplained" => dtile.unexplained.push(line.check(parse_bit(rest))?),
+                    _ => dtile.settings.push(line.check(parse_setting(stmt))?),
+                }
+            }
+            if res.tiles.insert(tcrd, dtile).is_some() {
+                return Err(line.error(format!("duplicate tile {tname}")));
+            }
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,476 @@
+use prjcombine_entity::{EntityBundleIndices, EntityBundleItemIndex, EntityId};
+use prjcombine_types::{
+    bsdata::{PolTileBit, TileBit},
+    diff::DiffNode,
+};
+
+use crate::db::{
+    BelAttribute, BelAttributeType, BelInfo, BelInput, BelKind, BelPinIndexing, ConnectorWire,
+    IntDb, PadKind, PinDir, PolTileWireCoord, SwitchBoxItem, TileClass,
+};
+
+fn pin_name(pname: &str, idx: EntityBundleItemIndex, indexing: BelPinIndexing) -> String {
+    match idx {
+        EntityBundleItemIndex::Single => pname.to_string(),
+        EntityBundleItemIndex::Array { index, .. } => {
+            format!("{pname}[{index}]", index = indexing.phys_to_virt(index))
+        }
+    }
+}
+
+fn pin_shape(
+    nonroutable: bool,
+    index: EntityBundleIndices<impl EntityId>,
+    indexing: BelPinIndexing,
+) -> String {
+    let nr = if nonroutable { "nonroutable " } else { "" };
+    match index {
+        EntityBundleIndices::Single(_) => format!("{nr}single"),
+        EntityBundleIndices::Array(range) => {
+            if indexing == Default::default() {
+                format!("{nr}[{n}]", n = range.len())
+            } else {
+                format!(
+                    "{nr}[{msb}:{lsb}]",
+                    msb = indexing.msb_index(range.len()),
+                    lsb = indexing.lsb_index
+                )
+            }
+        }
+    }
+}
+
+impl TileClass {
+    fn dump_bits(&self, bits: &[TileBit]) -> String {
+        format!(
+            "@[{bits}]",
+            bits = bits
+                .iter()
+                .rev()
+                .map(|&bit| self.dump_bit(bit))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn dump_polbits(&self, bits: &[PolTileBit]) -> String {
+        format!(
+            "@[{bits}]",
+            bits = bits
+                .iter()
+                .rev()
+                .map(|&bit| self.dump_polbit(bit))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Builds the diff tree of the tile class.  Switchbox items are keyed by the wires they
+    /// connect, bel pins and attributes by name.
+    pub fn diff_node(&self, db: &IntDb) -> DiffNode {
+        let mut res = DiffNode::leaf(&db.tile_slots[self.slot]);
+        let wire = |w: PolTileWireCoord| w.to_string(db, self);
+        for (cid, name) in &self.cells {
+            res.insert_leaf(format!("cell {name}"), cid);
+        }
+        for rect in self.bitrects.values() {
+            res.insert_leaf(
+                format!("bitrect {name}", name = rect.name),
+                format!(
+                    "{orientation:?} ({rf}{frames}, {rb}{bits})",
+                    orientation = rect.geometry.orientation,
+                    rf = if rect.geometry.rev_frames { "rev " } else { "" },
+                    rb = if rect.geometry.rev_bits { "rev " } else { "" },
+                    frames = rect.geometry.frames,
+                    bits = rect.geometry.bits,
+                ),
+            );
+        }
+        for (slot, bel) in &self.bels {
+            let sname = db.bel_slots.key(slot);
+            match bel {
+                BelInfo::SwitchBox(sb) => {
+                    let mut node = DiffNode::new();
+                    for item in &sb.items {
+                        let (key, item) = match item {
+                            SwitchBoxItem::Mux(mux) => {
+                                let mut item = if mux.bits.is_empty() {
+                                    DiffNode::new()
+                                } else {
+                                    DiffNode::leaf(self.dump_bits(&mux.bits))
+                                };
+                                for (&src, v) in &mux.src {
+                                    if mux.bits.is_empty() {
+                                        item.insert(wire(src), DiffNode::new());
+                                    } else {
+                                        item.insert_leaf(wire(src), format!("0b{v}"));
+                                    }
+                                }
+                                if let Some(ref v) = mux.bits_off {
+                                    item.insert_leaf("off", format!("0b{v}"));
+                                }
+                                (
+                                    format!("mux {dst}", dst = mux.dst.to_string(db, self)),
+                                    item,
+                                )
+                            }
+                            SwitchBoxItem::ProgBuf(buf) => (
+                                format!(
+                                    "progbuf {dst} = {src}",
+                                    dst = buf.dst.to_string(db, self),
+                                    src = wire(buf.src)
+                                ),
+                                DiffNode::leaf(self.dump_polbit(buf.bit)),
+                            ),
+                            SwitchBoxItem::PermaBuf(buf) => (
+                                format!(
+                                    "permabuf {dst} = {src}",
+                                    dst = buf.dst.to_string(db, self),
+                                    src = wire(buf.src)
+                                ),
+                                DiffNode::new(),
+                            ),
+                            SwitchBoxItem::Pass(pass) => (
+                                format!(
+                                    "pass {dst} = {src}",
+                                    dst = pass.dst.to_string(db, self),
+                                    src = pass.src.to_string(db, self)
+                                ),
+                                DiffNode::leaf(self.dump_polbit(pass.bit)),
+                            ),
+                            SwitchBoxItem::BiPass(pass) => (
+                                format!(
+                                    "bipass {a} = {b}",
+                                    a = pass.a.to_string(db, self),
+                                    b = pass.b.to_string(db, self)
+                                ),
+                                DiffNode::leaf(self.dump_polbit(pass.bit)),
+                            ),
+                            SwitchBoxItem::ProgInv(inv) => (
+                                format!(
+                                    "proginv {dst} = {src}",
+                                    dst = inv.dst.to_string(db, self),
+                                    src = inv.src.to_string(db, self)
+                                ),
+                                DiffNode::leaf(self.dump_polbit(inv.bit)),
+                            ),
+                            SwitchBoxItem::ProgDelay(delay) => {
+                                let mut item = if delay.bits.is_empty() {
+                                    DiffNode::leaf(format!("#{n}", n = delay.steps.len()))
+                                } else {
+                                    DiffNode::leaf(self.dump_bits(&delay.bits))
+                                };
+                                if !delay.bits.is_empty() {
+                                    for (idx, v) in delay.steps.iter().enumerate() {
+                                        item.insert_leaf(format!("step {idx}"), format!("0b{v}"));
+                                    }
+                                }
+                                (
+                                    format!(
+                                        "progdelay {dst} = {src}",
+                                        dst = delay.dst.to_string(db, self),
+                                        src = wire(delay.src)
+                                    ),
+                                    item,
+                                )
+                            }
+                            SwitchBoxItem::Bidi(bidi) => (
+                                format!(
+                                    "bidi {conn} {wire}",
+                                    conn = db.conn_slots.key(bidi.conn),
+                                    wire = bidi.wire.to_string(db, self)
+                                ),
+                                DiffNode::leaf(self.dump_polbit(bidi.bit_upstream)),
+                            ),
+                            SwitchBoxItem::PairMux(mux) => {
+                                let mut item = if mux.bits.is_empty() {
+                                    DiffNode::new()
+                                } else {
+                                    DiffNode::leaf(self.dump_bits(&mux.bits))
+                                };
+                                for (src, v) in &mux.src {
+                                    let src = format!(
+                                        "({src0}, {src1})",
+                                        src0 = src[0].map_or("_".to_string(), wire),
+                                        src1 = src[1].map_or("_".to_string(), wire),
+                                    );
+                                    if mux.bits.is_empty() {
+                                        item.insert(src, DiffNode::new());
+                                    } else {
+                                        item.insert_leaf(src, format!("0b{v}"));
+                                    }
+                                }
+                                (
+                                    format!(
+                                        "pair_mux ({dst0}, {dst1})",
+                                        dst0 = mux.dst[0].to_string(db, self),
+                                        dst1 = mux.dst[1].to_string(db, self),
+                                    ),
+                                    item,
+                                )
+                            }
+                            SwitchBoxItem::WireSupport(support) => (
+                                format!(
+                                    "wire_support {wires}",
+                                    wires = support
+                                        .wires
+                                        .iter()
+                                        .map(|w| w.to_string(db, self))
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                ),
+                                if support.bits.is_empty() {
+                                    DiffNode::new()
+                                } else {
+                                    DiffNode::leaf(self.dump_polbits(&support.bits))
+                                },
+                            ),
+                        };
+                        node.insert(key, item);
+                    }
+                    res.insert(format!("switchbox {sname}"), node);
+                }
+                BelInfo::Bel(bel) => {
+                    let BelKind::Class(bcid) = db.bel_slots[slot].kind else {
+                        unreachable!()
+                    };
+                    let bcls = &db.bel_classes[bcid];
+                    let mut node = DiffNode::new();
+                    for (pid, inp) in &bel.inputs {
+                        let (pname, idx) = bcls.inputs.key(pid);
+                        let pname = pin_name(pname, idx, bcls.inputs[pid].indexing);
+                        let val = match *inp {
+                            BelInput::Fixed(w) => wire(w),
+                            BelInput::Invertible(w, bit) => {
+                                format!(
+                                    "^{w} @{bit}",
+                                    w = wire(w.pos()),
+                                    bit = self.dump_polbit(bit)
+                                )
+                            }
+                        };
+                        node.insert_leaf(format!("input {pname}"), val);
+                    }
+                    for (pid, pwires) in &bel.outputs {
+                        let (pname, idx) = bcls.outputs.key(pid);
+                        let pname = pin_name(pname, idx, bcls.outputs[pid].indexing);
+                        node.insert_leaf(
+                            format!("output {pname}"),
+                            pwires
+                                .iter()
+                                .map(|w| w.to_string(db, self))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        );
+                    }
+                    for (pid, pwire) in &bel.bidirs {
+                        let (pname, idx) = bcls.bidirs.key(pid);
+                        let pname = pin_name(pname, idx, bcls.bidirs[pid].indexing);
+                        node.insert_leaf(format!("bidir {pname}"), pwire.to_string(db, self));
+                    }
+                    for (aid, attr) in &bel.attributes {
+                        let key = format!("attribute {aname}", aname = bcls.attributes.key(aid));
+                        match attr {
+                            BelAttribute::BitVec(bits) => {
+                                if let BelAttributeType::BitVecArray(width, _) =
+                                    bcls.attributes[aid].typ
+                                {
+                                    let mut item = DiffNode::new();
+                                    for (i, chunk) in bits.chunks(width).enumerate() {
+                                        item.insert_leaf(
+                                            format!("[{i}]"),
+                                            self.dump_polbits(chunk),
+                                        );
+                                    }
+                                    node.insert(key, item);
+                                } else {
+                                    node.insert_leaf(key, self.dump_polbits(bits));
+                                }
+                            }
+                            BelAttribute::Enum(ebits) => {
+                                let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                                    unreachable!()
+                                };
+                                let ecls = &db.enum_classes[eid];
+                                let mut item = DiffNode::leaf(self.dump_bits(&ebits.bits));
+                                for (k, v) in &ebits.values {
+                                    item.insert_leaf(&ecls.values[k], format!("0b{v}"));
+                                }
+                                node.insert(key, item);
+                            }
+                        }
+                    }
+                    res.insert(format!("bel {sname}"), node);
+                }
+                BelInfo::Legacy(bel) => {
+                    let mut node = DiffNode::leaf("legacy");
+                    for (pn, pin) in &bel.pins {
+                        let dir = match pin.dir {
+                            PinDir::Input => "input",
+                            PinDir::Output => "output",
+                            PinDir::Inout => "inout",
+                        };
+                        node.insert_leaf(
+                            format!("{dir} {pn}"),
+                            pin.wires
+                                .iter()
+                                .map(|w| w.to_string(db, self))
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        );
+                    }
+                    res.insert(format!("bel {sname}"), node);
+                }
+                BelInfo::OldTestMux => unreachable!(),
+                BelInfo::TestMux(tmux) => {
+                    let mut node = if tmux.bits.is_empty() {
+                        DiffNode::leaf(format!("#{n}", n = tmux.groups.len()))
+                    } else {
+                        let mut node = DiffNode::leaf(self.dump_bits(&tmux.bits));
+                        node.insert_leaf("primary", format!("0b{v}", v = tmux.bits_primary));
+                        for (idx, v) in tmux.groups.iter().enumerate() {
+                            node.insert_leaf(format!("test_group {idx}"), format!("0b{v}"));
+                        }
+                        node
+                    };
+                    for (dst, tmwire) in &tmux.wires {
+                        node.insert_leaf(
+                            format!("wire {dst}", dst = dst.to_string(db, self)),
+                            format!(
+                                "{psrc} || [{tsrc}]",
+                                psrc = wire(tmwire.primary_src),
+                                tsrc = tmwire
+                                    .test_src
+                                    .iter()
+                                    .map(|src| src.map_or("none".to_string(), wire))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            ),
+                        );
+                    }
+                    res.insert(format!("test_mux {sname}"), node);
+                }
+            }
+        }
+        res
+    }
+}
+
+impl IntDb {
+    /// Builds the diff tree of the interconnect database.  Every entity is keyed by its kind
+    /// and name, so renumbering does not show up as a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (_, name, ecls) in &self.enum_classes {
+            let mut node = DiffNode::new();
+            for val in ecls.values.values() {
+                node.insert(val, DiffNode::new());
+            }
+            res.insert(format!("enum {name}"), node);
+        }
+        for (_, name, bcls) in &self.bel_classes {
+            let mut node = DiffNode::new();
+            for (index, pname, pin) in bcls.inputs.bundles() {
+                node.insert_leaf(
+                    format!("input {pname}"),
+                    pin_shape(pin.nonroutable, index, pin.indexing),
+                );
+            }
+            for (index, pname, pin) in bcls.outputs.bundles() {
+                node.insert_leaf(
+                    format!("output {pname}"),
+                    pin_shape(pin.nonroutable, index, pin.indexing),
+                );
+            }
+            for (index, pname, pin) in bcls.bidirs.bundles() {
+                node.insert_leaf(
+                    format!("bidir {pname}"),
+                    pin_shape(pin.nonroutable, index, pin.indexing),
+                );
+            }
+            for (index, pname, pad) in bcls.pads.bundles() {
+                let kind = match pad.kind {
+                    PadKind::In => "input",
+                    PadKind::Out => "output",
+                    PadKind::Inout => "inout",
+                    PadKind::Power => "power",
+                    PadKind::Analog => "analog",
+                };
+                let val = match index {
+                    EntityBundleIndices::Single(_) => kind.to_string(),
+                    EntityBundleIndices::Array(range) => format!("[{n}] {kind}", n = range.len()),
+                };
+                node.insert_leaf(format!("pad {pname}"), val);
+            }
+            for (_, aname, attr) in &bcls.attributes {
+                node.insert_leaf(format!("attribute {aname}"), self.dump_typ(attr.typ));
+            }
+            res.insert(format!("bel_class {name}"), node);
+        }
+        for slot in self.region_slots.values() {
+            res.insert(format!("region_slot {slot}"), DiffNode::new());
+        }
+        for (_, name, w) in &self.wires {
+            res.insert_leaf(format!("wire {name}"), w.to_string(self));
+        }
+        for slot in self.tile_slots.values() {
+            res.insert(format!("tile_slot {slot}"), DiffNode::new());
+        }
+        for (_, name, bslot) in &self.bel_slots {
+            let kind = match bslot.kind {
+                BelKind::Routing => "routing",
+                BelKind::Class(bcls) => self.bel_classes.key(bcls),
+                BelKind::Legacy => "legacy",
+            };
+            res.insert_leaf(
+                format!("bel_slot {name}"),
+                format!("{tslot} {kind}", tslot = self.tile_slots[bslot.tile_slot]),
+            );
+        }
+        for (_, name, tcls) in &self.tile_classes {
+            res.insert(format!("tile_class {name}"), tcls.diff_node(self));
+        }
+        for (_, name, cslot) in &self.conn_slots {
+            res.insert_leaf(
+                format!("connector_slot {name}"),
+                format!(
+                    "opposite {oname}",
+                    oname = self.conn_slots.key(cslot.opposite)
+                ),
+            );
+        }
+        for (_, name, ccls) in &self.conn_classes {
+            let mut node = DiffNode::leaf(self.conn_slots.key(ccls.slot));
+            for (w, ti) in &ccls.wires {
+                let val = match ti {
+                    ConnectorWire::BlackHole => "blackhole".to_string(),
+                    &ConnectorWire::Reflect(ow) => format!("reflect {}", self.wires.key(ow)),
+                    &ConnectorWire::Pass(ow) => format!("pass {}", self.wires.key(ow)),
+                };
+                node.insert_leaf(self.wires.key(w), val);
+            }
+            res.insert(format!("connector_class {name}"), node);
+        }
+        for (_, tname, table) in &self.tables {
+            let mut node = DiffNode::new();
+            for (_, fname, &typ) in &table.fields {
+                node.insert_leaf(format!("field {fname}"), self.dump_typ(typ));
+            }
+            for (_, rname, row) in &table.rows {
+                let mut rnode = DiffNode::new();
+                for (fid, value) in row {
+                    rnode.insert_leaf(
+                        table.fields.key(fid),
+                        self.dump_value(table.fields[fid], value),
+                    );
+                }
+                node.insert(format!("row {rname}"), rnode);
+            }
+            res.insert(format!("table {tname}"), node);
+        }
+        for (_, name, &typ) in &self.devdata {
+            res.insert_leaf(format!("device_data {name}"), self.dump_typ(typ));
+        }
+        res
+    }
+}
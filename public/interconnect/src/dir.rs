@@ -1,6 +1,7 @@
 use bincode::{Decode, Encode};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DirH {
     W,
     E,
@@ -20,6 +21,18 @@ impl core::ops::Not for DirH {
     }
 }
 
+impl std::str::FromStr for DirH {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "W" => Ok(DirH::W),
+            "E" => Ok(DirH::E),
+            _ => Err(format!("invalid direction {s}")),
+        }
+    }
+}
+
 impl std::fmt::Display for DirH {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -34,6 +47,7 @@ impl std::fmt::Display for DirH {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DirV {
     S,
     N,
@@ -53,6 +67,18 @@ impl core::ops::Not for DirV {
     }
 }
 
+impl std::str::FromStr for DirV {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "S" => Ok(DirV::S),
+            "N" => Ok(DirV::N),
+            _ => Err(format!("invalid direction {s}")),
+        }
+    }
+}
+
 impl std::fmt::Display for DirV {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -67,6 +93,7 @@ impl std::fmt::Display for DirV {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Dir {
     H(DirH),
     V(DirV),
@@ -108,6 +135,20 @@ impl std::fmt::Display for Dir {
     }
 }
 
+impl std::str::FromStr for Dir {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "W" => Ok(Dir::W),
+            "E" => Ok(Dir::E),
+            "S" => Ok(Dir::S),
+            "N" => Ok(Dir::N),
+            _ => Err(format!("invalid direction {s}")),
+        }
+    }
+}
+
 impl From<DirH> for Dir {
     fn from(value: DirH) -> Self {
         Dir::H(value)
@@ -121,6 +162,7 @@ impl From<DirV> for Dir {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirHV {
     pub h: DirH,
     pub v: DirV,
@@ -174,7 +216,22 @@ impl std::fmt::Display for DirHV {
     }
 }
 
+impl std::str::FromStr for DirHV {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SW" => Ok(DirHV::SW),
+            "SE" => Ok(DirHV::SE),
+            "NW" => Ok(DirHV::NW),
+            "NE" => Ok(DirHV::NE),
+            _ => Err(format!("invalid direction {s}")),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirMap<T> {
     w: T,
     e: T,
@@ -282,6 +339,7 @@ impl<'a, T> IntoIterator for &'a DirMap<T> {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DirPartMap<T> {
     inner: DirMap<Option<T>>,
 }
@@ -114,7 +114,7 @@ impl TileClass {
                                     src = delay.src.to_string(db, self),
                                 )?;
                                 if delay.bits.is_empty() {
-                                    writeln!(o, " #{n}", n = delay.steps.len())?;
+                                    writeln!(o, " #{n};", n = delay.steps.len())?;
                                 } else {
                                     write!(o, " @[")?;
                                     let mut first = true;
@@ -222,7 +222,7 @@ impl TileClass {
                                     }
                                     write!(o, "]")?;
                                 }
-                                writeln!(o)?;
+                                writeln!(o, ";")?;
                             }
                         }
                     }
@@ -619,7 +619,7 @@ impl IntDb {
                 }
                 writeln!(
                     o,
-                    ": {kind}",
+                    ": {kind};",
                     kind = match pad.kind {
                         PadKind::In => "input",
                         PadKind::Out => "output",
@@ -645,14 +645,35 @@ impl IntDb {
         for (_, k, &w) in &self.wires {
             writeln!(o, "\twire {k}: {w};", w = w.to_string(self))?;
         }
+        // Bel slots and tile classes are listed under their tile slot, and connector classes
+        // under their connector slot, each slot once.  When the ids are not ordered by slot,
+        // every item of the kind is given its `#id` explicitly.
+        let bslot_ids = !self
+            .bel_slots
+            .values()
+            .map(|bslot| bslot.tile_slot)
+            .is_sorted();
+        let tcls_ids = !self.tile_classes.values().map(|tcls| tcls.slot).is_sorted();
+        let ccls_ids = !self.conn_classes.values().map(|ccls| ccls.slot).is_sorted();
+        let id_prefix = |explicit: bool, id: usize| {
+            if explicit {
+                format!("#{id} ")
+            } else {
+                String::new()
+            }
+        };
         for (tslot, tsname) in &self.tile_slots {
             writeln!(o)?;
             writeln!(o, "\ttile_slot {tsname} {{")?;
-            for (_, name, bslot) in &self.bel_slots {
+            for (bslot_id, name, bslot) in &self.bel_slots {
                 if bslot.tile_slot != tslot {
                     continue;
                 }
-                write!(o, "\t\tbel_slot {name}: ")?;
+                write!(
+                    o,
+                    "\t\tbel_slot {id}{name}: ",
+                    id = id_prefix(bslot_ids, bslot_id.to_idx())
+                )?;
                 match bslot.kind {
                     BelKind::Routing => write!(o, "routing")?,
                     BelKind::Class(bcls) => write!(o, "{}", self.bel_classes.key(bcls))?,
@@ -660,12 +681,16 @@ impl IntDb {
                 }
                 writeln!(o, ";")?
             }
-            for (_, name, tcls) in &self.tile_classes {
+            for (tcid, name, tcls) in &self.tile_classes {
                 if tcls.slot != tslot {
                     continue;
                 }
                 writeln!(o,)?;
-                writeln!(o, "\t\ttile_class {name} {{")?;
+                writeln!(
+                    o,
+                    "\t\ttile_class {id}{name} {{",
+                    id = id_prefix(tcls_ids, tcid.to_idx())
+                )?;
                 tcls.dump(self, o)?;
                 writeln!(o, "\t\t}}")?;
             }
@@ -679,12 +704,16 @@ impl IntDb {
                 "\t\topposite {oname};",
                 oname = self.conn_slots.key(cslot.opposite)
             )?;
-            for (_, name, ccls) in &self.conn_classes {
+            for (ccid, name, ccls) in &self.conn_classes {
                 if ccls.slot != csid {
                     continue;
                 }
                 writeln!(o,)?;
-                writeln!(o, "\t\tconnector_class {name} {{",)?;
+                writeln!(
+                    o,
+                    "\t\tconnector_class {id}{name} {{",
+                    id = id_prefix(ccls_ids, ccid.to_idx())
+                )?;
                 for (w, ti) in &ccls.wires {
                     let wn = &self.wires.key(w);
                     match ti {
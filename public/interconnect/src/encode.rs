@@ -0,0 +1,609 @@
+//! Tile-level bitstream encoding.
+//!
+//! The inverse of [`crate::decode`]: takes a per-tile configuration in the same form as
+//! produced by the decoder, and sets the corresponding bitstream bits.  Items that are not
+//! mentioned in the configuration are set to their unconfigured state (all raw bits clear for
+//! enums and multiplexers, all bits logically clear for bit-vector attributes, primary mode
+//! for test multiplexers).
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use prjcombine_entity::EntityVec;
+use prjcombine_types::{
+    bitrect::BitRect,
+    bitvec::BitVec,
+    bsdata::{BitRectId, BsData, DbValue, PolTileBit, TileBit, TileItemKind},
+};
+
+use crate::{
+    db::{
+        BelAttribute, BelAttributeType, BelInfo, BelInput, BelKind, PolTileWireCoord,
+        SwitchBoxItem, TileClass, TileWireCoord,
+    },
+    decode::{DecodedBitstream, DecodedTile, input_name, support_attr},
+    grid::{ExpandedGrid, TileCoord},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    UnknownTile {
+        tile: TileCoord,
+    },
+    UnknownSetting {
+        tile: String,
+        bel: String,
+        attr: String,
+    },
+    InvalidValue {
+        tile: String,
+        bel: String,
+        attr: String,
+        value: DbValue,
+    },
+    UnknownPip {
+        tile: String,
+        dst: String,
+        src: String,
+    },
+    // more than one source selected for a single multiplexer
+    MultipleSources {
+        tile: String,
+        dst: String,
+    },
+    // an item needs a bit set that has no location in the bitstream (a database placeholder)
+    UnmappedBit {
+        tile: String,
+        bit: String,
+        item: String,
+    },
+    // the same bitstream bit is required to be both set and clear
+    Conflict {
+        tile: String,
+        bit: String,
+        item: String,
+        other_tile: String,
+        other_item: String,
+    },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::UnknownTile { tile } => write!(f, "no tile at {tile:?}"),
+            EncodeError::UnknownSetting { tile, bel, attr } => {
+                write!(f, "{tile}: unknown setting {bel}:{attr}")
+            }
+            EncodeError::InvalidValue {
+                tile,
+                bel,
+                attr,
+                value,
+            } => write!(f, "{tile}: invalid value {value} for {bel}:{attr}"),
+            EncodeError::UnknownPip { tile, dst, src } => {
+                write!(f, "{tile}: unknown pip {dst} = {src}")
+            }
+            EncodeError::MultipleSources { tile, dst } => {
+                write!(f, "{tile}: multiple sources selected for {dst}")
+            }
+            EncodeError::UnmappedBit { tile, bit, item } => {
+                write!(
+                    f,
+                    "{tile}: bit {bit} of {item} is not mapped into the bitstream"
+                )
+            }
+            EncodeError::Conflict {
+                tile,
+                bit,
+                item,
+                other_tile,
+                other_item,
+            } => write!(
+                f,
+                "{tile}: bit {bit} of {item} conflicts with {other_tile} {other_item}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Setting(&'a DbValue),
+    Raw(&'a BitVec),
+}
+
+struct Assignment {
+    tcrd: TileCoord,
+    bit: TileBit,
+    item: String,
+    val: bool,
+}
+
+struct TileEncoder<'a, R: BitRect, F: FnMut(R::BitPos, bool)> {
+    egrid: &'a ExpandedGrid<'a>,
+    tcrd: TileCoord,
+    tcls: &'a TileClass,
+    rects: &'a EntityVec<BitRectId, R>,
+    set_bit: &'a mut F,
+    assigned: &'a mut HashMap<R::BitPos, Assignment>,
+    defaults: &'a mut Vec<(R::BitPos, bool)>,
+    settings: HashMap<String, Value<'a>>,
+    pips: HashSet<(TileWireCoord, PolTileWireCoord)>,
+}
+
+impl<'a, R: BitRect, F: FnMut(R::BitPos, bool)> TileEncoder<'a, R, F> {
+    fn tile_name(&self, tcrd: TileCoord) -> String {
+        format!(
+            "{tcrd} {kind}",
+            tcrd = tcrd.to_string(self.egrid.db),
+            kind = self.egrid.db.tile_classes.key(self.egrid[tcrd].class)
+        )
+    }
+
+    fn wire_name(&self, wire: TileWireCoord) -> String {
+        wire.to_string(self.egrid.db, self.tcls)
+    }
+
+    fn put(&mut self, bit: TileBit, val: bool, item: &str) -> Result<(), EncodeError> {
+        let Some(rect) = self.rects.get(bit.rect) else {
+            if !val {
+                return Ok(());
+            }
+            return Err(EncodeError::UnmappedBit {
+                tile: self.tile_name(self.tcrd),
+                bit: self.tcls.dump_bit(bit),
+                item: item.to_string(),
+            });
+        };
+        let pos = rect.xlat_pos_fwd((bit.frame, bit.bit));
+        if let Some(prev) = self.assigned.get(&pos) {
+            if prev.val == val {
+                return Ok(());
+            }
+            let otcls = &self.egrid.db[self.egrid[prev.tcrd].class];
+            return Err(EncodeError::Conflict {
+                tile: self.tile_name(self.tcrd),
+                bit: self.tcls.dump_bit(bit),
+                item: item.to_string(),
+                other_tile: format!(
+                    "{tile} bit {bit}",
+                    tile = self.tile_name(prev.tcrd),
+                    bit = otcls.dump_bit(prev.bit)
+                ),
+                other_item: prev.item.clone(),
+            });
+        }
+        self.assigned.insert(
+            pos,
+            Assignment {
+                tcrd: self.tcrd,
+                bit,
+                item: item.to_string(),
+                val,
+            },
+        );
+        (self.set_bit)(pos, val);
+        Ok(())
+    }
+
+    fn put_raw(&mut self, bits: &[TileBit], val: &BitVec, item: &str) -> Result<(), EncodeError> {
+        for (&bit, v) in bits.iter().zip(val) {
+            self.put(bit, v, item)?;
+        }
+        Ok(())
+    }
+
+    fn default(&mut self, bit: TileBit, val: bool) {
+        let Some(rect) = self.rects.get(bit.rect) else {
+            return;
+        };
+        let pos = rect.xlat_pos_fwd((bit.frame, bit.bit));
+        self.defaults.push((pos, val));
+    }
+
+    fn take(&mut self, bel: &str, attr: &impl Fn() -> String) -> Option<(String, Value<'a>)> {
+        if self.settings.is_empty() {
+            return None;
+        }
+        let attr = attr();
+        let value = self.settings.remove(&format!("{bel}:{attr}"))?;
+        Some((attr, value))
+    }
+
+    fn invalid_value(&self, bel: &str, attr: &str, value: Value) -> EncodeError {
+        EncodeError::InvalidValue {
+            tile: self.tile_name(self.tcrd),
+            bel: bel.to_string(),
+            attr: attr.to_string(),
+            value: match value {
+                Value::Setting(value) => value.clone(),
+                Value::Raw(value) => value.clone().into(),
+            },
+        }
+    }
+
+    fn bitvec_item(
+        &mut self,
+        bel: &str,
+        attr: impl Fn() -> String,
+        bits: &[PolTileBit],
+    ) -> Result<(), EncodeError> {
+        match self.take(bel, &attr) {
+            None => {
+                for &bit in bits {
+                    if bit.inv {
+                        self.default(bit.bit, true);
+                    }
+                }
+            }
+            Some((attr, Value::Setting(DbValue::BitVec(val)))) if val.len() == bits.len() => {
+                let item = format!("{bel}:{attr}");
+                for (&bit, v) in bits.iter().zip(val) {
+                    self.put(bit.bit, v ^ bit.inv, &item)?;
+                }
+            }
+            Some((attr, Value::Raw(val))) if val.len() == bits.len() => {
+                let item = format!("{bel}:{attr}");
+                for (&bit, v) in bits.iter().zip(val) {
+                    self.put(bit.bit, v, &item)?;
+                }
+            }
+            Some((attr, value)) => return Err(self.invalid_value(bel, &attr, value)),
+        }
+        Ok(())
+    }
+
+    fn enum_item<'b>(
+        &mut self,
+        bel: &str,
+        attr: impl Fn() -> String,
+        bits: &[TileBit],
+        lookup: impl Fn(&str) -> Option<&'b BitVec>,
+    ) -> Result<(), EncodeError> {
+        match self.take(bel, &attr) {
+            None => (),
+            Some((attr, value @ Value::Setting(DbValue::String(name)))) => match lookup(name) {
+                Some(val) => self.put_raw(bits, val, &format!("{bel}:{attr}"))?,
+                None => return Err(self.invalid_value(bel, &attr, value)),
+            },
+            Some((attr, Value::Raw(val))) if val.len() == bits.len() => {
+                self.put_raw(bits, val, &format!("{bel}:{attr}"))?
+            }
+            Some((attr, value)) => return Err(self.invalid_value(bel, &attr, value)),
+        }
+        Ok(())
+    }
+
+    fn steps_item(
+        &mut self,
+        bel: &str,
+        attr: impl Fn() -> String,
+        bits: &[TileBit],
+        steps: &[BitVec],
+        default: Option<&BitVec>,
+    ) -> Result<(), EncodeError> {
+        match self.take(bel, &attr) {
+            None => {
+                if let Some(default) = default {
+                    for (&bit, v) in bits.iter().zip(default) {
+                        self.default(bit, v);
+                    }
+                }
+            }
+            Some((attr, value @ Value::Setting(&DbValue::Int(step)))) => {
+                match steps.get(step as usize) {
+                    Some(val) => self.put_raw(bits, val, &format!("{bel}:{attr}"))?,
+                    None => return Err(self.invalid_value(bel, &attr, value)),
+                }
+            }
+            Some((attr, Value::Raw(val))) if val.len() == bits.len() => {
+                self.put_raw(bits, val, &format!("{bel}:{attr}"))?
+            }
+            Some((attr, value)) => return Err(self.invalid_value(bel, &attr, value)),
+        }
+        Ok(())
+    }
+
+    fn pip_item(
+        &mut self,
+        dst: TileWireCoord,
+        src: PolTileWireCoord,
+        bit: PolTileBit,
+    ) -> Result<(), EncodeError> {
+        if self.pips.remove(&(dst, src)) {
+            let item = format!(
+                "PIP.{dst}.{src}",
+                dst = self.wire_name(dst),
+                src = src.to_string(self.egrid.db, self.tcls)
+            );
+            self.put(bit.bit, !bit.inv, &item)?;
+        } else if bit.inv {
+            self.default(bit.bit, true);
+        }
+        Ok(())
+    }
+
+    fn encode_switchbox(&mut self, bel: &str, items: &[SwitchBoxItem]) -> Result<(), EncodeError> {
+        let db = self.egrid.db;
+        let tcls = self.tcls;
+        for item in items {
+            match item {
+                SwitchBoxItem::Mux(mux) => {
+                    if mux.bits.is_empty() {
+                        continue;
+                    }
+                    let attr = || format!("MUX.{dst}", dst = mux.dst.to_string(db, tcls));
+                    let mut selected = None;
+                    if !self.pips.is_empty() {
+                        for (&src, val) in &mux.src {
+                            if self.pips.remove(&(mux.dst, src)) {
+                                if selected.is_some() {
+                                    return Err(EncodeError::MultipleSources {
+                                        tile: self.tile_name(self.tcrd),
+                                        dst: self.wire_name(mux.dst),
+                                    });
+                                }
+                                selected = Some(val);
+                            }
+                        }
+                    }
+                    if let Some(val) = selected {
+                        self.put_raw(&mux.bits, val, &format!("{bel}:{attr}", attr = attr()))?;
+                    }
+                    self.enum_item(bel, attr, &mux.bits, |_| None)?;
+                }
+                SwitchBoxItem::ProgBuf(buf) => self.pip_item(buf.dst, buf.src, buf.bit)?,
+                SwitchBoxItem::PermaBuf(_) => (),
+                SwitchBoxItem::Pass(pass) => self.pip_item(pass.dst, pass.src.pos(), pass.bit)?,
+                SwitchBoxItem::BiPass(pass) => {
+                    let ab = self.pips.remove(&(pass.a, pass.b.pos()));
+                    let ba = self.pips.remove(&(pass.b, pass.a.pos()));
+                    if ab || ba {
+                        self.pips.insert((pass.a, pass.b.pos()));
+                    }
+                    self.pip_item(pass.a, pass.b.pos(), pass.bit)?;
+                }
+                SwitchBoxItem::ProgInv(inv) => {
+                    let attr = || format!("INV.{dst}", dst = inv.dst.to_string(db, tcls));
+                    self.bitvec_item(bel, attr, &[inv.bit])?;
+                }
+                SwitchBoxItem::ProgDelay(delay) => {
+                    let attr = || format!("DELAY.{dst}", dst = delay.dst.to_string(db, tcls));
+                    self.steps_item(bel, attr, &delay.bits, &delay.steps, None)?;
+                }
+                SwitchBoxItem::Bidi(bidi) => {
+                    let attr = || {
+                        format!(
+                            "BIDI.{conn}.{wire}",
+                            conn = db.conn_slots.key(bidi.conn),
+                            wire = bidi.wire.to_string(db, tcls)
+                        )
+                    };
+                    self.bitvec_item(bel, attr, &[bidi.bit_upstream])?;
+                }
+                SwitchBoxItem::PairMux(mux) => {
+                    let attr = || {
+                        format!(
+                            "MUX.{dst0}.{dst1}",
+                            dst0 = mux.dst[0].to_string(db, tcls),
+                            dst1 = mux.dst[1].to_string(db, tcls)
+                        )
+                    };
+                    let cur = mux.dst.map(|dst| {
+                        self.pips
+                            .iter()
+                            .find(|&&(pdst, _)| pdst == dst)
+                            .map(|&(_, src)| src)
+                    });
+                    if cur != [None, None] {
+                        let Some(val) = mux
+                            .src
+                            .iter()
+                            .find(|&(src, _)| *src == cur)
+                            .or_else(|| {
+                                mux.src.iter().find(|&(src, _)| {
+                                    src.iter().zip(&cur).all(|(s, c)| c.is_none() || s == c)
+                                })
+                            })
+                            .map(|(_, val)| val)
+                        else {
+                            let k = if cur[0].is_some() { 0 } else { 1 };
+                            return Err(EncodeError::UnknownPip {
+                                tile: self.tile_name(self.tcrd),
+                                dst: self.wire_name(mux.dst[k]),
+                                src: cur[k].unwrap().to_string(db, self.tcls),
+                            });
+                        };
+                        for (&dst, src) in mux.dst.iter().zip(cur) {
+                            if let Some(src) = src {
+                                self.pips.remove(&(dst, src));
+                            }
+                        }
+                        self.put_raw(&mux.bits, val, &format!("{bel}:{attr}", attr = attr()))?;
+                    }
+                    self.enum_item(bel, attr, &mux.bits, |_| None)?;
+                }
+                SwitchBoxItem::WireSupport(support) => {
+                    let attr = || support_attr(db, tcls, &support.wires);
+                    self.bitvec_item(bel, attr, &support.bits)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_tile(
+        &mut self,
+        bsdata: &BsData,
+        config: Option<&DecodedTile>,
+    ) -> Result<(), EncodeError> {
+        let db = self.egrid.db;
+        for (slot, bel) in &self.tcls.bels {
+            let bname = db.bel_slots.key(slot);
+            match bel {
+                BelInfo::SwitchBox(sb) => self.encode_switchbox(bname, &sb.items)?,
+                BelInfo::Bel(bel) => {
+                    let BelKind::Class(bcid) = db.bel_slots[slot].kind else {
+                        unreachable!()
+                    };
+                    let bcls = &db.bel_classes[bcid];
+                    for (pid, inp) in &bel.inputs {
+                        let BelInput::Invertible(_, bit) = *inp else {
+                            continue;
+                        };
+                        let attr = || format!("INV.{pname}", pname = input_name(bcls, pid));
+                        self.bitvec_item(bname, attr, &[bit])?;
+                    }
+                    for (aid, attr) in &bel.attributes {
+                        let aname = bcls.attributes.key(aid);
+                        match attr {
+                            BelAttribute::BitVec(bits) => {
+                                self.bitvec_item(bname, || aname.clone(), bits)?
+                            }
+                            BelAttribute::Enum(ebits) => {
+                                let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                                    unreachable!()
+                                };
+                                let ecls = &db.enum_classes[eid];
+                                self.enum_item(
+                                    bname,
+                                    || aname.clone(),
+                                    &ebits.bits,
+                                    |name| ebits.values.get(ecls.values.get(name)?),
+                                )?;
+                            }
+                        }
+                    }
+                }
+                BelInfo::TestMux(tmux) => self.steps_item(
+                    bname,
+                    || "TEST_GROUP".to_string(),
+                    &tmux.bits,
+                    &tmux.groups,
+                    Some(&tmux.bits_primary),
+                )?,
+                BelInfo::OldTestMux | BelInfo::Legacy(_) => (),
+            }
+        }
+        if let Some(btile) = bsdata
+            .tiles
+            .get(db.tile_classes.key(self.egrid[self.tcrd].class))
+        {
+            for (name, item) in &btile.items {
+                let (bel, attr) = name.split_once(':').unwrap_or(("", name));
+                match item.kind {
+                    TileItemKind::BitVec { .. } => {
+                        self.bitvec_item(bel, || attr.to_string(), &item.as_bitvec())?
+                    }
+                    TileItemKind::Enum { ref values } => self.enum_item(
+                        bel,
+                        || attr.to_string(),
+                        &item.bits,
+                        |name| values.get(name),
+                    )?,
+                }
+            }
+        }
+        if let Some(config) = config {
+            for &bit in &config.unexplained {
+                self.put(bit, true, "unexplained")?;
+            }
+        }
+        if let Some(key) = self.settings.keys().next() {
+            let (bel, attr) = key.split_once(':').unwrap();
+            return Err(EncodeError::UnknownSetting {
+                tile: self.tile_name(self.tcrd),
+                bel: bel.to_string(),
+                attr: attr.to_string(),
+            });
+        }
+        if let Some(&(dst, src)) = self.pips.iter().next() {
+            return Err(EncodeError::UnknownPip {
+                tile: self.tile_name(self.tcrd),
+                dst: self.wire_name(dst),
+                src: src.to_string(db, self.tcls),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a tile configuration into the bitstream.
+///
+/// `tile_bits` gives the bit rectangles of a tile (as returned by the `tile_bits` method of
+/// the family's `ExpandedDevice`), and `set_bit` writes a single bit of the bitstream.  The
+/// bitstream is assumed to start out blank: bits that are clear in the unconfigured state
+/// of an item are not written.
+pub fn encode_tiles<R: BitRect>(
+    egrid: &ExpandedGrid,
+    bsdata: &BsData,
+    config: &DecodedBitstream,
+    tile_bits: impl Fn(TileCoord) -> EntityVec<BitRectId, R>,
+    mut set_bit: impl FnMut(R::BitPos, bool),
+) -> Result<(), EncodeError> {
+    let db = egrid.db;
+    for &tcrd in config.tiles.keys() {
+        if egrid.die.get(tcrd.die).is_none()
+            || !egrid.cols(tcrd.die).contains(tcrd.col)
+            || !egrid.rows(tcrd.die).contains(tcrd.row)
+            || egrid.get_tile(tcrd).is_none()
+        {
+            return Err(EncodeError::UnknownTile { tile: tcrd });
+        }
+    }
+    let mut assigned = HashMap::new();
+    let mut defaults = vec![];
+    for (tcrd, tile) in egrid.tiles() {
+        let tcls = &db[tile.class];
+        let config = config.tiles.get(&tcrd);
+        if tcls.bitrects.is_empty() && config.is_none() {
+            continue;
+        }
+        let rects = tile_bits(tcrd);
+        let mut settings = HashMap::new();
+        let mut pips = HashSet::new();
+        if let Some(config) = config {
+            for setting in &config.settings {
+                settings.insert(
+                    format!("{bel}:{attr}", bel = setting.bel, attr = setting.attr),
+                    Value::Setting(&setting.value),
+                );
+            }
+            for setting in &config.invalid {
+                let DbValue::BitVec(ref val) = setting.value else {
+                    return Err(EncodeError::InvalidValue {
+                        tile: tcrd.to_string(db),
+                        bel: setting.bel.clone(),
+                        attr: setting.attr.clone(),
+                        value: setting.value.clone(),
+                    });
+                };
+                settings.insert(
+                    format!("{bel}:{attr}", bel = setting.bel, attr = setting.attr),
+                    Value::Raw(val),
+                );
+            }
+            pips.extend(config.pips.iter().copied());
+        }
+        let mut encoder = TileEncoder {
+            egrid,
+            tcrd,
+            tcls,
+            rects: &rects,
+            set_bit: &mut set_bit,
+            assigned: &mut assigned,
+            defaults: &mut defaults,
+            settings,
+            pips,
+        };
+        encoder.encode_tile(bsdata, config)?;
+    }
+    for (pos, val) in defaults {
+        if !assigned.contains_key(&pos) {
+            set_bit(pos, val);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,373 @@
+//! Device-wide routing graph.
+//!
+//! [`ExpandedGrid::routing_graph`] walks every tile of the device once and builds a graph
+//! whose nodes are resolved interconnect wires and whose edges are the PIPs of all
+//! switchboxes.  The result is self-contained and can be serialized with bincode, so that
+//! a place-and-route tool does not have to resolve wires through connectors on every query.
+//!
+//! Nodes and PIPs are also grouped into timing classes: all members of a class are
+//! structurally identical, so a timing model needs one delay per class rather than one per
+//! node or PIP.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use bincode::{Decode, Encode};
+use prjcombine_entity::{
+    EntityId, EntityVec,
+    id::{EntityIdU32, EntityTag},
+};
+
+use crate::{
+    db::{BelInfo, SwitchBoxItem, TileWireCoord, WireSlotId},
+    grid::{ExpandedGrid, TileCoord, WireCoord},
+};
+
+pub struct RoutingNodeTag;
+impl EntityTag for RoutingNodeTag {
+    const PREFIX: &'static str = "NODE";
+}
+pub struct RoutingPipTag;
+impl EntityTag for RoutingPipTag {
+    const PREFIX: &'static str = "PIP";
+}
+pub struct RoutingNodeClassTag;
+impl EntityTag for RoutingNodeClassTag {
+    const PREFIX: &'static str = "NODECLS";
+}
+pub struct RoutingPipClassTag;
+impl EntityTag for RoutingPipClassTag {
+    const PREFIX: &'static str = "PIPCLS";
+}
+pub type RoutingNodeId = EntityIdU32<RoutingNodeTag>;
+pub type RoutingPipId = EntityIdU32<RoutingPipTag>;
+pub type RoutingNodeClassId = EntityIdU32<RoutingNodeClassTag>;
+pub type RoutingPipClassId = EntityIdU32<RoutingPipClassTag>;
+
+/// The kind of [`SwitchBoxItem`] a PIP comes from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RoutingPipKind {
+    Mux,
+    ProgBuf,
+    PermaBuf,
+    Pass,
+    BiPass,
+    ProgInv,
+    ProgDelay,
+    PairMux,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingNode {
+    /// The canonical wire, as returned by [`ExpandedGrid::resolve_wire`].
+    pub wire: WireCoord,
+    /// All wires referenced by some tile that resolve to this node, sorted.
+    pub members: Vec<WireCoord>,
+    /// The PIPs driven by this node.
+    pub pips_fwd: Vec<RoutingPipId>,
+    /// The PIPs driving this node.
+    pub pips_bwd: Vec<RoutingPipId>,
+    pub class: RoutingNodeClassId,
+}
+
+/// Nodes of the same wire slot whose members form the same pattern around the canonical
+/// wire.  Nodes cut short at the die edge lose some members and get a class of their own.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingNodeClass {
+    pub slot: WireSlotId,
+    /// The members of the node relative to its canonical wire, sorted.
+    pub members: Vec<RoutingNodeMember>,
+}
+
+/// A wire of a node, given by its slot and its cell offset from the canonical wire.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingNodeMember {
+    pub die: i32,
+    pub col: i32,
+    pub row: i32,
+    pub slot: WireSlotId,
+}
+
+impl RoutingNodeMember {
+    pub fn new(canonical: WireCoord, wire: WireCoord) -> Self {
+        let delta = |a: usize, b: usize| b as i32 - a as i32;
+        Self {
+            die: delta(canonical.cell.die.to_idx(), wire.cell.die.to_idx()),
+            col: delta(canonical.cell.col.to_idx(), wire.cell.col.to_idx()),
+            row: delta(canonical.cell.row.to_idx(), wire.cell.row.to_idx()),
+            slot: wire.slot,
+        }
+    }
+}
+
+/// PIPs of the same kind between the same pair of wire slots.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingPipClass {
+    pub kind: RoutingPipKind,
+    pub slot_out: WireSlotId,
+    pub slot_in: WireSlotId,
+    pub inv: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingPip {
+    pub tile: TileCoord,
+    pub kind: RoutingPipKind,
+    pub node_out: RoutingNodeId,
+    pub node_in: RoutingNodeId,
+    pub tile_wire_out: TileWireCoord,
+    pub tile_wire_in: TileWireCoord,
+    /// True if the PIP inverts the signal.
+    pub inv: bool,
+    pub class: RoutingPipClassId,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoutingGraph {
+    pub nodes: EntityVec<RoutingNodeId, RoutingNode>,
+    pub pips: EntityVec<RoutingPipId, RoutingPip>,
+    pub node_classes: EntityVec<RoutingNodeClassId, RoutingNodeClass>,
+    pub pip_classes: EntityVec<RoutingPipClassId, RoutingPipClass>,
+    /// Maps canonical wires to nodes.
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
+    pub wire_nodes: HashMap<WireCoord, RoutingNodeId>,
+}
+
+impl RoutingGraph {
+    /// Looks up the node containing the given (not necessarily canonical) wire.
+    pub fn node(&self, egrid: &ExpandedGrid, wire: WireCoord) -> Option<RoutingNodeId> {
+        self.wire_nodes.get(&egrid.resolve_wire(wire)?).copied()
+    }
+}
+
+struct GraphBuilder<'a, 'b> {
+    egrid: &'a ExpandedGrid<'b>,
+    graph: RoutingGraph,
+    members: HashSet<WireCoord>,
+    pip_classes: HashMap<RoutingPipClass, RoutingPipClassId>,
+}
+
+impl GraphBuilder<'_, '_> {
+    fn node(&mut self, tcrd: TileCoord, tw: TileWireCoord) -> Option<RoutingNodeId> {
+        let raw = self.egrid.tile_wire(tcrd, tw);
+        let wire = self.egrid.resolve_wire(raw)?;
+        let nid = *self.graph.wire_nodes.entry(wire).or_insert_with(|| {
+            self.graph.nodes.push(RoutingNode {
+                wire,
+                members: vec![],
+                pips_fwd: vec![],
+                pips_bwd: vec![],
+                // assigned in finish, once all members are known
+                class: RoutingNodeClassId::from_idx(0),
+            })
+        });
+        if self.members.insert(raw) {
+            self.graph.nodes[nid].members.push(raw);
+        }
+        Some(nid)
+    }
+
+    fn pip(
+        &mut self,
+        tcrd: TileCoord,
+        kind: RoutingPipKind,
+        tile_wire_out: TileWireCoord,
+        tile_wire_in: TileWireCoord,
+        inv: bool,
+    ) {
+        let Some(node_out) = self.node(tcrd, tile_wire_out) else {
+            return;
+        };
+        let Some(node_in) = self.node(tcrd, tile_wire_in) else {
+            return;
+        };
+        let class = RoutingPipClass {
+            kind,
+            slot_out: tile_wire_out.wire,
+            slot_in: tile_wire_in.wire,
+            inv,
+        };
+        let class = *self
+            .pip_classes
+            .entry(class)
+            .or_insert_with(|| self.graph.pip_classes.push(class));
+        let pid = self.graph.pips.push(RoutingPip {
+            tile: tcrd,
+            kind,
+            node_out,
+            node_in,
+            tile_wire_out,
+            tile_wire_in,
+            inv,
+            class,
+        });
+        self.graph.nodes[node_out].pips_bwd.push(pid);
+        self.graph.nodes[node_in].pips_fwd.push(pid);
+    }
+
+    fn finish(self) -> RoutingGraph {
+        let mut graph = self.graph;
+        let mut node_classes = HashMap::new();
+        for node in graph.nodes.values_mut() {
+            node.members.sort();
+            let mut members: Vec<_> = node
+                .members
+                .iter()
+                .map(|&wire| RoutingNodeMember::new(node.wire, wire))
+                .collect();
+            members.sort();
+            let class = RoutingNodeClass {
+                slot: node.wire.slot,
+                members,
+            };
+            node.class = *node_classes
+                .entry(class.clone())
+                .or_insert_with(|| graph.node_classes.push(class));
+        }
+        graph
+    }
+}
+
+impl ExpandedGrid<'_> {
+    /// Builds the routing graph of the whole device.
+    pub fn routing_graph(&self) -> RoutingGraph {
+        let mut builder = GraphBuilder {
+            egrid: self,
+            graph: RoutingGraph::default(),
+            members: HashSet::new(),
+            pip_classes: HashMap::new(),
+        };
+        for (tcrd, tile) in self.tiles() {
+            let tcls = &self.db[tile.class];
+            for bel in tcls.bels.values() {
+                match bel {
+                    BelInfo::SwitchBox(sb) => {
+                        for item in &sb.items {
+                            match *item {
+                                SwitchBoxItem::Mux(ref mux) => {
+                                    for src in mux.src.keys() {
+                                        builder.pip(
+                                            tcrd,
+                                            RoutingPipKind::Mux,
+                                            mux.dst,
+                                            src.tw,
+                                            src.inv,
+                                        );
+                                    }
+                                }
+                                SwitchBoxItem::ProgBuf(buf) => builder.pip(
+                                    tcrd,
+                                    RoutingPipKind::ProgBuf,
+                                    buf.dst,
+                                    buf.src.tw,
+                                    buf.src.inv,
+                                ),
+                                SwitchBoxItem::PermaBuf(buf) => builder.pip(
+                                    tcrd,
+                                    RoutingPipKind::PermaBuf,
+                                    buf.dst,
+                                    buf.src.tw,
+                                    buf.src.inv,
+                                ),
+                                SwitchBoxItem::Pass(pass) => builder.pip(
+                                    tcrd,
+                                    RoutingPipKind::Pass,
+                                    pass.dst,
+                                    pass.src,
+                                    false,
+                                ),
+                                SwitchBoxItem::BiPass(pass) => {
+                                    builder.pip(
+                                        tcrd,
+                                        RoutingPipKind::BiPass,
+                                        pass.a,
+                                        pass.b,
+                                        false,
+                                    );
+                                    builder.pip(
+                                        tcrd,
+                                        RoutingPipKind::BiPass,
+                                        pass.b,
+                                        pass.a,
+                                        false,
+                                    );
+                                }
+                                SwitchBoxItem::ProgInv(inv) => {
+                                    builder.pip(
+                                        tcrd,
+                                        RoutingPipKind::ProgInv,
+                                        inv.dst,
+                                        inv.src,
+                                        false,
+                                    );
+                                    builder.pip(
+                                        tcrd,
+                                        RoutingPipKind::ProgInv,
+                                        inv.dst,
+                                        inv.src,
+                                        true,
+                                    );
+                                }
+                                SwitchBoxItem::ProgDelay(ref delay) => builder.pip(
+                                    tcrd,
+                                    RoutingPipKind::ProgDelay,
+                                    delay.dst,
+                                    delay.src.tw,
+                                    delay.src.inv,
+                                ),
+                                SwitchBoxItem::PairMux(ref mux) => {
+                                    // a source may appear in several selections,
+                                    // paired with different sources on the other half
+                                    let pips: BTreeSet<_> = mux
+                                        .src
+                                        .keys()
+                                        .flat_map(|src| mux.dst.into_iter().zip(*src))
+                                        .filter_map(|(dst, src)| Some((dst, src?)))
+                                        .collect();
+                                    for (dst, src) in pips {
+                                        builder.pip(
+                                            tcrd,
+                                            RoutingPipKind::PairMux,
+                                            dst,
+                                            src.tw,
+                                            src.inv,
+                                        );
+                                    }
+                                }
+                                SwitchBoxItem::Bidi(_) | SwitchBoxItem::WireSupport(_) => (),
+                            }
+                        }
+                    }
+                    BelInfo::Bel(bel) => {
+                        for inp in bel.inputs.values() {
+                            builder.node(tcrd, inp.wire());
+                        }
+                        for wires in bel.outputs.values() {
+                            for &wire in wires {
+                                builder.node(tcrd, wire);
+                            }
+                        }
+                        for &wire in bel.bidirs.values() {
+                            builder.node(tcrd, wire);
+                        }
+                    }
+                    BelInfo::Legacy(bel) => {
+                        for pin in bel.pins.values() {
+                            for &wire in &pin.wires {
+                                builder.node(tcrd, wire);
+                            }
+                        }
+                    }
+                    BelInfo::TestMux(_) | BelInfo::OldTestMux => (),
+                }
+            }
+        }
+        builder.finish()
+    }
+}
@@ -4,9 +4,10 @@ use crate::{db::*, dir::Dir};
 use bimap::BiHashMap;
 use bincode::{Decode, Encode};
 use prjcombine_entity::{
-    EntityBundleItemIndex, EntityId, EntityPartVec, EntityVec,
+    EntityBundleIndices, EntityBundleItemIndex, EntityId, EntityPartVec, EntityVec,
     id::{EntityIdU8, EntityIdU16, EntityRange, EntityTag, EntityTagArith},
 };
+use prjcombine_types::text::{parse_id, parse_num};
 use std::collections::{HashMap, HashSet};
 
 pub mod builder;
@@ -54,6 +55,7 @@ impl EntityTag for IobTag {
 pub type TileIobId = EntityIdU8<IobTag>;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EdgeIoCoord {
     W(RowId, TileIobId),
     E(RowId, TileIobId),
@@ -101,7 +103,32 @@ impl std::fmt::Display for EdgeIoCoord {
     }
 }
 
+impl std::str::FromStr for EdgeIoCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s.strip_prefix("IOB_").and_then(|rest| {
+            let (pos, iob) = rest.split_once('_')?;
+            let (edge, idx) = pos.split_at_checked(1)?;
+            Some((edge, idx, iob))
+        });
+        let Some((edge, idx, iob)) = parsed else {
+            return Err(format!("invalid edge IO \"{s}\""));
+        };
+        let idx = parse_num(idx)?;
+        let iob = TileIobId::from_idx(parse_num(iob)?);
+        Ok(match edge {
+            "W" => EdgeIoCoord::W(RowId::from_idx(idx), iob),
+            "E" => EdgeIoCoord::E(RowId::from_idx(idx), iob),
+            "S" => EdgeIoCoord::S(ColId::from_idx(idx), iob),
+            "N" => EdgeIoCoord::N(ColId::from_idx(idx), iob),
+            _ => return Err(format!("invalid edge IO \"{s}\"")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CellCoord {
     pub die: DieId,
     pub col: ColId,
@@ -192,7 +219,27 @@ impl std::fmt::Debug for CellCoord {
     }
 }
 
+impl std::str::FromStr for CellCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = s.split_once(ColTag::PREFIX).and_then(|(die, rest)| {
+            let (col, row) = rest.split_once(RowTag::PREFIX)?;
+            Some((die, col, row))
+        });
+        let Some((die, col, row)) = parsed else {
+            return Err(format!("invalid cell \"{s}\""));
+        };
+        Ok(CellCoord {
+            die: parse_id(die, DieTag::PREFIX)?,
+            col: ColId::from_idx(parse_num(col)?),
+            row: RowId::from_idx(parse_num(row)?),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileCoord {
     pub cell: CellCoord,
     pub slot: TileSlotId,
@@ -223,6 +270,7 @@ impl std::ops::DerefMut for TileCoord {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConnectorCoord {
     pub cell: CellCoord,
     pub slot: ConnectorSlotId,
@@ -253,6 +301,7 @@ impl std::ops::DerefMut for ConnectorCoord {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WireCoord {
     pub cell: CellCoord,
     pub slot: WireSlotId,
@@ -290,6 +339,7 @@ impl std::ops::DerefMut for WireCoord {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PolWireCoord {
     pub wire: WireCoord,
     pub inv: bool,
@@ -310,6 +360,7 @@ impl std::ops::DerefMut for PolWireCoord {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelCoord {
     pub cell: CellCoord,
     pub slot: BelSlotId,
@@ -324,6 +375,17 @@ impl BelCoord {
         )
     }
 
+    /// Parses a bel written by [`BelCoord::to_string`].
+    pub fn parse(db: &IntDb, s: &str) -> Result<Self, String> {
+        let Some((cell, slot)) = s.split_once('.') else {
+            return Err(format!("expected cell.bel, got \"{s}\""));
+        };
+        let Some((slot, _)) = db.bel_slots.get(slot) else {
+            return Err(format!("unknown bel slot {slot}"));
+        };
+        Ok(cell.parse::<CellCoord>()?.bel(slot))
+    }
+
     pub fn pad(self, pad: BelPadId) -> BelPadCoord {
         BelPadCoord { bel: self, pad }
     }
@@ -344,6 +406,7 @@ impl std::ops::DerefMut for BelCoord {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BelPadCoord {
     pub bel: BelCoord,
     pub pad: BelPadId,
@@ -363,6 +426,36 @@ impl BelPadCoord {
             }
         }
     }
+
+    /// Parses a bel pad written by [`BelPadCoord::to_string`].
+    pub fn parse(db: &IntDb, s: &str) -> Result<Self, String> {
+        let Some((bel, pad)) = s.rsplit_once('.') else {
+            return Err(format!("expected cell.bel.pad, got \"{s}\""));
+        };
+        let bel = BelCoord::parse(db, bel)?;
+        let BelKind::Class(bcid) = db.bel_slots[bel.slot].kind else {
+            return Err(format!("bel {bel} has no pads", bel = bel.to_string(db)));
+        };
+        let pads = &db.bel_classes[bcid].pads;
+        let pad = match pads.get(pad) {
+            Some((EntityBundleIndices::Single(pad), _)) => pad,
+            _ => {
+                let Some((name, idx)) = pad.strip_suffix(']').and_then(|s| s.rsplit_once('['))
+                else {
+                    return Err(format!("unknown pad {pad}"));
+                };
+                let Some((EntityBundleIndices::Array(range), _)) = pads.get(name) else {
+                    return Err(format!("unknown pad {pad}"));
+                };
+                let idx: usize = parse_num(idx)?;
+                if idx >= range.len() {
+                    return Err(format!("unknown pad {pad}"));
+                }
+                range.index(idx)
+            }
+        };
+        Ok(bel.pad(pad))
+    }
 }
 
 impl std::ops::Deref for BelPadCoord {
@@ -1,5 +1,10 @@
 pub mod db;
+pub mod decode;
+pub mod diff;
 pub mod dir;
 pub mod dump;
+pub mod encode;
+pub mod graph;
 pub mod grid;
+pub mod parse;
 pub mod slots;
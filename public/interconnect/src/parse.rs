@@ -0,0 +1,1275 @@
+//! Reading back the `intdb` section of the text dumps written by [`IntDb::dump`].
+
+use crate::db::{
+    BelAttribute, BelAttributeEnum, BelAttributeType, BelClass, BelClassAttribute, BelClassBidir,
+    BelClassInput, BelClassOutput, BelClassPad, BelInfo, BelInput, BelKind, BelPin, BelPinIndexing,
+    BelSlot, BiPass, Bidi, BitRectInfo, ConnectorClass, ConnectorSlot, ConnectorSlotId,
+    ConnectorWire, DeviceDataId, EnumClass, IntDb, LegacyBel, Mux, PadKind, PairMux, Pass,
+    PermaBuf, PinDir, PolTileWireCoord, ProgBuf, ProgDelay, ProgInv, SwitchBox, SwitchBoxItem,
+    Table, TableValue, TestMux, TestMuxWire, TileClass, TileSlotId, TileWireCoord, WireKind,
+    WireSupport,
+};
+use prjcombine_entity::{
+    EntityBundleIndices, EntityBundleMap, EntityId, EntityMap, EntityPartVec, EntitySet, EntityVec,
+};
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{BitRectGeometry, BitRectId, FrameOrientation, PolTileBit, TileBit},
+    text::{Line, TextError, TextParser, parse_bits, parse_num, split_assign},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Parses a `0b`-prefixed bit vector literal.
+fn parse_binary(s: &str) -> Result<BitVec, String> {
+    parse_bits(
+        s.strip_prefix("0b")
+            .ok_or_else(|| format!("expected binary value, got \"{s}\""))?,
+    )
+}
+
+/// Splits the explicit `#id` off the name of a bel slot, tile class or connector class.
+fn split_id(name: &str) -> Result<(Option<usize>, &str), String> {
+    let Some(rest) = name.strip_prefix('#') else {
+        return Ok((None, name));
+    };
+    let (id, name) = rest
+        .split_once(' ')
+        .ok_or_else(|| format!("missing name after \"#{rest}\""))?;
+    let id = id.parse().map_err(|_| format!("invalid id \"{id}\""))?;
+    Ok((Some(id), name))
+}
+
+/// Puts the items listed under their slots into id order: the order they appear in when
+/// none has an explicit `#id`, or the order of the ids when all of them have one.
+fn order_by_id<'a, T>(
+    what: &str,
+    items: Vec<(Line<'a>, Option<usize>, &'a str, T)>,
+) -> Result<Vec<(Line<'a>, &'a str, T)>, TextError> {
+    let Some(&(_, first_id, _, _)) = items.first() else {
+        return Ok(vec![]);
+    };
+    if first_id.is_none() {
+        return items
+            .into_iter()
+            .map(|(line, id, name, item)| match id {
+                None => Ok((line, name, item)),
+                Some(_) => Err(line.error(format!("{what} {name} has an id, the others do not"))),
+            })
+            .collect();
+    }
+    let num = items.len();
+    let mut res: Vec<_> = (0..num).map(|_| None).collect();
+    for (line, id, name, item) in items {
+        let Some(id) = id else {
+            return Err(line.error(format!("{what} {name} has no id, the others do")));
+        };
+        if id >= num {
+            return Err(line.error(format!("{what} id {id} out of range")));
+        }
+        if res[id].is_some() {
+            return Err(line.error(format!("duplicate {what} id {id}")));
+        }
+        res[id] = Some((line, name, item));
+    }
+    // All ids are distinct and in range, so every one is used.
+    Ok(res.into_iter().map(Option::unwrap).collect())
+}
+
+/// Parses a `[a, b, c]` list, which is dumped MSB-first, into LSB-first order.
+fn parse_rev_list<T>(s: &str, f: impl Fn(&str) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let items = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("expected list, got \"{s}\""))?;
+    if items.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut res = items.split(", ").map(f).collect::<Result<Vec<_>, _>>()?;
+    res.reverse();
+    Ok(res)
+}
+
+/// Splits a `thing @bit` item.
+fn split_bit(s: &str) -> Result<(&str, &str), String> {
+    s.rsplit_once(" @")
+        .ok_or_else(|| format!("expected bit, got \"{s}\""))
+}
+
+/// Parses a `(a, b)` pair.
+fn parse_pair(s: &str) -> Result<[&str; 2], String> {
+    let (a, b) = s
+        .strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .and_then(|s| s.split_once(", "))
+        .ok_or_else(|| format!("expected pair, got \"{s}\""))?;
+    Ok([a, b])
+}
+
+/// Collects the entries of a multi-line list, up to and including the closing `}`.
+fn parse_list_block<'a>(p: &mut TextParser<'a>) -> Result<Vec<(Line<'a>, &'a str)>, TextError> {
+    let mut res = vec![];
+    while !p.try_block_end() {
+        let line = p.expect_line()?;
+        res.push((line, line.list_item()?));
+    }
+    Ok(res)
+}
+
+/// Parses a bel class pin declaration, such as `A`, `A[4]` or `A[0:3]`.
+fn parse_pin_decl(s: &str) -> Result<(&str, Option<usize>, BelPinIndexing), String> {
+    let Some((name, spec)) = s.strip_suffix(']').and_then(|s| s.rsplit_once('[')) else {
+        return Ok((s, None, BelPinIndexing::default()));
+    };
+    if let Some((msb, lsb)) = spec.split_once(':') {
+        let msb: usize = parse_num(msb)?;
+        let lsb: usize = parse_num(lsb)?;
+        // A plain `[n]` is written for the default indexing, so a single-pin `[0:0]` can only
+        // be the wrong-endian one.
+        let indexing = BelPinIndexing {
+            lsb_index: lsb,
+            wrong_endian: msb < lsb || msb == 0,
+        };
+        Ok((name, Some(msb.abs_diff(lsb) + 1), indexing))
+    } else {
+        Ok((name, Some(parse_num(spec)?), BelPinIndexing::default()))
+    }
+}
+
+/// Resolves a bel pin name, such as `A` or `A[3]`, to its id.
+fn parse_pin<I: EntityId, T>(
+    pins: &EntityBundleMap<I, T>,
+    indexing: impl Fn(&T) -> BelPinIndexing,
+    s: &str,
+) -> Result<I, String> {
+    if let Some((EntityBundleIndices::Single(id), _)) = pins.get(s) {
+        return Ok(id);
+    }
+    if let Some((name, idx)) = s.strip_suffix(']').and_then(|s| s.rsplit_once('['))
+        && let Some((EntityBundleIndices::Array(range), pin)) = pins.get(name)
+        && let Some(idx) = indexing(pin).try_virt_to_phys(parse_num(idx)?)
+        && idx < range.len()
+    {
+        return Ok(range.index(idx));
+    }
+    Err(format!("unknown pin {s}"))
+}
+
+impl TileClass {
+    pub fn parse_wire(&self, db: &IntDb, s: &str) -> Result<TileWireCoord, String> {
+        let (cell, wire) = if self.cells.len() == 1 {
+            (self.cells.first_id().unwrap(), s)
+        } else {
+            let (cell, wire) = s
+                .split_once('.')
+                .ok_or_else(|| format!("expected cell.wire, got \"{s}\""))?;
+            let cell = self
+                .cells
+                .iter()
+                .find(|&(_, name)| name == cell)
+                .ok_or_else(|| format!("unknown cell {cell}"))?
+                .0;
+            (cell, wire)
+        };
+        let (wire, _) = db
+            .wires
+            .get(wire)
+            .ok_or_else(|| format!("unknown wire {wire}"))?;
+        Ok(TileWireCoord { cell, wire })
+    }
+
+    pub fn parse_polwire(&self, db: &IntDb, s: &str) -> Result<PolTileWireCoord, String> {
+        match s.strip_prefix('~') {
+            Some(s) => Ok(self.parse_wire(db, s)?.neg()),
+            None => Ok(self.parse_wire(db, s)?.pos()),
+        }
+    }
+
+    pub fn parse_bit(&self, s: &str) -> Result<TileBit, String> {
+        let err = || format!("invalid tile bit \"{s}\"");
+        let (rest, bit) = s
+            .strip_suffix(']')
+            .and_then(|s| s.rsplit_once('['))
+            .ok_or_else(err)?;
+        let (rect, frame) = rest
+            .strip_suffix(']')
+            .and_then(|s| s.rsplit_once('['))
+            .ok_or_else(err)?;
+        let rect = match self.bitrects.iter().find(|(_, info)| info.name == rect) {
+            Some((rect, _)) => rect,
+            None => BitRectId::from_idx(parse_num(rect.strip_prefix("XXX").ok_or_else(err)?)?),
+        };
+        Ok(TileBit::new(
+            rect.to_idx(),
+            parse_num(frame)?,
+            parse_num(bit)?,
+        ))
+    }
+
+    pub fn parse_polbit(&self, s: &str) -> Result<PolTileBit, String> {
+        match s.strip_prefix('!') {
+            Some(s) => Ok(PolTileBit {
+                bit: self.parse_bit(s)?,
+                inv: true,
+            }),
+            None => Ok(PolTileBit {
+                bit: self.parse_bit(s)?,
+                inv: false,
+            }),
+        }
+    }
+
+    fn parse_bitrect(&self, s: &str) -> Result<BitRectInfo, String> {
+        let (name, geometry) = s
+            .split_once(": ")
+            .ok_or_else(|| format!("invalid bitrect \"{s}\""))?;
+        let (orientation, dims) = geometry
+            .split_once(' ')
+            .ok_or_else(|| format!("invalid bitrect \"{s}\""))?;
+        let orientation = match orientation {
+            "Horizontal" => FrameOrientation::Horizontal,
+            "Vertical" => FrameOrientation::Vertical,
+            _ => return Err(format!("unknown orientation {orientation}")),
+        };
+        let [frames, bits] = parse_pair(dims)?;
+        let (rev_frames, frames) = match frames.strip_prefix("rev ") {
+            Some(frames) => (true, frames),
+            None => (false, frames),
+        };
+        let (rev_bits, bits) = match bits.strip_prefix("rev ") {
+            Some(bits) => (true, bits),
+            None => (false, bits),
+        };
+        Ok(BitRectInfo {
+            name: name.to_string(),
+            geometry: BitRectGeometry {
+                frames: parse_num(frames)?,
+                bits: parse_num(bits)?,
+                orientation,
+                rev_frames,
+                rev_bits,
+            },
+        })
+    }
+
+    fn parse_pair_src(&self, db: &IntDb, s: &str) -> Result<[Option<PolTileWireCoord>; 2], String> {
+        let [a, b] = parse_pair(s)?;
+        let parse = |s| {
+            if s == "_" {
+                Ok(None)
+            } else {
+                self.parse_polwire(db, s).map(Some)
+            }
+        };
+        Ok([parse(a)?, parse(b)?])
+    }
+
+    fn parse_switchbox(&self, db: &IntDb, p: &mut TextParser) -> Result<SwitchBox, TextError> {
+        let mut items = vec![];
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let item = if let Some((kw, head)) = line.block_start() {
+                let (head, bits) = line.check(
+                    head.rsplit_once(" @")
+                        .ok_or_else(|| format!("expected bits, got \"{head}\"")),
+                )?;
+                let bits = line.check(parse_rev_list(bits, |s| self.parse_bit(s)))?;
+                let entries = parse_list_block(p)?;
+                match kw {
+                    "mux" => {
+                        let dst = line.check(self.parse_wire(db, head))?;
+                        let mut src = BTreeMap::new();
+                        let mut bits_off = None;
+                        for (eline, entry) in entries {
+                            let (k, v) = eline.check(split_assign(entry))?;
+                            let v = eline.check(parse_binary(v))?;
+                            if k == "off" {
+                                bits_off = Some(v);
+                            } else {
+                                src.insert(eline.check(self.parse_polwire(db, k))?, v);
+                            }
+                        }
+                        SwitchBoxItem::Mux(Mux {
+                            dst,
+                            bits,
+                            src,
+                            bits_off,
+                        })
+                    }
+                    "progdelay" => {
+                        let (dst, src) = line.check(split_assign(head))?;
+                        let steps = entries
+                            .into_iter()
+                            .map(|(eline, entry)| eline.check(parse_binary(entry)))
+                            .collect::<Result<_, _>>()?;
+                        SwitchBoxItem::ProgDelay(ProgDelay {
+                            dst: line.check(self.parse_wire(db, dst))?,
+                            src: line.check(self.parse_polwire(db, src))?,
+                            bits,
+                            steps,
+                        })
+                    }
+                    "pair_mux" => {
+                        let [dst0, dst1] = line.check(parse_pair(head))?;
+                        let mut src = BTreeMap::new();
+                        for (eline, entry) in entries {
+                            let (k, v) = eline.check(split_assign(entry))?;
+                            src.insert(
+                                eline.check(self.parse_pair_src(db, k))?,
+                                eline.check(parse_binary(v))?,
+                            );
+                        }
+                        SwitchBoxItem::PairMux(PairMux {
+                            dst: [
+                                line.check(self.parse_wire(db, dst0))?,
+                                line.check(self.parse_wire(db, dst1))?,
+                            ],
+                            bits,
+                            src,
+                        })
+                    }
+                    _ => return Err(line.error(format!("unknown switchbox block {kw}"))),
+                }
+            } else {
+                let (kw, val) = line.keyword_statement()?;
+                line.check(self.parse_switchbox_item(db, kw, val))?
+            };
+            items.push(item);
+        }
+        Ok(SwitchBox { items })
+    }
+
+    fn parse_switchbox_item(
+        &self,
+        db: &IntDb,
+        kw: &str,
+        val: &str,
+    ) -> Result<SwitchBoxItem, String> {
+        let with_bit = |s| -> Result<_, String> {
+            let (s, bit) = split_bit(s)?;
+            Ok((s, self.parse_polbit(bit)?))
+        };
+        Ok(match kw {
+            "mux" => {
+                let (dst, srcs) = split_assign(val)?;
+                let mut src = BTreeMap::new();
+                if !srcs.is_empty() {
+                    for s in srcs.split(" | ") {
+                        src.insert(self.parse_polwire(db, s)?, BitVec::new());
+                    }
+                }
+                SwitchBoxItem::Mux(Mux {
+                    dst: self.parse_wire(db, dst)?,
+                    bits: vec![],
+                    src,
+                    bits_off: None,
+                })
+            }
+            "progbuf" => {
+                let (dst, src) = split_assign(val)?;
+                let (src, bit) = with_bit(src)?;
+                SwitchBoxItem::ProgBuf(ProgBuf {
+                    dst: self.parse_wire(db, dst)?,
+                    src: self.parse_polwire(db, src)?,
+                    bit,
+                })
+            }
+            "permabuf" => {
+                let (dst, src) = split_assign(val)?;
+                SwitchBoxItem::PermaBuf(PermaBuf {
+                    dst: self.parse_wire(db, dst)?,
+                    src: self.parse_polwire(db, src)?,
+                })
+            }
+            "pass" => {
+                let (dst, src) = split_assign(val)?;
+                let (src, bit) = with_bit(src)?;
+                SwitchBoxItem::Pass(Pass {
+                    dst: self.parse_wire(db, dst)?,
+                    src: self.parse_wire(db, src)?,
+                    bit,
+                })
+            }
+            "bipass" => {
+                let (a, b) = split_assign(val)?;
+                let (b, bit) = with_bit(b)?;
+                SwitchBoxItem::BiPass(BiPass {
+                    a: self.parse_wire(db, a)?,
+                    b: self.parse_wire(db, b)?,
+                    bit,
+                })
+            }
+            "proginv" => {
+                let (dst, src) = split_assign(val)?;
+                let (src, bit) = with_bit(src)?;
+                SwitchBoxItem::ProgInv(ProgInv {
+                    dst: self.parse_wire(db, dst)?,
+                    src: self.parse_wire(db, src)?,
+                    bit,
+                })
+            }
+            "progdelay" => {
+                let (dst, src) = split_assign(val)?;
+                let (src, steps) = src
+                    .rsplit_once(" #")
+                    .ok_or_else(|| format!("expected step count, got \"{src}\""))?;
+                SwitchBoxItem::ProgDelay(ProgDelay {
+                    dst: self.parse_wire(db, dst)?,
+                    src: self.parse_polwire(db, src)?,
+                    bits: vec![],
+                    steps: vec![BitVec::new(); parse_num(steps)?],
+                })
+            }
+            "bidi" => {
+                let (conn, wire) = val
+                    .split_once(' ')
+                    .ok_or_else(|| format!("invalid bidi \"{val}\""))?;
+                let (wire, bit_upstream) = with_bit(wire)?;
+                SwitchBoxItem::Bidi(Bidi {
+                    conn: db
+                        .conn_slots
+                        .get(conn)
+                        .ok_or_else(|| format!("unknown connector slot {conn}"))?
+                        .0,
+                    wire: self.parse_wire(db, wire)?,
+                    bit_upstream,
+                })
+            }
+            "pair_mux" => {
+                let (dst, srcs) = split_assign(val)?;
+                let [dst0, dst1] = parse_pair(dst)?;
+                let mut src = BTreeMap::new();
+                if !srcs.is_empty() {
+                    for s in srcs.split(" | ") {
+                        src.insert(self.parse_pair_src(db, s)?, BitVec::new());
+                    }
+                }
+                SwitchBoxItem::PairMux(PairMux {
+                    dst: [self.parse_wire(db, dst0)?, self.parse_wire(db, dst1)?],
+                    bits: vec![],
+                    src,
+                })
+            }
+            "wire_support" => {
+                let (wires, bits) = match val.split_once(" @") {
+                    Some((wires, bits)) => (wires, parse_rev_list(bits, |s| self.parse_polbit(s))?),
+                    None => (val, vec![]),
+                };
+                SwitchBoxItem::WireSupport(WireSupport {
+                    wires: wires
+                        .split(", ")
+                        .map(|s| self.parse_wire(db, s))
+                        .collect::<Result<_, _>>()?,
+                    bits,
+                })
+            }
+            _ => return Err(format!("unknown switchbox item {kw}")),
+        })
+    }
+
+    fn parse_bel(
+        &self,
+        db: &IntDb,
+        bcls: &BelClass,
+        p: &mut TextParser,
+    ) -> Result<crate::db::Bel, TextError> {
+        let mut bel = crate::db::Bel::default();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            if let Some(head) = line.text().strip_suffix(" @[") {
+                let aname = line.check(
+                    head.strip_prefix("attribute ")
+                        .ok_or_else(|| format!("expected attribute, got \"{head}\"")),
+                )?;
+                let (aid, attr) = line.check(
+                    bcls.attributes
+                        .get(aname)
+                        .ok_or_else(|| format!("unknown attribute {aname}")),
+                )?;
+                let BelAttributeType::BitVecArray(width, depth) = attr.typ else {
+                    return Err(line.error(format!("attribute {aname} is not an array")));
+                };
+                let mut bits = vec![];
+                loop {
+                    let iline = p.expect_line()?;
+                    if iline.text() == "];" {
+                        break;
+                    }
+                    let item = iline.list_item()?;
+                    bits.extend(iline.check(parse_rev_list(item, |s| self.parse_polbit(s)))?);
+                }
+                if bits.len() != width * depth {
+                    return Err(line.error(format!("attribute {aname} has wrong size")));
+                }
+                bel.attributes.insert(aid, BelAttribute::BitVec(bits));
+            } else if let Some((kw, head)) = line.block_start() {
+                let (aname, bits) = line.check(
+                    head.split_once(" @")
+                        .filter(|_| kw == "attribute")
+                        .ok_or_else(|| format!("unexpected \"{}\"", line.text())),
+                )?;
+                let (aid, attr) = line.check(
+                    bcls.attributes
+                        .get(aname)
+                        .ok_or_else(|| format!("unknown attribute {aname}")),
+                )?;
+                let BelAttributeType::Enum(eid) = attr.typ else {
+                    return Err(line.error(format!("attribute {aname} is not an enum")));
+                };
+                let ecls = &db.enum_classes[eid];
+                let mut values = EntityPartVec::new();
+                for (eline, entry) in parse_list_block(p)? {
+                    let (k, v) = eline.check(split_assign(entry))?;
+                    let vid = eline.check(
+                        ecls.values
+                            .get(k)
+                            .ok_or_else(|| format!("unknown enum value {k}")),
+                    )?;
+                    values.insert(vid, eline.check(parse_binary(v))?);
+                }
+                bel.attributes.insert(
+                    aid,
+                    BelAttribute::Enum(BelAttributeEnum {
+                        bits: line.check(parse_rev_list(bits, |s| self.parse_bit(s)))?,
+                        values,
+                    }),
+                );
+            } else {
+                let (kw, val) = line.keyword_statement()?;
+                line.check(self.parse_bel_statement(db, bcls, &mut bel, kw, val))?;
+            }
+        }
+        Ok(bel)
+    }
+
+    fn parse_bel_statement(
+        &self,
+        db: &IntDb,
+        bcls: &BelClass,
+        bel: &mut crate::db::Bel,
+        kw: &str,
+        val: &str,
+    ) -> Result<(), String> {
+        match kw {
+            "input" => {
+                let (pin, wire) = split_assign(val)?;
+                let pid = parse_pin(&bcls.inputs, |pin| pin.indexing, pin)?;
+                let input = match wire.strip_prefix('^') {
+                    Some(wire) => {
+                        let (wire, bit) = split_bit(wire)?;
+                        BelInput::Invertible(self.parse_wire(db, wire)?, self.parse_polbit(bit)?)
+                    }
+                    None => BelInput::Fixed(self.parse_polwire(db, wire)?),
+                };
+                bel.inputs.insert(pid, input);
+            }
+            "output" => {
+                let (pin, wires) = split_assign(val)?;
+                let pid = parse_pin(&bcls.outputs, |pin| pin.indexing, pin)?;
+                let wires = if wires.is_empty() {
+                    BTreeSet::new()
+                } else {
+                    wires
+                        .split(", ")
+                        .map(|s| self.parse_wire(db, s))
+                        .collect::<Result<_, _>>()?
+                };
+                bel.outputs.insert(pid, wires);
+            }
+            "bidir" => {
+                let (pin, wire) = split_assign(val)?;
+                let pid = parse_pin(&bcls.bidirs, |pin| pin.indexing, pin)?;
+                bel.bidirs.insert(pid, self.parse_wire(db, wire)?);
+            }
+            "attribute" => {
+                let (aname, bits) = val
+                    .split_once(" @")
+                    .ok_or_else(|| format!("invalid attribute \"{val}\""))?;
+                let (aid, attr) = bcls
+                    .attributes
+                    .get(aname)
+                    .ok_or_else(|| format!("unknown attribute {aname}"))?;
+                let bits = match attr.typ {
+                    BelAttributeType::Bool => vec![self.parse_polbit(bits)?],
+                    BelAttributeType::BitVec(width) => {
+                        let bits = parse_rev_list(bits, |s| self.parse_polbit(s))?;
+                        if bits.len() != width {
+                            return Err(format!("attribute {aname} has wrong width"));
+                        }
+                        bits
+                    }
+                    _ => return Err(format!("attribute {aname} is not a bit vector")),
+                };
+                bel.attributes.insert(aid, BelAttribute::BitVec(bits));
+            }
+            _ => return Err(format!("unknown bel statement {kw}")),
+        }
+        Ok(())
+    }
+
+    fn parse_legacy_bel(&self, db: &IntDb, p: &mut TextParser) -> Result<LegacyBel, TextError> {
+        let mut bel = LegacyBel::default();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            let dir = match kw {
+                "input" => PinDir::Input,
+                "output" => PinDir::Output,
+                "inout" => PinDir::Inout,
+                _ => return Err(line.error(format!("unknown pin direction {kw}"))),
+            };
+            let (pin, wires) = line.check(split_assign(val))?;
+            let wires = if wires.is_empty() {
+                BTreeSet::new()
+            } else {
+                wires
+                    .split(", ")
+                    .map(|s| line.check(self.parse_wire(db, s)))
+                    .collect::<Result<_, _>>()?
+            };
+            bel.pins.insert(pin.to_string(), BelPin { wires, dir });
+        }
+        Ok(bel)
+    }
+
+    fn parse_test_mux(
+        &self,
+        db: &IntDb,
+        line: Line,
+        head: &str,
+        p: &mut TextParser,
+    ) -> Result<TestMux, TextError> {
+        let mut tmux = TestMux::default();
+        if let Some(num) = head.strip_prefix('#') {
+            tmux.groups = vec![BitVec::new(); line.check(parse_num(num))?];
+        } else {
+            let bits = line.check(
+                head.strip_prefix('@')
+                    .ok_or_else(|| format!("expected bits, got \"{head}\"")),
+            )?;
+            tmux.bits = line.check(parse_rev_list(bits, |s| self.parse_bit(s)))?;
+            loop {
+                let gline = p.expect_line()?;
+                if gline.text() == "} {" {
+                    break;
+                }
+                let (k, v) = gline.check(split_assign(gline.list_item()?))?;
+                let v = gline.check(parse_binary(v))?;
+                if k == "primary" {
+                    tmux.bits_primary = v;
+                } else if let Some(idx) = k.strip_prefix("test_group ") {
+                    if gline.check(parse_num::<usize>(idx))? != tmux.groups.len() {
+                        return Err(gline.error(format!("unexpected test group {idx}")));
+                    }
+                    tmux.groups.push(v);
+                } else {
+                    return Err(gline.error(format!("unknown test mux setting {k}")));
+                }
+            }
+        }
+        while !p.try_block_end() {
+            let wline = p.expect_line()?;
+            let (dst, src) = wline.check(split_assign(wline.statement()?))?;
+            let (primary_src, test_src) = wline.check(
+                src.split_once(" || ")
+                    .ok_or_else(|| format!("invalid test mux wire \"{src}\"")),
+            )?;
+            let test_src = test_src
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .ok_or_else(|| wline.error(format!("invalid test mux wire \"{src}\"")))?;
+            let test_src = if test_src.is_empty() {
+                vec![]
+            } else {
+                test_src
+                    .split(", ")
+                    .map(|s| {
+                        if s == "none" {
+                            Ok(None)
+                        } else {
+                            wline.check(self.parse_polwire(db, s)).map(Some)
+                        }
+                    })
+                    .collect::<Result<_, _>>()?
+            };
+            tmux.wires.insert(
+                wline.check(self.parse_wire(db, dst))?,
+                TestMuxWire {
+                    primary_src: wline.check(self.parse_polwire(db, primary_src))?,
+                    test_src,
+                },
+            );
+        }
+        Ok(tmux)
+    }
+
+    /// Parses the body of a `tile_class` block, up to and including the closing `}`.
+    pub fn parse(db: &IntDb, slot: TileSlotId, p: &mut TextParser) -> Result<Self, TextError> {
+        let mut tcls = TileClass {
+            slot,
+            cells: EntityVec::new(),
+            bitrects: EntityVec::new(),
+            bels: EntityPartVec::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            if let Some((kw, head)) = line.block_start() {
+                let (sname, rest) = head.split_once(' ').unwrap_or((head, ""));
+                let (bslot, bslot_data) = line.check(
+                    db.bel_slots
+                        .get(sname)
+                        .ok_or_else(|| format!("unknown bel slot {sname}")),
+                )?;
+                let bel = match (kw, bslot_data.kind) {
+                    ("switchbox", _) => BelInfo::SwitchBox(tcls.parse_switchbox(db, p)?),
+                    ("test_mux", _) => BelInfo::TestMux(tcls.parse_test_mux(db, line, rest, p)?),
+                    ("bel", BelKind::Class(bcid)) => {
+                        BelInfo::Bel(tcls.parse_bel(db, &db.bel_classes[bcid], p)?)
+                    }
+                    ("bel", BelKind::Legacy) => BelInfo::Legacy(tcls.parse_legacy_bel(db, p)?),
+                    _ => return Err(line.error(format!("unexpected \"{}\"", line.text()))),
+                };
+                if tcls.bels.insert(bslot, bel).is_some() {
+                    return Err(line.error(format!("duplicate bel {sname}")));
+                }
+            } else {
+                let (kw, val) = line.keyword_statement()?;
+                match kw {
+                    "cell" => {
+                        tcls.cells.push(val.to_string());
+                    }
+                    "bitrect" => {
+                        let rect = line.check(tcls.parse_bitrect(val))?;
+                        tcls.bitrects.push(rect);
+                    }
+                    _ => return Err(line.error(format!("unknown tile class statement {kw}"))),
+                }
+            }
+        }
+        Ok(tcls)
+    }
+}
+
+impl IntDb {
+    pub fn parse_typ(&self, s: &str) -> Result<BelAttributeType, String> {
+        Ok(match s {
+            "bool" => BelAttributeType::Bool,
+            "u32" => BelAttributeType::U32,
+            _ => {
+                if let Some(dims) = s.strip_prefix("bitvec[").and_then(|s| s.strip_suffix(']')) {
+                    match dims.split_once("][") {
+                        Some((width, depth)) => {
+                            BelAttributeType::BitVecArray(parse_num(width)?, parse_num(depth)?)
+                        }
+                        None => BelAttributeType::BitVec(parse_num(dims)?),
+                    }
+                } else {
+                    let (eid, _) = self
+                        .enum_classes
+                        .get(s)
+                        .ok_or_else(|| format!("unknown type {s}"))?;
+                    BelAttributeType::Enum(eid)
+                }
+            }
+        })
+    }
+
+    pub fn parse_value(&self, typ: BelAttributeType, s: &str) -> Result<TableValue, String> {
+        Ok(match typ {
+            BelAttributeType::Enum(eid) => TableValue::Enum(
+                self.enum_classes[eid]
+                    .values
+                    .get(s)
+                    .ok_or_else(|| format!("unknown enum value {s}"))?,
+            ),
+            BelAttributeType::Bool => match s {
+                "true" => TableValue::BitVec(BitVec::repeat(true, 1)),
+                "false" => TableValue::BitVec(BitVec::repeat(false, 1)),
+                _ => return Err(format!("expected bool, got \"{s}\"")),
+            },
+            BelAttributeType::BitVec(width) => {
+                let val = parse_binary(s)?;
+                if val.len() != width {
+                    return Err(format!("expected {width} bits, got \"{s}\""));
+                }
+                TableValue::BitVec(val)
+            }
+            BelAttributeType::BitVecArray(width, depth) => {
+                let items = s
+                    .strip_prefix('[')
+                    .and_then(|s| s.strip_suffix(']'))
+                    .ok_or_else(|| format!("expected array, got \"{s}\""))?;
+                let mut val = BitVec::new();
+                for item in items.split(", ") {
+                    let item = parse_binary(item)?;
+                    if item.len() != width {
+                        return Err(format!("expected {width} bits, got \"{s}\""));
+                    }
+                    val.extend(item);
+                }
+                if val.len() != width * depth {
+                    return Err(format!("expected {depth} items, got \"{s}\""));
+                }
+                TableValue::BitVec(val)
+            }
+            BelAttributeType::U32 => TableValue::U32(parse_num(s)?),
+        })
+    }
+
+    /// Parses a `NAME = VALUE` device data assignment, as found in device blocks.
+    pub fn parse_device_data(&self, s: &str) -> Result<(DeviceDataId, TableValue), String> {
+        let (name, value) = split_assign(s)?;
+        let (id, &typ) = self
+            .devdata
+            .get(name)
+            .ok_or_else(|| format!("unknown device data {name}"))?;
+        Ok((id, self.parse_value(typ, value)?))
+    }
+
+    fn parse_wire_kind(&self, s: &str) -> Result<WireKind, String> {
+        let conn_slot = |name: &str| {
+            self.conn_slots
+                .get(name)
+                .map(|(id, _)| id)
+                .ok_or_else(|| format!("unknown connector slot {name}"))
+        };
+        Ok(match s {
+            "tie 0" => WireKind::Tie0,
+            "tie 1" => WireKind::Tie1,
+            "pullup" => WireKind::TiePullup,
+            "mux" => WireKind::MuxOut,
+            "bel" => WireKind::BelOut,
+            "test" => WireKind::TestOut,
+            "multi_root" => WireKind::MultiRoot,
+            "special" => WireKind::Special,
+            _ => match s.split_once(' ') {
+                Some(("regional", slot)) => WireKind::Regional(
+                    self.region_slots
+                        .get(slot)
+                        .ok_or_else(|| format!("unknown region slot {slot}"))?,
+                ),
+                Some(("multi_branch", slot)) => WireKind::MultiBranch(conn_slot(slot)?),
+                Some(("branch", slot)) => WireKind::Branch(conn_slot(slot)?),
+                _ => return Err(format!("unknown wire kind \"{s}\"")),
+            },
+        })
+    }
+
+    fn parse_bel_class(&self, p: &mut TextParser) -> Result<BelClass, TextError> {
+        let mut bcls = BelClass::default();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            let (nonroutable, kw, val) = if kw == "nonroutable" {
+                let (kw, val) = val.split_once(' ').unwrap_or((val, ""));
+                (true, kw, val)
+            } else {
+                (false, kw, val)
+            };
+            let ok = match kw {
+                "input" | "output" | "bidir" => {
+                    let (name, array, indexing) = line.check(parse_pin_decl(val))?;
+                    let name = name.to_string();
+                    match (kw, array) {
+                        ("input", None) => bcls
+                            .inputs
+                            .insert(
+                                name,
+                                BelClassInput {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                        ("input", Some(num)) => bcls
+                            .inputs
+                            .insert_array(
+                                name,
+                                num,
+                                BelClassInput {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                        ("output", None) => bcls
+                            .outputs
+                            .insert(
+                                name,
+                                BelClassOutput {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                        ("output", Some(num)) => bcls
+                            .outputs
+                            .insert_array(
+                                name,
+                                num,
+                                BelClassOutput {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                        (_, None) => bcls
+                            .bidirs
+                            .insert(
+                                name,
+                                BelClassBidir {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                        (_, Some(num)) => bcls
+                            .bidirs
+                            .insert_array(
+                                name,
+                                num,
+                                BelClassBidir {
+                                    nonroutable,
+                                    indexing,
+                                },
+                            )
+                            .is_some(),
+                    }
+                }
+                "pad" if !nonroutable => {
+                    let (decl, kind) = line.check(
+                        val.split_once(": ")
+                            .ok_or_else(|| format!("invalid pad \"{val}\"")),
+                    )?;
+                    let kind = match kind {
+                        "input" => PadKind::In,
+                        "output" => PadKind::Out,
+                        "inout" => PadKind::Inout,
+                        "power" => PadKind::Power,
+                        "analog" => PadKind::Analog,
+                        _ => return Err(line.error(format!("unknown pad kind {kind}"))),
+                    };
+                    let (name, array, _) = line.check(parse_pin_decl(decl))?;
+                    match array {
+                        None => bcls
+                            .pads
+                            .insert(name.to_string(), BelClassPad { kind })
+                            .is_some(),
+                        Some(num) => bcls
+                            .pads
+                            .insert_array(name.to_string(), num, BelClassPad { kind })
+                            .is_some(),
+                    }
+                }
+                "attribute" if !nonroutable => {
+                    let (name, typ) = line.check(
+                        val.split_once(": ")
+                            .ok_or_else(|| format!("invalid attribute \"{val}\"")),
+                    )?;
+                    let typ = line.check(self.parse_typ(typ))?;
+                    bcls.attributes
+                        .insert(name.to_string(), BelClassAttribute { typ })
+                        .1
+                        .is_none()
+                }
+                _ => return Err(line.error(format!("unknown bel class statement {kw}"))),
+            };
+            if !ok {
+                return Err(line.error(format!("duplicate {kw} \"{val}\"")));
+            }
+        }
+        Ok(bcls)
+    }
+
+    fn parse_table(&self, p: &mut TextParser) -> Result<Table, TextError> {
+        let mut table = Table {
+            fields: EntityMap::new(),
+            rows: EntityMap::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (name, row) = if let Some(name) = line
+                .block_start()
+                .and_then(|(kw, name)| (kw == "row").then_some(name))
+            {
+                let mut row = EntityPartVec::new();
+                while !p.try_block_end() {
+                    let fline = p.expect_line()?;
+                    let (fname, value) = fline.check(split_assign(fline.statement()?))?;
+                    let (fid, &typ) = fline.check(
+                        table
+                            .fields
+                            .get(fname)
+                            .ok_or_else(|| format!("unknown field {fname}")),
+                    )?;
+                    row.insert(fid, fline.check(self.parse_value(typ, value))?);
+                }
+                (name, row)
+            } else {
+                let (kw, val) = line.keyword_statement()?;
+                match kw {
+                    "field" => {
+                        let (fname, typ) = line.check(
+                            val.split_once(": ")
+                                .ok_or_else(|| format!("invalid field \"{val}\"")),
+                        )?;
+                        let typ = line.check(self.parse_typ(typ))?;
+                        if table.fields.insert(fname.to_string(), typ).1.is_some() {
+                            return Err(line.error(format!("duplicate field {fname}")));
+                        }
+                        continue;
+                    }
+                    "row" => (val, EntityPartVec::new()),
+                    _ => return Err(line.error(format!("unknown table statement {kw}"))),
+                }
+            };
+            if table.rows.insert(name.to_string(), row).1.is_some() {
+                return Err(line.error(format!("duplicate row {name}")));
+            }
+        }
+        Ok(table)
+    }
+
+    /// Parses the body of an `intdb` block, up to and including the closing `}`.
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut db = IntDb::default();
+        // Wires refer to connector slots before their blocks, and tile classes may use bel
+        // slots of later tile slots, so collect all slot names first.
+        let mut pending_bel_slots = vec![];
+        let mut cur_tslot = None;
+        for &line in p.remaining() {
+            match line.indent() {
+                0 => break,
+                1 => {
+                    cur_tslot = None;
+                    match line.block_start() {
+                        Some(("tile_slot", name)) => {
+                            let (tslot, new) = db.tile_slots.insert(name.to_string());
+                            if !new {
+                                return Err(line.error(format!("duplicate tile slot {name}")));
+                            }
+                            cur_tslot = Some(tslot);
+                        }
+                        Some(("connector_slot", name)) => {
+                            // The opposite is filled in when the block is parsed.
+                            let csid = db.conn_slots.next_id();
+                            if db
+                                .conn_slots
+                                .insert(name.to_string(), ConnectorSlot { opposite: csid })
+                                .1
+                                .is_some()
+                            {
+                                return Err(line.error(format!("duplicate connector slot {name}")));
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                2 => {
+                    if let Some(tslot) = cur_tslot
+                        && line.text().starts_with("bel_slot ")
+                    {
+                        pending_bel_slots.push((line, tslot));
+                    }
+                }
+                _ => (),
+            }
+        }
+        let mut opposites = EntityPartVec::new();
+        let mut tile_classes = vec![];
+        let mut conn_classes = vec![];
+        loop {
+            let line = p.expect_line()?;
+            if line.is_block_end() {
+                break;
+            }
+            if let Some((kw, name)) = line.block_start() {
+                match kw {
+                    "enum" => {
+                        let mut values = EntitySet::new();
+                        for (vline, value) in parse_list_block(p)? {
+                            if !values.insert(value.to_string()).1 {
+                                return Err(vline.error(format!("duplicate enum value {value}")));
+                            }
+                        }
+                        if db
+                            .enum_classes
+                            .insert(name.to_string(), EnumClass { values })
+                            .1
+                            .is_some()
+                        {
+                            return Err(line.error(format!("duplicate enum {name}")));
+                        }
+                    }
+                    "bel_class" => {
+                        let bcls = db.parse_bel_class(p)?;
+                        if db.bel_classes.insert(name.to_string(), bcls).1.is_some() {
+                            return Err(line.error(format!("duplicate bel class {name}")));
+                        }
+                    }
+                    "tile_slot" => {
+                        // Bel classes are all known by now.
+                        let mut bel_slots = vec![];
+                        for (sline, tile_slot) in std::mem::take(&mut pending_bel_slots) {
+                            let (_, val) = sline.keyword_statement()?;
+                            let (sname, kind) = sline.check(
+                                val.split_once(": ")
+                                    .ok_or_else(|| format!("invalid bel slot \"{val}\"")),
+                            )?;
+                            let (id, sname) = sline.check(split_id(sname))?;
+                            let kind = match kind {
+                                "routing" => BelKind::Routing,
+                                "legacy" => BelKind::Legacy,
+                                _ => BelKind::Class(
+                                    sline.check(
+                                        db.bel_classes
+                                            .get(kind)
+                                            .map(|(id, _)| id)
+                                            .ok_or_else(|| format!("unknown bel class {kind}")),
+                                    )?,
+                                ),
+                            };
+                            bel_slots.push((sline, id, sname, BelSlot { tile_slot, kind }));
+                        }
+                        for (sline, sname, bslot) in order_by_id("bel slot", bel_slots)? {
+                            if db.bel_slots.insert(sname.to_string(), bslot).1.is_some() {
+                                return Err(sline.error(format!("duplicate bel slot {sname}")));
+                            }
+                        }
+                        let tslot = db.tile_slots.get(name).unwrap();
+                        while !p.try_block_end() {
+                            let tline = p.expect_line()?;
+                            if let Some(tcname) = tline
+                                .block_start()
+                                .and_then(|(kw, name)| (kw == "tile_class").then_some(name))
+                            {
+                                let (id, tcname) = tline.check(split_id(tcname))?;
+                                let tcls = TileClass::parse(&db, tslot, p)?;
+                                tile_classes.push((tline, id, tcname, tcls));
+                            } else {
+                                // Already handled above.
+                                let (kw, _) = tline.keyword_statement()?;
+                                if kw != "bel_slot" {
+                                    return Err(
+                                        tline.error(format!("unknown tile slot statement {kw}"))
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    "connector_slot" => {
+                        let (csid, _) = db.conn_slots.get(name).unwrap();
+                        while !p.try_block_end() {
+                            let cline = p.expect_line()?;
+                            if let Some(ccname) = cline
+                                .block_start()
+                                .and_then(|(kw, name)| (kw == "connector_class").then_some(name))
+                            {
+                                let (id, ccname) = cline.check(split_id(ccname))?;
+                                let ccls = db.parse_conn_class(csid, p)?;
+                                conn_classes.push((cline, id, ccname, ccls));
+                            } else {
+                                let (kw, val) = cline.keyword_statement()?;
+                                if kw != "opposite" {
+                                    return Err(cline
+                                        .error(format!("unknown connector slot statement {kw}")));
+                                }
+                                let (opposite, _) = cline.check(
+                                    db.conn_slots
+                                        .get(val)
+                                        .ok_or_else(|| format!("unknown connector slot {val}")),
+                                )?;
+                                opposites.insert(csid, opposite);
+                            }
+                        }
+                    }
+                    "table" => {
+                        let table = db.parse_table(p)?;
+                        if db.tables.insert(name.to_string(), table).1.is_some() {
+                            return Err(line.error(format!("duplicate table {name}")));
+                        }
+                    }
+                    _ => return Err(line.error(format!("unknown intdb block {kw}"))),
+                }
+            } else {
+                let (kw, val) = line.keyword_statement()?;
+                match kw {
+                    "region_slot" => {
+                        if !db.region_slots.insert(val.to_string()).1 {
+                            return Err(line.error(format!("duplicate region slot {val}")));
+                        }
+                    }
+                    "wire" => {
+                        let (name, kind) = line.check(
+                            val.split_once(": ")
+                                .ok_or_else(|| format!("invalid wire \"{val}\"")),
+                        )?;
+                        let kind = line.check(db.parse_wire_kind(kind))?;
+                        if db.wires.insert(name.to_string(), kind).1.is_some() {
+                            return Err(line.error(format!("duplicate wire {name}")));
+                        }
+                    }
+                    "device_data" => {
+                        let (name, typ) = line.check(
+                            val.split_once(": ")
+                                .ok_or_else(|| format!("invalid device data \"{val}\"")),
+                        )?;
+                        let typ = line.check(db.parse_typ(typ))?;
+                        if db.devdata.insert(name.to_string(), typ).1.is_some() {
+                            return Err(line.error(format!("duplicate device data {name}")));
+                        }
+                    }
+                    _ => return Err(line.error(format!("unknown intdb statement {kw}"))),
+                }
+            }
+        }
+        for (tline, tcname, tcls) in order_by_id("tile class", tile_classes)? {
+            if db.tile_classes.insert(tcname.to_string(), tcls).1.is_some() {
+                return Err(tline.error(format!("duplicate tile class {tcname}")));
+            }
+        }
+        for (cline, ccname, ccls) in order_by_id("connector class", conn_classes)? {
+            if db.conn_classes.insert(ccname.to_string(), ccls).1.is_some() {
+                return Err(cline.error(format!("duplicate connector class {ccname}")));
+            }
+        }
+        for (csid, name, slot) in &mut db.conn_slots {
+            slot.opposite = *opposites
+                .get(csid)
+                .ok_or_else(|| p.missing(&format!("opposite of connector slot {name}")))?;
+        }
+        Ok(db)
+    }
+
+    fn parse_conn_class(
+        &self,
+        slot: ConnectorSlotId,
+        p: &mut TextParser,
+    ) -> Result<ConnectorClass, TextError> {
+        let mut ccls = ConnectorClass::new(slot);
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            let wire = |name: &str| {
+                self.wires
+                    .get(name)
+                    .map(|(id, _)| id)
+                    .ok_or_else(|| format!("unknown wire {name}"))
+            };
+            let (w, cw) = match kw {
+                "blackhole" => (val, ConnectorWire::BlackHole),
+                "reflect" | "pass" => {
+                    let (w, ow) = line.check(split_assign(val))?;
+                    let ow = line.check(wire(ow))?;
+                    if kw == "reflect" {
+                        (w, ConnectorWire::Reflect(ow))
+                    } else {
+                        (w, ConnectorWire::Pass(ow))
+                    }
+                }
+                _ => return Err(line.error(format!("unknown connector class statement {kw}"))),
+            };
+            ccls.wires.insert(line.check(wire(w))?, cw);
+        }
+        Ok(ccls)
+    }
+}
@@ -4,7 +4,7 @@ use std::path::Path;
 use prjcombine_types::bitvec::BitVec;
 
 /// Represents the contents of a JESD3 file.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct JedFile {
     /// The design specification: text after STX and before first field, not including
     /// the terminating `'*'`.
@@ -31,6 +31,98 @@ pub struct JedFile {
     pub electrical: BitVec,
     /// User fuses.  If empty, not specified in the file.
     pub user: BitVec,
+    /// The number of pins in the device package (`QP` field), if specified.
+    pub pin_count: Option<usize>,
+    /// The maximum number of test vectors (`QV` field), if specified.
+    pub vector_count: Option<usize>,
+    /// The package pin numbers corresponding to consecutive test vector positions
+    /// (`P` field).  A `None` means the vectors cover pins starting from 1 in order.
+    pub pins: Option<Vec<u32>>,
+    /// The default test condition (`X` field): the level driven onto inputs marked
+    /// with [`TestValue::DontCare`], if specified.
+    pub default_test_condition: Option<bool>,
+    /// The functional test vectors (`V` fields), in file order.
+    pub vectors: Vec<TestVector>,
+}
+
+/// A single position of a test vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TestValue {
+    /// `0`: drive input low.
+    Low,
+    /// `1`: drive input high.
+    High,
+    /// `C`: drive input low, high, low.
+    Clock,
+    /// `K`: drive input high, low, high.
+    ClockInverted,
+    /// `L`: test output low.
+    ExpectLow,
+    /// `H`: test output high.
+    ExpectHigh,
+    /// `Z`: test output high impedance.
+    ExpectZ,
+    /// `X`: output not tested, input set to the default test condition.
+    DontCare,
+    /// `N`: power pins and outputs not tested.
+    NotTested,
+    /// `P`: preload registers.
+    Preload,
+    /// `B`: buried register preload.
+    BuriedPreload,
+    /// `F`: float input or output.
+    Float,
+}
+
+impl TestValue {
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            '0' => TestValue::Low,
+            '1' => TestValue::High,
+            'C' => TestValue::Clock,
+            'K' => TestValue::ClockInverted,
+            'L' => TestValue::ExpectLow,
+            'H' => TestValue::ExpectHigh,
+            'Z' => TestValue::ExpectZ,
+            'X' => TestValue::DontCare,
+            'N' => TestValue::NotTested,
+            'P' => TestValue::Preload,
+            'B' => TestValue::BuriedPreload,
+            'F' => TestValue::Float,
+            _ => return None,
+        })
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            TestValue::Low => '0',
+            TestValue::High => '1',
+            TestValue::Clock => 'C',
+            TestValue::ClockInverted => 'K',
+            TestValue::ExpectLow => 'L',
+            TestValue::ExpectHigh => 'H',
+            TestValue::ExpectZ => 'Z',
+            TestValue::DontCare => 'X',
+            TestValue::NotTested => 'N',
+            TestValue::Preload => 'P',
+            TestValue::BuriedPreload => 'B',
+            TestValue::Float => 'F',
+        }
+    }
+}
+
+impl std::fmt::Display for TestValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// A functional test vector (`V` field).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TestVector {
+    /// The vector number.
+    pub index: u32,
+    pub values: Vec<TestValue>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -66,6 +158,10 @@ pub enum JedParserError {
     FuseSecurityDuplicated,
     FuseUserDuplicated,
     FuseElectricalDuplicated,
+    PinCountDuplicated,
+    VectorCountDuplicated,
+    PinListDuplicated,
+    TestConditionDuplicated,
     EtxChecksumMissing,
     EtxChecksumMismatch,
     InvalidArgument,
@@ -95,6 +191,12 @@ impl std::fmt::Display for JedParserError {
             JedParserError::FuseSecurityDuplicated => write!(f, "security fuse duplicated"),
             JedParserError::FuseUserDuplicated => write!(f, "user fuse duplicated"),
             JedParserError::FuseElectricalDuplicated => write!(f, "electrical fuse duplicated"),
+            JedParserError::PinCountDuplicated => write!(f, "pin count duplicated"),
+            JedParserError::VectorCountDuplicated => write!(f, "test vector count duplicated"),
+            JedParserError::PinListDuplicated => write!(f, "pin list duplicated"),
+            JedParserError::TestConditionDuplicated => {
+                write!(f, "default test condition duplicated")
+            }
             JedParserError::EtxChecksumMissing => write!(f, "etx checksum missing"),
             JedParserError::EtxChecksumMismatch => write!(f, "etx checksum mismatch"),
             JedParserError::InvalidArgument => write!(f, "invalid argument"),
@@ -122,6 +224,12 @@ impl JedFile {
         self
     }
 
+    pub fn with_vector(mut self, values: Vec<TestValue>) -> Self {
+        let index = self.vectors.last().map_or(1, |v| v.index + 1);
+        self.vectors.push(TestVector { index, values });
+        self
+    }
+
     pub fn fuse_checksum(&self) -> u16 {
         let mut checksum: u16 = 0;
         for (i, fuse) in self.fuses.as_ref().unwrap().iter().enumerate() {
@@ -141,6 +249,9 @@ impl JedFile {
         for note in &self.notes {
             writeln!(out, "N{note}*").unwrap();
         }
+        if let Some(n) = self.pin_count {
+            writeln!(out, "QP{n}*").unwrap();
+        }
         if let Some(ref fuses) = self.fuses {
             writeln!(out, "QF{n}*", n = fuses.len()).unwrap();
             writeln!(out, "F0*").unwrap();
@@ -170,6 +281,26 @@ impl JedFile {
         if let Some(security) = self.security {
             writeln!(out, "G{security}*", security = u32::from(security)).unwrap();
         }
+        if let Some(n) = self.vector_count {
+            writeln!(out, "QV{n}*").unwrap();
+        }
+        if let Some(cond) = self.default_test_condition {
+            writeln!(out, "X{cond}*", cond = u32::from(cond)).unwrap();
+        }
+        if let Some(ref pins) = self.pins {
+            write!(out, "P").unwrap();
+            for pin in pins {
+                write!(out, " {pin}").unwrap();
+            }
+            writeln!(out, "*").unwrap();
+        }
+        for vector in &self.vectors {
+            write!(out, "V{index:04} ", index = vector.index).unwrap();
+            for val in &vector.values {
+                write!(out, "{val}").unwrap();
+            }
+            writeln!(out, "*").unwrap();
+        }
         write!(out, "\x03").unwrap();
         if self.skip_etx_checksum {
             writeln!(out, "0000").unwrap();
@@ -199,6 +330,11 @@ impl JedFile {
         let mut electrical = BitVec::new();
         let mut user = BitVec::new();
         let mut security = None;
+        let mut pin_count = None;
+        let mut vector_count = None;
+        let mut pins = None;
+        let mut default_test_condition = None;
+        let mut vectors = vec![];
         if !options.skip_design_spec {
             let ds_end = position
                 + jed[position..etx]
@@ -226,6 +362,48 @@ impl JedFile {
                 let n: usize = arg.parse().unwrap();
                 fuses = Some(BitVec::repeat(false, n));
                 fuses_valid = Some(BitVec::repeat(false, n));
+            } else if let Some(arg) = field.strip_prefix("QP") {
+                if pin_count.is_some() {
+                    Err(JedParserError::PinCountDuplicated)?;
+                }
+                pin_count = Some(arg.parse().map_err(|_| JedParserError::InvalidArgument)?);
+            } else if let Some(arg) = field.strip_prefix("QV") {
+                if vector_count.is_some() {
+                    Err(JedParserError::VectorCountDuplicated)?;
+                }
+                vector_count = Some(arg.parse().map_err(|_| JedParserError::InvalidArgument)?);
+            } else if let Some(arg) = field.strip_prefix('P') {
+                if pins.is_some() {
+                    Err(JedParserError::PinListDuplicated)?;
+                }
+                pins = Some(
+                    arg.split_ascii_whitespace()
+                        .map(|pin| pin.parse())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| JedParserError::InvalidArgument)?,
+                );
+            } else if let Some(arg) = field.strip_prefix('X') {
+                if default_test_condition.is_some() {
+                    Err(JedParserError::TestConditionDuplicated)?;
+                }
+                default_test_condition = Some(match arg {
+                    "0" => false,
+                    "1" => true,
+                    _ => Err(JedParserError::InvalidArgument)?,
+                });
+            } else if let Some(arg) = field.strip_prefix('V') {
+                let sp = arg.find(' ').ok_or(JedParserError::InvalidArgument)?;
+                let index = arg[..sp]
+                    .parse()
+                    .map_err(|_| JedParserError::InvalidArgument)?;
+                let mut values = vec![];
+                for c in arg[sp..].chars() {
+                    if c.is_ascii_whitespace() {
+                        continue;
+                    }
+                    values.push(TestValue::from_char(c).ok_or(JedParserError::InvalidArgument)?);
+                }
+                vectors.push(TestVector { index, values });
             } else if let Some(arg) = field.strip_prefix("N") {
                 notes.push(arg.to_string());
             } else if let Some(arg) = field.strip_prefix('F') {
@@ -373,6 +551,11 @@ impl JedFile {
             electrical,
             user,
             security,
+            pin_count,
+            vector_count,
+            pins,
+            default_test_condition,
+            vectors,
         };
         if let Some(checksum) = fuse_checksum
             && checksum != res.fuse_checksum()
@@ -390,3 +573,257 @@ impl JedFile {
         Self::parse(&jed, options)
     }
 }
+
+/// A device model that JED test vectors can be applied to.
+pub trait TestTarget {
+    /// Drives the package pin with the given JEDEC pin number from the outside, or leaves
+    /// it undriven if `None`.  Pins unknown to the target are ignored.
+    fn set_pin(&mut self, pin: u32, val: Option<bool>);
+    /// Returns the value the device is driving on the package pin with the given JEDEC pin
+    /// number, or `None` if its output is disabled.
+    fn get_pin(&self, pin: u32) -> Option<bool>;
+    /// Propagates pin changes through the device.
+    fn settle(&mut self);
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TestVectorError {
+    Unsupported {
+        vector: u32,
+        value: TestValue,
+    },
+    Mismatch {
+        vector: u32,
+        pin: u32,
+        expected: TestValue,
+        found: Option<bool>,
+    },
+}
+
+impl std::fmt::Display for TestVectorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestVectorError::Unsupported { vector, value } => {
+                write!(f, "vector {vector}: unsupported test value {value}")
+            }
+            TestVectorError::Mismatch {
+                vector,
+                pin,
+                expected,
+                found,
+            } => {
+                let found = match found {
+                    None => 'Z',
+                    Some(false) => 'L',
+                    Some(true) => 'H',
+                };
+                write!(
+                    f,
+                    "vector {vector}: pin {pin}: expected {expected}, found {found}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TestVectorError {}
+
+impl JedFile {
+    /// Applies the test vectors to a target, checking the expected outputs.  Inputs are
+    /// applied first, then the `C` and `K` clock pulses are performed, and the outputs are
+    /// checked last.  Returns the first failing vector.
+    pub fn run_vectors(&self, target: &mut impl TestTarget) -> Result<(), TestVectorError> {
+        let default = self.default_test_condition.unwrap_or(false);
+        for vector in &self.vectors {
+            let pins: Vec<u32> = match self.pins {
+                Some(ref pins) => pins.clone(),
+                None => (1..=vector.values.len() as u32).collect(),
+            };
+            let mut clocks = vec![];
+            for (&pin, &value) in pins.iter().zip(&vector.values) {
+                match value {
+                    TestValue::Low => target.set_pin(pin, Some(false)),
+                    TestValue::High => target.set_pin(pin, Some(true)),
+                    TestValue::DontCare => target.set_pin(pin, Some(default)),
+                    TestValue::Clock => {
+                        target.set_pin(pin, Some(false));
+                        clocks.push((pin, false));
+                    }
+                    TestValue::ClockInverted => {
+                        target.set_pin(pin, Some(true));
+                        clocks.push((pin, true));
+                    }
+                    TestValue::ExpectLow
+                    | TestValue::ExpectHigh
+                    | TestValue::ExpectZ
+                    | TestValue::Float => target.set_pin(pin, None),
+                    TestValue::NotTested => (),
+                    TestValue::Preload | TestValue::BuriedPreload => {
+                        return Err(TestVectorError::Unsupported {
+                            vector: vector.index,
+                            value,
+                        });
+                    }
+                }
+            }
+            target.settle();
+            if !clocks.is_empty() {
+                for pulse in [true, false] {
+                    for &(pin, idle) in &clocks {
+                        target.set_pin(pin, Some(idle ^ pulse));
+                    }
+                    target.settle();
+                }
+            }
+            for (&pin, &value) in pins.iter().zip(&vector.values) {
+                let expected = match value {
+                    TestValue::ExpectLow => Some(false),
+                    TestValue::ExpectHigh => Some(true),
+                    TestValue::ExpectZ => None,
+                    _ => continue,
+                };
+                let found = target.get_pin(pin);
+                if found != expected {
+                    return Err(TestVectorError::Mismatch {
+                        vector: vector.index,
+                        pin,
+                        expected: value,
+                        found,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> JedFile {
+        let mut jed = JedFile::new()
+            .with_fuses(BitVec::from_iter((0..200).map(|i| i % 3 == 0)))
+            .with_note(" DEVICE XC2C32A-VQ44")
+            .with_vector(vec![
+                TestValue::Low,
+                TestValue::High,
+                TestValue::Clock,
+                TestValue::ClockInverted,
+                TestValue::ExpectLow,
+                TestValue::ExpectHigh,
+            ])
+            .with_vector(vec![
+                TestValue::ExpectZ,
+                TestValue::DontCare,
+                TestValue::NotTested,
+                TestValue::Preload,
+                TestValue::BuriedPreload,
+                TestValue::Float,
+            ]);
+        jed.design_spec = Some("test design".to_string());
+        jed.pin_count = Some(44);
+        jed.vector_count = Some(2);
+        jed.pins = Some(vec![1, 2, 3, 5, 8, 44]);
+        jed.default_test_condition = Some(true);
+        jed
+    }
+
+    #[test]
+    fn roundtrip() {
+        let jed = sample();
+        let text = jed.emit();
+        assert_eq!(
+            JedFile::parse(&text, &JedParserOptions::new()).unwrap(),
+            jed
+        );
+
+        let mut jed = JedFile::new();
+        jed.vectors.push(TestVector {
+            index: 7,
+            values: vec![TestValue::High; 3],
+        });
+        jed.default_test_condition = Some(false);
+        // without fuses there is no C field, which the parser reports as skipped
+        jed.skip_fuse_checksum = true;
+        jed.skip_etx_checksum = true;
+        let text = jed.emit();
+        assert_eq!(
+            JedFile::parse(&text, &JedParserOptions::new().skip_design_spec()).unwrap(),
+            JedFile {
+                design_spec: None,
+                ..jed
+            }
+        );
+    }
+
+    #[test]
+    fn emit_fields() {
+        let mut jed = JedFile::new();
+        jed.pin_count = Some(20);
+        jed.vector_count = Some(1);
+        jed.default_test_condition = Some(false);
+        jed.pins = Some(vec![2, 19]);
+        let jed = jed.with_vector(vec![TestValue::Low, TestValue::ExpectHigh]);
+        let text = jed.emit();
+        let body = text.split('\x03').next().unwrap();
+        assert_eq!(body, "\x02QP20*\nQV1*\nX0*\nP 2 19*\nV0001 0H*\n");
+    }
+
+    #[test]
+    fn parse_fields() {
+        // fields spanning lines, with extra whitespace between vector positions
+        let text =
+            "\x02design*\nQP4*QV2*\nX1*\nP 1 2\n 3 4*\nV0001 01\n LH*\nV0002 CK ZX*\n\x030000\n";
+        let jed = JedFile::parse(text, &JedParserOptions::new()).unwrap();
+        assert_eq!(jed.pin_count, Some(4));
+        assert_eq!(jed.vector_count, Some(2));
+        assert_eq!(jed.default_test_condition, Some(true));
+        assert_eq!(jed.pins, Some(vec![1, 2, 3, 4]));
+        assert_eq!(
+            jed.vectors,
+            [
+                TestVector {
+                    index: 1,
+                    values: vec![
+                        TestValue::Low,
+                        TestValue::High,
+                        TestValue::ExpectLow,
+                        TestValue::ExpectHigh,
+                    ],
+                },
+                TestVector {
+                    index: 2,
+                    values: vec![
+                        TestValue::Clock,
+                        TestValue::ClockInverted,
+                        TestValue::ExpectZ,
+                        TestValue::DontCare,
+                    ],
+                },
+            ]
+        );
+        assert_eq!(
+            JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap(),
+            jed
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let opts = JedParserOptions::new();
+        for (text, err) in [
+            ("\x02*QP4*QP4*\x030000", "pin count duplicated"),
+            ("\x02*QV1*QV2*\x030000", "test vector count duplicated"),
+            ("\x02*P 1*P 2*\x030000", "pin list duplicated"),
+            ("\x02*X0*X1*\x030000", "default test condition duplicated"),
+            ("\x02*X2*\x030000", "invalid argument"),
+            ("\x02*P 1 A*\x030000", "invalid argument"),
+            ("\x02*V0001 0Q*\x030000", "invalid argument"),
+            ("\x02*V0001*\x030000", "invalid argument"),
+        ] {
+            let res = JedFile::parse(text, &opts);
+            assert_eq!(res.unwrap_err().to_string(), err, "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::{
+    db::{BelSlotId, TileSlotId, WireSlotId},
+    grid::{BelCoord, CellCoord, ColId, DieId, RowId, TileCoord, WireCoord},
+};
+use pyo3::{exceptions::PyIndexError, prelude::*};
+
+/// Converts a Python index into an id that holds values below `limit`, raising `IndexError`
+/// for values it cannot hold.  Whether the id exists in a given grid is checked when the
+/// coordinate is used.
+fn index<I: EntityId>(idx: usize, limit: u16, what: &str) -> PyResult<I> {
+    if idx >= usize::from(limit) {
+        return Err(PyIndexError::new_err(format!("{what} {idx} out of range")));
+    }
+    Ok(I::from_idx(idx))
+}
+
+/// A cell of the expanded grid.
+#[pyclass(
+    name = "CellCoord",
+    module = "prjcombine",
+    frozen,
+    eq,
+    ord,
+    hash,
+    from_py_object
+)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PyCellCoord(pub CellCoord);
+
+#[pymethods]
+impl PyCellCoord {
+    #[new]
+    fn new(die: usize, col: usize, row: usize) -> PyResult<Self> {
+        Ok(PyCellCoord(CellCoord {
+            die: index::<DieId>(die, u8::MAX.into(), "die")?,
+            col: index::<ColId>(col, u16::MAX, "column")?,
+            row: index::<RowId>(row, u16::MAX, "row")?,
+        }))
+    }
+
+    #[getter]
+    fn die(&self) -> usize {
+        self.0.die.to_idx()
+    }
+
+    #[getter]
+    fn col(&self) -> usize {
+        self.0.col.to_idx()
+    }
+
+    #[getter]
+    fn row(&self) -> usize {
+        self.0.row.to_idx()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CellCoord(die={}, col={}, row={})",
+            self.die(),
+            self.col(),
+            self.row()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+macro_rules! slot_coord {
+    ($py:ident, $rust:ident, $slot:ident, $limit:expr, $name:literal) => {
+        #[doc = concat!("A `", $name, "`: a cell and a slot index within the interconnect database.")]
+        #[pyclass(name = $name, module = "prjcombine", frozen, eq, ord, hash, from_py_object)]
+        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $py(pub $rust);
+
+        #[pymethods]
+        impl $py {
+            #[new]
+            fn new(cell: PyCellCoord, slot: usize) -> PyResult<Self> {
+                Ok($py($rust {
+                    cell: cell.0,
+                    slot: index::<$slot>(slot, $limit, "slot")?,
+                }))
+            }
+
+            #[getter]
+            fn cell(&self) -> PyCellCoord {
+                PyCellCoord(self.0.cell)
+            }
+
+            #[getter]
+            fn slot(&self) -> usize {
+                self.0.slot.to_idx()
+            }
+
+            pub fn __repr__(&self) -> String {
+                format!(
+                    concat!($name, "({}, slot={})"),
+                    self.cell().__repr__(),
+                    self.slot()
+                )
+            }
+        }
+    };
+}
+
+slot_coord!(
+    PyTileCoord,
+    TileCoord,
+    TileSlotId,
+    u8::MAX.into(),
+    "TileCoord"
+);
+slot_coord!(PyWireCoord, WireCoord, WireSlotId, u16::MAX, "WireCoord");
+slot_coord!(PyBelCoord, BelCoord, BelSlotId, u16::MAX, "BelCoord");
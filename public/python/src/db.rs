@@ -0,0 +1,395 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use prjcombine_entity::{EntityId, EntityMap, EntityVec};
+use prjcombine_interconnect::{db::IntDb, grid::DieId};
+use prjcombine_types::bsdata::{BsData, Tile};
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+};
+use serde::Serialize;
+
+use crate::grid::{Edev, PyExpandedDevice};
+
+/// A loaded database of one family.
+///
+/// Expanded devices keep a clone of the database they borrow from, so that it is freed once
+/// Python drops both the database and every device expanded from it.
+#[derive(Clone)]
+pub enum Db {
+    Xc2000(Arc<prjcombine_xc2000::db::Database>),
+    Virtex(Arc<prjcombine_virtex::db::Database>),
+    Virtex2(Arc<prjcombine_virtex2::db::Database>),
+    Spartan6(Arc<prjcombine_spartan6::db::Database>),
+    Virtex4(Arc<prjcombine_virtex4::db::Database>),
+    Ultrascale(Arc<prjcombine_ultrascale::db::Database>),
+    Siliconblue(Arc<prjcombine_siliconblue::db::Database>),
+    Ecp(Arc<prjcombine_ecp::db::Database>),
+    Xc9500(Arc<prjcombine_xc9500::Database>),
+    Xpla3(Arc<prjcombine_xpla3::Database>),
+    Coolrunner2(Arc<prjcombine_coolrunner2::Database>),
+}
+
+impl Db {
+    pub fn load(target: &str, path: &str) -> Result<Self, String> {
+        fn wrap<T>(db: Result<T, Box<dyn std::error::Error>>) -> Result<Arc<T>, String> {
+            Ok(Arc::new(db.map_err(|e| e.to_string())?))
+        }
+        Ok(match target {
+            "xc2000" | "xc3000" | "xc3000a" | "xc4000" | "xc4000a" | "xc4000h" | "xc4000e"
+            | "xc4000ex" | "xc4000xla" | "xc4000xv" | "spartanxl" | "xc5200" => {
+                Db::Xc2000(wrap(prjcombine_xc2000::db::Database::from_file(path))?)
+            }
+            "virtex" => Db::Virtex(wrap(prjcombine_virtex::db::Database::from_file(path))?),
+            "virtex2" | "spartan3" | "fpgacore" => {
+                Db::Virtex2(wrap(prjcombine_virtex2::db::Database::from_file(path))?)
+            }
+            "spartan6" => Db::Spartan6(wrap(prjcombine_spartan6::db::Database::from_file(path))?),
+            "virtex4" | "virtex5" | "virtex6" | "virtex7" => {
+                Db::Virtex4(wrap(prjcombine_virtex4::db::Database::from_file(path))?)
+            }
+            "ultrascale" | "ultrascaleplus" => {
+                Db::Ultrascale(wrap(prjcombine_ultrascale::db::Database::from_file(path))?)
+            }
+            "siliconblue" => {
+                Db::Siliconblue(wrap(prjcombine_siliconblue::db::Database::from_file(path))?)
+            }
+            "ecp" | "xp" | "machxo" | "ecp2" | "ecp2m" | "xp2" | "ecp3" | "machxo2" | "ecp4"
+            | "scm" | "ecp5" | "crosslink" => {
+                Db::Ecp(wrap(prjcombine_ecp::db::Database::from_file(path))?)
+            }
+            "xc9500" | "xc9500xl" | "xc9500xv" => {
+                Db::Xc9500(wrap(prjcombine_xc9500::Database::from_file(path))?)
+            }
+            "xpla3" => Db::Xpla3(wrap(prjcombine_xpla3::Database::from_file(path))?),
+            "coolrunner2" => {
+                Db::Coolrunner2(wrap(prjcombine_coolrunner2::Database::from_file(path))?)
+            }
+            _ => return Err(format!("unknown target {target}")),
+        })
+    }
+
+    fn family(&self) -> &'static str {
+        match self {
+            Db::Xc2000(_) => "xc2000",
+            Db::Virtex(_) => "virtex",
+            Db::Virtex2(_) => "virtex2",
+            Db::Spartan6(_) => "spartan6",
+            Db::Virtex4(_) => "virtex4",
+            Db::Ultrascale(_) => "ultrascale",
+            Db::Siliconblue(_) => "siliconblue",
+            Db::Ecp(_) => "ecp",
+            Db::Xc9500(_) => "xc9500",
+            Db::Xpla3(_) => "xpla3",
+            Db::Coolrunner2(_) => "coolrunner2",
+        }
+    }
+
+    fn int(&self) -> Option<&IntDb> {
+        match self {
+            Db::Xc2000(db) => Some(&db.int),
+            Db::Virtex(db) => Some(&db.int),
+            Db::Virtex2(db) => Some(&db.int),
+            Db::Spartan6(db) => Some(&db.int),
+            Db::Virtex4(db) => Some(&db.int),
+            Db::Ultrascale(db) => Some(&db.int),
+            Db::Siliconblue(db) => Some(&db.int),
+            Db::Ecp(db) => Some(&db.int),
+            Db::Xc9500(_) | Db::Xpla3(_) | Db::Coolrunner2(_) => None,
+        }
+    }
+
+    fn bsdata_tile(&self, name: &str) -> Option<&Tile> {
+        fn get<'a>(bsdata: &'a BsData, name: &str) -> Option<&'a Tile> {
+            bsdata.tiles.get(name)
+        }
+        match self {
+            Db::Virtex(db) => get(&db.bsdata, name),
+            Db::Virtex4(db) => get(&db.bsdata, name),
+            Db::Ultrascale(db) => get(&db.bsdata, name),
+            Db::Ecp(db) => get(&db.bsdata, name),
+            Db::Xc9500(db) => match name {
+                "MC_BITS" => Some(&db.mc_bits),
+                "BLOCK_BITS" => Some(&db.block_bits),
+                "GLOBAL_BITS" => Some(&db.global_bits),
+                _ => None,
+            },
+            Db::Xpla3(db) => match name {
+                "MC_BITS" => Some(&db.mc_bits),
+                "BLOCK_BITS" => Some(&db.block_bits),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn bsdata_tile_names(&self) -> Vec<String> {
+        match self {
+            Db::Virtex(db) => db.bsdata.tiles.keys().cloned().collect(),
+            Db::Virtex4(db) => db.bsdata.tiles.keys().cloned().collect(),
+            Db::Ultrascale(db) => db.bsdata.tiles.keys().cloned().collect(),
+            Db::Ecp(db) => db.bsdata.tiles.keys().cloned().collect(),
+            Db::Xc9500(_) => vec!["MC_BITS".into(), "BLOCK_BITS".into(), "GLOBAL_BITS".into()],
+            Db::Xpla3(_) => vec!["MC_BITS".into(), "BLOCK_BITS".into()],
+            _ => vec![],
+        }
+    }
+}
+
+/// Converts any serializable database object into plain Python dicts and lists.
+pub fn to_py<'py, T: Serialize>(py: Python<'py>, val: &T) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(val).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+/// Package lookup on the per-device bond maps, which are either entity maps or plain maps
+/// depending on the family.
+trait DevBonds<B> {
+    fn bond(&self, package: &str) -> Option<B>;
+}
+
+impl<I: EntityId, B: Copy> DevBonds<B> for EntityMap<I, String, B> {
+    fn bond(&self, package: &str) -> Option<B> {
+        self.get(package).map(|(_, &bond)| bond)
+    }
+}
+
+impl<B: Copy> DevBonds<B> for BTreeMap<String, B> {
+    fn bond(&self, package: &str) -> Option<B> {
+        self.get(package).copied()
+    }
+}
+
+fn no_such(what: &str, name: impl std::fmt::Display) -> PyErr {
+    PyKeyError::new_err(format!("no {what} {name}"))
+}
+
+/// Extends a borrow of a shared database to `'static`, for storing expanded devices in
+/// Python objects.
+///
+/// # Safety
+///
+/// The result must not be used after the last clone of the `Arc` is dropped.
+unsafe fn extend<T>(db: &Arc<T>) -> &'static T {
+    unsafe { &*Arc::as_ptr(db) }
+}
+
+#[pyclass(name = "Database", module = "prjcombine", frozen)]
+pub struct PyDatabase {
+    pub db: Db,
+}
+
+macro_rules! with_db {
+    ($self:expr, $db:ident => $body:expr) => {
+        match &$self.db {
+            Db::Xc2000($db) => $body,
+            Db::Virtex($db) => $body,
+            Db::Virtex2($db) => $body,
+            Db::Spartan6($db) => $body,
+            Db::Virtex4($db) => $body,
+            Db::Ultrascale($db) => $body,
+            Db::Siliconblue($db) => $body,
+            Db::Ecp($db) => $body,
+            Db::Xc9500($db) => $body,
+            Db::Xpla3($db) => $body,
+            Db::Coolrunner2($db) => $body,
+        }
+    };
+}
+
+#[pymethods]
+impl PyDatabase {
+    /// The family crate this database belongs to, such as `virtex4` or `xc9500`.
+    #[getter]
+    fn family(&self) -> &'static str {
+        self.db.family()
+    }
+
+    /// Names of all devices in the database.
+    #[getter]
+    fn devices(&self) -> Vec<String> {
+        with_db!(self, db => db.devices.iter().map(|dev| dev.name.clone()).collect())
+    }
+
+    #[getter]
+    fn num_chips(&self) -> usize {
+        with_db!(self, db => db.chips.len())
+    }
+
+    #[getter]
+    fn num_bonds(&self) -> usize {
+        with_db!(self, db => db.bonds.len())
+    }
+
+    /// Returns the device with the given name, as a dict.
+    fn device<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        with_db!(self, db => {
+            let dev = db
+                .devices
+                .iter()
+                .find(|dev| dev.name == name)
+                .ok_or_else(|| no_such("device", name))?;
+            to_py(py, dev)
+        })
+    }
+
+    /// Returns the chip with the given index, as a dict.
+    fn chip<'py>(&self, py: Python<'py>, idx: usize) -> PyResult<Bound<'py, PyAny>> {
+        with_db!(self, db => {
+            let chip = db.chips.iter().nth(idx).ok_or_else(|| no_such("chip", idx))?.1;
+            to_py(py, chip)
+        })
+    }
+
+    /// Returns the bond with the given index, as a dict.
+    fn bond<'py>(&self, py: Python<'py>, idx: usize) -> PyResult<Bound<'py, PyAny>> {
+        with_db!(self, db => {
+            let bond = db.bonds.iter().nth(idx).ok_or_else(|| no_such("bond", idx))?.1;
+            to_py(py, bond)
+        })
+    }
+
+    /// Returns the bond of the given device in the given package, as a dict.
+    fn device_bond<'py>(
+        &self,
+        py: Python<'py>,
+        device: &str,
+        package: &str,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        with_db!(self, db => {
+            let dev = db
+                .devices
+                .iter()
+                .find(|dev| dev.name == device)
+                .ok_or_else(|| no_such("device", device))?;
+            let bond = dev
+                .bonds
+                .bond(package)
+                .ok_or_else(|| no_such("package", format!("{package} for device {device}")))?;
+            to_py(py, &db.bonds[bond])
+        })
+    }
+
+    /// Returns the whole interconnect database, as a dict.
+    fn intdb<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let int = self
+            .db
+            .int()
+            .ok_or_else(|| PyValueError::new_err("CPLD databases have no interconnect"))?;
+        to_py(py, int)
+    }
+
+    /// Names of the bitstream tiles in the database.
+    fn bsdata_tiles(&self) -> Vec<String> {
+        self.db.bsdata_tile_names()
+    }
+
+    /// Names of the items of a bitstream tile.
+    fn bsdata_items(&self, tile: &str) -> PyResult<Vec<String>> {
+        let tile = self
+            .db
+            .bsdata_tile(tile)
+            .ok_or_else(|| no_such("bitstream tile", tile))?;
+        Ok(tile.items.keys().cloned().collect())
+    }
+
+    /// Returns a bitstream tile item, as a dict.
+    fn bsdata_item<'py>(
+        &self,
+        py: Python<'py>,
+        tile: &str,
+        item: &str,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let tile_data = self
+            .db
+            .bsdata_tile(tile)
+            .ok_or_else(|| no_such("bitstream tile", tile))?;
+        let item = tile_data
+            .items
+            .get(item)
+            .ok_or_else(|| no_such("item", format!("{item} in tile {tile}")))?;
+        to_py(py, item)
+    }
+
+    /// Expands the interconnect grid of the given device.
+    fn expand(&self, device: &str) -> PyResult<PyExpandedDevice> {
+        macro_rules! find_dev {
+            ($db:expr) => {
+                $db.devices
+                    .iter()
+                    .find(|dev| dev.name == device)
+                    .ok_or_else(|| no_such("device", device))?
+            };
+        }
+        // SAFETY: the database outlives the expanded device, as the device keeps a clone of
+        // its `Arc` and drops the expanded grid first.
+        let edev = match &self.db {
+            Db::Xc2000(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Xc2000(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Virtex(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Virtex(db.chips[dev.chip].expand_grid(&dev.disabled, &db.int))
+            }
+            Db::Virtex2(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Virtex2(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Spartan6(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Spartan6(db.chips[dev.chip].expand_grid(&db.int, &dev.disabled))
+            }
+            Db::Virtex4(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                let chips: EntityVec<DieId, _> =
+                    dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+                let interposer = dev.interposer.map(|ip| &db.interposers[ip]);
+                Edev::Virtex4(prjcombine_virtex4::expand_grid(
+                    &chips,
+                    interposer,
+                    &dev.disabled,
+                    &db.int,
+                    &db.gtz,
+                ))
+            }
+            Db::Ultrascale(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                let chips: EntityVec<DieId, _> =
+                    dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+                Edev::Ultrascale(prjcombine_ultrascale::expand_grid(
+                    &chips,
+                    &db.interposers[dev.interposer],
+                    &dev.disabled,
+                    &db.int,
+                ))
+            }
+            Db::Siliconblue(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Siliconblue(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Ecp(db) => {
+                let db = unsafe { extend(db) };
+                let dev = find_dev!(db);
+                Edev::Ecp(db.chips[dev.chip].expand_grid(&db.int))
+            }
+            Db::Xc9500(_) | Db::Xpla3(_) | Db::Coolrunner2(_) => {
+                return Err(PyValueError::new_err(
+                    "CPLD databases have no interconnect grid",
+                ));
+            }
+        };
+        Ok(PyExpandedDevice::new(edev, self.db.clone()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<prjcombine.Database {}>", self.db.family())
+    }
+}
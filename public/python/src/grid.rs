@@ -0,0 +1,269 @@
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::{
+    db::IntDb,
+    grid::{BelCoord, CellCoord, ExpandedGrid, TileCoord, TilePip, WireCoord},
+};
+use pyo3::{
+    exceptions::{PyIndexError, PyKeyError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    coords::{PyBelCoord, PyCellCoord, PyTileCoord, PyWireCoord},
+    db::Db,
+};
+
+/// An expanded device of one family.
+pub enum Edev {
+    Xc2000(prjcombine_xc2000::expanded::ExpandedDevice<'static>),
+    Virtex(prjcombine_virtex::expanded::ExpandedDevice<'static>),
+    Virtex2(prjcombine_virtex2::expanded::ExpandedDevice<'static>),
+    Spartan6(prjcombine_spartan6::expanded::ExpandedDevice<'static>),
+    Virtex4(prjcombine_virtex4::expanded::ExpandedDevice<'static>),
+    Ultrascale(prjcombine_ultrascale::expanded::ExpandedDevice<'static>),
+    Siliconblue(prjcombine_siliconblue::expanded::ExpandedDevice<'static>),
+    Ecp(prjcombine_ecp::expanded::ExpandedDevice<'static>),
+}
+
+impl Edev {
+    fn egrid(&self) -> &ExpandedGrid<'_> {
+        match self {
+            Edev::Xc2000(edev) => &edev.egrid,
+            Edev::Virtex(edev) => &edev.egrid,
+            Edev::Virtex2(edev) => &edev.egrid,
+            Edev::Spartan6(edev) => &edev.egrid,
+            Edev::Virtex4(edev) => &edev.egrid,
+            Edev::Ultrascale(edev) => &edev.egrid,
+            Edev::Siliconblue(edev) => &edev.egrid,
+            Edev::Ecp(edev) => &edev.egrid,
+        }
+    }
+}
+
+/// A programmable interconnect point between two wires, as found by `wire_pips_fwd`
+/// and `wire_pips_bwd`.
+#[pyclass(name = "TilePip", module = "prjcombine", frozen)]
+pub struct PyTilePip {
+    #[pyo3(get)]
+    wire_out: PyWireCoord,
+    #[pyo3(get)]
+    wire_in: PyWireCoord,
+    #[pyo3(get)]
+    tile: PyTileCoord,
+    #[pyo3(get)]
+    inv: bool,
+}
+
+impl From<TilePip> for PyTilePip {
+    fn from(pip: TilePip) -> Self {
+        PyTilePip {
+            wire_out: PyWireCoord(pip.wire_out),
+            wire_in: PyWireCoord(pip.wire_in),
+            tile: PyTileCoord(pip.tile),
+            inv: pip.inv,
+        }
+    }
+}
+
+#[pymethods]
+impl PyTilePip {
+    fn __repr__(&self) -> String {
+        format!(
+            "TilePip(wire_out={}, wire_in={}, tile={}, inv={})",
+            self.wire_out.__repr__(),
+            self.wire_in.__repr__(),
+            self.tile.__repr__(),
+            if self.inv { "True" } else { "False" },
+        )
+    }
+}
+
+#[pyclass(name = "ExpandedDevice", module = "prjcombine", frozen)]
+pub struct PyExpandedDevice {
+    // Borrows from `_db`; declared first so that it is dropped first.
+    edev: Edev,
+    _db: Db,
+}
+
+impl PyExpandedDevice {
+    /// Wraps a device expanded from the given database.
+    pub fn new(edev: Edev, db: Db) -> Self {
+        PyExpandedDevice { edev, _db: db }
+    }
+
+    fn db(&self) -> &IntDb {
+        self.edev.egrid().db
+    }
+
+    fn check_cell(&self, cell: PyCellCoord) -> PyResult<CellCoord> {
+        let egrid = self.edev.egrid();
+        let cell = cell.0;
+        if egrid.die.get(cell.die).is_none()
+            || !egrid.cols(cell.die).contains(cell.col)
+            || !egrid.rows(cell.die).contains(cell.row)
+        {
+            return Err(PyIndexError::new_err(format!(
+                "cell {cell} is outside the grid"
+            )));
+        }
+        Ok(cell)
+    }
+
+    fn check_tile(&self, tile: PyTileCoord) -> PyResult<TileCoord> {
+        let cell = self.check_cell(PyCellCoord(tile.0.cell))?;
+        if tile.0.slot.to_idx() >= self.db().tile_slots.len() {
+            return Err(PyIndexError::new_err(format!(
+                "tile slot {slot} out of range",
+                slot = tile.0.slot
+            )));
+        }
+        Ok(cell.tile(tile.0.slot))
+    }
+
+    fn check_wire(&self, wire: PyWireCoord) -> PyResult<WireCoord> {
+        let cell = self.check_cell(PyCellCoord(wire.0.cell))?;
+        if wire.0.slot.to_idx() >= self.db().wires.len() {
+            return Err(PyIndexError::new_err(format!(
+                "wire slot {slot} out of range",
+                slot = wire.0.slot
+            )));
+        }
+        Ok(cell.wire(wire.0.slot))
+    }
+
+    fn check_bel(&self, bel: PyBelCoord) -> PyResult<BelCoord> {
+        let cell = self.check_cell(PyCellCoord(bel.0.cell))?;
+        if bel.0.slot.to_idx() >= self.db().bel_slots.len() {
+            return Err(PyIndexError::new_err(format!(
+                "bel slot {slot} out of range",
+                slot = bel.0.slot
+            )));
+        }
+        Ok(cell.bel(bel.0.slot))
+    }
+
+    /// Checks a wire and requires it to be its own canonical node, as the pip queries need.
+    fn check_resolved_wire(&self, wire: PyWireCoord) -> PyResult<WireCoord> {
+        let wire = self.check_wire(wire)?;
+        if self.edev.egrid().resolve_wire(wire) != Some(wire) {
+            return Err(PyValueError::new_err(format!(
+                "wire {name} is not a resolved wire",
+                name = wire.to_string(self.db())
+            )));
+        }
+        Ok(wire)
+    }
+}
+
+#[pymethods]
+impl PyExpandedDevice {
+    /// All cells of the grid, die by die.
+    fn cells(&self) -> Vec<PyCellCoord> {
+        self.edev
+            .egrid()
+            .cells()
+            .map(|(cell, _)| PyCellCoord(cell))
+            .collect()
+    }
+
+    /// All tiles of the grid, as `(TileCoord, tile class name)` pairs.
+    fn tiles(&self) -> Vec<(PyTileCoord, String)> {
+        let db = self.db();
+        self.edev
+            .egrid()
+            .tiles()
+            .map(|(tcrd, tile)| (PyTileCoord(tcrd), db.tile_classes.key(tile.class).clone()))
+            .collect()
+    }
+
+    /// The cells covered by a tile, in tile cell slot order.
+    fn tile_cells(&self, tile: PyTileCoord) -> PyResult<Vec<PyCellCoord>> {
+        let tcrd = self.check_tile(tile)?;
+        let egrid = self.edev.egrid();
+        let tile = egrid
+            .get_tile(tcrd)
+            .ok_or_else(|| PyKeyError::new_err(format!("no tile {}", tcrd.to_string(egrid.db))))?;
+        Ok(tile.cells.values().map(|&cell| PyCellCoord(cell)).collect())
+    }
+
+    /// Makes a tile coordinate from a cell and a tile slot name.
+    fn tile(&self, cell: PyCellCoord, slot: &str) -> PyResult<PyTileCoord> {
+        let cell = self.check_cell(cell)?;
+        let slot = self
+            .db()
+            .tile_slots
+            .get(slot)
+            .ok_or_else(|| PyKeyError::new_err(format!("no tile slot {slot}")))?;
+        Ok(PyTileCoord(cell.tile(slot)))
+    }
+
+    /// Makes a wire coordinate from a cell and a wire name.
+    fn wire(&self, cell: PyCellCoord, name: &str) -> PyResult<PyWireCoord> {
+        let cell = self.check_cell(cell)?;
+        let (slot, _) = self
+            .db()
+            .wires
+            .get(name)
+            .ok_or_else(|| PyKeyError::new_err(format!("no wire {name}")))?;
+        Ok(PyWireCoord(cell.wire(slot)))
+    }
+
+    /// Makes a bel coordinate from a cell and a bel slot name.
+    fn bel(&self, cell: PyCellCoord, slot: &str) -> PyResult<PyBelCoord> {
+        let cell = self.check_cell(cell)?;
+        let (slot, _) = self
+            .db()
+            .bel_slots
+            .get(slot)
+            .ok_or_else(|| PyKeyError::new_err(format!("no bel slot {slot}")))?;
+        Ok(PyBelCoord(cell.bel(slot)))
+    }
+
+    fn tile_name(&self, tile: PyTileCoord) -> PyResult<String> {
+        Ok(self.check_tile(tile)?.to_string(self.db()))
+    }
+
+    fn wire_name(&self, wire: PyWireCoord) -> PyResult<String> {
+        Ok(self.check_wire(wire)?.to_string(self.db()))
+    }
+
+    fn bel_name(&self, bel: PyBelCoord) -> PyResult<String> {
+        Ok(self.check_bel(bel)?.to_string(self.db()))
+    }
+
+    /// The tile containing a bel, or `None` if the bel does not exist in this device.
+    fn bel_tile(&self, bel: PyBelCoord) -> PyResult<Option<PyTileCoord>> {
+        let bel = self.check_bel(bel)?;
+        Ok(self.edev.egrid().find_tile_by_bel(bel).map(PyTileCoord))
+    }
+
+    /// Resolves a wire to its canonical node, or `None` if it is not connected.
+    fn resolve_wire(&self, wire: PyWireCoord) -> PyResult<Option<PyWireCoord>> {
+        let wire = self.check_wire(wire)?;
+        Ok(self.edev.egrid().resolve_wire(wire).map(PyWireCoord))
+    }
+
+    /// All pips driven by the given (resolved) wire.
+    fn wire_pips_fwd(&self, wire: PyWireCoord) -> PyResult<Vec<PyTilePip>> {
+        let wire = self.check_resolved_wire(wire)?;
+        Ok(self
+            .edev
+            .egrid()
+            .wire_pips_fwd(wire)
+            .into_iter()
+            .map(PyTilePip::from)
+            .collect())
+    }
+
+    /// All pips driving the given (resolved) wire.
+    fn wire_pips_bwd(&self, wire: PyWireCoord) -> PyResult<Vec<PyTilePip>> {
+        let wire = self.check_resolved_wire(wire)?;
+        Ok(self
+            .edev
+            .egrid()
+            .wire_pips_bwd(wire)
+            .into_iter()
+            .map(PyTilePip::from)
+            .collect())
+    }
+}
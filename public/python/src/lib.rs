@@ -0,0 +1,51 @@
+//! Python bindings for loading prjcombine databases and querying expanded devices.
+//!
+//! Complex database objects (chips, bonds, devices, bitstream tile items) are handed to
+//! Python as plain dicts and lists, in the same shape as `prjcombine-cli export-json`.
+//! Grid coordinates are exposed as small value classes mirroring the Rust ones.
+
+use std::path::Path;
+
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+mod coords;
+mod db;
+mod grid;
+
+use coords::{PyBelCoord, PyCellCoord, PyTileCoord, PyWireCoord};
+use db::{Db, PyDatabase};
+use grid::{PyExpandedDevice, PyTilePip};
+
+/// Loads a database file.  The target (such as `virtex4` or `xc9500xl`) is taken from the
+/// file name unless given explicitly.
+#[pyfunction]
+#[pyo3(signature = (path, target=None))]
+fn load(py: Python<'_>, path: &str, target: Option<&str>) -> PyResult<PyDatabase> {
+    let target = match target {
+        Some(target) => target,
+        None => Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| {
+                PyValueError::new_err("cannot determine target from file name; pass target=")
+            })?,
+    };
+    let db = py
+        .detach(|| Db::load(target, path))
+        .map_err(PyValueError::new_err)?;
+    Ok(PyDatabase { db })
+}
+
+/// The `prjcombine` Python module.
+#[pymodule]
+pub fn prjcombine(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(load, m)?)?;
+    m.add_class::<PyDatabase>()?;
+    m.add_class::<PyExpandedDevice>()?;
+    m.add_class::<PyTilePip>()?;
+    m.add_class::<PyCellCoord>()?;
+    m.add_class::<PyTileCoord>()?;
+    m.add_class::<PyWireCoord>()?;
+    m.add_class::<PyBelCoord>()?;
+    Ok(())
+}
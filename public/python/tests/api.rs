@@ -0,0 +1,196 @@
+//! Runs small Python scripts against the module, embedded in a Python interpreter.
+
+use std::ffi::CStr;
+
+use pyo3::{prelude::*, types::PyDict};
+
+const DB_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases");
+
+/// Runs a script with the module importable as `prjcombine` and the database directory
+/// in `DB_DIR`, failing the test on any Python exception.
+fn run(script: &CStr) {
+    Python::initialize();
+    Python::attach(|py| {
+        let module = PyModule::new(py, "prjcombine").unwrap();
+        prjcombine::prjcombine(&module).unwrap();
+        py.import("sys")
+            .unwrap()
+            .getattr("modules")
+            .unwrap()
+            .set_item("prjcombine", &module)
+            .unwrap();
+        let globals = PyDict::new(py);
+        globals.set_item("DB_DIR", DB_DIR).unwrap();
+        if let Err(err) = py.run(script, Some(&globals), None) {
+            err.display(py);
+            panic!("{err}");
+        }
+    });
+}
+
+#[test]
+fn load() {
+    run(cr#"
+import prjcombine
+
+db = prjcombine.load(f"{DB_DIR}/virtex.zstd")
+assert db.family == "virtex"
+assert "xcv50" in db.devices
+dev = db.device("xcv50")
+assert dev["name"] == "xcv50"
+assert db.device_bond("xcv50", "pq240")["pins"]
+assert db.num_chips > 0 and db.num_bonds > 0
+
+cpld = prjcombine.load(f"{DB_DIR}/xc9500.zstd")
+assert cpld.family == "xc9500"
+try:
+    cpld.expand("xc9536")
+    raise AssertionError("expanded a CPLD")
+except ValueError:
+    pass
+
+for call in [
+    lambda: prjcombine.load(f"{DB_DIR}/virtex.zstd", target="xc1234"),
+    lambda: prjcombine.load(f"{DB_DIR}/nonexistent.zstd"),
+]:
+    try:
+        call()
+        raise AssertionError("loaded a bad database")
+    except ValueError:
+        pass
+try:
+    db.device("xcv9999")
+    raise AssertionError("found a bad device")
+except KeyError:
+    pass
+"#);
+}
+
+#[test]
+fn expand() {
+    run(cr#"
+import prjcombine
+
+db = prjcombine.load(f"{DB_DIR}/virtex.zstd")
+edev = db.expand("xc2s15")
+# the expanded device keeps the database alive
+del db
+
+cells = edev.cells()
+assert len(cells) == len(set(cells))
+assert all(cell.die == 0 for cell in cells)
+tiles = edev.tiles()
+assert tiles
+kinds = set(kind for _, kind in tiles)
+assert "CLB" in kinds
+for tile, kind in tiles:
+    # global tiles cover no cells
+    tcells = edev.tile_cells(tile)
+    assert not tcells or tile.cell in tcells, (tile, tcells)
+    assert all(cell in cells for cell in tcells)
+assert edev.tile_name(tiles[0][0]) == "D0X0Y0.MAIN"
+
+tile, _ = next(t for t in tiles if t[1] == "CLB")
+assert edev.tile(tile.cell, edev.tile_name(tile).split(".")[1]) == tile
+"#);
+}
+
+#[test]
+fn resolve_wire() {
+    run(cr#"
+import prjcombine
+
+db = prjcombine.load(f"{DB_DIR}/virtex.zstd")
+edev = db.expand("xc2s15")
+wires = [name for name, _ in db.intdb()["wires"]]
+tile, _ = next(t for t in edev.tiles() if t[1] == "CLB")
+num_pips = 0
+for name in wires:
+    wire = edev.wire(tile.cell, name)
+    assert edev.wire_name(wire).endswith(name)
+    node = edev.resolve_wire(wire)
+    if node is None:
+        continue
+    assert edev.resolve_wire(node) == node
+    for pip in edev.wire_pips_bwd(node):
+        assert pip.wire_out == node
+        assert edev.resolve_wire(pip.wire_in) == pip.wire_in
+        assert pip in edev.wire_pips_fwd(pip.wire_in) or any(
+            p.wire_out == node for p in edev.wire_pips_fwd(pip.wire_in)
+        )
+        num_pips += 1
+assert num_pips > 0
+"#);
+}
+
+#[test]
+fn bsdata_tile() {
+    run(cr#"
+import prjcombine
+
+db = prjcombine.load(f"{DB_DIR}/virtex.zstd")
+tiles = db.bsdata_tiles()
+assert tiles
+for tile in tiles:
+    for name in db.bsdata_items(tile):
+        item = db.bsdata_item(tile, name)
+        assert set(item) == {"bits", "kind"}, (tile, name)
+assert db.bsdata_item("IO_E", "INT:MUX.LH[0]")["kind"] == {
+    "Enum": {"values": {"OUT_IO_IQ[3]": ""}}
+}
+try:
+    db.bsdata_items("NO_SUCH_TILE")
+    raise AssertionError("found a bad tile")
+except KeyError:
+    pass
+
+cpld = prjcombine.load(f"{DB_DIR}/xc9500.zstd")
+assert cpld.bsdata_tiles() == ["MC_BITS", "BLOCK_BITS", "GLOBAL_BITS"]
+assert cpld.bsdata_items("MC_BITS")
+"#);
+}
+
+#[test]
+fn out_of_range() {
+    run(cr#"
+import prjcombine
+
+db = prjcombine.load(f"{DB_DIR}/virtex.zstd")
+edev = db.expand("xc2s15")
+cell = edev.cells()[0]
+
+def expect(exc, call):
+    try:
+        call()
+    except exc:
+        return
+    raise AssertionError(f"no {exc.__name__}")
+
+# ids that cannot be represented at all
+expect(IndexError, lambda: prjcombine.CellCoord(1000, 0, 0))
+expect(IndexError, lambda: prjcombine.CellCoord(0, 100000, 0))
+expect(IndexError, lambda: prjcombine.WireCoord(cell, 100000))
+expect(IndexError, lambda: prjcombine.TileCoord(cell, 1000))
+
+# ids that do not exist in this grid or database
+far = prjcombine.CellCoord(0, 1000, 0)
+other_die = prjcombine.CellCoord(3, 0, 0)
+bad_wire = prjcombine.WireCoord(cell, 60000)
+for call in [
+    lambda: edev.tile(far, "MAIN"),
+    lambda: edev.wire(other_die, "GND"),
+    lambda: edev.tile_cells(prjcombine.TileCoord(far, 0)),
+    lambda: edev.tile_name(prjcombine.TileCoord(cell, 200)),
+    lambda: edev.wire_name(bad_wire),
+    lambda: edev.resolve_wire(bad_wire),
+    lambda: edev.resolve_wire(prjcombine.WireCoord(far, 0)),
+    lambda: edev.wire_pips_fwd(bad_wire),
+    lambda: edev.wire_pips_bwd(bad_wire),
+    lambda: edev.bel_name(prjcombine.BelCoord(cell, 60000)),
+    lambda: edev.bel_tile(prjcombine.BelCoord(far, 0)),
+]:
+    expect(IndexError, call)
+expect(KeyError, lambda: edev.wire(cell, "NO_SUCH_WIRE"))
+expect(KeyError, lambda: edev.tile(cell, "NO_SUCH_SLOT"))
+"#);
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use bitvec::prelude::*;
 use prjcombine_entity::EntityId;
+pub use prjcombine_types::bitstream::BitstreamError;
+use prjcombine_types::bitstream::check_len;
 use prjcombine_types::bsdata::{RectBitId, RectFrameId};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
@@ -61,6 +63,15 @@ impl prjcombine_types::bitrect::BitRect for BitRect {
         Some((RectFrameId::from_idx(rframe), RectBitId::from_idx(rbit)))
     }
 
+    fn contains(&self, bit: (RectFrameId, RectBitId)) -> bool {
+        match *self {
+            BitRect::Main(_, _, height, _, width) => {
+                bit.0.to_idx() < height && bit.1.to_idx() < width
+            }
+            _ => true,
+        }
+    }
+
     fn xlat_pos_fwd(&self, bit: (RectFrameId, RectBitId)) -> BitPos {
         let (rframe, rbit) = bit;
         let rframe = rframe.to_idx();
@@ -113,6 +124,14 @@ impl BitstreamBank {
         }
     }
 
+    pub fn new(frame_len: usize, frames: usize) -> Self {
+        Self {
+            frame_len,
+            frame_data: bitvec![0; frame_len * frames],
+            frame_present: bitvec![1; frames],
+        }
+    }
+
     pub fn frame(&self, idx: usize) -> &BitSlice {
         &self.frame_data[idx * self.frame_len..(idx + 1) * self.frame_len]
     }
@@ -214,8 +233,28 @@ impl Bitstream {
         }
     }
 
-    pub fn parse(data: &[u8]) -> Self {
-        assert_eq!(data[..4], [0x7e, 0xaa, 0x99, 0x7e]);
+    pub fn set(&mut self, bit: BitPos, val: bool) {
+        match bit {
+            BitPos::Main(bank, frame, bit) => self.cram[bank].frame_mut(frame).set(bit, val),
+            BitPos::Bram(bank, frame, bit) => self.bram[bank].frame_mut(frame).set(bit, val),
+            BitPos::Speed(bit) => {
+                self.speed &= !(1 << bit);
+                self.speed |= u8::from(val) << bit;
+            }
+            BitPos::CReg(bit) => {
+                self.creg &= !(1 << bit);
+                self.creg |= u16::from(val) << bit;
+            }
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, BitstreamError> {
+        if data.len() < 4 || data[..4] != [0x7e, 0xaa, 0x99, 0x7e] {
+            return Err(BitstreamError::Malformed {
+                offset: 0,
+                reason: "missing preamble".to_string(),
+            });
+        }
         let mut crc = Crc::new();
         let mut pos = 4;
         let mut speed = None;
@@ -227,70 +266,267 @@ impl Bitstream {
         let mut cram = [None, None, None, None];
         let mut bram = [None, None, None, None];
         loop {
+            let start = pos;
+            check_len(data.len(), pos + 1, start)?;
             let opcode = data[pos];
             match opcode & 0xf {
                 1 => {
+                    check_len(data.len(), pos + 2, start)?;
                     crc.feed(&data[pos..pos + 2]);
                     let payload = data[pos + 1];
                     pos += 2;
                     match opcode {
                         0x01 => match payload {
-                            // write CRAM
-                            0x01 => read_bank(
-                                data,
-                                &mut pos,
-                                &mut crc,
-                                &mut cram[bank_idx.unwrap()],
-                                bank_width.unwrap(),
-                                bank_height.unwrap(),
-                                bank_offset.unwrap(),
-                            ),
-                            // write BRAM
-                            0x03 => read_bank(
-                                data,
-                                &mut pos,
-                                &mut crc,
-                                &mut bram[bank_idx.unwrap()],
-                                bank_width.unwrap(),
-                                bank_height.unwrap(),
-                                bank_offset.unwrap(),
-                            ),
+                            // write CRAM, write BRAM
+                            0x01 | 0x03 => {
+                                let (Some(idx), Some(width), Some(height), Some(offset)) =
+                                    (bank_idx, bank_width, bank_height, bank_offset)
+                                else {
+                                    return Err(BitstreamError::Malformed {
+                                        offset: start,
+                                        reason: "bank write without bank parameters".to_string(),
+                                    });
+                                };
+                                let banks = if payload == 0x01 {
+                                    &mut cram
+                                } else {
+                                    &mut bram
+                                };
+                                let Some(bank) = banks.get_mut(idx) else {
+                                    return Err(BitstreamError::Malformed {
+                                        offset: start,
+                                        reason: "invalid bank index".to_string(),
+                                    });
+                                };
+                                read_bank(data, &mut pos, &mut crc, bank, width, height, offset)?;
+                            }
                             // CRC reset
                             0x05 => crc = Crc::new(),
                             // startup (end of bitstream)
                             0x06 => break,
-                            _ => panic!("unk cmd {payload:02x}"),
+                            _ => {
+                                return Err(BitstreamError::UnknownCommand {
+                                    offset: start,
+                                    opcode: opcode.into(),
+                                    payload: Some(payload.into()),
+                                });
+                            }
                         },
                         0x11 => bank_idx = Some(usize::from(payload)),
                         0x51 => speed = Some(payload),
-                        _ => panic!("unknown opcode {opcode:02x} {payload:02x}"),
+                        _ => {
+                            return Err(BitstreamError::UnknownCommand {
+                                offset: start,
+                                opcode: opcode.into(),
+                                payload: Some(payload.into()),
+                            });
+                        }
                     }
                 }
                 2 => {
+                    check_len(data.len(), pos + 3, start)?;
                     let b0 = data[pos + 1];
                     let b1 = data[pos + 2];
                     let payload = u16::from_be_bytes([b0, b1]);
                     crc.feed(&[opcode]);
                     match opcode {
-                        0x22 => assert_eq!(crc.get(), payload),
+                        0x22 => {
+                            if crc.get() != payload {
+                                return Err(BitstreamError::CrcMismatch {
+                                    offset: start,
+                                    expected: payload.into(),
+                                    actual: crc.get().into(),
+                                });
+                            }
+                        }
                         0x62 => bank_width = Some(usize::from(payload) + 1),
                         0x72 => bank_height = Some(usize::from(payload)),
                         0x82 => bank_offset = Some(usize::from(payload)),
                         0x92 => creg = Some(payload),
-                        _ => panic!("unknown opcode {opcode:02x} {payload:04x}"),
+                        _ => {
+                            return Err(BitstreamError::UnknownCommand {
+                                offset: start,
+                                opcode: opcode.into(),
+                                payload: Some(payload.into()),
+                            });
+                        }
                     }
                     crc.feed(&data[pos + 1..pos + 3]);
                     pos += 3;
                 }
-                _ => panic!("unknown opcode {opcode:02x}"),
+                _ => {
+                    return Err(BitstreamError::UnknownCommand {
+                        offset: start,
+                        opcode: opcode.into(),
+                        payload: None,
+                    });
+                }
             }
         }
-        Bitstream {
-            cram: cram.map(Option::unwrap),
+        let missing = |reason: &str| BitstreamError::Malformed {
+            offset: pos,
+            reason: reason.to_string(),
+        };
+        let [c0, c1, c2, c3] = cram;
+        Ok(Bitstream {
+            cram: [
+                c0.ok_or(missing("CRAM bank 0 not written"))?,
+                c1.ok_or(missing("CRAM bank 1 not written"))?,
+                c2.ok_or(missing("CRAM bank 2 not written"))?,
+                c3.ok_or(missing("CRAM bank 3 not written"))?,
+            ],
             bram: bram.map(|x| x.unwrap_or_else(BitstreamBank::empty)),
-            speed: speed.unwrap(),
-            creg: creg.unwrap(),
+            speed: speed.ok_or(missing("speed not set"))?,
+            creg: creg.ok_or(missing("configuration register not set"))?,
+        })
+    }
+
+    /// Serializes the bitstream in the same command layout as icecube's `*_bitmap.bin` output:
+    /// CRAM banks written whole, BRAM banks written in chunks of 128 frames (the last chunk
+    /// may be shorter).  The icecube harvester checks this against every bitstream it reads.
+    ///
+    /// Fails if the banks of one kind have different frame widths, or if a bank does not
+    /// fill a whole number of bytes.
+    pub fn emit(&self) -> Result<Vec<u8>, BitstreamError> {
+        let mut w = BitstreamWriter {
+            data: vec![0x7e, 0xaa, 0x99, 0x7e],
+            crc: Crc::new(),
+        };
+        w.cmd1(0x51, self.speed);
+        // CRC reset
+        w.cmd1(0x01, 0x05);
+        w.crc = Crc::new();
+        w.cmd2(0x92, self.creg);
+        let cram_width = self.cram[0].frame_len;
+        let mut cram_height = self.cram[0].frame_present.len();
+        w.cmd2(0x62, (cram_width - 1) as u16);
+        w.cmd2(0x72, cram_height as u16);
+        w.cmd2(0x82, 0);
+        for (idx, bank) in self.cram.iter().enumerate() {
+            w.check_width(idx, bank, cram_width, "CRAM")?;
+            // the two halves of the device can have a different number of rows
+            let height = bank.frame_present.len();
+            if height != cram_height {
+                w.cmd2(0x72, height as u16);
+                cram_height = height;
+            }
+            w.cmd1(0x11, idx as u8);
+            // write CRAM
+            w.cmd1(0x01, 0x01);
+            w.bank_data(bank, 0..cram_height)?;
+        }
+        if let Some(first) = self.bram.iter().find(|bank| !bank.frame_present.is_empty()) {
+            const BRAM_CHUNK: usize = 0x80;
+            let bram_width = first.frame_len;
+            w.cmd2(0x62, (bram_width - 1) as u16);
+            w.cmd2(0x72, BRAM_CHUNK as u16);
+            let mut cur_height = BRAM_CHUNK;
+            for (idx, bank) in self.bram.iter().enumerate() {
+                if bank.frame_present.is_empty() {
+                    continue;
+                }
+                w.check_width(idx, bank, bram_width, "BRAM")?;
+                w.cmd1(0x11, idx as u8);
+                for offset in (0..bank.frame_present.len()).step_by(BRAM_CHUNK) {
+                    // the last chunk is not padded, so that the bank keeps its length
+                    let height = (bank.frame_present.len() - offset).min(BRAM_CHUNK);
+                    if height != cur_height {
+                        w.cmd2(0x72, height as u16);
+                        cur_height = height;
+                    }
+                    w.cmd2(0x82, offset as u16);
+                    // write BRAM
+                    w.cmd1(0x01, 0x03);
+                    w.bank_data(bank, offset..offset + height)?;
+                }
+            }
+        }
+        w.cmd2(0x22, 0);
+        // startup
+        w.cmd1(0x01, 0x06);
+        w.data.push(0);
+        Ok(w.data)
+    }
+}
+
+struct BitstreamWriter {
+    data: Vec<u8>,
+    crc: Crc,
+}
+
+impl BitstreamWriter {
+    fn cmd1(&mut self, opcode: u8, payload: u8) {
+        let bytes = [opcode, payload];
+        self.crc.feed(&bytes);
+        self.data.extend(bytes);
+    }
+
+    fn cmd2(&mut self, opcode: u8, payload: u16) {
+        self.crc.feed(&[opcode]);
+        // the CRC check command carries the CRC of everything up to and including its opcode
+        let payload = if opcode == 0x22 {
+            self.crc.get()
+        } else {
+            payload
+        };
+        let bytes = payload.to_be_bytes();
+        self.crc.feed(&bytes);
+        self.data.push(opcode);
+        self.data.extend(bytes);
+    }
+
+    fn check_width(
+        &self,
+        idx: usize,
+        bank: &BitstreamBank,
+        width: usize,
+        kind: &str,
+    ) -> Result<(), BitstreamError> {
+        if bank.frame_len != width {
+            return Err(BitstreamError::Malformed {
+                offset: self.data.len(),
+                reason: format!(
+                    "{kind} bank {idx} has frame length {len}, expected {width}",
+                    len = bank.frame_len
+                ),
+            });
         }
+        Ok(())
+    }
+
+    fn bank_data(
+        &mut self,
+        bank: &BitstreamBank,
+        frames: std::ops::Range<usize>,
+    ) -> Result<(), BitstreamError> {
+        let start = self.data.len();
+        if !(bank.frame_len * frames.len()).is_multiple_of(8) {
+            return Err(BitstreamError::Malformed {
+                offset: start,
+                reason: format!(
+                    "{n} frames of {len} bits are not a whole number of bytes",
+                    n = frames.len(),
+                    len = bank.frame_len
+                ),
+            });
+        }
+        let mut byte = 0u8;
+        let mut nbits = 0;
+        for fi in frames {
+            for j in 0..bank.frame_len {
+                let bit = bank.frame(fi)[j];
+                byte = byte << 1 | u8::from(bit);
+                nbits += 1;
+                if nbits == 8 {
+                    self.data.push(byte);
+                    byte = 0;
+                    nbits = 0;
+                }
+            }
+        }
+        self.data.extend([0, 0]);
+        self.crc.feed(&self.data[start..]);
+        Ok(())
     }
 }
 
@@ -302,23 +538,35 @@ fn read_bank(
     width: usize,
     height: usize,
     offset: usize,
-) {
+) -> Result<(), BitstreamError> {
+    let start = *pos;
+    let malformed = |reason: &str| BitstreamError::Malformed {
+        offset: start,
+        reason: reason.to_string(),
+    };
     let nbits = width * height;
-    assert_eq!(nbits % 8, 0);
+    if !nbits.is_multiple_of(8) {
+        return Err(malformed("bank size not a multiple of 8 bits"));
+    }
     let nbytes = nbits / 8 + 2;
+    check_len(data.len(), *pos + nbytes, start)?;
     crc.feed(&data[*pos..*pos + nbytes]);
     let bank = bank.get_or_insert_with(|| BitstreamBank {
         frame_len: width,
         frame_data: BitVec::new(),
         frame_present: BitVec::new(),
     });
-    assert_eq!(bank.frame_len, width);
+    if bank.frame_len != width {
+        return Err(malformed("bank width mismatch"));
+    }
     while bank.frame_present.len() < height + offset {
         bank.frame_present.push(false);
         bank.frame_data.extend(bitvec![0; width]);
     }
     for i in 0..height {
-        assert!(!bank.frame_present[offset + i]);
+        if bank.frame_present[offset + i] {
+            return Err(malformed("frame written twice"));
+        }
         bank.frame_present.set(offset + i, true);
         let frame = bank.frame_mut(offset + i);
         for j in 0..width {
@@ -329,5 +577,83 @@ fn read_bank(
         }
     }
     *pos += nbytes;
-    assert_eq!(data[*pos - 2..*pos], [0, 0]);
+    if data[*pos - 2..*pos] != [0, 0] {
+        return Err(malformed("missing bank data trailer"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_check_value() {
+        // CRC-16/CCITT-FALSE
+        let mut crc = Crc::new();
+        crc.feed(b"123456789");
+        assert_eq!(crc.get(), 0x29b1);
+    }
+
+    // Built by hand in icecube's command order: 16-bit wide banks, 2 CRAM frames per bank,
+    // and a 130-frame BRAM bank 1, written as a 128-frame and a 2-frame chunk.
+    fn reference() -> Vec<u8> {
+        let mut data = vec![0x7e, 0xaa, 0x99, 0x7e, 0x51, 0x02, 0x01, 0x05];
+        let crc_start = data.len();
+        data.extend([
+            0x92, 0x00, 0x10, 0x62, 0x00, 0x0f, 0x72, 0x00, 0x02, 0x82, 0x00, 0x00,
+        ]);
+        for bank in 0..4 {
+            data.extend([0x11, bank, 0x01, 0x01, bank, 0x80, 0x00, 0x01, 0x00, 0x00]);
+        }
+        data.extend([
+            0x62, 0x00, 0x0f, 0x72, 0x00, 0x80, 0x11, 0x01, 0x82, 0x00, 0x00,
+        ]);
+        data.extend([0x01, 0x03]);
+        data.extend((0..0x100).map(|i| i as u8));
+        data.extend([0x00, 0x00, 0x72, 0x00, 0x02, 0x82, 0x00, 0x80, 0x01, 0x03]);
+        data.extend([0xde, 0xad, 0xbe, 0xef, 0x00, 0x00, 0x22]);
+        let mut crc = Crc::new();
+        crc.feed(&data[crc_start..]);
+        data.extend(crc.get().to_be_bytes());
+        data.extend([0x01, 0x06, 0x00]);
+        data
+    }
+
+    #[test]
+    fn emit_reference() {
+        let data = reference();
+        let bs = Bitstream::parse(&data).unwrap();
+        assert_eq!(bs.speed, 2);
+        assert_eq!(bs.creg, 0x10);
+        assert_eq!(bs.cram[3].frame_present.len(), 2);
+        // bank data 03 80 00 01, MSB first
+        let set: Vec<_> = (0..2)
+            .flat_map(|fi| (0..16).map(move |j| (fi, j)))
+            .filter(|&(fi, j)| bs.cram[3].frame(fi)[j])
+            .collect();
+        assert_eq!(set, [(0, 6), (0, 7), (0, 8), (1, 15)]);
+        assert!(bs.bram[0].frame_present.is_empty());
+        assert_eq!(bs.bram[1].frame_present.len(), 130);
+        assert_eq!(bs.emit().unwrap(), data);
+        let bs2 = Bitstream::parse(&bs.emit().unwrap()).unwrap();
+        assert!(Bitstream::diff(&bs, &bs2).is_empty());
+    }
+
+    #[test]
+    fn emit_errors() {
+        let bs = Bitstream::parse(&reference()).unwrap();
+        let mut bad = bs.clone();
+        bad.cram[2] = BitstreamBank::new(8, 2);
+        assert!(matches!(
+            bad.emit(),
+            Err(BitstreamError::Malformed { reason, .. }) if reason.contains("CRAM bank 2")
+        ));
+        let mut bad = bs;
+        bad.cram = std::array::from_fn(|_| BitstreamBank::new(5, 1));
+        assert!(matches!(
+            bad.emit(),
+            Err(BitstreamError::Malformed { reason, .. }) if reason.contains("whole number of bytes")
+        ));
+    }
 }
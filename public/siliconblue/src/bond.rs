@@ -3,10 +3,15 @@ use std::collections::BTreeMap;
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_interconnect::{db::IntDb, grid::BelPadCoord};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, split_assign},
+};
 
 use crate::defs;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, Vec<BelPadCoord>>,
 }
@@ -69,4 +74,40 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name.
+    pub fn diff_node(&self, db: &IntDb) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (pin, pads) in &self.pins {
+            let pads = if pads.is_empty() {
+                "nc".to_string()
+            } else {
+                pads.iter().map(|x| x.to_string(db)).join(" + ")
+            };
+            res.insert_leaf(format!("pin {pin}"), pads);
+        }
+        res
+    }
+
+    /// Parses the body of a `bond` block written by [`Bond::dump`].
+    pub fn parse(p: &mut TextParser, db: &IntDb) -> Result<Self, TextError> {
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            if kw != "pin" {
+                return Err(line.error(format!("unknown bond statement {kw}")));
+            }
+            let (pin, pads) = line.check(split_assign(val))?;
+            let pads = if pads == "nc" {
+                vec![]
+            } else {
+                pads.split(" + ")
+                    .map(|pad| line.check(BelPadCoord::parse(db, pad)))
+                    .collect::<Result<_, _>>()?
+            };
+            pins.insert(pin.to_string(), pads);
+        }
+        Ok(Bond { pins })
+    }
 }
@@ -2,17 +2,22 @@ use std::collections::{BTreeMap, BTreeSet};
 
 use bincode::{Decode, Encode};
 use itertools::Itertools;
-use prjcombine_entity::{EntityId, EntityRange, EntityVec};
+use prjcombine_entity::{EntityId, EntityRange, EntityVec, id::EntityTag};
 use prjcombine_interconnect::{
     db::{CellSlotId, IntDb, TileClassId},
     dir::{Dir, DirH, DirV},
-    grid::{BelCoord, CellCoord, ColId, DieId, DieIdExt, RowId, TileCoord},
+    grid::{BelCoord, CellCoord, ColId, ColTag, DieId, DieIdExt, RowId, RowTag, TileCoord},
+};
+use prjcombine_types::{
+    bimap::BiMap,
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign, split_list},
 };
-use prjcombine_types::bimap::BiMap;
 
 use crate::defs;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Ice65L01,
     Ice65L04,
@@ -280,14 +285,40 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ice65l01" => ChipKind::Ice65L01,
+            "ice65l04" => ChipKind::Ice65L04,
+            "ice65l08" => ChipKind::Ice65L08,
+            "ice65p04" => ChipKind::Ice65P04,
+            "ice40p01" => ChipKind::Ice40P01,
+            "ice40p08" => ChipKind::Ice40P08,
+            "ice40p03" => ChipKind::Ice40P03,
+            "ice40m08" => ChipKind::Ice40M08,
+            "ice40m16" => ChipKind::Ice40M16,
+            "ice40r04" => ChipKind::Ice40R04,
+            "ice40t04" => ChipKind::Ice40T04,
+            "ice40t01" => ChipKind::Ice40T01,
+            "ice40t05" => ChipKind::Ice40T05,
+            _ => return Err(format!("invalid chip kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpecialTile {
     // has IOI coords
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub io: BTreeMap<SpecialIoKey, BelCoord>,
     pub cells: EntityVec<CellSlotId, CellCoord>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialTileKey {
     Globals,
     GbRoot,
@@ -370,7 +401,42 @@ impl std::fmt::Display for SpecialTileKey {
     }
 }
 
+impl std::str::FromStr for SpecialTileKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GLOBALS" => SpecialTileKey::Globals,
+            "GB_ROOT" => SpecialTileKey::GbRoot,
+            "MISC" => SpecialTileKey::Misc,
+            "WARMBOOT" => SpecialTileKey::Warmboot,
+            "LSOSC" => SpecialTileKey::LsOsc,
+            "HSOSC" => SpecialTileKey::HsOsc,
+            _ => {
+                if let Some(edge) = s.strip_prefix("LATCH_IO_") {
+                    SpecialTileKey::LatchIo(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("PLL_STUB_") {
+                    SpecialTileKey::PllStub(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("PLL_") {
+                    SpecialTileKey::Pll(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("SPI_") {
+                    SpecialTileKey::Spi(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("I2C_FIFO_") {
+                    SpecialTileKey::I2cFifo(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("I2C_") {
+                    SpecialTileKey::I2c(edge.parse()?)
+                } else if let Some(edge) = s.strip_prefix("SPRAM_") {
+                    SpecialTileKey::SpramPair(edge.parse()?)
+                } else {
+                    return Err(format!("invalid special tile {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpecialIoKey {
     CfgSdo(usize),
     CfgSdi(usize),
@@ -435,7 +501,52 @@ impl std::fmt::Display for SpecialIoKey {
     }
 }
 
+impl std::str::FromStr for SpecialIoKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CFG_SCK" => SpecialIoKey::CfgSck,
+            "CFG_CS_B" => SpecialIoKey::CfgCsB,
+            "CBSEL0" => SpecialIoKey::CbSel0,
+            "CBSEL1" => SpecialIoKey::CbSel1,
+            "JTAG_TDI" => SpecialIoKey::JtagTdi,
+            "JTAG_TMS" => SpecialIoKey::JtagTms,
+            "JTAG_TCK" => SpecialIoKey::JtagTck,
+            "JTAG_TDO" => SpecialIoKey::JtagTdo,
+            "PLL_A" => SpecialIoKey::PllA,
+            "PLL_B" => SpecialIoKey::PllB,
+            "SPI_COPI" => SpecialIoKey::SpiCopi,
+            "SPI_CIPO" => SpecialIoKey::SpiCipo,
+            "SPI_SCK" => SpecialIoKey::SpiSck,
+            "SPI_CS_B0" => SpecialIoKey::SpiCsB0,
+            "SPI_CS_B1" => SpecialIoKey::SpiCsB1,
+            "I2C_SCL" => SpecialIoKey::I2cScl,
+            "I2C_SDA" => SpecialIoKey::I2cSda,
+            "RGB_LED0" => SpecialIoKey::RgbLed0,
+            "RGB_LED1" => SpecialIoKey::RgbLed1,
+            "RGB_LED2" => SpecialIoKey::RgbLed2,
+            "IR_LED" => SpecialIoKey::IrLed,
+            "BARCODE_LED" => SpecialIoKey::BarcodeLed,
+            "I3C0" => SpecialIoKey::I3c0,
+            "I3C1" => SpecialIoKey::I3c1,
+            _ => {
+                if let Some(idx) = s.strip_prefix("CFG_SDO") {
+                    SpecialIoKey::CfgSdo(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("CFG_SDI") {
+                    SpecialIoKey::CfgSdi(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("GB_IN") {
+                    SpecialIoKey::GbIn(parse_num(idx)?)
+                } else {
+                    return Err(format!("invalid special IO {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: usize,
@@ -448,6 +559,7 @@ pub struct Chip {
     pub rows_mac16: Vec<RowId>,
     pub ioi_iob: BiMap<BelCoord, BelCoord>,
     pub ioi_od: BTreeSet<BelCoord>,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub special_tiles: BTreeMap<SpecialTileKey, SpecialTile>,
 }
 
@@ -663,4 +775,150 @@ impl Chip {
 
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with IOs and special tiles
+    /// keyed by name.
+    pub fn diff_node(&self, db: &IntDb) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        res.insert_leaf("columns", self.columns);
+        res.insert_leaf("rows", self.rows);
+        res.insert_leaf("col_bio_split", self.col_bio_split);
+        for col in &self.cols_bram {
+            res.insert(format!("col_bram {col}"), DiffNode::new());
+        }
+        res.insert_leaf("row_mid", self.row_mid);
+        for &(row_hclk, row_start, row_end) in &self.rows_colbuf {
+            res.insert_leaf(
+                format!("row_colbuf {row_hclk}"),
+                format!("{row_start}..{row_end}"),
+            );
+        }
+        for row in &self.rows_mac16 {
+            res.insert(format!("row_mac16 {row}"), DiffNode::new());
+        }
+        for (ioi, iob) in &self.ioi_iob {
+            res.insert_leaf(
+                format!("iob {ioi}", ioi = ioi.to_string(db)),
+                iob.to_string(db),
+            );
+        }
+        for ioi in &self.ioi_od {
+            res.insert(
+                format!("ioi_od {ioi}", ioi = ioi.to_string(db)),
+                DiffNode::new(),
+            );
+        }
+        for (key, spec) in &self.special_tiles {
+            let mut node = DiffNode::new();
+            for (idx, v) in &spec.cells {
+                node.insert_leaf(format!("cell {idx}"), v);
+            }
+            for (k, v) in &spec.io {
+                node.insert_leaf(format!("io {k}"), v.to_string(db));
+            }
+            res.insert(format!("special {key}"), node);
+        }
+        res
+    }
+
+    /// Parses the body of a `chip` block written by [`Chip::dump`].
+    pub fn parse(p: &mut TextParser, db: &IntDb) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = None;
+        let mut col_bio_split = None;
+        let mut cols_bram = vec![];
+        let mut rows = None;
+        let mut row_mid = None;
+        let mut rows_colbuf = vec![];
+        let mut rows_mac16 = vec![];
+        let mut ioi_iob = BiMap::new();
+        let mut ioi_od = BTreeSet::new();
+        let mut special_tiles = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            if let Some((kw, key)) = line.block_start() {
+                if kw != "special" {
+                    return Err(line.error(format!("unknown chip block {kw}")));
+                }
+                let key: SpecialTileKey = line.check(key.parse())?;
+                let mut tile = SpecialTile {
+                    io: BTreeMap::new(),
+                    cells: EntityVec::new(),
+                };
+                while !p.try_block_end() {
+                    let line = p.expect_line()?;
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "cell" => {
+                            tile.cells.push(line.check(val.parse())?);
+                        }
+                        "io" => {
+                            let (k, v) = line.check(split_assign(val))?;
+                            tile.io.insert(
+                                line.check(k.parse())?,
+                                line.check(BelCoord::parse(db, v))?,
+                            );
+                        }
+                        _ => return Err(line.error(format!("unknown special tile statement {kw}"))),
+                    }
+                }
+                special_tiles.insert(key, tile);
+                continue;
+            }
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "columns" => columns = Some(line.check(parse_num(val))?),
+                "rows" => rows = Some(line.check(parse_num(val))?),
+                "col_bio_split" => col_bio_split = Some(line.check(parse_id(val, ColTag::PREFIX))?),
+                "cols_bram" => {
+                    for col in split_list(val) {
+                        cols_bram.push(line.check(parse_id(col, ColTag::PREFIX))?);
+                    }
+                }
+                "row_mid" => row_mid = Some(line.check(parse_id(val, RowTag::PREFIX))?),
+                "row_colbuf" => {
+                    let (row_hclk, range) = line.check(split_assign(val))?;
+                    let Some((row_start, row_end)) = range.split_once("..") else {
+                        return Err(line.error(format!("invalid row range \"{range}\"")));
+                    };
+                    rows_colbuf.push((
+                        line.check(parse_id(row_hclk, RowTag::PREFIX))?,
+                        line.check(parse_id(row_start, RowTag::PREFIX))?,
+                        line.check(parse_id(row_end, RowTag::PREFIX))?,
+                    ));
+                }
+                "rows_mac16" => {
+                    for row in split_list(val) {
+                        rows_mac16.push(line.check(parse_id(row, RowTag::PREFIX))?);
+                    }
+                }
+                "iob" => {
+                    let (ioi, iob) = line.check(split_assign(val))?;
+                    ioi_iob.insert(
+                        line.check(BelCoord::parse(db, ioi))?,
+                        line.check(BelCoord::parse(db, iob))?,
+                    );
+                }
+                "ioi_od" => {
+                    ioi_od.insert(line.check(BelCoord::parse(db, val))?);
+                }
+                _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns: columns.ok_or_else(|| p.missing("columns"))?,
+            col_bio_split: col_bio_split.ok_or_else(|| p.missing("col_bio_split"))?,
+            cols_bram,
+            rows: rows.ok_or_else(|| p.missing("rows"))?,
+            row_mid: row_mid.ok_or_else(|| p.missing("row_mid"))?,
+            rows_colbuf,
+            rows_mac16,
+            ioi_iob,
+            ioi_od,
+            special_tiles,
+        })
+    }
 }
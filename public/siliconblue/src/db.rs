@@ -1,16 +1,19 @@
 use std::{collections::BTreeMap, error::Error, fs::File, path::Path};
 
 use bincode::{Decode, Encode};
-use prjcombine_entity::EntityVec;
+use prjcombine_entity::{EntityVec, id::EntityTag};
 use prjcombine_interconnect::db::IntDb;
 use prjcombine_types::{
-    db::{BondId, ChipId, DumpFlags, SpeedId},
+    db::{BondId, BondTag, ChipId, ChipTag, DumpFlags, SpeedId, SpeedTag},
+    diff::{DbDiff, DiffNode},
     speed::Speed,
+    text::{TextError, TextParser, expect_next_id, parse_id, split_assign},
 };
 
 use crate::{bond::Bond, chip::Chip};
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -19,7 +22,47 @@ pub struct Device {
     pub temps: Vec<String>,
 }
 
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut bonds = BTreeMap::new();
+        let mut speeds = BTreeMap::new();
+        let mut temps = vec![];
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "bond" => {
+                    let (pkg, bond) = line.check(split_assign(val))?;
+                    bonds.insert(
+                        pkg.to_string(),
+                        line.check(parse_id(bond, BondTag::PREFIX))?,
+                    );
+                }
+                "speed" => {
+                    let (speed, sid) = line.check(split_assign(val))?;
+                    speeds.insert(
+                        speed.to_string(),
+                        line.check(parse_id(sid, SpeedTag::PREFIX))?,
+                    );
+                }
+                "temp" => temps.push(val.to_string()),
+                _ => return Err(line.error(format!("unknown device statement {kw}"))),
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds,
+            speeds,
+            temps,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -45,6 +88,80 @@ impl Database {
         Ok(())
     }
 
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        // Chips and bonds name their bels by the interconnect database, which comes last.
+        let Some(mut ip) = p.find_block("intdb") else {
+            return Err(p.missing("intdb"));
+        };
+        let int = IntDb::parse(&mut ip)?;
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut speeds = EntityVec::new();
+        let mut devices = vec![];
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match kw {
+                "chip" => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p, &int)?);
+                }
+                "bond" => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p, &int)?);
+                }
+                "speed" => {
+                    line.check(expect_next_id(name, speeds.next_id()))?;
+                    speeds.push(Speed::parse(&mut p)?);
+                }
+                "device" => devices.push(Device::parse(&mut p, name)?),
+                "intdb" => p.skip_block()?,
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            speeds,
+            devices,
+            int,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips, bonds and speeds are compared through the
+    /// devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node(&self.int));
+            for (pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node(&self.int));
+            }
+            for (sname, &speed) in &dev.speeds {
+                node.insert(format!("speed {sname}"), self.speeds[speed].diff_node());
+            }
+            for temp in &dev.temps {
+                node.insert(format!("temp {temp}"), DiffNode::new());
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("intdb", self.int.diff_node());
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
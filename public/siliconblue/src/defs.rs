@@ -1436,3 +1436,35 @@ pub const QUAD_V: &[EntityStaticRange<WireSlotId, 12>; 5] = &[
     wires::QUAD_V3,
     wires::QUAD_V4,
 ];
+
+pub const LONG_H: &[EntityStaticRange<WireSlotId, 2>; 13] = &[
+    wires::LONG_H0,
+    wires::LONG_H1,
+    wires::LONG_H2,
+    wires::LONG_H3,
+    wires::LONG_H4,
+    wires::LONG_H5,
+    wires::LONG_H6,
+    wires::LONG_H7,
+    wires::LONG_H8,
+    wires::LONG_H9,
+    wires::LONG_H10,
+    wires::LONG_H11,
+    wires::LONG_H12,
+];
+
+pub const LONG_V: &[EntityStaticRange<WireSlotId, 2>; 13] = &[
+    wires::LONG_V0,
+    wires::LONG_V1,
+    wires::LONG_V2,
+    wires::LONG_V3,
+    wires::LONG_V4,
+    wires::LONG_V5,
+    wires::LONG_V6,
+    wires::LONG_V7,
+    wires::LONG_V8,
+    wires::LONG_V9,
+    wires::LONG_V10,
+    wires::LONG_V11,
+    wires::LONG_V12,
+];
@@ -1,9 +1,13 @@
 use prjcombine_entity::{EntityId, EntityVec};
-use prjcombine_interconnect::grid::{ColId, ExpandedGrid, RowId, TileCoord};
-use prjcombine_types::bsdata::BitRectId;
+use prjcombine_interconnect::{
+    decode::{DecodedBitstream, decode_tiles},
+    encode::{EncodeError, encode_tiles},
+    grid::{ColId, ExpandedGrid, RowId, TileCoord},
+};
+use prjcombine_types::bsdata::{BitRectId, BsData};
 
 use crate::{
-    bitstream::{BitPos, BitRect},
+    bitstream::{BitPos, BitRect, Bitstream, BitstreamBank},
     chip::Chip,
     defs,
 };
@@ -185,6 +189,61 @@ impl ExpandedDevice<'_> {
             )
         }
     }
+
+    /// An all-zero bitstream with the bank layout of this device.
+    pub fn empty_bitstream(&self) -> Bitstream {
+        let cram_height = [
+            self.chip.row_mid.to_idx() * 16,
+            (self.chip.rows - self.chip.row_mid.to_idx()) * 16,
+        ];
+        // the BRAM banks all have the same width, even when one half of the device is shorter
+        let mut bram_width = 0;
+        for (tcrd, _) in self.egrid.tiles() {
+            for rect in self.tile_bits(tcrd).into_values() {
+                if let BitRect::Bram(_, bit) = rect {
+                    bram_width = bram_width.max(bit + 16);
+                }
+            }
+        }
+        let bram_height = if bram_width == 0 { 0 } else { 256 };
+        Bitstream {
+            cram: std::array::from_fn(|bank| {
+                BitstreamBank::new(self.frame_width, cram_height[bank & 1])
+            }),
+            bram: std::array::from_fn(|_| BitstreamBank::new(bram_width, bram_height)),
+            speed: 0,
+            creg: 0,
+        }
+    }
+
+    pub fn decode(
+        &self,
+        bsdata: &BsData,
+        bitstream: &Bitstream,
+    ) -> Result<DecodedBitstream, EncodeError> {
+        decode_tiles(
+            &self.egrid,
+            bsdata,
+            |tcrd| self.tile_bits(tcrd),
+            |bit| bitstream.get(bit),
+        )
+    }
+
+    pub fn encode(
+        &self,
+        bsdata: &BsData,
+        config: &DecodedBitstream,
+    ) -> Result<Bitstream, EncodeError> {
+        let mut bitstream = self.empty_bitstream();
+        encode_tiles(
+            &self.egrid,
+            bsdata,
+            config,
+            |tcrd| self.tile_bits(tcrd),
+            |bit, val| bitstream.set(bit, val),
+        )?;
+        Ok(bitstream)
+    }
 }
 
 impl<'a> std::ops::Deref for ExpandedDevice<'a> {
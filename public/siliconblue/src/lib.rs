@@ -5,3 +5,5 @@ pub mod db;
 pub mod defs;
 pub mod expand;
 pub mod expanded;
+pub mod pinout;
+pub mod timing;
@@ -0,0 +1,89 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use itertools::Itertools;
+use prjcombine_interconnect::db::{BelKind, IntDb, PadKind};
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{bond::Bond, chip::Chip, defs};
+
+impl Chip {
+    pub fn make_pinout(&self, bond: &Bond, db: &IntDb, device: &str, package: &str) -> Pinout {
+        let mut special_io: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for tile in self.special_tiles.values() {
+            for (&key, &io) in &tile.io {
+                special_io.entry(io).or_default().push(key);
+            }
+        }
+        let bank_name = |slot| {
+            if slot == defs::bslots::IO_BANK_SPI {
+                "SPI".to_string()
+            } else {
+                defs::bslots::IO_BANK.index_of(slot).unwrap().to_string()
+            }
+        };
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, pads) in &bond.pins {
+            let Some(&pad) = pads.first() else {
+                res.add_pin(PinoutPin::new(pin, "NC", PinKind::Nc));
+                continue;
+            };
+            let BelKind::Class(bcid) = db.bel_slots[pad.slot].kind else {
+                unreachable!()
+            };
+            let (pad_name, _) = db.bel_classes[bcid].pads.key(pad.pad);
+            let pad_kind = db.bel_classes[bcid].pads[pad.pad].kind;
+            let kind = if defs::bslots::IOI.contains(pad.slot) {
+                PinKind::Io
+            } else if matches!(pad_kind, PadKind::Power | PadKind::Analog) {
+                if pad_name.contains("GND") {
+                    PinKind::Ground
+                } else {
+                    PinKind::Power
+                }
+            } else if pad.slot == defs::bslots::CONFIG {
+                PinKind::Config
+            } else {
+                PinKind::Other
+            };
+            let mut ppin =
+                PinoutPin::new(pin, pads.iter().map(|x| x.to_string(db)).join(" + "), kind);
+            if kind == PinKind::Io {
+                let ioi = pad.bel;
+                let io = ioi.to_string(db);
+                ppin = ppin
+                    .with_bank(bank_name(self.get_io_bank(ioi).slot))
+                    .with_io(&io);
+                // the LVDS input lives in IOI 0 and takes IOI 1 as its complement
+                let ioi_p = ioi.cell.bel(defs::bslots::IOI[0]);
+                let ioi_n = ioi.cell.bel(defs::bslots::IOI[1]);
+                if self.ioi_has_lvds(ioi_p) {
+                    if ioi == ioi_p {
+                        ppin.diff = Some(DiffPolarity::P);
+                        partners.insert(io, ioi_n.to_string(db));
+                    } else {
+                        ppin.diff = Some(DiffPolarity::N);
+                        partners.insert(io, ioi_p.to_string(db));
+                    }
+                }
+                if let Some(keys) = special_io.get(&ioi) {
+                    for key in keys {
+                        ppin = ppin.with_function(key);
+                    }
+                }
+            } else if pad.slot == defs::bslots::IO_BANK_SPI
+                || defs::bslots::IO_BANK.contains(pad.slot)
+            {
+                let bank = bank_name(pad.slot);
+                ppin = ppin.with_name(format!("{pad_name}_{bank}")).with_bank(bank);
+            } else {
+                ppin = ppin.with_name(pad_name);
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
@@ -0,0 +1,928 @@
+//! Timing model of the interconnect and the main cells.
+//!
+//! [`TimingModel`] attaches the delays of a speed grade to the routing of an expanded device.
+//! Every PIP pays the delay of the multiplexer driving its destination wire.  Span wires
+//! (`QUAD_*` and `LONG_*`) additionally pay a delay that depends on the number of segments
+//! between the point where they are driven and the point where they are tapped, following
+//! the `Span4Mux_sN` / `Span12Mux_sN` model of the vendor tools.  Cell arcs cover the logic
+//! cells, block RAMs, and I/O interfaces.
+//!
+//! [`TimingModel::analyze`] builds a timing graph out of a set of used PIPs and cells, and
+//! computes the arrival times of rising and falling edges at every wire and cell pin, with
+//! input pads launching at time zero.  Register outputs are timed from the arrival of their
+//! clock, so clock insertion delay is included.  Arcs from the falling clock edge (the `_N`
+//! values of DDR I/O) have their input edges swapped.  Combinatorial loops are broken at
+//! an arbitrary point.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_entity::EntityBundleItemIndex;
+use prjcombine_interconnect::{
+    db::{Bel, BelInfo, BelInputId, BelKind, BelOutputId, IntDb, WireSlotId},
+    grid::{BelCoord, TileCoord, TilePip, WireCoord},
+};
+use prjcombine_types::{
+    speed::{EdgeArrival, EdgeDelay, SetupHoldRf, Speed, SpeedError},
+    timing::{ClockEdge, TimingGraph, TimingNodeId, TimingNodeKind},
+    units::Time,
+};
+
+use crate::{
+    defs::{self, bcls, bslots, wires},
+    expanded::ExpandedDevice,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SpanKind {
+    QuadH,
+    QuadV,
+    LongH,
+    LongV,
+}
+
+impl SpanKind {
+    fn is_long(self) -> bool {
+        matches!(self, SpanKind::LongH | SpanKind::LongV)
+    }
+}
+
+const QUAD_V_W: [prjcombine_entity::id::EntityStaticRange<WireSlotId, 12>; 4] = [
+    wires::QUAD_V1_W,
+    wires::QUAD_V2_W,
+    wires::QUAD_V3_W,
+    wires::QUAD_V4_W,
+];
+
+/// Returns the kind of a span wire and the number of segments from its root.
+fn span_pos(slot: WireSlotId) -> Option<(SpanKind, usize)> {
+    for i in 0..5 {
+        if defs::QUAD_H[i].contains(slot) {
+            return Some((SpanKind::QuadH, i));
+        }
+        if defs::QUAD_V[i].contains(slot) {
+            return Some((SpanKind::QuadV, i));
+        }
+    }
+    for (i, range) in QUAD_V_W.iter().enumerate() {
+        if range.contains(slot) {
+            return Some((SpanKind::QuadV, i + 1));
+        }
+    }
+    for i in 0..13 {
+        if defs::LONG_H[i].contains(slot) {
+            return Some((SpanKind::LongH, i));
+        }
+        if defs::LONG_V[i].contains(slot) {
+            return Some((SpanKind::LongV, i));
+        }
+    }
+    None
+}
+
+/// Turns a missing speed value into `None`, for arcs that not all devices have.
+fn optional<T>(res: Result<T, SpeedError>) -> Result<Option<T>, SpeedError> {
+    match res {
+        Ok(val) => Ok(Some(val)),
+        Err(SpeedError::Missing(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// A pin of a cell, as seen by the timing model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CellPin {
+    Input(BelInputId),
+    Output(BelOutputId),
+    /// The signal arriving at an I/O pad from outside the device.
+    PadIn,
+    /// The signal driven onto an I/O pad.
+    PadOut,
+}
+
+/// A point in the timing graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimingPoint {
+    /// A routing wire, at the point where it is driven.
+    Wire(WireCoord),
+    /// A cell pin.
+    Pin(BelCoord, CellPin),
+}
+
+impl TimingPoint {
+    pub fn to_string(&self, db: &IntDb) -> String {
+        match *self {
+            TimingPoint::Wire(wire) => wire.to_string(db),
+            TimingPoint::Pin(bel, pin) => {
+                let BelKind::Class(bcid) = db.bel_slots[bel.slot].kind else {
+                    unreachable!()
+                };
+                let bcls = &db.bel_classes[bcid];
+                let (name, idx, indexing) = match pin {
+                    CellPin::Input(pid) => {
+                        let (name, idx) = bcls.inputs.key(pid);
+                        (name, idx, bcls.inputs[pid].indexing)
+                    }
+                    CellPin::Output(pid) => {
+                        let (name, idx) = bcls.outputs.key(pid);
+                        (name, idx, bcls.outputs[pid].indexing)
+                    }
+                    CellPin::PadIn => return format!("{bel}.PAD.IN", bel = bel.to_string(db)),
+                    CellPin::PadOut => return format!("{bel}.PAD.OUT", bel = bel.to_string(db)),
+                };
+                match idx {
+                    EntityBundleItemIndex::Single => {
+                        format!("{bel}.{name}", bel = bel.to_string(db))
+                    }
+                    EntityBundleItemIndex::Array { index, .. } => format!(
+                        "{bel}.{name}[{index}]",
+                        bel = bel.to_string(db),
+                        index = indexing.phys_to_virt(index)
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// The timing-relevant configuration of a logic cell.  The fields mirror the `LC` bel
+/// attributes of the same names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct LcConfig {
+    pub ff_enable: bool,
+    pub ff_sr_async: bool,
+    pub carry_enable: bool,
+    pub ltin_enable: bool,
+    /// Only meaningful for `LC0`: `MUX_CI` is set to `CHAIN`.
+    pub ci_chain: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoInput {
+    Unused,
+    Direct,
+    Latched,
+    Registered,
+    Ddr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoOutput {
+    Unused,
+    Direct,
+    Registered,
+    Ddr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoOe {
+    /// The output enable is constant (always on or always off).
+    Fixed,
+    Direct,
+    Registered,
+}
+
+/// The I/O bank supply voltage.  Only affects iCE40 devices, which describe the I/O buffer
+/// separately from the I/O interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoVoltage {
+    V18,
+    V25,
+    V33,
+}
+
+impl std::fmt::Display for IoVoltage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoVoltage::V18 => write!(f, "1.8"),
+            IoVoltage::V25 => write!(f, "2.5"),
+            IoVoltage::V33 => write!(f, "3.3"),
+        }
+    }
+}
+
+/// The timing-relevant configuration of an I/O interface, as selected by its `PIN_TYPE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IoConfig {
+    pub input: IoInput,
+    pub output: IoOutput,
+    pub oe: IoOe,
+    /// The pad also drives the global network through the `IOB_PAIR` of its tile.
+    pub global: bool,
+    pub vccio: IoVoltage,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellConfig {
+    Lc(LcConfig),
+    Bram,
+    Io(IoConfig),
+}
+
+/// The timing arcs of a single cell.
+#[derive(Clone, Debug, Default)]
+pub struct CellArcs {
+    /// Propagation delays between pins of the cell.
+    pub delays: Vec<(CellPin, CellPin, EdgeDelay)>,
+    /// Setup/hold and recovery/removal checks of a data pin against an edge of a clock pin.
+    pub checks: Vec<(CellPin, BelInputId, ClockEdge, SetupHoldRf)>,
+}
+
+impl CellArcs {
+    fn delay(&mut self, from: CellPin, to: CellPin, delay: Option<EdgeDelay>) {
+        if let Some(delay) = delay {
+            self.delays.push((from, to, delay));
+        }
+    }
+
+    fn check(&mut self, data: CellPin, clk: BelInputId, sh: Option<SetupHoldRf>) {
+        self.check_edge(data, clk, ClockEdge::Rising, sh);
+    }
+
+    fn check_edge(
+        &mut self,
+        data: CellPin,
+        clk: BelInputId,
+        edge: ClockEdge,
+        sh: Option<SetupHoldRf>,
+    ) {
+        if let Some(sh) = sh {
+            self.checks.push((data, clk, edge, sh));
+        }
+    }
+}
+
+/// A timing check of a used cell, with the arrival times at both of its pins.
+#[derive(Clone, Debug)]
+pub struct TimingCheck {
+    pub bel: BelCoord,
+    pub data: CellPin,
+    pub clk: BelInputId,
+    pub edge: ClockEdge,
+    pub setup_hold: SetupHoldRf,
+    pub data_arrival: EdgeArrival,
+    pub clk_arrival: EdgeArrival,
+}
+
+impl TimingCheck {
+    /// Returns the setup slack for the given clock period, assuming the data was launched
+    /// by the previous active edge of the same clock.
+    pub fn setup_slack(&self, period: Time) -> Option<Time> {
+        let clk = self.edge.arrival(self.clk_arrival)?;
+        let rise = self
+            .data_arrival
+            .rise
+            .map(|data| period + clk.min - self.setup_hold.rise_setup - data.max);
+        let fall = self
+            .data_arrival
+            .fall
+            .map(|data| period + clk.min - self.setup_hold.fall_setup - data.max);
+        rise.into_iter().chain(fall).min()
+    }
+
+    /// Returns the hold slack, assuming the data was launched by the same active edge of
+    /// the same clock.
+    pub fn hold_slack(&self) -> Option<Time> {
+        let clk = self.edge.arrival(self.clk_arrival)?;
+        let rise = self
+            .data_arrival
+            .rise
+            .map(|data| data.min - clk.max - self.setup_hold.rise_hold);
+        let fall = self
+            .data_arrival
+            .fall
+            .map(|data| data.min - clk.max - self.setup_hold.fall_hold);
+        rise.into_iter().chain(fall).min()
+    }
+}
+
+/// The result of [`TimingModel::analyze`].
+#[derive(Clone, Debug, Default)]
+pub struct PathTiming {
+    pub arrival: BTreeMap<TimingPoint, EdgeArrival>,
+    pub checks: Vec<TimingCheck>,
+    pub broken_loops: usize,
+}
+
+struct GraphBuilder<'a> {
+    db: &'a IntDb,
+    graph: TimingGraph,
+    nodes: HashMap<TimingPoint, TimingNodeId>,
+}
+
+impl GraphBuilder<'_> {
+    fn node(&mut self, point: TimingPoint) -> TimingNodeId {
+        *self.nodes.entry(point).or_insert_with(|| {
+            self.graph
+                .add_node(point.to_string(self.db), TimingNodeKind::Internal)
+        })
+    }
+
+    fn edge(&mut self, from: TimingPoint, to: TimingPoint, delay: EdgeDelay) {
+        let from = self.node(from);
+        let to = self.node(to);
+        self.graph.add_edge(from, to, delay);
+    }
+}
+
+pub struct TimingModel<'a> {
+    pub edev: &'a ExpandedDevice<'a>,
+    pub speed: &'a Speed,
+}
+
+impl<'a> TimingModel<'a> {
+    pub fn new(edev: &'a ExpandedDevice<'a>, speed: &'a Speed) -> Self {
+        TimingModel { edev, speed }
+    }
+
+    fn int_delay(&self, key: &str) -> Result<EdgeDelay, SpeedError> {
+        self.speed.delay(&format!("INT:{key}"))
+    }
+
+    fn bel_info(&self, bel: BelCoord) -> &'a Bel {
+        let egrid = &self.edev.egrid;
+        let tcrd = egrid.bel_tile(bel);
+        let BelInfo::Bel(ref info) = egrid.db[egrid[tcrd].class].bels[bel.slot] else {
+            unreachable!()
+        };
+        info
+    }
+
+    fn is_ioi_tile(&self, tcrd: TileCoord) -> bool {
+        let egrid = &self.edev.egrid;
+        egrid.db[egrid[tcrd].class].bels.contains_id(bslots::IOI[0])
+    }
+
+    /// Returns the delay of a span wire between the point where it is driven and the point
+    /// where it is tapped.  Both wires are raw (unresolved) segments of the same span.
+    /// Wires other than spans have no delay of their own.
+    pub fn wire_delay(&self, driver: WireCoord, tap: WireCoord) -> Result<EdgeDelay, SpeedError> {
+        let (Some((kd, pd)), Some((kt, pt))) = (span_pos(driver.slot), span_pos(tap.slot)) else {
+            return Ok(EdgeDelay::ZERO);
+        };
+        let generic = if kd.is_long() { "LONG" } else { "QUAD" };
+        if kd != kt {
+            // Quad spans turning around the device corner.
+            return self.int_delay(generic);
+        }
+        let dir = match kd {
+            SpanKind::QuadH | SpanKind::LongH => "H",
+            SpanKind::QuadV | SpanKind::LongV => "V",
+        };
+        let dist = pd.abs_diff(pt);
+        match optional(self.speed.delay(&format!("INT:{generic}_{dir}_{dist}")))? {
+            Some(delay) => Ok(delay),
+            None => self.int_delay(generic),
+        }
+    }
+
+    /// Returns the delay of a PIP, ie. of the multiplexer driving its destination wire.
+    pub fn pip_delay(&self, pip: &TilePip) -> Result<EdgeDelay, SpeedError> {
+        let wout = pip.wire_out.slot;
+        let win = pip.wire_in.slot;
+        if let Some((kind, _)) = span_pos(wout) {
+            return if wires::OUT_LC.contains(win) {
+                self.int_delay(if kind.is_long() {
+                    "OUT_TO_LONG"
+                } else {
+                    "OUT_TO_QUAD"
+                })
+            } else if kind.is_long() {
+                Ok(EdgeDelay::ZERO)
+            } else if span_pos(win).is_some_and(|(k, _)| k.is_long()) {
+                self.int_delay("LONG_TO_QUAD")
+            } else if self.is_ioi_tile(pip.tile) {
+                self.int_delay("QUAD_IO")
+            } else {
+                Ok(EdgeDelay::ZERO)
+            };
+        }
+        if [
+            wires::IMUX_LC_I0,
+            wires::IMUX_LC_I1,
+            wires::IMUX_LC_I2,
+            wires::IMUX_LC_I3,
+        ]
+        .iter()
+        .any(|range| range.contains(wout))
+        {
+            self.int_delay("IMUX_LC")
+        } else if [
+            wires::IMUX_IO_DOUT0,
+            wires::IMUX_IO_DOUT1,
+            wires::IMUX_IO_OE,
+        ]
+        .iter()
+        .any(|range| range.contains(wout))
+            || wout == wires::IMUX_IO_EXTRA
+        {
+            self.int_delay("IMUX_IO")
+        } else if wout == wires::IMUX_CLK
+            || wout == wires::IMUX_IO_ICLK
+            || wout == wires::IMUX_IO_OCLK
+        {
+            self.int_delay("IMUX_CLK")
+        } else if wout == wires::IMUX_CE {
+            self.int_delay("IMUX_CE")
+        } else if wout == wires::IMUX_RST {
+            self.int_delay("IMUX_RST")
+        } else if [
+            wires::LOCAL_0,
+            wires::LOCAL_1,
+            wires::LOCAL_2,
+            wires::LOCAL_3,
+        ]
+        .iter()
+        .any(|range| range.contains(wout))
+        {
+            if wires::GLOBAL_OUT.contains(win) {
+                self.int_delay("GLOBAL_OUT")
+            } else {
+                self.int_delay("LOCAL")
+            }
+        } else if wires::GLOBAL_OUT.contains(wout) {
+            self.int_delay("GLOBAL")
+        } else if wires::GLOBAL_ROOT.contains(wout) && win == wires::IMUX_IO_EXTRA {
+            self.speed.delay("GB_FABRIC")
+        } else {
+            Ok(EdgeDelay::ZERO)
+        }
+    }
+
+    /// Returns the delay of the I/O buffer for iCE40 devices.
+    fn iob_delay(
+        &self,
+        ioi: BelCoord,
+        vccio: IoVoltage,
+        key: &str,
+    ) -> Result<Option<EdgeDelay>, SpeedError> {
+        let chip = self.edev.chip;
+        if chip.ioi_od.contains(&ioi) {
+            return optional(self.speed.delay(&format!("IOB_OD_{vccio}:{key}")));
+        }
+        if (ioi.col == chip.col_w() || ioi.col == chip.col_e())
+            && let Some(delay) = optional(self.speed.delay(&format!("IOB_W_{vccio}:{key}")))?
+        {
+            return Ok(Some(delay));
+        }
+        optional(self.speed.delay(&format!("IOB_{vccio}:{key}")))
+    }
+
+    /// Returns an I/O delay to or from the pad.  iCE65 devices describe the whole path with
+    /// a single value; iCE40 devices split it between the I/O interface and the I/O buffer.
+    fn io_delay(
+        &self,
+        ioi: BelCoord,
+        vccio: IoVoltage,
+        key_ice65: &str,
+        key_ice40: &str,
+        key_iob: &str,
+    ) -> Result<Option<EdgeDelay>, SpeedError> {
+        if let Some(delay) = optional(self.speed.delay(&format!("IO:{key_ice65}")))? {
+            return Ok(Some(delay));
+        }
+        let Some(delay) = optional(self.speed.delay(&format!("IO:{key_ice40}")))? else {
+            return Ok(None);
+        };
+        Ok(self
+            .iob_delay(ioi, vccio, key_iob)?
+            .map(|iob| delay.then(iob)))
+    }
+
+    fn io_setup_hold(
+        &self,
+        ioi: BelCoord,
+        vccio: IoVoltage,
+        key_ice65: &str,
+        key_ice40: &str,
+    ) -> Result<Option<SetupHoldRf>, SpeedError> {
+        if let Some(sh) = optional(self.speed.setup_hold(&format!("IO:{key_ice65}")))? {
+            return Ok(Some(sh));
+        }
+        let Some(sh) = optional(self.speed.setup_hold(&format!("IO:{key_ice40}")))? else {
+            return Ok(None);
+        };
+        let Some(ibuf) = self.iob_delay(ioi, vccio, "PAD_TO_PADIN")? else {
+            return Ok(None);
+        };
+        let (Some(rise), Some(fall)) = (ibuf.rise_to_rise, ibuf.fall_to_fall) else {
+            return Ok(None);
+        };
+        Ok(Some(SetupHoldRf {
+            rise_setup: sh.rise_setup + rise.max,
+            rise_hold: sh.rise_hold - rise.min,
+            fall_setup: sh.fall_setup + fall.max,
+            fall_hold: sh.fall_hold - fall.min,
+        }))
+    }
+
+    /// Returns the delay from an I/O pad to the global network.
+    pub fn global_delay(
+        &self,
+        ioi: BelCoord,
+        vccio: IoVoltage,
+    ) -> Result<Option<EdgeDelay>, SpeedError> {
+        self.io_delay(ioi, vccio, "PAD_TO_GB", "PADIN_TO_GB", "PAD_TO_PADIN")
+    }
+
+    /// Returns the timing arcs of a cell in the given configuration.  Arcs missing from
+    /// the speed grade are omitted; a value of the wrong kind is an error.
+    pub fn cell_arcs(&self, bel: BelCoord, cfg: &CellConfig) -> Result<CellArcs, SpeedError> {
+        let mut arcs = CellArcs::default();
+        match *cfg {
+            CellConfig::Lc(lc) => {
+                let plb = |key: &str| optional(self.speed.delay(&format!("PLB:{key}")));
+                let plb_sh = |key: &str| optional(self.speed.setup_hold(&format!("PLB:{key}")));
+                let lut_inputs = [
+                    bcls::LC::I0,
+                    bcls::LC::I1,
+                    if lc.ltin_enable {
+                        bcls::LC::LTIN
+                    } else {
+                        bcls::LC::I2
+                    },
+                    bcls::LC::I3,
+                ];
+                let o = CellPin::Output(bcls::LC::O);
+                for (i, pin) in lut_inputs.into_iter().enumerate() {
+                    let pin = CellPin::Input(pin);
+                    if lc.ff_enable {
+                        arcs.check(pin, bcls::LC::CLK, plb_sh(&format!("I{i}_SETUPHOLD_CLK"))?);
+                    } else {
+                        arcs.delay(pin, o, plb(&format!("I{i}_TO_O"))?);
+                    }
+                    arcs.delay(
+                        pin,
+                        CellPin::Output(bcls::LC::LTOUT),
+                        plb(&format!("I{i}_TO_CASC"))?,
+                    );
+                }
+                if lc.ff_enable {
+                    arcs.delay(CellPin::Input(bcls::LC::CLK), o, plb("CLK_TO_O")?);
+                    let rst = CellPin::Input(bcls::LC::RST);
+                    arcs.check(
+                        CellPin::Input(bcls::LC::CE),
+                        bcls::LC::CLK,
+                        plb_sh("CE_SETUPHOLD_CLK")?,
+                    );
+                    if lc.ff_sr_async {
+                        arcs.delay(rst, o, plb("RST_TO_O")?);
+                        arcs.check(rst, bcls::LC::CLK, plb_sh("RST_RECREM_CLK")?);
+                    } else {
+                        arcs.check(rst, bcls::LC::CLK, plb_sh("RST_SETUPHOLD_CLK")?);
+                    }
+                }
+                if lc.carry_enable {
+                    let co = CellPin::Output(bcls::LC::CO);
+                    arcs.delay(CellPin::Input(bcls::LC::I1), co, plb("I1_TO_CO")?);
+                    arcs.delay(CellPin::Input(bcls::LC::I2), co, plb("I2_TO_CO")?);
+                    arcs.delay(CellPin::Input(bcls::LC::CI), co, plb("CI_TO_CO")?);
+                }
+            }
+            CellConfig::Bram => {
+                let info = self.bel_info(bel);
+                let bram_sh = |key: &str| optional(self.speed.setup_hold(&format!("BRAM:{key}")));
+                let rclk_to_rdata = optional(self.speed.delay("BRAM:RCLK_TO_RDATA"))?;
+                for pin in bcls::BRAM::RDATA {
+                    arcs.delay(
+                        CellPin::Input(bcls::BRAM::RCLK),
+                        CellPin::Output(pin),
+                        rclk_to_rdata,
+                    );
+                }
+                for (pins, clk, key) in [
+                    (
+                        &bcls::BRAM::WADDR[..],
+                        bcls::BRAM::WCLK,
+                        "WADDR_SETUPHOLD_WCLK",
+                    ),
+                    (
+                        &bcls::BRAM::WDATA[..],
+                        bcls::BRAM::WCLK,
+                        "WDATA_SETUPHOLD_WCLK",
+                    ),
+                    (
+                        &bcls::BRAM::MASK[..],
+                        bcls::BRAM::WCLK,
+                        "MASK_SETUPHOLD_WCLK",
+                    ),
+                    (&[bcls::BRAM::WE], bcls::BRAM::WCLK, "WE_SETUPHOLD_WCLK"),
+                    (
+                        &[bcls::BRAM::WCLKE],
+                        bcls::BRAM::WCLK,
+                        "WCLKE_SETUPHOLD_WCLK",
+                    ),
+                    (
+                        &bcls::BRAM::RADDR[..],
+                        bcls::BRAM::RCLK,
+                        "RADDR_SETUPHOLD_RCLK",
+                    ),
+                    (&[bcls::BRAM::RE], bcls::BRAM::RCLK, "RE_SETUPHOLD_RCLK"),
+                    (
+                        &[bcls::BRAM::RCLKE],
+                        bcls::BRAM::RCLK,
+                        "RCLKE_SETUPHOLD_RCLK",
+                    ),
+                ] {
+                    for &pin in pins {
+                        if info.inputs.contains_id(pin) {
+                            arcs.check(CellPin::Input(pin), clk, bram_sh(key)?);
+                        }
+                    }
+                }
+            }
+            CellConfig::Io(io) => {
+                let v = io.vccio;
+                let io_sh = |key: &str| optional(self.speed.setup_hold(&format!("IO:{key}")));
+                let din0 = CellPin::Output(bcls::IOI::DIN0);
+                let iclk = CellPin::Input(bcls::IOI::ICLK);
+                let oclk = CellPin::Input(bcls::IOI::OCLK);
+                let ce = CellPin::Input(bcls::IOI::CE);
+                match io.input {
+                    IoInput::Unused => (),
+                    IoInput::Direct | IoInput::Latched => {
+                        arcs.delay(
+                            CellPin::PadIn,
+                            din0,
+                            self.io_delay(bel, v, "PAD_TO_DIN0", "PADIN_TO_DIN0", "PAD_TO_PADIN")?,
+                        );
+                        if io.input == IoInput::Latched {
+                            arcs.delay(
+                                CellPin::Input(bcls::IOI::LATCH),
+                                din0,
+                                optional(self.speed.delay("IO:LATCH_TO_DIN0"))?,
+                            );
+                        }
+                    }
+                    IoInput::Registered | IoInput::Ddr => {
+                        arcs.delay(iclk, din0, optional(self.speed.delay("IO:ICLK_P_TO_DIN0"))?);
+                        arcs.check(
+                            CellPin::PadIn,
+                            bcls::IOI::ICLK,
+                            self.io_setup_hold(
+                                bel,
+                                v,
+                                "PAD_SETUPHOLD_ICLK_P",
+                                "PADIN_SETUPHOLD_ICLK_P",
+                            )?,
+                        );
+                        arcs.check(ce, bcls::IOI::ICLK, io_sh("CE_SETUPHOLD_ICLK")?);
+                        if io.input == IoInput::Ddr {
+                            arcs.delay(
+                                iclk,
+                                CellPin::Output(bcls::IOI::DIN1),
+                                optional(self.speed.delay("IO:ICLK_N_TO_DIN1"))?
+                                    .map(EdgeDelay::invert_input),
+                            );
+                            arcs.check_edge(
+                                CellPin::PadIn,
+                                bcls::IOI::ICLK,
+                                ClockEdge::Falling,
+                                self.io_setup_hold(
+                                    bel,
+                                    v,
+                                    "PAD_SETUPHOLD_ICLK_N",
+                                    "PADIN_SETUPHOLD_ICLK_N",
+                                )?,
+                            );
+                        }
+                    }
+                }
+                let oclk_used = matches!(io.output, IoOutput::Registered | IoOutput::Ddr)
+                    || io.oe == IoOe::Registered;
+                match io.output {
+                    IoOutput::Unused => (),
+                    IoOutput::Direct => arcs.delay(
+                        CellPin::Input(bcls::IOI::DOUT0),
+                        CellPin::PadOut,
+                        self.io_delay(bel, v, "DOUT0_TO_PAD", "DOUT0_TO_PADOUT", "PADOUT_TO_PAD")?,
+                    ),
+                    IoOutput::Registered | IoOutput::Ddr => {
+                        arcs.delay(
+                            oclk,
+                            CellPin::PadOut,
+                            self.io_delay(
+                                bel,
+                                v,
+                                "OCLK_P_TO_PAD",
+                                "OCLK_P_TO_PADOUT",
+                                "PADOUT_TO_PAD",
+                            )?,
+                        );
+                        arcs.check(
+                            CellPin::Input(bcls::IOI::DOUT0),
+                            bcls::IOI::OCLK,
+                            io_sh("DOUT0_SETUPHOLD_OCLK_P")?,
+                        );
+                        if io.output == IoOutput::Ddr {
+                            arcs.delay(
+                                oclk,
+                                CellPin::PadOut,
+                                self.io_delay(
+                                    bel,
+                                    v,
+                                    "OCLK_N_TO_PAD",
+                                    "OCLK_N_TO_PADOUT",
+                                    "PADOUT_TO_PAD",
+                                )?
+                                .map(EdgeDelay::invert_input),
+                            );
+                            arcs.check_edge(
+                                CellPin::Input(bcls::IOI::DOUT1),
+                                bcls::IOI::OCLK,
+                                ClockEdge::Falling,
+                                io_sh("DOUT1_SETUPHOLD_OCLK_N")?,
+                            );
+                        }
+                    }
+                }
+                match io.oe {
+                    IoOe::Fixed => (),
+                    IoOe::Direct => arcs.delay(
+                        CellPin::Input(bcls::IOI::OE),
+                        CellPin::PadOut,
+                        self.io_delay(bel, v, "OE_TO_PAD_ON", "OE_TO_PADOEN", "PADOEN_TO_PAD")?,
+                    ),
+                    IoOe::Registered => {
+                        arcs.delay(
+                            oclk,
+                            CellPin::PadOut,
+                            self.io_delay(
+                                bel,
+                                v,
+                                "OCLK_P_TO_PAD_OE",
+                                "OCLK_P_TO_PADOEN",
+                                "PADOEN_TO_PAD",
+                            )?,
+                        );
+                        arcs.check(
+                            CellPin::Input(bcls::IOI::OE),
+                            bcls::IOI::OCLK,
+                            io_sh("OE_SETUPHOLD_OCLK_P")?,
+                        );
+                    }
+                }
+                if oclk_used {
+                    arcs.check(ce, bcls::IOI::OCLK, io_sh("CE_SETUPHOLD_OCLK")?);
+                }
+            }
+        }
+        Ok(arcs)
+    }
+
+    /// Computes arrival times over the given used PIPs and cells.  The PIPs are expected
+    /// to come from [`prjcombine_interconnect::grid::ExpandedGrid::wire_pips_bwd`] or
+    /// [`prjcombine_interconnect::grid::ExpandedGrid::wire_pips_fwd`].  Input pads of used
+    /// I/O cells launch at time zero.
+    pub fn analyze(
+        &self,
+        pips: &[TilePip],
+        cells: &[(BelCoord, CellConfig)],
+    ) -> Result<PathTiming, SpeedError> {
+        let egrid = &self.edev.egrid;
+        let mut graph = GraphBuilder {
+            db: egrid.db,
+            graph: TimingGraph::new(),
+            nodes: HashMap::new(),
+        };
+
+        // Routing.
+        let drivers: HashMap<WireCoord, WireCoord> = pips
+            .iter()
+            .map(|pip| (pip.wire_out, pip.wire_out_raw))
+            .collect();
+        for pip in pips {
+            let src = if pip.wire_in.slot == wires::SPECIAL_CI {
+                let idx = wires::IMUX_LC_I3.index_of(pip.wire_out.slot).unwrap();
+                TimingPoint::Pin(
+                    pip.wire_out.cell.bel(bslots::LC[idx]),
+                    CellPin::Input(bcls::LC::CI),
+                )
+            } else {
+                TimingPoint::Wire(pip.wire_in)
+            };
+            let driver = drivers.get(&pip.wire_in).copied().unwrap_or(pip.wire_in);
+            let delay = self
+                .wire_delay(driver, pip.wire_in_raw)?
+                .then(self.pip_delay(pip)?);
+            graph.edge(src, TimingPoint::Wire(pip.wire_out), delay);
+        }
+
+        // Cells.
+        let used: HashMap<BelCoord, CellConfig> = cells.iter().copied().collect();
+        let mut sources = vec![];
+        let mut checks = vec![];
+        for &(bel, ref cfg) in cells {
+            let info = self.bel_info(bel);
+            let tcrd = egrid.bel_tile(bel);
+            for (pid, inp) in &info.inputs {
+                if let Some(wire) = egrid.resolve_tile_wire(tcrd, inp.wire()) {
+                    graph.edge(
+                        TimingPoint::Wire(wire),
+                        TimingPoint::Pin(bel, CellPin::Input(pid)),
+                        EdgeDelay::ZERO,
+                    );
+                }
+            }
+            for (pid, outs) in &info.outputs {
+                for &out in outs {
+                    if let Some(wire) = egrid.resolve_tile_wire(tcrd, out) {
+                        graph.edge(
+                            TimingPoint::Pin(bel, CellPin::Output(pid)),
+                            TimingPoint::Wire(wire),
+                            EdgeDelay::ZERO,
+                        );
+                    }
+                }
+            }
+            let arcs = self.cell_arcs(bel, cfg)?;
+            for (from, to, delay) in arcs.delays {
+                graph.edge(
+                    TimingPoint::Pin(bel, from),
+                    TimingPoint::Pin(bel, to),
+                    delay,
+                );
+            }
+            for (data, clk, edge, setup_hold) in arcs.checks {
+                checks.push((bel, data, clk, edge, setup_hold));
+            }
+            match *cfg {
+                CellConfig::Lc(lc) => {
+                    let idx = bslots::LC.index_of(bel.slot).unwrap();
+                    let ci = TimingPoint::Pin(bel, CellPin::Input(bcls::LC::CI));
+                    if idx != 0 {
+                        let prev = bel.cell.bel(bslots::LC[idx - 1]);
+                        graph.edge(
+                            TimingPoint::Pin(prev, CellPin::Output(bcls::LC::CO)),
+                            ci,
+                            EdgeDelay::ZERO,
+                        );
+                        if lc.ltin_enable && !info.inputs.contains_id(bcls::LC::LTIN) {
+                            graph.edge(
+                                TimingPoint::Pin(prev, CellPin::Output(bcls::LC::LTOUT)),
+                                TimingPoint::Pin(bel, CellPin::Input(bcls::LC::LTIN)),
+                                EdgeDelay::ZERO,
+                            );
+                        }
+                    } else if lc.ci_chain
+                        && let Some(cell) = egrid.cell_delta(bel.cell, 0, -1)
+                    {
+                        let prev = cell.bel(bslots::LC[7]);
+                        if used.contains_key(&prev) {
+                            graph.edge(
+                                TimingPoint::Pin(prev, CellPin::Output(bcls::LC::CO)),
+                                ci,
+                                self.speed.delay("PLB:CARRY_INIT")?,
+                            );
+                        }
+                    }
+                }
+                CellConfig::Bram => (),
+                CellConfig::Io(io) => {
+                    let pad = TimingPoint::Pin(bel, CellPin::PadIn);
+                    if io.input != IoInput::Unused || io.global {
+                        sources.push(graph.node(pad));
+                    }
+                    if io.global
+                        && let Some(iob) = self.edev.chip.ioi_to_iob(bel)
+                        && let Some(delay) = self.global_delay(bel, io.vccio)?
+                    {
+                        let pair = iob.cell.bel(bslots::IOB_PAIR);
+                        let gout =
+                            TimingPoint::Pin(pair, CellPin::Output(bcls::IOB_PAIR::GLOBAL_OUT));
+                        graph.edge(pad, gout, delay);
+                        let wire = egrid.resolve_wire(iob.cell.wire(wires::IO_GLOBAL));
+                        if let Some(wire) = wire {
+                            graph.edge(gout, TimingPoint::Wire(wire), EdgeDelay::ZERO);
+                        }
+                    }
+                }
+            }
+        }
+
+        let (arrival, broken_loops) = graph.graph.arrival_times(&sources);
+        let arrival: BTreeMap<_, _> = graph
+            .nodes
+            .iter()
+            .filter(|&(_, &nid)| !arrival[nid].is_none())
+            .map(|(&point, &nid)| (point, arrival[nid]))
+            .collect();
+        let arrival_at = |point| arrival.get(&point).copied().unwrap_or_default();
+        let checks = checks
+            .into_iter()
+            .map(|(bel, data, clk, edge, setup_hold)| TimingCheck {
+                bel,
+                data,
+                clk,
+                edge,
+                setup_hold,
+                data_arrival: arrival_at(TimingPoint::Pin(bel, data)),
+                clk_arrival: arrival_at(TimingPoint::Pin(bel, CellPin::Input(clk))),
+            })
+            .collect();
+        Ok(PathTiming {
+            arrival,
+            checks,
+            broken_loops,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use prjcombine_siliconblue::{bitstream::Bitstream, db::Database};
+use prjcombine_test_util::check_decode_roundtrip;
+use prjcombine_types::bsdata::BsData;
+
+const DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/siliconblue.zstd"
+);
+
+#[test]
+fn decode_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    let bsdata = BsData::new();
+    for (chip_id, chip) in &db.chips {
+        let edev = chip.expand_grid(&db.int);
+        let name = chip_id.to_string();
+        check_decode_roundtrip(&name, &edev.egrid, &bsdata, |config| {
+            let bitstream = edev.encode(&bsdata, config).unwrap();
+            let data = bitstream.emit().unwrap();
+            let parsed = Bitstream::parse(&data).unwrap();
+            let diff = Bitstream::diff(&bitstream, &parsed);
+            assert!(diff.is_empty(), "{chip_id}: {diff:?}");
+            edev.decode(&bsdata, &parsed).unwrap()
+        });
+    }
+}
@@ -0,0 +1,31 @@
+use prjcombine_siliconblue::db::Database;
+use prjcombine_test_util::check_pinout;
+
+const DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/siliconblue.zstd"
+);
+
+// The database has no vendor pad numbers, so the IO names are made of the
+// functions and the bank alone.
+#[test]
+fn pinout() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db
+        .devices
+        .iter()
+        .find(|dev| dev.name == "iCE40HX1K")
+        .unwrap();
+    let bond = &db.bonds[dev.bonds["TQ144"]];
+    let pinout = db.chips[dev.chip].make_pinout(bond, &db.int, "iCE40HX1K", "TQ144");
+    check_pinout(
+        &pinout,
+        &[
+            ("1", "IO_3", Some("3"), Some("2")),
+            ("21", "IO_GB_IN1_3", Some("3"), Some("22")),
+            ("49", "IO_GB_IN6_PLL_A_2", Some("2"), None),
+            ("112", "IO_0", Some("0"), None),
+            ("129", "IO_GB_IN7_0", Some("0"), None),
+        ],
+    );
+}
@@ -0,0 +1,16 @@
+use prjcombine_siliconblue::db::Database;
+
+fn db_path(ext: &str) -> String {
+    format!(
+        "{dir}/../../databases/siliconblue.{ext}",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+#[test]
+fn text_roundtrip() {
+    let db = Database::from_file(db_path("zstd")).unwrap();
+    let parsed = Database::from_text_file(db_path("txt")).unwrap();
+    // Not assert_eq, the Debug output of a whole database is unreadable.
+    assert!(parsed == db, "text dump does not match the database");
+}
@@ -0,0 +1,369 @@
+use std::collections::{HashMap, VecDeque};
+
+use prjcombine_interconnect::{
+    db::{Bel, BelInfo, BelInputId, BelOutputId},
+    grid::{BelCoord, ExpandedGrid, TilePip, WireCoord},
+};
+use prjcombine_siliconblue::{
+    db::Database,
+    defs::{bcls, bslots, wires},
+    timing::{
+        CellConfig, CellPin, IoConfig, IoInput, IoOe, IoOutput, IoVoltage, LcConfig, TimingModel,
+        TimingPoint,
+    },
+};
+use prjcombine_types::{
+    speed::{EdgeArrival, EdgeDelay, Speed},
+    timing::ClockEdge,
+    units::{Scalar, Time},
+};
+
+const DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/siliconblue.zstd"
+);
+
+// Every delay looked up for the routing and the logic cells must be in every speed grade.
+#[test]
+fn speed_values() {
+    let db = Database::from_file(DB).unwrap();
+    for dev in &db.devices {
+        let edev = db.chips[dev.chip].expand_grid(&db.int);
+        let egrid = &edev.egrid;
+        let mut pips = vec![];
+        let mut lcs = vec![];
+        for (cell, _) in egrid.cells() {
+            for slot in db.int.wires.ids() {
+                pips.extend(egrid.wire_pips_bwd(cell.wire(slot)));
+            }
+            let lc = cell.bel(bslots::LC[0]);
+            if egrid.has_bel(lc) {
+                lcs.push(lc);
+            }
+        }
+        assert!(!pips.is_empty() && !lcs.is_empty(), "{}", dev.name);
+        let cfg = CellConfig::Lc(LcConfig {
+            ff_enable: true,
+            ff_sr_async: true,
+            carry_enable: true,
+            ltin_enable: false,
+            ci_chain: false,
+        });
+        for (sname, &speed) in &dev.speeds {
+            let model = TimingModel::new(&edev, &db.speeds[speed]);
+            for pip in &pips {
+                model
+                    .pip_delay(pip)
+                    .and_then(|_| model.wire_delay(pip.wire_out_raw, pip.wire_in_raw))
+                    .unwrap_or_else(|e| panic!("{} {sname}: {e}", dev.name));
+            }
+            let arcs = model
+                .cell_arcs(lcs[0], &cfg)
+                .unwrap_or_else(|e| panic!("{} {sname}: {e}", dev.name));
+            assert!(!arcs.delays.is_empty() && !arcs.checks.is_empty());
+        }
+    }
+}
+
+fn bel_info<'a>(egrid: &'a ExpandedGrid, bel: BelCoord) -> &'a Bel {
+    let tcrd = egrid.bel_tile(bel);
+    let BelInfo::Bel(ref info) = egrid.db[egrid[tcrd].class].bels[bel.slot] else {
+        unreachable!()
+    };
+    info
+}
+
+fn input_wire(egrid: &ExpandedGrid, bel: BelCoord, pin: BelInputId) -> WireCoord {
+    let tcrd = egrid.bel_tile(bel);
+    let wire = bel_info(egrid, bel).inputs[pin].wire();
+    egrid.resolve_tile_wire(tcrd, wire).unwrap()
+}
+
+fn output_wire(egrid: &ExpandedGrid, bel: BelCoord, pin: BelOutputId) -> WireCoord {
+    let tcrd = egrid.bel_tile(bel);
+    let &wire = bel_info(egrid, bel).outputs[pin].first().unwrap();
+    egrid.resolve_tile_wire(tcrd, wire).unwrap()
+}
+
+// The shortest chain of PIPs from one wire to another.
+fn route(egrid: &ExpandedGrid, from: WireCoord, to: WireCoord) -> Vec<TilePip> {
+    let mut queue = VecDeque::from([from]);
+    let mut parent: HashMap<WireCoord, TilePip> = HashMap::new();
+    while let Some(wire) = queue.pop_front() {
+        if wire == to {
+            let mut pips = vec![];
+            let mut cur = to;
+            while cur != from {
+                let pip = parent[&cur];
+                pips.push(pip);
+                cur = pip.wire_in;
+            }
+            pips.reverse();
+            return pips;
+        }
+        for pip in egrid.wire_pips_fwd(wire) {
+            if pip.wire_out != from && !parent.contains_key(&pip.wire_out) {
+                parent.insert(pip.wire_out, pip);
+                queue.push_back(pip.wire_out);
+            }
+        }
+    }
+    panic!(
+        "no route from {} to {}",
+        from.to_string(egrid.db),
+        to.to_string(egrid.db)
+    );
+}
+
+// The arrival at the end of a route, computed the way the model describes it.
+fn route_arrival(model: &TimingModel, pips: &[TilePip], start: EdgeArrival) -> EdgeArrival {
+    let mut arrival = start;
+    let mut driver = pips[0].wire_in;
+    for pip in pips {
+        let delay = model
+            .wire_delay(driver, pip.wire_in_raw)
+            .unwrap()
+            .then(model.pip_delay(pip).unwrap());
+        arrival = delay.propagate(arrival);
+        driver = pip.wire_out_raw;
+    }
+    arrival
+}
+
+fn plb(speed: &Speed, key: &str) -> EdgeDelay {
+    speed.delay(&format!("PLB:{key}")).unwrap()
+}
+
+// An input pad through a LUT into a flip-flop clocked from a global input:
+//
+//   pad -> IOI.DIN0 -> LC0.I0 -> LC0.O -> LC1.I0 (setup/hold against LC1.CLK)
+//   global pad -> IOB_PAIR.GLOBAL_OUT -> LC1.CLK -> LC1.O
+#[test]
+fn analyze_lut_to_ff() {
+    let db = Database::from_file(DB).unwrap();
+    for name in ["iCE65L04", "iCE40HX1K"] {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&db.int);
+        let egrid = &edev.egrid;
+        let chip = edev.chip;
+
+        let lcs: Vec<_> = egrid
+            .cells()
+            .map(|(cell, _)| cell.bel(bslots::LC[0]))
+            .filter(|&bel| egrid.has_bel(bel))
+            .collect();
+        let lc0 = lcs[lcs.len() / 2];
+        let lc1 = lc0.cell.bel(bslots::LC[1]);
+        let iois: Vec<_> = egrid
+            .cells()
+            .map(|(cell, _)| cell.bel(bslots::IOI[0]))
+            .filter(|&bel| egrid.has_bel(bel) && chip.ioi_to_iob(bel).is_some())
+            .collect();
+        let gioi = *iois
+            .iter()
+            .find(|&&bel| {
+                let iob = chip.ioi_to_iob(bel).unwrap();
+                egrid
+                    .resolve_wire(iob.cell.wire(wires::IO_GLOBAL))
+                    .is_some_and(|wire| !egrid.wire_pips_fwd(wire).is_empty())
+            })
+            .unwrap();
+        let dioi = *iois.iter().find(|&&bel| bel.cell != gioi.cell).unwrap();
+        let giob = chip.ioi_to_iob(gioi).unwrap();
+
+        let din0 = output_wire(egrid, dioi, bcls::IOI::DIN0);
+        let lc0_i0 = input_wire(egrid, lc0, bcls::LC::I0);
+        let lc0_o = output_wire(egrid, lc0, bcls::LC::O);
+        let lc1_i0 = input_wire(egrid, lc1, bcls::LC::I0);
+        let lc1_clk = input_wire(egrid, lc1, bcls::LC::CLK);
+        let gwire = egrid
+            .resolve_wire(giob.cell.wire(wires::IO_GLOBAL))
+            .unwrap();
+        let route_in = route(egrid, din0, lc0_i0);
+        let route_lut = route(egrid, lc0_o, lc1_i0);
+        let route_clk = route(egrid, gwire, lc1_clk);
+        let pips: Vec<_> = [&route_in, &route_lut, &route_clk]
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+
+        let io = |input, global| IoConfig {
+            input,
+            output: IoOutput::Unused,
+            oe: IoOe::Fixed,
+            global,
+            vccio: IoVoltage::V33,
+        };
+        let lut = LcConfig::default();
+        let ff = LcConfig {
+            ff_enable: true,
+            ..Default::default()
+        };
+        let cells = [
+            (dioi, CellConfig::Io(io(IoInput::Direct, false))),
+            (gioi, CellConfig::Io(io(IoInput::Unused, true))),
+            (lc0, CellConfig::Lc(lut)),
+            (lc1, CellConfig::Lc(ff)),
+        ];
+
+        for (sname, &speed) in &dev.speeds {
+            let speed = &db.speeds[speed];
+            let model = TimingModel::new(&edev, speed);
+            let timing = model.analyze(&pips, &cells).unwrap();
+            assert_eq!(timing.broken_loops, 0);
+            let at = |bel, pin| timing.arrival[&TimingPoint::Pin(bel, pin)];
+
+            // the data pad launches at zero, and DIN0 pays the input path of its I/O
+            let din0_arrival = at(dioi, CellPin::Output(bcls::IOI::DIN0));
+            let io_arcs = model
+                .cell_arcs(dioi, &CellConfig::Io(io(IoInput::Direct, false)))
+                .unwrap();
+            let &(_, _, pad_to_din0) = io_arcs
+                .delays
+                .iter()
+                .find(|&&(from, _, _)| from == CellPin::PadIn)
+                .unwrap();
+            assert_eq!(din0_arrival, pad_to_din0.propagate(EdgeArrival::ZERO));
+
+            // routing, then the LUT, then routing again
+            let lc0_in = at(lc0, CellPin::Input(bcls::LC::I0));
+            assert_eq!(lc0_in, route_arrival(&model, &route_in, din0_arrival));
+            let lc0_out = at(lc0, CellPin::Output(bcls::LC::O));
+            assert_eq!(lc0_out, plb(speed, "I0_TO_O").propagate(lc0_in));
+            let lc1_in = at(lc1, CellPin::Input(bcls::LC::I0));
+            assert_eq!(lc1_in, route_arrival(&model, &route_lut, lc0_out));
+            for arrival in [lc0_in, lc0_out, lc1_in] {
+                for range in [arrival.rise.unwrap(), arrival.fall.unwrap()] {
+                    assert!(range.min <= range.max, "{name} {sname}");
+                }
+            }
+            assert!(lc1_in.rise.unwrap().max > din0_arrival.rise.unwrap().max);
+
+            // the global input reaches the clock through the global network
+            let gout = at(
+                giob.cell.bel(bslots::IOB_PAIR),
+                CellPin::Output(bcls::IOB_PAIR::GLOBAL_OUT),
+            );
+            let global = model.global_delay(gioi, IoVoltage::V33).unwrap().unwrap();
+            assert_eq!(gout, global.propagate(EdgeArrival::ZERO));
+            let clk = at(lc1, CellPin::Input(bcls::LC::CLK));
+            assert_eq!(clk, route_arrival(&model, &route_clk, gout));
+            assert_eq!(
+                at(lc1, CellPin::Output(bcls::LC::O)),
+                plb(speed, "CLK_TO_O").propagate(clk)
+            );
+
+            // the flip-flop checks its data input against the clock
+            let check = timing
+                .checks
+                .iter()
+                .find(|check| check.bel == lc1 && check.data == CellPin::Input(bcls::LC::I0))
+                .unwrap();
+            assert_eq!(check.clk, bcls::LC::CLK);
+            assert_eq!(check.data_arrival, lc1_in);
+            assert_eq!(check.clk_arrival, clk);
+            assert_eq!(
+                check.setup_hold,
+                speed.setup_hold("PLB:I0_SETUPHOLD_CLK").unwrap()
+            );
+            let setup = check.setup_slack(Time(Scalar(0.0))).unwrap();
+            let slow = Time(Scalar(1e6));
+            assert_eq!(check.setup_slack(slow).unwrap(), setup + slow);
+            assert!(check.hold_slack().is_some());
+        }
+    }
+}
+
+#[test]
+fn bram_io_arcs() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db
+        .devices
+        .iter()
+        .find(|dev| dev.name == "iCE40HX1K")
+        .unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let egrid = &edev.egrid;
+    let speed = &db.speeds[dev.speeds.values().next().copied().unwrap()];
+    let model = TimingModel::new(&edev, speed);
+
+    let bram = egrid
+        .cells()
+        .map(|(cell, _)| cell.bel(bslots::BRAM))
+        .find(|&bel| egrid.has_bel(bel))
+        .unwrap();
+    let arcs = model.cell_arcs(bram, &CellConfig::Bram).unwrap();
+    let rclk_to_rdata = speed.delay("BRAM:RCLK_TO_RDATA").unwrap();
+    for pin in bcls::BRAM::RDATA {
+        assert!(arcs.delays.contains(&(
+            CellPin::Input(bcls::BRAM::RCLK),
+            CellPin::Output(pin),
+            rclk_to_rdata
+        )));
+    }
+    assert_eq!(arcs.delays.len(), bcls::BRAM::RDATA.len());
+    let waddr_sh = speed.setup_hold("BRAM:WADDR_SETUPHOLD_WCLK").unwrap();
+    let info = bel_info(egrid, bram);
+    for pin in bcls::BRAM::WADDR {
+        if info.inputs.contains_id(pin) {
+            assert!(arcs.checks.contains(&(
+                CellPin::Input(pin),
+                bcls::BRAM::WCLK,
+                ClockEdge::Rising,
+                waddr_sh
+            )));
+        }
+    }
+    assert!(
+        arcs.checks
+            .iter()
+            .any(|&(pin, clk, _, _)| pin == CellPin::Input(bcls::BRAM::RE)
+                && clk == bcls::BRAM::RCLK)
+    );
+
+    let ioi = egrid
+        .cells()
+        .map(|(cell, _)| cell.bel(bslots::IOI[0]))
+        .find(|&bel| egrid.has_bel(bel) && edev.chip.ioi_to_iob(bel).is_some())
+        .unwrap();
+    let ddr = IoConfig {
+        input: IoInput::Ddr,
+        output: IoOutput::Direct,
+        oe: IoOe::Registered,
+        global: false,
+        vccio: IoVoltage::V33,
+    };
+    let arcs = model.cell_arcs(ioi, &CellConfig::Io(ddr)).unwrap();
+    let iclk = CellPin::Input(bcls::IOI::ICLK);
+    let oclk = CellPin::Input(bcls::IOI::OCLK);
+    let delay = |from, to| {
+        arcs.delays
+            .iter()
+            .find(|&&(f, t, _)| f == from && t == to)
+            .map(|&(_, _, delay)| delay)
+    };
+    assert_eq!(
+        delay(iclk, CellPin::Output(bcls::IOI::DIN0)),
+        Some(speed.delay("IO:ICLK_P_TO_DIN0").unwrap())
+    );
+    // DIN1 is launched by the falling edge
+    assert_eq!(
+        delay(iclk, CellPin::Output(bcls::IOI::DIN1)),
+        Some(speed.delay("IO:ICLK_N_TO_DIN1").unwrap().invert_input())
+    );
+    assert!(delay(CellPin::Input(bcls::IOI::DOUT0), CellPin::PadOut).is_some());
+    assert!(delay(oclk, CellPin::PadOut).is_some());
+    let edges: Vec<_> = arcs
+        .checks
+        .iter()
+        .filter(|&&(pin, clk, _, _)| pin == CellPin::PadIn && clk == bcls::IOI::ICLK)
+        .map(|&(_, _, edge, _)| edge)
+        .collect();
+    assert_eq!(edges, [ClockEdge::Rising, ClockEdge::Falling]);
+    assert!(
+        arcs.checks
+            .iter()
+            .any(|&(pin, clk, _, _)| pin == CellPin::Input(bcls::IOI::OE) && clk == bcls::IOI::OCLK)
+    );
+}
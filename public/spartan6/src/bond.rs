@@ -1,9 +1,14 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_interconnect::grid::EdgeIoCoord;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_num, split_assign},
+};
 use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -30,7 +35,26 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "DONE" => CfgPad::Done,
+            "PROG_B" => CfgPad::ProgB,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            "SUSPEND" => CfgPad::Suspend,
+            "CMP_CS_B" => CfgPad::CmpCsB,
+            _ => return Err(format!("unknown config pad {s}")),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtPad {
     TxP(u8),
     TxN(u8),
@@ -65,7 +89,38 @@ impl std::fmt::Display for GtPad {
     }
 }
 
+impl std::str::FromStr for GtPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "VTRX" => GtPad::VtRx,
+            "VTTX" => GtPad::VtTx,
+            "AVCC" => GtPad::AVcc,
+            "RREF" => GtPad::RRef,
+            "AVTTRCAL" => GtPad::AVttRCal,
+            _ => {
+                for (prefix, pad) in [
+                    ("RXP", GtPad::RxP as fn(u8) -> GtPad),
+                    ("RXN", GtPad::RxN),
+                    ("TXP", GtPad::TxP),
+                    ("TXN", GtPad::TxN),
+                    ("CLKP", GtPad::ClkP),
+                    ("CLKN", GtPad::ClkN),
+                    ("AVCCPLL", GtPad::AVccPll),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown GT pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Io(EdgeIoCoord),
     Nc,
@@ -98,7 +153,37 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "VCCBATT" => BondPad::VccBatt,
+            "VFS" => BondPad::Vfs,
+            "RFUSE" => BondPad::RFuse,
+            _ => {
+                if s.starts_with("IOB_") {
+                    BondPad::Io(s.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCO") {
+                    BondPad::VccO(parse_num(bank)?)
+                } else if let Some((bank, pad)) =
+                    s.strip_prefix("GT").and_then(|s| s.split_once('_'))
+                {
+                    BondPad::Gt(parse_num(bank)?, pad.parse()?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
     // device bank -> pkg bank
@@ -144,7 +229,7 @@ fn pad_sort_key(name: &str) -> (usize, &str, u32) {
 impl Bond {
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         for (k, v) in &self.io_banks {
-            writeln!(o, "\tbank {k} = {v}")?;
+            writeln!(o, "\tbank {k} = {v};")?;
         }
         for (pin, pad) in self.pins.iter().sorted_by_key(|(k, _)| pad_sort_key(k)) {
             writeln!(o, "\tpin {pin} = {pad};")?;
@@ -154,4 +239,47 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name, plus banks and VREF pads.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (k, v) in &self.io_banks {
+            res.insert_leaf(format!("bank {k}"), v);
+        }
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        for v in &self.vref {
+            res.insert(format!("vref {v}"), DiffNode::new());
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Bond {
+            pins: BTreeMap::new(),
+            io_banks: BTreeMap::new(),
+            vref: BTreeSet::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "bank" => {
+                    let (k, v) = line.check(split_assign(val))?;
+                    res.io_banks
+                        .insert(line.check(parse_num(k))?, line.check(parse_num(v))?);
+                }
+                "pin" => {
+                    let (pin, pad) = line.check(split_assign(val))?;
+                    res.pins.insert(pin.to_string(), line.check(pad.parse())?);
+                }
+                "vref" => {
+                    res.vref.insert(line.check(val.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(res)
+    }
 }
@@ -0,0 +1,64 @@
+//! BSDL file generation.
+
+use prjcombine_types::bsdl::{Bsdl, InstructionRegister, PortKind};
+
+use crate::{
+    bond::{Bond, BondPad, CfgPad},
+    chip::Chip,
+};
+
+impl Chip {
+    pub fn make_bsdl(&self, bond: &Bond, entity: &str, package: &str, idcode: Option<u32>) -> Bsdl {
+        let bscan = self.get_bscan();
+        let mut res = Bsdl::new(entity, package, 6, bscan.bits);
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Io(crd) => {
+                    if let Some(&bpad) = bscan.io.get(&crd) {
+                        res.add_pad(&format!("IO_{pin}"), pin, bpad);
+                    } else {
+                        res.add_pin(&format!("IO_{pin}"), PortKind::Linkage, pin);
+                    }
+                }
+                BondPad::Cfg(CfgPad::Tck | CfgPad::Tms | CfgPad::Tdi) => {
+                    res.add_pin(&pad.to_string(), PortKind::In, pin);
+                }
+                BondPad::Cfg(CfgPad::Tdo) => {
+                    res.add_pin(&pad.to_string(), PortKind::Out, pin);
+                }
+                BondPad::Cfg(cpad) => {
+                    if let Some(&bpad) = bscan.cfg.get(&cpad) {
+                        res.add_pad(&pad.to_string(), pin, bpad);
+                    } else {
+                        res.add_pin(&pad.to_string(), PortKind::Linkage, pin);
+                    }
+                }
+                BondPad::Nc => (),
+                _ => res.add_pin(&pad.to_string(), PortKind::Linkage, pin),
+            }
+        }
+        for (name, opcode, register) in [
+            ("EXTEST", 0x0f, InstructionRegister::Standard),
+            ("SAMPLE", 0x01, InstructionRegister::Standard),
+            ("USER1", 0x02, InstructionRegister::Private),
+            ("USER2", 0x03, InstructionRegister::Private),
+            ("USER3", 0x1a, InstructionRegister::Private),
+            ("USER4", 0x1b, InstructionRegister::Private),
+            ("CFG_OUT", 0x04, InstructionRegister::Private),
+            ("CFG_IN", 0x05, InstructionRegister::Private),
+            ("INTEST", 0x07, InstructionRegister::Standard),
+            ("USERCODE", 0x08, InstructionRegister::Standard),
+            ("IDCODE", 0x09, InstructionRegister::Standard),
+            ("HIGHZ", 0x0a, InstructionRegister::Standard),
+            ("JPROGRAM", 0x0b, InstructionRegister::Private),
+            ("JSTART", 0x0c, InstructionRegister::Private),
+            ("JSHUTDOWN", 0x0d, InstructionRegister::Private),
+            ("ISC_DNA", 0x30, InstructionRegister::Private),
+            ("BYPASS", 0x3f, InstructionRegister::Standard),
+        ] {
+            res.add_instruction(name, opcode, register);
+        }
+        res.idcode = idcode.map(|idcode| (idcode, 0x0fff_ffff));
+        res
+    }
+}
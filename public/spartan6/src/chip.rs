@@ -6,7 +6,14 @@ use prjcombine_entity::{
 };
 use prjcombine_interconnect::{
     dir::{Dir, DirH, DirHV, DirV},
-    grid::{BelCoord, ColId, DieId, DieIdExt, EdgeIoCoord, RowId, TileCoord, TileIobId},
+    grid::{
+        BelCoord, ColId, ColTag, DieId, DieIdExt, EdgeIoCoord, IobTag, RowId, RowTag, TileCoord,
+        TileIobId,
+    },
+};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign, split_list},
 };
 use std::collections::BTreeMap;
 
@@ -20,6 +27,7 @@ impl EntityTagArith for RegTag {}
 pub type RegId = EntityIdU8<RegTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub columns: EntityVec<ColId, Column>,
     pub col_clk: ColId,
@@ -30,11 +38,13 @@ pub struct Chip {
     pub row_mcb_split: Option<RowId>,
     pub gts: Gts,
     pub mcbs: Vec<Mcb>,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub cfg_io: BTreeMap<SharedCfgPad, EdgeIoCoord>,
     pub has_encrypt: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     // ×16
     // 0 doubles as DIN, MISO, MISO1
@@ -91,7 +101,46 @@ impl std::fmt::Display for SharedCfgPad {
     }
 }
 
+impl std::str::FromStr for SharedCfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CSO_B" => SharedCfgPad::CsoB,
+            "RDWR_B" => SharedCfgPad::RdWrB,
+            "DOUT" => SharedCfgPad::Dout,
+            "INIT_B" => SharedCfgPad::InitB,
+            "CCLK" => SharedCfgPad::Cclk,
+            "USER_CCLK" => SharedCfgPad::UserCclk,
+            "MOSI" => SharedCfgPad::Mosi,
+            "CMP_MOSI" => SharedCfgPad::CmpMosi,
+            "CMP_CLK" => SharedCfgPad::CmpClk,
+            "FCS_B" => SharedCfgPad::FcsB,
+            "FOE_B" => SharedCfgPad::FoeB,
+            "FWE_B" => SharedCfgPad::FweB,
+            "LDC" => SharedCfgPad::Ldc,
+            "M0" => SharedCfgPad::M0,
+            "M1" => SharedCfgPad::M1,
+            "HDC" => SharedCfgPad::Hdc,
+            "HSWAP_EN" => SharedCfgPad::HswapEn,
+            "AWAKE" => SharedCfgPad::Awake,
+            _ => {
+                if let Some(idx) = s.strip_prefix("SCP") {
+                    SharedCfgPad::Scp(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix('D') {
+                    SharedCfgPad::Data(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix('A') {
+                    SharedCfgPad::Addr(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown config pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     pub kind: ColumnKind,
     pub io_s: ColumnIoKind,
@@ -99,6 +148,7 @@ pub struct Column {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnKind {
     Io,
     CleXL,
@@ -124,6 +174,7 @@ impl std::fmt::Display for ColumnKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnIoKind {
     None,
     Both,
@@ -143,12 +194,14 @@ impl std::fmt::Display for ColumnIoKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Row {
     pub io_w: bool,
     pub io_e: bool,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Gts {
     None,
     Single(ColId),
@@ -157,6 +210,7 @@ pub enum Gts {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct McbIo {
     pub row: RowId,
     pub iob: TileIobId,
@@ -168,7 +222,22 @@ impl std::fmt::Display for McbIo {
     }
 }
 
+impl std::str::FromStr for McbIo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (row, iob) = s
+            .split_once('.')
+            .ok_or_else(|| format!("invalid MCB IO \"{s}\""))?;
+        Ok(McbIo {
+            row: parse_id(row, RowTag::PREFIX)?,
+            iob: parse_id(iob, IobTag::PREFIX)?,
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mcb {
     pub row_mcb: RowId,
     pub row_mui: [RowId; 8],
@@ -187,6 +256,7 @@ pub struct Mcb {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisabledPart {
     Gtp,
     Mcb,
@@ -208,6 +278,7 @@ impl std::fmt::Display for DisabledPart {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DcmKind {
     Bot,
     BotMid,
@@ -216,6 +287,7 @@ pub enum DcmKind {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PllKind {
     BotOut0,
     BotOut1,
@@ -553,32 +625,54 @@ impl Chip {
 }
 
 impl Chip {
+    fn dump_column(cd: &Column) -> String {
+        let mut res = match cd.kind {
+            ColumnKind::Io => "io",
+            ColumnKind::CleXL => "clexl",
+            ColumnKind::CleXM => "clexm",
+            ColumnKind::CleClk => "clexl_clk",
+            ColumnKind::Bram => "bram",
+            ColumnKind::Dsp => "dsp",
+            ColumnKind::DspPlus => "dsp_gt",
+        }
+        .to_string();
+        match cd.io_s {
+            ColumnIoKind::None => (),
+            ColumnIoKind::Inner => res += " + io_s_inner",
+            ColumnIoKind::Outer => res += " + io_s_outer",
+            ColumnIoKind::Both => res += " + io_s",
+        }
+        match cd.io_n {
+            ColumnIoKind::None => (),
+            ColumnIoKind::Inner => res += " + io_n_inner",
+            ColumnIoKind::Outer => res += " + io_n_outer",
+            ColumnIoKind::Both => res += " + io_n",
+        }
+        res
+    }
+
+    fn dump_row(rd: &Row) -> &'static str {
+        match (rd.io_w, rd.io_e) {
+            (true, true) => "io_w + io_e",
+            (true, false) => "io_w",
+            (false, true) => "io_e",
+            (false, false) => "null",
+        }
+    }
+
+    fn dump_gts(&self) -> Option<String> {
+        match self.gts {
+            Gts::None => None,
+            Gts::Single(cl) => Some(format!("single {cl}")),
+            Gts::Double(cl, cr) => Some(format!("double {cl}, {cr}")),
+            Gts::Quad(cl, cr) => Some(format!("quad {cl}, {cr}")),
+        }
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         writeln!(o, "\tcolumns {{")?;
         for (col, cd) in &self.columns {
-            write!(o, "\t\t")?;
-            match cd.kind {
-                ColumnKind::Io => write!(o, "io")?,
-                ColumnKind::CleXL => write!(o, "clexl")?,
-                ColumnKind::CleXM => write!(o, "clexm")?,
-                ColumnKind::CleClk => write!(o, "clexl_clk")?,
-                ColumnKind::Bram => write!(o, "bram")?,
-                ColumnKind::Dsp => write!(o, "dsp")?,
-                ColumnKind::DspPlus => write!(o, "dsp_gt")?,
-            }
-            match cd.io_s {
-                ColumnIoKind::None => (),
-                ColumnIoKind::Inner => write!(o, " + io_s_inner")?,
-                ColumnIoKind::Outer => write!(o, " + io_s_outer")?,
-                ColumnIoKind::Both => write!(o, " + io_s")?,
-            }
-            match cd.io_n {
-                ColumnIoKind::None => (),
-                ColumnIoKind::Inner => write!(o, " + io_n_inner")?,
-                ColumnIoKind::Outer => write!(o, " + io_n_outer")?,
-                ColumnIoKind::Both => write!(o, " + io_n")?,
-            }
-            write!(o, ", // {col}")?;
+            write!(o, "\t\t{cd}, // {col}", cd = Self::dump_column(cd))?;
             if let Some((cl, cr)) = self.cols_clk_fold
                 && (col == cl || col == cr)
             {
@@ -622,14 +716,7 @@ impl Chip {
             if Some(row) == self.row_mcb_split {
                 writeln!(o, "\t\t// MCB split")?;
             }
-            write!(o, "\t\t")?;
-            match (rd.io_w, rd.io_e) {
-                (true, true) => write!(o, "io_w + io_e")?,
-                (true, false) => write!(o, "io_w")?,
-                (false, true) => write!(o, "io_e")?,
-                (false, false) => write!(o, "null")?,
-            }
-            write!(o, ", // {row}")?;
+            write!(o, "\t\t{rd}, // {row}", rd = Self::dump_row(rd))?;
             for (i, mcb) in self.mcbs.iter().enumerate() {
                 if row == mcb.row_mcb {
                     write!(o, " MCB{i}.MCB")?;
@@ -710,11 +797,8 @@ impl Chip {
             writeln!(o, "\trow_mcb_split {row};")?;
         }
 
-        match self.gts {
-            Gts::None => (),
-            Gts::Single(cl) => writeln!(o, "\tgts single {cl};")?,
-            Gts::Double(cl, cr) => writeln!(o, "\tgts double {cl}, {cr};")?,
-            Gts::Quad(cl, cr) => writeln!(o, "\tgts quad {cl}, {cr};")?,
+        if let Some(gts) = self.dump_gts() {
+            writeln!(o, "\tgts {gts};")?;
         }
 
         for mcb in &self.mcbs {
@@ -767,4 +851,278 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns, rows, memory
+    /// controllers and config pads keyed by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (col, cd) in &self.columns {
+            res.insert_leaf(format!("column {col}"), Self::dump_column(cd));
+        }
+        if let Some((cl, cr)) = self.cols_clk_fold {
+            res.insert_leaf("cols_clk_fold", format!("{cl}, {cr}"));
+        }
+        for (row, rd) in &self.rows {
+            res.insert_leaf(format!("row {row}"), Self::dump_row(rd));
+        }
+        res.insert_leaf(
+            "rows_pci_ce_split",
+            format!(
+                "{rb}, {rt}",
+                rb = self.rows_pci_ce_split.0,
+                rt = self.rows_pci_ce_split.1
+            ),
+        );
+        if let Some((rl, rr)) = self.rows_bank_split {
+            res.insert_leaf("rows_bank_split", format!("{rl}, {rr}"));
+        }
+        if let Some(row) = self.row_mcb_split {
+            res.insert_leaf("row_mcb_split", row);
+        }
+        if let Some(gts) = self.dump_gts() {
+            res.insert_leaf("gts", gts);
+        }
+        for mcb in &self.mcbs {
+            let mut node = DiffNode::new();
+            node.insert_leaf("mui", mcb.row_mui.iter().join(", "));
+            node.insert_leaf("iop_dq", mcb.iop_dq.iter().join(", "));
+            node.insert_leaf("iop_dqs", mcb.iop_dqs.iter().join(", "));
+            node.insert_leaf("io_dm", mcb.io_dm.iter().join(", "));
+            node.insert_leaf("iop_clk", mcb.iop_clk);
+            node.insert_leaf("io_addr", mcb.io_addr.iter().join(", "));
+            node.insert_leaf("io_ba", mcb.io_ba.iter().join(", "));
+            node.insert_leaf("io_ras", mcb.io_ras);
+            node.insert_leaf("io_cas", mcb.io_cas);
+            node.insert_leaf("io_we", mcb.io_we);
+            node.insert_leaf("io_odt", mcb.io_odt);
+            node.insert_leaf("io_cke", mcb.io_cke);
+            node.insert_leaf("io_reset", mcb.io_reset);
+            res.insert(format!("mcb {row}", row = mcb.row_mcb), node);
+        }
+        for (k, v) in &self.cfg_io {
+            res.insert_leaf(format!("cfg_io {k}"), v);
+        }
+        res.insert_leaf("has_encrypt", self.has_encrypt);
+        res
+    }
+
+    fn parse_column(s: &str) -> Result<Column, String> {
+        let mut parts = s.split(" + ");
+        let kind = match parts.next().unwrap() {
+            "io" => ColumnKind::Io,
+            "clexl" => ColumnKind::CleXL,
+            "clexm" => ColumnKind::CleXM,
+            "clexl_clk" => ColumnKind::CleClk,
+            "bram" => ColumnKind::Bram,
+            "dsp" => ColumnKind::Dsp,
+            "dsp_gt" => ColumnKind::DspPlus,
+            kind => return Err(format!("unknown column kind {kind}")),
+        };
+        let mut io_s = ColumnIoKind::None;
+        let mut io_n = ColumnIoKind::None;
+        for part in parts {
+            match part {
+                "io_s_inner" => io_s = ColumnIoKind::Inner,
+                "io_s_outer" => io_s = ColumnIoKind::Outer,
+                "io_s" => io_s = ColumnIoKind::Both,
+                "io_n_inner" => io_n = ColumnIoKind::Inner,
+                "io_n_outer" => io_n = ColumnIoKind::Outer,
+                "io_n" => io_n = ColumnIoKind::Both,
+                _ => return Err(format!("unknown column part \"{part}\"")),
+            }
+        }
+        Ok(Column { kind, io_s, io_n })
+    }
+
+    fn parse_row(s: &str) -> Result<Row, String> {
+        let mut row = Row {
+            io_w: false,
+            io_e: false,
+        };
+        if s != "null" {
+            for part in s.split(" + ") {
+                match part {
+                    "io_w" => row.io_w = true,
+                    "io_e" => row.io_e = true,
+                    _ => return Err(format!("unknown row part \"{part}\"")),
+                }
+            }
+        }
+        Ok(row)
+    }
+
+    fn parse_pair<T: EntityId>(s: &str, prefix: &str) -> Result<(T, T), String> {
+        let (a, b) = s
+            .split_once(", ")
+            .ok_or_else(|| format!("invalid pair \"{s}\""))?;
+        Ok((parse_id(a, prefix)?, parse_id(b, prefix)?))
+    }
+
+    fn parse_array<T: std::str::FromStr<Err = String>, const N: usize>(
+        s: &str,
+    ) -> Result<[T; N], String> {
+        let items = split_list(s)
+            .map(|x| x.parse())
+            .collect::<Result<Vec<T>, _>>()?;
+        let len = items.len();
+        items
+            .try_into()
+            .map_err(|_| format!("expected {N} items, got {len}"))
+    }
+
+    fn parse_rows<const N: usize>(s: &str) -> Result<[RowId; N], String> {
+        let rows = split_list(s)
+            .map(|x| parse_id(x, RowTag::PREFIX))
+            .collect::<Result<Vec<_>, _>>()?;
+        let len = rows.len();
+        rows.try_into()
+            .map_err(|_| format!("expected {N} rows, got {len}"))
+    }
+
+    fn parse_mcb(p: &mut TextParser, row_mcb: RowId) -> Result<Mcb, TextError> {
+        let mut row_mui = None;
+        let mut iop_dq = None;
+        let mut iop_dqs = None;
+        let mut io_dm = None;
+        let mut iop_clk = None;
+        let mut io_addr = None;
+        let mut io_ba = None;
+        let mut io_ras = None;
+        let mut io_cas = None;
+        let mut io_we = None;
+        let mut io_odt = None;
+        let mut io_cke = None;
+        let mut io_reset = None;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "mui" => row_mui = Some(line.check(Self::parse_rows(val))?),
+                "iop_dq" => iop_dq = Some(line.check(Self::parse_rows(val))?),
+                "iop_dqs" => iop_dqs = Some(line.check(Self::parse_rows(val))?),
+                "io_dm" => io_dm = Some(line.check(Self::parse_array(val))?),
+                "iop_clk" => iop_clk = Some(line.check(parse_id(val, RowTag::PREFIX))?),
+                "io_addr" => io_addr = Some(line.check(Self::parse_array(val))?),
+                "io_ba" => io_ba = Some(line.check(Self::parse_array(val))?),
+                "io_ras" => io_ras = Some(line.check(val.parse())?),
+                "io_cas" => io_cas = Some(line.check(val.parse())?),
+                "io_we" => io_we = Some(line.check(val.parse())?),
+                "io_odt" => io_odt = Some(line.check(val.parse())?),
+                "io_cke" => io_cke = Some(line.check(val.parse())?),
+                "io_reset" => io_reset = Some(line.check(val.parse())?),
+                _ => return Err(line.error(format!("unknown mcb statement {kw}"))),
+            }
+        }
+        Ok(Mcb {
+            row_mcb,
+            row_mui: row_mui.ok_or_else(|| p.missing("mui"))?,
+            iop_dq: iop_dq.ok_or_else(|| p.missing("iop_dq"))?,
+            iop_dqs: iop_dqs.ok_or_else(|| p.missing("iop_dqs"))?,
+            io_dm: io_dm.ok_or_else(|| p.missing("io_dm"))?,
+            iop_clk: iop_clk.ok_or_else(|| p.missing("iop_clk"))?,
+            io_addr: io_addr.ok_or_else(|| p.missing("io_addr"))?,
+            io_ba: io_ba.ok_or_else(|| p.missing("io_ba"))?,
+            io_ras: io_ras.ok_or_else(|| p.missing("io_ras"))?,
+            io_cas: io_cas.ok_or_else(|| p.missing("io_cas"))?,
+            io_we: io_we.ok_or_else(|| p.missing("io_we"))?,
+            io_odt: io_odt.ok_or_else(|| p.missing("io_odt"))?,
+            io_cke: io_cke.ok_or_else(|| p.missing("io_cke"))?,
+            io_reset: io_reset.ok_or_else(|| p.missing("io_reset"))?,
+        })
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut columns = EntityVec::new();
+        let mut cols_clk_fold = None;
+        let mut rows = EntityVec::new();
+        let mut rows_pci_ce_split = None;
+        let mut rows_bank_split = None;
+        let mut row_mcb_split = None;
+        let mut gts = Gts::None;
+        let mut mcbs = vec![];
+        let mut cfg_io = BTreeMap::new();
+        let mut has_encrypt = false;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("columns", "")) => {
+                    while !p.try_block_end() {
+                        let cline = p.expect_line()?;
+                        columns.push(cline.check(Self::parse_column(cline.list_item()?))?);
+                    }
+                }
+                Some(("rows", "")) => {
+                    while !p.try_block_end() {
+                        let rline = p.expect_line()?;
+                        rows.push(rline.check(Self::parse_row(rline.list_item()?))?);
+                    }
+                }
+                Some(("mcb", row)) => {
+                    let row = line.check(parse_id(row, RowTag::PREFIX))?;
+                    mcbs.push(Self::parse_mcb(p, row)?);
+                }
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "cols_clk_fold" => {
+                            cols_clk_fold = Some(line.check(Self::parse_pair(val, ColTag::PREFIX))?)
+                        }
+                        "rows_pci_ce_split" => {
+                            rows_pci_ce_split =
+                                Some(line.check(Self::parse_pair(val, RowTag::PREFIX))?)
+                        }
+                        "rows_bank_split" => {
+                            rows_bank_split =
+                                Some(line.check(Self::parse_pair(val, RowTag::PREFIX))?)
+                        }
+                        "row_mcb_split" => {
+                            row_mcb_split = Some(line.check(parse_id(val, RowTag::PREFIX))?)
+                        }
+                        "gts" => {
+                            gts = if let Some(col) = val.strip_prefix("single ") {
+                                Gts::Single(line.check(parse_id(col, ColTag::PREFIX))?)
+                            } else if let Some(cols) = val.strip_prefix("double ") {
+                                let (cl, cr) =
+                                    line.check(Self::parse_pair(cols, ColTag::PREFIX))?;
+                                Gts::Double(cl, cr)
+                            } else if let Some(cols) = val.strip_prefix("quad ") {
+                                let (cl, cr) =
+                                    line.check(Self::parse_pair(cols, ColTag::PREFIX))?;
+                                Gts::Quad(cl, cr)
+                            } else {
+                                return Err(line.error(format!("invalid gts \"{val}\"")));
+                            }
+                        }
+                        "cfg_io" => {
+                            let (pad, io) = line.check(split_assign(val))?;
+                            cfg_io.insert(line.check(pad.parse())?, line.check(io.parse())?);
+                        }
+                        "has_encrypt" => has_encrypt = true,
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        // The clock column is not dumped, it is the column holding the clock spine.
+        let Some(col_clk) = columns
+            .iter()
+            .find_map(|(col, cd)| (cd.kind == ColumnKind::CleClk).then_some(col))
+        else {
+            return Err(p.missing("clexl_clk column"));
+        };
+        Ok(Chip {
+            columns,
+            col_clk,
+            cols_clk_fold,
+            rows,
+            rows_pci_ce_split: rows_pci_ce_split.ok_or_else(|| p.missing("rows_pci_ce_split"))?,
+            rows_bank_split,
+            row_mcb_split,
+            gts,
+            mcbs,
+            cfg_io,
+            has_encrypt,
+        })
+    }
 }
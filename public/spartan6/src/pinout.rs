@@ -0,0 +1,65 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::grid::TileIobId;
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad},
+    chip::Chip,
+};
+
+impl Chip {
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let cfg_io: BTreeMap<_, _> = self.cfg_io.iter().map(|(&k, &v)| (v, k)).collect();
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(_) => PinKind::Io,
+                BondPad::Gt(..) => PinKind::Gt,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::VccInt
+                | BondPad::VccAux
+                | BondPad::VccO(_)
+                | BondPad::VccBatt
+                | BondPad::Vfs => PinKind::Power,
+                BondPad::Gnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::RFuse => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Io(io) => {
+                    ppin = ppin.with_bank(self.get_io_bank(io)).with_io(io);
+                    // every IOB pair is a master (odd, P) and a slave (even, N)
+                    let idx = io.iob().to_idx();
+                    ppin.diff = Some(if idx % 2 == 1 {
+                        DiffPolarity::P
+                    } else {
+                        DiffPolarity::N
+                    });
+                    partners.insert(
+                        io.to_string(),
+                        io.with_iob(TileIobId::from_idx(idx ^ 1)).to_string(),
+                    );
+                    if bond.vref.contains(&io) {
+                        ppin = ppin.with_function("VREF");
+                    }
+                    if let Some(cpad) = cfg_io.get(&io) {
+                        ppin = ppin.with_function(cpad);
+                    }
+                }
+                BondPad::Gt(bank, _) | BondPad::VccO(bank) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
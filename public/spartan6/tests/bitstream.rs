@@ -0,0 +1,36 @@
+use prjcombine_spartan6::db::Database;
+use prjcombine_test_util::{check_decode_roundtrip, check_emit_roundtrip, sample_config};
+use prjcombine_types::bsdata::BsData;
+use prjcombine_xilinx_bitstream::{BitstreamMode, KeyData, emit, parse_strict};
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan6.zstd");
+const DEVICES: [&str; 2] = ["xc6slx4", "xc6slx25t"];
+
+#[test]
+fn decode_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    let bsdata = BsData::new();
+    for name in DEVICES {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&db.int, &dev.disabled);
+        check_decode_roundtrip(name, &edev.egrid, &bsdata, |config| {
+            let bitstream = edev.encode(&bsdata, config).unwrap();
+            let data = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain).unwrap();
+            let parsed = parse_strict(&edev.bs_geom, &data, &KeyData::None).unwrap();
+            edev.decode(&bsdata, &parsed).unwrap()
+        });
+    }
+}
+
+#[test]
+fn emit_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    let bsdata = BsData::new();
+    for name in DEVICES {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&db.int, &dev.disabled);
+        let config = sample_config(&edev.egrid, &bsdata);
+        let bitstream = edev.encode(&bsdata, &config).unwrap();
+        check_emit_roundtrip(name, &edev.bs_geom, &bitstream, &[BitstreamMode::Plain]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+
+use prjcombine_interconnect::{
+    db::{BelInfo, SwitchBoxItem},
+    graph::RoutingPipKind,
+};
+use prjcombine_spartan6::db::Database;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan6.zstd");
+
+#[test]
+fn routing_graph_pair_mux() {
+    let mut db = Database::from_file(DB).unwrap();
+    // The database lists every source of a pair mux in a single selection.  Also make each
+    // source selectable on its own, as the same PIP may then be reached twice.
+    for tcls in db.int.tile_classes.values_mut() {
+        for bel in tcls.bels.values_mut() {
+            let BelInfo::SwitchBox(sb) = bel else {
+                continue;
+            };
+            for item in &mut sb.items {
+                let SwitchBoxItem::PairMux(mux) = item else {
+                    continue;
+                };
+                let mut extra = vec![];
+                for (src, bits) in &mux.src {
+                    if src[0].is_some() && src[1].is_some() {
+                        extra.push(([src[0], None], bits.clone()));
+                        extra.push(([None, src[1]], bits.clone()));
+                    }
+                }
+                assert!(!extra.is_empty());
+                mux.src.extend(extra);
+            }
+        }
+    }
+    let dev = db.devices.iter().find(|dev| dev.name == "xc6slx4").unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int, &dev.disabled);
+    let graph = edev.egrid.routing_graph();
+
+    let mut seen = HashSet::new();
+    for pip in graph.pips.values() {
+        assert!(
+            seen.insert((
+                pip.tile,
+                pip.kind,
+                pip.tile_wire_out,
+                pip.tile_wire_in,
+                pip.inv
+            )),
+            "duplicate pip {pip:?}"
+        );
+    }
+
+    // every source of a pair mux gives exactly one PIP to its half, however many
+    // selections it appears in
+    let mut expected = 0;
+    for (tcrd, tile) in edev.egrid.tiles() {
+        for bel in edev.egrid.db[tile.class].bels.values() {
+            let BelInfo::SwitchBox(sb) = bel else {
+                continue;
+            };
+            for item in &sb.items {
+                let SwitchBoxItem::PairMux(mux) = item else {
+                    continue;
+                };
+                let pips: HashSet<_> = mux
+                    .src
+                    .keys()
+                    .flat_map(|src| mux.dst.into_iter().zip(*src))
+                    .filter_map(|(dst, src)| Some((dst, src?)))
+                    .filter(|&(dst, src)| {
+                        edev.egrid
+                            .resolve_tile_wire(tcrd, dst)
+                            .and(edev.egrid.resolve_tile_wire(tcrd, src.tw))
+                            .is_some()
+                    })
+                    .collect();
+                expected += pips.len();
+            }
+        }
+    }
+    let actual = graph
+        .pips
+        .values()
+        .filter(|pip| pip.kind == RoutingPipKind::PairMux)
+        .count();
+    assert_ne!(actual, 0);
+    assert_eq!(actual, expected);
+}
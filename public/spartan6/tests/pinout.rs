@@ -0,0 +1,24 @@
+use prjcombine_spartan6::db::Database;
+use prjcombine_test_util::check_pinout;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan6.zstd");
+
+// The database has no vendor pair numbers, so the IO names are made of the
+// functions and the bank alone.
+#[test]
+fn pinout() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == "xc6slx9").unwrap();
+    let (_, &bond) = dev.bonds.get("tqg144").unwrap();
+    let pinout = db.chips[dev.chip].make_pinout(&db.bonds[bond], "xc6slx9", "tqg144");
+    check_pinout(
+        &pinout,
+        &[
+            ("P1", "IO_VREF_3", Some("3"), Some("P2")),
+            ("P2", "IO_3", Some("3"), Some("P1")),
+            ("P55", "IO_USER_CCLK_2", Some("2"), Some("P56")),
+            ("P56", "IO_D13_2", Some("2"), Some("P55")),
+            ("P132", "IO_0", Some("0"), Some("P131")),
+        ],
+    );
+}
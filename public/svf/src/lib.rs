@@ -0,0 +1,304 @@
+//! JTAG programming sequences for the CPLD families, and their SVF / XSVF encodings.
+//!
+//! The family crates build a [`JtagProgram`] for a single device from a JED file; this
+//! crate only holds the sequence representation and the emitters.  The emitted files
+//! assume the device is alone on the chain.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use prjcombine_types::bitvec::BitVec;
+
+/// A single step of a JTAG programming sequence.
+#[derive(Clone, Debug)]
+pub enum JtagOp {
+    /// A human-readable comment.  Emitted as `//` lines in SVF, and as `XCOMMENT` in XSVF.
+    Comment(String),
+    /// Shifts a value into the instruction register, then goes to Run-Test/Idle.
+    ShiftIr(BitVec),
+    /// Shifts a value into the data register, then goes to Run-Test/Idle.
+    ShiftDr(DrShift),
+    /// Stays in Run-Test/Idle for at least `tck` clocks and at least `usecs` microseconds.
+    ///
+    /// XSVF can only express wait times, so the clock count is converted assuming
+    /// a TCK frequency of at most 1MHz.
+    RunTest { tck: u32, usecs: u32 },
+}
+
+/// A data register shift, with an optional check of the value shifted out.
+#[derive(Clone, Debug)]
+pub struct DrShift {
+    pub tdi: BitVec,
+    /// The expected value shifted out, and the mask of bits that are to be compared.
+    pub check: Option<(BitVec, BitVec)>,
+}
+
+/// Options common to all CPLD programming sequence generators.
+#[derive(Clone, Debug)]
+pub struct ProgramOptions {
+    /// If true, the device is erased before programming.
+    pub erase: bool,
+    /// If true, the programmed fuses are read back and compared before the final
+    /// (protection and `DONE`) programming pass.
+    pub verify: bool,
+}
+
+impl Default for ProgramOptions {
+    fn default() -> Self {
+        Self {
+            erase: true,
+            verify: true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProgramError {
+    /// The device is not present in the database.
+    UnknownDevice(String),
+    /// The JED file has no fuses.
+    MissingFuses,
+    /// The JED file has a different number of fuses than the device.
+    FuseCountMismatch { expected: usize, got: usize },
+    /// The JED file has a user field of the wrong length for the device.
+    UserCodeLengthMismatch { expected: usize, got: usize },
+}
+
+impl std::fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramError::UnknownDevice(device) => write!(f, "unknown device {device}"),
+            ProgramError::MissingFuses => write!(f, "JED file has no fuses"),
+            ProgramError::FuseCountMismatch { expected, got } => {
+                write!(f, "fuse count mismatch: expected {expected}, got {got}")
+            }
+            ProgramError::UserCodeLengthMismatch { expected, got } => {
+                write!(
+                    f,
+                    "user field length mismatch: expected {expected}, got {got}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+/// Builds a [`BitVec`] of the given length from the low bits of an integer.
+pub fn bits_from_u64(val: u64, len: usize) -> BitVec {
+    (0..len).map(|i| i < 64 && (val >> i & 1) != 0).collect()
+}
+
+/// A sequence of JTAG operations for a single device on the chain.
+#[derive(Clone, Debug, Default)]
+pub struct JtagProgram {
+    pub ops: Vec<JtagOp>,
+}
+
+fn hex(val: &BitVec) -> String {
+    let mut res = String::new();
+    for i in (0..val.len().div_ceil(4)).rev() {
+        let mut digit = 0;
+        for j in 0..4 {
+            let idx = i * 4 + j;
+            if idx < val.len() && val[idx] {
+                digit |= 1 << j;
+            }
+        }
+        write!(res, "{digit:x}").unwrap();
+    }
+    res
+}
+
+fn xsvf_bytes(val: &BitVec, out: &mut Vec<u8>) {
+    let num = val.len().div_ceil(8);
+    for i in (0..num).rev() {
+        let mut byte = 0;
+        for j in 0..8 {
+            let idx = i * 8 + j;
+            if idx < val.len() && val[idx] {
+                byte |= 1 << j;
+            }
+        }
+        out.push(byte);
+    }
+}
+
+const XCOMPLETE: u8 = 0x00;
+const XTDOMASK: u8 = 0x01;
+const XSIR: u8 = 0x02;
+const XRUNTEST: u8 = 0x04;
+const XREPEAT: u8 = 0x07;
+const XSDRSIZE: u8 = 0x08;
+const XSDRTDO: u8 = 0x09;
+const XSTATE: u8 = 0x12;
+const XENDIR: u8 = 0x13;
+const XENDDR: u8 = 0x14;
+const XCOMMENT: u8 = 0x16;
+const XWAIT: u8 = 0x17;
+
+const XSTATE_RESET: u8 = 0x00;
+const XSTATE_IDLE: u8 = 0x01;
+
+impl JtagProgram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn comment(&mut self, text: impl Into<String>) {
+        self.ops.push(JtagOp::Comment(text.into()));
+    }
+
+    pub fn sir(&mut self, len: usize, val: u64) {
+        self.ops.push(JtagOp::ShiftIr(bits_from_u64(val, len)));
+    }
+
+    pub fn sdr(&mut self, tdi: BitVec) {
+        self.ops.push(JtagOp::ShiftDr(DrShift { tdi, check: None }));
+    }
+
+    pub fn sdr_check(&mut self, tdi: BitVec, tdo: BitVec, mask: BitVec) {
+        assert_eq!(tdi.len(), tdo.len());
+        assert_eq!(tdi.len(), mask.len());
+        self.ops.push(JtagOp::ShiftDr(DrShift {
+            tdi,
+            check: Some((tdo, mask)),
+        }));
+    }
+
+    pub fn run_test(&mut self, tck: u32, usecs: u32) {
+        self.ops.push(JtagOp::RunTest { tck, usecs });
+    }
+
+    pub fn emit_svf(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "TRST OFF;").unwrap();
+        writeln!(out, "ENDIR IDLE;").unwrap();
+        writeln!(out, "ENDDR IDLE;").unwrap();
+        writeln!(out, "STATE RESET;").unwrap();
+        writeln!(out, "STATE IDLE;").unwrap();
+        for op in &self.ops {
+            match op {
+                JtagOp::Comment(text) => {
+                    for line in text.lines() {
+                        writeln!(out, "// {line}").unwrap();
+                    }
+                }
+                JtagOp::ShiftIr(tdi) => {
+                    writeln!(
+                        out,
+                        "SIR {len} TDI ({tdi});",
+                        len = tdi.len(),
+                        tdi = hex(tdi)
+                    )
+                    .unwrap();
+                }
+                JtagOp::ShiftDr(shift) => {
+                    write!(
+                        out,
+                        "SDR {len} TDI ({tdi})",
+                        len = shift.tdi.len(),
+                        tdi = hex(&shift.tdi)
+                    )
+                    .unwrap();
+                    if let Some((ref tdo, ref mask)) = shift.check {
+                        write!(
+                            out,
+                            " TDO ({tdo}) MASK ({mask})",
+                            tdo = hex(tdo),
+                            mask = hex(mask)
+                        )
+                        .unwrap();
+                    }
+                    writeln!(out, ";").unwrap();
+                }
+                &JtagOp::RunTest { tck, usecs } => {
+                    if usecs == 0 {
+                        writeln!(out, "RUNTEST {tck} TCK;").unwrap();
+                    } else {
+                        writeln!(
+                            out,
+                            "RUNTEST {tck} TCK {secs:.2E} SEC;",
+                            secs = f64::from(usecs) / 1e6
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    pub fn emit_svf_to_file(&self, fname: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(fname, self.emit_svf())
+    }
+
+    pub fn emit_xsvf(&self) -> Vec<u8> {
+        let mut out = vec![
+            XSTATE,
+            XSTATE_RESET,
+            XSTATE,
+            XSTATE_IDLE,
+            XENDIR,
+            0,
+            XENDDR,
+            0,
+            XREPEAT,
+            0,
+            XRUNTEST,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let mut sdr_size = None;
+        let mut tdo_mask: Option<BitVec> = None;
+        for op in &self.ops {
+            match op {
+                JtagOp::Comment(text) => {
+                    out.push(XCOMMENT);
+                    out.extend(text.bytes());
+                    out.push(0);
+                }
+                JtagOp::ShiftIr(tdi) => {
+                    out.push(XSIR);
+                    out.push(tdi.len().try_into().unwrap());
+                    xsvf_bytes(tdi, &mut out);
+                }
+                JtagOp::ShiftDr(shift) => {
+                    let len = shift.tdi.len();
+                    if sdr_size != Some(len) {
+                        out.push(XSDRSIZE);
+                        out.extend(u32::try_from(len).unwrap().to_be_bytes());
+                        sdr_size = Some(len);
+                        tdo_mask = None;
+                    }
+                    let (tdo, mask) = match shift.check {
+                        Some((ref tdo, ref mask)) => (tdo.clone(), mask.clone()),
+                        None => (BitVec::repeat(false, len), BitVec::repeat(false, len)),
+                    };
+                    if tdo_mask.as_ref() != Some(&mask) {
+                        out.push(XTDOMASK);
+                        xsvf_bytes(&mask, &mut out);
+                        tdo_mask = Some(mask);
+                    }
+                    out.push(XSDRTDO);
+                    xsvf_bytes(&shift.tdi, &mut out);
+                    xsvf_bytes(&tdo, &mut out);
+                }
+                &JtagOp::RunTest { tck, usecs } => {
+                    out.push(XWAIT);
+                    out.push(XSTATE_IDLE);
+                    out.push(XSTATE_IDLE);
+                    out.extend(usecs.max(tck).to_be_bytes());
+                }
+            }
+        }
+        out.push(XCOMPLETE);
+        out
+    }
+
+    pub fn emit_xsvf_to_file(&self, fname: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(fname, self.emit_xsvf())
+    }
+}
@@ -0,0 +1,70 @@
+use prjcombine_svf::{JtagProgram, bits_from_u64};
+
+fn sample() -> JtagProgram {
+    let mut prog = JtagProgram::new();
+    prog.comment("check IDCODE");
+    prog.sir(8, 0xfe);
+    prog.sdr_check(
+        bits_from_u64(0, 12),
+        bits_from_u64(0xabc, 12),
+        bits_from_u64(0xfff, 12),
+    );
+    prog.sdr(bits_from_u64(0x2a, 6));
+    prog.run_test(1, 100);
+    prog.sdr(bits_from_u64(0x15, 6));
+    prog.run_test(20, 0);
+    prog
+}
+
+#[test]
+fn emit_svf() {
+    assert_eq!(
+        sample().emit_svf(),
+        "\
+TRST OFF;
+ENDIR IDLE;
+ENDDR IDLE;
+STATE RESET;
+STATE IDLE;
+// check IDCODE
+SIR 8 TDI (fe);
+SDR 12 TDI (000) TDO (abc) MASK (fff);
+SDR 6 TDI (2a);
+RUNTEST 1 TCK 1.00E-4 SEC;
+SDR 6 TDI (15);
+RUNTEST 20 TCK;
+"
+    );
+}
+
+#[test]
+fn emit_xsvf() {
+    let mut expected = vec![
+        // XSTATE RESET, XSTATE IDLE, XENDIR IDLE, XENDDR IDLE, XREPEAT 0, XRUNTEST 0
+        0x12, 0x00, 0x12, 0x01, 0x13, 0x00, 0x14, 0x00, 0x07, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+    ];
+    // XCOMMENT
+    expected.push(0x16);
+    expected.extend(b"check IDCODE\0");
+    expected.extend([
+        // XSIR
+        0x02, 8, 0xfe, //
+        // XSDRSIZE, XTDOMASK, XSDRTDO
+        0x08, 0, 0, 0, 12, //
+        0x01, 0x0f, 0xff, //
+        0x09, 0x00, 0x00, 0x0a, 0xbc, //
+        // a new size resets the mask; unchecked shifts have an all-zero one
+        0x08, 0, 0, 0, 6, //
+        0x01, 0x00, //
+        0x09, 0x2a, 0x00, //
+        // XWAIT IDLE IDLE, in microseconds
+        0x17, 0x01, 0x01, 0, 0, 0, 100, //
+        // same size and mask: only XSDRTDO
+        0x09, 0x15, 0x00, //
+        // clock counts become microseconds
+        0x17, 0x01, 0x01, 0, 0, 0, 20, //
+        // XCOMPLETE
+        0x00,
+    ]);
+    assert_eq!(sample().emit_xsvf(), expected);
+}
@@ -0,0 +1,471 @@
+//! Helpers shared by the tests of the bitstream, boundary scan and pinout crates.  Not part of the
+//! public API: the family crates only pull this in as a dev-dependency.
+
+use std::collections::{BTreeSet, HashMap};
+
+use bitvec::prelude::*;
+use prjcombine_interconnect::{
+    db::{BelAttribute, BelAttributeType, BelInfo, BelInput, BelKind, SwitchBoxItem, TileClass},
+    decode::{DecodedBitstream, DecodedSetting, DecodedTile},
+    grid::ExpandedGrid,
+};
+use prjcombine_types::{
+    bscan::BScanPad,
+    bsdata::{BsData, Tile, TileBit, TileItemKind},
+    bsdl::{Bsdl, CellFunction},
+    pinout::{PinKind, Pinout},
+};
+use prjcombine_xilinx_bitstream::{
+    Bitstream, BitstreamGeom, BitstreamMode, DeviceKind, KeyData, emit, parse_strict,
+};
+
+/// A fixed xorshift sequence of bits, used to fill bitstreams with data that covers every
+/// bit without depending on a random number generator.
+pub struct Scrambler {
+    state: u32,
+}
+
+impl Scrambler {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Scrambler { state: 0x2545f491 }
+    }
+
+    pub fn next_bit(&mut self) -> bool {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state & 1 != 0
+    }
+
+    /// Overwrites every bit of the slice with the next bits of the sequence.
+    pub fn fill<T: BitStore, O: BitOrder>(&mut self, bits: &mut BitSlice<T, O>) {
+        for mut bit in bits.iter_mut() {
+            *bit = self.next_bit();
+        }
+    }
+}
+
+/// Fills every frame, BRAM and IOB bit of a Xilinx bitstream with pseudo-random data, so
+/// that round-trip tests cover every bit.  On XC4000 and XC5200, the check bits at the end
+/// of the final frame are set, as the parser leaves them.
+pub fn scramble(bitstream: &mut Bitstream) {
+    let mut scrambler = Scrambler::new();
+    for die in bitstream.die.values_mut() {
+        scrambler.fill(&mut die.frame_data);
+        scrambler.fill(&mut die.bram_data);
+        scrambler.fill(&mut die.iob);
+    }
+    let check_bits = match bitstream.kind {
+        DeviceKind::Xc4000 | DeviceKind::S40Xl if !bitstream.die.first().unwrap().frame(0)[1] => 7,
+        DeviceKind::Xc5200 if bitstream.die.first().unwrap().frame(0)[0] => 12,
+        _ => 0,
+    };
+    if check_bits != 0 {
+        let die = bitstream.die.first_mut().unwrap();
+        let frame = die.frame_mut(die.frame_info.len() - 1);
+        let len = frame.len();
+        frame[len - check_bits..].fill(true);
+    }
+}
+
+/// Builds a configuration with some content in every tile class of the grid: on the first
+/// tile of each class, a source of the first multiplexer, the first programmable buffer,
+/// and a value of the first enum attribute of every bel and of the legacy bitstream data.
+/// The settings and PIPs are listed in the order the decoder reports them.
+pub fn sample_config(egrid: &ExpandedGrid, bsdata: &BsData) -> DecodedBitstream {
+    let db = egrid.db;
+    let mut res = DecodedBitstream::default();
+    let mut seen = BTreeSet::new();
+    for (tcrd, tile) in egrid.tiles() {
+        let tcls = &db[tile.class];
+        if tcls.bitrects.is_empty() || !seen.insert(tile.class) {
+            continue;
+        }
+        // Placeholder bits and bits outside the described rects cannot be decoded, and items
+        // sharing bits with other items of the tile do not decode unambiguously.
+        let uses = item_bit_uses(tcls, bsdata.tiles.get(db.tile_classes.key(tile.class)));
+        let mapped = |bits: &[TileBit]| {
+            bits.iter()
+                .all(|bit| tcls.bitrects.get(bit.rect).is_some() && uses[bit] == 1)
+        };
+        let mut dtile = DecodedTile::default();
+        for (slot, bel) in &tcls.bels {
+            let bname = db.bel_slots.key(slot);
+            match bel {
+                BelInfo::SwitchBox(sb) => {
+                    let (mut has_mux, mut has_buf) = (false, false);
+                    for item in &sb.items {
+                        match item {
+                            SwitchBoxItem::Mux(mux) if !has_mux && mapped(&mux.bits) => {
+                                if let Some((&src, _)) = mux.src.iter().find(|&(_, val)| {
+                                    val.any() && mux.bits_off.as_ref() != Some(val)
+                                }) {
+                                    dtile.pips.push((mux.dst, src));
+                                    has_mux = true;
+                                }
+                            }
+                            SwitchBoxItem::ProgBuf(buf) if !has_buf && mapped(&[buf.bit.bit]) => {
+                                dtile.pips.push((buf.dst, buf.src));
+                                has_buf = true;
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                BelInfo::Bel(bel) => {
+                    let BelKind::Class(bcid) = db.bel_slots[slot].kind else {
+                        unreachable!()
+                    };
+                    let bcls = &db.bel_classes[bcid];
+                    let setting = bel.attributes.iter().find_map(|(aid, attr)| {
+                        let BelAttribute::Enum(ebits) = attr else {
+                            return None;
+                        };
+                        if !mapped(&ebits.bits) {
+                            return None;
+                        }
+                        let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                            unreachable!()
+                        };
+                        let (vid, _) = ebits.values.iter().find(|(_, val)| val.any())?;
+                        Some(DecodedSetting {
+                            bel: bname.to_string(),
+                            attr: bcls.attributes.key(aid).to_string(),
+                            value: db.enum_classes[eid].values[vid].clone().into(),
+                        })
+                    });
+                    dtile.settings.extend(setting);
+                }
+                _ => (),
+            }
+        }
+        if let Some(btile) = bsdata.tiles.get(db.tile_classes.key(tile.class)) {
+            let setting = btile.items.iter().find_map(|(name, item)| {
+                let TileItemKind::Enum { ref values } = item.kind else {
+                    return None;
+                };
+                if !mapped(&item.bits) {
+                    return None;
+                }
+                let (vname, _) = values.iter().find(|(_, val)| val.any())?;
+                let (bel, attr) = name.split_once(':').unwrap_or(("", name));
+                Some(DecodedSetting {
+                    bel: bel.to_string(),
+                    attr: attr.to_string(),
+                    value: vname.clone().into(),
+                })
+            });
+            dtile.settings.extend(setting);
+        }
+        if !dtile.is_empty() {
+            res.tiles.insert(tcrd, dtile);
+        }
+    }
+    res
+}
+
+/// Counts the items of a tile class that use each bit.
+pub fn item_bit_uses(tcls: &TileClass, btile: Option<&Tile>) -> HashMap<TileBit, usize> {
+    let mut bits: Vec<TileBit> = vec![];
+    for bel in tcls.bels.values() {
+        match bel {
+            BelInfo::SwitchBox(sb) => {
+                for item in &sb.items {
+                    match item {
+                        SwitchBoxItem::Mux(mux) => bits.extend(&mux.bits),
+                        SwitchBoxItem::ProgBuf(buf) => bits.push(buf.bit.bit),
+                        SwitchBoxItem::PermaBuf(_) => (),
+                        SwitchBoxItem::Pass(pass) => bits.push(pass.bit.bit),
+                        SwitchBoxItem::BiPass(pass) => bits.push(pass.bit.bit),
+                        SwitchBoxItem::ProgInv(inv) => bits.push(inv.bit.bit),
+                        SwitchBoxItem::ProgDelay(delay) => bits.extend(&delay.bits),
+                        SwitchBoxItem::Bidi(bidi) => bits.push(bidi.bit_upstream.bit),
+                        SwitchBoxItem::PairMux(mux) => bits.extend(&mux.bits),
+                        SwitchBoxItem::WireSupport(support) => {
+                            bits.extend(support.bits.iter().map(|bit| bit.bit))
+                        }
+                    }
+                }
+            }
+            BelInfo::Bel(bel) => {
+                for inp in bel.inputs.values() {
+                    if let BelInput::Invertible(_, bit) = *inp {
+                        bits.push(bit.bit);
+                    }
+                }
+                for attr in bel.attributes.values() {
+                    match attr {
+                        BelAttribute::BitVec(pbits) => bits.extend(pbits.iter().map(|bit| bit.bit)),
+                        BelAttribute::Enum(ebits) => bits.extend(&ebits.bits),
+                    }
+                }
+            }
+            BelInfo::TestMux(tmux) => bits.extend(&tmux.bits),
+            BelInfo::OldTestMux | BelInfo::Legacy(_) => (),
+        }
+    }
+    if let Some(btile) = btile {
+        for item in btile.items.values() {
+            bits.extend(&item.bits);
+        }
+    }
+    let mut res = HashMap::new();
+    for bit in bits {
+        *res.entry(bit).or_default() += 1;
+    }
+    res
+}
+
+/// Checks that an empty configuration and the [`sample_config`] of the grid survive
+/// `roundtrip`, which encodes a configuration, writes and parses the bitstream, and
+/// decodes it again.
+pub fn check_decode_roundtrip(
+    name: &str,
+    egrid: &ExpandedGrid,
+    bsdata: &BsData,
+    roundtrip: impl Fn(&DecodedBitstream) -> DecodedBitstream,
+) {
+    let empty = DecodedBitstream::default();
+    assert_eq!(roundtrip(&empty), empty, "{name}");
+    let config = sample_config(egrid, bsdata);
+    assert!(!config.tiles.is_empty(), "{name}: no configurable tiles");
+    let decoded = roundtrip(&config);
+    for (tcrd, tile) in &config.tiles {
+        assert_eq!(
+            decoded.tiles.get(tcrd),
+            Some(tile),
+            "{name} {tcrd}",
+            tcrd = tcrd.to_string(egrid.db)
+        );
+    }
+    assert_eq!(decoded, config, "{name}");
+}
+
+/// Checks that a Xilinx bitstream and a [`scramble`]d copy of it are written and parsed back
+/// unchanged in every given mode.  The bitstream is passed through the emitter and parser
+/// once first, so that it has every register the emitter writes.
+pub fn check_emit_roundtrip(
+    name: &str,
+    geom: &BitstreamGeom,
+    bitstream: &Bitstream,
+    modes: &[BitstreamMode],
+) {
+    let data = emit(geom, bitstream, BitstreamMode::Plain).unwrap();
+    let bitstream = parse_strict(geom, &data, &KeyData::None).unwrap();
+    let mut scrambled = bitstream.clone();
+    scramble(&mut scrambled);
+    for bitstream in [bitstream, scrambled] {
+        for &mode in modes {
+            let data = emit(geom, &bitstream, mode).unwrap();
+            let parsed = parse_strict(geom, &data, &KeyData::None).unwrap();
+            let diff = Bitstream::diff(&bitstream, &parsed);
+            assert!(diff.is_empty(), "{name} {mode:?}: {diff:?}");
+        }
+    }
+}
+
+/// Checks a BSDL description against the boundary scan register it was made from:
+/// the register length, the cells of every bonded pad, and the control cell references,
+/// both in the [`Bsdl`] and in its emitted text.
+pub fn check_bsdl(bsdl: &Bsdl, bits: usize, pads: &[BScanPad]) {
+    assert_eq!(bsdl.cells.len(), bits);
+    for insn in &bsdl.instructions {
+        assert!(insn.opcode >> bsdl.ir_len == 0, "{name}", name = insn.name);
+    }
+    let bypass = bsdl
+        .instructions
+        .iter()
+        .find(|insn| insn.name == "BYPASS")
+        .unwrap();
+    assert_eq!(bypass.opcode, (1 << bsdl.ir_len) - 1);
+
+    let check_port = |idx: usize, function: CellFunction, control: Option<(usize, bool)>| {
+        let cell = &bsdl.cells[idx];
+        assert_eq!(cell.function, function, "cell {idx}");
+        assert_eq!(cell.control, control, "cell {idx}");
+        let port = cell.port.as_ref().unwrap();
+        assert!(bsdl.ports.contains_key(port), "cell {idx}: {port}");
+    };
+    let check_control = |idx: usize, safe: bool| {
+        let cell = &bsdl.cells[idx];
+        assert_eq!(cell.function, CellFunction::Control, "cell {idx}");
+        assert_eq!(cell.safe, Some(safe), "cell {idx}");
+        assert_eq!(cell.port, None, "cell {idx}");
+    };
+    for &pad in pads {
+        match pad {
+            BScanPad::Input(i) => check_port(i, CellFunction::Input, None),
+            BScanPad::Output(o) => check_port(o, CellFunction::Output2, None),
+            BScanPad::OutputTristate(o, t) => {
+                check_control(t, true);
+                check_port(o, CellFunction::Output3, Some((t, true)));
+            }
+            BScanPad::OutputEnable(o, e) => {
+                check_control(e, false);
+                check_port(o, CellFunction::Output3, Some((e, false)));
+            }
+            BScanPad::InputOutputTristate(i, o, t) => {
+                check_control(t, true);
+                check_port(o, CellFunction::Output3, Some((t, true)));
+                check_port(i, CellFunction::Input, None);
+            }
+            BScanPad::InputOutputEnable(i, o, e) => {
+                check_control(e, false);
+                check_port(o, CellFunction::Output3, Some((e, false)));
+                check_port(i, CellFunction::Input, None);
+            }
+            BScanPad::BiTristate(b, t) => {
+                check_control(t, true);
+                check_port(b, CellFunction::Bidir, Some((t, true)));
+                assert_eq!(bsdl.cells[b].kind, "BC_7");
+            }
+        }
+    }
+
+    let mut out = vec![];
+    bsdl.emit(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let ent = &bsdl.entity;
+    assert!(text.contains(&format!(
+        "attribute BOUNDARY_LENGTH of {ent} : entity is {bits};"
+    )));
+    // the boundary register entries: `"<num> (<cell>, <port>, <function>, <safe>[, <ccell>,
+    // <disval>, Z])"`
+    let mut entries = vec![];
+    for line in text.lines() {
+        let Some(line) = line.trim().strip_prefix('"') else {
+            continue;
+        };
+        let Some((num, rest)) = line.split_once(" (") else {
+            continue;
+        };
+        let Ok(num) = num.parse::<usize>() else {
+            continue;
+        };
+        let fields: Vec<_> = rest.split_once(')').unwrap().0.split(", ").collect();
+        entries.push((num, fields));
+    }
+    assert_eq!(
+        entries.iter().map(|&(num, _)| num).collect::<Vec<_>>(),
+        (0..bits).rev().collect::<Vec<_>>()
+    );
+    let mut referenced = vec![false; bits];
+    for (num, fields) in &entries {
+        if fields[1] != "*" {
+            assert!(
+                text.contains(&format!("\t\"{port}:", port = fields[1])),
+                "cell {num}: {port} not in the pin map",
+                port = fields[1]
+            );
+        }
+        match fields.len() {
+            4 => (),
+            7 => {
+                let ccell: usize = fields[4].parse().unwrap();
+                let control = &entries[bits - 1 - ccell].1;
+                assert_eq!(control[2], "control", "cell {num}");
+                assert_eq!(control[3], fields[5], "cell {num}");
+                assert_eq!(fields[6], "Z", "cell {num}");
+                referenced[ccell] = true;
+            }
+            _ => panic!("cell {num}: malformed entry {fields:?}"),
+        }
+    }
+    for (num, fields) in &entries {
+        if fields[2] == "control" {
+            assert!(referenced[*num], "control cell {num} is not used");
+        }
+    }
+}
+
+/// The IDCODE put into the descriptions checked by [`check_device_bsdl`].
+const TEST_IDCODE: u32 = 0x0123_4093;
+
+/// Checks the BSDL descriptions of a device in each of its packages.  `bonds` gives the
+/// package name, the bond and its pads; `make_bsdl` builds the description of a bond with
+/// the given IDCODE, and `cell` gives the boundary scan cells of a pad, or `None` for pads
+/// that are not in the boundary register, such as the TAP pins.
+pub fn check_device_bsdl<'a, B: 'a, P, I: IntoIterator<Item = P>>(
+    device: &str,
+    bits: usize,
+    bonds: impl IntoIterator<Item = (&'a str, &'a B, I)>,
+    make_bsdl: impl Fn(&'a B, &'a str, Option<u32>) -> Bsdl,
+    cell: impl Fn(P) -> Option<BScanPad>,
+) {
+    let mut num_bonds = 0;
+    for (package, bond, pads) in bonds {
+        let pads: Vec<_> = pads.into_iter().filter_map(&cell).collect();
+        assert!(
+            !pads.is_empty(),
+            "{device} {package}: no boundary scan pads"
+        );
+
+        let bsdl = make_bsdl(bond, package, None);
+        assert_eq!(bsdl.idcode, None, "{device} {package}");
+        let mut out = vec![];
+        bsdl.emit(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(!text.contains("IDCODE_REGISTER"), "{device} {package}");
+
+        let bsdl = make_bsdl(bond, package, Some(TEST_IDCODE));
+        assert_eq!(
+            bsdl.idcode,
+            Some((TEST_IDCODE, 0x0fff_ffff)),
+            "{device} {package}"
+        );
+        let mut out = vec![];
+        bsdl.emit(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(
+            text.contains(&format!(
+                "attribute IDCODE_REGISTER of {ent} : entity is \"XXXX0001001000110100000010010011\";",
+                ent = bsdl.entity
+            )),
+            "{device} {package}"
+        );
+        check_bsdl(&bsdl, bits, &pads);
+        num_bonds += 1;
+    }
+    assert_ne!(num_bonds, 0, "{device}: no bonds");
+}
+
+/// Checks a package pinout: every IO pin has a name, its IO is bonded once, and its
+/// differential partner, if any, is an IO of the other polarity in the same bank that
+/// points back at it.  Then checks the `(pin, name, bank, diff_pin)` of the given pins.
+pub fn check_pinout(pinout: &Pinout, expected: &[(&str, &str, Option<&str>, Option<&str>)]) {
+    let ctx = format!("{} {}", pinout.device, pinout.package);
+    let pins: HashMap<&str, _> = pinout
+        .pins
+        .iter()
+        .map(|pin| (pin.pin.as_str(), pin))
+        .collect();
+    assert_eq!(pins.len(), pinout.pins.len(), "{ctx}: duplicate pins");
+    let mut ios = BTreeSet::new();
+    for pin in &pinout.pins {
+        if pin.kind != PinKind::Io {
+            continue;
+        }
+        let name = &pin.pin;
+        assert!(!pin.name.is_empty(), "{ctx} {name}");
+        if let Some(io) = &pin.io {
+            assert!(ios.insert(io), "{ctx} {name}: IO {io} bonded twice");
+        }
+        if let Some(other) = &pin.diff_pin {
+            let partner = pins[other.as_str()];
+            assert_eq!(partner.kind, PinKind::Io, "{ctx} {name}");
+            assert_eq!(partner.bank, pin.bank, "{ctx} {name}");
+            assert_eq!(partner.diff_pin.as_ref(), Some(&pin.pin), "{ctx} {name}");
+            assert!(pin.diff.is_some(), "{ctx} {name}");
+            assert_ne!(partner.diff, pin.diff, "{ctx} {name}");
+        }
+    }
+    for &(name, fname, bank, diff_pin) in expected {
+        let pin = pins
+            .get(name)
+            .unwrap_or_else(|| panic!("{ctx}: no pin {name}"));
+        assert_eq!(pin.name, fname, "{ctx} {name}");
+        assert_eq!(pin.bank.as_deref(), bank, "{ctx} {name}");
+        assert_eq!(pin.diff_pin.as_deref(), diff_pin, "{ctx} {name}");
+    }
+}
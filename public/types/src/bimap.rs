@@ -149,3 +149,32 @@ mod bincode {
         }
     }
 }
+
+#[cfg(feature = "serde")]
+mod serde {
+    use serde::de::{Deserialize, Deserializer};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::BiMap;
+
+    impl<L: Ord + Clone + Serialize, R: Ord + Clone + Serialize> Serialize for BiMap<L, R> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(self)
+        }
+    }
+
+    impl<'de, L: Ord + Clone + Deserialize<'de>, R: Ord + Clone + Deserialize<'de>> Deserialize<'de>
+        for BiMap<L, R>
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let pairs = Vec::<(L, R)>::deserialize(deserializer)?;
+            Ok(pairs.into_iter().collect())
+        }
+    }
+}
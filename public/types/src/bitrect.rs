@@ -8,4 +8,10 @@ pub trait BitRect: Copy + Debug + Hash + Eq + Sync + Send {
 
     fn xlat_pos_rev(&self, bit: Self::BitPos) -> Option<(RectFrameId, RectBitId)>;
     fn xlat_pos_fwd(&self, bit: (RectFrameId, RectBitId)) -> Self::BitPos;
+
+    // tile class geometry is the largest over all instances of the class, so a bit valid for
+    // the class is not necessarily inside every rect
+    fn contains(&self, _bit: (RectFrameId, RectBitId)) -> bool {
+        true
+    }
 }
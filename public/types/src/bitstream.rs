@@ -0,0 +1,121 @@
+//! Errors shared by the bitstream parsers of all families.
+
+use std::fmt::{self, Display};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BitstreamError {
+    /// The structure starting at the given byte offset runs past the end of input.
+    Truncated { offset: usize },
+    /// A word that cannot start a packet.
+    InvalidWord { offset: usize, word: u32 },
+    /// A command or opcode that the parser does not know, with its payload if the
+    /// format has one.
+    UnknownCommand {
+        offset: usize,
+        opcode: u32,
+        payload: Option<u32>,
+    },
+    /// A well-formed packet that does not fit the expected bitstream structure;
+    /// `packet` is `None` when the input ended instead.
+    UnexpectedPacket {
+        offset: usize,
+        expected: String,
+        packet: Option<String>,
+    },
+    /// The checksum stored in the bitstream does not match the one computed over
+    /// the preceding data.
+    CrcMismatch {
+        offset: usize,
+        expected: u32,
+        actual: u32,
+    },
+    /// The ECC word stored in a frame does not match the one computed over its
+    /// contents.  The frame is identified by its family-specific address.
+    EccMismatch {
+        frame: String,
+        expected: u32,
+        actual: u32,
+    },
+    /// A frame address that does not exist on the device.
+    InvalidFrameAddress { offset: usize, far: u32 },
+    /// The input violates the bitstream format in some other way.
+    Malformed { offset: usize, reason: String },
+    /// The input is valid, but uses a feature the parser does not handle.
+    Unsupported { offset: usize, reason: String },
+}
+
+impl Display for BitstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitstreamError::Truncated { offset } => {
+                write!(f, "bitstream truncated (structure at {offset:#x})")
+            }
+            BitstreamError::InvalidWord { offset, word } => {
+                write!(f, "invalid word {word:08x} at {offset:#x}")
+            }
+            BitstreamError::UnknownCommand {
+                offset,
+                opcode,
+                payload: Some(payload),
+            } => write!(
+                f,
+                "unknown command {opcode:02x} (payload {payload:04x}) at {offset:#x}"
+            ),
+            BitstreamError::UnknownCommand {
+                offset,
+                opcode,
+                payload: None,
+            } => write!(f, "unknown command {opcode:02x} at {offset:#x}"),
+            BitstreamError::UnexpectedPacket {
+                offset,
+                expected,
+                packet: Some(packet),
+            } => write!(f, "expected {expected} at {offset:#x}, got {packet}"),
+            BitstreamError::UnexpectedPacket {
+                offset,
+                expected,
+                packet: None,
+            } => write!(
+                f,
+                "expected {expected} at {offset:#x}, got end of bitstream"
+            ),
+            BitstreamError::CrcMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CRC mismatch at {offset:#x}: expected {expected:08x}, found {actual:08x}"
+            ),
+            BitstreamError::EccMismatch {
+                frame,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "ECC mismatch at frame {frame}: computed {expected:04x} found {actual:04x}"
+            ),
+            BitstreamError::InvalidFrameAddress { offset, far } => {
+                write!(f, "invalid frame address {far:08x} at {offset:#x}")
+            }
+            BitstreamError::Malformed { offset, reason } => {
+                write!(f, "malformed bitstream at {offset:#x}: {reason}")
+            }
+            BitstreamError::Unsupported { offset, reason } => {
+                write!(f, "unsupported bitstream feature at {offset:#x}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BitstreamError {}
+
+/// Returns [`BitstreamError::Truncated`] for the structure at `offset` unless the input is at
+/// least `end` bytes long.
+pub fn check_len(data_len: usize, end: usize, offset: usize) -> Result<(), BitstreamError> {
+    if end > data_len {
+        Err(BitstreamError::Truncated { offset })
+    } else {
+        Ok(())
+    }
+}
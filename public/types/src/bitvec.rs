@@ -279,6 +279,35 @@ mod bincode {
     }
 }
 
+/// Bit vectors are serialized as strings of `0` and `1`, MSB first, matching their
+/// `Display` form.
+#[cfg(feature = "serde")]
+mod serde {
+    use serde::de::{Deserialize, Deserializer, Error};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::BitVec;
+
+    impl Serialize for BitVec {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_str(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for BitVec {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s = String::deserialize(deserializer)?;
+            crate::text::parse_bits(&s).map_err(D::Error::custom)
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! __bit_to_bool {
     (0) => {
@@ -10,7 +10,11 @@ use bincode::{Decode, Encode};
 use itertools::*;
 use prjcombine_entity::id::{EntityIdU16, EntityTag, EntityTagArith};
 
-use crate::bitvec::BitVec;
+use crate::{
+    bitvec::BitVec,
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_bits, parse_id, parse_num, split_assign},
+};
 
 pub struct BitRectTag;
 impl EntityTag for BitRectTag {
@@ -34,6 +38,7 @@ pub type RectFrameId = EntityIdU16<RectFrameTag>;
 pub type RectBitId = EntityIdU16<RectBitTag>;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileBit {
     pub rect: BitRectId,
     pub frame: RectFrameId,
@@ -67,6 +72,7 @@ impl TileBit {
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PolTileBit {
     pub bit: TileBit,
     pub inv: bool,
@@ -92,6 +98,24 @@ impl core::fmt::Debug for PolTileBit {
     }
 }
 
+impl std::str::FromStr for TileBit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('.');
+        let (Some(rect), Some(frame), Some(bit), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("invalid tile bit \"{s}\""));
+        };
+        Ok(TileBit {
+            rect: parse_id(rect, BitRectTag::PREFIX)?,
+            frame: parse_id(frame, RectFrameTag::PREFIX)?,
+            bit: parse_id(bit, RectBitTag::PREFIX)?,
+        })
+    }
+}
+
 impl core::ops::Not for PolTileBit {
     type Output = PolTileBit;
 
@@ -104,6 +128,7 @@ impl core::ops::Not for PolTileBit {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitRectGeometry {
     pub frames: usize,
     pub bits: usize,
@@ -113,18 +138,21 @@ pub struct BitRectGeometry {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FrameOrientation {
     Horizontal,
     Vertical,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EnumData<K: Ord> {
     pub bits: Vec<TileBit>,
     pub values: BTreeMap<K, BitVec>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tile {
     pub items: BTreeMap<String, TileItem>,
 }
@@ -196,9 +224,97 @@ impl Tile {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the tile: items by name, with enum values by name below them.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (key, item) in &self.items {
+            let bits = item
+                .bits
+                .iter()
+                .rev()
+                .map(|bit| format!("{bit:?}"))
+                .join(" ");
+            let node = match item.kind {
+                TileItemKind::Enum { ref values } => {
+                    let mut node = DiffNode::leaf(bits);
+                    for (vkey, val) in values {
+                        node.insert_leaf(vkey, val);
+                    }
+                    node
+                }
+                TileItemKind::BitVec { ref invert } => {
+                    DiffNode::leaf(format!("{bits} inv {invert}"))
+                }
+            };
+            res.insert(key, node);
+        }
+        res
+    }
+
+    /// Parses the body of a tile as written by [`Tile::dump`], up to and including
+    /// the closing `}`.
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Tile::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let Some((key, rest)) = line.text().rsplit_once(':') else {
+                return Err(line.error("expected tile item"));
+            };
+            let mut words = rest.split_whitespace();
+            let mut bits = vec![];
+            let mut invert = None;
+            while let Some(word) = words.next() {
+                if word == "inv" {
+                    invert =
+                        Some(parse_bits(words.next().unwrap_or("")).map_err(|e| line.error(e))?);
+                    break;
+                }
+                bits.push(word.parse().map_err(|e: String| line.error(e))?);
+            }
+            if words.next().is_some() {
+                return Err(line.error("trailing garbage after tile item"));
+            }
+            bits.reverse();
+            let kind = if let Some(invert) = invert {
+                if invert.len() != bits.len() {
+                    return Err(line.error("inversion mask length mismatch"));
+                }
+                TileItemKind::BitVec { invert }
+            } else {
+                let mut values = BTreeMap::new();
+                while let Some(vline) = p.peek()
+                    && vline.indent() > line.indent()
+                    && !vline.is_block_end()
+                {
+                    p.expect_line()?;
+                    let Some((val, vkey)) = vline.text().split_once(':') else {
+                        return Err(vline.error("expected enum value"));
+                    };
+                    let val = parse_bits(val).map_err(|e| vline.error(e))?;
+                    if val.len() != bits.len() {
+                        return Err(vline.error("enum value length mismatch"));
+                    }
+                    if values.insert(vkey.trim().to_string(), val).is_some() {
+                        return Err(vline.error(format!("duplicate enum value {vkey}")));
+                    }
+                }
+                TileItemKind::Enum { values }
+            };
+            if res
+                .items
+                .insert(key.to_string(), TileItem { bits, kind })
+                .is_some()
+            {
+                return Err(line.error(format!("duplicate tile item {key}")));
+            }
+        }
+        Ok(res)
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TileItem {
     pub bits: Vec<TileBit>,
     pub kind: TileItemKind,
@@ -344,12 +460,14 @@ impl From<EnumData<String>> for TileItem {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TileItemKind {
     Enum { values: BTreeMap<String, BitVec> },
     BitVec { invert: BitVec },
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DbValue {
     String(String),
     BitVec(BitVec),
@@ -366,6 +484,23 @@ impl std::fmt::Display for DbValue {
     }
 }
 
+impl std::str::FromStr for DbValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(v) = s.strip_prefix('"') {
+            let v = v
+                .strip_suffix('"')
+                .ok_or_else(|| format!("unterminated string {s}"))?;
+            Ok(DbValue::String(v.to_string()))
+        } else if let Some(v) = s.strip_prefix("0b") {
+            Ok(DbValue::BitVec(parse_bits(v)?))
+        } else {
+            Ok(DbValue::Int(parse_num(s)?))
+        }
+    }
+}
+
 impl From<BitVec> for DbValue {
     fn from(value: BitVec) -> Self {
         Self::BitVec(value)
@@ -391,6 +526,7 @@ impl From<u32> for DbValue {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BsData {
     pub tiles: BTreeMap<String, Tile>,
     pub device_data: BTreeMap<String, BTreeMap<String, DbValue>>,
@@ -498,6 +634,71 @@ impl BsData {
         }
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (tname, tile) in &self.tiles {
+            res.insert(format!("bstile {tname}"), tile.diff_node());
+        }
+        for (name, value) in &self.misc_data {
+            res.insert_leaf(format!("misc_data {name}"), value);
+        }
+        for (name, data) in &self.device_data {
+            let mut node = DiffNode::new();
+            for (name, value) in data {
+                node.insert_leaf(name, value);
+            }
+            res.insert(format!("device_data {name}"), node);
+        }
+        res
+    }
+
+    /// Parses the sections written by [`BsData::dump`].  Stops at the first line that does
+    /// not belong to a bitstream data section.
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = BsData::new();
+        while let Some(line) = p.peek() {
+            match line.block_start() {
+                Some(("bstile", name)) => {
+                    p.expect_line()?;
+                    let tile = Tile::parse(p)?;
+                    if res.tiles.insert(name.to_string(), tile).is_some() {
+                        return Err(line.error(format!("duplicate tile {name}")));
+                    }
+                }
+                Some(("device_data", name)) => {
+                    p.expect_line()?;
+                    let mut data = BTreeMap::new();
+                    while !p.try_block_end() {
+                        let line = p.expect_line()?;
+                        let (key, val) =
+                            split_assign(line.statement()?).map_err(|e| line.error(e))?;
+                        data.insert(key.to_string(), val.parse().map_err(|e| line.error(e))?);
+                    }
+                    res.device_data.insert(name.to_string(), data);
+                }
+                Some(_) => break,
+                None => {
+                    let Ok(("misc_data", stmt)) = line.keyword_statement() else {
+                        break;
+                    };
+                    p.expect_line()?;
+                    let (key, val) = split_assign(stmt).map_err(|e| line.error(e))?;
+                    res.misc_data
+                        .insert(key.to_string(), val.parse().map_err(|e| line.error(e))?);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Reads back a complete text file written by [`BsData::dump`].
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        let res = Self::parse(&mut p)?;
+        p.expect_done()?;
+        Ok(res)
+    }
 }
 
 impl Default for BsData {
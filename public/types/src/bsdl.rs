@@ -0,0 +1,400 @@
+//! A generic writer for BSDL (IEEE 1149.1 Boundary Scan Description Language) files.
+//!
+//! The per-family crates fill a [`Bsdl`] from the package pinout and the boundary scan
+//! register layout returned by their `get_bscan()`, then call [`Bsdl::emit`].
+
+use std::collections::BTreeMap;
+
+use crate::bscan::BScanPad;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortKind {
+    In,
+    Out,
+    InOut,
+    Linkage,
+}
+
+impl std::fmt::Display for PortKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortKind::In => write!(f, "in"),
+            PortKind::Out => write!(f, "out"),
+            PortKind::InOut => write!(f, "inout"),
+            PortKind::Linkage => write!(f, "linkage"),
+        }
+    }
+}
+
+/// The register connected between TDI and TDO by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionRegister {
+    /// One of the instructions defined by the standard, with implied register access
+    /// (`EXTEST`, `SAMPLE`, `PRELOAD`, `IDCODE`, `BYPASS`).
+    Standard,
+    Boundary,
+    Bypass,
+    DeviceId,
+    /// A vendor-specific instruction that must not be used by board test tools.
+    Private,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub name: String,
+    pub opcode: u64,
+    pub register: InstructionRegister,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellFunction {
+    Input,
+    Output2,
+    Output3,
+    Control,
+    Bidir,
+    Internal,
+}
+
+impl std::fmt::Display for CellFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CellFunction::Input => write!(f, "input"),
+            CellFunction::Output2 => write!(f, "output2"),
+            CellFunction::Output3 => write!(f, "output3"),
+            CellFunction::Control => write!(f, "control"),
+            CellFunction::Bidir => write!(f, "bidir"),
+            CellFunction::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub kind: &'static str,
+    pub port: Option<String>,
+    pub function: CellFunction,
+    /// The safe value of the cell; `None` is emitted as `X`.
+    pub safe: Option<bool>,
+    /// The control cell and the value of it that disables the output.
+    pub control: Option<(usize, bool)>,
+}
+
+impl Cell {
+    pub const INTERNAL: Cell = Cell {
+        kind: "BC_1",
+        port: None,
+        function: CellFunction::Internal,
+        safe: None,
+        control: None,
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct Bsdl {
+    pub entity: String,
+    pub package: String,
+    /// Port name to kind and list of package pins.  Ports with more than one pin are
+    /// emitted as `bit_vector`s.
+    pub ports: BTreeMap<String, (PortKind, Vec<String>)>,
+    pub ir_len: usize,
+    pub instructions: Vec<Instruction>,
+    /// The device IDCODE and a mask of the bits that are to be compared.
+    pub idcode: Option<(u32, u32)>,
+    pub cells: Vec<Cell>,
+}
+
+/// Turns an arbitrary name into a valid VHDL identifier.
+pub fn port_name(name: &str) -> String {
+    let mut res = String::new();
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c == '_' && (res.is_empty() || res.ends_with('_')) {
+            continue;
+        }
+        res.push(c.to_ascii_uppercase());
+    }
+    while res.ends_with('_') {
+        res.pop();
+    }
+    if !res.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        res.insert(0, 'P');
+    }
+    res
+}
+
+impl Bsdl {
+    pub fn new(entity: &str, package: &str, ir_len: usize, bits: usize) -> Self {
+        Self {
+            entity: port_name(entity),
+            package: port_name(package),
+            ports: BTreeMap::new(),
+            ir_len,
+            instructions: vec![],
+            idcode: None,
+            cells: vec![Cell::INTERNAL; bits],
+        }
+    }
+
+    pub fn add_instruction(&mut self, name: &str, opcode: u64, register: InstructionRegister) {
+        self.instructions.push(Instruction {
+            name: name.to_string(),
+            opcode,
+            register,
+        });
+    }
+
+    /// Adds a package pin to a port.  Pins added with the same port name are grouped
+    /// into a `bit_vector`.
+    pub fn add_pin(&mut self, port: &str, kind: PortKind, pin: &str) {
+        let entry = self
+            .ports
+            .entry(port_name(port))
+            .or_insert_with(|| (kind, vec![]));
+        assert_eq!(entry.0, kind);
+        entry.1.push(pin.to_string());
+    }
+
+    /// Adds a package pin connected to the boundary scan register.  Since boundary
+    /// cells must refer to scalar ports, the pin name is appended to the port name
+    /// if it is already taken.
+    pub fn add_pad(&mut self, port: &str, pin: &str, pad: BScanPad) {
+        let mut port = port_name(port);
+        if self.ports.contains_key(&port) {
+            port = port_name(&format!("{port}_{pin}"));
+        }
+        let port = &port[..];
+        let port_cell = |function, control| Cell {
+            kind: "BC_1",
+            port: Some(port.to_string()),
+            function,
+            safe: None,
+            control,
+        };
+        let control_cell = |safe| Cell {
+            kind: "BC_1",
+            port: None,
+            function: CellFunction::Control,
+            safe: Some(safe),
+            control: None,
+        };
+        let kind = match pad {
+            BScanPad::Input(i) => {
+                self.cells[i] = port_cell(CellFunction::Input, None);
+                PortKind::In
+            }
+            BScanPad::Output(o) => {
+                self.cells[o] = port_cell(CellFunction::Output2, None);
+                PortKind::Out
+            }
+            BScanPad::OutputTristate(o, t) => {
+                self.cells[t] = control_cell(true);
+                self.cells[o] = port_cell(CellFunction::Output3, Some((t, true)));
+                PortKind::Out
+            }
+            BScanPad::OutputEnable(o, e) => {
+                self.cells[e] = control_cell(false);
+                self.cells[o] = port_cell(CellFunction::Output3, Some((e, false)));
+                PortKind::Out
+            }
+            BScanPad::InputOutputTristate(i, o, t) => {
+                self.cells[t] = control_cell(true);
+                self.cells[o] = port_cell(CellFunction::Output3, Some((t, true)));
+                self.cells[i] = port_cell(CellFunction::Input, None);
+                PortKind::InOut
+            }
+            BScanPad::InputOutputEnable(i, o, e) => {
+                self.cells[e] = control_cell(false);
+                self.cells[o] = port_cell(CellFunction::Output3, Some((e, false)));
+                self.cells[i] = port_cell(CellFunction::Input, None);
+                PortKind::InOut
+            }
+            BScanPad::BiTristate(b, t) => {
+                self.cells[t] = control_cell(true);
+                self.cells[b] = Cell {
+                    kind: "BC_7",
+                    ..port_cell(CellFunction::Bidir, Some((t, true)))
+                };
+                PortKind::InOut
+            }
+        };
+        self.add_pin(port, kind, pin);
+    }
+
+    pub fn emit(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let ent = &self.entity;
+        let ir_len = self.ir_len;
+        writeln!(
+            o,
+            "-- BSDL description of {ent}, package {pkg}",
+            pkg = self.package
+        )?;
+        writeln!(o)?;
+        writeln!(o, "entity {ent} is")?;
+        writeln!(o)?;
+        writeln!(
+            o,
+            "generic (PHYSICAL_PIN_MAP : string := \"{pkg}\");",
+            pkg = self.package
+        )?;
+        writeln!(o)?;
+        writeln!(o, "port (")?;
+        for (i, (name, (kind, pins))) in self.ports.iter().enumerate() {
+            let sep = if i == self.ports.len() - 1 { "" } else { ";" };
+            if pins.len() == 1 {
+                writeln!(o, "\t{name}: {kind} bit{sep}")?;
+            } else {
+                writeln!(
+                    o,
+                    "\t{name}: {kind} bit_vector (1 to {n}){sep}",
+                    n = pins.len()
+                )?;
+            }
+        }
+        writeln!(o, ");")?;
+        writeln!(o)?;
+        writeln!(o, "use STD_1149_1_2001.all;")?;
+        writeln!(o)?;
+        writeln!(
+            o,
+            "attribute COMPONENT_CONFORMANCE of {ent} : entity is \"STD_1149_1_2001\";"
+        )?;
+        writeln!(
+            o,
+            "attribute PIN_MAP of {ent} : entity is PHYSICAL_PIN_MAP;"
+        )?;
+        writeln!(o)?;
+        writeln!(o, "constant {pkg} : PIN_MAP_STRING :=", pkg = self.package)?;
+        for (i, (name, (_, pins))) in self.ports.iter().enumerate() {
+            let sep = if i == self.ports.len() - 1 { ";" } else { "," };
+            if pins.len() == 1 {
+                writeln!(o, "\t\"{name}:{pin}{sep}\" &", pin = pins[0])?;
+            } else {
+                writeln!(o, "\t\"{name}:({pins}){sep}\" &", pins = pins.join(","))?;
+            }
+        }
+        writeln!(o, "\t\"\";")?;
+        writeln!(o)?;
+        writeln!(o, "attribute TAP_SCAN_IN of TDI : signal is true;")?;
+        writeln!(o, "attribute TAP_SCAN_MODE of TMS : signal is true;")?;
+        writeln!(o, "attribute TAP_SCAN_OUT of TDO : signal is true;")?;
+        writeln!(
+            o,
+            "attribute TAP_SCAN_CLOCK of TCK : signal is (1.0e6, BOTH);"
+        )?;
+        writeln!(o)?;
+        writeln!(
+            o,
+            "attribute INSTRUCTION_LENGTH of {ent} : entity is {ir_len};"
+        )?;
+        writeln!(o, "attribute INSTRUCTION_OPCODE of {ent} : entity is")?;
+        for (i, insn) in self.instructions.iter().enumerate() {
+            let desc = format!(
+                "{name} ({opcode:0ir_len$b})",
+                name = insn.name,
+                opcode = insn.opcode
+            );
+            if i == self.instructions.len() - 1 {
+                writeln!(o, "\t\"{desc}\";")?;
+            } else {
+                writeln!(o, "\t\"{desc},\" &")?;
+            }
+        }
+        writeln!(
+            o,
+            "attribute INSTRUCTION_CAPTURE of {ent} : entity is \"{x}01\";",
+            x = "X".repeat(ir_len - 2)
+        )?;
+        let private: Vec<_> = self
+            .instructions
+            .iter()
+            .filter(|insn| insn.register == InstructionRegister::Private)
+            .map(|insn| insn.name.as_str())
+            .collect();
+        if !private.is_empty() {
+            writeln!(
+                o,
+                "attribute INSTRUCTION_PRIVATE of {ent} : entity is \"{insns}\";",
+                insns = private.join(", ")
+            )?;
+        }
+        writeln!(o)?;
+        if let Some((idcode, mask)) = self.idcode {
+            let bits: String = (0..32)
+                .rev()
+                .map(|i| {
+                    if (mask >> i & 1) == 0 {
+                        'X'
+                    } else if (idcode >> i & 1) != 0 {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                })
+                .collect();
+            writeln!(
+                o,
+                "attribute IDCODE_REGISTER of {ent} : entity is \"{bits}\";"
+            )?;
+            writeln!(o)?;
+        }
+        let mut access: Vec<String> = vec![];
+        for (reg, kind) in [
+            ("BOUNDARY", InstructionRegister::Boundary),
+            ("BYPASS", InstructionRegister::Bypass),
+            ("DEVICE_ID", InstructionRegister::DeviceId),
+        ] {
+            let insns: Vec<_> = self
+                .instructions
+                .iter()
+                .filter(|insn| insn.register == kind)
+                .map(|insn| insn.name.as_str())
+                .collect();
+            if !insns.is_empty() {
+                access.push(format!("{reg} ({insns})", insns = insns.join(", ")));
+            }
+        }
+        if !access.is_empty() {
+            writeln!(o, "attribute REGISTER_ACCESS of {ent} : entity is")?;
+            for (i, item) in access.iter().enumerate() {
+                if i == access.len() - 1 {
+                    writeln!(o, "\t\"{item}\";")?;
+                } else {
+                    writeln!(o, "\t\"{item},\" &")?;
+                }
+            }
+            writeln!(o)?;
+        }
+        writeln!(
+            o,
+            "attribute BOUNDARY_LENGTH of {ent} : entity is {n};",
+            n = self.cells.len()
+        )?;
+        writeln!(o, "attribute BOUNDARY_REGISTER of {ent} : entity is")?;
+        writeln!(o, "\t-- cell, port, function, safe [, ccell, disval, rslt]")?;
+        for (i, cell) in self.cells.iter().enumerate().rev() {
+            let port = cell.port.as_deref().unwrap_or("*");
+            let safe = match cell.safe {
+                None => "X",
+                Some(false) => "0",
+                Some(true) => "1",
+            };
+            let mut desc = format!(
+                "{kind}, {port}, {function}, {safe}",
+                kind = cell.kind,
+                function = cell.function
+            );
+            if let Some((ccell, disval)) = cell.control {
+                desc += &format!(", {ccell}, {disval}, Z", disval = u8::from(disval));
+            }
+            if i == 0 {
+                writeln!(o, "\t\"{i} ({desc})\";")?;
+            } else {
+                writeln!(o, "\t\"{i} ({desc}),\" &")?;
+            }
+        }
+        writeln!(o)?;
+        writeln!(o, "end {ent};")?;
+        Ok(())
+    }
+}
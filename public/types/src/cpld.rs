@@ -6,6 +6,14 @@ use prjcombine_entity::{
     id::{EntityIdU8, EntityTag},
 };
 
+use crate::{
+    diff::DiffNode,
+    text::{parse_id, parse_num},
+};
+
+pub mod design;
+pub mod timing;
+
 pub struct ClusterTag;
 pub struct BlockTag;
 pub struct MacrocellTag;
@@ -35,6 +43,7 @@ pub type ProductTermId = EntityIdU8<ProductTermTag>;
 pub type IpadId = EntityIdU8<IpadTag>;
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MacrocellCoord {
     pub cluster: ClusterId,
     pub block: BlockId,
@@ -63,7 +72,24 @@ impl std::fmt::Display for MacrocellCoord {
     }
 }
 
+impl std::str::FromStr for MacrocellCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid macrocell \"{s}\"");
+        let rest = s.strip_prefix(ClusterTag::PREFIX).ok_or_else(err)?;
+        let (cluster, rest) = rest.split_once(BlockTag::PREFIX).ok_or_else(err)?;
+        let (block, macrocell) = rest.split_once(MacrocellTag::PREFIX).ok_or_else(err)?;
+        Ok(MacrocellCoord {
+            cluster: ClusterId::from_idx(parse_num(cluster)?),
+            block: BlockId::from_idx(parse_num(block)?),
+            macrocell: MacrocellId::from_idx(parse_num(macrocell)?),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoCoord {
     Ipad(IpadId),
     Macrocell(MacrocellCoord),
@@ -83,3 +109,25 @@ impl std::fmt::Display for IoCoord {
         }
     }
 }
+
+impl std::str::FromStr for IoCoord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(mc) = s.strip_prefix("IOB_") {
+            Ok(IoCoord::Macrocell(mc.parse()?))
+        } else {
+            Ok(IoCoord::Ipad(parse_id(s, IpadTag::PREFIX)?))
+        }
+    }
+}
+
+/// Builds the diff tree of a JED tile layout: every bit is keyed by its name and has its
+/// position within the tile as the value.
+pub fn jed_bits_diff_node(bits: &[(String, usize)]) -> DiffNode {
+    let mut res = DiffNode::new();
+    for (pos, (name, idx)) in bits.iter().enumerate() {
+        res.insert_leaf(format!("{name}[{idx}]"), pos);
+    }
+    res
+}
@@ -0,0 +1,310 @@
+//! Technology-independent description of a CPLD design.
+//!
+//! A [`Design`] is a set of named input pins and named macrocells, each macrocell described by
+//! a sum-of-products equation, an optional register, and an optional output pin.  The family
+//! crates (`prjcombine-xc9500`, `prjcombine-xpla3`, `prjcombine-coolrunner2`) provide compilers
+//! that place such a design onto a device, route it, and produce a JED file.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::MacrocellCoord;
+
+/// A signal that can be used as a product term input.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Signal {
+    /// An input-only pin, named by its key in [`Design::inputs`].
+    Input(String),
+    /// The output of a macrocell (registered if it has a register, combinatorial otherwise).
+    Macrocell(String),
+    /// The input buffer of the output pin of a macrocell (for bidirectional pins).
+    Pad(String),
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Signal::Input(name) | Signal::Macrocell(name) => write!(f, "{name}"),
+            Signal::Pad(name) => write!(f, "{name}.PAD"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Literal {
+    pub signal: Signal,
+    pub inv: bool,
+}
+
+impl Literal {
+    pub fn pos(signal: Signal) -> Self {
+        Literal { signal, inv: false }
+    }
+
+    pub fn neg(signal: Signal) -> Self {
+        Literal { signal, inv: true }
+    }
+}
+
+/// An AND of literals.  An empty product term is constant 1.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ProductTerm {
+    pub literals: BTreeSet<Literal>,
+}
+
+impl ProductTerm {
+    pub fn new(literals: impl IntoIterator<Item = Literal>) -> Self {
+        ProductTerm {
+            literals: literals.into_iter().collect(),
+        }
+    }
+
+    /// Returns the literal if this product term consists of exactly one literal.
+    pub fn single(&self) -> Option<&Literal> {
+        if self.literals.len() == 1 {
+            self.literals.first()
+        } else {
+            None
+        }
+    }
+
+    pub fn signals(&self) -> impl Iterator<Item = &Signal> {
+        self.literals.iter().map(|lit| &lit.signal)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Dff,
+    Tff,
+    Latch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Register {
+    pub kind: RegisterKind,
+    /// The clock (or latch gate).  A single literal driven by a global clock pin is routed
+    /// through the global clock network; anything else uses a product term.
+    pub clk: ProductTerm,
+    /// If true, the register is clocked on the falling edge (or the latch is transparent
+    /// when `clk` is low).
+    pub clk_inv: bool,
+    pub ce: Option<ProductTerm>,
+    pub rst: Option<ProductTerm>,
+    pub set: Option<ProductTerm>,
+    pub init: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Slew {
+    #[default]
+    Slow,
+    Fast,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Output {
+    /// The package pin name, or `None` to let the compiler choose.
+    pub pin: Option<String>,
+    /// The output enable, or `None` for an always-enabled output.
+    pub oe: Option<ProductTerm>,
+    pub slew: Slew,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Macrocell {
+    /// The sum of products driving this macrocell.  An empty sum is constant 0.
+    pub sum: Vec<ProductTerm>,
+    /// If true, the sum is inverted before going to the register or output.
+    pub invert: bool,
+    pub reg: Option<Register>,
+    pub output: Option<Output>,
+}
+
+impl Macrocell {
+    /// Iterates over all product terms used by this macrocell, including control terms.
+    pub fn product_terms(&self) -> impl Iterator<Item = &ProductTerm> {
+        let reg_terms = self.reg.iter().flat_map(|reg| {
+            [
+                Some(&reg.clk),
+                reg.ce.as_ref(),
+                reg.rst.as_ref(),
+                reg.set.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+        });
+        let oe_term = self.output.iter().filter_map(|out| out.oe.as_ref());
+        self.sum.iter().chain(reg_terms).chain(oe_term)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Input {
+    /// The package pin name, or `None` to let the compiler choose.
+    pub pin: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Design {
+    pub inputs: BTreeMap<String, Input>,
+    pub macrocells: BTreeMap<String, Macrocell>,
+}
+
+impl Design {
+    /// Checks that all signals referenced by the design exist.
+    pub fn check(&self) -> Result<(), CompileError> {
+        for name in self.inputs.keys() {
+            if self.macrocells.contains_key(name) {
+                return Err(CompileError::DuplicateName(name.clone()));
+            }
+        }
+        for mc in self.macrocells.values() {
+            for pt in mc.product_terms() {
+                for signal in pt.signals() {
+                    let valid = match signal {
+                        Signal::Input(name) => self.inputs.contains_key(name),
+                        Signal::Macrocell(name) => self.macrocells.contains_key(name),
+                        Signal::Pad(name) => self
+                            .macrocells
+                            .get(name)
+                            .is_some_and(|mc| mc.output.is_some()),
+                    };
+                    if !valid {
+                        return Err(CompileError::UnknownSignal(signal.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the set of signals used as a given kind of control input, as single literals.
+    /// Used by the compilers to prefer placing such inputs on global network pins.
+    pub fn control_signals(&self, kind: ControlKind) -> BTreeSet<&Signal> {
+        let mut res = BTreeSet::new();
+        for mc in self.macrocells.values() {
+            let terms: Vec<&ProductTerm> = match kind {
+                ControlKind::Clock => mc.reg.iter().map(|reg| &reg.clk).collect(),
+                ControlKind::SetReset => mc
+                    .reg
+                    .iter()
+                    .flat_map(|reg| [reg.rst.as_ref(), reg.set.as_ref()])
+                    .flatten()
+                    .collect(),
+                ControlKind::OutputEnable => {
+                    mc.output.iter().filter_map(|out| out.oe.as_ref()).collect()
+                }
+            };
+            for term in terms {
+                if let Some(lit) = term.single() {
+                    res.insert(&lit.signal);
+                }
+            }
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlKind {
+    Clock,
+    SetReset,
+    OutputEnable,
+}
+
+/// The physical resources chosen by a compiler.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Placement {
+    /// Maps input and output signal names to package pin names.
+    pub pins: BTreeMap<String, String>,
+    /// Maps macrocell names to their physical macrocells.
+    pub macrocells: BTreeMap<String, MacrocellCoord>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompileError {
+    UnknownDevice(String),
+    UnknownPackage(String),
+    DuplicateName(String),
+    UnknownSignal(String),
+    UnknownPin(String),
+    // the pin is not a general-purpose I/O usable by the design
+    PinNotUsable(String),
+    PinConflict(String),
+    NoFreePin(String),
+    NoFreeMacrocell(String),
+    ProductTermsExhausted(String),
+    ControlTermsExhausted(String),
+    RoutingFailed(String),
+    Unsupported {
+        signal: String,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::UnknownDevice(dev) => write!(f, "unknown device {dev}"),
+            CompileError::UnknownPackage(pkg) => write!(f, "unknown package {pkg}"),
+            CompileError::DuplicateName(name) => {
+                write!(f, "{name} is both an input and a macrocell")
+            }
+            CompileError::UnknownSignal(name) => write!(f, "unknown signal {name}"),
+            CompileError::UnknownPin(pin) => write!(f, "unknown pin {pin}"),
+            CompileError::PinNotUsable(pin) => write!(f, "pin {pin} is not a usable I/O pin"),
+            CompileError::PinConflict(pin) => write!(f, "pin {pin} is assigned more than once"),
+            CompileError::NoFreePin(name) => write!(f, "no free pin for {name}"),
+            CompileError::NoFreeMacrocell(name) => write!(f, "no free macrocell for {name}"),
+            CompileError::ProductTermsExhausted(name) => {
+                write!(f, "not enough product terms for {name}")
+            }
+            CompileError::ControlTermsExhausted(name) => {
+                write!(f, "not enough control terms for {name}")
+            }
+            CompileError::RoutingFailed(block) => write!(f, "cannot route inputs of {block}"),
+            CompileError::Unsupported { signal, reason } => {
+                write!(f, "{signal}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Assigns each item a distinct slot from its list of candidates (bipartite matching).
+/// Returns the chosen slot for every item, or `None` if no complete assignment exists.
+pub fn assign_slots(candidates: &[Vec<usize>], num_slots: usize) -> Option<Vec<usize>> {
+    fn augment(
+        item: usize,
+        candidates: &[Vec<usize>],
+        slot_item: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for &slot in &candidates[item] {
+            if visited[slot] {
+                continue;
+            }
+            visited[slot] = true;
+            if slot_item[slot].is_none_or(|other| augment(other, candidates, slot_item, visited)) {
+                slot_item[slot] = Some(item);
+                return true;
+            }
+        }
+        false
+    }
+    let mut slot_item = vec![None; num_slots];
+    for item in 0..candidates.len() {
+        let mut visited = vec![false; num_slots];
+        if !augment(item, candidates, &mut slot_item, &mut visited) {
+            return None;
+        }
+    }
+    let mut res = vec![0; candidates.len()];
+    for (slot, item) in slot_item.into_iter().enumerate() {
+        if let Some(item) = item {
+            res[item] = slot;
+        }
+    }
+    Some(res)
+}
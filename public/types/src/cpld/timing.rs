@@ -0,0 +1,132 @@
+//! Timing graph construction shared by the CPLD families.
+//!
+//! The [`MacrocellTiming`] helper covers the parts of the [`TimingGraph`] that are built
+//! the same way for all families: input pads, macrocell registers, and output buffers.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    speed::{EdgeDelay, Speed, SpeedError},
+    timing::{ClockEdge, TimingGraph, TimingNodeId, TimingNodeKind, TimingRegister},
+};
+
+/// Helpers for the parts of a CPLD timing graph built the same way by all families.
+/// The speed values are looked up under the names shared by the CPLD speed databases.
+pub struct MacrocellTiming<'a> {
+    pub graph: TimingGraph,
+    pub speed: &'a Speed,
+}
+
+/// The nodes of a macrocell register.
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterNodes {
+    pub clk: TimingNodeId,
+    pub d: TimingNodeId,
+    pub ce: Option<TimingNodeId>,
+    pub sr: TimingNodeId,
+    pub q: TimingNodeId,
+}
+
+impl<'a> MacrocellTiming<'a> {
+    pub fn new(speed: &'a Speed) -> Self {
+        MacrocellTiming {
+            graph: TimingGraph::new(),
+            speed,
+        }
+    }
+
+    pub fn delay(&self, name: &str) -> Result<EdgeDelay, SpeedError> {
+        self.speed.delay(name)
+    }
+
+    /// Adds an input pad node for every bonded pad, named by the first of its package pins.
+    /// Returns the pad nodes and the pin of every pad.
+    pub fn add_input_pads<'p, K: Copy + Ord>(
+        &mut self,
+        pins: impl IntoIterator<Item = (&'p str, K)>,
+        name: impl Fn(K) -> String,
+    ) -> (BTreeMap<K, TimingNodeId>, BTreeMap<K, &'p str>) {
+        let mut pad_pins: BTreeMap<K, &str> = BTreeMap::new();
+        for (pin, pad) in pins {
+            pad_pins.entry(pad).or_insert(pin);
+        }
+        let pads = pad_pins
+            .iter()
+            .map(|(&pad, &pin)| {
+                let node = self
+                    .graph
+                    .add_node(name(pad), TimingNodeKind::Input(pin.into()));
+                (pad, node)
+            })
+            .collect();
+        (pads, pad_pins)
+    }
+
+    /// Adds a macrocell register, using `DEL_CLK_Q` and `DEL_SR_Q` for the outputs,
+    /// the given setup-hold value for the data input, `SETUPHOLD_CE_CLK` for the clock
+    /// enable, `RECREM_SR_CLK` (where the speed data has one) for set and reset, and
+    /// a minimum period of twice the given clock pulse width.
+    pub fn add_register(
+        &mut self,
+        name: String,
+        nodes: RegisterNodes,
+        edge: ClockEdge,
+        setup_hold_d: &str,
+        width_clk: &str,
+    ) -> Result<(), SpeedError> {
+        let mut checks = vec![(nodes.d, self.speed.setup_hold(setup_hold_d)?)];
+        if let Some(ce) = nodes.ce {
+            checks.push((ce, self.speed.setup_hold("SETUPHOLD_CE_CLK")?));
+        }
+        match self.speed.setup_hold("RECREM_SR_CLK") {
+            Ok(recrem) => checks.push((nodes.sr, recrem)),
+            Err(SpeedError::Missing(_)) => (),
+            Err(e) => return Err(e),
+        }
+        let width = self.speed.pulse_width(width_clk)?;
+        // the speed data has a plain clock-to-out delay; launch both output edges from
+        // the active clock edge
+        let clk_to_q = self.speed.delay("DEL_CLK_Q")?;
+        self.graph.add_register(TimingRegister {
+            name,
+            clk: nodes.clk,
+            edge,
+            q: nodes.q,
+            clk_to_q: EdgeDelay {
+                rise_to_rise: clk_to_q.rise_to_rise,
+                rise_to_fall: clk_to_q.fall_to_fall,
+                fall_to_rise: None,
+                fall_to_fall: None,
+            },
+            checks,
+            min_period: width + width,
+        });
+        self.graph
+            .add_edge(nodes.sr, nodes.q, self.speed.delay("DEL_SR_Q")?);
+        Ok(())
+    }
+
+    /// Adds an output pad node driven from `out` through the `DEL_OBUF_FAST` or
+    /// `DEL_OBUF_SLOW` delay (with the given suffix), and from `oe` through the given
+    /// output enable delay.
+    pub fn add_output_pad(
+        &mut self,
+        name: String,
+        pin: &str,
+        out: TimingNodeId,
+        slew_fast: bool,
+        suffix: &str,
+        oe: Option<(TimingNodeId, EdgeDelay)>,
+    ) -> Result<TimingNodeId, SpeedError> {
+        let obuf = self
+            .graph
+            .add_node(name, TimingNodeKind::Output(pin.into()));
+        let slew = if slew_fast { "FAST" } else { "SLOW" };
+        let del_obuf = self.speed.delay(&format!("DEL_OBUF_{slew}{suffix}"))?;
+        self.graph.add_edge(out, obuf, del_obuf);
+        if let Some((oe, del_oe)) = oe {
+            self.graph.add_edge(oe, obuf, del_oe);
+        }
+        Ok(obuf)
+    }
+}
@@ -1,5 +1,10 @@
 use bincode::{Decode, Encode};
-use prjcombine_entity::id::{EntityIdU8, EntityIdU16, EntityTag};
+use prjcombine_entity::{
+    EntityMap, EntityVec,
+    id::{EntityIdU8, EntityIdU16, EntityTag},
+};
+
+use crate::text::{parse_id, split_assign};
 
 pub struct ChipTag;
 pub struct SpeedTag;
@@ -35,11 +40,56 @@ pub type DevBondId = EntityIdU8<DevBondTag>;
 pub type DevSpeedId = EntityIdU8<DevSpeedTag>;
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceCombo {
     pub devbond: DevBondId,
     pub speed: DevSpeedId,
 }
 
+/// The packages, speed grades and combos of a device, collected from the `bond`, `speed`
+/// and `combo` statements of a `device` block.
+#[derive(Debug, Default)]
+pub struct DeviceCombosParser {
+    pub bonds: EntityMap<DevBondId, String, BondId>,
+    pub speeds: EntityVec<DevSpeedId, String>,
+    pub combos: Vec<DeviceCombo>,
+}
+
+impl DeviceCombosParser {
+    /// Handles a device statement, returning `false` if the keyword is not one of ours.
+    pub fn statement(&mut self, kw: &str, val: &str) -> Result<bool, String> {
+        match kw {
+            "bond" => {
+                let (pkg, bond) = split_assign(val)?;
+                let bond = parse_id(bond, BondTag::PREFIX)?;
+                if self.bonds.insert(pkg.to_string(), bond).1.is_some() {
+                    return Err(format!("duplicate package {pkg}"));
+                }
+            }
+            "speed" => {
+                self.speeds.push(val.to_string());
+            }
+            "combo" => {
+                let (pkg, speed) = val
+                    .split_once(' ')
+                    .ok_or_else(|| format!("invalid combo \"{val}\""))?;
+                let (devbond, _) = self
+                    .bonds
+                    .get(pkg)
+                    .ok_or_else(|| format!("unknown package {pkg}"))?;
+                let (speed, _) = self
+                    .speeds
+                    .iter()
+                    .find(|&(_, name)| name == speed)
+                    .ok_or_else(|| format!("unknown speed {speed}"))?;
+                self.combos.push(DeviceCombo { devbond, speed });
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct DumpFlags {
     pub intdb: bool,
@@ -0,0 +1,154 @@
+//! Semantic comparison of databases.
+//!
+//! Every database type can describe itself as a tree of named entries (devices, packages,
+//! tile classes, bel classes, switchbox items keyed by the wires they drive, bitstream tile
+//! items, enum values, bond pins, speed values, …).  Two databases are compared by walking
+//! their trees by name.  This makes the result independent of the order and numbering of
+//! the underlying entities, and reports changes at the level of the objects that actually
+//! changed.
+
+use std::collections::BTreeMap;
+
+/// A node of a name-keyed database tree.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DiffNode {
+    pub value: Option<String>,
+    pub children: BTreeMap<String, DiffNode>,
+}
+
+impl DiffNode {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A node with a value; children can still be added to it.
+    pub fn leaf(value: impl ToString) -> Self {
+        DiffNode {
+            value: Some(value.to_string()),
+            children: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a child node.  Keys identify the object within its parent, so they should be
+    /// unique; if the key is already taken anyway, the node is added numbered as by
+    /// [`insert_numbered`](Self::insert_numbered), so that both objects still show up in
+    /// the diff.
+    pub fn insert(&mut self, key: impl Into<String>, node: DiffNode) {
+        self.insert_numbered(&key.into(), node);
+    }
+
+    pub fn insert_leaf(&mut self, key: impl Into<String>, value: impl ToString) {
+        self.insert(key, DiffNode::leaf(value));
+    }
+
+    /// Adds a child node for an object without a unique name.  If the key is already
+    /// taken, ` #2`, ` #3`, … is appended to it.
+    pub fn insert_numbered(&mut self, key: &str, node: DiffNode) {
+        let mut numbered = key.to_string();
+        let mut idx = 1;
+        while self.children.contains_key(&numbered) {
+            idx += 1;
+            numbered = format!("{key} #{idx}");
+        }
+        self.children.insert(numbered, node);
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DiffChange {
+    Added(Option<String>),
+    Removed(Option<String>),
+    Changed(Option<String>, Option<String>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DiffEntry {
+    pub path: Vec<String>,
+    pub change: DiffChange,
+}
+
+/// The result of comparing two databases.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DbDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+impl DbDiff {
+    pub fn new(old: &DiffNode, new: &DiffNode) -> Self {
+        let mut res = DbDiff::default();
+        res.walk(&mut vec![], old, new);
+        res
+    }
+
+    fn walk(&mut self, path: &mut Vec<String>, old: &DiffNode, new: &DiffNode) {
+        if old.value != new.value {
+            self.entries.push(DiffEntry {
+                path: path.clone(),
+                change: DiffChange::Changed(old.value.clone(), new.value.clone()),
+            });
+        }
+        for (key, onode) in &old.children {
+            path.push(key.clone());
+            match new.children.get(key) {
+                Some(nnode) => self.walk(path, onode, nnode),
+                None => self.entries.push(DiffEntry {
+                    path: path.clone(),
+                    change: DiffChange::Removed(onode.value.clone()),
+                }),
+            }
+            path.pop();
+        }
+        for (key, nnode) in &new.children {
+            if !old.children.contains_key(key) {
+                path.push(key.clone());
+                self.entries.push(DiffEntry {
+                    path: path.clone(),
+                    change: DiffChange::Added(nnode.value.clone()),
+                });
+                path.pop();
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes a human-readable report, one change per line.
+    pub fn write_text(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        for entry in &self.entries {
+            let path = entry.path.join(" / ");
+            match entry.change {
+                DiffChange::Added(ref val) => {
+                    added += 1;
+                    match val {
+                        Some(val) => writeln!(o, "+ {path} = {val}")?,
+                        None => writeln!(o, "+ {path}")?,
+                    }
+                }
+                DiffChange::Removed(ref val) => {
+                    removed += 1;
+                    match val {
+                        Some(val) => writeln!(o, "- {path} = {val}")?,
+                        None => writeln!(o, "- {path}")?,
+                    }
+                }
+                DiffChange::Changed(ref old, ref new) => {
+                    changed += 1;
+                    writeln!(
+                        o,
+                        "~ {path}: {old} -> {new}",
+                        old = old.as_deref().unwrap_or("(none)"),
+                        new = new.as_deref().unwrap_or("(none)"),
+                    )?;
+                }
+            }
+        }
+        writeln!(o, "{added} added, {removed} removed, {changed} changed")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! KiCad schematic symbol generation.
+//!
+//! A [`KicadSymbol`] is built from a package [`Pinout`], with one unit per IO bank
+//! and further units for transceiver, configuration, JTAG and power pins, and is
+//! written out as a KiCad 7 `.kicad_sym` library.
+
+use std::collections::HashMap;
+
+use crate::pinout::{PinKind, Pinout, pin_sort_key};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KicadPinType {
+    Input,
+    Output,
+    Bidirectional,
+    PowerIn,
+    Passive,
+    NoConnect,
+    Unspecified,
+}
+
+impl std::fmt::Display for KicadPinType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KicadPinType::Input => write!(f, "input"),
+            KicadPinType::Output => write!(f, "output"),
+            KicadPinType::Bidirectional => write!(f, "bidirectional"),
+            KicadPinType::PowerIn => write!(f, "power_in"),
+            KicadPinType::Passive => write!(f, "passive"),
+            KicadPinType::NoConnect => write!(f, "no_connect"),
+            KicadPinType::Unspecified => write!(f, "unspecified"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KicadPin {
+    pub number: String,
+    pub name: String,
+    pub typ: KicadPinType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KicadUnit {
+    pub name: String,
+    /// Pins drawn on the left side of the body, top to bottom.
+    pub pins_left: Vec<KicadPin>,
+    /// Pins drawn on the right side of the body, top to bottom.
+    pub pins_right: Vec<KicadPin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KicadSymbol {
+    pub name: String,
+    pub description: String,
+    pub units: Vec<KicadUnit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum UnitKind {
+    Io,
+    Ps,
+    Gt,
+    Config,
+    Jtag,
+    SysMon,
+    Power,
+    Other,
+}
+
+const JTAG_PINS: &[&str] = &["TCK", "TMS", "TDI", "TDO", "TRST"];
+
+// all dimensions are in units of 1.27mm, the KiCad schematic grid
+const PIN_LENGTH: i32 = 4;
+const PIN_PITCH: i32 = 2;
+
+fn is_jtag(name: &str) -> bool {
+    JTAG_PINS.contains(&name.split('_').next().unwrap())
+}
+
+fn pin_type(kind: PinKind, name: &str) -> KicadPinType {
+    match kind {
+        PinKind::Io | PinKind::Ps => KicadPinType::Bidirectional,
+        PinKind::Config if is_jtag(name) => {
+            if name.starts_with("TDO") {
+                KicadPinType::Output
+            } else {
+                KicadPinType::Input
+            }
+        }
+        PinKind::Config => KicadPinType::Bidirectional,
+        PinKind::Gt => {
+            if name.contains("TXP") || name.contains("TXN") {
+                KicadPinType::Output
+            } else if name.contains("RXP") || name.contains("RXN") || name.contains("REFCLK") {
+                KicadPinType::Input
+            } else if name.contains("VCC") || name.contains("VTT") || name.contains("GND") {
+                KicadPinType::PowerIn
+            } else {
+                KicadPinType::Passive
+            }
+        }
+        PinKind::SysMon => KicadPinType::Passive,
+        PinKind::Power | PinKind::Ground => KicadPinType::PowerIn,
+        PinKind::Nc => KicadPinType::NoConnect,
+        PinKind::Other => KicadPinType::Unspecified,
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn coord(v: i32) -> String {
+    let v = v * 127;
+    let sign = if v < 0 { "-" } else { "" };
+    let v = v.abs();
+    format!("{sign}{int}.{frac:02}", int = v / 100, frac = v % 100)
+}
+
+impl KicadUnit {
+    /// Returns the half width, top and bottom of the unit body.
+    fn body(&self) -> (i32, i32, i32) {
+        let max_name = |pins: &[KicadPin]| {
+            pins.iter()
+                .map(|pin| pin.name.chars().count() as i32)
+                .max()
+                .unwrap_or(0)
+        };
+        // a name character is about one grid unit wide
+        let half_width = ((max_name(&self.pins_left) + max_name(&self.pins_right) + 5) / 2).max(4);
+        // keep the pins on the 2.54mm grid
+        let half_width = half_width + half_width % 2;
+        let rows = self.pins_left.len().max(self.pins_right.len()) as i32;
+        let top = PIN_PITCH * (rows / 2 + 1);
+        let bottom = top - PIN_PITCH * (rows + 1);
+        (half_width, top, bottom)
+    }
+}
+
+impl KicadSymbol {
+    /// Groups the pins of a pinout into units: one per IO, PS and transceiver bank,
+    /// then configuration, JTAG, system monitor, power and everything else.  Power
+    /// units have the supply pins on the left and the ground pins on the right;
+    /// other units are split in half.
+    pub fn from_pinout(pinout: &Pinout) -> Self {
+        let mut groups: HashMap<(UnitKind, Option<&str>), Vec<_>> = HashMap::new();
+        for pin in &pinout.pins {
+            let kind = match pin.kind {
+                PinKind::Io => UnitKind::Io,
+                PinKind::Ps => UnitKind::Ps,
+                PinKind::Gt => UnitKind::Gt,
+                PinKind::Config if is_jtag(&pin.name) => UnitKind::Jtag,
+                PinKind::Config => UnitKind::Config,
+                PinKind::SysMon => UnitKind::SysMon,
+                PinKind::Power | PinKind::Ground => UnitKind::Power,
+                PinKind::Nc | PinKind::Other => UnitKind::Other,
+            };
+            let bank = match kind {
+                UnitKind::Io | UnitKind::Ps | UnitKind::Gt => pin.bank.as_deref(),
+                _ => None,
+            };
+            groups.entry((kind, bank)).or_default().push(pin);
+        }
+        let mut groups = Vec::from_iter(groups);
+        groups.sort_by(|((ka, ba), _), ((kb, bb), _)| {
+            (ka, ba.map(pin_sort_key)).cmp(&(kb, bb.map(pin_sort_key)))
+        });
+        let mut units = vec![];
+        for ((kind, bank), mut pins) in groups {
+            pins.sort_by(|a, b| {
+                (pin_sort_key(&a.name), pin_sort_key(&a.pin))
+                    .cmp(&(pin_sort_key(&b.name), pin_sort_key(&b.pin)))
+            });
+            let pins = Vec::from_iter(pins.into_iter().map(|pin| {
+                (
+                    pin.kind,
+                    KicadPin {
+                        number: pin.pin.clone(),
+                        name: pin.name.clone(),
+                        typ: pin_type(pin.kind, &pin.name),
+                    },
+                )
+            }));
+            let (pins_left, pins_right) = if kind == UnitKind::Power {
+                let (left, right): (Vec<_>, Vec<_>) = pins
+                    .into_iter()
+                    .partition(|(pkind, _)| *pkind == PinKind::Power);
+                (
+                    left.into_iter().map(|(_, pin)| pin).collect(),
+                    right.into_iter().map(|(_, pin)| pin).collect(),
+                )
+            } else {
+                let mut left = Vec::from_iter(pins.into_iter().map(|(_, pin)| pin));
+                let right = left.split_off(left.len().div_ceil(2));
+                (left, right)
+            };
+            let name = match (kind, bank) {
+                (UnitKind::Io, Some(bank)) => format!("BANK {bank}"),
+                (UnitKind::Io, None) => "IO".to_string(),
+                (UnitKind::Ps, Some(bank)) => format!("PS BANK {bank}"),
+                (UnitKind::Ps, None) => "PS".to_string(),
+                (UnitKind::Gt, Some(bank)) => format!("GT BANK {bank}"),
+                (UnitKind::Gt, None) => "GT".to_string(),
+                (UnitKind::Config, _) => "CONFIG".to_string(),
+                (UnitKind::Jtag, _) => "JTAG".to_string(),
+                (UnitKind::SysMon, _) => "SYSMON".to_string(),
+                (UnitKind::Power, _) => "POWER".to_string(),
+                (UnitKind::Other, _) => "OTHER".to_string(),
+            };
+            units.push(KicadUnit {
+                name,
+                pins_left,
+                pins_right,
+            });
+        }
+        let name = format!(
+            "{device}_{package}",
+            device = pinout.device,
+            package = pinout.package
+        )
+        .replace([':', '/', ' '], "_");
+        KicadSymbol {
+            name,
+            description: format!(
+                "{device} in {package} package",
+                device = pinout.device,
+                package = pinout.package
+            ),
+            units,
+        }
+    }
+
+    fn emit_symbol(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
+        let name = &self.name;
+        writeln!(
+            o,
+            "  (symbol {name} (in_bom yes) (on_board yes)",
+            name = quote(name)
+        )?;
+        let top = self
+            .units
+            .iter()
+            .map(|unit| unit.body().1)
+            .max()
+            .unwrap_or(0);
+        let bottom = self
+            .units
+            .iter()
+            .map(|unit| unit.body().2)
+            .min()
+            .unwrap_or(0);
+        for (prop, value, y, hide) in [
+            ("Reference", "U", top + 1, false),
+            ("Value", name.as_str(), bottom - 1, false),
+            ("Footprint", "", bottom - 3, true),
+            ("Datasheet", "", bottom - 3, true),
+            (
+                "ki_description",
+                self.description.as_str(),
+                bottom - 3,
+                true,
+            ),
+        ] {
+            writeln!(
+                o,
+                "    (property {prop} {value} (at 0 {y} 0) (effects (font (size 1.27 1.27)){hide}))",
+                prop = quote(prop),
+                value = quote(value),
+                y = coord(y),
+                hide = if hide { " hide" } else { "" }
+            )?;
+        }
+        for (uidx, unit) in self.units.iter().enumerate() {
+            let (half_width, top, bottom) = unit.body();
+            writeln!(
+                o,
+                "    (symbol {sub} (unit_name {uname})",
+                sub = quote(&format!("{name}_{unit}_1", unit = uidx + 1)),
+                uname = quote(&unit.name)
+            )?;
+            writeln!(
+                o,
+                "      (rectangle (start {x0} {top}) (end {x1} {bottom}) (stroke (width 0.254) (type default)) (fill (type background)))",
+                x0 = coord(-half_width),
+                x1 = coord(half_width),
+                top = coord(top),
+                bottom = coord(bottom),
+            )?;
+            for (pins, x, angle) in [
+                (&unit.pins_left, -half_width - PIN_LENGTH, 0),
+                (&unit.pins_right, half_width + PIN_LENGTH, 180),
+            ] {
+                for (i, pin) in pins.iter().enumerate() {
+                    let y = top - PIN_PITCH * (i as i32 + 1);
+                    writeln!(
+                        o,
+                        "      (pin {typ} line (at {x} {y} {angle}) (length {len}) (name {pname} (effects (font (size 1.27 1.27)))) (number {number} (effects (font (size 1.27 1.27)))))",
+                        typ = pin.typ,
+                        x = coord(x),
+                        y = coord(y),
+                        len = coord(PIN_LENGTH),
+                        pname = quote(&pin.name),
+                        number = quote(&pin.number),
+                    )?;
+                }
+            }
+            writeln!(o, "    )")?;
+        }
+        writeln!(o, "  )")?;
+        Ok(())
+    }
+}
+
+/// Writes a symbol library containing the given symbols.
+pub fn emit_library(o: &mut dyn std::io::Write, symbols: &[KicadSymbol]) -> std::io::Result<()> {
+    writeln!(
+        o,
+        "(kicad_symbol_lib (version 20220914) (generator prjcombine)"
+    )?;
+    for symbol in symbols {
+        symbol.emit_symbol(o)?;
+    }
+    writeln!(o, ")")?;
+    Ok(())
+}
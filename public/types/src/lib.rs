@@ -1,9 +1,18 @@
 pub mod bimap;
 pub mod bitrect;
+pub mod bitstream;
 pub mod bitvec;
 pub mod bscan;
 pub mod bsdata;
+pub mod bsdl;
 pub mod cpld;
 pub mod db;
+pub mod diff;
+pub mod kicad;
+pub mod pinout;
+#[cfg(feature = "serde")]
+pub mod serde_util;
 pub mod speed;
+pub mod text;
+pub mod timing;
 pub mod units;
@@ -0,0 +1,319 @@
+//! Family-independent package pinout tables.
+//!
+//! The per-family crates fill a [`Pinout`] from a package bond and the chip's IO
+//! bank and special function information.  The table can then be written out as CSV
+//! (or serialized with serde), or turned into a skeleton constraint file in one of
+//! the vendor formats.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PinKind {
+    /// A user IO pin, possibly with a dual-purpose function.
+    Io,
+    /// A processing system IO pin; these are not placed by fabric constraints.
+    Ps,
+    /// A dedicated configuration or JTAG pin.
+    Config,
+    /// A multi-gigabit transceiver pin.
+    Gt,
+    /// A dedicated system monitor / ADC pin.
+    SysMon,
+    Power,
+    Ground,
+    Nc,
+    Other,
+}
+
+impl std::fmt::Display for PinKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PinKind::Io => write!(f, "io"),
+            PinKind::Ps => write!(f, "ps"),
+            PinKind::Config => write!(f, "config"),
+            PinKind::Gt => write!(f, "gt"),
+            PinKind::SysMon => write!(f, "sysmon"),
+            PinKind::Power => write!(f, "power"),
+            PinKind::Ground => write!(f, "ground"),
+            PinKind::Nc => write!(f, "nc"),
+            PinKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiffPolarity {
+    P,
+    N,
+}
+
+impl std::fmt::Display for DiffPolarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffPolarity::P => write!(f, "P"),
+            DiffPolarity::N => write!(f, "N"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PinoutPin {
+    pub pin: String,
+    /// The family-specific pad the pin is bonded to, as shown in the database dump.
+    pub pad: String,
+    /// The vendor-style pin function name (`IO_L12P_T1_34`, `VCCO_34`, …).  Filled in
+    /// by [`Pinout::finish`] from the other fields unless the family provides one;
+    /// the filled-in IO names (`IO_GCLK0_1`) leave out the `L<n>P`/`L<n>N` pair
+    /// number, which the databases do not record, and the polarity is only
+    /// given by [`diff`](Self::diff).
+    pub name: String,
+    pub kind: PinKind,
+    pub bank: Option<String>,
+    /// The IO coordinate, for IO pins.
+    pub io: Option<String>,
+    pub diff: Option<DiffPolarity>,
+    /// The package pin of the other half of the differential pair, if it is bonded.
+    pub diff_pin: Option<String>,
+    /// Special functions of the pin (VREF, VRP/VRN, configuration, clock inputs, …).
+    pub functions: Vec<String>,
+}
+
+impl PinoutPin {
+    pub fn new(pin: &str, pad: impl std::fmt::Display, kind: PinKind) -> Self {
+        Self {
+            pin: pin.to_string(),
+            pad: pad.to_string(),
+            name: String::new(),
+            kind,
+            bank: None,
+            io: None,
+            diff: None,
+            diff_pin: None,
+            functions: vec![],
+        }
+    }
+
+    pub fn with_name(mut self, name: impl std::fmt::Display) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_bank(mut self, bank: impl std::fmt::Display) -> Self {
+        self.bank = Some(bank.to_string());
+        self
+    }
+
+    pub fn with_io(mut self, io: impl std::fmt::Display) -> Self {
+        self.io = Some(io.to_string());
+        self
+    }
+
+    pub fn with_function(mut self, function: impl std::fmt::Display) -> Self {
+        self.functions.push(function.to_string());
+        self
+    }
+
+    fn default_name(&self) -> String {
+        if self.kind == PinKind::Io && self.io.is_some() {
+            let mut parts = vec!["IO".to_string()];
+            parts.extend(self.functions.iter().cloned());
+            parts.extend(self.bank.iter().cloned());
+            parts.join("_")
+        } else if let Some(bank) = &self.bank
+            && let Some(prefix) = self.pad.strip_suffix(bank.as_str())
+            && prefix.ends_with(|c: char| c.is_ascii_alphabetic())
+        {
+            // split the bank number off the pad name (`VCCO34` → `VCCO_34`)
+            format!("{prefix}_{bank}")
+        } else {
+            self.pad.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintFormat {
+    /// Xilinx ISE user constraints.
+    Ucf,
+    /// Xilinx Vivado design constraints.
+    Xdc,
+    /// iCEcube2 / nextpnr physical constraints.
+    Pcf,
+    /// Lattice Diamond logical preferences.
+    Lpf,
+}
+
+impl std::fmt::Display for ConstraintFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintFormat::Ucf => write!(f, "ucf"),
+            ConstraintFormat::Xdc => write!(f, "xdc"),
+            ConstraintFormat::Pcf => write!(f, "pcf"),
+            ConstraintFormat::Lpf => write!(f, "lpf"),
+        }
+    }
+}
+
+impl std::str::FromStr for ConstraintFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ucf" => Ok(ConstraintFormat::Ucf),
+            "xdc" => Ok(ConstraintFormat::Xdc),
+            "pcf" => Ok(ConstraintFormat::Pcf),
+            "lpf" => Ok(ConstraintFormat::Lpf),
+            _ => Err(format!("unknown constraint format {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pinout {
+    pub device: String,
+    pub package: String,
+    pub pins: Vec<PinoutPin>,
+}
+
+/// Orders pin names naturally, so that `A2` sorts before `A10` and `AA1` after `Y9`.
+pub fn pin_sort_key(name: &str) -> (usize, &str, u64, &str) {
+    let pos = name
+        .find(|x: char| x.is_ascii_digit())
+        .unwrap_or(name.len());
+    let end = name[pos..]
+        .find(|x: char| !x.is_ascii_digit())
+        .map_or(name.len(), |x| pos + x);
+    let num = name[pos..end].parse().unwrap_or(0);
+    (pos, &name[..pos], num, &name[end..])
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// The port name used for an IO pin in the skeleton constraint files.
+fn port_name(pin: &str) -> String {
+    let mut res = "io_".to_string();
+    for c in pin.chars() {
+        res.push(if c.is_ascii_alphanumeric() { c } else { '_' });
+    }
+    res
+}
+
+impl Pinout {
+    pub fn new(device: &str, package: &str) -> Self {
+        Self {
+            device: device.to_string(),
+            package: package.to_string(),
+            pins: vec![],
+        }
+    }
+
+    pub fn add_pin(&mut self, pin: PinoutPin) {
+        self.pins.push(pin);
+    }
+
+    /// Sorts the pins naturally by name and fills in `diff_pin` from the IO
+    /// coordinates of the differential partners.  `partners` maps the IO coordinate
+    /// of a pin to the IO coordinate of the other half of its pair; pins whose
+    /// partner is not bonded lose their polarity.  Pins without a name get one
+    /// derived from their kind, functions and bank.
+    pub fn finish(&mut self, partners: &HashMap<String, String>) {
+        self.pins
+            .sort_by(|a, b| pin_sort_key(&a.pin).cmp(&pin_sort_key(&b.pin)));
+        let io_pins: HashMap<String, String> = self
+            .pins
+            .iter()
+            .filter_map(|pin| Some((pin.io.clone()?, pin.pin.clone())))
+            .collect();
+        for pin in &mut self.pins {
+            if pin.diff.is_none() {
+                continue;
+            }
+            if let Some(io) = &pin.io
+                && let Some(partner) = partners.get(io)
+            {
+                pin.diff_pin = io_pins.get(partner).cloned();
+                if pin.diff_pin.is_none() {
+                    // the other half of the pair is not bonded in this package
+                    pin.diff = None;
+                }
+            }
+        }
+        for pin in &mut self.pins {
+            if pin.name.is_empty() {
+                pin.name = pin.default_name();
+            }
+        }
+    }
+
+    pub fn emit_csv(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
+        writeln!(o, "pin,pad,name,kind,bank,io,diff,diff_pin,functions")?;
+        for pin in &self.pins {
+            writeln!(
+                o,
+                "{pin},{pad},{name},{kind},{bank},{io},{diff},{diff_pin},{functions}",
+                pin = csv_field(&pin.pin),
+                pad = csv_field(&pin.pad),
+                name = csv_field(&pin.name),
+                kind = pin.kind,
+                bank = csv_field(pin.bank.as_deref().unwrap_or("")),
+                io = csv_field(pin.io.as_deref().unwrap_or("")),
+                diff = pin.diff.map(|x| x.to_string()).unwrap_or_default(),
+                diff_pin = csv_field(pin.diff_pin.as_deref().unwrap_or("")),
+                functions = csv_field(&pin.functions.join(" ")),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes a constraint file with a commented-out location constraint for every
+    /// user IO pin, to be uncommented and renamed as needed.
+    pub fn emit_constraints(
+        &self,
+        o: &mut dyn std::io::Write,
+        format: ConstraintFormat,
+    ) -> std::io::Result<()> {
+        writeln!(
+            o,
+            "# {device} {package} pin constraints",
+            device = self.device,
+            package = self.package
+        )?;
+        writeln!(o)?;
+        for pin in &self.pins {
+            if pin.kind != PinKind::Io {
+                continue;
+            }
+            let port = port_name(&pin.pin);
+            let loc = &pin.pin;
+            let constraint = match format {
+                ConstraintFormat::Ucf => format!("NET \"{port}\" LOC = \"{loc}\";"),
+                ConstraintFormat::Xdc => {
+                    format!("set_property PACKAGE_PIN {loc} [get_ports {{{port}}}]")
+                }
+                ConstraintFormat::Pcf => format!("set_io {port} {loc}"),
+                ConstraintFormat::Lpf => format!("LOCATE COMP \"{port}\" SITE \"{loc}\";"),
+            };
+            let mut notes = vec![pin.pad.clone()];
+            if let Some(bank) = &pin.bank {
+                notes.push(format!("bank {bank}"));
+            }
+            if let (Some(diff), Some(diff_pin)) = (pin.diff, &pin.diff_pin) {
+                notes.push(format!("diff {diff} with {diff_pin}"));
+            }
+            notes.extend(pin.functions.iter().cloned());
+            writeln!(o, "# {constraint} # {notes}", notes = notes.join(", "))?;
+        }
+        Ok(())
+    }
+}
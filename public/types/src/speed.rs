@@ -2,10 +2,15 @@ use std::collections::BTreeMap;
 
 use bincode::{Decode, Encode};
 
-use crate::units::{Resistance, Scalar, Temperature, Time, Voltage};
+use crate::{
+    diff::DiffNode,
+    text::{TextError, TextParser},
+    units::{Resistance, Scalar, Temperature, Time, Voltage},
+};
 
 /// A simple propagation delay, with minimum and maximum value.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimeRange {
     pub min: Time,
     pub max: Time,
@@ -17,6 +22,33 @@ impl std::fmt::Display for TimeRange {
     }
 }
 
+impl TimeRange {
+    pub const ZERO: TimeRange = TimeRange::point(Time::ZERO);
+
+    pub const fn point(t: Time) -> Self {
+        TimeRange { min: t, max: t }
+    }
+
+    /// Returns the smallest range covering both ranges.
+    pub fn hull(self, other: TimeRange) -> Self {
+        TimeRange {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+impl std::ops::Add for TimeRange {
+    type Output = TimeRange;
+
+    fn add(self, rhs: TimeRange) -> TimeRange {
+        TimeRange {
+            min: self.min + rhs.min,
+            max: self.max + rhs.max,
+        }
+    }
+}
+
 /// An unateness-aware delay through binate combinational logic.
 ///
 /// The `rise_to_rise` field describes the input-to-output delay when a rising edge
@@ -49,6 +81,7 @@ impl std::fmt::Display for TimeRange {
 /// 4. When the path is a reset-to-out with a constant reset value, only one of the four
 ///    fields is applicable.  In this case, a simple [`Delay`] should be used.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DelayRfBinate {
     pub rise_to_rise: Time,
     pub rise_to_fall: Time,
@@ -68,6 +101,7 @@ impl std::fmt::Display for DelayRfBinate {
 
 /// A version of [`DelayRfBinate`] with min and max values.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DelayRfBinateRange {
     pub rise_to_rise: TimeRange,
     pub rise_to_fall: TimeRange,
@@ -90,6 +124,7 @@ impl std::fmt::Display for DelayRfBinateRange {
 /// The `rise` field describes the input-to-output delay for a rising edge on the output,
 /// and the `fall` field describes the input-to-output delay for a falling edge on the output.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DelayRfUnate {
     pub rise: Time,
     pub fall: Time,
@@ -102,6 +137,7 @@ impl std::fmt::Display for DelayRfUnate {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DelayRfUnateRange {
     pub rise: TimeRange,
     pub fall: TimeRange,
@@ -113,6 +149,156 @@ impl std::fmt::Display for DelayRfUnateRange {
     }
 }
 
+fn add_opt(a: Option<TimeRange>, b: Option<TimeRange>) -> Option<TimeRange> {
+    Some(a? + b?)
+}
+
+fn hull_opt(a: Option<TimeRange>, b: Option<TimeRange>) -> Option<TimeRange> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.hull(b)),
+        _ => a.or(b),
+    }
+}
+
+/// A delay in the form used for path analysis: the range of each combination of input
+/// and output edge, or `None` when an input edge cannot cause the given output edge.
+///
+/// All `Delay*` speed values convert to this form, see [`Speed::delay`].  Delays from an edge
+/// are taken to be from a rising edge; use [`EdgeDelay::invert_input`] for falling-edge arcs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct EdgeDelay {
+    pub rise_to_rise: Option<TimeRange>,
+    pub rise_to_fall: Option<TimeRange>,
+    pub fall_to_rise: Option<TimeRange>,
+    pub fall_to_fall: Option<TimeRange>,
+}
+
+impl EdgeDelay {
+    pub const ZERO: EdgeDelay = EdgeDelay::pos_unate(TimeRange::ZERO, TimeRange::ZERO);
+
+    pub const fn pos_unate(rise: TimeRange, fall: TimeRange) -> Self {
+        EdgeDelay {
+            rise_to_rise: Some(rise),
+            rise_to_fall: None,
+            fall_to_rise: None,
+            fall_to_fall: Some(fall),
+        }
+    }
+
+    pub const fn neg_unate(rise: TimeRange, fall: TimeRange) -> Self {
+        EdgeDelay {
+            rise_to_rise: None,
+            rise_to_fall: Some(fall),
+            fall_to_rise: Some(rise),
+            fall_to_fall: None,
+        }
+    }
+
+    pub const fn from_edge(rise: TimeRange, fall: TimeRange) -> Self {
+        EdgeDelay {
+            rise_to_rise: Some(rise),
+            rise_to_fall: Some(fall),
+            fall_to_rise: None,
+            fall_to_fall: None,
+        }
+    }
+
+    /// Returns the same delay with the input edges swapped.
+    pub fn invert_input(self) -> Self {
+        EdgeDelay {
+            rise_to_rise: self.fall_to_rise,
+            rise_to_fall: self.fall_to_fall,
+            fall_to_rise: self.rise_to_rise,
+            fall_to_fall: self.rise_to_fall,
+        }
+    }
+
+    /// Returns the delay of this path followed by another one.
+    pub fn then(self, next: EdgeDelay) -> Self {
+        EdgeDelay {
+            rise_to_rise: hull_opt(
+                add_opt(self.rise_to_rise, next.rise_to_rise),
+                add_opt(self.rise_to_fall, next.fall_to_rise),
+            ),
+            rise_to_fall: hull_opt(
+                add_opt(self.rise_to_rise, next.rise_to_fall),
+                add_opt(self.rise_to_fall, next.fall_to_fall),
+            ),
+            fall_to_rise: hull_opt(
+                add_opt(self.fall_to_rise, next.rise_to_rise),
+                add_opt(self.fall_to_fall, next.fall_to_rise),
+            ),
+            fall_to_fall: hull_opt(
+                add_opt(self.fall_to_rise, next.rise_to_fall),
+                add_opt(self.fall_to_fall, next.fall_to_fall),
+            ),
+        }
+    }
+
+    /// Propagates the arrival times of a rising and a falling edge at the input to
+    /// the output.
+    pub fn propagate(self, arrival: EdgeArrival) -> EdgeArrival {
+        EdgeArrival {
+            rise: hull_opt(
+                add_opt(arrival.rise, self.rise_to_rise),
+                add_opt(arrival.fall, self.fall_to_rise),
+            ),
+            fall: hull_opt(
+                add_opt(arrival.rise, self.rise_to_fall),
+                add_opt(arrival.fall, self.fall_to_fall),
+            ),
+        }
+    }
+
+    /// Returns the range covering all edge combinations, ignoring unateness.
+    pub fn range(self) -> Option<TimeRange> {
+        [self.rise_to_fall, self.fall_to_rise, self.fall_to_fall]
+            .into_iter()
+            .fold(self.rise_to_rise, hull_opt)
+    }
+}
+
+/// The arrival times of the rising and falling edges of a signal, or `None` for an edge
+/// that never arrives.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EdgeArrival {
+    pub rise: Option<TimeRange>,
+    pub fall: Option<TimeRange>,
+}
+
+impl EdgeArrival {
+    pub const ZERO: EdgeArrival = EdgeArrival {
+        rise: Some(TimeRange::ZERO),
+        fall: Some(TimeRange::ZERO),
+    };
+
+    pub fn is_none(self) -> bool {
+        self.rise.is_none() && self.fall.is_none()
+    }
+
+    /// Merges another set of arrival times into this one.
+    pub fn merge(&mut self, other: EdgeArrival) {
+        self.rise = hull_opt(self.rise, other.rise);
+        self.fall = hull_opt(self.fall, other.fall);
+    }
+
+    /// Returns the range covering both edges.
+    pub fn range(self) -> Option<TimeRange> {
+        hull_opt(self.rise, self.fall)
+    }
+}
+
+impl std::fmt::Display for EdgeArrival {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.rise, self.fall) {
+            (Some(rise), Some(fall)) => write!(f, "r {rise} f {fall}"),
+            (Some(rise), None) => write!(f, "r {rise}"),
+            (None, Some(fall)) => write!(f, "f {fall}"),
+            (None, None) => write!(f, "none"),
+        }
+    }
+}
+
 /// A simple setup-hold constraint.
 ///
 /// This constraint describes a relation between a data input and a clock input.
@@ -136,6 +322,7 @@ impl std::fmt::Display for DelayRfUnateRange {
 /// Note that both `setup` and `hold` are simple `Time` values, not ranges — there is no use for
 /// a range, as only the `max` value would be actually meaningful for the setup and hold checks.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetupHold {
     pub setup: Time,
     pub hold: Time,
@@ -157,6 +344,7 @@ impl std::fmt::Display for SetupHold {
 /// - there must be no falling edge on the data input within the time window
 ///   `[t - setup_fall, t + hold_fall]`
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetupHoldRf {
     pub rise_setup: Time,
     pub rise_hold: Time,
@@ -197,6 +385,7 @@ impl std::fmt::Display for SetupHoldRf {
 /// Since only one edge of reset and only one edge of clock are applicable, there is no unate-aware
 /// variant of `RecRem`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecRem {
     pub recovery: Time,
     pub removal: Time,
@@ -212,6 +401,7 @@ impl std::fmt::Display for RecRem {
 ///
 /// The factor is `a * t + b`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DerateFactorTemperatureLinear {
     pub a: Scalar,
     pub b: Scalar,
@@ -233,6 +423,7 @@ impl std::fmt::Display for DerateFactorTemperatureLinear {
 ///
 /// The factor is `1 / (a * V * V + b + V + c)`.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DerateFactorVoltageInvQuadratic {
     pub a: Scalar,
     pub b: Scalar,
@@ -259,6 +450,7 @@ impl std::fmt::Display for DerateFactorVoltageInvQuadratic {
 
 /// An unateness-aware resistance.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ResistanceRf {
     pub rise: Resistance,
     pub fall: Resistance,
@@ -272,6 +464,7 @@ impl std::fmt::Display for ResistanceRf {
 
 /// A single speed value in the speed database.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpeedVal {
     /// A simple propagation delay.
     ///
@@ -374,8 +567,194 @@ impl std::fmt::Display for SpeedVal {
     }
 }
 
+/// Splits off a `[min, max]` time range from the start of the string.
+fn take_range(s: &str) -> Result<(TimeRange, &str), String> {
+    let err = || format!("expected time range, got \"{s}\"");
+    let (range, rest) = s
+        .strip_prefix('[')
+        .and_then(|s| s.split_once(']'))
+        .ok_or_else(err)?;
+    let (min, max) = range.split_once(", ").ok_or_else(err)?;
+    Ok((
+        TimeRange {
+            min: min.parse()?,
+            max: max.parse()?,
+        },
+        rest.trim_start(),
+    ))
+}
+
+/// Parses a sequence of tagged values, such as `r setup 1ps r hold 2ps`, checking that
+/// the tags match the expected ones.
+fn parse_tagged<'a, const N: usize>(s: &'a str, tags: [&str; N]) -> Result<[&'a str; N], String> {
+    let mut res = [""; N];
+    let mut rest = s;
+    for (i, tag) in tags.into_iter().enumerate() {
+        let val = rest
+            .strip_prefix(tag)
+            .ok_or_else(|| format!("expected \"{tag}\" in \"{s}\""))?;
+        if let Some(next) = tags.get(i + 1) {
+            let pos = val
+                .find(&format!(" {next}"))
+                .ok_or_else(|| format!("expected \"{next}\" in \"{s}\""))?;
+            res[i] = &val[..pos];
+            rest = &val[pos + 1..];
+        } else {
+            res[i] = val;
+        }
+    }
+    Ok(res)
+}
+
+fn parse_ranges<const N: usize>(s: &str, tags: [&str; N]) -> Result<[TimeRange; N], String> {
+    let mut res = [TimeRange {
+        min: Time::ZERO,
+        max: Time::ZERO,
+    }; N];
+    let mut rest = s;
+    for (i, tag) in tags.into_iter().enumerate() {
+        let val = rest
+            .strip_prefix(tag)
+            .ok_or_else(|| format!("expected \"{tag}\" in \"{s}\""))?;
+        (res[i], rest) = take_range(val)?;
+    }
+    if !rest.is_empty() {
+        return Err(format!("trailing garbage in \"{s}\""));
+    }
+    Ok(res)
+}
+
+fn parse_unate(s: &str) -> Result<DelayRfUnate, String> {
+    let [rise, fall] = parse_tagged(s, ["+", "-"])?;
+    Ok(DelayRfUnate {
+        rise: rise.parse()?,
+        fall: fall.parse()?,
+    })
+}
+
+fn parse_unate_range(s: &str) -> Result<DelayRfUnateRange, String> {
+    let [rise, fall] = parse_ranges(s, ["r ", "f "])?;
+    Ok(DelayRfUnateRange { rise, fall })
+}
+
+impl std::str::FromStr for SpeedVal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(v) = s.strip_prefix("delay rf binate ") {
+            if v.starts_with("r-r ") {
+                let [rise_to_rise, rise_to_fall, fall_to_rise, fall_to_fall] =
+                    parse_ranges(v, ["r-r ", "r-f ", "f-r ", "f-f "])?;
+                Ok(SpeedVal::DelayRfBinateRange(DelayRfBinateRange {
+                    rise_to_rise,
+                    rise_to_fall,
+                    fall_to_rise,
+                    fall_to_fall,
+                }))
+            } else {
+                let [rr, rf, fr, ff] = parse_tagged(v, ["++", "+-", "-+", "--"])?;
+                Ok(SpeedVal::DelayRfBinate(DelayRfBinate {
+                    rise_to_rise: rr.parse()?,
+                    rise_to_fall: rf.parse()?,
+                    fall_to_rise: fr.parse()?,
+                    fall_to_fall: ff.parse()?,
+                }))
+            }
+        } else if let Some(v) = s.strip_prefix("delay rf pos unate ") {
+            if v.starts_with('r') {
+                Ok(SpeedVal::DelayRfPosUnateRange(parse_unate_range(v)?))
+            } else {
+                Ok(SpeedVal::DelayRfPosUnate(parse_unate(v)?))
+            }
+        } else if let Some(v) = s.strip_prefix("delay rf neg unate ") {
+            if v.starts_with('r') {
+                Ok(SpeedVal::DelayRfNegUnateRange(parse_unate_range(v)?))
+            } else {
+                Ok(SpeedVal::DelayRfNegUnate(parse_unate(v)?))
+            }
+        } else if let Some(v) = s.strip_prefix("delay rf from edge ") {
+            if v.starts_with('r') {
+                Ok(SpeedVal::DelayRfFromEdgeRange(parse_unate_range(v)?))
+            } else {
+                Ok(SpeedVal::DelayRfFromEdge(parse_unate(v)?))
+            }
+        } else if let Some(v) = s.strip_prefix("delay ") {
+            if v.starts_with('[') {
+                let [range] = parse_ranges(v, [""])?;
+                Ok(SpeedVal::DelayRange(range))
+            } else {
+                Ok(SpeedVal::Delay(v.parse()?))
+            }
+        } else if s.starts_with("setup ") {
+            let [setup, hold] = parse_tagged(s, ["setup ", "hold "])?;
+            Ok(SpeedVal::SetupHold(SetupHold {
+                setup: setup.parse()?,
+                hold: hold.parse()?,
+            }))
+        } else if s.starts_with("r setup ") {
+            let [rs, rh, fs, fh] = parse_tagged(s, ["r setup ", "r hold ", "f setup ", "f hold "])?;
+            Ok(SpeedVal::SetupHoldRf(SetupHoldRf {
+                rise_setup: rs.parse()?,
+                rise_hold: rh.parse()?,
+                fall_setup: fs.parse()?,
+                fall_hold: fh.parse()?,
+            }))
+        } else if s.starts_with("recovery ") {
+            let [recovery, removal] = parse_tagged(s, ["recovery ", "removal "])?;
+            Ok(SpeedVal::RecRem(RecRem {
+                recovery: recovery.parse()?,
+                removal: removal.parse()?,
+            }))
+        } else if let Some(v) = s.strip_prefix("pulsewidth ") {
+            Ok(SpeedVal::PulseWidth(v.parse()?))
+        } else if let Some(v) = s.strip_prefix("period ") {
+            Ok(SpeedVal::Period(v.parse()?))
+        } else if let Some(v) = s.strip_prefix("scalar ") {
+            Ok(SpeedVal::Scalar(v.parse()?))
+        } else if let Some(v) = s.strip_prefix("derate temperature linear ") {
+            let (a, b) = v
+                .strip_prefix('(')
+                .and_then(|v| v.strip_suffix(')'))
+                .and_then(|v| v.split_once("t + "))
+                .ok_or_else(|| format!("invalid derate \"{s}\""))?;
+            Ok(SpeedVal::DerateFactorTemperatureLinear(
+                DerateFactorTemperatureLinear {
+                    a: a.parse()?,
+                    b: b.parse()?,
+                },
+            ))
+        } else if let Some(v) = s.strip_prefix("derate voltage inverse quadratic ") {
+            let (a, b, c) = v
+                .strip_prefix('(')
+                .and_then(|v| v.strip_suffix(")¯¹"))
+                .and_then(|v| v.split_once("V² + "))
+                .and_then(|(a, v)| Some((a, v.split_once("V + ")?)))
+                .map(|(a, (b, c))| (a, b, c))
+                .ok_or_else(|| format!("invalid derate \"{s}\""))?;
+            Ok(SpeedVal::DerateFactorVoltageInvQuadratic(
+                DerateFactorVoltageInvQuadratic {
+                    a: a.parse()?,
+                    b: b.parse()?,
+                    c: c.parse()?,
+                },
+            ))
+        } else if let Some(v) = s.strip_prefix("res rf ") {
+            let [rise, fall] = parse_tagged(v, ["+", "-"])?;
+            Ok(SpeedVal::ResistanceRf(ResistanceRf {
+                rise: rise.parse()?,
+                fall: fall.parse()?,
+            }))
+        } else if let Some(v) = s.strip_prefix("res ") {
+            Ok(SpeedVal::Resistance(v.parse()?))
+        } else {
+            Err(format!("unknown speed value \"{s}\""))
+        }
+    }
+}
+
 /// A string-keyed database of speed values, describing a particular speed grade of a device.
 #[derive(Clone, Debug, Eq, PartialEq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Speed {
     pub vals: BTreeMap<String, SpeedVal>,
 }
@@ -384,6 +763,120 @@ impl Speed {
     pub fn new() -> Self {
         Default::default()
     }
+
+    fn get(&self, name: &str) -> Result<SpeedVal, SpeedError> {
+        self.vals
+            .get(name)
+            .copied()
+            .ok_or_else(|| SpeedError::Missing(name.to_string()))
+    }
+
+    /// Returns a delay value, keeping the delay of each combination of input and output edge.
+    /// Plain delays apply to both edges of a positive-unate path.
+    pub fn delay(&self, name: &str) -> Result<EdgeDelay, SpeedError> {
+        let point = TimeRange::point;
+        Ok(match self.get(name)? {
+            SpeedVal::Delay(t) => EdgeDelay::pos_unate(point(t), point(t)),
+            SpeedVal::DelayRange(r) => EdgeDelay::pos_unate(r, r),
+            SpeedVal::DelayRfBinate(d) => EdgeDelay {
+                rise_to_rise: Some(point(d.rise_to_rise)),
+                rise_to_fall: Some(point(d.rise_to_fall)),
+                fall_to_rise: Some(point(d.fall_to_rise)),
+                fall_to_fall: Some(point(d.fall_to_fall)),
+            },
+            SpeedVal::DelayRfBinateRange(d) => EdgeDelay {
+                rise_to_rise: Some(d.rise_to_rise),
+                rise_to_fall: Some(d.rise_to_fall),
+                fall_to_rise: Some(d.fall_to_rise),
+                fall_to_fall: Some(d.fall_to_fall),
+            },
+            SpeedVal::DelayRfPosUnate(d) => EdgeDelay::pos_unate(point(d.rise), point(d.fall)),
+            SpeedVal::DelayRfPosUnateRange(d) => EdgeDelay::pos_unate(d.rise, d.fall),
+            SpeedVal::DelayRfNegUnate(d) => EdgeDelay::neg_unate(point(d.rise), point(d.fall)),
+            SpeedVal::DelayRfNegUnateRange(d) => EdgeDelay::neg_unate(d.rise, d.fall),
+            SpeedVal::DelayRfFromEdge(d) => EdgeDelay::from_edge(point(d.rise), point(d.fall)),
+            SpeedVal::DelayRfFromEdgeRange(d) => EdgeDelay::from_edge(d.rise, d.fall),
+            _ => return Err(SpeedError::WrongKind(name.to_string(), "delay")),
+        })
+    }
+
+    /// Returns a setup-hold constraint.  Plain constraints apply to both data edges, and
+    /// recovery-removal constraints are returned with recovery as setup and removal as hold.
+    pub fn setup_hold(&self, name: &str) -> Result<SetupHoldRf, SpeedError> {
+        let both = |setup, hold| SetupHoldRf {
+            rise_setup: setup,
+            rise_hold: hold,
+            fall_setup: setup,
+            fall_hold: hold,
+        };
+        Ok(match self.get(name)? {
+            SpeedVal::SetupHold(sh) => both(sh.setup, sh.hold),
+            SpeedVal::SetupHoldRf(sh) => sh,
+            SpeedVal::RecRem(rr) => both(rr.recovery, rr.removal),
+            _ => {
+                return Err(SpeedError::WrongKind(
+                    name.to_string(),
+                    "setup-hold constraint",
+                ));
+            }
+        })
+    }
+
+    /// Returns a minimum pulse width or period value.
+    pub fn pulse_width(&self, name: &str) -> Result<Time, SpeedError> {
+        match self.get(name)? {
+            SpeedVal::PulseWidth(t) | SpeedVal::Period(t) => Ok(t),
+            _ => Err(SpeedError::WrongKind(name.to_string(), "pulse width")),
+        }
+    }
+}
+
+/// An error looking up a value in a [`Speed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpeedError {
+    /// The speed grade has no value with this name.
+    Missing(String),
+    /// The value exists, but is not of the requested kind.
+    WrongKind(String, &'static str),
+}
+
+impl std::fmt::Display for SpeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpeedError::Missing(name) => write!(f, "missing speed value {name}"),
+            SpeedError::WrongKind(name, kind) => write!(f, "speed value {name} is not a {kind}"),
+        }
+    }
+}
+
+impl std::error::Error for SpeedError {}
+
+impl Speed {
+    /// Parses the body of a speed block as written by the [`Display`](std::fmt::Display)
+    /// impl, up to and including the closing `}`.
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Speed::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let Some((key, val)) = line.text().rsplit_once(": ") else {
+                return Err(line.error("expected speed value"));
+            };
+            let key = key.trim_end();
+            let val = val.parse().map_err(|e| line.error(e))?;
+            if res.vals.insert(key.to_string(), val).is_some() {
+                return Err(line.error(format!("duplicate speed value {key}")));
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (k, v) in &self.vals {
+            res.insert_leaf(k, v);
+        }
+        res
+    }
 }
 
 impl std::fmt::Display for Speed {
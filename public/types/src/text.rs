@@ -0,0 +1,275 @@
+//! Support for reading back the human-readable text dumps written by the various `dump` methods.
+//!
+//! The dump format is line-oriented: every statement is on its own line and ends with `;`,
+//! blocks are opened by a `<keyword> <name> {` line and closed by a `}` line.  Blank lines
+//! and `//` comment lines carry no information and are skipped.
+
+use prjcombine_entity::EntityId;
+
+use crate::bitvec::BitVec;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TextError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            write!(f, "at end of file: {}", self.msg)
+        } else {
+            write!(f, "line {}: {}", self.line, self.msg)
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// A single significant line of the dump.
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    /// 1-based line number in the source text.
+    pub num: usize,
+    pub raw: &'a str,
+}
+
+impl<'a> Line<'a> {
+    pub fn text(&self) -> &'a str {
+        self.raw.trim()
+    }
+
+    /// The number of leading tabs.
+    pub fn indent(&self) -> usize {
+        self.raw.len() - self.raw.trim_start_matches('\t').len()
+    }
+
+    pub fn error(&self, msg: impl Into<String>) -> TextError {
+        TextError {
+            line: self.num,
+            msg: msg.into(),
+        }
+    }
+
+    /// Attaches this line's number to a value parsing error.
+    pub fn check<T>(&self, res: Result<T, String>) -> Result<T, TextError> {
+        res.map_err(|msg| self.error(msg))
+    }
+
+    /// Returns the line with the terminating `;` stripped, or an error if this is not a statement.
+    pub fn statement(&self) -> Result<&'a str, TextError> {
+        self.text()
+            .strip_suffix(';')
+            .ok_or_else(|| self.error(format!("expected statement, got \"{}\"", self.text())))
+    }
+
+    /// Splits a statement into its keyword and the rest.
+    pub fn keyword_statement(&self) -> Result<(&'a str, &'a str), TextError> {
+        let stmt = self.statement()?;
+        Ok(stmt.split_once(' ').unwrap_or((stmt, "")))
+    }
+
+    /// If this line opens a block, returns the keyword and the (possibly empty) name.
+    pub fn block_start(&self) -> Option<(&'a str, &'a str)> {
+        let head = self.text().strip_suffix('{')?.trim_end();
+        Some(head.split_once(' ').unwrap_or((head, "")))
+    }
+
+    /// Returns the name of the block opened by this line, which must use the given keyword.
+    pub fn expect_block(&self, keyword: &str) -> Result<&'a str, TextError> {
+        match self.block_start() {
+            Some((kw, name)) if kw == keyword => Ok(name),
+            _ => Err(self.error(format!("expected {keyword} block, got \"{}\"", self.text()))),
+        }
+    }
+
+    pub fn is_block_end(&self) -> bool {
+        self.text() == "}"
+    }
+
+    /// Returns an entry of a multi-line list with the terminating `,` and any trailing
+    /// `// ...` comment stripped.
+    pub fn list_item(&self) -> Result<&'a str, TextError> {
+        let text = match self.text().split_once("//") {
+            Some((item, _)) => item.trim_end(),
+            None => self.text(),
+        };
+        text.strip_suffix(',')
+            .ok_or_else(|| self.error(format!("expected list entry, got \"{}\"", self.text())))
+    }
+}
+
+/// A cursor over the significant lines of a text dump.
+#[derive(Clone)]
+pub struct TextParser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl<'a> TextParser<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, raw)| Line { num: i + 1, raw })
+            .filter(|line| !line.text().is_empty() && !line.text().starts_with("//"))
+            .collect();
+        Self { lines, pos: 0 }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pos == self.lines.len()
+    }
+
+    pub fn peek(&self) -> Option<Line<'a>> {
+        self.lines.get(self.pos).copied()
+    }
+
+    /// Consumes and returns the next line, failing at end of input.
+    pub fn expect_line(&mut self) -> Result<Line<'a>, TextError> {
+        let line = self.peek().ok_or_else(|| TextError {
+            line: 0,
+            msg: "unexpected end of file".into(),
+        })?;
+        self.pos += 1;
+        Ok(line)
+    }
+
+    /// Returns an error for a required statement missing from the block that was just closed.
+    pub fn missing(&self, what: &str) -> TextError {
+        let line = self.pos.checked_sub(1).map_or(0, |pos| self.lines[pos].num);
+        TextError {
+            line,
+            msg: format!("block is missing {what}"),
+        }
+    }
+
+    /// Fails unless the whole input has been consumed.
+    pub fn expect_done(&self) -> Result<(), TextError> {
+        match self.peek() {
+            Some(line) => Err(line.error(format!("unexpected \"{}\"", line.text()))),
+            None => Ok(()),
+        }
+    }
+
+    /// Consumes the next line if it closes a block.
+    pub fn try_block_end(&mut self) -> bool {
+        if self.peek().is_some_and(|line| line.is_block_end()) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns a parser positioned inside the top-level block opened by `<keyword> {`, if
+    /// there is one.  Used to read sections that the rest of the dump depends on before
+    /// their turn comes.
+    pub fn find_block(&self, keyword: &str) -> Option<TextParser<'a>> {
+        let pos = self
+            .lines
+            .iter()
+            .position(|line| line.indent() == 0 && line.block_start() == Some((keyword, "")))?;
+        Some(TextParser {
+            lines: self.lines.clone(),
+            pos: pos + 1,
+        })
+    }
+
+    /// Consumes the rest of the current block, up to and including the closing `}`.
+    pub fn skip_block(&mut self) -> Result<(), TextError> {
+        let mut depth = 0;
+        loop {
+            let line = self.expect_line()?;
+            if line.is_block_end() {
+                if depth == 0 {
+                    return Ok(());
+                }
+                depth -= 1;
+            } else if line.text() != "} {" && line.block_start().is_some() {
+                depth += 1;
+            }
+        }
+    }
+
+    /// The lines that have not been consumed yet.
+    pub fn remaining(&self) -> &[Line<'a>] {
+        &self.lines[self.pos..]
+    }
+
+    /// Parses a block of `name[idx],` entries, as used for JED bit lists, up to
+    /// and including the closing `}`.
+    pub fn parse_jed_bits(&mut self) -> Result<Vec<(String, usize)>, TextError> {
+        let mut res = vec![];
+        while !self.try_block_end() {
+            let line = self.expect_line()?;
+            let parsed = line
+                .text()
+                .strip_suffix("],")
+                .and_then(|s| s.rsplit_once('['))
+                .and_then(|(name, idx)| Some((name.to_string(), idx.parse().ok()?)));
+            match parsed {
+                Some(entry) => res.push(entry),
+                None => return Err(line.error("expected JED bit entry")),
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Parses a plain decimal number.
+pub fn parse_num<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(format!("expected number, got \"{s}\""));
+    }
+    s.parse()
+        .map_err(|_| format!("number out of range: \"{s}\""))
+}
+
+/// Parses a `0x`-prefixed hexadecimal number.
+pub fn parse_hex(s: &str) -> Result<u32, String> {
+    s.strip_prefix("0x")
+        .and_then(|v| u32::from_str_radix(v, 16).ok())
+        .ok_or_else(|| format!("expected hex number, got \"{s}\""))
+}
+
+/// Parses an entity id written with the given prefix, such as `CHIP3` or `BANK0`.
+pub fn parse_id<I: EntityId>(s: &str, prefix: &str) -> Result<I, String> {
+    let idx = s
+        .strip_prefix(prefix)
+        .ok_or_else(|| format!("expected {prefix} id, got \"{s}\""))?;
+    Ok(I::from_idx(parse_num(idx)?))
+}
+
+/// Parses a bit vector written MSB-first as a string of `0` and `1`.
+pub fn parse_bits(s: &str) -> Result<BitVec, String> {
+    s.chars()
+        .rev()
+        .map(|c| match c {
+            '0' => Ok(false),
+            '1' => Ok(true),
+            _ => Err(format!("invalid bit vector \"{s}\"")),
+        })
+        .collect()
+}
+
+/// Splits an `a = b` assignment.
+pub fn split_assign(s: &str) -> Result<(&str, &str), String> {
+    s.split_once(" = ")
+        .ok_or_else(|| format!("expected assignment, got \"{s}\""))
+}
+
+/// Splits a `, `-separated list, which may be empty.
+pub fn split_list(s: &str) -> impl Iterator<Item = &str> {
+    s.split(", ").filter(|item| !item.is_empty())
+}
+
+/// Checks that a block name is the next id in sequence, as entity collections are dumped
+/// in id order.
+pub fn expect_next_id<I: std::fmt::Display>(s: &str, next: I) -> Result<(), String> {
+    if s == next.to_string() {
+        Ok(())
+    } else {
+        Err(format!("expected {next}, got \"{s}\""))
+    }
+}
@@ -0,0 +1,408 @@
+//! Static timing analysis over a graph of delays.
+//!
+//! The family crates describe a configured device as a [`TimingGraph`]: a set of nodes
+//! connected by delay edges, with input and output pads as the timing start and end points,
+//! and a set of registers that launch data from their outputs and check it at their inputs.
+//! [`TimingGraph::analyze`] computes the usual data sheet figures for the design from it,
+//! while [`TimingGraph::arrival_times`] gives the raw arrival times for other analyses.
+//!
+//! Rising and falling edges are propagated separately, using the unateness of every edge,
+//! so that a register clocked on the falling edge of its clock input is timed from the
+//! falling edge of the clock pin, and the rise and fall delays of the speed data are kept
+//! apart.
+//!
+//! Combinatorial loops are broken at an arbitrary point, and the number of broken edges is
+//! reported.  Registers whose output reaches neither an output pad nor another register are
+//! ignored, so that unused macrocells with leftover clock connections don't show up.
+
+use std::collections::BTreeMap;
+
+use prjcombine_entity::{
+    EntityId, EntityVec,
+    id::{EntityIdU32, EntityTag},
+};
+
+use crate::{
+    speed::{EdgeArrival, EdgeDelay, SetupHoldRf, TimeRange},
+    units::{Scalar, Time},
+};
+
+pub struct TimingNodeTag;
+impl EntityTag for TimingNodeTag {
+    const PREFIX: &'static str = "TN";
+}
+pub type TimingNodeId = EntityIdU32<TimingNodeTag>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimingNodeKind {
+    Internal,
+    /// An input pad, named by its package pin.
+    Input(String),
+    /// An output pad, named by its package pin.
+    Output(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TimingNode {
+    pub name: String,
+    pub kind: TimingNodeKind,
+    pub edges: Vec<(TimingNodeId, EdgeDelay)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockEdge {
+    Rising,
+    Falling,
+    Both,
+}
+
+impl ClockEdge {
+    /// Returns the arrival time of the active edge(s) out of the arrival times of a clock.
+    pub fn arrival(self, clk: EdgeArrival) -> Option<TimeRange> {
+        match self {
+            ClockEdge::Rising => clk.rise,
+            ClockEdge::Falling => clk.fall,
+            ClockEdge::Both => clk.range(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TimingRegister {
+    pub name: String,
+    pub clk: TimingNodeId,
+    pub edge: ClockEdge,
+    pub q: TimingNodeId,
+    /// The delay from the active clock edge to the output, in the from-edge form.
+    pub clk_to_q: EdgeDelay,
+    /// The inputs checked against the clock: data, clock enable, and the recovery-removal
+    /// constraints of asynchronous set and reset.
+    pub checks: Vec<(TimingNodeId, SetupHoldRf)>,
+    /// The minimum clock period allowed by the pulse width constraints.
+    pub min_period: Time,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TimingGraph {
+    pub nodes: EntityVec<TimingNodeId, TimingNode>,
+    pub registers: Vec<TimingRegister>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TimingReport {
+    /// Combinatorial delays to the rising and falling output edges, keyed by
+    /// (input pin, output pin).
+    pub pin_to_pin: BTreeMap<(String, String), EdgeArrival>,
+    /// Clock-to-out delays to the rising and falling output edges, keyed by
+    /// (clock pin, output pin).
+    pub clock_to_out: BTreeMap<(String, String), EdgeArrival>,
+    /// Setup and hold requirements of both data edges at the pins, keyed by
+    /// (data pin, clock pin).
+    pub setup_hold: BTreeMap<(String, String), SetupHoldRf>,
+    /// The minimum clock period, keyed by clock pin.
+    pub min_period: BTreeMap<String, Time>,
+    /// The number of edges removed to break combinatorial loops.
+    pub broken_loops: usize,
+}
+
+fn merge_map(
+    tgt: &mut BTreeMap<(String, String), EdgeArrival>,
+    key: (&str, &str),
+    val: EdgeArrival,
+) {
+    tgt.entry((key.0.to_string(), key.1.to_string()))
+        .or_default()
+        .merge(val);
+}
+
+/// Returns the setup and hold requirements of a check at the pins, given the arrival times
+/// of the data edges and of the active clock edge.  A data edge that never arrives gets
+/// the requirement of the other one.
+fn check_requirement(data: EdgeArrival, clk: TimeRange, sh: SetupHoldRf) -> Option<SetupHoldRf> {
+    let rise = data.rise.or(data.fall)?;
+    let fall = data.fall.or(data.rise)?;
+    Some(SetupHoldRf {
+        rise_setup: rise.max + sh.rise_setup - clk.min,
+        rise_hold: clk.max + sh.rise_hold - rise.min,
+        fall_setup: fall.max + sh.fall_setup - clk.min,
+        fall_hold: clk.max + sh.fall_hold - fall.min,
+    })
+}
+
+impl TimingGraph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_node(&mut self, name: impl Into<String>, kind: TimingNodeKind) -> TimingNodeId {
+        self.nodes.push(TimingNode {
+            name: name.into(),
+            kind,
+            edges: vec![],
+        })
+    }
+
+    pub fn add_edge(&mut self, from: TimingNodeId, to: TimingNodeId, delay: EdgeDelay) {
+        self.nodes[from].edges.push((to, delay));
+    }
+
+    pub fn add_register(&mut self, reg: TimingRegister) {
+        self.registers.push(reg);
+    }
+
+    /// Returns the nodes in topological order, and the set of (node, edge index) pairs
+    /// that had to be removed to make the graph acyclic.
+    fn sort(&self) -> (Vec<TimingNodeId>, Vec<Vec<bool>>) {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Mark {
+            New,
+            Open,
+            Done,
+        }
+        let mut mark = vec![Mark::New; self.nodes.len()];
+        let mut broken: Vec<Vec<bool>> = self
+            .nodes
+            .values()
+            .map(|node| vec![false; node.edges.len()])
+            .collect();
+        let mut order = vec![];
+        for root in self.nodes.ids() {
+            if mark[root.to_idx()] != Mark::New {
+                continue;
+            }
+            mark[root.to_idx()] = Mark::Open;
+            let mut stack = vec![(root, 0)];
+            while let Some(&mut (node, ref mut idx)) = stack.last_mut() {
+                if let Some(&(next, _)) = self.nodes[node].edges.get(*idx) {
+                    let edge = *idx;
+                    *idx += 1;
+                    match mark[next.to_idx()] {
+                        Mark::New => {
+                            mark[next.to_idx()] = Mark::Open;
+                            stack.push((next, 0));
+                        }
+                        Mark::Open => broken[node.to_idx()][edge] = true,
+                        Mark::Done => (),
+                    }
+                } else {
+                    mark[node.to_idx()] = Mark::Done;
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+        order.reverse();
+        (order, broken)
+    }
+
+    /// Propagates the given arrival times at the sources through the graph, visiting nodes
+    /// starting from position `start` of the topological order.
+    fn propagate(
+        &self,
+        order: &[TimingNodeId],
+        start: usize,
+        broken: &[Vec<bool>],
+        sources: &[(TimingNodeId, EdgeArrival)],
+    ) -> Vec<EdgeArrival> {
+        let mut arrival = vec![EdgeArrival::default(); self.nodes.len()];
+        for &(src, init) in sources {
+            arrival[src.to_idx()].merge(init);
+        }
+        for &node in &order[start..] {
+            let cur = arrival[node.to_idx()];
+            if cur.is_none() {
+                continue;
+            }
+            for (idx, &(next, delay)) in self.nodes[node].edges.iter().enumerate() {
+                if !broken[node.to_idx()][idx] {
+                    arrival[next.to_idx()].merge(delay.propagate(cur));
+                }
+            }
+        }
+        arrival
+    }
+
+    /// Computes the arrival times of both edges at every node reachable from the given
+    /// sources, with all sources launching both edges at time zero.  Also returns the number
+    /// of edges removed to break combinatorial loops.
+    pub fn arrival_times(
+        &self,
+        sources: &[TimingNodeId],
+    ) -> (EntityVec<TimingNodeId, EdgeArrival>, usize) {
+        let (order, broken) = self.sort();
+        let sources: Vec<_> = sources
+            .iter()
+            .map(|&src| (src, EdgeArrival::ZERO))
+            .collect();
+        let arrival = self.propagate(&order, 0, &broken, &sources);
+        let broken_loops = broken.iter().flatten().filter(|&&x| x).count();
+        (arrival.into_iter().collect(), broken_loops)
+    }
+
+    pub fn analyze(&self) -> TimingReport {
+        let mut report = TimingReport::default();
+        let (order, broken) = self.sort();
+        report.broken_loops = broken.iter().flatten().filter(|&&x| x).count();
+        let mut pos = vec![0; self.nodes.len()];
+        for (i, &node) in order.iter().enumerate() {
+            pos[node.to_idx()] = i;
+        }
+        let propagate_from = |src: TimingNodeId, init: EdgeArrival| {
+            self.propagate(&order, pos[src.to_idx()], &broken, &[(src, init)])
+        };
+
+        let live: Vec<bool> = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(sidx, sreg)| {
+                let arrival = propagate_from(sreg.q, EdgeArrival::ZERO);
+                self.nodes.iter().any(|(node, nnode)| {
+                    matches!(nnode.kind, TimingNodeKind::Output(_))
+                        && !arrival[node.to_idx()].is_none()
+                }) || self.registers.iter().enumerate().any(|(didx, dreg)| {
+                    didx != sidx
+                        && dreg
+                            .checks
+                            .iter()
+                            .any(|&(check, _)| !arrival[check.to_idx()].is_none())
+                })
+            })
+            .collect();
+
+        // per register, the arrival time of the active edge of each clock pin at its
+        // clock input
+        let mut clocks: Vec<BTreeMap<&str, TimeRange>> =
+            vec![BTreeMap::new(); self.registers.len()];
+        for (src, node) in &self.nodes {
+            let TimingNodeKind::Input(ref pin) = node.kind else {
+                continue;
+            };
+            let arrival = propagate_from(src, EdgeArrival::ZERO);
+            for (dst, dnode) in &self.nodes {
+                if let TimingNodeKind::Output(ref opin) = dnode.kind
+                    && !arrival[dst.to_idx()].is_none()
+                {
+                    merge_map(&mut report.pin_to_pin, (pin, opin), arrival[dst.to_idx()]);
+                }
+            }
+            for (ridx, reg) in self.registers.iter().enumerate() {
+                if live[ridx]
+                    && let Some(clk) = reg.edge.arrival(arrival[reg.clk.to_idx()])
+                {
+                    clocks[ridx].insert(pin, clk);
+                }
+            }
+        }
+
+        // setup and hold at the pins
+        for (src, node) in &self.nodes {
+            let TimingNodeKind::Input(ref pin) = node.kind else {
+                continue;
+            };
+            let arrival = propagate_from(src, EdgeArrival::ZERO);
+            for (ridx, reg) in self.registers.iter().enumerate() {
+                for &(check, sh) in &reg.checks {
+                    for (&cpin, &clk) in &clocks[ridx] {
+                        let Some(req) = check_requirement(arrival[check.to_idx()], clk, sh) else {
+                            continue;
+                        };
+                        let key = (pin.clone(), cpin.to_string());
+                        let entry = report.setup_hold.entry(key).or_insert(req);
+                        entry.rise_setup = entry.rise_setup.max(req.rise_setup);
+                        entry.rise_hold = entry.rise_hold.max(req.rise_hold);
+                        entry.fall_setup = entry.fall_setup.max(req.fall_setup);
+                        entry.fall_hold = entry.fall_hold.max(req.fall_hold);
+                    }
+                }
+            }
+        }
+
+        // clock-to-out and register-to-register paths
+        for (sidx, sreg) in self.registers.iter().enumerate() {
+            for (&cpin, &sclk) in &clocks[sidx] {
+                let period = report
+                    .min_period
+                    .entry(cpin.to_string())
+                    .or_insert(Time::ZERO);
+                *period = (*period).max(sreg.min_period);
+                let launch = sreg.clk_to_q.propagate(EdgeArrival {
+                    rise: Some(sclk),
+                    fall: None,
+                });
+                let arrival = propagate_from(sreg.q, launch);
+                for (dst, dnode) in &self.nodes {
+                    if let TimingNodeKind::Output(ref opin) = dnode.kind
+                        && !arrival[dst.to_idx()].is_none()
+                    {
+                        merge_map(
+                            &mut report.clock_to_out,
+                            (cpin, opin),
+                            arrival[dst.to_idx()],
+                        );
+                    }
+                }
+                for (didx, dreg) in self.registers.iter().enumerate() {
+                    let Some(&dclk) = clocks[didx].get(cpin) else {
+                        continue;
+                    };
+                    let factor = if sreg.edge == dreg.edge && sreg.edge != ClockEdge::Both {
+                        1.0
+                    } else {
+                        2.0
+                    };
+                    for &(check, sh) in &dreg.checks {
+                        let Some(req) = check_requirement(arrival[check.to_idx()], dclk, sh) else {
+                            continue;
+                        };
+                        let path = req.rise_setup.max(req.fall_setup);
+                        let path = Time(path.0 * Scalar(factor));
+                        let period = report.min_period.get_mut(cpin).unwrap();
+                        *period = (*period).max(path);
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+impl TimingReport {
+    /// Returns the maximum frequency of a clock pin in MHz.
+    pub fn fmax(&self, pin: &str) -> Option<Scalar> {
+        let period = *self.min_period.get(pin)?;
+        (period > Time::ZERO).then(|| Scalar(1e6) / period.0)
+    }
+}
+
+impl std::fmt::Display for TimingReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pin_to_pin {{")?;
+        for ((src, dst), delay) in &self.pin_to_pin {
+            writeln!(f, "\t{src} -> {dst}: {delay}")?;
+        }
+        writeln!(f, "}}")?;
+        writeln!(f, "clock_to_out {{")?;
+        for ((clk, dst), delay) in &self.clock_to_out {
+            writeln!(f, "\t{clk} -> {dst}: {delay}")?;
+        }
+        writeln!(f, "}}")?;
+        writeln!(f, "setup_hold {{")?;
+        for ((src, clk), sh) in &self.setup_hold {
+            writeln!(f, "\t{src} @ {clk}: {sh}")?;
+        }
+        writeln!(f, "}}")?;
+        writeln!(f, "clocks {{")?;
+        for (clk, period) in &self.min_period {
+            match self.fmax(clk) {
+                Some(fmax) => writeln!(f, "\t{clk}: period {period} fmax {fmax:.1}MHz")?,
+                None => writeln!(f, "\t{clk}: period {period}")?,
+            }
+        }
+        writeln!(f, "}}")?;
+        if self.broken_loops != 0 {
+            writeln!(f, "broken_loops {};", self.broken_loops)?;
+        }
+        Ok(())
+    }
+}
@@ -50,6 +50,80 @@ impl std::fmt::Display for Scalar {
     }
 }
 
+impl std::str::FromStr for Scalar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Scalar)
+            .map_err(|_| format!("invalid number \"{s}\""))
+    }
+}
+
+/// Finite values are serialized as plain numbers; infinities and NaN, which formats like
+/// JSON cannot represent, are serialized as strings (`"inf"`, `"-inf"`, `"NaN"`).
+#[cfg(feature = "serde")]
+mod scalar_serde {
+    use serde::de::{Deserialize, Deserializer, Error, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::Scalar;
+
+    impl Serialize for Scalar {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if self.0.is_finite() {
+                serializer.serialize_f64(self.0)
+            } else {
+                serializer.collect_str(self)
+            }
+        }
+    }
+
+    struct ScalarVisitor;
+
+    impl Visitor<'_> for ScalarVisitor {
+        type Value = Scalar;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("number")
+        }
+
+        fn visit_f64<E: Error>(self, v: f64) -> Result<Scalar, E> {
+            Ok(Scalar(v))
+        }
+
+        fn visit_i64<E: Error>(self, v: i64) -> Result<Scalar, E> {
+            Ok(Scalar(v as f64))
+        }
+
+        fn visit_u64<E: Error>(self, v: u64) -> Result<Scalar, E> {
+            Ok(Scalar(v as f64))
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Scalar, E> {
+            v.parse().map_err(E::custom)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Scalar {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(ScalarVisitor)
+        }
+    }
+}
+
+fn parse_with_unit(s: &str, unit: &str) -> Result<Scalar, String> {
+    s.strip_suffix(unit)
+        .ok_or_else(|| format!("expected value in {unit}, got \"{s}\""))?
+        .parse()
+}
+
 impl std::ops::Add for Scalar {
     type Output = Scalar;
 
@@ -104,12 +178,21 @@ impl From<i32> for Scalar {
 
 /// A time-dimension value for speed data.  The unit is ps.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Time(pub Scalar);
 
 impl Time {
     pub const ZERO: Time = Time(Scalar(0.0));
 }
 
+impl std::ops::Add for Time {
+    type Output = Time;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Time(self.0 + rhs.0)
+    }
+}
+
 impl std::ops::Sub for Time {
     type Output = Time;
 
@@ -132,8 +215,17 @@ impl std::fmt::Display for Time {
     }
 }
 
+impl std::str::FromStr for Time {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_with_unit(s, "ps").map(Time)
+    }
+}
+
 /// A temperature-dimension value for speed data.  The unit is °C.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Temperature(pub Scalar);
 
 impl std::fmt::Display for Temperature {
@@ -144,6 +236,7 @@ impl std::fmt::Display for Temperature {
 
 /// A voltage-dimension value for speed data.  The unit is V.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Voltage(pub Scalar);
 
 impl std::fmt::Display for Voltage {
@@ -154,6 +247,7 @@ impl std::fmt::Display for Voltage {
 
 /// A resistance-dimension value for speed data.  The unit is Ω.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Resistance(pub Scalar);
 
 impl std::fmt::Display for Resistance {
@@ -162,8 +256,17 @@ impl std::fmt::Display for Resistance {
     }
 }
 
+impl std::str::FromStr for Resistance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_with_unit(s, "Ω").map(Resistance)
+    }
+}
+
 /// A capacitance-dimension value for speed data.  The unit is pF.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Capacitance(pub Scalar);
 
 impl std::fmt::Display for Capacitance {
@@ -0,0 +1,113 @@
+use prjcombine_test_util::check_bsdl;
+use prjcombine_types::{
+    bscan::BScanPad,
+    bsdl::{Bsdl, InstructionRegister, PortKind, port_name},
+};
+
+fn sample() -> (Bsdl, Vec<BScanPad>) {
+    let mut bsdl = Bsdl::new("xc-test", "pq208", 5, 7);
+    for (name, kind, pin) in [
+        ("TDI", PortKind::In, "P3"),
+        ("TDO", PortKind::Out, "P4"),
+        ("VCC", PortKind::Linkage, "P5"),
+        ("VCC", PortKind::Linkage, "P6"),
+    ] {
+        bsdl.add_pin(name, kind, pin);
+    }
+    let pads = [
+        ("IO_P1", "P1", BScanPad::InputOutputTristate(2, 1, 0)),
+        ("DONE", "P2", BScanPad::OutputEnable(4, 3)),
+        ("io_p1", "P7", BScanPad::Input(5)),
+    ];
+    for (port, pin, pad) in pads {
+        bsdl.add_pad(port, pin, pad);
+    }
+    for (name, opcode, register) in [
+        ("EXTEST", 0x00, InstructionRegister::Standard),
+        ("SAMPLE", 0x01, InstructionRegister::Standard),
+        ("USER1", 0x02, InstructionRegister::Private),
+        ("IDCODE", 0x09, InstructionRegister::Standard),
+        ("BYPASS", 0x1f, InstructionRegister::Standard),
+    ] {
+        bsdl.add_instruction(name, opcode, register);
+    }
+    bsdl.idcode = Some((0x0123_4093, 0x0fff_ffff));
+    (bsdl, pads.map(|(_, _, pad)| pad).to_vec())
+}
+
+#[test]
+fn port_names() {
+    assert_eq!(port_name("io-l1p/gclk"), "IO_L1P_GCLK");
+    assert_eq!(port_name("__a__b__"), "A_B");
+    assert_eq!(port_name("1v8"), "P1V8");
+}
+
+#[test]
+fn emit() {
+    let (bsdl, pads) = sample();
+    check_bsdl(&bsdl, 7, &pads);
+    let mut out = vec![];
+    bsdl.emit(&mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+-- BSDL description of XC_TEST, package PQ208\n\
+\n\
+entity XC_TEST is\n\
+\n\
+generic (PHYSICAL_PIN_MAP : string := \"PQ208\");\n\
+\n\
+port (\n\
+\tDONE: out bit;\n\
+\tIO_P1: inout bit;\n\
+\tIO_P1_P7: in bit;\n\
+\tTDI: in bit;\n\
+\tTDO: out bit;\n\
+\tVCC: linkage bit_vector (1 to 2)\n\
+);\n\
+\n\
+use STD_1149_1_2001.all;\n\
+\n\
+attribute COMPONENT_CONFORMANCE of XC_TEST : entity is \"STD_1149_1_2001\";\n\
+attribute PIN_MAP of XC_TEST : entity is PHYSICAL_PIN_MAP;\n\
+\n\
+constant PQ208 : PIN_MAP_STRING :=\n\
+\t\"DONE:P2,\" &\n\
+\t\"IO_P1:P1,\" &\n\
+\t\"IO_P1_P7:P7,\" &\n\
+\t\"TDI:P3,\" &\n\
+\t\"TDO:P4,\" &\n\
+\t\"VCC:(P5,P6);\" &\n\
+\t\"\";\n\
+\n\
+attribute TAP_SCAN_IN of TDI : signal is true;\n\
+attribute TAP_SCAN_MODE of TMS : signal is true;\n\
+attribute TAP_SCAN_OUT of TDO : signal is true;\n\
+attribute TAP_SCAN_CLOCK of TCK : signal is (1.0e6, BOTH);\n\
+\n\
+attribute INSTRUCTION_LENGTH of XC_TEST : entity is 5;\n\
+attribute INSTRUCTION_OPCODE of XC_TEST : entity is\n\
+\t\"EXTEST (00000),\" &\n\
+\t\"SAMPLE (00001),\" &\n\
+\t\"USER1 (00010),\" &\n\
+\t\"IDCODE (01001),\" &\n\
+\t\"BYPASS (11111)\";\n\
+attribute INSTRUCTION_CAPTURE of XC_TEST : entity is \"XXX01\";\n\
+attribute INSTRUCTION_PRIVATE of XC_TEST : entity is \"USER1\";\n\
+\n\
+attribute IDCODE_REGISTER of XC_TEST : entity is \"XXXX0001001000110100000010010011\";\n\
+\n\
+attribute BOUNDARY_LENGTH of XC_TEST : entity is 7;\n\
+attribute BOUNDARY_REGISTER of XC_TEST : entity is\n\
+\t-- cell, port, function, safe [, ccell, disval, rslt]\n\
+\t\"6 (BC_1, *, internal, X),\" &\n\
+\t\"5 (BC_1, IO_P1_P7, input, X),\" &\n\
+\t\"4 (BC_1, DONE, output3, X, 3, 0, Z),\" &\n\
+\t\"3 (BC_1, *, control, 0),\" &\n\
+\t\"2 (BC_1, IO_P1, input, X),\" &\n\
+\t\"1 (BC_1, IO_P1, output3, X, 0, 1, Z),\" &\n\
+\t\"0 (BC_1, *, control, 1)\";\n\
+\n\
+end XC_TEST;\n"
+    );
+}
@@ -0,0 +1,20 @@
+use prjcombine_types::diff::{DbDiff, DiffChange, DiffNode};
+
+#[test]
+fn insert_duplicate() {
+    let mut old = DiffNode::new();
+    old.insert_leaf("pin A1", "IOB0");
+    let mut new = old.clone();
+    new.insert_leaf("pin A1", "IOB1");
+    assert_eq!(
+        new.children.keys().collect::<Vec<_>>(),
+        ["pin A1", "pin A1 #2"]
+    );
+    let diff = DbDiff::new(&old, &new);
+    assert_eq!(diff.entries.len(), 1);
+    assert_eq!(diff.entries[0].path, ["pin A1 #2"]);
+    assert_eq!(
+        diff.entries[0].change,
+        DiffChange::Added(Some("IOB1".into()))
+    );
+}
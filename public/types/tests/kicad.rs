@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use prjcombine_types::{
+    kicad::{KicadPin, KicadPinType, KicadSymbol, KicadUnit, emit_library},
+    pinout::{DiffPolarity, PinKind, Pinout, PinoutPin},
+};
+
+fn sample() -> Pinout {
+    let mut pinout = Pinout::new("xc-test", "ff/256");
+    for (pin, io, bank, diff) in [
+        ("A10", "X0Y1.0", "1", Some(DiffPolarity::P)),
+        ("A2", "X0Y1.1", "1", Some(DiffPolarity::N)),
+        ("B1", "X0Y2.0", "2", None),
+        ("B2", "X0Y2.1", "2", None),
+        ("C1", "X0Y3.0", "10", None),
+    ] {
+        let mut pin = PinoutPin::new(pin, format!("IOB_{io}"), PinKind::Io)
+            .with_io(io)
+            .with_bank(bank);
+        pin.diff = diff;
+        pinout.add_pin(pin);
+    }
+    for (pin, name) in [
+        ("G1", "MGTRXP0_112"),
+        ("G2", "MGTTXP0_112"),
+        ("G3", "MGTAVCC_112"),
+    ] {
+        pinout.add_pin(
+            PinoutPin::new(pin, name, PinKind::Gt)
+                .with_name(name)
+                .with_bank("112"),
+        );
+    }
+    for (pin, pad, kind) in [
+        ("D4", "TCK", PinKind::Config),
+        ("D5", "TDO", PinKind::Config),
+        ("D6", "DONE", PinKind::Config),
+        ("C3", "GND", PinKind::Ground),
+        ("AA2", "VCCINT", PinKind::Power),
+        ("E6", "NC", PinKind::Nc),
+    ] {
+        pinout.add_pin(PinoutPin::new(pin, pad, kind));
+    }
+    pinout.add_pin(PinoutPin::new("AA1", "VCCO1", PinKind::Power).with_bank("1"));
+    let partners = HashMap::from_iter(
+        [("X0Y1.0", "X0Y1.1"), ("X0Y1.1", "X0Y1.0")].map(|(a, b)| (a.to_string(), b.to_string())),
+    );
+    pinout.finish(&partners);
+    pinout
+}
+
+fn pin(number: &str, name: &str, typ: KicadPinType) -> KicadPin {
+    KicadPin {
+        number: number.to_string(),
+        name: name.to_string(),
+        typ,
+    }
+}
+
+fn unit(name: &str, pins_left: Vec<KicadPin>, pins_right: Vec<KicadPin>) -> KicadUnit {
+    KicadUnit {
+        name: name.to_string(),
+        pins_left,
+        pins_right,
+    }
+}
+
+#[test]
+fn kicad_units() {
+    use KicadPinType::*;
+    let symbol = KicadSymbol::from_pinout(&sample());
+    assert_eq!(symbol.name, "xc-test_ff_256");
+    assert_eq!(symbol.description, "xc-test in ff/256 package");
+    // one unit per IO bank, in natural bank order, then the other pin groups;
+    // power units have the supplies on the left and the grounds on the right
+    assert_eq!(
+        symbol.units,
+        [
+            unit(
+                "BANK 1",
+                vec![pin("A2", "IO_1", Bidirectional)],
+                vec![pin("A10", "IO_1", Bidirectional)],
+            ),
+            unit(
+                "BANK 2",
+                vec![pin("B1", "IO_2", Bidirectional)],
+                vec![pin("B2", "IO_2", Bidirectional)],
+            ),
+            unit("BANK 10", vec![pin("C1", "IO_10", Bidirectional)], vec![]),
+            unit(
+                "GT BANK 112",
+                vec![
+                    pin("G1", "MGTRXP0_112", Input),
+                    pin("G2", "MGTTXP0_112", Output),
+                ],
+                vec![pin("G3", "MGTAVCC_112", PowerIn)],
+            ),
+            unit("CONFIG", vec![pin("D6", "DONE", Bidirectional)], vec![]),
+            unit(
+                "JTAG",
+                vec![pin("D4", "TCK", Input)],
+                vec![pin("D5", "TDO", Output)],
+            ),
+            unit(
+                "POWER",
+                vec![pin("AA1", "VCCO_1", PowerIn), pin("AA2", "VCCINT", PowerIn)],
+                vec![pin("C3", "GND", PowerIn)],
+            ),
+            unit("OTHER", vec![pin("E6", "NC", NoConnect)], vec![]),
+        ]
+    );
+}
+
+#[test]
+fn kicad_emit() {
+    let pinout = sample();
+    let symbol = KicadSymbol::from_pinout(&pinout);
+    let mut buf = vec![];
+    emit_library(&mut buf, &[symbol]).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(
+        lines[0],
+        "(kicad_symbol_lib (version 20220914) (generator prjcombine)"
+    );
+    assert_eq!(
+        lines[1],
+        "  (symbol \"xc-test_ff_256\" (in_bom yes) (on_board yes)"
+    );
+    assert_eq!(lines[lines.len() - 1], ")");
+
+    // every unit is a numbered sub-symbol, with its body and its pins on the grid
+    let units: Vec<_> = lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("    (symbol "))
+        .collect();
+    assert_eq!(
+        units,
+        [
+            "\"xc-test_ff_256_1_1\" (unit_name \"BANK 1\")",
+            "\"xc-test_ff_256_2_1\" (unit_name \"BANK 2\")",
+            "\"xc-test_ff_256_3_1\" (unit_name \"BANK 10\")",
+            "\"xc-test_ff_256_4_1\" (unit_name \"GT BANK 112\")",
+            "\"xc-test_ff_256_5_1\" (unit_name \"CONFIG\")",
+            "\"xc-test_ff_256_6_1\" (unit_name \"JTAG\")",
+            "\"xc-test_ff_256_7_1\" (unit_name \"POWER\")",
+            "\"xc-test_ff_256_8_1\" (unit_name \"OTHER\")",
+        ]
+    );
+    let start = lines
+        .iter()
+        .position(|line| line.contains("(unit_name \"JTAG\")"))
+        .unwrap();
+    assert_eq!(
+        lines[start + 1..start + 4],
+        [
+            "      (rectangle (start -7.62 2.54) (end 7.62 -2.54) (stroke (width 0.254) (type default)) (fill (type background)))",
+            "      (pin input line (at -12.70 0.00 0) (length 5.08) (name \"TCK\" (effects (font (size 1.27 1.27)))) (number \"D4\" (effects (font (size 1.27 1.27)))))",
+            "      (pin output line (at 12.70 0.00 180) (length 5.08) (name \"TDO\" (effects (font (size 1.27 1.27)))) (number \"D5\" (effects (font (size 1.27 1.27)))))",
+        ]
+    );
+
+    // every package pin is drawn exactly once
+    for pin in &pinout.pins {
+        let number = format!("(number \"{pin}\" ", pin = pin.pin);
+        assert_eq!(text.matches(&number).count(), 1, "{pin}", pin = pin.pin);
+    }
+}
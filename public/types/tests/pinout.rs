@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use prjcombine_types::pinout::{
+    ConstraintFormat, DiffPolarity, PinKind, Pinout, PinoutPin, pin_sort_key,
+};
+
+fn emit(f: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+    let mut buf = vec![];
+    f(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+fn sample() -> Pinout {
+    let mut pinout = Pinout::new("xc-test", "pq208");
+    for (pin, pad, io, bank, diff, function) in [
+        (
+            "A10",
+            "IOB1P",
+            "X0Y1.0",
+            "1",
+            Some(DiffPolarity::P),
+            Some("GCLK0"),
+        ),
+        ("A2", "IOB1N", "X0Y1.1", "1", Some(DiffPolarity::N), None),
+        // the other half of the pair is not bonded
+        ("B1", "IOB2P", "X0Y2.0", "2", Some(DiffPolarity::P), None),
+        ("B2", "IOB3", "X0Y3.0", "2", None, Some("VREF")),
+    ] {
+        let mut pin = PinoutPin::new(pin, pad, PinKind::Io)
+            .with_io(io)
+            .with_bank(bank);
+        pin.diff = diff;
+        if let Some(function) = function {
+            pin = pin.with_function(function);
+        }
+        pinout.add_pin(pin);
+    }
+    pinout.add_pin(PinoutPin::new("AA1", "VCCO1", PinKind::Power).with_bank("1"));
+    pinout.add_pin(PinoutPin::new("C3", "GND", PinKind::Ground));
+    pinout.add_pin(PinoutPin::new("D4", "TCK", PinKind::Config));
+    pinout.add_pin(PinoutPin::new("E5", "DXP", PinKind::Other).with_name("DXP, diode"));
+    let partners = HashMap::from_iter(
+        [
+            ("X0Y1.0", "X0Y1.1"),
+            ("X0Y1.1", "X0Y1.0"),
+            ("X0Y2.0", "X0Y2.1"),
+        ]
+        .map(|(a, b)| (a.to_string(), b.to_string())),
+    );
+    pinout.finish(&partners);
+    pinout
+}
+
+#[test]
+fn pin_order() {
+    let mut pins = ["AA1", "Y9", "A10", "B1", "A2", "A"];
+    pins.sort_by_key(|pin| pin_sort_key(pin));
+    assert_eq!(pins, ["A", "A2", "A10", "B1", "Y9", "AA1"]);
+}
+
+#[test]
+fn pinout_csv() {
+    let pinout = sample();
+    assert_eq!(
+        emit(|o| pinout.emit_csv(o)),
+        "\
+pin,pad,name,kind,bank,io,diff,diff_pin,functions
+A2,IOB1N,IO_1,io,1,X0Y1.1,N,A10,
+A10,IOB1P,IO_GCLK0_1,io,1,X0Y1.0,P,A2,GCLK0
+B1,IOB2P,IO_2,io,2,X0Y2.0,,,
+B2,IOB3,IO_VREF_2,io,2,X0Y3.0,,,VREF
+C3,GND,GND,ground,,,,,
+D4,TCK,TCK,config,,,,,
+E5,DXP,\"DXP, diode\",other,,,,,
+AA1,VCCO1,VCCO_1,power,1,,,,
+"
+    );
+}
+
+#[test]
+fn pinout_constraints() {
+    let pinout = sample();
+    assert_eq!(
+        emit(|o| pinout.emit_constraints(o, ConstraintFormat::Xdc)),
+        "\
+# xc-test pq208 pin constraints
+
+# set_property PACKAGE_PIN A2 [get_ports {io_A2}] # IOB1N, bank 1, diff N with A10
+# set_property PACKAGE_PIN A10 [get_ports {io_A10}] # IOB1P, bank 1, diff P with A2, GCLK0
+# set_property PACKAGE_PIN B1 [get_ports {io_B1}] # IOB2P, bank 2
+# set_property PACKAGE_PIN B2 [get_ports {io_B2}] # IOB3, bank 2, VREF
+"
+    );
+    // the other formats only differ in the constraint itself
+    for (format, constraint) in [
+        (ConstraintFormat::Ucf, "NET \"io_A2\" LOC = \"A2\";"),
+        (ConstraintFormat::Pcf, "set_io io_A2 A2"),
+        (ConstraintFormat::Lpf, "LOCATE COMP \"io_A2\" SITE \"A2\";"),
+    ] {
+        assert_eq!(format.to_string().parse::<ConstraintFormat>(), Ok(format));
+        let text = emit(|o| pinout.emit_constraints(o, format));
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 6, "{format}");
+        assert_eq!(
+            lines[2],
+            format!("# {constraint} # IOB1N, bank 1, diff N with A10"),
+            "{format}"
+        );
+    }
+}
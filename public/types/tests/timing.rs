@@ -0,0 +1,74 @@
+use prjcombine_types::{
+    speed::{EdgeArrival, EdgeDelay, SetupHoldRf, TimeRange},
+    timing::{ClockEdge, TimingGraph, TimingNodeKind, TimingRegister},
+    units::{Scalar, Time},
+};
+
+fn ns(t: f64) -> Time {
+    Time(Scalar(t))
+}
+
+fn point(t: f64) -> TimeRange {
+    TimeRange::point(ns(t))
+}
+
+#[test]
+fn rise_fall_propagation() {
+    let mut graph = TimingGraph::new();
+    let a = graph.add_node("A", TimingNodeKind::Input("A".into()));
+    let c = graph.add_node("C", TimingNodeKind::Input("C".into()));
+    let x = graph.add_node("X", TimingNodeKind::Internal);
+    let o = graph.add_node("O", TimingNodeKind::Output("O".into()));
+    let clk = graph.add_node("CLK", TimingNodeKind::Internal);
+    let q = graph.add_node("Q", TimingNodeKind::Internal);
+    let oq = graph.add_node("OQ", TimingNodeKind::Output("OQ".into()));
+    graph.add_edge(a, x, EdgeDelay::pos_unate(point(1.0), point(2.0)));
+    graph.add_edge(x, o, EdgeDelay::neg_unate(point(10.0), point(20.0)));
+    graph.add_edge(c, clk, EdgeDelay::pos_unate(point(1.0), point(3.0)));
+    graph.add_edge(q, oq, EdgeDelay::ZERO);
+    graph.add_edge(x, q, EdgeDelay::ZERO);
+    graph.add_register(TimingRegister {
+        name: "R".into(),
+        clk,
+        edge: ClockEdge::Falling,
+        q,
+        clk_to_q: EdgeDelay::from_edge(point(5.0), point(7.0)),
+        checks: vec![(
+            x,
+            SetupHoldRf {
+                rise_setup: ns(0.5),
+                rise_hold: ns(0.0),
+                fall_setup: ns(1.5),
+                fall_hold: ns(0.0),
+            },
+        )],
+        min_period: ns(4.0),
+    });
+
+    let report = graph.analyze();
+    // the rising output edge comes from the falling edge at X and vice versa
+    assert_eq!(
+        report.pin_to_pin[&("A".to_string(), "O".to_string())],
+        EdgeArrival {
+            rise: Some(point(12.0)),
+            fall: Some(point(21.0)),
+        }
+    );
+    // the register is clocked by the falling edge of C
+    assert_eq!(
+        report.clock_to_out[&("C".to_string(), "OQ".to_string())],
+        EdgeArrival {
+            rise: Some(point(8.0)),
+            fall: Some(point(10.0)),
+        }
+    );
+    assert_eq!(
+        report.setup_hold[&("A".to_string(), "C".to_string())],
+        SetupHoldRf {
+            rise_setup: ns(-1.5),
+            rise_hold: ns(2.0),
+            fall_setup: ns(0.5),
+            fall_hold: ns(1.0),
+        }
+    );
+}
@@ -1,9 +1,15 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
-use prjcombine_interconnect::grid::{DieId, TileIobId};
+use prjcombine_entity::{EntityId, id::EntityTag};
+use prjcombine_interconnect::grid::{DieId, DieTag, TileIobId};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign},
+};
 use std::collections::BTreeMap;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -46,7 +52,40 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "M2" => CfgPad::M2,
+            "PROG_B" => CfgPad::ProgB,
+            "INIT_B" => CfgPad::InitB,
+            "RDWR_B" => CfgPad::RdWrB,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            "HSWAP_EN" => CfgPad::HswapEn,
+            "CFGBVS" => CfgPad::CfgBvs,
+            "POR_OVERRIDE" => CfgPad::PorOverride,
+            _ => {
+                for (prefix, pad) in [("DATA", CfgPad::Data as fn(u8) -> CfgPad)] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown config pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtPad {
     RxP(u8),
     RxN(u8),
@@ -77,7 +116,36 @@ impl std::fmt::Display for GtPad {
     }
 }
 
+impl std::str::FromStr for GtPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "AVCC" => GtPad::AVcc,
+            "RREF" => GtPad::RRef,
+            "AVTTRCAL" => GtPad::AVttRCal,
+            "AVTT" => GtPad::AVtt,
+            _ => {
+                for (prefix, pad) in [
+                    ("CLKP", GtPad::ClkP as fn(u8) -> GtPad),
+                    ("CLKN", GtPad::ClkN),
+                    ("RXP", GtPad::RxP),
+                    ("RXN", GtPad::RxN),
+                    ("TXP", GtPad::TxP),
+                    ("TXN", GtPad::TxN),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown GT pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtRegion {
     All,
     L,
@@ -114,7 +182,31 @@ impl std::fmt::Display for GtRegion {
     }
 }
 
+impl std::str::FromStr for GtRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ALL" => GtRegion::All,
+            "L" => GtRegion::L,
+            "R" => GtRegion::R,
+            "LS" => GtRegion::LS,
+            "RS" => GtRegion::RS,
+            "LN" => GtRegion::LN,
+            "RN" => GtRegion::RN,
+            "LC" => GtRegion::LC,
+            "RC" => GtRegion::RC,
+            "LLC" => GtRegion::LLC,
+            "RLC" => GtRegion::RLC,
+            "LUC" => GtRegion::LUC,
+            "RUC" => GtRegion::RUC,
+            _ => return Err(format!("unknown GT region {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtRegionPad {
     AVtt,
     AVcc,
@@ -133,7 +225,22 @@ impl std::fmt::Display for GtRegionPad {
     }
 }
 
+impl std::str::FromStr for GtRegionPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "AVTT" => GtRegionPad::AVtt,
+            "AVCC" => GtRegionPad::AVcc,
+            "VCCAUX" => GtRegionPad::VccAux,
+            "VCCINT" => GtRegionPad::VccInt,
+            _ => return Err(format!("unknown GT region pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SysMonPad {
     VP,
     VN,
@@ -148,7 +255,20 @@ impl std::fmt::Display for SysMonPad {
     }
 }
 
+impl std::str::FromStr for SysMonPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "VP" => SysMonPad::VP,
+            "VN" => SysMonPad::VN,
+            _ => return Err(format!("unknown sysmon pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PsPad {
     Mio(u32),
     Clk,
@@ -225,7 +345,59 @@ impl std::fmt::Display for PsPad {
     }
 }
 
+impl std::str::FromStr for PsPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLK" => PsPad::Clk,
+            "POR_B" => PsPad::PorB,
+            "SRST_B" => PsPad::SrstB,
+            "DDR_DRST_B" => PsPad::DdrDrstB,
+            "DDR_ACT_N" => PsPad::DdrActN,
+            "DDR_ALERT_N" => PsPad::DdrAlertN,
+            "DDR_PARITY" => PsPad::DdrParity,
+            "DDR_ZQ" => PsPad::DdrZq,
+            "ERROR_OUT" => PsPad::ErrorOut,
+            "ERROR_STATUS" => PsPad::ErrorStatus,
+            "DONE" => PsPad::Done,
+            "INIT_B" => PsPad::InitB,
+            "PROG_B" => PsPad::ProgB,
+            "JTAG_TCK" => PsPad::JtagTck,
+            "JTAG_TDI" => PsPad::JtagTdi,
+            "JTAG_TDO" => PsPad::JtagTdo,
+            "JTAG_TMS" => PsPad::JtagTms,
+            "PAD_I" => PsPad::PadI,
+            "PAD_O" => PsPad::PadO,
+            _ => {
+                for (prefix, pad) in [
+                    ("DDR_DQS_P", PsPad::DdrDqsP as fn(u32) -> PsPad),
+                    ("DDR_DQS_N", PsPad::DdrDqsN),
+                    ("DDR_CS_B", PsPad::DdrCsB),
+                    ("DDR_CKP", PsPad::DdrCkP),
+                    ("DDR_CKN", PsPad::DdrCkN),
+                    ("DDR_CKE", PsPad::DdrCke),
+                    ("DDR_ODT", PsPad::DdrOdt),
+                    ("DDR_DQ", PsPad::DdrDq),
+                    ("DDR_DM", PsPad::DdrDm),
+                    ("DDR_BA", PsPad::DdrBa),
+                    ("DDR_BG", PsPad::DdrBg),
+                    ("DDR_A", PsPad::DdrA),
+                    ("MODE", PsPad::Mode),
+                    ("MIO", PsPad::Mio),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown PS pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HbmPad {
     Vcc,
     VccIo,
@@ -246,7 +418,23 @@ impl std::fmt::Display for HbmPad {
     }
 }
 
+impl std::str::FromStr for HbmPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "VCC" => HbmPad::Vcc,
+            "VCCIO" => HbmPad::VccIo,
+            "VCCAUX" => HbmPad::VccAux,
+            "RSVD" => HbmPad::Rsvd,
+            "RSVD_GND" => HbmPad::RsvdGnd,
+            _ => return Err(format!("unknown HBM pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RfDacPad {
     VOutP(u8),
     VOutN(u8),
@@ -271,7 +459,31 @@ impl std::fmt::Display for RfDacPad {
     }
 }
 
+impl std::str::FromStr for RfDacPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLKP" => RfDacPad::ClkP,
+            "CLKN" => RfDacPad::ClkN,
+            "REXT" => RfDacPad::RExt,
+            "SYSREFP" => RfDacPad::SysRefP,
+            "SYSREFN" => RfDacPad::SysRefN,
+            _ => {
+                if let Some(idx) = s.strip_prefix("VOUT").and_then(|s| s.strip_suffix('P')) {
+                    RfDacPad::VOutP(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("VOUT").and_then(|s| s.strip_suffix('N')) {
+                    RfDacPad::VOutN(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown RF DAC pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RfAdcPad {
     VInP(u8),
     VInN(u8),
@@ -302,7 +514,43 @@ impl std::fmt::Display for RfAdcPad {
     }
 }
 
+impl std::str::FromStr for RfAdcPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLKP" => RfAdcPad::ClkP,
+            "CLKN" => RfAdcPad::ClkN,
+            "REXT" => RfAdcPad::RExt,
+            "PLL_TEST_OUT_P" => RfAdcPad::PllTestOutP,
+            "PLL_TEST_OUT_N" => RfAdcPad::PllTestOutN,
+            _ => {
+                if let Some(idx) = s.strip_prefix("VCM") {
+                    RfAdcPad::VCm(parse_num(idx)?)
+                } else if let Some(idx) = s
+                    .strip_prefix("VIN_PAIR")
+                    .and_then(|s| s.strip_suffix("_P"))
+                {
+                    RfAdcPad::VInPairP(parse_num(idx)?)
+                } else if let Some(idx) = s
+                    .strip_prefix("VIN_PAIR")
+                    .and_then(|s| s.strip_suffix("_N"))
+                {
+                    RfAdcPad::VInPairN(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("VIN").and_then(|s| s.strip_suffix("_P")) {
+                    RfAdcPad::VInP(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("VIN").and_then(|s| s.strip_suffix("_N")) {
+                    RfAdcPad::VInN(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown RF ADC pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     // bank, bel idx
     Hpio(u32, TileIobId),
@@ -436,12 +684,123 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn split(s: &str) -> Result<(&str, &str), String> {
+            s.split_once('_')
+                .ok_or_else(|| format!("invalid bond pad \"{s}\""))
+        }
+        fn parse_io(s: &str) -> Result<(u32, TileIobId), String> {
+            let (bank, idx) = split(s)?;
+            Ok((parse_num(bank)?, TileIobId::from_idx(parse_num(idx)?)))
+        }
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "VCCBRAM" => BondPad::VccBram,
+            "VCC_BATT" => BondPad::VccBatt,
+            "DXN" => BondPad::Dxn,
+            "DXP" => BondPad::Dxp,
+            "RSVD" => BondPad::Rsvd,
+            "RSVDGND" => BondPad::RsvdGnd,
+            "VCC_PS_AUX" => BondPad::VccPsAux,
+            "VCC_PS_PLL" => BondPad::VccPsPll,
+            "SYSMON_VREFP" => BondPad::SysMonVRefP,
+            "SYSMON_VREFN" => BondPad::SysMonVRefN,
+            "SYSMON_GND" => BondPad::SysMonGnd,
+            "SYSMON_VCC" => BondPad::SysMonVcc,
+            "PS_SYSMON_GND" => BondPad::PsSysMonGnd,
+            "PS_SYSMON_VCC" => BondPad::PsSysMonVcc,
+            "VCCAUX_HPIO" => BondPad::VccAuxHpio,
+            "VCCAUX_HDIO" => BondPad::VccAuxHdio,
+            "VCCAUX_XP5IO" => BondPad::VccAuxXp5io,
+            "VCCAUX_IO" => BondPad::VccAuxIo,
+            "VCCINT_IO" => BondPad::VccIntIo,
+            "VCCINT_HPIO" => BondPad::VccIntHpio,
+            "VCCINT_XP5IO" => BondPad::VccIntXp5io,
+            "VCC_PS_INT_LP" => BondPad::VccPsIntLp,
+            "VCC_PS_INT_FP" => BondPad::VccPsIntFp,
+            "VCC_PS_INT_FP_DDR" => BondPad::VccPsIntFpDdr,
+            "VCC_PS_BATT" => BondPad::VccPsBatt,
+            "VCC_PS_DDR_PLL" => BondPad::VccPsDdrPll,
+            "VCCINT_VCU" => BondPad::VccIntVcu,
+            "GND_SENSE" => BondPad::GndSense,
+            "VCCINT_SENSE" => BondPad::VccIntSense,
+            "VCCINT_AMS" => BondPad::VccIntAms,
+            "VCC_SDFEC" => BondPad::VccSdfec,
+            "RFDAC_GND" => BondPad::RfDacGnd,
+            "RFDAC_AGND" => BondPad::RfDacSubGnd,
+            "RFDAC_AVCC" => BondPad::RfDacAVcc,
+            "RFDAC_AVCCAUX" => BondPad::RfDacAVccAux,
+            "RFDAC_AVTT" => BondPad::RfDacAVtt,
+            "RFADC_GND" => BondPad::RfAdcGnd,
+            "RFADC_SUBGND" => BondPad::RfAdcSubGnd,
+            "RFADC_AVCC" => BondPad::RfAdcAVcc,
+            "RFADC_AVCCAUX" => BondPad::RfAdcAVccAux,
+            _ => {
+                if let Some(rest) = s.strip_prefix("HPIOB_") {
+                    let (bank, idx) = parse_io(rest)?;
+                    BondPad::Hpio(bank, idx)
+                } else if let Some(rest) = s.strip_prefix("HDIOBLC_") {
+                    let (bank, idx) = parse_io(rest)?;
+                    BondPad::HdioLc(bank, idx)
+                } else if let Some(rest) = s.strip_prefix("HDIOB_") {
+                    let (bank, idx) = parse_io(rest)?;
+                    BondPad::Hdio(bank, idx)
+                } else if let Some(rest) = s.strip_prefix("XP5IOB_") {
+                    let (bank, idx) = parse_io(rest)?;
+                    BondPad::Xp5io(bank, idx)
+                } else if let Some(bank) =
+                    s.strip_prefix("IO_").and_then(|s| s.strip_suffix("_VREF"))
+                {
+                    BondPad::IoVref(parse_num(bank)?)
+                } else if let Some(bank) =
+                    s.strip_prefix("XP5IO_").and_then(|s| s.strip_suffix("_VR"))
+                {
+                    BondPad::Xp5ioVr(parse_num(bank)?)
+                } else if let Some(rest) = s.strip_prefix("GT_") {
+                    let (region, pad) = split(rest)?;
+                    BondPad::GtRegion(region.parse()?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("GT") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::Gt(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("SYSMON_") {
+                    let (die, pad) = split(rest)?;
+                    BondPad::SysMon(parse_id(die, DieTag::PREFIX)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("PS") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::IoPs(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("HBM") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::Hbm(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("RFDAC") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::RfDac(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("RFADC") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::RfAdc(parse_num(bank)?, pad.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCO") {
+                    BondPad::VccO(parse_num(bank)?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     // ×32 total, but 0-3 are dedicated; high 16 bits are also low 16 bits of Addr
     Data(u8),
@@ -552,4 +911,27 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            if kw != "pin" {
+                return Err(line.error(format!("unknown bond statement {kw}")));
+            }
+            let (pin, pad) = line.check(split_assign(val))?;
+            pins.insert(pin.to_string(), line.check(pad.parse())?);
+        }
+        Ok(Bond { pins })
+    }
 }
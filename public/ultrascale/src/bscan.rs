@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::bscan::{BScanBuilder, BScanPad};
+
+use crate::expanded::{ExpandedDevice, IoCoord};
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub io: BTreeMap<IoCoord, BScanPad>,
+}
+
+impl ExpandedDevice<'_> {
+    /// The chain follows the order of the expanded IO list within each die.  On devices with
+    /// several SLRs, the primary die comes first and the others follow in die order.
+    /// Dedicated configuration pads are not modelled.
+    pub fn get_bscan(&self) -> BScan {
+        let mut io = BTreeMap::new();
+        let mut builder = BScanBuilder::new();
+        let primary = self.interposer.primary;
+        let dies = std::iter::once(primary).chain(self.chips.ids().filter(|&die| die != primary));
+        for die in dies {
+            for &crd in &self.io {
+                if crd.cell().die == die {
+                    io.insert(crd, builder.get_toi());
+                }
+            }
+        }
+        BScan {
+            bits: builder.bits,
+            io,
+        }
+    }
+}
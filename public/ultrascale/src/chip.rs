@@ -6,9 +6,13 @@ use prjcombine_entity::{
 };
 use prjcombine_interconnect::{
     dir::DirH,
-    grid::{ColId, DieId, RowId, TileIobId},
+    grid::{ColId, ColTag, DieId, DieTag, IobTag, RowId, TileIobId},
 };
-use std::collections::BTreeSet;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{Line, TextError, TextParser, parse_id, parse_num, split_list},
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct RegTag;
 impl EntityTag for RegTag {
@@ -18,6 +22,7 @@ impl EntityTagArith for RegTag {}
 pub type RegId = EntityIdU8<RegTag>;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Ultrascale,
     UltrascalePlus,
@@ -32,12 +37,26 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ultrascale" => ChipKind::Ultrascale,
+            "ultrascaleplus" => ChipKind::UltrascalePlus,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interposer {
     pub primary: DieId,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: EntityVec<ColId, Column>,
@@ -54,6 +73,7 @@ pub struct Chip {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConfigKind {
     Config,
     Csec,
@@ -76,7 +96,21 @@ impl std::fmt::Display for ConfigKind {
     }
 }
 
+impl std::str::FromStr for ConfigKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "config" => ConfigKind::Config,
+            "csec" => ConfigKind::Csec,
+            "csec_v2" => ConfigKind::CsecV2,
+            _ => return Err(format!("unknown config kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnKind {
     // both W and E; W can only be plain
     CleL(CleLKind),
@@ -146,7 +180,59 @@ impl std::fmt::Display for ColumnKind {
     }
 }
 
+impl std::str::FromStr for ColumnKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "clel" => ColumnKind::CleL(CleLKind::Plain),
+            "clel[dcg10]" => ColumnKind::CleL(CleLKind::Dcg10),
+            "clem" => ColumnKind::CleM(CleMKind::Plain),
+            "clem + clkbuf" => ColumnKind::CleM(CleMKind::ClkBuf),
+            "clem + laguna" => ColumnKind::CleM(CleMKind::Laguna),
+            "bram" => ColumnKind::Bram(BramKind::Plain),
+            "bram[td]" => ColumnKind::Bram(BramKind::Td),
+            "bram + auxclmp" => ColumnKind::Bram(BramKind::AuxClmp),
+            "bram + auxclmp?" => ColumnKind::Bram(BramKind::AuxClmpMaybe),
+            "bram + bramclmp" => ColumnKind::Bram(BramKind::BramClmp),
+            "bram + bramclmp?" => ColumnKind::Bram(BramKind::BramClmpMaybe),
+            "dsp" => ColumnKind::Dsp(DspKind::Plain),
+            "dsp + clkbuf" => ColumnKind::Dsp(DspKind::ClkBuf),
+            "uram" => ColumnKind::Uram,
+            "sdfec" => ColumnKind::Sdfec,
+            "dfe_b" => ColumnKind::DfeB,
+            "dfe_c" => ColumnKind::DfeC,
+            "dfe_df" => ColumnKind::DfeDF,
+            "dfe_e" => ColumnKind::DfeE,
+            "hdios" => ColumnKind::HdioS,
+            "cont_uram" => ColumnKind::ContUram,
+            "cont_hard" => ColumnKind::ContHard,
+            _ => {
+                if let Some(args) = s.strip_prefix("hard[").and_then(|s| s.strip_suffix(']')) {
+                    let (idx, kind) = args
+                        .split_once(", ")
+                        .ok_or_else(|| format!("invalid column kind {s}"))?;
+                    let kind = match kind {
+                        "clk" => HardKind::Clk,
+                        "!clk" => HardKind::NonClk,
+                        "term" => HardKind::Term,
+                        _ => return Err(format!("unknown hard column kind {kind}")),
+                    };
+                    ColumnKind::Hard(kind, parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("io[").and_then(|s| s.strip_suffix(']')) {
+                    ColumnKind::Io(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix("gt[").and_then(|s| s.strip_suffix(']')) {
+                    ColumnKind::Gt(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown column kind {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CleMKind {
     Plain,
     ClkBuf,
@@ -154,6 +240,7 @@ pub enum CleMKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BramKind {
     Plain,
     AuxClmp,
@@ -164,12 +251,14 @@ pub enum BramKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CleLKind {
     Plain,
     Dcg10,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HardKind {
     Clk,
     NonClk,
@@ -177,18 +266,21 @@ pub enum HardKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DspKind {
     Plain,
     ClkBuf,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     pub kind: ColumnKind,
     pub clk: [Option<u8>; 4],
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HardRowKind {
     None,
     Cfg,
@@ -225,13 +317,38 @@ impl std::fmt::Display for HardRowKind {
     }
 }
 
+impl std::str::FromStr for HardRowKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => HardRowKind::None,
+            "cfg" => HardRowKind::Cfg,
+            "ams" => HardRowKind::Ams,
+            "pcie" => HardRowKind::Pcie,
+            "pcie4c" => HardRowKind::Pcie4C,
+            "pcie4ce" => HardRowKind::Pcie4CE,
+            "cmac" => HardRowKind::Cmac,
+            "ilkn" => HardRowKind::Ilkn,
+            "dfe_a" => HardRowKind::DfeA,
+            "dfe_g" => HardRowKind::DfeG,
+            "hdio" => HardRowKind::Hdio,
+            "hdio[ams]" => HardRowKind::HdioAms,
+            "hdiol" => HardRowKind::HdioL,
+            _ => return Err(format!("unknown hard row kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HardColumn {
     pub col: ColId,
     pub regs: EntityVec<RegId, HardRowKind>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoRowKind {
     None,
     Hpio,
@@ -268,13 +385,38 @@ impl std::fmt::Display for IoRowKind {
     }
 }
 
+impl std::str::FromStr for IoRowKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "none" => IoRowKind::None,
+            "hpio" => IoRowKind::Hpio,
+            "hrio" => IoRowKind::Hrio,
+            "hdiol" => IoRowKind::HdioL,
+            "cp5io" => IoRowKind::Xp5io,
+            "gth" => IoRowKind::Gth,
+            "gty" => IoRowKind::Gty,
+            "gtm" => IoRowKind::Gtm,
+            "gtf" => IoRowKind::Gtf,
+            "hsadc" => IoRowKind::HsAdc,
+            "hsdac" => IoRowKind::HsDac,
+            "rfadc" => IoRowKind::RfAdc,
+            "rfdac" => IoRowKind::RfDac,
+            _ => return Err(format!("unknown IO row kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IoColumn {
     pub col: ColId,
     pub regs: EntityVec<RegId, IoRowKind>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ps {
     pub col: ColId,
     pub has_vcu: bool,
@@ -282,6 +424,7 @@ pub struct Ps {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PsIntfKind {
     Alto,
     Da6,
@@ -304,6 +447,22 @@ impl std::fmt::Display for PsIntfKind {
     }
 }
 
+impl std::str::FromStr for PsIntfKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "alto" => PsIntfKind::Alto,
+            "da6" => PsIntfKind::Da6,
+            "da7" => PsIntfKind::Da7,
+            "da8" => PsIntfKind::Da8,
+            "dc12" => PsIntfKind::Dc12,
+            "mx8" => PsIntfKind::Mx8,
+            _ => return Err(format!("unknown PS interface kind {s}")),
+        })
+    }
+}
+
 impl Ps {
     pub fn height(self) -> usize {
         if self.has_vcu { 240 } else { 180 }
@@ -311,6 +470,7 @@ impl Ps {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisabledPart {
     Region(DieId, RegId),
     TopRow(DieId, RegId),
@@ -351,6 +511,35 @@ impl std::fmt::Display for DisabledPart {
     }
 }
 
+impl std::str::FromStr for DisabledPart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split(' ').collect();
+        let die = |s| parse_id::<DieId>(s, DieTag::PREFIX);
+        let col = |s| parse_id::<ColId>(s, ColTag::PREFIX);
+        let reg = |s| parse_id::<RegId>(s, RegTag::PREFIX);
+        let iob = |s| parse_id::<TileIobId>(s, IobTag::PREFIX);
+        Ok(match words[..] {
+            ["region", d, r] => DisabledPart::Region(die(d)?, reg(r)?),
+            ["top_row", d, r] => DisabledPart::TopRow(die(d)?, reg(r)?),
+            ["hard_ip", d, c, r] => DisabledPart::HardIp(die(d)?, col(c)?, reg(r)?),
+            ["gt", d, c, r] => DisabledPart::Gt(die(d)?, col(c)?, reg(r)?),
+            ["gt_bufs", d, c, r] => DisabledPart::GtBufs(die(d)?, col(c)?, reg(r)?),
+            ["gtm_spare_bufs", d, c, r] => DisabledPart::GtmSpareBufs(die(d)?, col(c)?, reg(r)?),
+            ["hdio", d, c, r, i] => DisabledPart::HdioIob(die(d)?, col(c)?, reg(r)?, iob(i)?),
+            ["hpio", d, c, r, i] => DisabledPart::HpioIob(die(d)?, col(c)?, reg(r)?, iob(i)?),
+            ["hpio_dci", d, c, r] => DisabledPart::HpioDci(die(d)?, col(c)?, reg(r)?),
+            ["dfe"] => DisabledPart::Dfe,
+            ["sdfec"] => DisabledPart::Sdfec,
+            ["ps"] => DisabledPart::Ps,
+            ["vcu"] => DisabledPart::Vcu,
+            ["hbm_left"] => DisabledPart::HbmLeft,
+            _ => return Err(format!("unknown disabled part \"{s}\"")),
+        })
+    }
+}
+
 impl Chip {
     pub const ROWS_PER_REG: usize = 60;
 
@@ -490,9 +679,6 @@ impl Chip {
             if self.cols_fsr_gap.contains(&col) {
                 writeln!(o, "\t\t// FSR gap")?;
             }
-            if matches!(cd.kind, ColumnKind::ContUram | ColumnKind::ContHard) {
-                continue;
-            }
             write!(o, "\t\t{}", cd.kind)?;
             if let ColumnKind::Io(idx) | ColumnKind::Gt(idx) = cd.kind {
                 writeln!(o, " [")?;
@@ -526,18 +712,7 @@ impl Chip {
                 }
                 write!(o, "]")?;
             }
-            if matches!(
-                cd.kind,
-                ColumnKind::Uram
-                    | ColumnKind::Hard(_, _)
-                    | ColumnKind::DfeC
-                    | ColumnKind::DfeDF
-                    | ColumnKind::DfeE
-            ) {
-                write!(o, ", // {col}-{cr}", cr = col + 1)?;
-            } else {
-                write!(o, ", // {col}")?;
-            }
+            write!(o, ", // {col}")?;
             if let Some(ps) = self.ps
                 && ps.col == col
             {
@@ -565,11 +740,230 @@ impl Chip {
 
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns keyed by
+    /// position and their regions by row.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        if let Some(ps) = self.ps {
+            let mut node = DiffNode::leaf(ps.intf_kind);
+            node.insert_leaf("col", ps.col);
+            node.insert_leaf("vcu", ps.has_vcu);
+            res.insert("ps", node);
+        }
+        res.insert_leaf("hbm", self.has_hbm);
+        res.insert_leaf("config", self.config_kind);
+        res.insert_leaf("dmc", self.is_dmc);
+        res.insert_leaf("config_alt", self.is_alt_cfg);
+        for (col, cd) in &self.columns {
+            let mut node = DiffNode::leaf(cd.kind);
+            if let ColumnKind::Io(idx) | ColumnKind::Gt(idx) = cd.kind {
+                for (reg, kind) in &self.cols_io[idx].regs {
+                    node.insert_leaf(format!("reg {row}", row = self.row_reg_bot(reg)), kind);
+                }
+            }
+            if let ColumnKind::Hard(_, idx) = cd.kind {
+                for (reg, kind) in &self.cols_hard[idx].regs {
+                    node.insert_leaf(format!("reg {row}", row = self.row_reg_bot(reg)), kind);
+                }
+            }
+            if cd.clk.iter().any(|x| x.is_some()) {
+                node.insert_leaf(
+                    "clk",
+                    cd.clk
+                        .iter()
+                        .map(|v| match v {
+                            Some(v) => v.to_string(),
+                            None => "-".to_string(),
+                        })
+                        .join(", "),
+                );
+            }
+            res.insert(format!("column {col}"), node);
+        }
+        for col in &self.cols_vbrk {
+            res.insert(format!("col_vbrk {col}"), DiffNode::new());
+        }
+        for col in &self.cols_fsr_gap {
+            res.insert(format!("col_fsr_gap {col}"), DiffNode::new());
+        }
+        res.insert_leaf("regs", self.regs);
+        res
+    }
+}
+
+/// Parses the region list of an IO, GT or hard column, up to the closing `]`.
+fn parse_reg_list<'a, T: std::str::FromStr<Err = String>>(
+    p: &mut TextParser<'a>,
+) -> Result<(EntityVec<RegId, T>, Line<'a>), TextError> {
+    let mut regs = EntityVec::new();
+    loop {
+        let line = p.expect_line()?;
+        if line.text().starts_with(']') {
+            return Ok((regs, line));
+        }
+        regs.push(line.check(line.list_item()?.parse())?);
+    }
+}
+
+impl Chip {
+    fn parse_clk(s: &str) -> Result<[Option<u8>; 4], String> {
+        let items = s
+            .strip_prefix("clk [")
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| format!("invalid column part \"{s}\""))?;
+        let items: Vec<_> = items.split(", ").collect();
+        if items.len() != 4 {
+            return Err(format!("expected 4 clock entries in \"{s}\""));
+        }
+        let mut clk = [None; 4];
+        for (i, item) in items.into_iter().enumerate() {
+            if item != "-" {
+                clk[i] = Some(parse_num(item)?);
+            }
+        }
+        Ok(clk)
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = EntityVec::new();
+        let mut cols_vbrk = BTreeSet::new();
+        let mut cols_fsr_gap = BTreeSet::new();
+        let mut cols_hard = BTreeMap::new();
+        let mut cols_io = BTreeMap::new();
+        let mut regs = None;
+        let mut ps = None;
+        let mut has_hbm = false;
+        let mut config_kind = None;
+        let mut is_dmc = false;
+        let mut is_alt_cfg = false;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            if line.block_start() == Some(("columns", "")) {
+                while !p.try_block_end() {
+                    let col = columns.next_id();
+                    let line = p.expect_line()?;
+                    // IO, GT and hard columns list their regions between `[` and `]`, and
+                    // the rest of the entry follows the `]`.
+                    let (ckind, end, rest) = match line.text().strip_suffix(" [") {
+                        Some(head) => {
+                            let ckind = line.check(head.parse())?;
+                            let end = match ckind {
+                                ColumnKind::Io(idx) | ColumnKind::Gt(idx) => {
+                                    let (regs, end) = parse_reg_list(p)?;
+                                    cols_io.insert(idx, IoColumn { col, regs });
+                                    end
+                                }
+                                ColumnKind::Hard(_, idx) => {
+                                    let (regs, end) = parse_reg_list(p)?;
+                                    cols_hard.insert(idx, HardColumn { col, regs });
+                                    end
+                                }
+                                _ => return Err(line.error("unexpected region list")),
+                            };
+                            (ckind, end, &end.list_item()?[1..])
+                        }
+                        None => {
+                            let item = line.list_item()?;
+                            let (head, rest) =
+                                item.split_at(item.find(" + clk [").unwrap_or(item.len()));
+                            (line.check(head.parse())?, line, rest)
+                        }
+                    };
+                    let clk = match rest.strip_prefix(" + ") {
+                        Some(clk) => end.check(Self::parse_clk(clk))?,
+                        None if rest.is_empty() => [None; 4],
+                        None => return Err(end.error(format!("unexpected \"{}\"", end.text()))),
+                    };
+                    columns.push(Column { kind: ckind, clk });
+                }
+                continue;
+            }
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "ps" => {
+                    let Some((col, intf)) = val.split_once(": ") else {
+                        return Err(line.error(format!("invalid ps \"{val}\"")));
+                    };
+                    let (intf, has_vcu) = match intf.strip_suffix(" + vcu") {
+                        Some(intf) => (intf, true),
+                        None => (intf, false),
+                    };
+                    ps = Some(Ps {
+                        col: line.check(parse_id(col, ColTag::PREFIX))?,
+                        has_vcu,
+                        intf_kind: line.check(intf.parse())?,
+                    });
+                }
+                "hbm" => has_hbm = true,
+                "config" => config_kind = Some(line.check(val.parse())?),
+                "dmc" => is_dmc = true,
+                "config_alt" => is_alt_cfg = true,
+                "cols_vbrk" => {
+                    for col in split_list(val) {
+                        cols_vbrk.insert(line.check(parse_id(col, ColTag::PREFIX))?);
+                    }
+                }
+                "cols_fsr_gap" => {
+                    for col in split_list(val) {
+                        cols_fsr_gap.insert(line.check(parse_id(col, ColTag::PREFIX))?);
+                    }
+                }
+                "regs" => regs = Some(line.check(parse_num(val))?),
+                _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+            }
+        }
+        if cols_io.keys().copied().ne(0..cols_io.len()) {
+            return Err(p.missing("IO column"));
+        }
+        if cols_hard.keys().copied().ne(0..cols_hard.len()) {
+            return Err(p.missing("hard column"));
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns,
+            cols_vbrk,
+            cols_fsr_gap,
+            cols_hard: cols_hard.into_values().collect(),
+            cols_io: cols_io.into_values().collect(),
+            regs: regs.ok_or_else(|| p.missing("regs"))?,
+            ps,
+            has_hbm,
+            config_kind: config_kind.ok_or_else(|| p.missing("config"))?,
+            is_dmc,
+            is_alt_cfg,
+        })
+    }
 }
 
 impl Interposer {
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut primary = None;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "primary" => primary = Some(line.check(parse_id(val, DieTag::PREFIX))?),
+                _ => return Err(line.error(format!("unknown interposer statement {kw}"))),
+            }
+        }
+        Ok(Interposer {
+            primary: primary.ok_or_else(|| p.missing("primary"))?,
+        })
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         writeln!(o, "\tprimary {};", self.primary)?;
         Ok(())
     }
+
+    /// Builds the diff tree of the interposer, one entry per field.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("primary", self.primary);
+        res
+    }
 }
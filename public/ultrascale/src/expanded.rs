@@ -11,6 +11,7 @@ use crate::chip::{
 use crate::bond::SharedCfgPad;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClkSrc {
     DspSplitter(ColId),
     Gt(ColId),
@@ -20,24 +21,28 @@ pub enum ClkSrc {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HpioCoord {
     pub cell: CellCoord,
     pub iob: TileIobId,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HdioCoord {
     pub cell: CellCoord,
     pub iob: TileIobId,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Xp5ioCoord {
     pub cell: CellCoord,
     pub iob: TileIobId,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoCoord {
     Hpio(HpioCoord),
     Hdio(HdioCoord),
@@ -45,7 +50,18 @@ pub enum IoCoord {
     Xp5io(Xp5ioCoord),
 }
 
+impl IoCoord {
+    pub fn cell(self) -> CellCoord {
+        match self {
+            IoCoord::Hpio(crd) => crd.cell,
+            IoCoord::Hdio(crd) | IoCoord::HdioLc(crd) => crd.cell,
+            IoCoord::Xp5io(crd) => crd.cell,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoKind {
     Hpio,
     Hrio,
@@ -54,6 +70,7 @@ pub enum IoKind {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoDiffKind {
     None,
     P(IoCoord),
@@ -61,6 +78,7 @@ pub enum IoDiffKind {
 }
 
 #[derive(Copy, Clone, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IoInfo {
     pub kind: IoKind,
     pub bank: u32,
@@ -73,6 +91,7 @@ pub struct IoInfo {
 }
 
 #[derive(Copy, Clone, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtInfo {
     pub kind: IoRowKind,
     pub bank: u32,
@@ -1,8 +1,10 @@
 pub mod bond;
+pub mod bscan;
 pub mod chip;
 pub mod db;
 pub mod defs;
 mod expand;
 pub mod expanded;
+pub mod pinout;
 
 pub use expand::expand_grid;
@@ -0,0 +1,312 @@
+//! Package pinout tables.
+
+use std::{collections::HashMap, fmt::Write};
+
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::grid::TileIobId;
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad, GtPad, SharedCfgPad},
+    chip::{ChipKind, DisabledPart},
+    expanded::{ExpandedDevice, IoCoord, IoDiffKind, IoInfo, IoKind},
+};
+
+fn io_name(io: IoCoord) -> String {
+    match io {
+        IoCoord::Hpio(crd) => format!("{cell}.HPIOB{iob:#}", cell = crd.cell, iob = crd.iob),
+        IoCoord::Hdio(crd) => format!("{cell}.HDIOB{iob:#}", cell = crd.cell, iob = crd.iob),
+        IoCoord::HdioLc(crd) => format!("{cell}.HDIOBLC{iob:#}", cell = crd.cell, iob = crd.iob),
+        IoCoord::Xp5io(crd) => format!("{cell}.XP5IOB{iob:#}", cell = crd.cell, iob = crd.iob),
+    }
+}
+
+fn io_key(io: IoCoord, bank: u32) -> (u8, u32, TileIobId) {
+    match io {
+        IoCoord::Hpio(crd) => (0, bank, crd.iob),
+        IoCoord::Hdio(crd) => (1, bank, crd.iob),
+        IoCoord::HdioLc(crd) => (2, bank, crd.iob),
+        IoCoord::Xp5io(crd) => (3, bank, crd.iob),
+    }
+}
+
+impl ExpandedDevice<'_> {
+    /// Builds the vendor pin function name of an IO.  Only the configuration
+    /// functions of the primary die are part of the name, and Zynq devices
+    /// leave out the ones that only matter for the fabric configuration modes.
+    fn io_function_name(&self, io: IoCoord, info: &IoInfo) -> String {
+        let pchip = self.chips[self.interposer.primary];
+        let is_csec = pchip.config_kind.is_csec();
+        let is_zynq = pchip.ps.is_some() && !self.disabled.contains(&DisabledPart::Ps);
+        let mut res = "IO".to_string();
+        match io {
+            IoCoord::Hdio(crd) => {
+                let idx = crd.iob.to_idx();
+                write!(res, "_L{}{}", 1 + idx / 2, ['P', 'N'][idx % 2]).unwrap();
+            }
+            IoCoord::HdioLc(crd) => {
+                let chip = self.chips[crd.cell.die];
+                let idx = if crd.cell.row == chip.row_rclk(crd.cell.row) {
+                    42 + crd.iob.to_idx()
+                } else {
+                    crd.iob.to_idx()
+                };
+                write!(res, "_L{}{}", 1 + idx / 2, ['P', 'N'][idx % 2]).unwrap();
+            }
+            IoCoord::Hpio(crd) => {
+                let idx = crd.iob.to_idx();
+                let group = idx / 13;
+                if idx % 13 != 12 {
+                    let pair = 1 + group * 6 + idx % 13 / 2;
+                    write!(res, "_L{pair}{}", ['P', 'N'][idx % 13 % 2]).unwrap();
+                }
+                let half = if idx % 13 < 6 { 'L' } else { 'U' };
+                write!(res, "_T{group}{half}_N{}", idx % 13).unwrap();
+            }
+            IoCoord::Xp5io(crd) => {
+                let idx = crd.iob.to_idx();
+                write!(res, "_L{}{}", idx / 2, ['P', 'N'][idx % 2]).unwrap();
+            }
+        }
+        if info.is_gc {
+            res += if info.kind == IoKind::Hdio {
+                "_HDGC"
+            } else {
+                "_GC"
+            };
+        }
+        if info.is_dbc {
+            res += "_DBC";
+        }
+        if info.is_qbc {
+            res += "_QBC";
+        }
+        if info.is_vrp {
+            res += "_VRP";
+        }
+        if let IoCoord::Xp5io(crd) = io {
+            let nibble = crd.iob.to_idx() / 6;
+            let npin = crd.iob.to_idx() % 6;
+            if npin < 2 {
+                res += "_XCC";
+            }
+            write!(res, "_N{nibble}P{npin}").unwrap();
+        }
+        if let Some(sm) = info.sm_pair {
+            let pn = match info.diff {
+                IoDiffKind::N(_) => 'N',
+                _ => 'P',
+            };
+            write!(res, "_AD{sm}{pn}").unwrap();
+        }
+        let cfg = self.cfg_io[self.interposer.primary]
+            .get_by_right(&io)
+            .copied();
+        match cfg {
+            Some(SharedCfgPad::Data(d)) if !is_zynq => {
+                if d >= 16 && !is_csec {
+                    write!(res, "_A{:02}", d - 16).unwrap();
+                }
+                write!(res, "_D{d:02}").unwrap();
+                if (4..12).contains(&d) && is_csec {
+                    write!(res, "_OSPID{:02}", d - 4).unwrap();
+                }
+            }
+            Some(SharedCfgPad::Addr(a)) if !is_zynq => write!(res, "_A{a}").unwrap(),
+            Some(SharedCfgPad::Rs(a)) if !is_zynq => write!(res, "_RS{a}").unwrap(),
+            Some(SharedCfgPad::EmCclk) if !is_zynq => res += "_EMCCLK",
+            Some(SharedCfgPad::Dout) if !is_zynq => res += "_DOUT_CSO_B",
+            Some(SharedCfgPad::FweB) if !is_zynq => res += "_FWE_FCS2_B",
+            Some(SharedCfgPad::FoeB) if !is_zynq => res += "_FOE_B",
+            Some(SharedCfgPad::CsiB) if !is_zynq => {
+                res += if is_csec { "_CSI_B" } else { "_CSI_ADV_B" };
+            }
+            Some(SharedCfgPad::Busy) if !is_zynq => res += "_BUSY",
+            Some(SharedCfgPad::Fcs1B) if !is_zynq => res += "_FCS1_B",
+            Some(SharedCfgPad::OspiDs) if !is_zynq => res += "_OSPI_DS",
+            Some(SharedCfgPad::OspiEccFail) if !is_zynq => res += "_OSPI_ECC_FAIL",
+            Some(SharedCfgPad::OspiRstB) if !is_zynq => res += "_OSPI_RST_B",
+            Some(SharedCfgPad::PudcB) if !is_zynq => res += "_PUDC_B",
+            Some(SharedCfgPad::PerstN0) => {
+                res += if is_csec { "_PERSTN0_B" } else { "_PERSTN0" };
+            }
+            Some(SharedCfgPad::PerstN1) => res += "_PERSTN1",
+            Some(SharedCfgPad::SmbAlert) => res += "_SMBALERT",
+            Some(SharedCfgPad::I2cSclk) => res += "_I2C_SCLK",
+            Some(SharedCfgPad::I2cSda) => {
+                res += if self.kind == ChipKind::Ultrascale || is_csec {
+                    "_I2C_SDA"
+                } else {
+                    "_PERSTN1_I2C_SDA"
+                };
+            }
+            _ => (),
+        }
+        write!(res, "_{}", info.bank).unwrap();
+        res
+    }
+
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let mut ios = HashMap::new();
+        for &io in &self.io {
+            let info = self.get_io_info(io);
+            ios.insert(io_key(io, info.bank), (io, info));
+        }
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Hpio(..)
+                | BondPad::Hdio(..)
+                | BondPad::HdioLc(..)
+                | BondPad::Xp5io(..) => PinKind::Io,
+                BondPad::IoPs(..) => PinKind::Ps,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::Gt(..) | BondPad::GtRegion(..) => PinKind::Gt,
+                BondPad::SysMon(..)
+                | BondPad::SysMonVRefP
+                | BondPad::SysMonVRefN
+                | BondPad::SysMonGnd
+                | BondPad::SysMonVcc
+                | BondPad::PsSysMonGnd
+                | BondPad::PsSysMonVcc => PinKind::SysMon,
+                BondPad::IoVref(_)
+                | BondPad::VccInt
+                | BondPad::VccAux
+                | BondPad::VccBram
+                | BondPad::VccAuxHpio
+                | BondPad::VccAuxHdio
+                | BondPad::VccAuxXp5io
+                | BondPad::VccAuxIo
+                | BondPad::VccIntIo
+                | BondPad::VccIntHpio
+                | BondPad::VccIntXp5io
+                | BondPad::VccO(_)
+                | BondPad::VccBatt
+                | BondPad::VccPsAux
+                | BondPad::VccPsPll
+                | BondPad::VccPsIntLp
+                | BondPad::VccPsIntFp
+                | BondPad::VccPsIntFpDdr
+                | BondPad::VccPsBatt
+                | BondPad::VccPsDdrPll
+                | BondPad::VccIntVcu
+                | BondPad::VccIntAms
+                | BondPad::VccSdfec
+                | BondPad::RfDacAVcc
+                | BondPad::RfDacAVccAux
+                | BondPad::RfDacAVtt
+                | BondPad::RfAdcAVcc
+                | BondPad::RfAdcAVccAux => PinKind::Power,
+                BondPad::Gnd
+                | BondPad::GndSense
+                | BondPad::RfDacGnd
+                | BondPad::RfDacSubGnd
+                | BondPad::RfAdcGnd
+                | BondPad::RfAdcSubGnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::Xp5ioVr(_)
+                | BondPad::Rsvd
+                | BondPad::RsvdGnd
+                | BondPad::Dxp
+                | BondPad::Dxn
+                | BondPad::VccIntSense
+                | BondPad::Hbm(..)
+                | BondPad::RfDac(..)
+                | BondPad::RfAdc(..) => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Hpio(bank, iob)
+                | BondPad::Hdio(bank, iob)
+                | BondPad::HdioLc(bank, iob)
+                | BondPad::Xp5io(bank, iob) => {
+                    ppin = ppin.with_bank(bank);
+                    let kind = match pad {
+                        BondPad::Hpio(..) => 0,
+                        BondPad::Hdio(..) => 1,
+                        BondPad::HdioLc(..) => 2,
+                        _ => 3,
+                    };
+                    let Some(&(io, info)) = ios.get(&(kind, bank, iob)) else {
+                        res.add_pin(ppin);
+                        continue;
+                    };
+                    ppin = ppin.with_io(io_name(io));
+                    ppin = ppin.with_function(match info.kind {
+                        IoKind::Hpio => "HP",
+                        IoKind::Hrio => "HR",
+                        IoKind::Hdio => "HD",
+                        IoKind::Xp5io => "XP5",
+                    });
+                    let partner = |other| match (io, other) {
+                        // HDIOLC pairs are reported with plain HDIO coordinates
+                        (IoCoord::HdioLc(_), IoCoord::Hdio(crd)) => IoCoord::HdioLc(crd),
+                        _ => other,
+                    };
+                    let polarity = match info.diff {
+                        IoDiffKind::P(other) => {
+                            ppin.diff = Some(DiffPolarity::P);
+                            partners.insert(io_name(io), io_name(partner(other)));
+                            "P"
+                        }
+                        IoDiffKind::N(other) => {
+                            ppin.diff = Some(DiffPolarity::N);
+                            partners.insert(io_name(io), io_name(partner(other)));
+                            "N"
+                        }
+                        IoDiffKind::None => "",
+                    };
+                    let die = match io {
+                        IoCoord::Hpio(crd) => crd.cell.die,
+                        IoCoord::Hdio(crd) | IoCoord::HdioLc(crd) => crd.cell.die,
+                        IoCoord::Xp5io(crd) => crd.cell.die,
+                    };
+                    if let Some(cpad) = self.cfg_io[die].get_by_right(&io) {
+                        ppin = ppin.with_function(cpad);
+                    }
+                    for (cond, function) in [
+                        (info.is_gc, "GC"),
+                        (info.is_qbc, "QBC"),
+                        (info.is_dbc, "DBC"),
+                        (info.is_vrp, "VRP"),
+                    ] {
+                        if cond {
+                            ppin = ppin.with_function(function);
+                        }
+                    }
+                    if let Some(pair) = info.sm_pair {
+                        ppin = ppin.with_function(format!("AD{pair}{polarity}"));
+                    }
+                    ppin = ppin.with_name(self.io_function_name(io, &info));
+                }
+                BondPad::IoVref(bank) => {
+                    ppin = ppin
+                        .with_bank(bank)
+                        .with_name(format!("VREF_{bank}"))
+                        .with_function("VREF");
+                }
+                BondPad::Gt(bank, gpad) => {
+                    let name = match gpad {
+                        GtPad::ClkP(idx) => format!("MGTREFCLK{idx}P_{bank}"),
+                        GtPad::ClkN(idx) => format!("MGTREFCLK{idx}N_{bank}"),
+                        _ => format!("MGT{gpad}_{bank}"),
+                    };
+                    ppin = ppin.with_bank(bank).with_name(name);
+                }
+                BondPad::Xp5ioVr(bank)
+                | BondPad::VccO(bank)
+                | BondPad::IoPs(bank, _)
+                | BondPad::Hbm(bank, _)
+                | BondPad::RfDac(bank, _)
+                | BondPad::RfAdc(bank, _) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::{BTreeSet, HashMap};
+
+use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_types::bscan::BScanPad;
+use prjcombine_ultrascale::{bond::BondPad, db::Database, expand_grid, expanded::IoCoord};
+
+const ULTRASCALE_DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascale.zstd"
+);
+const ULTRASCALEPLUS_DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascaleplus.zstd"
+);
+
+fn pad_bits(pad: BScanPad) -> Vec<usize> {
+    match pad {
+        BScanPad::Input(i) | BScanPad::Output(i) => vec![i],
+        BScanPad::OutputTristate(o, t)
+        | BScanPad::OutputEnable(o, t)
+        | BScanPad::BiTristate(o, t) => vec![o, t],
+        BScanPad::InputOutputTristate(i, o, t) | BScanPad::InputOutputEnable(i, o, t) => {
+            vec![i, o, t]
+        }
+    }
+}
+
+fn check_bscan(db: &str, devices: &[&str]) {
+    let db = Database::from_file(db).unwrap();
+    for &name in devices {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let chips: EntityVec<DieId, _> = dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+        let interposer = &db.interposers[dev.interposer];
+        let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int);
+        let bscan = edev.get_bscan();
+
+        // Every IO has a cell, and the cells cover the chain without overlap.
+        assert_eq!(bscan.io.len(), edev.io.len(), "{name}");
+        assert_eq!(bscan.bits, 3 * edev.io.len(), "{name}");
+        let mut bits = BTreeSet::new();
+        for &pad in bscan.io.values() {
+            for bit in pad_bits(pad) {
+                assert!(bits.insert(bit), "{name}: bit {bit} used twice");
+            }
+        }
+        assert_eq!(bits.len(), bscan.bits, "{name}");
+
+        // The chain starts on the primary SLR, then walks the others in die order, and the IO
+        // list within a die.
+        let mut prev: Option<(usize, usize, usize)> = None;
+        let mut order: Vec<_> = edev
+            .io
+            .iter()
+            .enumerate()
+            .map(|(idx, io)| {
+                let die = io.cell().die;
+                let rank = if die == interposer.primary {
+                    0
+                } else {
+                    1 + die.to_idx()
+                };
+                let BScanPad::InputOutputTristate(_, _, t) = bscan.io[io] else {
+                    unreachable!()
+                };
+                (t, rank, idx)
+            })
+            .collect();
+        order.sort();
+        for cur in order {
+            if let Some(prev) = prev {
+                assert!(
+                    (prev.1, prev.2) < (cur.1, cur.2),
+                    "{name}: chain out of order"
+                );
+            }
+            prev = Some(cur);
+        }
+
+        // Every bonded IO pad is on the chain, with a cell of its own.
+        let mut ios = HashMap::new();
+        for &io in &edev.io {
+            let info = edev.get_io_info(io);
+            let kind = match io {
+                IoCoord::Hpio(_) => 0,
+                IoCoord::Hdio(_) => 1,
+                IoCoord::HdioLc(_) => 2,
+                IoCoord::Xp5io(_) => 3,
+            };
+            let iob = match io {
+                IoCoord::Hpio(crd) => crd.iob,
+                IoCoord::Hdio(crd) | IoCoord::HdioLc(crd) => crd.iob,
+                IoCoord::Xp5io(crd) => crd.iob,
+            };
+            ios.insert((kind, info.bank, iob), io);
+        }
+        for (_, package, &bond) in &dev.bonds {
+            let bond = &db.bonds[bond];
+            let mut seen = BTreeSet::new();
+            for (pin, &pad) in &bond.pins {
+                let key = match pad {
+                    BondPad::Hpio(bank, iob) => (0, bank, iob),
+                    BondPad::Hdio(bank, iob) => (1, bank, iob),
+                    BondPad::HdioLc(bank, iob) => (2, bank, iob),
+                    BondPad::Xp5io(bank, iob) => (3, bank, iob),
+                    _ => continue,
+                };
+                let io = ios[&key];
+                let cell = bscan.io[&io];
+                assert!(seen.insert(cell), "{name}-{package}: {pin} shares a cell");
+            }
+        }
+    }
+}
+
+#[test]
+fn bscan_ultrascale() {
+    // xcvu190 has its primary SLR in the middle.
+    check_bscan(ULTRASCALE_DB, &["xcku040", "xcvu190"]);
+}
+
+#[test]
+fn bscan_ultrascaleplus() {
+    check_bscan(ULTRASCALEPLUS_DB, &["xczu3eg", "xcvu9p"]);
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use prjcombine_entity::EntityVec;
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_test_util::check_pinout;
+use prjcombine_types::pinout::{PinKind, Pinout};
+use prjcombine_ultrascale::{db::Database, expand_grid};
+
+const ULTRASCALE_DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascale.zstd"
+);
+const ULTRASCALEPLUS_DB: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascaleplus.zstd"
+);
+
+fn pinout(db: &Database, device: &str, package: &str) -> Pinout {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let chips: EntityVec<DieId, _> = dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+    let interposer = &db.interposers[dev.interposer];
+    let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int);
+    let (_, &bond) = dev.bonds.get(package).unwrap();
+    let pinout = edev.make_pinout(&db.bonds[bond], device, package);
+    // the vendor names every IO pin of a package differently
+    let mut names = HashSet::new();
+    for pin in &pinout.pins {
+        if pin.kind == PinKind::Io {
+            assert!(names.insert(&pin.name), "{device} {package}: {}", pin.name);
+        }
+    }
+    pinout
+}
+
+#[test]
+fn pinout_ultrascale() {
+    let db = Database::from_file(ULTRASCALE_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xcku040", "ffva1156"),
+        &[
+            ("AK17", "IO_L12P_T1U_N10_GC_45", Some("45"), Some("AK16")),
+            ("G10", "IO_L12P_T1U_N10_GC_66", Some("66"), Some("F10")),
+            ("A8", "IO_T0U_N12_VRP_66", Some("66"), None),
+            ("A10", "IO_L4N_T0U_N7_DBC_AD7N_66", Some("66"), Some("B10")),
+            ("K26", "IO_L3P_T0L_N4_AD15P_A26_65", Some("65"), Some("K27")),
+            (
+                "AE10",
+                "IO_L16N_T2U_N7_QBC_AD3N_84",
+                Some("84"),
+                Some("AD10"),
+            ),
+        ],
+    );
+}
+
+#[test]
+fn pinout_ultrascaleplus() {
+    let db = Database::from_file(ULTRASCALEPLUS_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xczu3eg", "sbva484"),
+        &[
+            ("D5", "IO_L7P_HDGC_AD5P_26", Some("26"), Some("C5")),
+            ("A6", "IO_L12N_AD0N_26", Some("26"), Some("B7")),
+            ("A2", "IO_T3U_N12_66", Some("66"), None),
+        ],
+    );
+    check_pinout(
+        &pinout(&db, "xcvu9p", "flga2104"),
+        &[
+            ("B11", "IO_L12P_T1U_N10_GC_71", Some("71"), Some("A11")),
+            ("B10", "IO_L11N_T1U_N9_GC_71", Some("71"), Some("C10")),
+        ],
+    );
+}
@@ -0,0 +1,27 @@
+use prjcombine_types::db::DumpFlags;
+use prjcombine_ultrascale::db::Database;
+
+// No text dump is checked in for these, so they are dumped on the fly.
+const TARGETS: [&str; 2] = ["ultrascale", "ultrascaleplus"];
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+#[test]
+fn dump_roundtrip() {
+    for target in TARGETS {
+        let db = Database::from_file(db_path(target)).unwrap();
+        let mut text = vec![];
+        db.dump(&mut text, DumpFlags::all()).unwrap();
+        let parsed = Database::from_text(std::str::from_utf8(&text).unwrap()).unwrap();
+        // Not assert_eq, the Debug output of a whole database is unreadable.
+        assert!(
+            parsed == db,
+            "{target}: text dump does not match the database"
+        );
+    }
+}
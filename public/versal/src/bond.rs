@@ -1,6 +1,7 @@
 use bincode::{Decode, Encode};
 
 #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -17,6 +17,7 @@ impl EntityTagArith for RegTag {}
 pub type RegId = EntityIdU8<RegTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub columns: EntityVec<ColId, Column>,
     pub cols_vbrk: BTreeSet<ColId>,
@@ -34,6 +35,7 @@ pub struct Chip {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InterposerKind {
     Single,
     Column,
@@ -41,12 +43,14 @@ pub enum InterposerKind {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interposer {
     pub kind: InterposerKind,
     pub sll_columns: EntityVec<DieId, Vec<ColId>>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     pub kind: ColumnKind,
     pub has_bli_s: bool,
@@ -54,6 +58,7 @@ pub struct Column {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CleKind {
     Plain,
     Sll,
@@ -61,6 +66,7 @@ pub enum CleKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BramKind {
     Plain,
     ClkBuf,
@@ -69,6 +75,7 @@ pub enum BramKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnKind {
     Cle(CleKind),
     Bram(BramKind),
@@ -87,6 +94,7 @@ pub enum ColumnKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PsKind {
     Ps9,
     PsX,
@@ -94,6 +102,7 @@ pub enum PsKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CpmKind {
     None,
     Cpm4,
@@ -102,6 +111,7 @@ pub enum CpmKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HardRowKind {
     None,
     Hdio,
@@ -121,12 +131,14 @@ pub enum HardRowKind {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HardColumn {
     pub col: ColId,
     pub regs: EntityVec<RegId, HardRowKind>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtRowKind {
     None,
     Gty,
@@ -143,12 +155,14 @@ pub enum GtRowKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BotKind {
     Xpio(usize),
     Ssit,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TopKind {
     Xpio(usize),
     Ssit,
@@ -159,6 +173,7 @@ pub enum TopKind {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RightKind {
     Term,
     Term2,
@@ -168,6 +183,7 @@ pub enum RightKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NocEndpoint {
     // tile idx, switch idx, port idx
     BotNps(usize, usize, usize),
@@ -184,6 +200,7 @@ pub enum NocEndpoint {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisabledPart {
     HardIp(DieId, ColId, RegId),
     HardIpSite(DieId, ColId, RegId),
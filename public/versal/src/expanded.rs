@@ -1,8 +1,11 @@
-use prjcombine_entity::{EntityBitVec, EntityVec, entity_id};
+use prjcombine_entity::{EntityBitVec, EntityId, EntityRange, EntityVec, entity_id};
 use prjcombine_interconnect::grid::{ColId, DieId, ExpandedGrid, RowId};
+use prjcombine_xilinx_bitstream::{
+    BitstreamGeom, DeviceKind, DieBitstreamGeom, FrameAddr, FrameInfo, FrameMaskMode,
+};
 use std::collections::{BTreeSet, HashMap};
 
-use crate::chip::{Chip, DisabledPart, Interposer};
+use crate::chip::{Chip, DisabledPart, Interposer, RegId};
 
 entity_id! {
     pub id UbumpId u8;
@@ -33,3 +36,64 @@ impl<'a> std::ops::Deref for ExpandedDevice<'a> {
         &self.egrid
     }
 }
+
+impl ExpandedDevice<'_> {
+    /// Builds the CFRAME geometry of the device, for use with
+    /// [`prjcombine_xilinx_bitstream::parse_cfi`].  Every clock region of a die is one CFRAME
+    /// row (the `region` of the frame address), and every grid column is one CFRAME column
+    /// (the `major` of the frame address).  The number of frames in each column is not part
+    /// of the database yet, and has to be supplied by the caller.
+    pub fn bitstream_geom(
+        &self,
+        frame_len: usize,
+        col_frames: impl Fn(DieId, ColId) -> usize,
+    ) -> BitstreamGeom {
+        let die: EntityVec<DieId, _> = self
+            .chips
+            .iter()
+            .map(|(die, chip)| {
+                let mut frame_info = vec![];
+                for reg in chip.regs() {
+                    for col in chip.columns.ids() {
+                        for minor in 0..col_frames(die, col) {
+                            frame_info.push(FrameInfo {
+                                addr: FrameAddr {
+                                    typ: 0,
+                                    region: reg.to_idx() as i32,
+                                    major: col.to_idx() as u32,
+                                    minor: minor as u32,
+                                },
+                                mask_mode: [FrameMaskMode::None].into_iter().collect(),
+                            });
+                        }
+                    }
+                }
+                DieBitstreamGeom {
+                    frame_len,
+                    frame_info,
+                    bram_frame_len: 0,
+                    bram_frame_info: vec![],
+                    iob_frame_len: 0,
+                }
+            })
+            .collect();
+        BitstreamGeom {
+            kind: DeviceKind::Versal,
+            die_order: die.ids().into_iter().collect(),
+            die,
+            has_gtz_bot: false,
+            has_gtz_top: false,
+        }
+    }
+
+    /// Returns the grid column and rows configured by the given CFRAME frame.
+    pub fn frame_cells(&self, die: DieId, addr: FrameAddr) -> (ColId, EntityRange<RowId>) {
+        let chip = self.chips[die];
+        let reg = RegId::from_idx(addr.region as usize);
+        let row = chip.row_reg_bot(reg);
+        (
+            ColId::from_idx(addr.major as usize),
+            EntityRange::new(row.to_idx(), row.to_idx() + Chip::ROWS_PER_REG),
+        )
+    }
+}
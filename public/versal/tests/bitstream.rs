@@ -0,0 +1,203 @@
+use std::collections::{BTreeSet, HashSet};
+
+use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_interconnect::{
+    db::IntDb,
+    grid::{CellCoord, DieId, DieIdExt},
+};
+use prjcombine_versal::{
+    chip::{
+        BotKind, Chip, CleKind, Column, ColumnKind, CpmKind, GtRowKind, Interposer, InterposerKind,
+        PsKind, RightKind, TopKind,
+    },
+    defs,
+    expand::expand_grid,
+    expanded::ExpandedDevice,
+};
+use prjcombine_xilinx_bitstream::{
+    BitPos, Bitstream, BitstreamGeom, DeviceKind, Pdi, PdiImage, PdiPartition, PdiPartitionKind,
+    parse_cfi,
+};
+
+const FRAME_WORDS: usize = 4;
+
+// A small device: the PS in the bottom two clock regions left of the CFRAME column, CLE
+// pairs on both sides of it, and a DSP column.
+fn chip() -> Chip {
+    let column = |kind| Column {
+        kind,
+        has_bli_s: false,
+        has_bli_n: false,
+    };
+    Chip {
+        columns: EntityVec::from_iter([
+            column(ColumnKind::None),
+            column(ColumnKind::Cle(CleKind::Plain)),
+            column(ColumnKind::Cle(CleKind::Plain)),
+            column(ColumnKind::None),
+            column(ColumnKind::Cfrm),
+            column(ColumnKind::Cle(CleKind::Plain)),
+            column(ColumnKind::Cle(CleKind::Plain)),
+            column(ColumnKind::Dsp),
+            column(ColumnKind::ContDsp),
+            column(ColumnKind::None),
+        ]),
+        cols_vbrk: BTreeSet::new(),
+        cols_cpipe: BTreeSet::new(),
+        cols_hard: vec![],
+        regs: 3,
+        regs_gt_left: EntityVec::from_iter([GtRowKind::None; 3]),
+        ps: PsKind::Ps9,
+        cpm: CpmKind::None,
+        has_xram_top: false,
+        is_vr: false,
+        top: TopKind::Xpio(0),
+        bottom: BotKind::Xpio(0),
+        right: RightKind::Term,
+    }
+}
+
+fn intdb() -> IntDb {
+    bincode::decode_from_slice(defs::INIT, bincode::config::standard())
+        .unwrap()
+        .0
+}
+
+fn interposer() -> Interposer {
+    Interposer {
+        kind: InterposerKind::Single,
+        sll_columns: EntityVec::from_iter([vec![]]),
+    }
+}
+
+// The frame counts per column are not in the database; these are arbitrary.
+fn geom(edev: &ExpandedDevice) -> BitstreamGeom {
+    edev.bitstream_geom(FRAME_WORDS * 32, |die, col| {
+        match edev.chips[die].columns[col].kind {
+            ColumnKind::Cle(_) => 2,
+            _ => 1,
+        }
+    })
+}
+
+/// Returns the cells covered by tiles of the given class.
+fn class_cells(edev: &ExpandedDevice, class: &str) -> HashSet<CellCoord> {
+    let tcid = edev.egrid.db.tile_classes.get(class).unwrap().0;
+    edev.egrid
+        .tiles()
+        .filter(|(_, tile)| tile.class == tcid)
+        .flat_map(|(_, tile)| tile.cells.values().copied())
+        .collect()
+}
+
+fn cfi_write(reg: u32, data: &[u32]) -> Vec<u32> {
+    let mut res = vec![1 << 29 | 2 << 27 | reg << 13 | data.len() as u32];
+    res.extend(data);
+    res
+}
+
+// A PDI with a single CFI partition writing the given frames from the given address on.
+fn cfi_pdi(far: u32, frames: &[u32]) -> Pdi {
+    let mut words = vec![0xffffffff, 0xaa995566, 0x20000000];
+    words.extend(cfi_write(4, &[1]));
+    words.extend(cfi_write(1, &[far]));
+    words.extend(cfi_write(2, &[]));
+    words.push(2 << 29 | 2 << 27 | frames.len() as u32);
+    words.extend(frames);
+    words.extend(cfi_write(4, &[0xd]));
+    Pdi {
+        boot_header: None,
+        version: 0x00040000,
+        idcode: 0,
+        attributes: 0,
+        pdi_id: 0,
+        parent_id: 0,
+        images: vec![PdiImage {
+            name: "pl_cfi".to_string(),
+            id: 0x18700000,
+            unique_id: 0,
+            parent_unique_id: 0,
+            function_id: 0,
+            attributes: 0,
+            partitions: vec![PdiPartition {
+                id: 0x1c000000,
+                kind: PdiPartitionKind::Cfi,
+                attributes: 0,
+                exec_addr: 0,
+                load_addr: 0xf6000000,
+                offset: 0,
+                data: words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            }],
+        }],
+    }
+}
+
+#[test]
+fn cframe_geom() {
+    let db = intdb();
+    let chip = chip();
+    let interposer = interposer();
+    let edev = expand_grid(
+        &EntityVec::from_iter([&chip]),
+        &interposer,
+        &BTreeSet::new(),
+        &db,
+    );
+    let geom = geom(&edev);
+    let die = DieId::from_idx(0);
+    assert_eq!(geom.kind, DeviceKind::Versal);
+    assert_eq!(geom.die_order, [die]);
+    // one CFRAME row per clock region, with the frames of every column
+    assert_eq!(geom.die[die].frame_info.len(), 3 * (4 * 2 + 6));
+    let int = class_cells(&edev, "INT");
+    for finfo in &geom.die[die].frame_info {
+        let addr = finfo.addr;
+        let (col, rows) = edev.frame_cells(die, addr);
+        assert_eq!(col.to_idx(), addr.major as usize);
+        assert_eq!(rows.len(), Chip::ROWS_PER_REG);
+        for row in rows {
+            assert_eq!(chip.row_to_reg(row).to_idx(), addr.region as usize);
+            // every frame maps onto interconnect, except in the PS hole
+            let cell = die.cell(col, row);
+            assert_eq!(
+                int.contains(&cell),
+                !chip.in_int_hole(col, row),
+                "{addr:?} {cell}"
+            );
+        }
+    }
+}
+
+#[test]
+fn cfi_to_grid() {
+    let db = intdb();
+    let chip = chip();
+    let interposer = interposer();
+    let edev = expand_grid(
+        &EntityVec::from_iter([&chip]),
+        &interposer,
+        &BTreeSet::new(),
+        &db,
+    );
+    let geom = geom(&edev);
+    let die = DieId::from_idx(0);
+    // both frames of the CLE column 5 in the top clock region
+    let frames: Vec<u32> = (0..2 * FRAME_WORDS as u32).map(|i| 1 << i).collect();
+    let bs = parse_cfi(&geom, &cfi_pdi(5 << 8 | 2 << 18, &frames)).unwrap();
+    let diff = Bitstream::diff(&Bitstream::new(&geom), &bs);
+    assert_eq!(diff.len(), 2 * FRAME_WORDS);
+    let cle = class_cells(&edev, "CLE_BC");
+    for pos in diff.keys() {
+        let &BitPos::Main(pdie, frame, _) = pos else {
+            panic!("unexpected bit {pos:?}");
+        };
+        assert_eq!(pdie, die);
+        let (col, rows) = edev.frame_cells(die, geom.die[die].frame_info[frame].addr);
+        assert_eq!(col.to_idx(), 5);
+        assert_eq!(rows.first().unwrap().to_idx(), 2 * Chip::ROWS_PER_REG);
+        assert!(
+            rows.into_iter()
+                .all(|row| cle.contains(&die.cell(col, row)))
+        );
+    }
+}
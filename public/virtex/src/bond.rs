@@ -1,9 +1,14 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_interconnect::grid::EdgeIoCoord;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_num, split_assign},
+};
 use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -34,7 +39,28 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "PROG_B" => CfgPad::ProgB,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "M2" => CfgPad::M2,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            _ => return Err(format!("unknown config pad {s}")),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Clk(u32),
     Io(EdgeIoCoord),
@@ -65,7 +91,34 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "DXN" => BondPad::Dxn,
+            "DXP" => BondPad::Dxp,
+            _ => {
+                if s.starts_with("IOB_") {
+                    BondPad::Io(s.parse()?)
+                } else if let Some(idx) = s.strip_prefix("CLK") {
+                    BondPad::Clk(parse_num(idx)?)
+                } else if let Some(bank) = s.strip_prefix("VCCO") {
+                    BondPad::VccO(parse_num(bank)?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
     // device bank -> pkg bank
@@ -113,7 +166,7 @@ fn pad_sort_key(name: &str) -> (usize, &str, u32) {
 impl Bond {
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         for (k, v) in &self.io_banks {
-            writeln!(o, "\tbank {k} = {v}")?;
+            writeln!(o, "\tbank {k} = {v};")?;
         }
         for (pin, pad) in self.pins.iter().sorted_by_key(|(k, _)| pad_sort_key(k)) {
             writeln!(o, "\tpin {pin} = {pad};")?;
@@ -129,4 +182,61 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name, plus banks and special pads.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (k, v) in &self.io_banks {
+            res.insert_leaf(format!("bank {k}"), v);
+        }
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        for v in &self.vref {
+            res.insert(format!("vref {v}"), DiffNode::new());
+        }
+        for v in &self.diffp {
+            res.insert(format!("diffp {v}"), DiffNode::new());
+        }
+        for v in &self.diffn {
+            res.insert(format!("diffn {v}"), DiffNode::new());
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Bond {
+            pins: BTreeMap::new(),
+            io_banks: BTreeMap::new(),
+            vref: BTreeSet::new(),
+            diffp: BTreeSet::new(),
+            diffn: BTreeSet::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "bank" => {
+                    let (k, v) = line.check(split_assign(val))?;
+                    res.io_banks
+                        .insert(line.check(parse_num(k))?, line.check(parse_num(v))?);
+                }
+                "pin" => {
+                    let (pin, pad) = line.check(split_assign(val))?;
+                    res.pins.insert(pin.to_string(), line.check(pad.parse())?);
+                }
+                "vref" => {
+                    res.vref.insert(line.check(val.parse())?);
+                }
+                "diffp" => {
+                    res.diffp.insert(line.check(val.parse())?);
+                }
+                "diffn" => {
+                    res.diffn.insert(line.check(val.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(res)
+    }
 }
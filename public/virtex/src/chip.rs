@@ -1,15 +1,20 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
-use prjcombine_entity::{EntityId, EntityRange};
+use prjcombine_entity::{EntityId, EntityRange, id::EntityTag};
 use prjcombine_interconnect::{
     dir::{DirH, DirHV, DirV},
-    grid::{BelCoord, ColId, DieId, DieIdExt, EdgeIoCoord, RowId, TileCoord, TileIobId},
+    grid::{BelCoord, ColId, ColTag, DieId, DieIdExt, EdgeIoCoord, RowId, TileCoord, TileIobId},
+};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign, split_list},
 };
 use std::collections::{BTreeMap, BTreeSet};
 
 use crate::defs::{bslots, tslots};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Virtex,
     VirtexE,
@@ -26,7 +31,21 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "virtex" => ChipKind::Virtex,
+            "virtexe" => ChipKind::VirtexE,
+            "virtexem" => ChipKind::VirtexEM,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     Data(u8), // ×8
     CsB,
@@ -47,17 +66,37 @@ impl std::fmt::Display for SharedCfgPad {
     }
 }
 
+impl std::str::FromStr for SharedCfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CS_B" => SharedCfgPad::CsB,
+            "RDWR_B" => SharedCfgPad::RdWrB,
+            "DOUT" => SharedCfgPad::Dout,
+            "INIT_B" => SharedCfgPad::InitB,
+            _ => match s.strip_prefix('D') {
+                Some(idx) => SharedCfgPad::Data(parse_num(idx)?),
+                None => return Err(format!("unknown config pad {s}")),
+            },
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: usize,
     pub cols_bram: BTreeSet<ColId>,
     pub cols_clkv: Vec<(ColId, ColId, ColId)>,
     pub rows: usize,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub cfg_io: BTreeMap<SharedCfgPad, EdgeIoCoord>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisabledPart {
     // Virtex-E: primary DLLs are disabled
     PrimaryDlls,
@@ -277,4 +316,73 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns and config
+    /// pads keyed by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        res.insert_leaf("columns", self.columns);
+        res.insert_leaf("rows", self.rows);
+        for col in &self.cols_bram {
+            res.insert(format!("col_bram {col}"), DiffNode::new());
+        }
+        for &(col_hclk, col_start, col_end) in &self.cols_clkv {
+            res.insert_leaf(
+                format!("col_clkv {col_hclk}"),
+                format!("{col_start}..{col_end}"),
+            );
+        }
+        for (k, v) in &self.cfg_io {
+            res.insert_leaf(format!("cfg_io {k}"), v);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = None;
+        let mut rows = None;
+        let mut cols_bram = BTreeSet::new();
+        let mut cols_clkv = vec![];
+        let mut cfg_io = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "columns" => columns = Some(line.check(parse_num(val))?),
+                "rows" => rows = Some(line.check(parse_num(val))?),
+                "cols_bram" => {
+                    for col in split_list(val) {
+                        cols_bram.insert(line.check(parse_id(col, ColTag::PREFIX))?);
+                    }
+                }
+                "col_clkv" => {
+                    let (col_hclk, range) = line.check(split_assign(val))?;
+                    let (col_start, col_end) = range
+                        .split_once("..")
+                        .ok_or_else(|| line.error(format!("invalid range \"{range}\"")))?;
+                    cols_clkv.push((
+                        line.check(parse_id(col_hclk, ColTag::PREFIX))?,
+                        line.check(parse_id(col_start, ColTag::PREFIX))?,
+                        line.check(parse_id(col_end, ColTag::PREFIX))?,
+                    ));
+                }
+                "cfg_io" => {
+                    let (pad, io) = line.check(split_assign(val))?;
+                    cfg_io.insert(line.check(pad.parse())?, line.check(io.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns: columns.ok_or_else(|| p.missing("columns"))?,
+            cols_bram,
+            cols_clkv,
+            rows: rows.ok_or_else(|| p.missing("rows"))?,
+            cfg_io,
+        })
+    }
 }
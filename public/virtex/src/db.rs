@@ -1,11 +1,15 @@
 use std::{collections::BTreeSet, error::Error, fs::File, path::Path};
 
 use bincode::{Decode, Encode};
-use prjcombine_entity::{EntityMap, EntityVec};
-use prjcombine_interconnect::db::IntDb;
+use prjcombine_entity::{EntityMap, EntityVec, id::EntityTag};
+use prjcombine_interconnect::{db::IntDb, grid::ColTag};
 use prjcombine_types::{
     bsdata::BsData,
-    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, DumpFlags},
+    db::{
+        BondId, ChipId, ChipTag, DevBondId, DevSpeedId, DeviceCombo, DeviceCombosParser, DumpFlags,
+    },
+    diff::{DbDiff, DiffNode},
+    text::{TextError, TextParser, expect_next_id, parse_id},
 };
 
 use crate::{
@@ -14,6 +18,7 @@ use crate::{
 };
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -23,7 +28,45 @@ pub struct Device {
     pub disabled: BTreeSet<DisabledPart>,
 }
 
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut combos = DeviceCombosParser::default();
+        let mut disabled = BTreeSet::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "disabled" => {
+                    disabled.insert(match val.split_once(' ') {
+                        None if val == "primary_dlls" => DisabledPart::PrimaryDlls,
+                        Some(("bram", col)) => {
+                            DisabledPart::Bram(line.check(parse_id(col, ColTag::PREFIX))?)
+                        }
+                        _ => return Err(line.error(format!("unknown disabled part {val}"))),
+                    });
+                }
+                _ => {
+                    if !line.check(combos.statement(kw, val))? {
+                        return Err(line.error(format!("unknown device statement {kw}")));
+                    }
+                }
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds: combos.bonds,
+            speeds: combos.speeds,
+            combos: combos.combos,
+            disabled,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -49,6 +92,91 @@ impl Database {
         Ok(())
     }
 
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut devices = vec![];
+        let mut int = None;
+        let mut bsdata = None;
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match kw {
+                "chip" => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                "bond" => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                "device" => devices.push(Device::parse(&mut p, name)?),
+                "intdb" => {
+                    int = Some(IntDb::parse(&mut p)?);
+                    // The bitstream data sections make up the rest of the dump.
+                    bsdata = Some(BsData::parse(&mut p)?);
+                }
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            devices,
+            int: int.ok_or_else(|| p.missing("intdb"))?,
+            bsdata: bsdata.ok_or_else(|| p.missing("bitstream data"))?,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips and bonds are compared through the
+    /// devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node());
+            for (_, pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for speed in dev.speeds.values() {
+                node.insert(format!("speed {speed}"), DiffNode::new());
+            }
+            for combo in &dev.combos {
+                node.insert(
+                    format!(
+                        "combo {pkg} {speed}",
+                        pkg = dev.bonds.key(combo.devbond),
+                        speed = dev.speeds[combo.speed]
+                    ),
+                    DiffNode::new(),
+                );
+            }
+            for &dis in &dev.disabled {
+                let dis = match dis {
+                    DisabledPart::PrimaryDlls => "primary_dlls".to_string(),
+                    DisabledPart::Bram(col) => format!("bram {col}"),
+                };
+                node.insert(format!("disabled {dis}"), DiffNode::new());
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("intdb", self.int.diff_node());
+        res.insert("bsdata", self.bsdata.diff_node());
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
@@ -0,0 +1,57 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad},
+    chip::Chip,
+};
+
+impl Chip {
+    /// The bond only records the polarity of differential IOs, not their pairing, so
+    /// `diff_pin` is never filled in.
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let cfg_io: BTreeMap<_, _> = self.cfg_io.iter().map(|(&k, &v)| (v, k)).collect();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(_) | BondPad::Clk(_) => PinKind::Io,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::VccInt | BondPad::VccAux | BondPad::VccO(_) => PinKind::Power,
+                BondPad::Gnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::Dxn | BondPad::Dxp => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Io(io) => {
+                    ppin = ppin.with_bank(self.get_io_bank(io)).with_io(io);
+                    if bond.diffp.contains(&io) {
+                        ppin.diff = Some(DiffPolarity::P);
+                    }
+                    if bond.diffn.contains(&io) {
+                        ppin.diff = Some(DiffPolarity::N);
+                    }
+                    if bond.vref.contains(&io) {
+                        ppin = ppin.with_function("VREF");
+                    }
+                    if let Some(cpad) = cfg_io.get(&io) {
+                        ppin = ppin.with_function(cpad);
+                    }
+                }
+                BondPad::Clk(bank) => {
+                    ppin = ppin.with_bank(bank).with_function("GCK");
+                }
+                BondPad::VccO(bank) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&HashMap::new());
+        res
+    }
+}
@@ -0,0 +1,38 @@
+use prjcombine_test_util::{check_decode_roundtrip, check_emit_roundtrip, sample_config};
+use prjcombine_virtex::db::Database;
+use prjcombine_xilinx_bitstream::{BitstreamMode, KeyData, emit, parse_strict};
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+const DEVICES: [&str; 4] = ["xcv50", "xc2s15", "xcv50e", "xc2s50e"];
+
+#[test]
+fn decode_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    for name in DEVICES {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&dev.disabled, &db.int);
+        check_decode_roundtrip(name, &edev.egrid, &db.bsdata, |config| {
+            let bitstream = edev.encode(&db.bsdata, config).unwrap();
+            let data = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain).unwrap();
+            let parsed = parse_strict(&edev.bs_geom, &data, &KeyData::None).unwrap();
+            edev.decode(&db.bsdata, &parsed).unwrap()
+        });
+    }
+}
+
+#[test]
+fn emit_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    for name in DEVICES {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&dev.disabled, &db.int);
+        let config = sample_config(&edev.egrid, &db.bsdata);
+        let bitstream = edev.encode(&db.bsdata, &config).unwrap();
+        check_emit_roundtrip(
+            name,
+            &edev.bs_geom,
+            &bitstream,
+            &[BitstreamMode::Plain, BitstreamMode::Debug],
+        );
+    }
+}
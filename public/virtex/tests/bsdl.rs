@@ -0,0 +1,32 @@
+use prjcombine_test_util::check_device_bsdl;
+use prjcombine_virtex::{
+    bond::{BondPad, CfgPad},
+    db::Database,
+};
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+
+#[test]
+fn bsdl() {
+    let db = Database::from_file(DB).unwrap();
+    for dev in &db.devices {
+        let chip = &db.chips[dev.chip];
+        let bscan = chip.get_bscan();
+        check_device_bsdl(
+            &dev.name,
+            bscan.bits,
+            dev.bonds.iter().map(|(_, package, &bond)| {
+                let bond = &db.bonds[bond];
+                (package.as_str(), bond, bond.pins.values().copied())
+            }),
+            |bond, package, idcode| chip.make_bsdl(bond, &dev.name, package, idcode),
+            |pad| match pad {
+                BondPad::Io(crd) => bscan.io.get(&crd).copied(),
+                BondPad::Clk(idx) => bscan.clk.get(&idx).copied(),
+                BondPad::Cfg(CfgPad::Tck | CfgPad::Tms | CfgPad::Tdi | CfgPad::Tdo) => None,
+                BondPad::Cfg(cpad) => bscan.cfg.get(&cpad).copied(),
+                _ => None,
+            },
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_types::diff::{DiffChange, DiffEntry};
+use prjcombine_virtex::db::Database;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+
+#[test]
+fn diff_same() {
+    let db = Database::from_file(DB).unwrap();
+    assert!(db.diff(&db).is_empty());
+}
+
+#[test]
+fn diff_renumbered_bonds() {
+    let db = Database::from_file(DB).unwrap();
+    let mut new = db.clone();
+    let num = db.bonds.len();
+    new.bonds = db
+        .bonds
+        .values()
+        .rev()
+        .cloned()
+        .collect::<EntityVec<_, _>>();
+    for dev in &mut new.devices {
+        for bond in dev.bonds.values_mut() {
+            *bond = EntityId::from_idx(num - 1 - bond.to_idx());
+        }
+    }
+    assert!(new != db);
+    assert!(db.diff(&new).is_empty());
+}
+
+#[test]
+fn diff_changes() {
+    let db = Database::from_file(DB).unwrap();
+    let mut new = db.clone();
+    let dev = db.devices.iter().find(|dev| dev.name == "xcv50").unwrap();
+    let (_, pkg, &bid) = dev.bonds.iter().next().unwrap();
+    let (pin, _) = db.bonds[bid].pins.iter().next().unwrap();
+    let pad = new.bonds[bid].pins.remove(pin).unwrap();
+    new.bonds[bid].pins.insert(format!("{pin}X"), pad);
+    let (tname, tile) = new.bsdata.tiles.iter_mut().next().unwrap();
+    let tname = tname.clone();
+    let iname = tile.items.keys().next().unwrap().clone();
+    tile.items.remove(&iname);
+
+    let item = db.bsdata.tiles[&tname]
+        .diff_node()
+        .children
+        .remove(&iname)
+        .unwrap();
+
+    let diff = db.diff(&new);
+    let path = |path: &[&str]| path.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let entries: Vec<_> = diff
+        .entries
+        .iter()
+        .filter(|entry| entry.path[0] == "device xcv50" || entry.path[0] == "bsdata")
+        .collect();
+    let pad = pad.to_string();
+    assert_eq!(
+        entries,
+        [
+            &DiffEntry {
+                path: path(&["bsdata", &format!("bstile {tname}"), &iname]),
+                change: DiffChange::Removed(item.value.clone()),
+            },
+            &DiffEntry {
+                path: path(&[
+                    "device xcv50",
+                    &format!("bond {pkg}"),
+                    &format!("pin {pin}")
+                ]),
+                change: DiffChange::Removed(Some(pad.clone())),
+            },
+            &DiffEntry {
+                path: path(&[
+                    "device xcv50",
+                    &format!("bond {pkg}"),
+                    &format!("pin {pin}X")
+                ]),
+                change: DiffChange::Added(Some(pad)),
+            },
+        ]
+    );
+}
+
+#[test]
+fn diff_chip() {
+    let db = Database::from_file(DB).unwrap();
+    let mut new = db.clone();
+    let dev = db.devices.iter().find(|dev| dev.name == "xcv50").unwrap();
+    new.chips[dev.chip].rows += 4;
+
+    let diff = db.diff(&new);
+    let entries: Vec<_> = diff
+        .entries
+        .iter()
+        .filter(|entry| entry.path[0] == "device xcv50")
+        .collect();
+    let rows = db.chips[dev.chip].rows;
+    assert_eq!(
+        entries,
+        [&DiffEntry {
+            path: vec![
+                "device xcv50".to_string(),
+                "chip".to_string(),
+                "rows".to_string()
+            ],
+            change: DiffChange::Changed(Some(rows.to_string()), Some((rows + 4).to_string())),
+        }]
+    );
+}
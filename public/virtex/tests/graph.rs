@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use prjcombine_interconnect::graph::RoutingNodeMember;
+use prjcombine_virtex::db::Database;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+
+#[test]
+fn routing_graph() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == "xcv50").unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&dev.disabled, &db.int);
+    let graph = edev.egrid.routing_graph();
+    assert!(!graph.pips.is_empty());
+
+    let mut seen = HashSet::new();
+    for (nid, node) in &graph.nodes {
+        assert!(node.members.is_sorted(), "{nid}");
+        for &wire in &node.members {
+            assert!(seen.insert(wire), "{nid}: duplicate member");
+            assert_eq!(graph.node(&edev.egrid, wire), Some(nid));
+        }
+        let class = &graph.node_classes[node.class];
+        assert_eq!(class.slot, node.wire.slot);
+        let mut members: Vec<_> = node
+            .members
+            .iter()
+            .map(|&wire| RoutingNodeMember::new(node.wire, wire))
+            .collect();
+        members.sort();
+        assert_eq!(class.members, members, "{nid}");
+    }
+    for (pid, pip) in &graph.pips {
+        assert!(graph.nodes[pip.node_out].pips_bwd.contains(&pid));
+        assert!(graph.nodes[pip.node_in].pips_fwd.contains(&pid));
+        let class = graph.pip_classes[pip.class];
+        assert_eq!(class.kind, pip.kind);
+        assert_eq!(class.slot_out, pip.tile_wire_out.wire);
+        assert_eq!(class.slot_in, pip.tile_wire_in.wire);
+    }
+    assert!(graph.pip_classes.len() * 10 < graph.pips.len());
+
+    let config = bincode::config::standard();
+    let data = bincode::encode_to_vec(&graph, config).unwrap();
+    let (decoded, _): (prjcombine_interconnect::graph::RoutingGraph, _) =
+        bincode::decode_from_slice(&data, config).unwrap();
+    assert_eq!(decoded, graph);
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+use prjcombine_virtex::db::Database;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+
+#[test]
+fn json_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    let json = serde_json::to_string(&db).unwrap();
+    let new: Database = serde_json::from_str(&json).unwrap();
+    assert!(new == db);
+    // the pretty form reads back the same
+    let pretty = serde_json::to_string_pretty(&db).unwrap();
+    let new: Database = serde_json::from_str(&pretty).unwrap();
+    assert!(new == db);
+}
+
+#[test]
+fn json_layout() {
+    let db = Database::from_file(DB).unwrap();
+    let json = serde_json::to_value(&db).unwrap();
+    // entity collections are arrays in id order, named ones of (name, value) pairs
+    let devices = json["devices"].as_array().unwrap();
+    assert_eq!(devices.len(), db.devices.len());
+    assert_eq!(
+        devices[0]["name"],
+        db.devices.first().unwrap().name.as_str()
+    );
+    let tcls = json["int"]["tile_classes"].as_array().unwrap();
+    assert_eq!(tcls.len(), db.int.tile_classes.len());
+    for ((_, name, _), entry) in db.int.tile_classes.iter().zip(tcls) {
+        assert_eq!(entry[0], name.as_str());
+    }
+}
@@ -0,0 +1,22 @@
+use prjcombine_test_util::check_pinout;
+use prjcombine_virtex::db::Database;
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex.zstd");
+
+// The database has no vendor pair numbers, so the IO names are made of the
+// functions and the bank alone.
+#[test]
+fn pinout() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == "xc2s15").unwrap();
+    let (_, &bond) = dev.bonds.get("vq100").unwrap();
+    let pinout = db.chips[dev.chip].make_pinout(&db.bonds[bond], "xc2s15", "vq100");
+    check_pinout(
+        &pinout,
+        &[
+            ("P3", "IO_7", Some("7"), None),
+            ("P4", "IO_VREF_7", Some("7"), None),
+            ("P16", "IO_VREF_6", Some("6"), None),
+        ],
+    );
+}
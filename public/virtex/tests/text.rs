@@ -0,0 +1,23 @@
+use prjcombine_virtex::db::Database;
+
+const TARGETS: [&str; 1] = ["virtex"];
+
+fn db_path(target: &str, ext: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.{ext}",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+#[test]
+fn text_roundtrip() {
+    for target in TARGETS {
+        let db = Database::from_file(db_path(target, "zstd")).unwrap();
+        let parsed = Database::from_text_file(db_path(target, "txt")).unwrap();
+        // Not assert_eq, the Debug output of a whole database is unreadable.
+        assert!(
+            parsed == db,
+            "{target}: text dump does not match the database"
+        );
+    }
+}
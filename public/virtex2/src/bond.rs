@@ -1,9 +1,14 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_interconnect::grid::EdgeIoCoord;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_num, split_assign},
+};
 use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtPad {
     RxP,
     RxN,
@@ -32,7 +37,27 @@ impl std::fmt::Display for GtPad {
     }
 }
 
+impl std::str::FromStr for GtPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "RXP" => GtPad::RxP,
+            "RXN" => GtPad::RxN,
+            "TXP" => GtPad::TxP,
+            "TXN" => GtPad::TxN,
+            "GNDA" => GtPad::GndA,
+            "VTRX" => GtPad::VtRx,
+            "VTTX" => GtPad::VtTx,
+            "AVCCAUXRX" => GtPad::AVccAuxRx,
+            "AVCCAUXTX" => GtPad::AVccAuxTx,
+            _ => return Err(format!("unknown GT pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -69,7 +94,31 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "M2" => CfgPad::M2,
+            "PROG_B" => CfgPad::ProgB,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            "PWRDWN_B" => CfgPad::PwrdwnB,
+            "HSWAP_EN" => CfgPad::HswapEn,
+            "SUSPEND" => CfgPad::Suspend,
+            _ => return Err(format!("unknown config pad {s}")),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Io(EdgeIoCoord),
     Gt(u32, GtPad),
@@ -104,7 +153,38 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "VCCBATT" => BondPad::VccBatt,
+            "DXN" => BondPad::Dxn,
+            "DXP" => BondPad::Dxp,
+            "RSVD" => BondPad::Rsvd,
+            _ => {
+                if s.starts_with("IOB_") {
+                    BondPad::Io(s.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCO") {
+                    BondPad::VccO(parse_num(bank)?)
+                } else if let Some((bank, pad)) =
+                    s.strip_prefix("GT").and_then(|s| s.split_once('_'))
+                {
+                    BondPad::Gt(parse_num(bank)?, pad.parse()?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
     // device bank -> pkg bank
@@ -150,7 +230,7 @@ fn pad_sort_key(name: &str) -> (usize, &str, u32) {
 impl Bond {
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         for (k, v) in &self.io_banks {
-            writeln!(o, "\tbank {k} = {v}")?;
+            writeln!(o, "\tbank {k} = {v};")?;
         }
         for (pin, pad) in self.pins.iter().sorted_by_key(|(k, _)| pad_sort_key(k)) {
             writeln!(o, "\tpin {pin} = {pad};")?;
@@ -160,4 +240,47 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name, plus banks and VREF pads.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (k, v) in &self.io_banks {
+            res.insert_leaf(format!("bank {k}"), v);
+        }
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        for v in &self.vref {
+            res.insert(format!("vref {v}"), DiffNode::new());
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Bond {
+            pins: BTreeMap::new(),
+            io_banks: BTreeMap::new(),
+            vref: BTreeSet::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "bank" => {
+                    let (k, v) = line.check(split_assign(val))?;
+                    res.io_banks
+                        .insert(line.check(parse_num(k))?, line.check(parse_num(v))?);
+                }
+                "pin" => {
+                    let (pin, pad) = line.check(split_assign(val))?;
+                    res.pins.insert(pin.to_string(), line.check(pad.parse())?);
+                }
+                "vref" => {
+                    res.vref.insert(line.check(val.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(res)
+    }
 }
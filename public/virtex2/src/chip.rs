@@ -1,9 +1,14 @@
 use bincode::{Decode, Encode};
-use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_entity::{EntityId, EntityVec, id::EntityTag};
 use prjcombine_interconnect::db::CellSlotId;
 use prjcombine_interconnect::dir::{Dir, DirH, DirHV, DirV};
 use prjcombine_interconnect::grid::{
-    BelCoord, CellCoord, ColId, DieId, DieIdExt, EdgeIoCoord, RowId, TileCoord, TileIobId,
+    BelCoord, CellCoord, ColId, ColTag, DieId, DieIdExt, EdgeIoCoord, RowId, RowTag, TileCoord,
+    TileIobId,
+};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign},
 };
 use std::collections::BTreeMap;
 
@@ -13,6 +18,7 @@ use crate::iob::{
 };
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Virtex2,
     Virtex2P,
@@ -39,6 +45,24 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "virtex2" => ChipKind::Virtex2,
+            "virtex2p" => ChipKind::Virtex2P,
+            "virtex2px" => ChipKind::Virtex2PX,
+            "spartan3" => ChipKind::Spartan3,
+            "spartan3e" => ChipKind::Spartan3E,
+            "spartan3a" => ChipKind::Spartan3A,
+            "spartan3adsp" => ChipKind::Spartan3ADsp,
+            "fpgacore" => ChipKind::FpgaCore,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 impl ChipKind {
     pub fn is_virtex2(self) -> bool {
         matches!(self, Self::Virtex2 | Self::Virtex2P | Self::Virtex2PX)
@@ -55,6 +79,7 @@ impl ChipKind {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: EntityVec<ColId, Column>,
@@ -75,18 +100,21 @@ pub struct Chip {
     pub dcms: Option<Dcms>,
     pub has_ll: bool,
     pub has_small_int: bool,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub cfg_io: BTreeMap<SharedCfgPad, EdgeIoCoord>,
     pub dci_io: BTreeMap<u32, (EdgeIoCoord, EdgeIoCoord)>,
     pub dci_io_alt: BTreeMap<u32, (EdgeIoCoord, EdgeIoCoord)>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     pub kind: ColumnKind,
     pub io: ColumnIoKind,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnKind {
     Io,
     Clb,
@@ -108,6 +136,7 @@ impl std::fmt::Display for ColumnKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnIoKind {
     None,
     Single,
@@ -143,6 +172,7 @@ impl std::fmt::Display for ColumnIoKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RowIoKind {
     None,
     Single,
@@ -168,6 +198,7 @@ impl std::fmt::Display for RowIoKind {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Dcms {
     Two,
     Four,
@@ -175,6 +206,7 @@ pub enum Dcms {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     Data(u8), // ×8
     CsiB,     // Called CS_B on Virtex 2 and Spartan 3.
@@ -222,7 +254,41 @@ impl std::fmt::Display for SharedCfgPad {
     }
 }
 
+impl std::str::FromStr for SharedCfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CSI_B" => SharedCfgPad::CsiB,
+            "CSO_B" => SharedCfgPad::CsoB,
+            "RDWR_B" => SharedCfgPad::RdWrB,
+            "DOUT" => SharedCfgPad::Dout,
+            "INIT_B" => SharedCfgPad::InitB,
+            "CCLK" => SharedCfgPad::Cclk,
+            "M0" => SharedCfgPad::M0,
+            "M1" => SharedCfgPad::M1,
+            "M2" => SharedCfgPad::M2,
+            "LDC0" => SharedCfgPad::Ldc0,
+            "LDC1" => SharedCfgPad::Ldc1,
+            "LDC2" => SharedCfgPad::Ldc2,
+            "HDC" => SharedCfgPad::Hdc,
+            "HSWAP_EN" => SharedCfgPad::HswapEn,
+            "AWAKE" => SharedCfgPad::Awake,
+            _ => {
+                if let Some(idx) = s.strip_prefix('D') {
+                    SharedCfgPad::Data(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix('A') {
+                    SharedCfgPad::Addr(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown config pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DcmPairKind {
     S,
     SingleS,
@@ -236,12 +302,14 @@ pub enum DcmPairKind {
 }
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DcmPair {
     pub kind: DcmPairKind,
     pub cell: CellCoord,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoDiffKind {
     P(TileIobId),
     N(TileIobId),
@@ -885,10 +953,59 @@ impl Chip {
 }
 
 impl Chip {
+    fn dump_column(&self, col: ColId) -> String {
+        let cd = &self.columns[col];
+        let mut res = match cd.kind {
+            ColumnKind::Io => "io".to_string(),
+            ColumnKind::Clb => "clb".to_string(),
+            ColumnKind::Bram => "bram".to_string(),
+            ColumnKind::BramCont(i) => format!("bram {i}"),
+            ColumnKind::Dsp => "dsp".to_string(),
+        };
+        match cd.io {
+            ColumnIoKind::None => (),
+            ColumnIoKind::Single => res += " + io single",
+            ColumnIoKind::Double(i) => res += &format!(" + io double {i}"),
+            ColumnIoKind::Triple(i) => res += &format!(" + io triple {i}"),
+            ColumnIoKind::Quad(i) => res += &format!(" + io quad {i}"),
+            ColumnIoKind::SingleW => res += " + io single w",
+            ColumnIoKind::SingleE => res += " + io single e",
+            ColumnIoKind::SingleWAlt => res += " + io single w alt",
+            ColumnIoKind::SingleEAlt => res += " + io single e alt",
+            ColumnIoKind::DoubleW(i) => res += &format!(" + io double w {i}"),
+            ColumnIoKind::DoubleE(i) => res += &format!(" + io double e {i}"),
+            ColumnIoKind::DoubleEClk(i) => res += &format!(" + io double e clock {i}"),
+        }
+        if let Some(&(bb, bt)) = self.cols_gt.get(&col) {
+            res += &format!(" + gt [{bb}, {bt}]");
+        }
+        res
+    }
+
+    fn dump_row(rd: RowIoKind) -> String {
+        match rd {
+            RowIoKind::None => "null".to_string(),
+            RowIoKind::Single => "io single".to_string(),
+            RowIoKind::Double(i) => format!("io double {i}"),
+            RowIoKind::Triple(i) => format!("io triple {i}"),
+            RowIoKind::Quad(i) => format!("io quad {i}"),
+            RowIoKind::DoubleS(i) => format!("io double s {i}"),
+            RowIoKind::DoubleN(i) => format!("io double n {i}"),
+        }
+    }
+
+    fn dump_dcms(dcms: Dcms) -> &'static str {
+        match dcms {
+            Dcms::Two => "2",
+            Dcms::Four => "4",
+            Dcms::Eight => "8",
+        }
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         writeln!(o, "\tkind {};", self.kind)?;
         writeln!(o, "\tcolumns {{")?;
-        for (col, cd) in &self.columns {
+        for col in self.columns.ids() {
             if let Some((cl, cr)) = self.cols_clkv {
                 if col == cl {
                     writeln!(o, "\t\t// clock left spine")?;
@@ -900,32 +1017,7 @@ impl Chip {
             if col == self.col_clk {
                 writeln!(o, "\t\t// clock spine")?;
             }
-            write!(o, "\t\t")?;
-            match cd.kind {
-                ColumnKind::Io => write!(o, "io")?,
-                ColumnKind::Clb => write!(o, "clb")?,
-                ColumnKind::Bram => write!(o, "bram")?,
-                ColumnKind::BramCont(i) => write!(o, "bram {i}")?,
-                ColumnKind::Dsp => write!(o, "dsp")?,
-            }
-            match cd.io {
-                ColumnIoKind::None => (),
-                ColumnIoKind::Single => write!(o, " + io single")?,
-                ColumnIoKind::Double(i) => write!(o, " + io double {i}")?,
-                ColumnIoKind::Triple(i) => write!(o, " + io triple {i}")?,
-                ColumnIoKind::Quad(i) => write!(o, " + io quad {i}")?,
-                ColumnIoKind::SingleW => write!(o, " + io single w")?,
-                ColumnIoKind::SingleE => write!(o, " + io single e")?,
-                ColumnIoKind::SingleWAlt => write!(o, " + io single w alt")?,
-                ColumnIoKind::SingleEAlt => write!(o, " + io single e alt")?,
-                ColumnIoKind::DoubleW(i) => write!(o, " + io double w {i}")?,
-                ColumnIoKind::DoubleE(i) => write!(o, " + io double e {i}")?,
-                ColumnIoKind::DoubleEClk(i) => write!(o, " + io double e clock {i}")?,
-            }
-            if let Some(&(bb, bt)) = self.cols_gt.get(&col) {
-                write!(o, " + gt [{bb}, {bt}]")?;
-            }
-            writeln!(o, ", // {col}")?;
+            writeln!(o, "\t\t{cd}, // {col}", cd = self.dump_column(col))?;
         }
         writeln!(o, "\t}}")?;
         writeln!(o, "\tcol_clk {};", self.col_clk)?;
@@ -934,7 +1026,7 @@ impl Chip {
         }
         let mut clkv_idx = 0;
         writeln!(o, "\trows {{")?;
-        for (row, rd) in &self.rows {
+        for (row, &rd) in &self.rows {
             if row == self.rows_hclk[clkv_idx].0 {
                 writeln!(o, "\t\t// clock row")?;
             }
@@ -948,17 +1040,7 @@ impl Chip {
             if row == self.row_mid() {
                 writeln!(o, "\t\t// spine row")?;
             }
-            write!(o, "\t\t")?;
-            match rd {
-                RowIoKind::None => write!(o, "null")?,
-                RowIoKind::Single => write!(o, "io single")?,
-                RowIoKind::Double(i) => write!(o, "io double {i}")?,
-                RowIoKind::Triple(i) => write!(o, "io triple {i}")?,
-                RowIoKind::Quad(i) => write!(o, "io quad {i}")?,
-                RowIoKind::DoubleS(i) => write!(o, "io double s {i}")?,
-                RowIoKind::DoubleN(i) => write!(o, "io double n {i}")?,
-            }
-            write!(o, ", // {row}")?;
+            write!(o, "\t\t{rd}, // {row}", rd = Self::dump_row(rd))?;
             if let Some((row_s, row_n)) = self.rows_ram {
                 if row == row_s {
                     write!(o, " BRAM S TERM")?;
@@ -990,11 +1072,7 @@ impl Chip {
             )?;
         }
         if let Some(dcms) = self.dcms {
-            match dcms {
-                Dcms::Two => writeln!(o, "\tdcms 2;")?,
-                Dcms::Four => writeln!(o, "\tdcms 4;")?,
-                Dcms::Eight => writeln!(o, "\tdcms 8;")?,
-            }
+            writeln!(o, "\tdcms {};", Self::dump_dcms(dcms))?;
         }
         if self.has_ll {
             writeln!(o, "\thas_ll;")?;
@@ -1015,4 +1093,244 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns, rows and
+    /// config pads keyed by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        for col in self.columns.ids() {
+            res.insert_leaf(format!("column {col}"), self.dump_column(col));
+        }
+        res.insert_leaf("col_clk", self.col_clk);
+        if let Some((cl, cr)) = self.cols_clkv {
+            res.insert_leaf("cols_clkv", format!("{cl}, {cr}"));
+        }
+        for (row, &rd) in &self.rows {
+            res.insert_leaf(format!("row {row}"), Self::dump_row(rd));
+        }
+        if let Some(row) = self.row_pci {
+            res.insert_leaf("row_pci", row);
+        }
+        if let Some((rb, rt)) = self.rows_ram {
+            res.insert_leaf("rows_ram", format!("{rb}, {rt}"));
+        }
+        for &(row_hclk, row_start, row_end) in &self.rows_hclk {
+            res.insert_leaf(
+                format!("row_hclk {row_hclk}"),
+                format!("{row_start}..{row_end}"),
+            );
+        }
+        for &(col, row) in &self.holes_ppc {
+            res.insert(format!("ppc {col}, {row}"), DiffNode::new());
+        }
+        if let Some(dcms) = self.dcms {
+            res.insert_leaf("dcms", Self::dump_dcms(dcms));
+        }
+        res.insert_leaf("has_ll", self.has_ll);
+        res.insert_leaf("has_small_int", self.has_small_int);
+        for (k, v) in &self.cfg_io {
+            res.insert_leaf(format!("cfg_io {k}"), v);
+        }
+        for (k, &(vp, vn)) in &self.dci_io {
+            res.insert_leaf(format!("dci {k}"), format!("vp {vp}, vn {vn}"));
+        }
+        for (k, &(vp, vn)) in &self.dci_io_alt {
+            res.insert_leaf(format!("dci alt {k}"), format!("vp {vp}, vn {vn}"));
+        }
+        res
+    }
+
+    fn parse_column(s: &str) -> Result<(Column, Option<(u32, u32)>), String> {
+        let mut parts = s.split(" + ");
+        let kind = match parts.next().unwrap() {
+            "io" => ColumnKind::Io,
+            "clb" => ColumnKind::Clb,
+            "bram" => ColumnKind::Bram,
+            "dsp" => ColumnKind::Dsp,
+            kind => match kind.strip_prefix("bram ") {
+                Some(idx) => ColumnKind::BramCont(parse_num(idx)?),
+                None => return Err(format!("unknown column kind {kind}")),
+            },
+        };
+        let mut io = ColumnIoKind::None;
+        let mut gt = None;
+        for part in parts {
+            if let Some(banks) = part.strip_prefix("gt [").and_then(|s| s.strip_suffix(']')) {
+                let (bb, bt) = banks
+                    .split_once(", ")
+                    .ok_or_else(|| format!("invalid gt banks \"{banks}\""))?;
+                gt = Some((parse_num(bb)?, parse_num(bt)?));
+                continue;
+            }
+            let words: Vec<_> = part.split(' ').collect();
+            io = match words[..] {
+                ["io", "single"] => ColumnIoKind::Single,
+                ["io", "double", i] => ColumnIoKind::Double(parse_num(i)?),
+                ["io", "triple", i] => ColumnIoKind::Triple(parse_num(i)?),
+                ["io", "quad", i] => ColumnIoKind::Quad(parse_num(i)?),
+                ["io", "single", "w"] => ColumnIoKind::SingleW,
+                ["io", "single", "e"] => ColumnIoKind::SingleE,
+                ["io", "single", "w", "alt"] => ColumnIoKind::SingleWAlt,
+                ["io", "single", "e", "alt"] => ColumnIoKind::SingleEAlt,
+                ["io", "double", "w", i] => ColumnIoKind::DoubleW(parse_num(i)?),
+                ["io", "double", "e", i] => ColumnIoKind::DoubleE(parse_num(i)?),
+                ["io", "double", "e", "clock", i] => ColumnIoKind::DoubleEClk(parse_num(i)?),
+                _ => return Err(format!("unknown column part \"{part}\"")),
+            };
+        }
+        Ok((Column { kind, io }, gt))
+    }
+
+    fn parse_row(s: &str) -> Result<RowIoKind, String> {
+        let words: Vec<_> = s.split(' ').collect();
+        Ok(match words[..] {
+            ["null"] => RowIoKind::None,
+            ["io", "single"] => RowIoKind::Single,
+            ["io", "double", i] => RowIoKind::Double(parse_num(i)?),
+            ["io", "triple", i] => RowIoKind::Triple(parse_num(i)?),
+            ["io", "quad", i] => RowIoKind::Quad(parse_num(i)?),
+            ["io", "double", "s", i] => RowIoKind::DoubleS(parse_num(i)?),
+            ["io", "double", "n", i] => RowIoKind::DoubleN(parse_num(i)?),
+            _ => return Err(format!("unknown row kind \"{s}\"")),
+        })
+    }
+
+    fn parse_dci(s: &str) -> Result<(u32, (EdgeIoCoord, EdgeIoCoord)), String> {
+        let (bank, pads) = split_assign(s)?;
+        let (vp, vn) = pads
+            .strip_prefix("vp ")
+            .and_then(|s| s.split_once(", vn "))
+            .ok_or_else(|| format!("invalid dci \"{s}\""))?;
+        Ok((parse_num(bank)?, (vp.parse()?, vn.parse()?)))
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = EntityVec::new();
+        let mut col_clk = None;
+        let mut cols_clkv = None;
+        let mut cols_gt = BTreeMap::new();
+        let mut rows = EntityVec::new();
+        let mut rows_ram = None;
+        let mut rows_hclk = vec![];
+        let mut row_pci = None;
+        let mut holes_ppc = vec![];
+        let mut dcms = None;
+        let mut has_ll = false;
+        let mut has_small_int = false;
+        let mut cfg_io = BTreeMap::new();
+        let mut dci_io = BTreeMap::new();
+        let mut dci_io_alt = BTreeMap::new();
+        let parse_col = |s| parse_id(s, ColTag::PREFIX);
+        let parse_row = |s| parse_id(s, RowTag::PREFIX);
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("columns", "")) => {
+                    while !p.try_block_end() {
+                        let cline = p.expect_line()?;
+                        let (column, gt) = cline.check(Self::parse_column(cline.list_item()?))?;
+                        let col = columns.push(column);
+                        if let Some(gt) = gt {
+                            cols_gt.insert(col, gt);
+                        }
+                    }
+                }
+                Some(("rows", "")) => {
+                    while !p.try_block_end() {
+                        let rline = p.expect_line()?;
+                        rows.push(rline.check(Self::parse_row(rline.list_item()?))?);
+                    }
+                }
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "kind" => kind = Some(line.check(val.parse())?),
+                        "col_clk" => col_clk = Some(line.check(parse_col(val))?),
+                        "cols_clkv" => {
+                            let (cl, cr) = line.check(
+                                val.split_once(", ")
+                                    .ok_or_else(|| format!("invalid columns \"{val}\"")),
+                            )?;
+                            cols_clkv =
+                                Some((line.check(parse_col(cl))?, line.check(parse_col(cr))?));
+                        }
+                        "row_pci" => row_pci = Some(line.check(parse_row(val))?),
+                        "rows_ram" => {
+                            let (rb, rt) = line.check(
+                                val.split_once(", ")
+                                    .ok_or_else(|| format!("invalid rows \"{val}\"")),
+                            )?;
+                            rows_ram =
+                                Some((line.check(parse_row(rb))?, line.check(parse_row(rt))?));
+                        }
+                        "row_hclk" => {
+                            let (row_hclk, range) = line.check(split_assign(val))?;
+                            let (row_start, row_end) = range
+                                .split_once("..")
+                                .ok_or_else(|| line.error(format!("invalid range \"{range}\"")))?;
+                            rows_hclk.push((
+                                line.check(parse_row(row_hclk))?,
+                                line.check(parse_row(row_start))?,
+                                line.check(parse_row(row_end))?,
+                            ));
+                        }
+                        "ppc" => {
+                            let parsed = val.split_once(", ").and_then(|(cols, rows)| {
+                                Some((cols.split_once(':')?.0, rows.split_once(':')?.0))
+                            });
+                            let Some((col, row)) = parsed else {
+                                return Err(line.error(format!("invalid ppc \"{val}\"")));
+                            };
+                            holes_ppc
+                                .push((line.check(parse_col(col))?, line.check(parse_row(row))?));
+                        }
+                        "dcms" => {
+                            dcms = Some(match val {
+                                "2" => Dcms::Two,
+                                "4" => Dcms::Four,
+                                "8" => Dcms::Eight,
+                                _ => return Err(line.error(format!("invalid dcm count {val}"))),
+                            })
+                        }
+                        "has_ll" => has_ll = true,
+                        "has_small_int" => has_small_int = true,
+                        "cfg_io" => {
+                            let (pad, io) = line.check(split_assign(val))?;
+                            cfg_io.insert(line.check(pad.parse())?, line.check(io.parse())?);
+                        }
+                        "dci" => {
+                            let (dci, val) = match val.strip_prefix("alt ") {
+                                Some(val) => (&mut dci_io_alt, val),
+                                None => (&mut dci_io, val),
+                            };
+                            let (bank, pads) = line.check(Self::parse_dci(val))?;
+                            dci.insert(bank, pads);
+                        }
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns,
+            col_clk: col_clk.ok_or_else(|| p.missing("col_clk"))?,
+            cols_clkv,
+            cols_gt,
+            rows,
+            rows_ram,
+            rows_hclk,
+            row_pci,
+            holes_ppc,
+            dcms,
+            has_ll,
+            has_small_int,
+            cfg_io,
+            dci_io,
+            dci_io_alt,
+        })
+    }
 }
@@ -1,13 +1,20 @@
 use std::{error::Error, fs::File, path::Path};
 
 use bincode::{Decode, Encode};
-use prjcombine_entity::{EntityMap, EntityPartVec, EntityVec};
+use prjcombine_entity::{EntityMap, EntityPartVec, EntityVec, id::EntityTag};
 use prjcombine_interconnect::db::{DeviceDataId, IntDb, TableValue};
-use prjcombine_types::db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, DumpFlags};
+use prjcombine_types::{
+    db::{
+        BondId, ChipId, ChipTag, DevBondId, DevSpeedId, DeviceCombo, DeviceCombosParser, DumpFlags,
+    },
+    diff::{DbDiff, DiffNode},
+    text::{TextError, TextParser, expect_next_id, parse_id},
+};
 
 use crate::{bond::Bond, chip::Chip};
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -17,7 +24,40 @@ pub struct Device {
     pub data: EntityPartVec<DeviceDataId, TableValue>,
 }
 
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str, int: &IntDb) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut combos = DeviceCombosParser::default();
+        let mut data = EntityPartVec::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "device_data" => {
+                    let (ddid, value) = line.check(int.parse_device_data(val))?;
+                    data.insert(ddid, value);
+                }
+                _ => {
+                    if !line.check(combos.statement(kw, val))? {
+                        return Err(line.error(format!("unknown device statement {kw}")));
+                    }
+                }
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds: combos.bonds,
+            speeds: combos.speeds,
+            combos: combos.combos,
+            data,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -42,6 +82,87 @@ impl Database {
         Ok(())
     }
 
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        // Device data is typed by the interconnect database, which comes last.
+        let Some(mut ip) = p.find_block("intdb") else {
+            return Err(p.missing("intdb"));
+        };
+        let int = IntDb::parse(&mut ip)?;
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut devices = vec![];
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match kw {
+                "chip" => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                "bond" => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                "device" => devices.push(Device::parse(&mut p, name, &int)?),
+                "intdb" => p.skip_block()?,
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            devices,
+            int,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips and bonds are compared through the
+    /// devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node());
+            for (_, pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for speed in dev.speeds.values() {
+                node.insert(format!("speed {speed}"), DiffNode::new());
+            }
+            for combo in &dev.combos {
+                node.insert(
+                    format!(
+                        "combo {pkg} {speed}",
+                        pkg = dev.bonds.key(combo.devbond),
+                        speed = dev.speeds[combo.speed]
+                    ),
+                    DiffNode::new(),
+                );
+            }
+            for (ddid, value) in &dev.data {
+                node.insert_leaf(
+                    format!("device_data {ddname}", ddname = self.int.devdata.key(ddid)),
+                    self.int.dump_value(self.int.devdata[ddid], value),
+                );
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("intdb", self.int.diff_node());
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
@@ -1,9 +1,11 @@
 use prjcombine_entity::{EntityId, EntityPartVec, EntityVec};
+use prjcombine_interconnect::decode::{DecodedBitstream, decode_tiles};
+use prjcombine_interconnect::encode::{EncodeError, encode_tiles};
 use prjcombine_interconnect::grid::{
     BelCoord, CellCoord, ColId, DieId, ExpandedGrid, Rect, RowId, TileCoord,
 };
-use prjcombine_types::bsdata::BitRectId;
-use prjcombine_xilinx_bitstream::{BitRect, BitstreamGeom, Reg};
+use prjcombine_types::bsdata::{BitRectId, BsData};
+use prjcombine_xilinx_bitstream::{BitRect, Bitstream, BitstreamGeom, Reg};
 
 use crate::{
     chip::{Chip, ChipKind},
@@ -226,6 +228,35 @@ impl ExpandedDevice<'_> {
         )
     }
 
+    pub fn decode(
+        &self,
+        bsdata: &BsData,
+        bitstream: &Bitstream,
+    ) -> Result<DecodedBitstream, EncodeError> {
+        decode_tiles(
+            &self.egrid,
+            bsdata,
+            |tcrd| self.tile_bits(tcrd),
+            |bit| bitstream.get_bit(bit),
+        )
+    }
+
+    pub fn encode(
+        &self,
+        bsdata: &BsData,
+        config: &DecodedBitstream,
+    ) -> Result<Bitstream, EncodeError> {
+        let mut bitstream = Bitstream::new(&self.bs_geom);
+        encode_tiles(
+            &self.egrid,
+            bsdata,
+            config,
+            |tcrd| self.tile_bits(tcrd),
+            |bit, val| bitstream.set_bit(bit, val),
+        )?;
+        Ok(bitstream)
+    }
+
     pub fn tile_bits(&self, tcrd: TileCoord) -> EntityVec<BitRectId, BitRect> {
         let col = tcrd.col;
         let row = tcrd.row;
@@ -1,8 +1,10 @@
 pub mod bond;
 pub mod bscan;
+pub mod bsdl;
 pub mod chip;
 pub mod db;
 pub mod defs;
 mod expand;
 pub mod expanded;
 pub mod iob;
+pub mod pinout;
@@ -0,0 +1,82 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad},
+    chip::{Chip, IoDiffKind},
+    iob::IobKind,
+};
+
+impl Chip {
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let cfg_io: BTreeMap<_, _> = self.cfg_io.iter().map(|(&k, &v)| (v, k)).collect();
+        let mut vr_io = BTreeMap::new();
+        for (&(vrp, vrn), suffix) in self
+            .dci_io
+            .values()
+            .map(|x| (x, ""))
+            .chain(self.dci_io_alt.values().map(|x| (x, "_ALT")))
+        {
+            vr_io.insert(vrp, format!("VRP{suffix}"));
+            vr_io.insert(vrn, format!("VRN{suffix}"));
+        }
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(_) => PinKind::Io,
+                BondPad::Gt(..) => PinKind::Gt,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::VccInt | BondPad::VccAux | BondPad::VccO(_) | BondPad::VccBatt => {
+                    PinKind::Power
+                }
+                BondPad::Gnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::Rsvd | BondPad::Dxn | BondPad::Dxp => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Io(io) => {
+                    let info = self.get_io_info(io);
+                    ppin = ppin.with_bank(info.bank).with_io(io);
+                    match info.diff {
+                        IoDiffKind::P(other) => {
+                            ppin.diff = Some(DiffPolarity::P);
+                            partners.insert(io.to_string(), io.with_iob(other).to_string());
+                        }
+                        IoDiffKind::N(other) => {
+                            ppin.diff = Some(DiffPolarity::N);
+                            partners.insert(io.to_string(), io.with_iob(other).to_string());
+                        }
+                        IoDiffKind::None => (),
+                    }
+                    match info.pad_kind {
+                        Some(IobKind::Clk) => ppin = ppin.with_function("GCLK"),
+                        Some(IobKind::Ibuf) => ppin = ppin.with_function("INPUT_ONLY"),
+                        Some(IobKind::Obuf) => ppin = ppin.with_function("OUTPUT_ONLY"),
+                        Some(IobKind::Iob) | None => (),
+                    }
+                    if bond.vref.contains(&io) {
+                        ppin = ppin.with_function("VREF");
+                    }
+                    if let Some(vr) = vr_io.get(&io) {
+                        ppin = ppin.with_function(vr);
+                    }
+                    if let Some(cpad) = cfg_io.get(&io) {
+                        ppin = ppin.with_function(cpad);
+                    }
+                }
+                BondPad::Gt(bank, _) | BondPad::VccO(bank) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
@@ -0,0 +1,42 @@
+use prjcombine_test_util::{check_decode_roundtrip, check_emit_roundtrip, sample_config};
+use prjcombine_types::bsdata::BsData;
+use prjcombine_virtex2::db::Database;
+use prjcombine_xilinx_bitstream::{BitstreamMode, KeyData, emit, parse_strict};
+
+const VIRTEX2_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex2.zstd");
+const VIRTEX2_DEVICES: [&str; 2] = ["xc2v40", "xc2vp2"];
+const SPARTAN3_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan3.zstd");
+const SPARTAN3_DEVICES: [&str; 4] = ["xc3s50", "xc3s100e", "xc3s50a", "xc3sd1800a"];
+
+fn check_roundtrip(db: &str, devices: &[&str]) {
+    let db = Database::from_file(db).unwrap();
+    let bsdata = BsData::new();
+    for &name in devices {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let edev = db.chips[dev.chip].expand_grid(&db.int);
+        check_decode_roundtrip(name, &edev.egrid, &bsdata, |config| {
+            let bitstream = edev.encode(&bsdata, config).unwrap();
+            let data = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain).unwrap();
+            let parsed = parse_strict(&edev.bs_geom, &data, &KeyData::None).unwrap();
+            edev.decode(&bsdata, &parsed).unwrap()
+        });
+        let config = sample_config(&edev.egrid, &bsdata);
+        let bitstream = edev.encode(&bsdata, &config).unwrap();
+        check_emit_roundtrip(
+            name,
+            &edev.bs_geom,
+            &bitstream,
+            &[BitstreamMode::Plain, BitstreamMode::Debug],
+        );
+    }
+}
+
+#[test]
+fn roundtrip_virtex2() {
+    check_roundtrip(VIRTEX2_DB, &VIRTEX2_DEVICES);
+}
+
+#[test]
+fn roundtrip_spartan3() {
+    check_roundtrip(SPARTAN3_DB, &SPARTAN3_DEVICES);
+}
@@ -0,0 +1,40 @@
+use prjcombine_test_util::check_device_bsdl;
+use prjcombine_virtex2::{
+    bond::{BondPad, CfgPad},
+    db::Database,
+};
+
+const DBS: [&str; 2] = [
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex2.zstd"),
+    concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan3.zstd"),
+];
+
+#[test]
+fn bsdl() {
+    for path in DBS {
+        let db = Database::from_file(path).unwrap();
+        for dev in &db.devices {
+            let chip = &db.chips[dev.chip];
+            let bscan = chip.get_bscan();
+            check_device_bsdl(
+                &dev.name,
+                bscan.bits,
+                dev.bonds.iter().map(|(_, package, &bond)| {
+                    let bond = &db.bonds[bond];
+                    (package.as_str(), bond, bond.pins.values().copied())
+                }),
+                |bond, package, idcode| {
+                    let bsdl = chip.make_bsdl(bond, &dev.name, package, idcode);
+                    assert_eq!(bsdl.ir_len, chip.jtag_ir_len());
+                    bsdl
+                },
+                |pad| match pad {
+                    BondPad::Io(crd) => bscan.io.get(&crd).copied(),
+                    BondPad::Cfg(CfgPad::Tck | CfgPad::Tms | CfgPad::Tdi | CfgPad::Tdo) => None,
+                    BondPad::Cfg(cpad) => bscan.cfg.get(&cpad).copied(),
+                    _ => None,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_interconnect::{
+    db::{BelAttribute, BelAttributeType, BelInfo, BelKind, SwitchBoxItem},
+    decode::{DecodedBitstream, DecodedSetting, DecodedTile},
+    grid::TileCoord,
+};
+use prjcombine_test_util::item_bit_uses;
+use prjcombine_types::{
+    bitrect::BitRect as _,
+    bitvec::BitVec,
+    bsdata::{BsData, TileBit},
+};
+use prjcombine_virtex2::{db::Database, expanded::ExpandedDevice};
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex2.zstd");
+const DEVICE: &str = "xc2v40";
+
+/// Sets the given bits of a tile on top of an unconfigured bitstream and decodes it.
+fn decode_bits(edev: &ExpandedDevice, tcrd: TileCoord, bits: &[TileBit]) -> DecodedBitstream {
+    let bsdata = BsData::new();
+    let mut bitstream = edev.encode(&bsdata, &DecodedBitstream::default()).unwrap();
+    let rects = edev.tile_bits(tcrd);
+    for bit in bits {
+        bitstream.set_bit(rects[bit.rect].xlat_pos_fwd((bit.frame, bit.bit)), true);
+    }
+    edev.decode(&bsdata, &bitstream).unwrap()
+}
+
+fn set_bits(bits: &[TileBit], val: &BitVec) -> Vec<TileBit> {
+    bits.iter()
+        .zip(val)
+        .filter(|&(_, v)| v)
+        .map(|(&bit, _)| bit)
+        .collect()
+}
+
+#[test]
+fn decode_pip() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    // the first multiplexer with bits of its own, and one of its sources
+    let (tcrd, dst, src, bits) = edev
+        .egrid
+        .tiles()
+        .find_map(|(tcrd, tile)| {
+            let tcls = &db.int[tile.class];
+            let uses = item_bit_uses(tcls, None);
+            tcls.bels.values().find_map(|bel| {
+                let BelInfo::SwitchBox(sb) = bel else {
+                    return None;
+                };
+                sb.items.iter().find_map(|item| {
+                    let SwitchBoxItem::Mux(mux) = item else {
+                        return None;
+                    };
+                    if mux.bits.is_empty() || mux.bits.iter().any(|bit| uses[bit] != 1) {
+                        return None;
+                    }
+                    let (&src, val) = mux
+                        .src
+                        .iter()
+                        .find(|&(_, val)| val.any() && mux.bits_off.as_ref() != Some(val))?;
+                    Some((tcrd, mux.dst, src, set_bits(&mux.bits, val)))
+                })
+            })
+        })
+        .unwrap();
+    let decoded = decode_bits(&edev, tcrd, &bits);
+    assert_eq!(
+        decoded.tiles,
+        BTreeMap::from_iter([(
+            tcrd,
+            DecodedTile {
+                pips: vec![(dst, src)],
+                ..Default::default()
+            }
+        )])
+    );
+}
+
+#[test]
+fn decode_setting() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    // the first enum attribute with bits of its own, and one of its values
+    let (tcrd, setting, bits) = edev
+        .egrid
+        .tiles()
+        .find_map(|(tcrd, tile)| {
+            let tcls = &db.int[tile.class];
+            let uses = item_bit_uses(tcls, None);
+            tcls.bels.iter().find_map(|(slot, bel)| {
+                let BelInfo::Bel(bel) = bel else {
+                    return None;
+                };
+                let BelKind::Class(bcid) = db.int.bel_slots[slot].kind else {
+                    unreachable!()
+                };
+                let bcls = &db.int.bel_classes[bcid];
+                bel.attributes.iter().find_map(|(aid, attr)| {
+                    let BelAttribute::Enum(ebits) = attr else {
+                        return None;
+                    };
+                    if ebits.bits.iter().any(|bit| uses[bit] != 1) {
+                        return None;
+                    }
+                    let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                        unreachable!()
+                    };
+                    let (vid, val) = ebits.values.iter().find(|(_, val)| val.any())?;
+                    let setting = DecodedSetting {
+                        bel: db.int.bel_slots.key(slot).to_string(),
+                        attr: bcls.attributes.key(aid).to_string(),
+                        value: db.int.enum_classes[eid].values[vid].clone().into(),
+                    };
+                    Some((tcrd, setting, set_bits(&ebits.bits, val)))
+                })
+            })
+        })
+        .unwrap();
+    let decoded = decode_bits(&edev, tcrd, &bits);
+    assert_eq!(
+        decoded.tiles,
+        BTreeMap::from_iter([(
+            tcrd,
+            DecodedTile {
+                settings: vec![setting],
+                ..Default::default()
+            }
+        )])
+    );
+}
+
+#[test]
+fn decode_unexplained() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    // a bit of a tile that no item of its class uses
+    let (tcrd, bit) = edev
+        .egrid
+        .tiles()
+        .find_map(|(tcrd, tile)| {
+            let tcls = &db.int[tile.class];
+            let uses = item_bit_uses(tcls, None);
+            let (rid, rect) = tcls.bitrects.iter().next()?;
+            let geom = &rect.geometry;
+            (0..geom.frames)
+                .flat_map(|frame| (0..geom.bits).map(move |bit| (frame, bit)))
+                .map(|(frame, bit)| TileBit::new(rid.to_idx(), frame, bit))
+                .find(|bit| !uses.contains_key(bit))
+                .map(|bit| (tcrd, bit))
+        })
+        .unwrap();
+    let decoded = decode_bits(&edev, tcrd, &[bit]);
+    assert_eq!(
+        decoded.tiles,
+        BTreeMap::from_iter([(
+            tcrd,
+            DecodedTile {
+                unexplained: vec![bit],
+                ..Default::default()
+            }
+        )])
+    );
+}
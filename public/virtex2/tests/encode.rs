@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use prjcombine_interconnect::{
+    db::{BelAttribute, BelAttributeType, BelInfo, BelKind, Mux, SwitchBoxItem},
+    decode::{DecodedBitstream, DecodedSetting, DecodedTile},
+    encode::EncodeError,
+    grid::TileCoord,
+};
+use prjcombine_test_util::sample_config;
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{BsData, TileBit},
+};
+use prjcombine_virtex2::{db::Database, expanded::ExpandedDevice};
+
+const DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex2.zstd");
+const DEVICE: &str = "xc2v40";
+
+fn encode(edev: &ExpandedDevice, tcrd: TileCoord, tile: DecodedTile) -> EncodeError {
+    let config = DecodedBitstream {
+        tiles: BTreeMap::from_iter([(tcrd, tile)]),
+    };
+    edev.encode(&BsData::new(), &config).unwrap_err()
+}
+
+struct EnumAttr {
+    tcrd: TileCoord,
+    bel: String,
+    attr: String,
+    bits: Vec<TileBit>,
+    // a value with some bit clear, and its bit pattern
+    value: String,
+    val: BitVec,
+}
+
+/// Returns the first bel enum attribute of the device.
+fn find_enum(edev: &ExpandedDevice) -> EnumAttr {
+    let db = edev.egrid.db;
+    edev.egrid
+        .tiles()
+        .find_map(|(tcrd, tile)| {
+            db[tile.class].bels.iter().find_map(|(slot, bel)| {
+                let BelInfo::Bel(bel) = bel else {
+                    return None;
+                };
+                let BelKind::Class(bcid) = db.bel_slots[slot].kind else {
+                    unreachable!()
+                };
+                let bcls = &db.bel_classes[bcid];
+                bel.attributes.iter().find_map(|(aid, attr)| {
+                    let BelAttribute::Enum(ebits) = attr else {
+                        return None;
+                    };
+                    let BelAttributeType::Enum(eid) = bcls.attributes[aid].typ else {
+                        unreachable!()
+                    };
+                    let (vid, val) = ebits.values.iter().find(|(_, val)| !val.all())?;
+                    Some(EnumAttr {
+                        tcrd,
+                        bel: db.bel_slots.key(slot).to_string(),
+                        attr: bcls.attributes.key(aid).to_string(),
+                        bits: ebits.bits.clone(),
+                        value: db.enum_classes[eid].values[vid].clone(),
+                        val: val.clone(),
+                    })
+                })
+            })
+        })
+        .unwrap()
+}
+
+/// Returns the first tile with a multiplexer of at least two sources.
+fn find_mux<'a>(edev: &ExpandedDevice<'a>) -> (TileCoord, &'a Mux) {
+    let db = edev.egrid.db;
+    edev.egrid
+        .tiles()
+        .find_map(|(tcrd, tile)| {
+            db[tile.class].bels.values().find_map(|bel| {
+                let BelInfo::SwitchBox(sb) = bel else {
+                    return None;
+                };
+                sb.items.iter().find_map(|item| match item {
+                    SwitchBoxItem::Mux(mux) if !mux.bits.is_empty() && mux.src.len() >= 2 => {
+                        Some((tcrd, mux))
+                    }
+                    _ => None,
+                })
+            })
+        })
+        .unwrap()
+}
+
+#[test]
+fn encode_roundtrip() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let bsdata = BsData::new();
+    let config = sample_config(&edev.egrid, &bsdata);
+    assert!(config.tiles.values().any(|tile| !tile.pips.is_empty()));
+    assert!(config.tiles.values().any(|tile| !tile.settings.is_empty()));
+    let bitstream = edev.encode(&bsdata, &config).unwrap();
+    assert_eq!(edev.decode(&bsdata, &bitstream).unwrap(), config);
+}
+
+#[test]
+fn encode_conflict() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    // the attribute value clears a bit, and the same bit is also requested set
+    let EnumAttr {
+        tcrd,
+        bel,
+        attr,
+        bits,
+        value,
+        val,
+    } = find_enum(&edev);
+    let clear = bits
+        .iter()
+        .zip(&val)
+        .find(|&(_, v)| !v)
+        .map(|(&bit, _)| bit)
+        .unwrap();
+    let err = encode(
+        &edev,
+        tcrd,
+        DecodedTile {
+            settings: vec![DecodedSetting {
+                bel: bel.clone(),
+                attr: attr.clone(),
+                value: value.into(),
+            }],
+            unexplained: vec![clear],
+            ..Default::default()
+        },
+    );
+    assert!(
+        matches!(
+            err,
+            EncodeError::Conflict { ref item, ref other_item, .. }
+                if item == "unexplained" && *other_item == format!("{bel}:{attr}")
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn encode_multiple_sources() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let (tcrd, mux) = find_mux(&edev);
+    let err = encode(
+        &edev,
+        tcrd,
+        DecodedTile {
+            pips: mux.src.keys().take(2).map(|&src| (mux.dst, src)).collect(),
+            ..Default::default()
+        },
+    );
+    assert!(matches!(err, EncodeError::MultipleSources { .. }), "{err}");
+}
+
+#[test]
+fn encode_unknown_setting() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let EnumAttr {
+        tcrd, bel, value, ..
+    } = find_enum(&edev);
+    let err = encode(
+        &edev,
+        tcrd,
+        DecodedTile {
+            settings: vec![DecodedSetting {
+                bel: bel.clone(),
+                attr: "NO_SUCH_ATTR".to_string(),
+                value: value.into(),
+            }],
+            ..Default::default()
+        },
+    );
+    assert!(
+        matches!(
+            err,
+            EncodeError::UnknownSetting { bel: ref ebel, ref attr, .. }
+                if *ebel == bel && attr == "NO_SUCH_ATTR"
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn encode_invalid_value() {
+    let db = Database::from_file(DB).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let EnumAttr {
+        tcrd, bel, attr, ..
+    } = find_enum(&edev);
+    let err = encode(
+        &edev,
+        tcrd,
+        DecodedTile {
+            settings: vec![DecodedSetting {
+                bel,
+                attr,
+                value: "NO_SUCH_VALUE".to_string().into(),
+            }],
+            ..Default::default()
+        },
+    );
+    assert!(matches!(err, EncodeError::InvalidValue { .. }), "{err}");
+}
@@ -0,0 +1,40 @@
+use prjcombine_test_util::check_pinout;
+use prjcombine_types::pinout::Pinout;
+use prjcombine_virtex2::db::Database;
+
+const VIRTEX2_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex2.zstd");
+const SPARTAN3_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/spartan3.zstd");
+
+fn pinout(db: &str, device: &str, package: &str) -> Pinout {
+    let db = Database::from_file(db).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let (_, &bond) = dev.bonds.get(package).unwrap();
+    db.chips[dev.chip].make_pinout(&db.bonds[bond], device, package)
+}
+
+// The database has no vendor pair numbers, so the IO names are made of the
+// functions and the bank alone.
+#[test]
+fn pinout_virtex2() {
+    check_pinout(
+        &pinout(VIRTEX2_DB, "xc2v40", "fg256"),
+        &[
+            ("A5", "IO_VRN_0", Some("0"), Some("B5")),
+            ("B5", "IO_VRP_0", Some("0"), Some("A5")),
+            ("A7", "IO_0", Some("0"), Some("B7")),
+        ],
+    );
+}
+
+#[test]
+fn pinout_spartan3() {
+    check_pinout(
+        &pinout(SPARTAN3_DB, "xc3s200", "ft256"),
+        &[
+            ("A3", "IO_VREF_0", Some("0"), None),
+            ("A4", "IO_VRN_0", Some("0"), Some("B4")),
+            ("B4", "IO_VRP_0", Some("0"), Some("A4")),
+            ("T9", "IO_4", Some("4"), Some("R9")),
+        ],
+    );
+}
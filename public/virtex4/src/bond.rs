@@ -1,8 +1,13 @@
 use bincode::{Decode, Encode};
 use itertools::Itertools;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_num, split_assign},
+};
 use std::collections::BTreeMap;
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Tck,
     Tdi,
@@ -52,7 +57,36 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "M2" => CfgPad::M2,
+            "PROG_B" => CfgPad::ProgB,
+            "INIT_B" => CfgPad::InitB,
+            "RDWR_B" => CfgPad::RdWrB,
+            "CSI_B" => CfgPad::CsiB,
+            "TCK" => CfgPad::Tck,
+            "TMS" => CfgPad::Tms,
+            "TDI" => CfgPad::Tdi,
+            "TDO" => CfgPad::Tdo,
+            "PWRDWN_B" => CfgPad::PwrdwnB,
+            "HSWAP_EN" => CfgPad::HswapEn,
+            "DIN" => CfgPad::Din,
+            "DOUT" => CfgPad::Dout,
+            "CFGBVS" => CfgPad::CfgBvs,
+            _ => return Err(format!("unknown config pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtPad {
     RxP(u8),
     RxN(u8),
@@ -107,7 +141,45 @@ impl std::fmt::Display for GtPad {
     }
 }
 
+impl std::str::FromStr for GtPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GNDA" => GtPad::GndA,
+            "AVCCAUXTX" => GtPad::AVccAuxTx,
+            "AVCCAUXMGT" => GtPad::AVccAuxMgt,
+            "RTERM" => GtPad::RTerm,
+            "MGTVREF" => GtPad::MgtVRef,
+            "AVCC" => GtPad::AVcc,
+            "AVCCPLL" => GtPad::AVccPll,
+            "RREF" => GtPad::RRef,
+            "AVTTRCAL" => GtPad::AVttRCal,
+            "RBIAS" => GtPad::RBias,
+            _ => {
+                for (prefix, pad) in [
+                    ("RXP", GtPad::RxP as fn(u8) -> GtPad),
+                    ("RXN", GtPad::RxN),
+                    ("TXP", GtPad::TxP),
+                    ("TXN", GtPad::TxN),
+                    ("CLKP", GtPad::ClkP),
+                    ("CLKN", GtPad::ClkN),
+                    ("AVCCAUXRX", GtPad::AVccAuxRx),
+                    ("VTRX", GtPad::VtRx),
+                    ("VTTX", GtPad::VtTx),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown GT pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtRegion {
     All,
     S,
@@ -144,7 +216,30 @@ impl std::fmt::Display for GtRegion {
     }
 }
 
+impl std::str::FromStr for GtRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ALL" => GtRegion::All,
+            "S" => GtRegion::S,
+            "N" => GtRegion::N,
+            "L" => GtRegion::L,
+            "R" => GtRegion::R,
+            "LS" => GtRegion::LS,
+            "RS" => GtRegion::RS,
+            "LN" => GtRegion::LN,
+            "RN" => GtRegion::RN,
+            "H" => GtRegion::H,
+            "LH" => GtRegion::LH,
+            "RH" => GtRegion::RH,
+            _ => GtRegion::Num(parse_num(s)?),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtRegionPad {
     AVtt,
     AGnd,
@@ -169,7 +264,25 @@ impl std::fmt::Display for GtRegionPad {
     }
 }
 
+impl std::str::FromStr for GtRegionPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "AVTT" => GtRegionPad::AVtt,
+            "AGND" => GtRegionPad::AGnd,
+            "AVCC" => GtRegionPad::AVcc,
+            "AVCCRX" => GtRegionPad::AVccRx,
+            "AVCCPLL" => GtRegionPad::AVccPll,
+            "AVTTRXC" => GtRegionPad::AVttRxC,
+            "VCCAUX" => GtRegionPad::VccAux,
+            _ => return Err(format!("unknown GT region pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SysMonPad {
     VP,
     VN,
@@ -192,7 +305,24 @@ impl std::fmt::Display for SysMonPad {
     }
 }
 
+impl std::str::FromStr for SysMonPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "VP" => SysMonPad::VP,
+            "VN" => SysMonPad::VN,
+            "AVSS" => SysMonPad::AVss,
+            "AVDD" => SysMonPad::AVdd,
+            "VREFP" => SysMonPad::VRefP,
+            "VREFN" => SysMonPad::VRefN,
+            _ => return Err(format!("unknown sysmon pad {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtzPad {
     RxP(u8),
     RxN(u8),
@@ -245,7 +375,47 @@ impl std::fmt::Display for GtzPad {
     }
 }
 
+impl std::str::FromStr for GtzPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "AGND" => GtzPad::AGnd,
+            "AVCC" => GtzPad::AVcc,
+            "VCCH" => GtzPad::VccH,
+            "VCCL" => GtzPad::VccL,
+            "OBSCLKP" => GtzPad::ObsClkP,
+            "OBSCLKN" => GtzPad::ObsClkN,
+            "THERM_IN" => GtzPad::ThermIn,
+            "THERM_OUT" => GtzPad::ThermOut,
+            "SENSE_AGND" => GtzPad::SenseAGnd,
+            "SENSE_GND" => GtzPad::SenseGnd,
+            "SENSE_GNDL" => GtzPad::SenseGndL,
+            "SENSE_AVCC" => GtzPad::SenseAVcc,
+            "SENSE_VCC" => GtzPad::SenseVcc,
+            "SENSE_VCCL" => GtzPad::SenseVccL,
+            "SENSE_VCCH" => GtzPad::SenseVccH,
+            _ => {
+                for (prefix, pad) in [
+                    ("RXP", GtzPad::RxP as fn(u8) -> GtzPad),
+                    ("RXN", GtzPad::RxN),
+                    ("TXP", GtzPad::TxP),
+                    ("TXN", GtzPad::TxN),
+                    ("CLKP", GtzPad::ClkP),
+                    ("CLKN", GtzPad::ClkN),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown GTZ pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PsPad {
     Mio(u32),
     Clk,
@@ -298,7 +468,47 @@ impl std::fmt::Display for PsPad {
     }
 }
 
+impl std::str::FromStr for PsPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CLK" => PsPad::Clk,
+            "POR_B" => PsPad::PorB,
+            "SRST_B" => PsPad::SrstB,
+            "DDR_VRP" => PsPad::DdrVrP,
+            "DDR_VRN" => PsPad::DdrVrN,
+            "DDR_CKP" => PsPad::DdrCkP,
+            "DDR_CKN" => PsPad::DdrCkN,
+            "DDR_CKE" => PsPad::DdrCke,
+            "DDR_ODT" => PsPad::DdrOdt,
+            "DDR_DRST_B" => PsPad::DdrDrstB,
+            "DDR_CS_B" => PsPad::DdrCsB,
+            "DDR_RAS_B" => PsPad::DdrRasB,
+            "DDR_CAS_B" => PsPad::DdrCasB,
+            "DDR_WE_B" => PsPad::DdrWeB,
+            _ => {
+                for (prefix, pad) in [
+                    ("MIO", PsPad::Mio as fn(u32) -> PsPad),
+                    ("DDR_DQS_P", PsPad::DdrDqsP),
+                    ("DDR_DQS_N", PsPad::DdrDqsN),
+                    ("DDR_DQ", PsPad::DdrDq),
+                    ("DDR_DM", PsPad::DdrDm),
+                    ("DDR_A", PsPad::DdrA),
+                    ("DDR_BA", PsPad::DdrBa),
+                ] {
+                    if let Some(idx) = s.strip_prefix(prefix) {
+                        return Ok(pad(parse_num(idx)?));
+                    }
+                }
+                return Err(format!("unknown PS pad {s}"));
+            }
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     // bank, pin within bank
     Io(u32, u32),
@@ -358,12 +568,73 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "VCCAUX" => BondPad::VccAux,
+            "VCCBRAM" => BondPad::VccBram,
+            "VCC_BATT" => BondPad::VccBatt,
+            "DXN" => BondPad::Dxn,
+            "DXP" => BondPad::Dxp,
+            "RSVD" => BondPad::Rsvd,
+            "RSVDGND" => BondPad::RsvdGnd,
+            "VFS" => BondPad::Vfs,
+            "VCC_PS_INT" => BondPad::VccPsInt,
+            "VCC_PS_AUX" => BondPad::VccPsAux,
+            "VCC_PS_PLL" => BondPad::VccPsPll,
+            _ => {
+                fn split(s: &str) -> Result<(&str, &str), String> {
+                    s.split_once('_')
+                        .ok_or_else(|| format!("invalid bond pad \"{s}\""))
+                }
+                if let Some(rest) = s.strip_prefix("IOB_") {
+                    let (bank, idx) = split(rest)?;
+                    BondPad::Io(parse_num(bank)?, parse_num(idx)?)
+                } else if let Some(rest) = s.strip_prefix("GTREG_") {
+                    let (region, pad) = split(rest)?;
+                    BondPad::GtRegion(region.parse()?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("GTZ") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::Gtz(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("GT") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::Gt(parse_num(bank)?, pad.parse()?)
+                } else if let Some(rest) = s.strip_prefix("SYSMON") {
+                    let (bank, pad) = split(rest)?;
+                    BondPad::SysMon(parse_num(bank)?, pad.parse()?)
+                } else if let Some(idx) = s.strip_prefix("VCCAUX_IO") {
+                    BondPad::VccAuxIo(parse_num(idx)?)
+                } else if let Some(bank) = s.strip_prefix("VCCO") {
+                    BondPad::VccO(parse_num(bank)?)
+                } else if let Some((bank, idx)) =
+                    s.strip_prefix("PS").and_then(|s| s.split_once(".VREF"))
+                {
+                    BondPad::PsVref(parse_num(bank)?, parse_num(idx)?)
+                } else if let Some((bank, pad)) =
+                    s.strip_prefix("PS").and_then(|s| s.split_once('_'))
+                {
+                    BondPad::PsIo(parse_num(bank)?, pad.parse()?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     // ×32
     // [V5+] high 16 bits are also low 16 bits of Addr
@@ -458,4 +729,27 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            if kw != "pin" {
+                return Err(line.error(format!("unknown bond statement {kw}")));
+            }
+            let (pin, pad) = line.check(split_assign(val))?;
+            pins.insert(pin.to_string(), line.check(pad.parse())?);
+        }
+        Ok(Bond { pins })
+    }
 }
@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+
+use prjcombine_types::bscan::{BScanBuilder, BScanPad};
+
+use crate::expanded::{ExpandedDevice, IoCoord};
+
+#[derive(Debug)]
+pub struct BScan {
+    pub bits: usize,
+    pub io: BTreeMap<IoCoord, BScanPad>,
+}
+
+impl ExpandedDevice<'_> {
+    /// The chain follows the order of the expanded IO list within each die.  On devices with
+    /// an interposer, the dies are chained in bitstream order, primary die first.  Dedicated
+    /// configuration pads are not modelled.
+    pub fn get_bscan(&self) -> BScan {
+        let mut io = BTreeMap::new();
+        let mut builder = BScanBuilder::new();
+        for &die in &self.bs_geom.die_order {
+            for &crd in &self.io {
+                if crd.cell.die == die {
+                    io.insert(crd, builder.get_toi());
+                }
+            }
+        }
+        BScan {
+            bits: builder.bits,
+            io,
+        }
+    }
+}
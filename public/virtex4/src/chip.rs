@@ -6,9 +6,13 @@ use prjcombine_entity::{
 };
 use prjcombine_interconnect::{
     dir::DirH,
-    grid::{ColId, DieId, RowId},
+    grid::{ColId, ColTag, DieId, DieTag, RowId, RowTag},
 };
-use std::collections::BTreeSet;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{Line, TextError, TextParser, parse_id, parse_num, split_list},
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct RegTag;
 impl EntityTag for RegTag {
@@ -18,6 +22,7 @@ impl EntityTagArith for RegTag {}
 pub type RegId = EntityIdU8<RegTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: EntityVec<ColId, ColumnKind>,
@@ -41,6 +46,7 @@ pub struct Chip {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Virtex4,
     Virtex5,
@@ -59,7 +65,22 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "virtex4" => ChipKind::Virtex4,
+            "virtex5" => ChipKind::Virtex5,
+            "virtex6" => ChipKind::Virtex6,
+            "virtex7" => ChipKind::Virtex7,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColumnKind {
     ClbLL,
     ClbLM,
@@ -88,7 +109,27 @@ impl std::fmt::Display for ColumnKind {
     }
 }
 
+impl std::str::FromStr for ColumnKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "io" => ColumnKind::Io,
+            "clbll" => ColumnKind::ClbLL,
+            "clblm" => ColumnKind::ClbLM,
+            "bram" => ColumnKind::Bram,
+            "dsp" => ColumnKind::Dsp,
+            "gt" => ColumnKind::Gt,
+            "cmt" => ColumnKind::Cmt,
+            "clk" => ColumnKind::Clk,
+            "cfg" => ColumnKind::Cfg,
+            _ => return Err(format!("unknown column kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgRowKind {
     Dcm,
     Ccm,
@@ -105,7 +146,21 @@ impl std::fmt::Display for CfgRowKind {
     }
 }
 
+impl std::str::FromStr for CfgRowKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "dcm" => CfgRowKind::Dcm,
+            "ccm" => CfgRowKind::Ccm,
+            "sysmon" => CfgRowKind::Sysmon,
+            _ => return Err(format!("unknown config row kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GtKind {
     Gtp,
     Gtx,
@@ -122,7 +177,21 @@ impl std::fmt::Display for GtKind {
     }
 }
 
+impl std::str::FromStr for GtKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "gtp" => GtKind::Gtp,
+            "gtx" => GtKind::Gtx,
+            "gth" => GtKind::Gth,
+            _ => return Err(format!("unknown GT kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IoKind {
     Hpio,
     Hrio,
@@ -137,13 +206,27 @@ impl std::fmt::Display for IoKind {
     }
 }
 
+impl std::str::FromStr for IoKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "hpio" => IoKind::Hpio,
+            "hrio" => IoKind::Hrio,
+            _ => return Err(format!("unknown IO kind {s}")),
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IoColumn {
     pub col: ColId,
     pub regs: EntityVec<RegId, Option<IoKind>>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtColumn {
     pub col: ColId,
     pub is_middle: bool,
@@ -151,6 +234,7 @@ pub struct GtColumn {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HardColumn {
     pub col: ColId,
     pub rows_emac: Vec<RowId>,
@@ -158,6 +242,7 @@ pub struct HardColumn {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pcie2 {
     pub side: DirH,
     pub col: ColId,
@@ -165,6 +250,7 @@ pub struct Pcie2 {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DisabledPart {
     Emac(RowId),
     GtxRow(RegId),
@@ -184,6 +270,7 @@ impl std::fmt::Display for DisabledPart {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interposer {
     pub primary: DieId,
     pub gtz_bot: bool,
@@ -191,6 +278,7 @@ pub struct Interposer {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum XadcIoLoc {
     W,
     E,
@@ -381,8 +469,8 @@ impl Chip {
         }
 
         writeln!(o, "\tregs {r};", r = self.regs)?;
-        writeln!(o, "\treg_cfg {v}", v = self.reg_cfg)?;
-        writeln!(o, "\treg_clk {v}", v = self.reg_clk)?;
+        writeln!(o, "\treg_cfg {v};", v = self.reg_cfg)?;
+        writeln!(o, "\treg_clk {v};", v = self.reg_clk)?;
         for &(col, row) in &self.holes_ppc {
             let (col_r, row_t): (ColId, RowId) = match self.kind {
                 ChipKind::Virtex4 => (col + 9, row + 24),
@@ -417,9 +505,374 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns, banks, GT
+    /// quads and hard blocks keyed by their position.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        res.insert_leaf("has_ps", self.has_ps);
+        res.insert_leaf("has_slr", self.has_slr);
+        res.insert_leaf("no_tb_uturn", self.has_no_tbuturn);
+        for (col, cd) in &self.columns {
+            res.insert_leaf(format!("column {col}"), cd);
+        }
+        for col in &self.cols_vbrk {
+            res.insert(format!("col_vbrk {col}"), DiffNode::new());
+        }
+        for col in &self.cols_mgt_buf {
+            res.insert(format!("col_mgt_buf {col}"), DiffNode::new());
+        }
+        if let Some((cl, cr)) = self.cols_qbuf {
+            res.insert_leaf("cols_qbuf", format!("{cl}, {cr}"));
+        }
+        if let Some(ref hard) = self.col_hard {
+            let mut node = DiffNode::new();
+            for &row in &hard.rows_pcie {
+                node.insert(format!("pcie {row}"), DiffNode::new());
+            }
+            for &row in &hard.rows_emac {
+                node.insert(format!("emac {row}"), DiffNode::new());
+            }
+            res.insert(format!("col_hard {col}", col = hard.col), node);
+        }
+        for ioc in &self.cols_io {
+            let mut node = DiffNode::new();
+            for (reg, kind) in &ioc.regs {
+                if let Some(kind) = kind {
+                    node.insert_leaf(format!("bank {row}", row = self.row_reg_bot(reg)), kind);
+                }
+            }
+            res.insert(format!("col_io {col}", col = ioc.col), node);
+        }
+        for gtc in &self.cols_gt {
+            let mut node = DiffNode::new();
+            node.insert_leaf("mid", gtc.is_middle);
+            for (reg, kind) in &gtc.regs {
+                if let Some(kind) = kind {
+                    node.insert_leaf(format!("gt {row}", row = self.row_reg_bot(reg)), kind);
+                }
+            }
+            res.insert(format!("col_gt {col}", col = gtc.col), node);
+        }
+        for &(row, kind) in &self.rows_cfg {
+            res.insert_leaf(format!("row_cfg {row}"), kind);
+        }
+        res.insert_leaf("regs", self.regs);
+        res.insert_leaf("reg_cfg", self.reg_cfg);
+        res.insert_leaf("reg_clk", self.reg_clk);
+        for &(col, row) in &self.holes_ppc {
+            res.insert(format!("ppc {col} {row}"), DiffNode::new());
+        }
+        for pcie in &self.holes_pcie2 {
+            res.insert(
+                format!(
+                    "pcie2 {side} {col} {row}",
+                    side = pcie.side,
+                    col = pcie.col,
+                    row = pcie.row
+                ),
+                DiffNode::new(),
+            );
+        }
+        for &(col, row) in &self.holes_pcie3 {
+            res.insert(format!("pcie3 {col} {row}"), DiffNode::new());
+        }
+        res.insert_leaf("has_bram_fx", self.has_bram_fx);
+        res
+    }
+}
+
+fn parse_range<I: EntityId>(s: &str, prefix: &str) -> Result<I, String> {
+    let (start, _) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid range \"{s}\""))?;
+    parse_id(start, prefix)
+}
+
+/// Column data from the `columns` block that can only be resolved once the region count is known.
+#[derive(Default)]
+struct ColumnsParser {
+    cols_mgt_buf: BTreeSet<ColId>,
+    cols_qbuf: Vec<ColId>,
+    col_hard: Option<HardColumn>,
+    cols_io: Vec<(ColId, BTreeMap<RowId, IoKind>)>,
+    cols_gt: Vec<(ColId, bool, BTreeMap<RowId, GtKind>)>,
+    rows_cfg: Vec<(RowId, CfgRowKind)>,
+}
+
+impl ColumnsParser {
+    /// Parses one entry of the `columns` block, including the sub-blocks attached to it.
+    fn column(&mut self, p: &mut TextParser, col: ColId) -> Result<ColumnKind, TextError> {
+        let line = p.expect_line()?;
+        let (head, mut is_open) = match line.text().strip_suffix(" {") {
+            Some(head) => (head, true),
+            None => (line.list_item()?, false),
+        };
+        let mut parts = head.split(" + ");
+        let kind: ColumnKind = line.check(parts.next().unwrap().parse())?;
+        // The `hard` and `gt` parts name the block that the line opens.
+        let mut block = "";
+        for part in parts {
+            match part {
+                "mgt_buf" => {
+                    self.cols_mgt_buf.insert(col);
+                }
+                "qbuf" => self.cols_qbuf.push(col),
+                "hard" | "gt" => block = part,
+                _ => return Err(line.error(format!("unknown column part \"{part}\""))),
+            }
+        }
+        while is_open {
+            let mut items = vec![];
+            let end = loop {
+                let line = p.expect_line()?;
+                if line.text().starts_with('}') {
+                    break line;
+                }
+                items.push(line);
+            };
+            // Bare blocks are told apart by their contents; an empty one can only be an IO
+            // column without banks, or a GT column without GTs.
+            if block.is_empty() {
+                block = match items.first() {
+                    Some(item) => item.statement()?.split(' ').next().unwrap(),
+                    None if kind == ColumnKind::Gt => "gt",
+                    None => "bank",
+                };
+            }
+            match block {
+                "hard" => self.parse_hard(col, &items)?,
+                "bank" => self.parse_io(col, &items)?,
+                "gt" => self.parse_gt(col, &items)?,
+                _ => self.parse_cfg(&items)?,
+            }
+            match end.text()[1..].trim().strip_suffix('{') {
+                Some(next) => block = next.trim().strip_prefix("+ ").unwrap_or(""),
+                None => {
+                    if end.list_item()? != "}" {
+                        return Err(end.error(format!("unexpected \"{}\"", end.text())));
+                    }
+                    is_open = false;
+                }
+            }
+        }
+        Ok(kind)
+    }
+
+    fn parse_hard(&mut self, col: ColId, items: &[Line]) -> Result<(), TextError> {
+        let mut hard = HardColumn {
+            col,
+            rows_emac: vec![],
+            rows_pcie: vec![],
+        };
+        for line in items {
+            let (kw, val) = line.keyword_statement()?;
+            let row = line.check(parse_id(val, RowTag::PREFIX))?;
+            match kw {
+                "pcie" => hard.rows_pcie.push(row),
+                "emac" => hard.rows_emac.push(row),
+                _ => return Err(line.error(format!("unknown hard column statement {kw}"))),
+            }
+        }
+        self.col_hard = Some(hard);
+        Ok(())
+    }
+
+    fn parse_io(&mut self, col: ColId, items: &[Line]) -> Result<(), TextError> {
+        let mut banks = BTreeMap::new();
+        for line in items {
+            let words: Vec<_> = line.statement()?.split(' ').collect();
+            let ["bank", row, kind] = words[..] else {
+                return Err(line.error(format!("expected bank, got \"{}\"", line.text())));
+            };
+            banks.insert(
+                line.check(parse_id(row, RowTag::PREFIX))?,
+                line.check(kind.parse())?,
+            );
+        }
+        self.cols_io.push((col, banks));
+        Ok(())
+    }
+
+    fn parse_gt(&mut self, col: ColId, items: &[Line]) -> Result<(), TextError> {
+        let mut is_middle = false;
+        let mut gts = BTreeMap::new();
+        for line in items {
+            let words: Vec<_> = line.statement()?.split(' ').collect();
+            let (row, kind) = match words[..] {
+                ["gt", row, kind] => (row, kind),
+                ["gt", row, "mid", kind] => {
+                    is_middle = true;
+                    (row, kind)
+                }
+                _ => return Err(line.error(format!("expected gt, got \"{}\"", line.text()))),
+            };
+            gts.insert(
+                line.check(parse_id(row, RowTag::PREFIX))?,
+                line.check(kind.parse())?,
+            );
+        }
+        self.cols_gt.push((col, is_middle, gts));
+        Ok(())
+    }
+
+    fn parse_cfg(&mut self, items: &[Line]) -> Result<(), TextError> {
+        for line in items {
+            let (kind, row) = line.keyword_statement()?;
+            self.rows_cfg.push((
+                line.check(parse_id(row, RowTag::PREFIX))?,
+                line.check(kind.parse())?,
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Chip {
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = EntityVec::new();
+        let mut cp = ColumnsParser::default();
+        let mut cols_vbrk = BTreeSet::new();
+        let mut regs = None;
+        let mut reg_cfg = None;
+        let mut reg_clk = None;
+        let mut holes_ppc = vec![];
+        let mut holes_pcie2 = vec![];
+        let mut holes_pcie3 = vec![];
+        let mut has_bram_fx = false;
+        let mut has_ps = false;
+        let mut has_slr = false;
+        let mut has_no_tbuturn = false;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            if line.block_start() == Some(("columns", "")) {
+                while !p.try_block_end() {
+                    let col = columns.next_id();
+                    columns.push(cp.column(p, col)?);
+                }
+                continue;
+            }
+            let (kw, val) = line.keyword_statement()?;
+            let parse_col = |s| line.check(parse_id(s, ColTag::PREFIX));
+            let parse_hole = |s: &str| -> Result<(ColId, RowId), TextError> {
+                let Some((cols, rows)) = s.split_once(' ') else {
+                    return Err(line.error(format!("invalid hole \"{s}\"")));
+                };
+                Ok((
+                    line.check(parse_range(cols, ColTag::PREFIX))?,
+                    line.check(parse_range(rows, RowTag::PREFIX))?,
+                ))
+            };
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "has_ps" => has_ps = true,
+                "has_slr" => has_slr = true,
+                "no_tb_uturn" => has_no_tbuturn = true,
+                "has_bram_fx" => has_bram_fx = true,
+                "cols_vbrk" => {
+                    for col in split_list(val) {
+                        cols_vbrk.insert(parse_col(col)?);
+                    }
+                }
+                "regs" => regs = Some(line.check(parse_num(val))?),
+                "reg_cfg" => reg_cfg = Some(line.check(parse_id(val, RegTag::PREFIX))?),
+                "reg_clk" => reg_clk = Some(line.check(parse_id(val, RegTag::PREFIX))?),
+                "ppc" => holes_ppc.push(parse_hole(val)?),
+                "pcie2" => {
+                    let Some((side, hole)) = val.split_once(' ') else {
+                        return Err(line.error(format!("invalid pcie2 \"{val}\"")));
+                    };
+                    let (col, row) = parse_hole(hole)?;
+                    holes_pcie2.push(Pcie2 {
+                        side: line.check(side.parse())?,
+                        col,
+                        row,
+                    });
+                }
+                "pcie3" => holes_pcie3.push(parse_hole(val)?),
+                _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+            }
+        }
+        let mut chip = Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns,
+            cols_vbrk,
+            cols_mgt_buf: cp.cols_mgt_buf,
+            cols_qbuf: match cp.cols_qbuf[..] {
+                [] => None,
+                [cl, cr] => Some((cl, cr)),
+                _ => return Err(p.missing("second qbuf column")),
+            },
+            col_hard: cp.col_hard,
+            cols_io: vec![],
+            cols_gt: vec![],
+            regs: regs.ok_or_else(|| p.missing("regs"))?,
+            reg_cfg: reg_cfg.ok_or_else(|| p.missing("reg_cfg"))?,
+            reg_clk: reg_clk.ok_or_else(|| p.missing("reg_clk"))?,
+            rows_cfg: cp.rows_cfg,
+            holes_ppc,
+            holes_pcie2,
+            holes_pcie3,
+            has_bram_fx,
+            has_ps,
+            has_slr,
+            has_no_tbuturn,
+        };
+        for (col, banks) in cp.cols_io {
+            let mut regs = EntityVec::from_iter(chip.regs().map(|_| None));
+            for (row, kind) in banks {
+                regs[chip.row_to_reg(row)] = Some(kind);
+            }
+            chip.cols_io.push(IoColumn { col, regs });
+        }
+        for (col, is_middle, gts) in cp.cols_gt {
+            let mut regs = EntityVec::from_iter(chip.regs().map(|_| None));
+            for (row, kind) in gts {
+                regs[chip.row_to_reg(row)] = Some(kind);
+            }
+            chip.cols_gt.push(GtColumn {
+                col,
+                is_middle,
+                regs,
+            });
+        }
+        Ok(chip)
+    }
 }
 
 impl Interposer {
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut primary = None;
+        let mut gtz_bot = false;
+        let mut gtz_top = false;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "primary" => primary = Some(line.check(parse_id(val, DieTag::PREFIX))?),
+                "gtz_bot" => gtz_bot = true,
+                "gtz_top" => gtz_top = true,
+                _ => return Err(line.error(format!("unknown interposer statement {kw}"))),
+            }
+        }
+        Ok(Interposer {
+            primary: primary.ok_or_else(|| p.missing("primary"))?,
+            gtz_bot,
+            gtz_top,
+        })
+    }
+
+    /// Builds the diff tree of the interposer, one entry per field.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("primary", self.primary);
+        res.insert_leaf("gtz_bot", self.gtz_bot);
+        res.insert_leaf("gtz_top", self.gtz_top);
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         writeln!(o, "\tprimary {};", self.primary)?;
         if self.gtz_bot {
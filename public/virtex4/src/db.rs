@@ -2,23 +2,29 @@ use std::{collections::BTreeSet, error::Error, fs::File, path::Path};
 
 use bincode::{Decode, Encode};
 use itertools::Itertools;
-use prjcombine_entity::{EntityMap, EntityPartVec, EntityVec};
+use prjcombine_entity::{EntityMap, EntityPartVec, EntityVec, id::EntityTag};
 use prjcombine_interconnect::{
     db::{DeviceDataId, IntDb, TableValue},
-    grid::DieId,
+    grid::{DieId, RowTag},
 };
 use prjcombine_types::{
     bsdata::BsData,
-    db::{BondId, ChipId, DevBondId, DevSpeedId, DeviceCombo, DumpFlags, InterposerId},
+    db::{
+        BondId, ChipId, ChipTag, DevBondId, DevSpeedId, DeviceCombo, DeviceCombosParser, DumpFlags,
+        InterposerId, InterposerTag,
+    },
+    diff::{DbDiff, DiffNode},
+    text::{TextError, TextParser, expect_next_id, parse_id, split_list},
 };
 
 use crate::{
     bond::Bond,
-    chip::{Chip, DisabledPart, Interposer},
+    chip::{Chip, DisabledPart, Interposer, RegTag},
     gtz::GtzDb,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chips: EntityVec<DieId, ChipId>,
@@ -30,7 +36,68 @@ pub struct Device {
     pub data: EntityPartVec<DeviceDataId, TableValue>,
 }
 
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str, int: &IntDb) -> Result<Self, TextError> {
+        let mut chips = None;
+        let mut interposer = None;
+        let mut combos = DeviceCombosParser::default();
+        let mut disabled = BTreeSet::new();
+        let mut data = EntityPartVec::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => {
+                    let mut dies = EntityVec::new();
+                    for chip in split_list(val) {
+                        dies.push(line.check(parse_id(chip, ChipTag::PREFIX))?);
+                    }
+                    chips = Some(dies);
+                }
+                "interposer" => {
+                    interposer = Some(line.check(parse_id(val, InterposerTag::PREFIX))?)
+                }
+                "disabled" => {
+                    let words: Vec<_> = val.split(' ').collect();
+                    let part = match words[..] {
+                        ["emac", row] => {
+                            DisabledPart::Emac(line.check(parse_id(row, RowTag::PREFIX))?)
+                        }
+                        ["gtx", reg] => {
+                            DisabledPart::GtxRow(line.check(parse_id(reg, RegTag::PREFIX))?)
+                        }
+                        ["sysmon"] => DisabledPart::SysMon,
+                        ["gtp"] => DisabledPart::Gtp,
+                        _ => return Err(line.error(format!("unknown disabled part \"{val}\""))),
+                    };
+                    disabled.insert(part);
+                }
+                "device_data" => {
+                    let (ddid, value) = line.check(int.parse_device_data(val))?;
+                    data.insert(ddid, value);
+                }
+                _ => {
+                    if !line.check(combos.statement(kw, val))? {
+                        return Err(line.error(format!("unknown device statement {kw}")));
+                    }
+                }
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chips: chips.ok_or_else(|| p.missing("chip"))?,
+            interposer,
+            bonds: combos.bonds,
+            speeds: combos.speeds,
+            combos: combos.combos,
+            disabled,
+            data,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub interposers: EntityVec<InterposerId, Interposer>,
@@ -58,6 +125,120 @@ impl Database {
         Ok(())
     }
 
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        // Device data is typed by the interconnect database, which comes later.
+        let Some(mut ip) = p.find_block("intdb") else {
+            return Err(p.missing("intdb"));
+        };
+        let int = IntDb::parse(&mut ip)?;
+        let mut chips = EntityVec::new();
+        let mut interposers = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut devices = vec![];
+        let mut sections = None;
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match kw {
+                "chip" => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                "interposer" => {
+                    line.check(expect_next_id(name, interposers.next_id()))?;
+                    interposers.push(Interposer::parse(&mut p)?);
+                }
+                "bond" => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                "device" => devices.push(Device::parse(&mut p, name, &int)?),
+                "intdb" => {
+                    p.skip_block()?;
+                    // The GTZ and bitstream data sections make up the rest of the dump.
+                    let gtz = GtzDb::parse(&mut p)?;
+                    sections = Some((gtz, BsData::parse(&mut p)?));
+                }
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        let Some((gtz, bsdata)) = sections else {
+            return Err(p.missing("intdb"));
+        };
+        Ok(Database {
+            chips,
+            interposers,
+            bonds,
+            devices,
+            int,
+            bsdata,
+            gtz,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips and bonds are compared through the
+    /// devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            for (die, &chip) in &dev.chips {
+                node.insert(format!("chip {die}"), self.chips[chip].diff_node());
+            }
+            if let Some(ipid) = dev.interposer {
+                node.insert("interposer", self.interposers[ipid].diff_node());
+            }
+            for (_, pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for speed in dev.speeds.values() {
+                node.insert(format!("speed {speed}"), DiffNode::new());
+            }
+            for combo in &dev.combos {
+                node.insert(
+                    format!(
+                        "combo {pkg} {speed}",
+                        pkg = dev.bonds.key(combo.devbond),
+                        speed = dev.speeds[combo.speed]
+                    ),
+                    DiffNode::new(),
+                );
+            }
+            for &dis in &dev.disabled {
+                let dis = match dis {
+                    DisabledPart::Emac(reg) => format!("emac {reg}"),
+                    DisabledPart::GtxRow(reg) => format!("gtx {reg}"),
+                    DisabledPart::SysMon => "sysmon".to_string(),
+                    DisabledPart::Gtp => "gtp".to_string(),
+                };
+                node.insert(format!("disabled {dis}"), DiffNode::new());
+            }
+            for (ddid, value) in &dev.data {
+                node.insert_leaf(
+                    format!("device_data {ddname}", ddname = self.int.devdata.key(ddid)),
+                    self.int.dump_value(self.int.devdata[ddid], value),
+                );
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("intdb", self.int.diff_node());
+        res.insert("gtz", self.gtz.diff_node());
+        res.insert("bsdata", self.bsdata.diff_node());
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
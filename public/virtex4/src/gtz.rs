@@ -6,6 +6,10 @@ use prjcombine_entity::{
     id::{EntityIdU16, EntityTag, EntityTagArith},
 };
 use prjcombine_interconnect::{db::PinDir, dir::DirV};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign},
+};
 
 impl EntityTag for GtzBel {
     const PREFIX: &'static str = "GTZ";
@@ -27,6 +31,7 @@ pub type GtzIntColId = EntityIdU16<GtzIntColTag>;
 pub type GtzIntRowId = EntityIdU16<GtzIntRowTag>;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtzBel {
     pub side: DirV,
     pub pins: BTreeMap<String, GtzIntPin>,
@@ -34,6 +39,7 @@ pub struct GtzBel {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtzIntPin {
     pub dir: PinDir,
     pub col: GtzIntColId,
@@ -41,16 +47,26 @@ pub struct GtzIntPin {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtzClkPin {
     pub dir: PinDir,
     pub idx: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Encode, Decode, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GtzDb {
     pub gtz: EntityMap<GtzBelId, String, GtzBel>,
 }
 
+fn dump_dir(dir: PinDir) -> &'static str {
+    match dir {
+        PinDir::Input => "input",
+        PinDir::Output => "output",
+        PinDir::Inout => unreachable!(),
+    }
+}
+
 impl GtzDb {
     pub fn dump(&self, o: &mut dyn std::io::Write) -> std::io::Result<()> {
         for (_, name, gtz) in &self.gtz {
@@ -59,11 +75,7 @@ impl GtzDb {
                 writeln!(
                     o,
                     "\t{dir} {pname} = INT {col} {row};",
-                    dir = match pin.dir {
-                        PinDir::Input => "input",
-                        PinDir::Output => "output",
-                        PinDir::Inout => unreachable!(),
-                    },
+                    dir = dump_dir(pin.dir),
                     col = pin.col,
                     row = pin.row
                 )?;
@@ -72,11 +84,7 @@ impl GtzDb {
                 writeln!(
                     o,
                     "\t{dir} {pname} = GCLK{idx};",
-                    dir = match pin.dir {
-                        PinDir::Input => "input",
-                        PinDir::Output => "output",
-                        PinDir::Inout => unreachable!(),
-                    },
+                    dir = dump_dir(pin.dir),
                     idx = pin.idx
                 )?;
             }
@@ -85,4 +93,81 @@ impl GtzDb {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the GTZ database: one node per GTZ, with its pins by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (_, name, gtz) in &self.gtz {
+            let mut node = DiffNode::leaf(gtz.side);
+            for (pname, pin) in &gtz.pins {
+                node.insert_leaf(
+                    format!("pin {pname}"),
+                    format!(
+                        "{dir} INT {col} {row}",
+                        dir = dump_dir(pin.dir),
+                        col = pin.col,
+                        row = pin.row
+                    ),
+                );
+            }
+            for (pname, pin) in &gtz.clk_pins {
+                node.insert_leaf(
+                    format!("pin {pname}"),
+                    format!("{dir} GCLK{idx}", dir = dump_dir(pin.dir), idx = pin.idx),
+                );
+            }
+            res.insert(format!("gtz {name}"), node);
+        }
+        res
+    }
+
+    /// Parses the `gtz` blocks at the current position, stopping at the first other line.
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = GtzDb::default();
+        while let Some(line) = p.peek()
+            && let Some(("gtz", header)) = line.block_start()
+        {
+            p.expect_line()?;
+            let Some((name, side)) = header.split_once(": ") else {
+                return Err(line.error(format!("invalid gtz header \"{header}\"")));
+            };
+            let mut gtz = GtzBel {
+                side: line.check(side.parse())?,
+                pins: BTreeMap::new(),
+                clk_pins: BTreeMap::new(),
+            };
+            while !p.try_block_end() {
+                let line = p.expect_line()?;
+                let (dir, val) = line.keyword_statement()?;
+                let dir = match dir {
+                    "input" => PinDir::Input,
+                    "output" => PinDir::Output,
+                    _ => return Err(line.error(format!("unknown pin direction {dir}"))),
+                };
+                let (pname, wire) = line.check(split_assign(val))?;
+                if let Some(idx) = wire.strip_prefix("GCLK") {
+                    let idx = line.check(parse_num(idx))?;
+                    gtz.clk_pins
+                        .insert(pname.to_string(), GtzClkPin { dir, idx });
+                } else {
+                    let words: Vec<_> = wire.split(' ').collect();
+                    let ["INT", col, row] = words[..] else {
+                        return Err(line.error(format!("invalid gtz pin \"{wire}\"")));
+                    };
+                    gtz.pins.insert(
+                        pname.to_string(),
+                        GtzIntPin {
+                            dir,
+                            col: line.check(parse_id(col, GtzIntColTag::PREFIX))?,
+                            row: line.check(parse_id(row, GtzIntRowTag::PREFIX))?,
+                        },
+                    );
+                }
+            }
+            if res.gtz.insert(name.to_string(), gtz).1.is_some() {
+                return Err(line.error(format!("duplicate gtz {name}")));
+            }
+        }
+        Ok(res)
+    }
 }
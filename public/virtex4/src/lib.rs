@@ -1,9 +1,11 @@
 pub mod bond;
+pub mod bscan;
 pub mod chip;
 pub mod db;
 pub mod defs;
 mod expand;
 pub mod expanded;
 pub mod gtz;
+pub mod pinout;
 
 pub use expand::expand_grid;
@@ -0,0 +1,324 @@
+//! Package pinout tables.
+
+use std::{collections::HashMap, fmt::Write};
+
+use prjcombine_interconnect::grid::DieIdExt;
+use prjcombine_types::pinout::{DiffPolarity, PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad, GtPad, GtzPad, SharedCfgPad, SysMonPad},
+    chip::{CfgRowKind, ChipKind, DisabledPart},
+    expanded::{ExpandedDevice, IoCoord, IoDiffKind, IoInfo, IoVrKind},
+};
+
+fn io_name(io: IoCoord) -> String {
+    format!("{cell}.{iob}", cell = io.cell, iob = io.iob)
+}
+
+/// Appends the configuration function of a shared configuration IO, in the
+/// vendor spelling of the given family.
+fn push_cfg_name(res: &mut String, kind: ChipKind, cfg: SharedCfgPad, is_spartan: bool) {
+    match (kind, cfg) {
+        (ChipKind::Virtex4, SharedCfgPad::Data(d)) => write!(res, "_D{d}").unwrap(),
+        (ChipKind::Virtex4, _) => unreachable!(),
+        (ChipKind::Virtex5 | ChipKind::Virtex6, cfg) => match cfg {
+            SharedCfgPad::Data(d) => {
+                if d >= 16 {
+                    write!(res, "_A{:02}", d - 16).unwrap();
+                }
+                write!(res, "_D{d}").unwrap();
+                if d < 3 {
+                    write!(res, "_FS{d}").unwrap();
+                }
+            }
+            SharedCfgPad::FoeB => *res += "_FOE_B_MOSI",
+            cfg => write!(res, "_{cfg}").unwrap(),
+        },
+        (ChipKind::Virtex7, cfg) => match cfg {
+            SharedCfgPad::Data(d) => {
+                if d >= 16 && !is_spartan {
+                    write!(res, "_A{:02}", d - 16).unwrap();
+                }
+                write!(res, "_D{d:02}").unwrap();
+                match d {
+                    0 => *res += "_MOSI",
+                    1 => *res += "_DIN",
+                    _ => (),
+                }
+            }
+            SharedCfgPad::Addr(_)
+            | SharedCfgPad::FweB
+            | SharedCfgPad::FoeB
+            | SharedCfgPad::AdvB
+                if is_spartan => {}
+            SharedCfgPad::CsoB => *res += "_DOUT_CSO_B",
+            SharedCfgPad::RdWrB => *res += "_RDWR_B",
+            SharedCfgPad::EmCclk => *res += "_EMCCLK",
+            cfg => write!(res, "_{cfg}").unwrap(),
+        },
+    }
+}
+
+impl ExpandedDevice<'_> {
+    /// Collects the system monitor auxiliary analog inputs, as the
+    /// `(sysmon, index, polarity)` of every IO that doubles as one.
+    fn sysmon_vaux_ios(&self) -> HashMap<IoCoord, (u32, usize, char)> {
+        let mut sysmons = vec![];
+        for (die, chip) in &self.chips {
+            match self.kind {
+                ChipKind::Virtex4 => {
+                    for (row, _) in chip
+                        .rows_cfg
+                        .iter()
+                        .filter(|&&(_, kind)| kind == CfgRowKind::Sysmon)
+                    {
+                        sysmons.push((die.cell(self.col_cfg, *row), 8));
+                    }
+                }
+                ChipKind::Virtex5 => sysmons.push((self.tile_cfg(die).cell, 16)),
+                ChipKind::Virtex6 => {
+                    if !self.disabled.contains(&DisabledPart::SysMon) {
+                        sysmons.push((self.tile_cfg(die).cell, 16));
+                    }
+                }
+                ChipKind::Virtex7 => {
+                    let is_primary = self.interposer.is_none_or(|ip| ip.primary == die);
+                    if chip.regs > 1 && is_primary {
+                        let row = chip.row_reg_hclk(chip.reg_cfg);
+                        sysmons.push((die.cell(self.col_cfg, row), 16));
+                    }
+                }
+            }
+        }
+        let mut res = HashMap::new();
+        for (sysmon, (cell, num)) in sysmons.into_iter().enumerate() {
+            for idx in 0..num {
+                if let Some((vauxp, vauxn)) = self.get_sysmon_vaux(cell, idx) {
+                    res.insert(vauxp, (sysmon as u32, idx, 'P'));
+                    res.insert(vauxn, (sysmon as u32, idx, 'N'));
+                }
+            }
+        }
+        res
+    }
+
+    /// Builds the vendor pin function name of an IO.  The suffixes and their
+    /// order differ between the families.
+    fn io_function_name(
+        &self,
+        device: &str,
+        package: &str,
+        info: &IoInfo,
+        cfg: Option<SharedCfgPad>,
+        vaux: Option<(u32, usize, char)>,
+    ) -> String {
+        let mut res = match info.diff {
+            IoDiffKind::None => format!("IO_{}", info.pkgid),
+            IoDiffKind::P(_) => format!("IO_L{}P", info.pkgid),
+            IoDiffKind::N(_) => format!("IO_L{}N", info.pkgid),
+        };
+        let push_vr = |res: &mut String| {
+            if info.is_vref {
+                *res += "_VREF";
+            }
+            match info.vr {
+                IoVrKind::VrP => *res += "_VRP",
+                IoVrKind::VrN => *res += "_VRN",
+                IoVrKind::None => (),
+            }
+        };
+        match self.kind {
+            ChipKind::Virtex4 => {
+                if let Some(cfg) = cfg {
+                    push_cfg_name(&mut res, self.kind, cfg, false);
+                }
+                if info.is_gc {
+                    res += "_GC";
+                }
+                push_vr(&mut res);
+                if info.is_srcc {
+                    res += "_CC";
+                }
+                if let Some((sysmon, idx, _)) = vaux {
+                    write!(res, "_{}{idx}", ["SM", "ADC"][sysmon as usize]).unwrap();
+                }
+                if info.is_lc {
+                    res += "_LC";
+                }
+            }
+            ChipKind::Virtex5 | ChipKind::Virtex6 => {
+                if self.kind == ChipKind::Virtex5 {
+                    if info.is_srcc {
+                        res += "_CC";
+                    }
+                } else {
+                    if info.is_srcc {
+                        res += "_SRCC";
+                    }
+                    if info.is_mrcc {
+                        res += "_MRCC";
+                    }
+                }
+                if info.is_gc {
+                    res += "_GC";
+                }
+                push_vr(&mut res);
+                if let Some(cfg) = cfg {
+                    push_cfg_name(&mut res, self.kind, cfg, false);
+                }
+                if let Some((_, idx, pn)) = vaux {
+                    write!(res, "_SM{idx}{pn}").unwrap();
+                }
+            }
+            ChipKind::Virtex7 => {
+                // A few pairs of bank 16 in the Kintex-7 FBG484 packages are
+                // documented as single-ended.
+                if matches!(package, "fbg484" | "fbv484" | "fbv485")
+                    && device.contains("7k")
+                    && info.bank == 16
+                    && matches!(info.biob, 2 | 14 | 37)
+                {
+                    res = format!("IO_{}", info.pkgid);
+                }
+                if let Some(byte) = info.byte {
+                    write!(res, "_T{byte}").unwrap();
+                }
+                // The two analog inputs in bank 35 list their analog function
+                // before the clock capability.
+                let early_vaux = info.bank == 35 && matches!(info.biob, 21 | 22);
+                if early_vaux && let Some((_, idx, pn)) = vaux {
+                    write!(res, "_AD{idx}{pn}").unwrap();
+                }
+                if info.is_srcc {
+                    res += "_SRCC";
+                }
+                if info.is_mrcc {
+                    res += "_MRCC";
+                }
+                if info.is_dqs {
+                    res += "_DQS";
+                }
+                if let Some(cfg) = cfg {
+                    push_cfg_name(&mut res, self.kind, cfg, device.contains("7s"));
+                }
+                if !early_vaux && let Some((_, idx, pn)) = vaux {
+                    write!(res, "_AD{idx}{pn}").unwrap();
+                }
+                push_vr(&mut res);
+            }
+        }
+        write!(res, "_{}", info.bank).unwrap();
+        res
+    }
+
+    /// System monitor auxiliary analog inputs appear in the pin names, but are
+    /// not listed among the functions.
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let mut ios = HashMap::new();
+        for &io in &self.io {
+            let info = self.get_io_info(io);
+            ios.insert((info.bank, info.biob), (io, info));
+        }
+        let vaux = self.sysmon_vaux_ios();
+        let mut partners = HashMap::new();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(..) => PinKind::Io,
+                BondPad::PsIo(..) | BondPad::PsVref(..) => PinKind::Ps,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::Gt(..) | BondPad::Gtz(..) | BondPad::GtRegion(..) => PinKind::Gt,
+                BondPad::SysMon(..) => PinKind::SysMon,
+                BondPad::VccInt
+                | BondPad::VccAux
+                | BondPad::VccBram
+                | BondPad::VccO(_)
+                | BondPad::VccBatt
+                | BondPad::VccAuxIo(_)
+                | BondPad::Vfs
+                | BondPad::VccPsInt
+                | BondPad::VccPsAux
+                | BondPad::VccPsPll => PinKind::Power,
+                BondPad::Gnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+                BondPad::Rsvd | BondPad::RsvdGnd | BondPad::Dxp | BondPad::Dxn => PinKind::Other,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            match pad {
+                BondPad::Io(bank, biob) => {
+                    ppin = ppin.with_bank(bank);
+                    let Some(&(io, ref info)) = ios.get(&(bank, biob)) else {
+                        res.add_pin(ppin);
+                        continue;
+                    };
+                    let cfg = self.cfg_io.get_by_right(&io).copied();
+                    ppin = ppin.with_io(io_name(io)).with_name(self.io_function_name(
+                        device,
+                        package,
+                        info,
+                        cfg,
+                        vaux.get(&io).copied(),
+                    ));
+                    match info.diff {
+                        IoDiffKind::P(other) => {
+                            ppin.diff = Some(DiffPolarity::P);
+                            partners.insert(io_name(io), io_name(other));
+                        }
+                        IoDiffKind::N(other) => {
+                            ppin.diff = Some(DiffPolarity::N);
+                            partners.insert(io_name(io), io_name(other));
+                        }
+                        IoDiffKind::None => (),
+                    }
+                    if let Some(cpad) = cfg {
+                        ppin = ppin.with_function(cpad);
+                    }
+                    for (cond, function) in [
+                        (info.is_gc, "GC"),
+                        (info.is_srcc, "SRCC"),
+                        (info.is_mrcc, "MRCC"),
+                        (info.is_dqs, "DQS"),
+                        (info.is_lc, "LC"),
+                        (info.is_vref, "VREF"),
+                        (info.vr == IoVrKind::VrP, "VRP"),
+                        (info.vr == IoVrKind::VrN, "VRN"),
+                    ] {
+                        if cond {
+                            ppin = ppin.with_function(function);
+                        }
+                    }
+                }
+                BondPad::Gt(bank, gpad) => {
+                    let name = match gpad {
+                        GtPad::ClkP(idx) => format!("MGTREFCLK{idx}P_{bank}"),
+                        GtPad::ClkN(idx) => format!("MGTREFCLK{idx}N_{bank}"),
+                        _ => format!("MGT{gpad}_{bank}"),
+                    };
+                    ppin = ppin.with_bank(bank).with_name(name);
+                }
+                BondPad::Gtz(bank, gpad) => {
+                    let name = match gpad {
+                        GtzPad::ClkP(idx) => format!("MGTZREFCLK{idx}P_{bank}"),
+                        GtzPad::ClkN(idx) => format!("MGTZREFCLK{idx}N_{bank}"),
+                        _ => format!("MGTZ{gpad}_{bank}"),
+                    };
+                    ppin = ppin.with_bank(bank).with_name(name);
+                }
+                BondPad::SysMon(bank, spad) => {
+                    ppin = ppin.with_name(match spad {
+                        SysMonPad::AVss => format!("GNDADC_{bank}"),
+                        SysMonPad::AVdd => format!("VCCADC_{bank}"),
+                        _ => format!("{spad}_{bank}"),
+                    });
+                }
+                BondPad::VccO(bank) | BondPad::PsVref(bank, _) | BondPad::PsIo(bank, _) => {
+                    ppin = ppin.with_bank(bank);
+                }
+                _ => (),
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&partners);
+        res
+    }
+}
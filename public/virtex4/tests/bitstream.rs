@@ -0,0 +1,54 @@
+use prjcombine_entity::EntityVec;
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_test_util::{check_decode_roundtrip, check_emit_roundtrip, sample_config};
+use prjcombine_virtex4::{db::Database, expand_grid};
+use prjcombine_xilinx_bitstream::{BitstreamMode, KeyData, emit, parse_strict};
+
+const VIRTEX4_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex4.zstd");
+const VIRTEX5_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex5.zstd");
+const VIRTEX6_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex6.zstd");
+const VIRTEX7_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex7.zstd");
+
+fn check_roundtrip(db: &str, devices: &[&str]) {
+    let db = Database::from_file(db).unwrap();
+    for &name in devices {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let chips: EntityVec<DieId, _> = dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+        let interposer = dev.interposer.map(|ip| &db.interposers[ip]);
+        let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int, &db.gtz);
+        check_decode_roundtrip(name, &edev.egrid, &db.bsdata, |config| {
+            let bitstream = edev.encode(&db.bsdata, config).unwrap();
+            let data = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain).unwrap();
+            let parsed = parse_strict(&edev.bs_geom, &data, &KeyData::None).unwrap();
+            edev.decode(&db.bsdata, &parsed).unwrap()
+        });
+        let config = sample_config(&edev.egrid, &db.bsdata);
+        let bitstream = edev.encode(&db.bsdata, &config).unwrap();
+        check_emit_roundtrip(
+            name,
+            &edev.bs_geom,
+            &bitstream,
+            &[BitstreamMode::Plain, BitstreamMode::Compress],
+        );
+    }
+}
+
+#[test]
+fn roundtrip_virtex4() {
+    check_roundtrip(VIRTEX4_DB, &["xc4vlx15", "xc4vfx12"]);
+}
+
+#[test]
+fn roundtrip_virtex5() {
+    check_roundtrip(VIRTEX5_DB, &["xc5vlx30"]);
+}
+
+#[test]
+fn roundtrip_virtex6() {
+    check_roundtrip(VIRTEX6_DB, &["xc6vlx75t"]);
+}
+
+#[test]
+fn roundtrip_virtex7() {
+    check_roundtrip(VIRTEX7_DB, &["xc7a12t", "xc7k70t"]);
+}
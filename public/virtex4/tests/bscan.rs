@@ -0,0 +1,114 @@
+use std::collections::{BTreeSet, HashMap};
+
+use prjcombine_entity::EntityVec;
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_types::bscan::BScanPad;
+use prjcombine_virtex4::{bond::BondPad, db::Database, expand_grid};
+
+const VIRTEX4_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex4.zstd");
+const VIRTEX5_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex5.zstd");
+const VIRTEX6_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex6.zstd");
+const VIRTEX7_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex7.zstd");
+
+fn pad_bits(pad: BScanPad) -> Vec<usize> {
+    match pad {
+        BScanPad::Input(i) | BScanPad::Output(i) => vec![i],
+        BScanPad::OutputTristate(o, t)
+        | BScanPad::OutputEnable(o, t)
+        | BScanPad::BiTristate(o, t) => vec![o, t],
+        BScanPad::InputOutputTristate(i, o, t) | BScanPad::InputOutputEnable(i, o, t) => {
+            vec![i, o, t]
+        }
+    }
+}
+
+fn check_bscan(db: &str, devices: &[&str]) {
+    let db = Database::from_file(db).unwrap();
+    for &name in devices {
+        let dev = db.devices.iter().find(|dev| dev.name == name).unwrap();
+        let chips: EntityVec<DieId, _> = dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+        let interposer = dev.interposer.map(|ip| &db.interposers[ip]);
+        let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int, &db.gtz);
+        let bscan = edev.get_bscan();
+
+        // Every IO has a cell, and the cells cover the chain without overlap.
+        assert_eq!(bscan.io.len(), edev.io.len(), "{name}");
+        assert_eq!(bscan.bits, 3 * edev.io.len(), "{name}");
+        let mut bits = BTreeSet::new();
+        for &pad in bscan.io.values() {
+            for bit in pad_bits(pad) {
+                assert!(bits.insert(bit), "{name}: bit {bit} used twice");
+            }
+        }
+        assert_eq!(bits.len(), bscan.bits, "{name}");
+
+        // The chain walks the dies in bitstream order, and the IO list within a die.
+        let mut prev: Option<(usize, usize, usize)> = None;
+        let mut order: Vec<_> = edev
+            .io
+            .iter()
+            .enumerate()
+            .map(|(idx, io)| {
+                let rank = edev
+                    .bs_geom
+                    .die_order
+                    .iter()
+                    .position(|&die| die == io.cell.die)
+                    .unwrap();
+                let BScanPad::InputOutputTristate(_, _, t) = bscan.io[io] else {
+                    unreachable!()
+                };
+                (t, rank, idx)
+            })
+            .collect();
+        order.sort();
+        for cur in order {
+            if let Some(prev) = prev {
+                assert!(
+                    (prev.1, prev.2) < (cur.1, cur.2),
+                    "{name}: chain out of order"
+                );
+            }
+            prev = Some(cur);
+        }
+
+        // Every bonded IO pad is on the chain, with a cell of its own.
+        let mut ios = HashMap::new();
+        for &io in &edev.io {
+            let info = edev.get_io_info(io);
+            ios.insert((info.bank, info.biob), io);
+        }
+        for (_, package, &bond) in &dev.bonds {
+            let bond = &db.bonds[bond];
+            let mut seen = BTreeSet::new();
+            for (pin, &pad) in &bond.pins {
+                let BondPad::Io(bank, biob) = pad else {
+                    continue;
+                };
+                let io = ios[&(bank, biob)];
+                let cell = bscan.io[&io];
+                assert!(seen.insert(cell), "{name}-{package}: {pin} shares a cell");
+            }
+        }
+    }
+}
+
+#[test]
+fn bscan_virtex4() {
+    check_bscan(VIRTEX4_DB, &["xc4vlx15", "xc4vfx12"]);
+}
+
+#[test]
+fn bscan_virtex5() {
+    check_bscan(VIRTEX5_DB, &["xc5vlx30"]);
+}
+
+#[test]
+fn bscan_virtex6() {
+    check_bscan(VIRTEX6_DB, &["xc6vlx75t"]);
+}
+
+#[test]
+fn bscan_virtex7() {
+    check_bscan(VIRTEX7_DB, &["xc7a12t", "xc7k70t", "xc7v2000t"]);
+}
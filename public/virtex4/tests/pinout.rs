@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use prjcombine_entity::EntityVec;
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_test_util::check_pinout;
+use prjcombine_types::pinout::{PinKind, Pinout};
+use prjcombine_virtex4::{db::Database, expand_grid};
+
+const VIRTEX4_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex4.zstd");
+const VIRTEX5_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex5.zstd");
+const VIRTEX6_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex6.zstd");
+const VIRTEX7_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../databases/virtex7.zstd");
+
+fn pinout(db: &Database, device: &str, package: &str) -> Pinout {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let chips: EntityVec<DieId, _> = dev.chips.values().map(|&chip| &db.chips[chip]).collect();
+    let interposer = dev.interposer.map(|ip| &db.interposers[ip]);
+    let edev = expand_grid(&chips, interposer, &dev.disabled, &db.int, &db.gtz);
+    let (_, &bond) = dev.bonds.get(package).unwrap();
+    let pinout = edev.make_pinout(&db.bonds[bond], device, package);
+    // the vendor names every IO pin of a package differently
+    let mut names = HashSet::new();
+    for pin in &pinout.pins {
+        if pin.kind == PinKind::Io {
+            assert!(names.insert(&pin.name), "{device} {package}: {}", pin.name);
+        }
+    }
+    pinout
+}
+
+#[test]
+fn pinout_virtex4() {
+    let db = Database::from_file(VIRTEX4_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xc4vfx12", "ff668"),
+        &[
+            ("AE14", "IO_L5P_GC_LC_4", Some("4"), Some("AE13")),
+            ("G5", "IO_L23N_VRP_6", Some("6"), Some("G6")),
+            ("AD5", "IO_L22P_8", Some("8"), Some("AD4")),
+            ("D12", "IO_L8N_D16_CC_LC_1", Some("1"), Some("E13")),
+            ("C11", "IO_L6P_D21_LC_1", Some("1"), Some("D11")),
+        ],
+    );
+}
+
+#[test]
+fn pinout_virtex5() {
+    let db = Database::from_file(VIRTEX5_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xc5vlx50t", "ff1136"),
+        &[
+            ("AH15", "IO_L5P_GC_4", Some("4"), Some("AG15")),
+            ("H18", "IO_L0N_CC_GC_3", Some("3"), Some("H17")),
+            ("G15", "IO_L2P_GC_VRN_3", Some("3"), Some("G16")),
+            ("AE24", "IO_L19N_21", Some("21"), Some("AD24")),
+            ("H22", "IO_L4N_VREF_A10_D26_1", Some("1"), Some("G22")),
+            ("G23", "IO_L8P_CC_A03_D19_1", Some("1"), Some("H23")),
+            ("K32", "IO_L11N_CC_SM14N_11", Some("11"), Some("K33")),
+        ],
+    );
+}
+
+#[test]
+fn pinout_virtex6() {
+    let db = Database::from_file(VIRTEX6_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xc6vlx240t", "ff1156"),
+        &[
+            ("J9", "IO_L0P_GC_34", Some("34"), Some("H9")),
+            ("AC22", "IO_L19P_24", Some("24"), Some("AD22")),
+            ("A8", "IO_L5N_A08_D24_34", Some("34"), Some("A9")),
+            ("A13", "IO_L3P_SM1P_35", Some("35"), Some("A14")),
+        ],
+    );
+}
+
+#[test]
+fn pinout_virtex7() {
+    let db = Database::from_file(VIRTEX7_DB).unwrap();
+    check_pinout(
+        &pinout(&db, "xc7a35t", "csg324"),
+        &[
+            ("E3", "IO_L12P_T1_MRCC_35", Some("35"), Some("D3")),
+            ("C2", "IO_L16P_T2_35", Some("35"), Some("C1")),
+            ("A9", "IO_L14N_T2_SRCC_16", Some("16"), Some("A10")),
+            ("A1", "IO_L9N_T1_DQS_AD7N_35", Some("35"), Some("B1")),
+            ("L16", "IO_L3N_T0_DQS_EMCCLK_14", Some("14"), Some("L15")),
+            ("J5", "IO_25_35", Some("35"), None),
+            // the other half of the pair is not bonded in this package
+            ("D10", "IO_L19N_T3_VREF_16", Some("16"), None),
+        ],
+    );
+    check_pinout(
+        &pinout(&db, "xc7a35t", "cpg236"),
+        &[
+            ("W5", "IO_L12P_T1_MRCC_34", Some("34"), Some("W4")),
+            ("V17", "IO_L19N_T3_A09_D25_VREF_14", Some("14"), Some("V16")),
+            ("U16", "IO_L23N_T3_A02_D18_14", Some("14"), Some("U15")),
+            ("A18", "IO_L19N_T3_VREF_16", Some("16"), Some("B18")),
+        ],
+    );
+    check_pinout(
+        &pinout(&db, "xc7z020", "clg400"),
+        &[
+            ("H16", "IO_L13P_T2_MRCC_35", Some("35"), Some("H17")),
+            ("M14", "IO_L23P_T3_35", Some("35"), Some("M15")),
+            ("R19", "IO_0_34", Some("34"), None),
+        ],
+    );
+}
@@ -0,0 +1,40 @@
+use prjcombine_types::db::DumpFlags;
+use prjcombine_virtex4::db::Database;
+
+const TARGETS: [&str; 3] = ["virtex4", "virtex5", "virtex6"];
+// No text dump is checked in for these, so they are dumped on the fly.
+const TARGETS_ZSTD: [&str; 1] = ["virtex7"];
+
+fn db_path(target: &str, ext: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.{ext}",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+#[test]
+fn text_roundtrip() {
+    for target in TARGETS {
+        let db = Database::from_file(db_path(target, "zstd")).unwrap();
+        let parsed = Database::from_text_file(db_path(target, "txt")).unwrap();
+        // Not assert_eq, the Debug output of a whole database is unreadable.
+        assert!(
+            parsed == db,
+            "{target}: text dump does not match the database"
+        );
+    }
+}
+
+#[test]
+fn dump_roundtrip() {
+    for target in TARGETS_ZSTD {
+        let db = Database::from_file(db_path(target, "zstd")).unwrap();
+        let mut text = vec![];
+        db.dump(&mut text, DumpFlags::all()).unwrap();
+        let parsed = Database::from_text(std::str::from_utf8(&text).unwrap()).unwrap();
+        assert!(
+            parsed == db,
+            "{target}: text dump does not match the database"
+        );
+    }
+}
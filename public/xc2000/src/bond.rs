@@ -3,8 +3,13 @@ use std::collections::BTreeMap;
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_interconnect::grid::EdgeIoCoord;
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, split_assign},
+};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CfgPad {
     Cclk,
     Done,
@@ -32,7 +37,26 @@ impl std::fmt::Display for CfgPad {
     }
 }
 
+impl std::str::FromStr for CfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "CCLK" => CfgPad::Cclk,
+            "DONE" => CfgPad::Done,
+            "PROG_B" => CfgPad::ProgB,
+            "PWRDWN_B" => CfgPad::PwrdwnB,
+            "M0" => CfgPad::M0,
+            "M1" => CfgPad::M1,
+            "TDO" => CfgPad::Tdo,
+            "M2" => CfgPad::M2,
+            _ => return Err(format!("unknown config pad {s}")),
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Io(EdgeIoCoord),
     Gnd,
@@ -56,7 +80,28 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "GND" => BondPad::Gnd,
+            "VCC" => BondPad::Vcc,
+            "NC" => BondPad::Nc,
+            "VCCINT" => BondPad::VccInt,
+            _ => {
+                if s.starts_with("IOB_") {
+                    BondPad::Io(s.parse()?)
+                } else {
+                    BondPad::Cfg(s.parse()?)
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub pins: BTreeMap<String, BondPad>,
 }
@@ -91,4 +136,27 @@ impl Bond {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the bond: pins by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            if kw != "pin" {
+                return Err(line.error(format!("unknown bond statement {kw}")));
+            }
+            let (pin, pad) = line.check(split_assign(val))?;
+            pins.insert(pin.to_string(), line.check(pad.parse())?);
+        }
+        Ok(Bond { pins })
+    }
 }
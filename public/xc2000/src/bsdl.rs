@@ -0,0 +1,70 @@
+//! BSDL file generation.
+
+use std::collections::BTreeMap;
+
+use prjcombine_types::bsdl::{Bsdl, InstructionRegister, PortKind};
+
+use crate::{
+    bond::{Bond, BondPad, CfgPad},
+    chip::{Chip, SharedCfgPad},
+};
+
+impl Chip {
+    /// Panics on XC2000 and XC3000, which have no boundary scan.
+    pub fn make_bsdl(&self, bond: &Bond, entity: &str, package: &str, idcode: Option<u32>) -> Bsdl {
+        let bscan = self.get_bscan();
+        let mut res = Bsdl::new(entity, package, 3, bscan.bits);
+        // the TAP pins other than the XC4000 TDO are shared with IOBs; their boundary
+        // cells are left out of the description
+        let mut tap = BTreeMap::new();
+        for (spad, kind) in [
+            (SharedCfgPad::Tck, PortKind::In),
+            (SharedCfgPad::Tms, PortKind::In),
+            (SharedCfgPad::Tdi, PortKind::In),
+            (SharedCfgPad::Tdo, PortKind::Out),
+        ] {
+            if let Some(&crd) = self.cfg_io.get(&spad) {
+                tap.insert(crd, (spad, kind));
+            }
+        }
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Io(crd) => {
+                    if let Some(&(spad, kind)) = tap.get(&crd) {
+                        res.add_pin(&spad.to_string(), kind, pin);
+                    } else if let Some(&bpad) = bscan.io.get(&crd) {
+                        res.add_pad(&format!("IO_{pin}"), pin, bpad);
+                    } else {
+                        res.add_pin(&format!("IO_{pin}"), PortKind::Linkage, pin);
+                    }
+                }
+                BondPad::Cfg(CfgPad::Tdo) => {
+                    res.add_pin(&pad.to_string(), PortKind::Out, pin);
+                }
+                BondPad::Cfg(cpad) => {
+                    if let Some(&bpad) = bscan.cfg.get(&cpad) {
+                        res.add_pad(&pad.to_string(), pin, bpad);
+                    } else {
+                        res.add_pin(&pad.to_string(), PortKind::Linkage, pin);
+                    }
+                }
+                BondPad::Nc => (),
+                _ => res.add_pin(&pad.to_string(), PortKind::Linkage, pin),
+            }
+        }
+        for (name, opcode, register) in [
+            ("EXTEST", 0, InstructionRegister::Standard),
+            ("SAMPLE", 1, InstructionRegister::Standard),
+            ("USER1", 2, InstructionRegister::Private),
+            ("USER2", 3, InstructionRegister::Private),
+            ("READBACK", 4, InstructionRegister::Private),
+            ("CONFIGURE", 5, InstructionRegister::Private),
+            ("IDCODE", 6, InstructionRegister::Standard),
+            ("BYPASS", 7, InstructionRegister::Standard),
+        ] {
+            res.add_instruction(name, opcode, register);
+        }
+        res.idcode = idcode.map(|idcode| (idcode, 0x0fff_ffff));
+        res
+    }
+}
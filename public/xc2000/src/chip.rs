@@ -2,15 +2,22 @@ use std::collections::{BTreeMap, BTreeSet};
 
 use bincode::{Decode, Encode};
 use itertools::Itertools;
-use prjcombine_entity::{EntityId, EntityRange};
+use prjcombine_entity::{EntityId, EntityRange, id::EntityTag};
 use prjcombine_interconnect::{
     dir::{DirH, DirHV, DirV},
-    grid::{BelCoord, ColId, DieId, DieIdExt, EdgeIoCoord, RowId, TileCoord, TileIobId},
+    grid::{
+        BelCoord, ColId, ColTag, DieId, DieIdExt, EdgeIoCoord, RowId, RowTag, TileCoord, TileIobId,
+    },
+};
+use prjcombine_types::{
+    diff::DiffNode,
+    text::{TextError, TextParser, parse_id, parse_num, split_assign},
 };
 
 use crate::{xc2000, xc3000, xc4000, xc5200};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SharedCfgPad {
     Addr(u8),
     Data(u8),
@@ -56,7 +63,40 @@ impl std::fmt::Display for SharedCfgPad {
     }
 }
 
+impl std::str::FromStr for SharedCfgPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "LDC" => SharedCfgPad::Ldc,
+            "HDC" => SharedCfgPad::Hdc,
+            "RCLK_B" => SharedCfgPad::RclkB,
+            "DOUT" => SharedCfgPad::Dout,
+            "M2" => SharedCfgPad::M2,
+            "INIT_B" => SharedCfgPad::InitB,
+            "CS0_B" => SharedCfgPad::Cs0B,
+            "CS1_B" => SharedCfgPad::Cs1B,
+            "TCK" => SharedCfgPad::Tck,
+            "TDI" => SharedCfgPad::Tdi,
+            "TMS" => SharedCfgPad::Tms,
+            "TDO" => SharedCfgPad::Tdo,
+            "M0" => SharedCfgPad::M0,
+            "M1" => SharedCfgPad::M1,
+            _ => {
+                if let Some(idx) = s.strip_prefix('A') {
+                    SharedCfgPad::Addr(parse_num(idx)?)
+                } else if let Some(idx) = s.strip_prefix('D') {
+                    SharedCfgPad::Data(parse_num(idx)?)
+                } else {
+                    return Err(format!("unknown config pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Xc2000,
     Xc3000,
@@ -94,6 +134,28 @@ impl std::fmt::Display for ChipKind {
     }
 }
 
+impl std::str::FromStr for ChipKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "xc2000" => ChipKind::Xc2000,
+            "xc3000" => ChipKind::Xc3000,
+            "xc3000a" => ChipKind::Xc3000A,
+            "xc4000" => ChipKind::Xc4000,
+            "xc4000a" => ChipKind::Xc4000A,
+            "xc4000h" => ChipKind::Xc4000H,
+            "xc4000e" => ChipKind::Xc4000E,
+            "xc4000ex" => ChipKind::Xc4000Ex,
+            "xc4000xla" => ChipKind::Xc4000Xla,
+            "xc4000xv" => ChipKind::Xc4000Xv,
+            "spartanxl" => ChipKind::SpartanXl,
+            "xc5200" => ChipKind::Xc5200,
+            _ => return Err(format!("unknown chip kind {s}")),
+        })
+    }
+}
+
 impl ChipKind {
     pub fn is_xc3000(self) -> bool {
         matches!(self, Self::Xc3000 | Self::Xc3000A)
@@ -123,6 +185,7 @@ impl ChipKind {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub columns: usize,
@@ -134,6 +197,7 @@ pub struct Chip {
     // XC2000 only
     pub cols_bidi: BTreeSet<ColId>,
     pub rows_bidi: BTreeSet<RowId>,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub cfg_io: BTreeMap<SharedCfgPad, EdgeIoCoord>,
     pub unbonded_io: BTreeSet<EdgeIoCoord>,
 }
@@ -801,4 +865,82 @@ impl Chip {
         }
         Ok(())
     }
+
+    /// Builds the diff tree of the chip: one entry per field, with columns, rows and IOs
+    /// keyed by name.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("kind", self.kind);
+        res.insert_leaf("columns", self.columns);
+        res.insert_leaf("rows", self.rows);
+        res.insert_leaf("small", self.is_small);
+        res.insert_leaf("buff_large", self.is_buff_large);
+        for col in &self.cols_bidi {
+            res.insert(format!("col_bidi {col}"), DiffNode::new());
+        }
+        for row in &self.rows_bidi {
+            res.insert(format!("row_bidi {row}"), DiffNode::new());
+        }
+        for (k, v) in &self.cfg_io {
+            res.insert_leaf(format!("cfg_io {k}"), v);
+        }
+        for io in &self.unbonded_io {
+            res.insert(format!("unbonded_io {io}"), DiffNode::new());
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut columns = None;
+        let mut rows = None;
+        let mut is_small = false;
+        let mut is_buff_large = false;
+        let mut cols_bidi = BTreeSet::new();
+        let mut rows_bidi = BTreeSet::new();
+        let mut cfg_io = BTreeMap::new();
+        let mut unbonded_io = BTreeSet::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "kind" => kind = Some(line.check(val.parse())?),
+                "columns" => columns = Some(line.check(parse_num(val))?),
+                "rows" => rows = Some(line.check(parse_num(val))?),
+                "small" => is_small = true,
+                "buff_large" => is_buff_large = true,
+                "cols_bidi" => {
+                    for col in val.split(", ") {
+                        cols_bidi.insert(line.check(parse_id(col, ColTag::PREFIX))?);
+                    }
+                }
+                "rows_bidi" => {
+                    for row in val.split(", ") {
+                        rows_bidi.insert(line.check(parse_id(row, RowTag::PREFIX))?);
+                    }
+                }
+                "cfg_io" => {
+                    let (pad, io) = line.check(split_assign(val))?;
+                    cfg_io.insert(line.check(pad.parse())?, line.check(io.parse())?);
+                }
+                "unbonded_io" => {
+                    for io in val.split(", ") {
+                        unbonded_io.insert(line.check(io.parse())?);
+                    }
+                }
+                _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            columns: columns.ok_or_else(|| p.missing("columns"))?,
+            rows: rows.ok_or_else(|| p.missing("rows"))?,
+            is_small,
+            is_buff_large,
+            cols_bidi,
+            rows_bidi,
+            cfg_io,
+            unbonded_io,
+        })
+    }
 }
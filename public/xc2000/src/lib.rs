@@ -1,9 +1,11 @@
 pub mod bond;
 pub mod bscan;
+pub mod bsdl;
 pub mod chip;
 pub mod db;
 mod expand;
 pub mod expanded;
+pub mod pinout;
 pub mod xc2000;
 pub mod xc3000;
 pub mod xc4000;
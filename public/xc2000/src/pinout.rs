@@ -0,0 +1,36 @@
+//! Package pinout tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prjcombine_types::pinout::{PinKind, Pinout, PinoutPin};
+
+use crate::{
+    bond::{Bond, BondPad},
+    chip::Chip,
+};
+
+impl Chip {
+    pub fn make_pinout(&self, bond: &Bond, device: &str, package: &str) -> Pinout {
+        let cfg_io: BTreeMap<_, _> = self.cfg_io.iter().map(|(&k, &v)| (v, k)).collect();
+        let mut res = Pinout::new(device, package);
+        for (pin, &pad) in &bond.pins {
+            let kind = match pad {
+                BondPad::Io(_) => PinKind::Io,
+                BondPad::Cfg(_) => PinKind::Config,
+                BondPad::Vcc | BondPad::VccInt => PinKind::Power,
+                BondPad::Gnd => PinKind::Ground,
+                BondPad::Nc => PinKind::Nc,
+            };
+            let mut ppin = PinoutPin::new(pin, pad, kind);
+            if let BondPad::Io(io) = pad {
+                ppin = ppin.with_io(io);
+                if let Some(cpad) = cfg_io.get(&io) {
+                    ppin = ppin.with_function(cpad);
+                }
+            }
+            res.add_pin(ppin);
+        }
+        res.finish(&HashMap::new());
+        res
+    }
+}
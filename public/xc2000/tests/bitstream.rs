@@ -0,0 +1,103 @@
+use prjcombine_xc2000::db::Database;
+use prjcombine_xilinx_bitstream::{
+    Bitstream, BitstreamError, BitstreamMode, KeyData, emit, parse_strict,
+};
+
+fn check_emit_roundtrip(db: &str) {
+    let path = format!("{}/../../databases/{db}.zstd", env!("CARGO_MANIFEST_DIR"));
+    let db = Database::from_file(path).unwrap();
+    let dev = &db.devices[0];
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let bitstream = Bitstream::new(&edev.bs_geom);
+    prjcombine_test_util::check_emit_roundtrip(
+        &dev.name,
+        &edev.bs_geom,
+        &bitstream,
+        &[BitstreamMode::Plain],
+    );
+}
+
+#[test]
+fn emit_roundtrip_xc2000() {
+    check_emit_roundtrip("xc2000");
+}
+
+#[test]
+fn emit_roundtrip_xc3000() {
+    check_emit_roundtrip("xc3000");
+}
+
+#[test]
+fn emit_roundtrip_xc4000() {
+    check_emit_roundtrip("xc4000");
+}
+
+#[test]
+fn emit_roundtrip_xc4000xla() {
+    check_emit_roundtrip("xc4000xla");
+}
+
+#[test]
+fn emit_roundtrip_spartanxl() {
+    check_emit_roundtrip("spartanxl");
+}
+
+#[test]
+fn emit_roundtrip_xc5200() {
+    check_emit_roundtrip("xc5200");
+}
+
+// The check bits at the end of the final frame are computed by the emitter; any value other
+// than all set or all clear is rejected instead of being overwritten.
+// `crc_enable` is the bit of the first frame that enables CRC checking and its value.
+fn check_emit_bad_check_bits(db: &str, crc_enable: (usize, bool)) {
+    let path = format!("{}/../../databases/{db}.zstd", env!("CARGO_MANIFEST_DIR"));
+    let db = Database::from_file(path).unwrap();
+    let dev = &db.devices[0];
+    let edev = db.chips[dev.chip].expand_grid(&db.int);
+    let mut bitstream = Bitstream::new(&edev.bs_geom);
+    let die = bitstream.die.first_mut().unwrap();
+    die.frame_mut(0).set(crc_enable.0, crc_enable.1);
+    let data = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain).unwrap();
+    let mut bitstream = parse_strict(&edev.bs_geom, &data, &KeyData::None).unwrap();
+    let die = bitstream.die.first_mut().unwrap();
+    let frame = die.frame_mut(die.frame_info.len() - 1);
+    let last = frame.len() - 1;
+    let bit = frame[last];
+    frame.set(last, !bit);
+    let res = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain);
+    assert!(
+        matches!(res, Err(BitstreamError::Malformed { .. })),
+        "{name}: {res:?}",
+        name = dev.name
+    );
+}
+
+#[test]
+fn emit_bad_check_bits_xc4000() {
+    check_emit_bad_check_bits("xc4000", (1, false));
+}
+
+#[test]
+fn emit_bad_check_bits_xc5200() {
+    check_emit_bad_check_bits("xc5200", (0, true));
+}
+
+#[test]
+fn emit_wrong_geometry() {
+    let path = format!("{}/../../databases/xc2000.zstd", env!("CARGO_MANIFEST_DIR"));
+    let xc2000 = Database::from_file(path).unwrap();
+    let path = format!("{}/../../databases/xc5200.zstd", env!("CARGO_MANIFEST_DIR"));
+    let xc5200 = Database::from_file(path).unwrap();
+    let bitstream = Bitstream::new(
+        &xc2000.chips[xc2000.devices[0].chip]
+            .expand_grid(&xc2000.int)
+            .bs_geom,
+    );
+    let edev = xc5200.chips[xc5200.devices[0].chip].expand_grid(&xc5200.int);
+    let res = emit(&edev.bs_geom, &bitstream, BitstreamMode::Plain);
+    assert!(
+        matches!(res, Err(BitstreamError::Malformed { .. })),
+        "{res:?}"
+    );
+}
@@ -0,0 +1,55 @@
+use prjcombine_test_util::check_device_bsdl;
+use prjcombine_xc2000::{
+    bond::{BondPad, CfgPad},
+    chip::SharedCfgPad,
+    db::Database,
+};
+
+// XC2000 and XC3000 have no boundary scan
+const DBS: [&str; 8] = [
+    "xc4000",
+    "xc4000a",
+    "xc4000h",
+    "xc4000e",
+    "xc4000ex",
+    "xc4000xla",
+    "xc4000xv",
+    "xc5200",
+];
+
+#[test]
+fn bsdl() {
+    for name in DBS {
+        let path = format!("{}/../../databases/{name}.zstd", env!("CARGO_MANIFEST_DIR"));
+        let db = Database::from_file(path).unwrap();
+        for dev in &db.devices {
+            let chip = &db.chips[dev.chip];
+            let bscan = chip.get_bscan();
+            // the TAP pins shared with IOBs are left out of the description
+            let tap: Vec<_> = [
+                SharedCfgPad::Tck,
+                SharedCfgPad::Tms,
+                SharedCfgPad::Tdi,
+                SharedCfgPad::Tdo,
+            ]
+            .into_iter()
+            .filter_map(|spad| chip.cfg_io.get(&spad).copied())
+            .collect();
+            check_device_bsdl(
+                &dev.name,
+                bscan.bits,
+                dev.bonds.iter().map(|(_, package, &bond)| {
+                    let bond = &db.bonds[bond];
+                    (package.as_str(), bond, bond.pins.values().copied())
+                }),
+                |bond, package, idcode| chip.make_bsdl(bond, &dev.name, package, idcode),
+                |pad| match pad {
+                    BondPad::Io(crd) if !tap.contains(&crd) => bscan.io.get(&crd).copied(),
+                    BondPad::Cfg(CfgPad::Tdo) => None,
+                    BondPad::Cfg(cpad) => bscan.cfg.get(&cpad).copied(),
+                    _ => None,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use prjcombine_test_util::check_pinout;
+use prjcombine_xc2000::db::Database;
+
+// The XC4000 IOs have no banks, and the dual-purpose configuration pins are
+// named after their function.
+#[test]
+fn pinout() {
+    let path = format!(
+        "{}/../../databases/xc4000e.zstd",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let db = Database::from_file(path).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == "xc4005e").unwrap();
+    let (_, &bond) = dev.bonds.get("pc84").unwrap();
+    let pinout = db.chips[dev.chip].make_pinout(&db.bonds[bond], "xc4005e", "pc84");
+    check_pinout(
+        &pinout,
+        &[
+            ("P3", "IO_A8", None, None),
+            ("P13", "IO_A16", None, None),
+            ("P35", "IO", None, None),
+        ],
+    );
+}
@@ -0,0 +1,36 @@
+use prjcombine_xc2000::db::Database;
+
+const TARGETS: [&str; 12] = [
+    "xc2000",
+    "xc3000",
+    "xc3000a",
+    "xc4000",
+    "xc4000a",
+    "xc4000h",
+    "xc4000e",
+    "xc4000ex",
+    "xc4000xla",
+    "xc4000xv",
+    "spartanxl",
+    "xc5200",
+];
+
+fn db_path(target: &str, ext: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.{ext}",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+#[test]
+fn text_roundtrip() {
+    for target in TARGETS {
+        let db = Database::from_file(db_path(target, "zstd")).unwrap();
+        let parsed = Database::from_text_file(db_path(target, "txt")).unwrap();
+        // Not assert_eq, the Debug output of a whole database is unreadable.
+        assert!(
+            parsed == db,
+            "{target}: text dump does not match the database"
+        );
+    }
+}
@@ -2,184 +2,10 @@ use std::{error::Error, fs::read_to_string, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
 
-use prjcombine_entity::EntityId;
-use prjcombine_jed::JedFile;
-use prjcombine_types::{
-    bitvec::BitVec,
-    bsdata::{Tile, TileBit, TileItemKind},
+use prjcombine_xc9500::{
+    Database,
+    bitstream::{Bitstream, set_tile_item},
 };
-use prjcombine_xc9500::{Chip, ChipKind, Database};
-
-struct Bitstream {
-    fbs: Vec<Vec<[u8; 15]>>,
-    uim: Vec<Vec<Vec<[u8; 5]>>>,
-}
-
-impl Bitstream {
-    fn new(chip: &Chip) -> Self {
-        let rows = if chip.kind == ChipKind::Xc9500 {
-            72
-        } else {
-            108
-        };
-        let fbs = (0..chip.blocks)
-            .map(|_| {
-                vec![
-                    if chip.kind == ChipKind::Xc9500 {
-                        [
-                            0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0, 0, 0, 0, 0, 0,
-                        ]
-                    } else {
-                        [0; 15]
-                    };
-                    rows
-                ]
-            })
-            .collect();
-        let uim = if chip.kind == ChipKind::Xc9500 {
-            (0..chip.blocks)
-                .map(|_| (0..chip.blocks).map(|_| vec![[0; 5]; 18]).collect())
-                .collect()
-        } else {
-            vec![]
-        };
-        Bitstream { fbs, uim }
-    }
-
-    fn to_jed(&self, device: &str) -> JedFile {
-        let mut res = BitVec::new();
-        if !self.uim.is_empty() {
-            for fb in 0..self.fbs.len() {
-                for row in 0..72 {
-                    for col in 0..15 {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            res.push((self.fbs[fb][row][col] >> j & 1) != 0);
-                        }
-                    }
-                }
-                for sfb in 0..self.fbs.len() {
-                    for row in 0..18 {
-                        for col in 0..5 {
-                            let sz = if col == 0 { 8 } else { 7 };
-                            for j in 0..sz {
-                                res.push((self.uim[fb][sfb][row][col] >> j & 1) != 0);
-                            }
-                        }
-                    }
-                }
-            }
-        } else {
-            for row in 0..108 {
-                for col in 0..15 {
-                    for fb in 0..self.fbs.len() {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            res.push((self.fbs[fb][row][col] >> j & 1) != 0);
-                        }
-                    }
-                }
-            }
-        }
-        JedFile::new()
-            .with_fuses(res)
-            .with_note(format!(" DEVICE {device}"))
-    }
-
-    fn put_bit(&mut self, fb: usize, row: usize, col: usize, bit: usize, val: bool) {
-        if val {
-            self.fbs[fb][row][col] |= 1 << bit;
-        } else {
-            self.fbs[fb][row][col] &= !(1 << bit);
-        }
-    }
-
-    fn put_global(&mut self, crd: TileBit, val: bool) {
-        self.put_bit(
-            crd.rect.to_idx(),
-            crd.frame.to_idx(),
-            crd.bit.to_idx() % 9,
-            6 + crd.bit.to_idx() / 9,
-            val,
-        );
-    }
-
-    fn put_fb(&mut self, fb: usize, crd: TileBit, val: bool) {
-        self.put_bit(
-            fb,
-            crd.frame.to_idx(),
-            crd.bit.to_idx() % 9,
-            6 + crd.bit.to_idx() / 9,
-            val,
-        );
-    }
-
-    fn put_mc(&mut self, fb: usize, mc: usize, crd: TileBit, val: bool) {
-        self.put_bit(fb, crd.frame.to_idx(), mc % 9, 6 + mc / 9, val);
-    }
-
-    fn put_pt(&mut self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool, val: bool) {
-        self.put_bit(
-            fb,
-            imux * 2 + usize::from(pol),
-            pt + (mc % 3) * 5,
-            mc / 3,
-            val,
-        );
-    }
-
-    fn put_uim(&mut self, fb: usize, sfb: usize, imux: usize, mc: usize, val: bool) {
-        if val {
-            self.uim[fb][sfb][mc][imux % 5] |= 1 << (imux / 5);
-        } else {
-            self.uim[fb][sfb][mc][imux % 5] &= !(1 << (imux / 5));
-        }
-    }
-}
-
-fn set_tile_item(tile: &Tile, chip: &Chip, item: &str, mut put_bit: impl FnMut(TileBit, bool)) {
-    let is_large = chip.io_special.contains_key("GOE2");
-    if let Some((name, val)) = item.split_once('=') {
-        let item = tile.items.get(name).unwrap_or_else(|| {
-            &tile.items[&format!("{}.{}", name, if is_large { "LARGE" } else { "SMALL" })]
-        });
-        match &item.kind {
-            TileItemKind::Enum { values } => {
-                let val = &values[val];
-                for (k, v) in item.bits.iter().zip(val.iter()) {
-                    put_bit(*k, v);
-                }
-            }
-            TileItemKind::BitVec { invert } => {
-                assert_eq!(val.len(), item.bits.len());
-                for (i, (k, v)) in item.bits.iter().zip(val.chars().rev()).enumerate() {
-                    put_bit(
-                        *k,
-                        match v {
-                            '0' => false,
-                            '1' => true,
-                            _ => unreachable!(),
-                        } ^ invert[i],
-                    )
-                }
-            }
-        }
-    } else {
-        let (name, val) = if let Some(name) = item.strip_prefix('!') {
-            (name, false)
-        } else {
-            (item, true)
-        };
-        let item = &tile.items[name];
-        match item.kind {
-            TileItemKind::Enum { .. } => unreachable!(),
-            TileItemKind::BitVec { ref invert } => {
-                assert_eq!(item.bits.len(), 1);
-                put_bit(item.bits[0], val ^ invert[0]);
-            }
-        }
-    }
-}
 
 pub fn main() -> Result<(), Box<dyn Error>> {
     let m = Command::new("xc9500_as")
@@ -2,118 +2,12 @@ use std::{error::Error, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
 
-use prjcombine_entity::EntityId;
 use prjcombine_jed::{JedFile, JedParserOptions};
 use prjcombine_types::{
     bitvec::BitVec,
     bsdata::{Tile, TileBit, TileItemKind},
 };
-use prjcombine_xc9500::{Chip, ChipKind, Database};
-
-struct Bitstream {
-    fbs: Vec<Vec<[u8; 15]>>,
-    uim: Vec<Vec<Vec<[u8; 5]>>>,
-}
-
-impl Bitstream {
-    fn from_jed(jed: &JedFile, chip: &Chip) -> Self {
-        let fuses = jed.fuses.as_ref().unwrap();
-        let mut fbs = vec![];
-        let mut uim = vec![];
-        let mut pos = 0;
-        if chip.kind == ChipKind::Xc9500 {
-            for _ in 0..chip.blocks {
-                let mut rows = vec![];
-                for _ in 0..72 {
-                    let mut row = [0; 15];
-                    for col in 0..15 {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            if fuses[pos + j] {
-                                row[col] |= 1 << j;
-                            }
-                        }
-                        pos += sz;
-                    }
-                    rows.push(row);
-                }
-                fbs.push(rows);
-                let mut uim_fb = vec![];
-                for _ in 0..chip.blocks {
-                    let mut rows = vec![];
-                    for _ in 0..18 {
-                        let mut row = [0; 5];
-                        for col in 0..5 {
-                            let sz = if col == 0 { 8 } else { 7 };
-                            for j in 0..sz {
-                                if fuses[pos + j] {
-                                    row[col] |= 1 << j;
-                                }
-                            }
-                            pos += sz;
-                        }
-                        rows.push(row);
-                    }
-                    uim_fb.push(rows);
-                }
-                uim.push(uim_fb);
-            }
-        } else {
-            for _ in 0..chip.blocks {
-                fbs.push(vec![[0; 15]; 108]);
-            }
-            for row in 0..108 {
-                for col in 0..15 {
-                    for fb in 0..chip.blocks {
-                        let sz = if col < 9 { 8 } else { 6 };
-                        for j in 0..sz {
-                            if fuses[pos + j] {
-                                fbs[fb][row][col] |= 1 << j;
-                            }
-                        }
-                        pos += sz;
-                    }
-                }
-            }
-        }
-        assert_eq!(pos, fuses.len());
-        Bitstream { fbs, uim }
-    }
-
-    fn get_bit(&self, fb: usize, row: usize, col: usize, bit: usize) -> bool {
-        (self.fbs[fb][row][col] >> bit & 1) != 0
-    }
-
-    fn get_global(&self, crd: TileBit) -> bool {
-        self.get_bit(
-            crd.rect.to_idx(),
-            crd.frame.to_idx(),
-            crd.bit.to_idx() % 9,
-            6 + crd.bit.to_idx() / 9,
-        )
-    }
-
-    fn get_fb(&self, fb: usize, crd: TileBit) -> bool {
-        self.get_bit(
-            fb,
-            crd.frame.to_idx(),
-            crd.bit.to_idx() % 9,
-            6 + crd.bit.to_idx() / 9,
-        )
-    }
-
-    fn get_mc(&self, fb: usize, mc: usize, crd: TileBit) -> bool {
-        self.get_bit(fb, crd.frame.to_idx(), mc % 9, 6 + mc / 9)
-    }
-
-    fn get_pt(&self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool) -> bool {
-        self.get_bit(fb, imux * 2 + usize::from(pol), pt + (mc % 3) * 5, mc / 3)
-    }
-
-    fn get_uim(&self, fb: usize, sfb: usize, imux: usize, mc: usize) -> bool {
-        (self.uim[fb][sfb][mc][imux % 5] >> (imux / 5) & 1) != 0
-    }
-}
+use prjcombine_xc9500::{Chip, ChipKind, Database, bitstream::Bitstream};
 
 fn print_tile(tile: &Tile, chip: &Chip, get_bit: impl Fn(TileBit) -> bool) {
     let is_large = chip.io_special.contains_key("GOE2");
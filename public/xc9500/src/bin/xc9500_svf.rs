@@ -0,0 +1,73 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, ArgAction, Command, value_parser};
+
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_svf::ProgramOptions;
+use prjcombine_xc9500::{Database, svf::program};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xc9500_svf")
+        .arg(
+            Arg::new("dbdir")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("out")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(Arg::new("xsvf").long("xsvf").action(ArgAction::SetTrue))
+        .arg(
+            Arg::new("no-erase")
+                .long("no-erase")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-verify")
+                .long("no-verify")
+                .action(ArgAction::SetTrue),
+        )
+        .get_matches();
+    let arg_dbdir = m.get_one::<PathBuf>("dbdir").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let arg_out = m.get_one::<PathBuf>("out").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let device = device.unwrap();
+    let dev = if let Some(pos) = device.find('-') {
+        &device[..pos]
+    } else {
+        &device[..]
+    };
+    let dbfn = if dev.ends_with("xv") {
+        arg_dbdir.join("xc9500xv.zstd")
+    } else if dev.ends_with("xl") {
+        arg_dbdir.join("xc9500xl.zstd")
+    } else {
+        arg_dbdir.join("xc9500.zstd")
+    };
+    let db = Database::from_file(dbfn)?;
+    let options = ProgramOptions {
+        erase: !m.get_flag("no-erase"),
+        verify: !m.get_flag("no-verify"),
+    };
+    let prog = program(&db, dev, &jed, &options)?;
+    if m.get_flag("xsvf") {
+        prog.emit_xsvf_to_file(arg_out)?;
+    } else {
+        prog.emit_svf_to_file(arg_out)?;
+    }
+    Ok(())
+}
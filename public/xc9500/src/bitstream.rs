@@ -0,0 +1,297 @@
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{Tile, TileBit, TileItem, TileItemKind},
+};
+
+use crate::{Chip, ChipKind};
+
+pub struct Bitstream {
+    pub fbs: Vec<Vec<[u8; 15]>>,
+    pub uim: Vec<Vec<Vec<[u8; 5]>>>,
+}
+
+impl Bitstream {
+    pub fn new(chip: &Chip) -> Self {
+        let rows = if chip.kind == ChipKind::Xc9500 {
+            72
+        } else {
+            108
+        };
+        let fbs = (0..chip.blocks)
+            .map(|_| {
+                vec![
+                    if chip.kind == ChipKind::Xc9500 {
+                        [
+                            0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0xc0, 0, 0, 0, 0, 0, 0,
+                        ]
+                    } else {
+                        [0; 15]
+                    };
+                    rows
+                ]
+            })
+            .collect();
+        let uim = if chip.kind == ChipKind::Xc9500 {
+            (0..chip.blocks)
+                .map(|_| (0..chip.blocks).map(|_| vec![[0; 5]; 18]).collect())
+                .collect()
+        } else {
+            vec![]
+        };
+        Bitstream { fbs, uim }
+    }
+
+    pub fn from_jed(jed: &JedFile, chip: &Chip) -> Self {
+        let fuses = jed.fuses.as_ref().unwrap();
+        let mut res = Bitstream::new(chip);
+        let mut pos = 0;
+        if chip.kind == ChipKind::Xc9500 {
+            for fb in 0..chip.blocks {
+                for row in 0..72 {
+                    for col in 0..15 {
+                        let sz = if col < 9 { 8 } else { 6 };
+                        for j in 0..sz {
+                            res.put_bit(fb, row, col, j, fuses[pos + j]);
+                        }
+                        pos += sz;
+                    }
+                }
+                for sfb in 0..chip.blocks {
+                    for row in 0..18 {
+                        for col in 0..5 {
+                            let sz = if col == 0 { 8 } else { 7 };
+                            for j in 0..sz {
+                                res.put_uim(fb, sfb, col + j * 5, row, fuses[pos + j]);
+                            }
+                            pos += sz;
+                        }
+                    }
+                }
+            }
+        } else {
+            for row in 0..108 {
+                for col in 0..15 {
+                    for fb in 0..chip.blocks {
+                        let sz = if col < 9 { 8 } else { 6 };
+                        for j in 0..sz {
+                            res.put_bit(fb, row, col, j, fuses[pos + j]);
+                        }
+                        pos += sz;
+                    }
+                }
+            }
+        }
+        assert_eq!(pos, fuses.len());
+        res
+    }
+
+    pub fn to_jed(&self, device: &str) -> JedFile {
+        let mut res = BitVec::new();
+        if !self.uim.is_empty() {
+            for fb in 0..self.fbs.len() {
+                for row in 0..72 {
+                    for col in 0..15 {
+                        let sz = if col < 9 { 8 } else { 6 };
+                        for j in 0..sz {
+                            res.push((self.fbs[fb][row][col] >> j & 1) != 0);
+                        }
+                    }
+                }
+                for sfb in 0..self.fbs.len() {
+                    for row in 0..18 {
+                        for col in 0..5 {
+                            let sz = if col == 0 { 8 } else { 7 };
+                            for j in 0..sz {
+                                res.push((self.uim[fb][sfb][row][col] >> j & 1) != 0);
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            for row in 0..108 {
+                for col in 0..15 {
+                    for fb in 0..self.fbs.len() {
+                        let sz = if col < 9 { 8 } else { 6 };
+                        for j in 0..sz {
+                            res.push((self.fbs[fb][row][col] >> j & 1) != 0);
+                        }
+                    }
+                }
+            }
+        }
+        JedFile::new()
+            .with_fuses(res)
+            .with_note(format!(" DEVICE {device}"))
+    }
+
+    pub fn get_bit(&self, fb: usize, row: usize, col: usize, bit: usize) -> bool {
+        (self.fbs[fb][row][col] >> bit & 1) != 0
+    }
+
+    pub fn get_global(&self, crd: TileBit) -> bool {
+        self.get_bit(
+            crd.rect.to_idx(),
+            crd.frame.to_idx(),
+            crd.bit.to_idx() % 9,
+            6 + crd.bit.to_idx() / 9,
+        )
+    }
+
+    pub fn get_fb(&self, fb: usize, crd: TileBit) -> bool {
+        self.get_bit(
+            fb,
+            crd.frame.to_idx(),
+            crd.bit.to_idx() % 9,
+            6 + crd.bit.to_idx() / 9,
+        )
+    }
+
+    pub fn get_mc(&self, fb: usize, mc: usize, crd: TileBit) -> bool {
+        self.get_bit(fb, crd.frame.to_idx(), mc % 9, 6 + mc / 9)
+    }
+
+    pub fn get_pt(&self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool) -> bool {
+        self.get_bit(fb, imux * 2 + usize::from(pol), pt + (mc % 3) * 5, mc / 3)
+    }
+
+    pub fn get_uim(&self, fb: usize, sfb: usize, imux: usize, mc: usize) -> bool {
+        (self.uim[fb][sfb][mc][imux % 5] >> (imux / 5) & 1) != 0
+    }
+
+    pub fn put_bit(&mut self, fb: usize, row: usize, col: usize, bit: usize, val: bool) {
+        if val {
+            self.fbs[fb][row][col] |= 1 << bit;
+        } else {
+            self.fbs[fb][row][col] &= !(1 << bit);
+        }
+    }
+
+    pub fn put_global(&mut self, crd: TileBit, val: bool) {
+        self.put_bit(
+            crd.rect.to_idx(),
+            crd.frame.to_idx(),
+            crd.bit.to_idx() % 9,
+            6 + crd.bit.to_idx() / 9,
+            val,
+        );
+    }
+
+    pub fn put_fb(&mut self, fb: usize, crd: TileBit, val: bool) {
+        self.put_bit(
+            fb,
+            crd.frame.to_idx(),
+            crd.bit.to_idx() % 9,
+            6 + crd.bit.to_idx() / 9,
+            val,
+        );
+    }
+
+    pub fn put_mc(&mut self, fb: usize, mc: usize, crd: TileBit, val: bool) {
+        self.put_bit(fb, crd.frame.to_idx(), mc % 9, 6 + mc / 9, val);
+    }
+
+    pub fn put_pt(&mut self, fb: usize, mc: usize, pt: usize, imux: usize, pol: bool, val: bool) {
+        self.put_bit(
+            fb,
+            imux * 2 + usize::from(pol),
+            pt + (mc % 3) * 5,
+            mc / 3,
+            val,
+        );
+    }
+
+    pub fn put_uim(&mut self, fb: usize, sfb: usize, imux: usize, mc: usize, val: bool) {
+        if val {
+            self.uim[fb][sfb][mc][imux % 5] |= 1 << (imux / 5);
+        } else {
+            self.uim[fb][sfb][mc][imux % 5] &= !(1 << (imux / 5));
+        }
+    }
+}
+
+pub fn set_tile_item(tile: &Tile, chip: &Chip, item: &str, mut put_bit: impl FnMut(TileBit, bool)) {
+    let is_large = chip.io_special.contains_key("GOE2");
+    if let Some((name, val)) = item.split_once('=') {
+        let item = tile.items.get(name).unwrap_or_else(|| {
+            &tile.items[&format!("{}.{}", name, if is_large { "LARGE" } else { "SMALL" })]
+        });
+        match &item.kind {
+            TileItemKind::Enum { values } => {
+                let val = &values[val];
+                for (k, v) in item.bits.iter().zip(val.iter()) {
+                    put_bit(*k, v);
+                }
+            }
+            TileItemKind::BitVec { invert } => {
+                assert_eq!(val.len(), item.bits.len());
+                for (i, (k, v)) in item.bits.iter().zip(val.chars().rev()).enumerate() {
+                    put_bit(
+                        *k,
+                        match v {
+                            '0' => false,
+                            '1' => true,
+                            _ => unreachable!(),
+                        } ^ invert[i],
+                    )
+                }
+            }
+        }
+    } else {
+        let (name, val) = if let Some(name) = item.strip_prefix('!') {
+            (name, false)
+        } else {
+            (item, true)
+        };
+        let item = &tile.items[name];
+        match item.kind {
+            TileItemKind::Enum { .. } => unreachable!(),
+            TileItemKind::BitVec { ref invert } => {
+                assert_eq!(item.bits.len(), 1);
+                put_bit(item.bits[0], val ^ invert[0]);
+            }
+        }
+    }
+}
+
+fn get_item<'a>(tile: &'a Tile, chip: &Chip, name: &str) -> &'a TileItem {
+    let is_large = chip.io_special.contains_key("GOE2");
+    tile.items.get(name).unwrap_or_else(|| {
+        &tile.items[&format!("{}.{}", name, if is_large { "LARGE" } else { "SMALL" })]
+    })
+}
+
+/// Returns the current value of an enum item, or `None` if the bits don't match any value.
+pub fn get_tile_enum<'a>(
+    tile: &'a Tile,
+    chip: &Chip,
+    name: &str,
+    get_bit: impl Fn(TileBit) -> bool,
+) -> Option<&'a str> {
+    let item = get_item(tile, chip, name);
+    let TileItemKind::Enum { ref values } = item.kind else {
+        unreachable!()
+    };
+    let bits: BitVec = item.bits.iter().map(|&crd| get_bit(crd)).collect();
+    values
+        .iter()
+        .find(|(_, val)| **val == bits)
+        .map(|(vn, _)| vn.as_str())
+}
+
+/// Returns the current value of a single-bit item.
+pub fn get_tile_bool(
+    tile: &Tile,
+    chip: &Chip,
+    name: &str,
+    get_bit: impl Fn(TileBit) -> bool,
+) -> bool {
+    let item = get_item(tile, chip, name);
+    let TileItemKind::BitVec { ref invert } = item.kind else {
+        unreachable!()
+    };
+    assert_eq!(item.bits.len(), 1);
+    get_bit(item.bits[0]) ^ invert[0]
+}
@@ -0,0 +1,775 @@
+//! Compiles a [`Design`] into a JED file.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{Tile, TileItemKind},
+    cpld::{
+        BlockId, MacrocellCoord, MacrocellId,
+        design::{
+            CompileError, ControlKind, Design, Literal, Placement, ProductTerm, RegisterKind,
+            Signal, Slew, assign_slots,
+        },
+    },
+};
+
+use crate::{
+    BondPad, Chip, ChipKind, Database,
+    bitstream::{Bitstream, set_tile_item},
+};
+
+const MCS_PER_FB: usize = 18;
+const PTS_PER_MC: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PtAlloc {
+    Special,
+    Sum,
+    Export,
+}
+
+#[derive(Debug, Default)]
+struct McPlan {
+    specials: [Option<ProductTerm>; PTS_PER_MC],
+    sum: Vec<ProductTerm>,
+    items: Vec<String>,
+}
+
+struct Compiler<'a> {
+    db: &'a Database,
+    chip: &'a Chip,
+    design: &'a Design,
+    is_xl: bool,
+    num_imux: usize,
+    io_special: BTreeMap<String, MacrocellCoord>,
+    pins: &'a BTreeMap<String, BondPad>,
+    pad_pins: BTreeMap<MacrocellCoord, &'a str>,
+    used_pads: BTreeSet<MacrocellCoord>,
+    input_pads: BTreeMap<&'a str, MacrocellCoord>,
+    mc_place: BTreeMap<&'a str, MacrocellCoord>,
+    mc_names: BTreeMap<MacrocellCoord, &'a str>,
+    fb_signals: Vec<BTreeSet<&'a Signal>>,
+    // per FCLK/FOE network: the GCLK/GOE pad index and the inversion
+    fclk: [Option<(usize, bool)>; 3],
+    foe: Vec<Option<(usize, bool)>>,
+    fsr_inv: Option<bool>,
+    bs: Bitstream,
+}
+
+impl<'a> Compiler<'a> {
+    fn special_index(&self, pad: MacrocellCoord, prefix: &str) -> Option<usize> {
+        self.io_special.iter().find_map(|(name, &crd)| {
+            if crd != pad {
+                return None;
+            }
+            let suffix = name.strip_prefix(prefix)?;
+            if suffix.is_empty() {
+                Some(0)
+            } else {
+                suffix.parse().ok()
+            }
+        })
+    }
+
+    fn is_special(&self, pad: MacrocellCoord) -> bool {
+        self.io_special.values().any(|&crd| crd == pad)
+    }
+
+    fn pad_of(&self, signal: &Signal) -> Option<MacrocellCoord> {
+        match signal {
+            Signal::Input(name) => self.input_pads.get(name.as_str()).copied(),
+            Signal::Pad(name) => self.mc_place.get(name.as_str()).copied(),
+            Signal::Macrocell(_) => None,
+        }
+    }
+
+    /// Returns the global network pad index if the term is a single literal driven
+    /// by a special pad of the given kind.
+    fn global_source(&self, term: &ProductTerm, prefix: &str) -> Option<(usize, bool)> {
+        let lit = term.single()?;
+        let pad = self.pad_of(&lit.signal)?;
+        Some((self.special_index(pad, prefix)?, lit.inv))
+    }
+
+    fn enum_values(&self, tile: &'a Tile, name: &str) -> Option<&'a BTreeMap<String, BitVec>> {
+        let is_large = self.chip.io_special.contains_key("GOE2");
+        let item = tile.items.get(name).or_else(|| {
+            tile.items.get(&format!(
+                "{name}.{}",
+                if is_large { "LARGE" } else { "SMALL" }
+            ))
+        })?;
+        match item.kind {
+            TileItemKind::Enum { ref values } => Some(values),
+            TileItemKind::BitVec { .. } => None,
+        }
+    }
+
+    fn set_global(&mut self, item: &str) {
+        let bs = &mut self.bs;
+        set_tile_item(&self.db.global_bits, self.chip, item, |crd, val| {
+            bs.put_global(crd, val)
+        });
+    }
+
+    fn set_fb(&mut self, fb: usize, item: &str) {
+        let tile = if item.starts_with("IM[") {
+            &self.chip.imux_bits
+        } else {
+            &self.db.block_bits
+        };
+        let bs = &mut self.bs;
+        set_tile_item(tile, self.chip, item, |crd, val| bs.put_fb(fb, crd, val));
+    }
+
+    fn set_mc(&mut self, fb: usize, mc: usize, item: &str) {
+        let bs = &mut self.bs;
+        set_tile_item(&self.db.mc_bits, self.chip, item, |crd, val| {
+            bs.put_mc(fb, mc, crd, val)
+        });
+    }
+
+    fn resolve_pin(&mut self, pin: &str) -> Result<MacrocellCoord, CompileError> {
+        let Some(&pad) = self.pins.get(pin) else {
+            return Err(CompileError::UnknownPin(pin.to_string()));
+        };
+        let BondPad::Iob(crd) = pad else {
+            return Err(CompileError::PinNotUsable(pin.to_string()));
+        };
+        if !self.used_pads.insert(crd) {
+            return Err(CompileError::PinConflict(pin.to_string()));
+        }
+        Ok(crd)
+    }
+
+    /// Returns the signals a macrocell needs routed into its FB, ignoring control terms
+    /// that are likely to use a global network.
+    fn mc_signals(&self, name: &str) -> BTreeSet<&'a Signal> {
+        let mc = &self.design.macrocells[name];
+        let mut terms: Vec<&ProductTerm> = mc.sum.iter().collect();
+        if let Some(ref reg) = mc.reg {
+            if self.global_source(&reg.clk, "GCLK").is_none() {
+                terms.push(&reg.clk);
+            }
+            terms.extend(reg.ce.iter());
+            for term in [&reg.rst, &reg.set].into_iter().flatten() {
+                if self.global_source(term, "GSR").is_none() {
+                    terms.push(term);
+                }
+            }
+        }
+        if let Some(oe) = mc.output.as_ref().and_then(|out| out.oe.as_ref())
+            && self.global_source(oe, "GOE").is_none()
+        {
+            terms.push(oe);
+        }
+        terms.into_iter().flat_map(|term| term.signals()).collect()
+    }
+
+    fn place_mc(&mut self, name: &'a str, crd: MacrocellCoord) {
+        self.mc_place.insert(name, crd);
+        self.mc_names.insert(crd, name);
+        let signals = self.mc_signals(name);
+        self.fb_signals[crd.block.to_idx()].extend(signals);
+    }
+
+    fn place(&mut self) -> Result<(), CompileError> {
+        let design = self.design;
+        for (name, inp) in &design.inputs {
+            if let Some(ref pin) = inp.pin {
+                let pad = self.resolve_pin(pin)?;
+                self.input_pads.insert(name, pad);
+            }
+        }
+        for (name, mc) in &design.macrocells {
+            if let Some(pin) = mc.output.as_ref().and_then(|out| out.pin.as_ref()) {
+                let pad = self.resolve_pin(pin)?;
+                self.mc_place.insert(name, pad);
+                self.mc_names.insert(pad, name);
+            }
+        }
+
+        let clocks = design.control_signals(ControlKind::Clock);
+        let srs = design.control_signals(ControlKind::SetReset);
+        let oes = design.control_signals(ControlKind::OutputEnable);
+        for (name, inp) in &design.inputs {
+            if inp.pin.is_some() {
+                continue;
+            }
+            let signal = Signal::Input(name.clone());
+            let prefix = if clocks.contains(&signal) {
+                Some("GCLK")
+            } else if srs.contains(&signal) {
+                Some("GSR")
+            } else if oes.contains(&signal) {
+                Some("GOE")
+            } else {
+                None
+            };
+            let free: Vec<MacrocellCoord> = self
+                .pad_pins
+                .keys()
+                .copied()
+                .filter(|pad| !self.used_pads.contains(pad) && !self.mc_names.contains_key(pad))
+                .collect();
+            let pad = prefix
+                .and_then(|prefix| {
+                    free.iter()
+                        .copied()
+                        .find(|&pad| self.special_index(pad, prefix).is_some())
+                })
+                .or_else(|| free.iter().copied().find(|&pad| !self.is_special(pad)))
+                .or_else(|| free.first().copied())
+                .ok_or_else(|| CompileError::NoFreePin(name.clone()))?;
+            self.used_pads.insert(pad);
+            self.input_pads.insert(name, pad);
+        }
+
+        // now that all input pads are known, account for the pinned macrocells
+        for (&crd, &name) in &self.mc_names.clone() {
+            let signals = self.mc_signals(name);
+            self.fb_signals[crd.block.to_idx()].extend(signals);
+        }
+
+        for with_output in [true, false] {
+            for (name, mc) in &design.macrocells {
+                if self.mc_place.contains_key(name.as_str()) || mc.output.is_some() != with_output {
+                    continue;
+                }
+                let signals = self.mc_signals(name);
+                let mut candidates = vec![];
+                for fb in 0..self.chip.blocks {
+                    for m in 0..MCS_PER_FB {
+                        let crd =
+                            MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(m));
+                        if self.mc_names.contains_key(&crd) {
+                            continue;
+                        }
+                        let has_pad =
+                            self.pad_pins.contains_key(&crd) && !self.used_pads.contains(&crd);
+                        if with_output && !has_pad {
+                            continue;
+                        }
+                        // outputs avoid the global network pads, buried logic avoids
+                        // free pads altogether
+                        let cost = if with_output {
+                            usize::from(self.is_special(crd))
+                        } else {
+                            usize::from(has_pad)
+                        };
+                        candidates.push((cost, crd));
+                    }
+                }
+                candidates.sort();
+                let fits = |crd: MacrocellCoord| {
+                    self.fb_signals[crd.block.to_idx()].union(&signals).count() <= self.num_imux
+                };
+                let crd = candidates
+                    .iter()
+                    .map(|&(_, crd)| crd)
+                    .find(|&crd| fits(crd))
+                    .or_else(|| candidates.first().map(|&(_, crd)| crd))
+                    .ok_or_else(|| {
+                        if with_output {
+                            CompileError::NoFreePin(name.clone())
+                        } else {
+                            CompileError::NoFreeMacrocell(name.clone())
+                        }
+                    })?;
+                if with_output {
+                    self.used_pads.insert(crd);
+                }
+                self.place_mc(name, crd);
+            }
+        }
+        Ok(())
+    }
+
+    fn alloc_fclk(&mut self, term: &ProductTerm, clk_inv: bool) -> Option<(usize, bool)> {
+        let (gclk, inv) = self.global_source(term, "GCLK")?;
+        let inv = inv ^ clk_inv;
+        if self.is_xl {
+            if gclk >= self.fclk.len() {
+                return None;
+            }
+            self.fclk[gclk] = Some((gclk, false));
+            return Some((gclk, inv));
+        }
+        let suffix = format!("PAD{gclk}");
+        for fclk in 0..self.fclk.len() {
+            let reachable = self
+                .enum_values(&self.db.global_bits, &format!("FCLK{fclk}_MUX"))
+                .is_some_and(|values| values.keys().any(|v| v.ends_with(&suffix)));
+            if reachable && self.fclk[fclk].is_none_or(|cur| cur == (gclk, inv)) {
+                self.fclk[fclk] = Some((gclk, inv));
+                return Some((fclk, inv));
+            }
+        }
+        None
+    }
+
+    fn alloc_foe(&mut self, term: &ProductTerm) -> Option<(usize, bool)> {
+        let (goe, inv) = self.global_source(term, "GOE")?;
+        if self.is_xl {
+            if goe >= self.foe.len() {
+                return None;
+            }
+            self.foe[goe] = Some((goe, false));
+            return Some((goe, inv));
+        }
+        let suffix = format!("PAD{goe}");
+        for foe in 0..self.foe.len() {
+            let reachable = self
+                .enum_values(&self.db.global_bits, &format!("FOE{foe}_MUX"))
+                .is_some_and(|values| values.keys().any(|v| v.ends_with(&suffix)));
+            if reachable && self.foe[foe].is_none_or(|cur| cur == (goe, inv)) {
+                self.foe[foe] = Some((goe, inv));
+                return Some((foe, inv));
+            }
+        }
+        None
+    }
+
+    fn alloc_fsr(&mut self, term: &ProductTerm) -> bool {
+        let Some((_, inv)) = self.global_source(term, "GSR") else {
+            return false;
+        };
+        if self.fsr_inv.is_some_and(|cur| cur != inv) {
+            return false;
+        }
+        self.fsr_inv = Some(inv);
+        true
+    }
+
+    fn plan_mc(&mut self, name: &str) -> Result<McPlan, CompileError> {
+        let mc = &self.design.macrocells[name];
+        let mut plan = McPlan {
+            sum: mc.sum.clone(),
+            ..Default::default()
+        };
+        let items = &mut plan.items;
+        items.push(if mc.invert { "INV" } else { "!INV" }.into());
+        items.push("SUM_HP".into());
+        if !self.is_xl {
+            // all used macrocells feed the UIM; this is harmless for unused outputs
+            items.push("UIM_OE_MUX=VCC".into());
+            items.push("!UIM_OUT_INV".into());
+        }
+        match mc.reg {
+            None => items.push("OUT_MUX=COMB".into()),
+            Some(ref reg) => {
+                items.push("OUT_MUX=FF".into());
+                items.push(
+                    match reg.kind {
+                        RegisterKind::Dff => "REG_MODE=DFF",
+                        RegisterKind::Tff => "REG_MODE=TFF",
+                        RegisterKind::Latch => {
+                            return Err(CompileError::Unsupported {
+                                signal: name.to_string(),
+                                reason: "latches are not supported by XC9500",
+                            });
+                        }
+                    }
+                    .into(),
+                );
+                items.push(if reg.init { "REG_INIT" } else { "!REG_INIT" }.into());
+
+                if let Some((fclk, inv)) = self.alloc_fclk(&reg.clk, reg.clk_inv) {
+                    items.push(format!("CLK_MUX=FCLK{fclk}"));
+                    if self.is_xl {
+                        items.push(if inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                    }
+                } else {
+                    let mut clk = reg.clk.clone();
+                    if self.is_xl {
+                        items.push(if reg.clk_inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                    } else if reg.clk_inv {
+                        let Some(lit) = clk.single() else {
+                            return Err(CompileError::Unsupported {
+                                signal: name.to_string(),
+                                reason: "inverted product term clocks must be a single literal on XC9500",
+                            });
+                        };
+                        clk = ProductTerm::new([Literal {
+                            signal: lit.signal.clone(),
+                            inv: !lit.inv,
+                        }]);
+                    }
+                    items.push("CLK_MUX=PT".into());
+                    plan.specials[0] = Some(clk);
+                }
+
+                for (term, mux, pt) in [(&reg.rst, "RST_MUX", 2), (&reg.set, "SET_MUX", 3)] {
+                    match term {
+                        Some(term) if self.alloc_fsr(term) => items.push(format!("{mux}=FSR")),
+                        Some(term) => {
+                            items.push(format!("{mux}=PT"));
+                            plan.specials[pt] = Some(term.clone());
+                        }
+                        // an unallocated control PT is constant 0
+                        None => items.push(format!("{mux}=PT")),
+                    }
+                }
+
+                if let Some(ref ce) = reg.ce {
+                    if !self.is_xl {
+                        return Err(CompileError::Unsupported {
+                            signal: name.to_string(),
+                            reason: "clock enable is not supported by XC9500",
+                        });
+                    }
+                    let Some(pt) = [2, 3].into_iter().find(|&pt| plan.specials[pt].is_none())
+                    else {
+                        return Err(CompileError::ControlTermsExhausted(name.to_string()));
+                    };
+                    items.push(format!("CE_MUX=PT{pt}"));
+                    plan.specials[pt] = Some(ce.clone());
+                } else if self.is_xl {
+                    items.push("CE_MUX=NONE".into());
+                }
+            }
+        }
+
+        if let Some(ref out) = mc.output {
+            items.push(
+                match out.slew {
+                    Slew::Slow => "IOB_SLEW=SLOW",
+                    Slew::Fast => "IOB_SLEW=FAST",
+                }
+                .into(),
+            );
+            match out.oe {
+                None => {
+                    if self.is_xl {
+                        // the unallocated OE PT is constant 0, invert it
+                        items.push("OE_MUX=PT".into());
+                        items.push("OE_INV".into());
+                    } else {
+                        items.push("IOB_OE_MUX=VCC".into());
+                    }
+                }
+                Some(ref oe) => {
+                    if let Some((foe, inv)) = self.alloc_foe(oe) {
+                        items.push(format!("OE_MUX=FOE{foe}"));
+                        if self.is_xl {
+                            items.push(if inv { "OE_INV" } else { "!OE_INV" }.into());
+                        }
+                    } else {
+                        items.push("OE_MUX=PT".into());
+                        if self.is_xl {
+                            items.push("!OE_INV".into());
+                        }
+                        plan.specials[1] = Some(oe.clone());
+                    }
+                    if !self.is_xl {
+                        items.push("IOB_OE_MUX=OE_MUX".into());
+                    }
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    fn source_values(&self, fb: usize, signal: &Signal) -> Vec<String> {
+        match signal {
+            Signal::Macrocell(name) => {
+                let crd = self.mc_place[name.as_str()];
+                if self.is_xl {
+                    vec![format!("MC_{crd}")]
+                } else if crd.block.to_idx() == fb {
+                    vec![format!("FBK_{}", crd.macrocell), "UIM".into()]
+                } else {
+                    vec!["UIM".into()]
+                }
+            }
+            Signal::Input(_) | Signal::Pad(_) => {
+                vec![format!("IOB_{}", self.pad_of(signal).unwrap())]
+            }
+        }
+    }
+
+    fn emit_fb(
+        &mut self,
+        fb: usize,
+        plans: BTreeMap<usize, McPlan>,
+        names: &BTreeMap<usize, &str>,
+    ) -> Result<(), CompileError> {
+        let mut slots: [[Option<(ProductTerm, PtAlloc)>; PTS_PER_MC]; MCS_PER_FB] =
+            Default::default();
+        let mut leftovers: [Vec<ProductTerm>; MCS_PER_FB] = Default::default();
+        for (m, plan) in plans {
+            for (pt, term) in plan.specials.into_iter().enumerate() {
+                if let Some(term) = term {
+                    slots[m][pt] = Some((term, PtAlloc::Special));
+                }
+            }
+            let mut sum = plan.sum.into_iter();
+            for slot in &mut slots[m] {
+                if slot.is_none()
+                    && let Some(term) = sum.next()
+                {
+                    *slot = Some((term, PtAlloc::Sum));
+                }
+            }
+            leftovers[m] = sum.collect();
+            for item in &plan.items {
+                self.set_mc(fb, m, item);
+            }
+        }
+
+        // borrow the free product terms of a neighbouring macrocell through the export chain
+        let mut donor_to: [Option<usize>; MCS_PER_FB] = [None; MCS_PER_FB];
+        let mut export = false;
+        for r in 0..MCS_PER_FB {
+            for d in [(r + 1) % MCS_PER_FB, (r + MCS_PER_FB - 1) % MCS_PER_FB] {
+                if leftovers[r].is_empty() {
+                    break;
+                }
+                if donor_to[d].is_some() || donor_to[r] == Some(d) || !leftovers[d].is_empty() {
+                    continue;
+                }
+                let free: Vec<usize> = (0..PTS_PER_MC)
+                    .filter(|&pt| slots[d][pt].is_none())
+                    .collect();
+                if free.is_empty() {
+                    continue;
+                }
+                donor_to[d] = Some(r);
+                export = true;
+                for pt in free {
+                    if let Some(term) = leftovers[r].pop() {
+                        slots[d][pt] = Some((term, PtAlloc::Export));
+                    }
+                }
+                if d == (r + 1) % MCS_PER_FB {
+                    self.set_mc(fb, d, "EXPORT_CHAIN_DIR=DOWN");
+                    self.set_mc(fb, r, "IMPORT_DOWN_ALLOC=SUM");
+                } else {
+                    self.set_mc(fb, d, "EXPORT_CHAIN_DIR=UP");
+                    self.set_mc(fb, r, "IMPORT_UP_ALLOC=SUM");
+                }
+            }
+            if !leftovers[r].is_empty() {
+                return Err(CompileError::ProductTermsExhausted(names[&r].to_string()));
+            }
+        }
+        if export {
+            self.set_fb(fb, "EXPORT_ENABLE");
+        }
+
+        let signals: Vec<Signal> = slots
+            .iter()
+            .flatten()
+            .flatten()
+            .flat_map(|(term, _)| term.signals())
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut candidates = vec![];
+        let mut choices = HashMap::new();
+        for (i, signal) in signals.iter().enumerate() {
+            let sources = self.source_values(fb, signal);
+            let mut cands = vec![];
+            for im in 0..self.num_imux {
+                let Some(values) = self.enum_values(&self.chip.imux_bits, &format!("IM[{im}].MUX"))
+                else {
+                    continue;
+                };
+                if let Some(src) = sources.iter().find(|src| values.contains_key(*src)) {
+                    cands.push(im);
+                    choices.insert((i, im), src.clone());
+                }
+            }
+            candidates.push(cands);
+        }
+        let Some(assignment) = assign_slots(&candidates, self.num_imux) else {
+            return Err(CompileError::RoutingFailed(format!("FB {fb}")));
+        };
+        let mut signal_im = HashMap::new();
+        for (i, &im) in assignment.iter().enumerate() {
+            let src = &choices[&(i, im)];
+            self.set_fb(fb, &format!("IM[{im}].MUX={src}"));
+            let signal = &signals[i];
+            if src == "UIM" {
+                let crd = self.mc_place[self.source_name(signal)];
+                self.bs
+                    .put_uim(fb, crd.block.to_idx(), im, crd.macrocell.to_idx(), true);
+            } else if src.starts_with("IOB_")
+                && let Some(ref tile) = self.chip.uim_ibuf_bits
+            {
+                let pad = self.pad_of(signal).unwrap();
+                for i in 0..2 {
+                    let item = format!(
+                        "FB[{}].MC[{}].IBUF_UIM_ENABLE.{i}",
+                        pad.block, pad.macrocell
+                    );
+                    if tile.items.contains_key(&item) {
+                        let bs = &mut self.bs;
+                        set_tile_item(tile, self.chip, &item, |crd, val| bs.put_global(crd, val));
+                    }
+                }
+            }
+            signal_im.insert(signal, im);
+        }
+
+        for (m, mslots) in slots.iter().enumerate() {
+            for (pt, slot) in mslots.iter().enumerate() {
+                let Some((term, alloc)) = slot else {
+                    continue;
+                };
+                let alloc = match alloc {
+                    PtAlloc::Special => "SPECIAL",
+                    PtAlloc::Sum => "SUM",
+                    PtAlloc::Export => "EXPORT",
+                };
+                self.set_mc(fb, m, &format!("PT[{pt}].ALLOC={alloc}"));
+                self.set_mc(fb, m, &format!("PT[{pt}].HP"));
+                for lit in &term.literals {
+                    self.bs
+                        .put_pt(fb, m, pt, signal_im[&lit.signal], !lit.inv, true);
+                }
+            }
+        }
+        self.set_fb(fb, "ENABLE");
+        Ok(())
+    }
+
+    fn source_name(&self, signal: &'a Signal) -> &'a str {
+        match signal {
+            Signal::Input(name) | Signal::Macrocell(name) | Signal::Pad(name) => name,
+        }
+    }
+
+    fn emit(&mut self) -> Result<(), CompileError> {
+        let mut plans: Vec<BTreeMap<usize, McPlan>> =
+            (0..self.chip.blocks).map(|_| BTreeMap::new()).collect();
+        let mut names: Vec<BTreeMap<usize, &str>> = vec![BTreeMap::new(); self.chip.blocks];
+        for (crd, name) in self.mc_names.clone() {
+            let plan = self.plan_mc(name)?;
+            plans[crd.block.to_idx()].insert(crd.macrocell.to_idx(), plan);
+            names[crd.block.to_idx()].insert(crd.macrocell.to_idx(), name);
+        }
+        for (fb, plans) in plans.into_iter().enumerate() {
+            if !plans.is_empty() {
+                self.emit_fb(fb, plans, &names[fb])?;
+            }
+        }
+
+        if self.is_xl {
+            for i in 0..self.fclk.len() {
+                if self.fclk[i].is_some() {
+                    self.set_global(&format!("FCLK{i}_ENABLE"));
+                }
+            }
+            for i in 0..self.foe.len() {
+                if self.foe[i].is_some() {
+                    self.set_global(&format!("FOE{i}_ENABLE"));
+                }
+            }
+        } else {
+            for (kind, pads) in [("FCLK", self.fclk.to_vec()), ("FOE", self.foe.clone())] {
+                for (i, pad) in pads.into_iter().enumerate() {
+                    let Some((pad, inv)) = pad else {
+                        continue;
+                    };
+                    let suffix = format!("PAD{pad}");
+                    let value = self
+                        .enum_values(&self.db.global_bits, &format!("{kind}{i}_MUX"))
+                        .unwrap()
+                        .keys()
+                        .find(|v| v.ends_with(&suffix))
+                        .unwrap()
+                        .clone();
+                    self.set_global(&format!("{kind}{i}_MUX={value}"));
+                    self.set_global(&format!("{}{kind}{i}_INV", if inv { "" } else { "!" }));
+                }
+            }
+        }
+        if self.fsr_inv == Some(true) {
+            self.set_global("FSR_INV");
+        }
+        if self.db.global_bits.items.contains_key("DONE") {
+            self.set_global("DONE");
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a design for the given device and package.  Returns the JED file and the
+/// chosen placement.
+pub fn compile(
+    db: &Database,
+    device: &str,
+    package: &str,
+    design: &Design,
+) -> Result<(JedFile, Placement), CompileError> {
+    design.check()?;
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(CompileError::UnknownDevice(device.to_string()));
+    };
+    let Some(&bond) = dev.bonds.get(package) else {
+        return Err(CompileError::UnknownPackage(package.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[bond];
+    let is_xl = chip.kind != ChipKind::Xc9500;
+    let mut io_special = chip.io_special.clone();
+    io_special.extend(
+        bond.io_special_override
+            .iter()
+            .map(|(k, &v)| (k.clone(), v)),
+    );
+    // the small XC9500 devices only have two FOE networks
+    let num_foe = if is_xl || chip.io_special.contains_key("GOE2") {
+        4
+    } else {
+        2
+    };
+    let mut pad_pins = BTreeMap::new();
+    for (pin, &pad) in &bond.pins {
+        if let BondPad::Iob(crd) = pad {
+            pad_pins.entry(crd).or_insert(pin.as_str());
+        }
+    }
+    let mut compiler = Compiler {
+        db,
+        chip,
+        design,
+        is_xl,
+        num_imux: if is_xl { 54 } else { 36 },
+        io_special,
+        pins: &bond.pins,
+        pad_pins,
+        used_pads: BTreeSet::new(),
+        input_pads: BTreeMap::new(),
+        mc_place: BTreeMap::new(),
+        mc_names: BTreeMap::new(),
+        fb_signals: vec![BTreeSet::new(); chip.blocks],
+        fclk: [None; 3],
+        foe: vec![None; num_foe],
+        fsr_inv: None,
+        bs: Bitstream::new(chip),
+    };
+    compiler.place()?;
+    compiler.emit()?;
+
+    let mut placement = Placement::default();
+    for (&name, &pad) in &compiler.input_pads {
+        placement
+            .pins
+            .insert(name.to_string(), compiler.pad_pins[&pad].to_string());
+    }
+    for (&name, &crd) in &compiler.mc_place {
+        placement.macrocells.insert(name.to_string(), crd);
+        if design.macrocells[name].output.is_some() {
+            placement
+                .pins
+                .insert(name.to_string(), compiler.pad_pins[&crd].to_string());
+        }
+    }
+    Ok((compiler.bs.to_jed(device), placement))
+}
@@ -3,16 +3,24 @@ use std::{collections::BTreeMap, error::Error, fs::File, path::Path};
 use bincode::{Decode, Encode};
 use itertools::Itertools;
 use prjcombine_entity::{
-    EntityVec,
+    EntityId, EntityVec,
     id::{EntityIdU8, EntityTag},
 };
 use prjcombine_types::{
     bsdata::Tile,
     cpld::MacrocellCoord,
-    db::{BondId, ChipId, DumpFlags, SpeedId},
+    db::{BondId, BondTag, ChipId, ChipTag, DumpFlags, SpeedId, SpeedTag},
+    diff::{DbDiff, DiffNode},
     speed::Speed,
+    text::{TextError, TextParser, expect_next_id, parse_hex, parse_id, parse_num, split_assign},
 };
 
+pub mod bitstream;
+pub mod compile;
+pub mod sim;
+pub mod svf;
+pub mod timing;
+
 pub struct BankTag;
 impl EntityTag for BankTag {
     const PREFIX: &'static str = "BANK";
@@ -20,6 +28,7 @@ impl EntityTag for BankTag {
 pub type BankId = EntityIdU8<BankTag>;
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChipKind {
     Xc9500,
     Xc9500Xl,
@@ -37,10 +46,12 @@ impl std::fmt::Display for ChipKind {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub kind: ChipKind,
     pub idcode: u32,
     pub blocks: usize,
+    #[cfg_attr(feature = "serde", serde(with = "prjcombine_types::serde_util::pairs"))]
     pub io: BTreeMap<MacrocellCoord, BankId>,
     pub banks: usize,
     pub tdo_bank: BankId,
@@ -78,9 +89,97 @@ impl Chip {
         }
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::leaf(&self.kind);
+        res.insert_leaf("idcode", format!("0x{:08x}", self.idcode));
+        res.insert_leaf("blocks", self.blocks);
+        res.insert_leaf("banks", self.banks);
+        for (k, v) in &self.io {
+            res.insert_leaf(format!("io {k}"), v);
+        }
+        res.insert_leaf("tdo_bank", self.tdo_bank);
+        for (k, v) in &self.io_special {
+            res.insert_leaf(format!("io_special {k}"), v);
+        }
+        res.insert_leaf("program_time", self.program_time);
+        res.insert_leaf("erase_time", self.erase_time);
+        res.insert("bstile IMUX_BITS", self.imux_bits.diff_node());
+        if let Some(ref bits) = self.uim_ibuf_bits {
+            res.insert("bstile UIM_IBUF_BITS", bits.diff_node());
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut kind = None;
+        let mut idcode = None;
+        let mut blocks = None;
+        let mut banks = None;
+        let mut io = BTreeMap::new();
+        let mut tdo_bank = None;
+        let mut io_special = BTreeMap::new();
+        let mut program_time = None;
+        let mut erase_time = None;
+        let mut imux_bits = None;
+        let mut uim_ibuf_bits = None;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("bstile", "IMUX_BITS")) => imux_bits = Some(Tile::parse(p)?),
+                Some(("bstile", "UIM_IBUF_BITS")) => uim_ibuf_bits = Some(Tile::parse(p)?),
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "kind" => {
+                            kind = Some(match val {
+                                "xc9500" => ChipKind::Xc9500,
+                                "xc9500xl" => ChipKind::Xc9500Xl,
+                                "xc9500xv" => ChipKind::Xc9500Xv,
+                                _ => return Err(line.error(format!("unknown chip kind {val}"))),
+                            })
+                        }
+                        "idcode" => idcode = Some(line.check(parse_hex(val))?),
+                        "blocks" => blocks = Some(line.check(parse_num(val))?),
+                        "banks" => banks = Some(line.check(parse_num(val))?),
+                        "io" => {
+                            let (mc, bank) = line.check(split_assign(val))?;
+                            io.insert(
+                                line.check(mc.parse())?,
+                                line.check(parse_id(bank, BankTag::PREFIX))?,
+                            );
+                        }
+                        "tdo_bank" => tdo_bank = Some(line.check(parse_id(val, BankTag::PREFIX))?),
+                        "io_special" => {
+                            let (name, mc) = line.check(split_assign(val))?;
+                            io_special.insert(name.to_string(), line.check(mc.parse())?);
+                        }
+                        "program_time" => program_time = Some(line.check(parse_num(val))?),
+                        "erase_time" => erase_time = Some(line.check(parse_num(val))?),
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        Ok(Chip {
+            kind: kind.ok_or_else(|| p.missing("kind"))?,
+            idcode: idcode.ok_or_else(|| p.missing("idcode"))?,
+            blocks: blocks.ok_or_else(|| p.missing("blocks"))?,
+            io,
+            banks: banks.ok_or_else(|| p.missing("banks"))?,
+            tdo_bank: tdo_bank.ok_or_else(|| p.missing("tdo_bank"))?,
+            io_special,
+            imux_bits: imux_bits.ok_or_else(|| p.missing("IMUX_BITS"))?,
+            uim_ibuf_bits,
+            program_time: program_time.ok_or_else(|| p.missing("program_time"))?,
+            erase_time: erase_time.ok_or_else(|| p.missing("erase_time"))?,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Nc,
     Gnd,
@@ -109,7 +208,33 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCCINT" => BondPad::VccInt,
+            "TMS" => BondPad::Tms,
+            "TCK" => BondPad::Tck,
+            "TDI" => BondPad::Tdi,
+            "TDO" => BondPad::Tdo,
+            _ => {
+                if let Some(mc) = s.strip_prefix("IOB_") {
+                    BondPad::Iob(mc.parse()?)
+                } else if let Some(bank) = s.strip_prefix("VCCIO") {
+                    BondPad::VccIo(BankId::from_idx(parse_num(bank)?))
+                } else {
+                    return Err(format!("unknown bond pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub io_special_override: BTreeMap<String, MacrocellCoord>,
     pub pins: BTreeMap<String, BondPad>,
@@ -130,9 +255,80 @@ impl Bond {
         }
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for (k, v) in &self.io_special_override {
+            res.insert_leaf(format!("io_special_override {k}"), v);
+        }
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut res = Bond {
+            io_special_override: BTreeMap::new(),
+            pins: BTreeMap::new(),
+        };
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            let (name, val) = line.check(split_assign(val))?;
+            match kw {
+                "io_special_override" => {
+                    res.io_special_override
+                        .insert(name.to_string(), line.check(val.parse())?);
+                }
+                "pin" => {
+                    res.pins.insert(name.to_string(), line.check(val.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut bonds = BTreeMap::new();
+        let mut speeds = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "bond" => {
+                    let (pkg, bond) = line.check(split_assign(val))?;
+                    bonds.insert(
+                        pkg.to_string(),
+                        line.check(parse_id(bond, BondTag::PREFIX))?,
+                    );
+                }
+                "speed" => {
+                    let (sname, speed) = line.check(split_assign(val))?;
+                    speeds.insert(
+                        sname.to_string(),
+                        line.check(parse_id(speed, SpeedTag::PREFIX))?,
+                    );
+                }
+                _ => return Err(line.error(format!("unknown device statement {kw}"))),
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds,
+            speeds,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -141,6 +337,7 @@ pub struct Device {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -168,6 +365,88 @@ impl Database {
         Ok(bincode::decode_from_std_read(&mut cf, config)?)
     }
 
+    /// Reads back a database from the full text dump written by [`Database::dump`].
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut speeds = EntityVec::new();
+        let mut devices = vec![];
+        let mut mc_bits = None;
+        let mut block_bits = None;
+        let mut global_bits = None;
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match kw {
+                "chip" => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                "bond" => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                "speed" => {
+                    line.check(expect_next_id(name, speeds.next_id()))?;
+                    speeds.push(Speed::parse(&mut p)?);
+                }
+                "device" => devices.push(Device::parse(&mut p, name)?),
+                "bstile" => {
+                    let tile = Some(Tile::parse(&mut p)?);
+                    match name {
+                        "MC_BITS" => mc_bits = tile,
+                        "BLOCK_BITS" => block_bits = tile,
+                        "GLOBAL_BITS" => global_bits = tile,
+                        _ => return Err(line.error(format!("unknown tile {name}"))),
+                    }
+                }
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            speeds,
+            devices,
+            mc_bits: mc_bits.ok_or_else(|| p.missing("MC_BITS"))?,
+            block_bits: block_bits.ok_or_else(|| p.missing("BLOCK_BITS"))?,
+            global_bits: global_bits.ok_or_else(|| p.missing("GLOBAL_BITS"))?,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips, bonds and speeds are compared through
+    /// the devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node());
+            for (pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for (sname, &speed) in &dev.speeds {
+                node.insert(format!("speed {sname}"), self.speeds[speed].diff_node());
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("bstile MC_BITS", self.mc_bits.diff_node());
+        res.insert("bstile BLOCK_BITS", self.block_bits.diff_node());
+        res.insert("bstile GLOBAL_BITS", self.global_bits.diff_node());
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
@@ -249,8 +528,8 @@ impl Database {
                 for (pkg, bond) in &dev.bonds {
                     writeln!(o, "\tbond {pkg} = {bond};")?;
                 }
-                for speed in dev.speeds.values() {
-                    writeln!(o, "\tspeed {speed};")?;
+                for (sname, speed) in &dev.speeds {
+                    writeln!(o, "\tspeed {sname} = {speed};")?;
                 }
                 writeln!(o, "}}")?;
                 writeln!(o)?;
@@ -0,0 +1,563 @@
+//! Functional simulation of a configured device.
+//!
+//! The simulator evaluates the configuration as decoded from the bitstream, following the
+//! semantics described in the device structure documentation.  It is cycle-based: all
+//! combinatorial logic is re-evaluated until it is stable, and registers are updated when
+//! their clock rises during that process.  Timing is not modelled, so inputs that change
+//! together with a clock are assumed to meet setup time.  Pins that are not driven by
+//! either side keep their last value, like with a bus keeper.
+
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::TestTarget;
+use prjcombine_types::cpld::{BlockId, MacrocellCoord, MacrocellId};
+
+use crate::{
+    Bond, BondPad, Chip, ChipKind, Database,
+    bitstream::{Bitstream, get_tile_bool, get_tile_enum},
+};
+
+pub(crate) const MCS_PER_FB: usize = 18;
+pub(crate) const PTS_PER_MC: usize = 5;
+const MAX_SETTLE_STEPS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PtAlloc {
+    None,
+    Sum,
+    Export,
+    Special,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ImuxSource {
+    None,
+    Uim(Vec<MacrocellCoord>),
+    Mc(MacrocellCoord),
+    Iob(MacrocellCoord),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ClkSource {
+    Pt,
+    Fclk(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OeSource {
+    Pt,
+    Foe(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OeMux {
+    Gnd,
+    Vcc,
+    Oe,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct McConfig {
+    pub(crate) pt_alloc: [PtAlloc; PTS_PER_MC],
+    pub(crate) export_up: bool,
+    pub(crate) import_up_sum: bool,
+    pub(crate) import_down_sum: bool,
+    pub(crate) inv: bool,
+    pub(crate) out_ff: bool,
+    pub(crate) tff: bool,
+    pub(crate) init: bool,
+    pub(crate) clk: ClkSource,
+    pub(crate) clk_inv: bool,
+    pub(crate) rst_fsr: bool,
+    pub(crate) set_fsr: bool,
+    pub(crate) ce: Option<usize>,
+    pub(crate) oe: OeSource,
+    pub(crate) oe_inv: bool,
+    pub(crate) uim_oe: OeMux,
+    pub(crate) iob_oe: OeMux,
+    pub(crate) uim_out_inv: bool,
+    pub(crate) iob_gnd: bool,
+    pub(crate) pt_hp: [bool; PTS_PER_MC],
+    pub(crate) sum_hp: bool,
+    pub(crate) slew_fast: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FbConfig {
+    pub(crate) enable: bool,
+    pub(crate) export_enable: bool,
+    pub(crate) imux: Vec<ImuxSource>,
+    // the (FB input, true polarity) pairs included in each product term
+    pub(crate) pts: Vec<[Vec<(usize, bool)>; PTS_PER_MC]>,
+    pub(crate) mcs: Vec<McConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct McState {
+    reg: bool,
+    clk: bool,
+    out: bool,
+    out_uim: bool,
+    oe: bool,
+}
+
+/// The configuration of a device, as decoded from the bitstream.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) pins: BTreeMap<String, MacrocellCoord>,
+    pub(crate) fclk: Vec<Option<(MacrocellCoord, bool)>>,
+    pub(crate) foe: Vec<Option<(MacrocellCoord, bool)>>,
+    pub(crate) fsr: Option<(MacrocellCoord, bool)>,
+    pub(crate) done: bool,
+    pub(crate) fbs: Vec<FbConfig>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    cfg: Config,
+    ext: BTreeMap<MacrocellCoord, Option<bool>>,
+    ibuf: BTreeMap<MacrocellCoord, bool>,
+    mcs: Vec<[McState; MCS_PER_FB]>,
+}
+
+impl Config {
+    pub(crate) fn new(db: &Database, chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let is_xl = chip.kind != ChipKind::Xc9500;
+        let num_imux = if is_xl { 54 } else { 36 };
+        let mut io_special = chip.io_special.clone();
+        io_special.extend(
+            bond.io_special_override
+                .iter()
+                .map(|(k, &v)| (k.clone(), v)),
+        );
+        let get_global =
+            |name: &str| get_tile_enum(&db.global_bits, chip, name, |crd| bs.get_global(crd));
+        let get_global_bool =
+            |name: &str| get_tile_bool(&db.global_bits, chip, name, |crd| bs.get_global(crd));
+
+        let num_foe = if is_xl || chip.io_special.contains_key("GOE2") {
+            4
+        } else {
+            2
+        };
+        let mut fclk = vec![];
+        let mut foe = vec![];
+        if is_xl {
+            for i in 0..3 {
+                fclk.push(
+                    get_global_bool(&format!("FCLK{i}_ENABLE"))
+                        .then(|| io_special.get(&format!("GCLK{i}")).map(|&pad| (pad, false)))
+                        .flatten(),
+                );
+            }
+            for i in 0..num_foe {
+                foe.push(
+                    get_global_bool(&format!("FOE{i}_ENABLE"))
+                        .then(|| io_special.get(&format!("GOE{i}")).map(|&pad| (pad, false)))
+                        .flatten(),
+                );
+            }
+        } else {
+            for (kind, prefix, num, res) in [
+                ("FCLK", "GCLKCLKPAD", 3, &mut fclk),
+                ("FOE", "GOEOEPAD", num_foe, &mut foe),
+            ] {
+                let pad_kind = if kind == "FCLK" { "GCLK" } else { "GOE" };
+                for i in 0..num {
+                    let pad = get_global(&format!("{kind}{i}_MUX"))
+                        .and_then(|val| val.strip_prefix(prefix))
+                        .and_then(|idx| io_special.get(&format!("{pad_kind}{idx}")));
+                    let inv = get_global_bool(&format!("{kind}{i}_INV"));
+                    res.push(pad.map(|&pad| (pad, inv)));
+                }
+            }
+        }
+        let fsr = io_special
+            .get("GSR")
+            .map(|&pad| (pad, get_global_bool("FSR_INV")));
+        let done = !db.global_bits.items.contains_key("DONE") || get_global_bool("DONE");
+
+        let mut mc_names = BTreeMap::new();
+        for fb in 0..chip.blocks {
+            for mc in 0..MCS_PER_FB {
+                let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+                mc_names.insert(crd.to_string(), crd);
+            }
+        }
+
+        let mut fbs = vec![];
+        for fb in 0..chip.blocks {
+            let get_fb_bool =
+                |name: &str| get_tile_bool(&db.block_bits, chip, name, |crd| bs.get_fb(fb, crd));
+            let mut imux = vec![];
+            for im in 0..num_imux {
+                let val = get_tile_enum(&chip.imux_bits, chip, &format!("IM[{im}].MUX"), |crd| {
+                    bs.get_fb(fb, crd)
+                });
+                imux.push(match val {
+                    Some("UIM") => {
+                        let mut mcs = vec![];
+                        for sfb in 0..chip.blocks {
+                            for mc in 0..MCS_PER_FB {
+                                if bs.get_uim(fb, sfb, im, mc) {
+                                    mcs.push(MacrocellCoord::simple(
+                                        BlockId::from_idx(sfb),
+                                        MacrocellId::from_idx(mc),
+                                    ));
+                                }
+                            }
+                        }
+                        ImuxSource::Uim(mcs)
+                    }
+                    Some(val) => {
+                        if let Some(mc) = val.strip_prefix("FBK_MC") {
+                            ImuxSource::Mc(MacrocellCoord::simple(
+                                BlockId::from_idx(fb),
+                                MacrocellId::from_idx(mc.parse().unwrap()),
+                            ))
+                        } else if let Some(crd) = val.strip_prefix("MC_") {
+                            ImuxSource::Mc(mc_names[crd])
+                        } else if let Some(crd) = val.strip_prefix("IOB_") {
+                            ImuxSource::Iob(mc_names[crd])
+                        } else {
+                            ImuxSource::None
+                        }
+                    }
+                    None => ImuxSource::None,
+                });
+            }
+            let mut pts = vec![];
+            let mut mcs = vec![];
+            for mc in 0..MCS_PER_FB {
+                pts.push(core::array::from_fn(|pt| {
+                    let mut lits = vec![];
+                    for im in 0..num_imux {
+                        for pol in [true, false] {
+                            if bs.get_pt(fb, mc, pt, im, pol) {
+                                lits.push((im, pol));
+                            }
+                        }
+                    }
+                    lits
+                }));
+                let get = |name: &str| {
+                    get_tile_enum(&db.mc_bits, chip, name, |crd| bs.get_mc(fb, mc, crd))
+                };
+                let get_bool = |name: &str| {
+                    get_tile_bool(&db.mc_bits, chip, name, |crd| bs.get_mc(fb, mc, crd))
+                };
+                let get_oe_mux = |name: &str| match get(name) {
+                    Some("VCC") => OeMux::Vcc,
+                    Some("OE_MUX") => OeMux::Oe,
+                    _ => OeMux::Gnd,
+                };
+                mcs.push(McConfig {
+                    pt_alloc: core::array::from_fn(|pt| match get(&format!("PT[{pt}].ALLOC")) {
+                        Some("SUM") => PtAlloc::Sum,
+                        Some("EXPORT") => PtAlloc::Export,
+                        Some("SPECIAL") => PtAlloc::Special,
+                        _ => PtAlloc::None,
+                    }),
+                    export_up: get("EXPORT_CHAIN_DIR") == Some("UP"),
+                    import_up_sum: get("IMPORT_UP_ALLOC") == Some("SUM"),
+                    import_down_sum: get("IMPORT_DOWN_ALLOC") == Some("SUM"),
+                    inv: get_bool("INV"),
+                    out_ff: get("OUT_MUX") == Some("FF"),
+                    tff: get("REG_MODE") == Some("TFF"),
+                    init: get_bool("REG_INIT"),
+                    clk: match get("CLK_MUX").and_then(|val| val.strip_prefix("FCLK")) {
+                        Some(idx) => ClkSource::Fclk(idx.parse().unwrap()),
+                        None => ClkSource::Pt,
+                    },
+                    clk_inv: is_xl && get_bool("CLK_INV"),
+                    rst_fsr: get("RST_MUX") == Some("FSR"),
+                    set_fsr: get("SET_MUX") == Some("FSR"),
+                    ce: if is_xl {
+                        match get("CE_MUX") {
+                            Some("PT2") => Some(2),
+                            Some("PT3") => Some(3),
+                            _ => None,
+                        }
+                    } else {
+                        None
+                    },
+                    oe: match get("OE_MUX").and_then(|val| val.strip_prefix("FOE")) {
+                        Some(idx) => OeSource::Foe(idx.parse().unwrap()),
+                        None => OeSource::Pt,
+                    },
+                    oe_inv: is_xl && get_bool("OE_INV"),
+                    uim_oe: if is_xl {
+                        OeMux::Vcc
+                    } else {
+                        get_oe_mux("UIM_OE_MUX")
+                    },
+                    iob_oe: if is_xl {
+                        OeMux::Oe
+                    } else {
+                        get_oe_mux("IOB_OE_MUX")
+                    },
+                    uim_out_inv: !is_xl && get_bool("UIM_OUT_INV"),
+                    iob_gnd: get_bool("IOB_GND"),
+                    pt_hp: core::array::from_fn(|pt| get_bool(&format!("PT[{pt}].HP"))),
+                    sum_hp: get_bool("SUM_HP"),
+                    slew_fast: get("IOB_SLEW") == Some("FAST"),
+                });
+            }
+            fbs.push(FbConfig {
+                enable: get_fb_bool("ENABLE"),
+                export_enable: get_fb_bool("EXPORT_ENABLE"),
+                imux,
+                pts,
+                mcs,
+            });
+        }
+
+        let mut pins = BTreeMap::new();
+        for (pin, &pad) in &bond.pins {
+            if let BondPad::Iob(crd) = pad {
+                pins.insert(pin.clone(), crd);
+            }
+        }
+        Config {
+            pins,
+            fclk,
+            foe,
+            fsr,
+            done,
+            fbs,
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new(db: &Database, chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let cfg = Config::new(db, chip, bond, bs);
+        let mcs = cfg
+            .fbs
+            .iter()
+            .map(|fbc| {
+                core::array::from_fn(|mc| McState {
+                    reg: fbc.mcs[mc].init,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let mut res = Simulator {
+            cfg,
+            ext: chip.io.keys().map(|&crd| (crd, None)).collect(),
+            ibuf: chip.io.keys().map(|&crd| (crd, false)).collect(),
+            mcs,
+        };
+        res.settle_inner(false);
+        res
+    }
+
+    /// Drives a package pin from the outside, or leaves it undriven if `None`.
+    pub fn set_pin(&mut self, pin: &str, val: Option<bool>) {
+        if let Some(&crd) = self.cfg.pins.get(pin) {
+            self.ext.insert(crd, val);
+        }
+    }
+
+    /// Returns the value driven by the device on a package pin, or `None` if the output
+    /// buffer is disabled.
+    pub fn get_pin(&self, pin: &str) -> Option<bool> {
+        self.cfg.pins.get(pin).and_then(|&crd| self.pad_output(crd))
+    }
+
+    /// Returns the output of a macrocell.
+    pub fn get_mc(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].out
+    }
+
+    /// Returns the state of a macrocell's register.
+    pub fn get_reg(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].reg
+    }
+
+    /// Re-evaluates the device until it is stable.
+    pub fn settle(&mut self) {
+        self.settle_inner(true);
+    }
+
+    fn settle_inner(&mut self, clocked: bool) {
+        for _ in 0..MAX_SETTLE_STEPS {
+            if !self.step(clocked) {
+                break;
+            }
+        }
+    }
+
+    fn pad_output(&self, crd: MacrocellCoord) -> Option<bool> {
+        let fb = crd.block.to_idx();
+        let mc = crd.macrocell.to_idx();
+        let state = &self.mcs[fb][mc];
+        if self.cfg.fbs[fb].mcs[mc].iob_gnd {
+            Some(false)
+        } else if self.cfg.done && state.oe {
+            Some(state.out)
+        } else {
+            None
+        }
+    }
+
+    fn pad_input(&self, pad: Option<(MacrocellCoord, bool)>) -> bool {
+        pad.is_some_and(|(crd, inv)| self.ibuf[&crd] ^ inv)
+    }
+
+    /// Performs a single evaluation step.  Returns true if anything changed.
+    fn step(&mut self, clocked: bool) -> bool {
+        let mut changed = false;
+        let pads: Vec<MacrocellCoord> = self.ibuf.keys().copied().collect();
+        for crd in pads {
+            let val = self.pad_output(crd).or(self.ext[&crd]);
+            if let Some(val) = val
+                && self.ibuf[&crd] != val
+            {
+                self.ibuf.insert(crd, val);
+                changed = true;
+            }
+        }
+        let fclk: Vec<bool> = self
+            .cfg
+            .fclk
+            .iter()
+            .map(|&pad| self.pad_input(pad))
+            .collect();
+        let foe: Vec<bool> = self
+            .cfg
+            .foe
+            .iter()
+            .map(|&pad| self.pad_input(pad))
+            .collect();
+        let fsr = self.pad_input(self.cfg.fsr);
+
+        let mut new_mcs = self.mcs.clone();
+        for (fb, fbc) in self.cfg.fbs.iter().enumerate() {
+            let inputs: Vec<bool> = fbc
+                .imux
+                .iter()
+                .map(|src| match *src {
+                    ImuxSource::None => false,
+                    ImuxSource::Uim(ref mcs) => mcs
+                        .iter()
+                        .all(|crd| self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].out_uim),
+                    ImuxSource::Mc(crd) => self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].out,
+                    ImuxSource::Iob(crd) => self.ibuf[&crd],
+                })
+                .collect();
+            let pts: Vec<[bool; PTS_PER_MC]> = fbc
+                .pts
+                .iter()
+                .map(|mc_pts| {
+                    core::array::from_fn(|pt| {
+                        !fbc.enable || mc_pts[pt].iter().all(|&(im, pol)| inputs[im] == pol)
+                    })
+                })
+                .collect();
+            let alloc_or = |mc: usize, alloc: PtAlloc| {
+                (0..PTS_PER_MC).any(|pt| fbc.mcs[mc].pt_alloc[pt] == alloc && pts[mc][pt])
+            };
+
+            // the export chain is a ring, iterate until it is stable
+            let mut export_sum = [false; MCS_PER_FB];
+            let mut chain_up = [false; MCS_PER_FB];
+            let mut chain_down = [false; MCS_PER_FB];
+            for _ in 0..=2 * MCS_PER_FB {
+                let mut stable = true;
+                for mc in 0..MCS_PER_FB {
+                    let mcc = &fbc.mcs[mc];
+                    let import_up = chain_up[(mc + MCS_PER_FB - 1) % MCS_PER_FB];
+                    let import_down = chain_down[(mc + 1) % MCS_PER_FB];
+                    let sum = (!mcc.import_up_sum && import_up)
+                        || (!mcc.import_down_sum && import_down)
+                        || alloc_or(mc, PtAlloc::Export);
+                    let up = mcc.export_up && (fbc.export_enable || mc != 0) && sum;
+                    let down = !mcc.export_up && sum;
+                    if (sum, up, down) != (export_sum[mc], chain_up[mc], chain_down[mc]) {
+                        stable = false;
+                    }
+                    export_sum[mc] = sum;
+                    chain_up[mc] = up;
+                    chain_down[mc] = down;
+                }
+                if stable {
+                    break;
+                }
+            }
+
+            for mc in 0..MCS_PER_FB {
+                let mcc = &fbc.mcs[mc];
+                let special = |pt: usize| mcc.pt_alloc[pt] == PtAlloc::Special && pts[mc][pt];
+                let sum = (mcc.import_up_sum && export_sum[(mc + MCS_PER_FB - 1) % MCS_PER_FB])
+                    || (mcc.import_down_sum && export_sum[(mc + 1) % MCS_PER_FB])
+                    || alloc_or(mc, PtAlloc::Sum);
+                let xor = sum ^ special(4) ^ mcc.inv;
+                let clk = match mcc.clk {
+                    ClkSource::Pt => special(0),
+                    ClkSource::Fclk(idx) => fclk.get(idx).copied().unwrap_or(false),
+                } ^ mcc.clk_inv;
+                let rst = if mcc.rst_fsr {
+                    fsr
+                } else {
+                    mcc.ce != Some(2) && special(2)
+                };
+                let set = if mcc.set_fsr {
+                    fsr
+                } else {
+                    mcc.ce != Some(3) && special(3)
+                };
+                let ce = mcc.ce.is_none_or(special);
+
+                let state = &mut new_mcs[fb][mc];
+                if rst {
+                    state.reg = false;
+                } else if set {
+                    state.reg = true;
+                } else if clocked && clk && !state.clk && ce {
+                    state.reg = if mcc.tff { state.reg ^ xor } else { xor };
+                }
+                state.clk = clk;
+                state.out = if mcc.out_ff { state.reg } else { xor };
+                let oe = match mcc.oe {
+                    OeSource::Pt => special(1),
+                    OeSource::Foe(idx) => foe.get(idx).copied().unwrap_or(false),
+                } ^ mcc.oe_inv;
+                let mux_oe = |mux: OeMux| match mux {
+                    OeMux::Gnd => false,
+                    OeMux::Vcc => true,
+                    OeMux::Oe => oe,
+                };
+                state.oe = mux_oe(mcc.iob_oe);
+                state.out_uim = (!mux_oe(mcc.uim_oe) || state.out) ^ mcc.uim_out_inv;
+            }
+        }
+        for (old, new) in self.mcs.iter().zip(&new_mcs) {
+            for (old, new) in old.iter().zip(new) {
+                if (old.reg, old.out, old.out_uim, old.oe)
+                    != (new.reg, new.out, new.out_uim, new.oe)
+                {
+                    changed = true;
+                }
+            }
+        }
+        self.mcs = new_mcs;
+        changed
+    }
+}
+
+/// JEDEC pin numbers refer to the `P<n>` pins of the package.
+impl TestTarget for Simulator {
+    fn set_pin(&mut self, pin: u32, val: Option<bool>) {
+        Simulator::set_pin(self, &format!("P{pin}"), val);
+    }
+
+    fn get_pin(&self, pin: u32) -> Option<bool> {
+        Simulator::get_pin(self, &format!("P{pin}"))
+    }
+
+    fn settle(&mut self) {
+        Simulator::settle(self);
+    }
+}
@@ -0,0 +1,395 @@
+//! Generates JTAG programming sequences (SVF / XSVF) from JED files.
+//!
+//! The sequences follow the ISP protocol described in the JTAG chapter of the
+//! documentation.  Programming is done in two passes: the main pass writes everything
+//! except the protection and `DONE` fuses, which are written by a final pass after
+//! the (optional) verification.
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagProgram, ProgramError, ProgramOptions, bits_from_u64};
+use prjcombine_types::{bitvec::BitVec, bsdata::TileItemKind};
+
+use crate::{Chip, ChipKind, Database};
+
+const IR_LEN: usize = 8;
+const IR_ISPEN: u64 = 0xe8;
+const IR_FPGM: u64 = 0xea;
+const IR_FBULK: u64 = 0xed;
+const IR_FVFY: u64 = 0xee;
+const IR_ISPEX: u64 = 0xf0;
+const IR_IDCODE: u64 = 0xfe;
+const IR_BYPASS: u64 = 0xff;
+
+const ISPEX_TIME: u32 = 100;
+
+const FINAL_ITEMS: [&str; 5] = [
+    "READ_PROT",
+    "READ_PROT_A",
+    "READ_PROT_B",
+    "WRITE_PROT",
+    "DONE",
+];
+
+#[derive(Clone)]
+struct Word {
+    addr: u32,
+    data: BitVec,
+    /// Bits of the word actually backed by fuses.
+    valid: BitVec,
+}
+
+struct Programmer<'a> {
+    chip: &'a Chip,
+    prog: JtagProgram,
+    /// Width of the data field in the ISPCONFIGURATION register.
+    data_len: usize,
+    /// Width of the address field in the ISPCONFIGURATION register.
+    addr_len: usize,
+    /// The control value that triggers an operation.
+    ctrl_trigger: u64,
+    /// The neutral control value, also returned on success.
+    ctrl_neutral: u64,
+}
+
+fn xc9500_words(chip: &Chip, fuses: &BitVec) -> Vec<Word> {
+    let mut res = vec![];
+    let mut pos = 0;
+    let mut push = |addr: u32, sz: usize, pos: &mut usize| {
+        let mut data = BitVec::repeat(true, 8);
+        let mut valid = BitVec::repeat(false, 8);
+        for j in 0..sz {
+            data.set(j, fuses[*pos + j]);
+            valid.set(j, true);
+        }
+        *pos += sz;
+        res.push(Word { addr, data, valid });
+    };
+    for fb in 0..chip.blocks as u32 {
+        for row in 0..72 {
+            for col in 0..15 {
+                let sz = if col < 9 { 8 } else { 6 };
+                push(fb << 13 | row << 5 | col / 5 << 3 | col % 5, sz, &mut pos);
+            }
+        }
+        for sfb in 0..chip.blocks as u32 {
+            for row in 0..18 {
+                for col in 0..5 {
+                    let sz = if col == 0 { 8 } else { 7 };
+                    push(fb << 13 | 1 << 12 | sfb << 8 | row << 3 | col, sz, &mut pos);
+                }
+            }
+        }
+    }
+    res
+}
+
+fn xc9500xl_words(chip: &Chip, fuses: &BitVec) -> Vec<Word> {
+    let mut res = vec![];
+    let mut pos = 0;
+    for row in 0..108 {
+        for col in 0..15 {
+            let sz = if col < 9 { 8 } else { 6 };
+            let mut data = BitVec::repeat(false, 8 * chip.blocks);
+            let mut valid = BitVec::repeat(false, 8 * chip.blocks);
+            for fb in 0..chip.blocks {
+                for j in 0..sz {
+                    data.set(fb * 8 + j, fuses[pos]);
+                    valid.set(fb * 8 + j, true);
+                    pos += 1;
+                }
+            }
+            res.push(Word {
+                addr: row << 5 | col / 5 << 3 | col % 5,
+                data,
+                valid,
+            });
+        }
+    }
+    res
+}
+
+/// Returns the number of fuses in a JED file for the given chip.
+pub fn jed_fuse_count(chip: &Chip) -> usize {
+    let main_row_bits = 8 * 9 + 6 * 6;
+    if chip.kind == ChipKind::Xc9500 {
+        chip.blocks * (72 * main_row_bits + chip.blocks * 18 * (8 + 7 * 4))
+    } else {
+        chip.blocks * 108 * main_row_bits
+    }
+}
+
+impl Programmer<'_> {
+    fn is_xl(&self) -> bool {
+        self.chip.kind != ChipKind::Xc9500
+    }
+
+    fn dr_len(&self) -> usize {
+        2 + self.data_len + self.addr_len
+    }
+
+    fn dr(&self, ctrl: u64, data: Option<&BitVec>, addr: u32) -> BitVec {
+        let mut res = bits_from_u64(ctrl, 2);
+        match data {
+            Some(data) => res.extend(data.iter()),
+            None => res.extend(BitVec::repeat(true, self.data_len).iter()),
+        }
+        res.extend(bits_from_u64(addr.into(), self.addr_len).iter());
+        res
+    }
+
+    fn status_check(&self) -> (BitVec, BitVec) {
+        let mut tdo = BitVec::repeat(false, self.dr_len());
+        let mut mask = BitVec::repeat(false, self.dr_len());
+        for i in 0..2 {
+            tdo.set(i, (self.ctrl_neutral >> i & 1) != 0);
+            mask.set(i, true);
+        }
+        (tdo, mask)
+    }
+
+    fn shift(&mut self, tdi: BitVec, check: Option<(BitVec, BitVec)>) {
+        match check {
+            Some((tdo, mask)) => self.prog.sdr_check(tdi, tdo, mask),
+            None => self.prog.sdr(tdi),
+        }
+    }
+
+    fn finish_status(&mut self) {
+        let tdi = self.dr(self.ctrl_neutral, None, 0);
+        let (tdo, mask) = self.status_check();
+        self.prog.sdr_check(tdi, tdo, mask);
+    }
+
+    fn check_idcode(&mut self) {
+        self.prog.comment("check IDCODE");
+        self.prog.sir(IR_LEN, IR_IDCODE);
+        self.prog.sdr_check(
+            BitVec::repeat(false, 32),
+            bits_from_u64(self.chip.idcode.into(), 32),
+            bits_from_u64(0x0fff_ffff, 32),
+        );
+    }
+
+    fn isp_enter(&mut self) {
+        self.prog.comment("enter ISP mode");
+        self.prog.sir(IR_LEN, IR_ISPEN);
+        if self.is_xl() {
+            self.prog.sdr(bits_from_u64(0b000101, 6));
+        } else {
+            let len = self.chip.blocks + 4;
+            self.prog
+                .sdr(bits_from_u64((1 << (self.chip.blocks + 1)) - 1, len));
+        }
+        self.prog.run_test(1, 0);
+    }
+
+    fn isp_exit(&mut self) {
+        self.prog.comment("exit ISP mode");
+        self.prog.sir(IR_LEN, IR_ISPEX);
+        self.prog.run_test(1, ISPEX_TIME);
+    }
+
+    fn erase(&mut self) {
+        self.prog
+            .comment("unlock write protection and bulk erase the device");
+        self.prog.sir(IR_LEN, IR_FBULK);
+        let erase_time = self.chip.erase_time;
+        if self.is_xl() {
+            // the FBULK instruction uses the short ISPADDRESS register
+            let mut tdi = bits_from_u64(self.ctrl_trigger, 2);
+            tdi.extend(bits_from_u64(0xaa55, 16).iter());
+            self.prog.sdr(tdi);
+            self.prog.run_test(1, erase_time);
+            self.prog.sdr_check(
+                bits_from_u64(self.ctrl_neutral, 18),
+                bits_from_u64(self.ctrl_neutral, 18),
+                bits_from_u64(3, 18),
+            );
+        } else {
+            // the first erase uses the unlock address, which has the area bit clear
+            // and thus erases the main areas; the second one erases the UIM areas
+            for addr in [0x1aa55, 1 << 12] {
+                let tdi = self.dr(self.ctrl_trigger, None, addr);
+                self.shift(tdi, None);
+                self.prog.run_test(1, erase_time);
+                self.finish_status();
+            }
+        }
+    }
+
+    /// Programs the given words.  On XC9500XL/XV, `words` must consist of whole rows.
+    /// If `erased` is set, words that are already in the erased state are skipped.
+    fn program(&mut self, words: &[Word], erased: bool) {
+        self.prog.sir(IR_LEN, IR_FPGM);
+        let program_time = self.chip.program_time;
+        let mut pending = false;
+        if self.is_xl() {
+            for row in words.chunks(15) {
+                if erased && row.iter().all(|word| !word.data.any()) {
+                    continue;
+                }
+                for (i, word) in row.iter().enumerate() {
+                    let ctrl = if i == 14 { self.ctrl_trigger } else { 0b01 };
+                    let tdi = self.dr(ctrl, Some(&word.data), word.addr);
+                    let check = (i == 0 && pending).then(|| self.status_check());
+                    self.shift(tdi, check);
+                }
+                self.prog.run_test(1, program_time);
+                pending = true;
+            }
+        } else {
+            for word in words {
+                if erased && word.data.all() {
+                    continue;
+                }
+                let tdi = self.dr(self.ctrl_trigger, Some(&word.data), word.addr);
+                let check = pending.then(|| self.status_check());
+                self.shift(tdi, check);
+                self.prog.run_test(1, program_time);
+                pending = true;
+            }
+        }
+        if pending {
+            self.finish_status();
+        }
+    }
+
+    fn verify(&mut self, words: &[Word]) {
+        self.prog.sir(IR_LEN, IR_FVFY);
+        let mut prev: Option<&Word> = None;
+        for word in words {
+            let tdi = self.dr(self.ctrl_trigger, None, word.addr);
+            let check = prev.map(|prev| self.read_check(prev));
+            self.shift(tdi, check);
+            self.prog.run_test(1, 0);
+            prev = Some(word);
+        }
+        if let Some(prev) = prev {
+            let tdi = self.dr(self.ctrl_neutral, None, 0);
+            let (tdo, mask) = self.read_check(prev);
+            self.prog.sdr_check(tdi, tdo, mask);
+        }
+    }
+
+    fn read_check(&self, word: &Word) -> (BitVec, BitVec) {
+        let (mut tdo, mut mask) = self.status_check();
+        for i in 0..self.data_len {
+            tdo.set(2 + i, word.data[i]);
+            mask.set(2 + i, word.valid[i]);
+        }
+        (tdo, mask)
+    }
+}
+
+/// Generates a JTAG sequence programming the given JED file into a device.
+///
+/// If the JED file has the security fuse set, the read protection fuses are programmed
+/// in addition to whatever the fuse list contains.
+pub fn program(
+    db: &Database,
+    device: &str,
+    jed: &JedFile,
+    options: &ProgramOptions,
+) -> Result<JtagProgram, ProgramError> {
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(ProgramError::UnknownDevice(device.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let Some(ref fuses) = jed.fuses else {
+        return Err(ProgramError::MissingFuses);
+    };
+    let expected = jed_fuse_count(chip);
+    if fuses.len() != expected {
+        return Err(ProgramError::FuseCountMismatch {
+            expected,
+            got: fuses.len(),
+        });
+    }
+    let xl = chip.kind != ChipKind::Xc9500;
+    let mut words = if xl {
+        xc9500xl_words(chip, fuses)
+    } else {
+        xc9500_words(chip, fuses)
+    };
+    let locate = |rect: usize, row: usize, bit: usize| -> (usize, usize) {
+        let (col, bitpos) = (bit % 9, 6 + bit / 9);
+        if xl {
+            (row * 15 + col, rect * 8 + bitpos)
+        } else {
+            (
+                rect * (72 * 15 + chip.blocks * 18 * 5) + row * 15 + col,
+                bitpos,
+            )
+        }
+    };
+
+    // the final pass fuses are programmed only after everything else is in place
+    let mut final_bits = vec![];
+    for name in FINAL_ITEMS {
+        let Some(item) = db.global_bits.items.get(name) else {
+            continue;
+        };
+        let TileItemKind::BitVec { ref invert } = item.kind else {
+            unreachable!()
+        };
+        for (i, crd) in item.bits.iter().enumerate() {
+            let (idx, bit) = locate(crd.rect.to_idx(), crd.frame.to_idx(), crd.bit.to_idx());
+            if name.starts_with("READ_PROT") && jed.security == Some(true) {
+                words[idx].data.set(bit, !invert[i]);
+            }
+            final_bits.push((idx, bit));
+        }
+    }
+    let mut main_words = words.clone();
+    for &(idx, bit) in &final_bits {
+        main_words[idx].data.set(bit, !xl);
+    }
+
+    let mut p = Programmer {
+        chip,
+        prog: JtagProgram::new(),
+        data_len: if xl { 8 * chip.blocks } else { 8 },
+        addr_len: if xl { 16 } else { 17 },
+        ctrl_trigger: if xl { 0b11 } else { 0b10 },
+        ctrl_neutral: if xl { 0b01 } else { 0b11 },
+    };
+    p.prog.comment(format!("device {device}"));
+    p.check_idcode();
+    p.isp_enter();
+    if options.erase {
+        p.erase();
+        // read protection is only reevaluated when entering ISP mode
+        p.isp_exit();
+        p.isp_enter();
+    }
+    p.prog.comment("program main fuses");
+    p.program(&main_words, options.erase);
+    if options.verify {
+        p.prog.comment("verify main fuses");
+        p.verify(&main_words);
+    }
+    let mut final_words: Vec<usize> = if xl {
+        final_bits
+            .iter()
+            .flat_map(|&(idx, _)| {
+                let row = idx / 15 * 15;
+                row..row + 15
+            })
+            .collect()
+    } else {
+        final_bits.iter().map(|&(idx, _)| idx).collect()
+    };
+    final_words.sort_unstable();
+    final_words.dedup();
+    let final_words: Vec<Word> = final_words
+        .into_iter()
+        .filter(|&idx| xl || !words[idx].data.all())
+        .map(|idx| words[idx].clone())
+        .collect();
+    p.prog.comment("program protection and DONE fuses");
+    p.program(&final_words, true);
+    p.isp_exit();
+    p.prog.sir(IR_LEN, IR_BYPASS);
+    Ok(p.prog)
+}
@@ -0,0 +1,268 @@
+//! Static timing analysis of a configured device.
+//!
+//! The timing graph is built from the configuration as decoded for the simulator, using
+//! the delays of the chosen speed grade.  Product term delays depend on the power mode of
+//! the product term (and of the sum term, for product terms allocated to it).  Exported
+//! product terms pay `DEL_EXP_D` for the first hop and `DEL_EXP_EXP` for each further one.
+//! The speed data does not distinguish the fast feedback path, so all macrocell feedback
+//! uses `DEL_UIM_IMUX`.
+
+use prjcombine_entity::EntityId;
+use prjcombine_types::{
+    cpld::{
+        BlockId, MacrocellCoord, MacrocellId,
+        timing::{MacrocellTiming, RegisterNodes},
+    },
+    speed::{EdgeDelay, Speed, SpeedError},
+    timing::{ClockEdge, TimingGraph, TimingNodeId, TimingNodeKind},
+};
+
+use crate::{
+    Bond, Chip, ChipKind, Database,
+    bitstream::Bitstream,
+    sim::{ClkSource, Config, ImuxSource, MCS_PER_FB, OeMux, OeSource, PTS_PER_MC, PtAlloc},
+};
+
+#[derive(Clone, Copy)]
+struct McNodes {
+    d: TimingNodeId,
+    exp: TimingNodeId,
+    clk: TimingNodeId,
+    sr: TimingNodeId,
+    ce: TimingNodeId,
+    oe: TimingNodeId,
+    q: TimingNodeId,
+    out: TimingNodeId,
+}
+
+/// Builds the timing graph of a configured device for the given speed grade.
+pub fn timing_graph(
+    db: &Database,
+    chip: &Chip,
+    bond: &Bond,
+    bs: &Bitstream,
+    speed: &Speed,
+) -> Result<TimingGraph, SpeedError> {
+    let cfg = Config::new(db, chip, bond, bs);
+    let is_xl = chip.kind != ChipKind::Xc9500;
+    let mut mt = MacrocellTiming::new(speed);
+    let (ibufs, pad_pins) = mt.add_input_pads(
+        cfg.pins.iter().map(|(pin, &crd)| (pin.as_str(), crd)),
+        |crd| format!("{crd}.I"),
+    );
+
+    let mut mcs: Vec<Vec<McNodes>> = vec![];
+    for fb in 0..chip.blocks {
+        let mut fb_mcs = vec![];
+        for mc in 0..MCS_PER_FB {
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            let mut node = |name: &str| {
+                mt.graph
+                    .add_node(format!("{crd}.{name}"), TimingNodeKind::Internal)
+            };
+            fb_mcs.push(McNodes {
+                d: node("D"),
+                exp: node("EXP"),
+                clk: node("CLK"),
+                sr: node("SR"),
+                ce: node("CE"),
+                oe: node("OE"),
+                q: node("Q"),
+                out: node("OUT"),
+            });
+        }
+        mcs.push(fb_mcs);
+    }
+    let mc_nodes = |crd: MacrocellCoord| mcs[crd.block.to_idx()][crd.macrocell.to_idx()];
+
+    let del_ibuf_imux = speed.delay("DEL_IBUF_IMUX")?;
+    let del_uim_imux = speed.delay("DEL_UIM_IMUX")?;
+    let del_exp_d = speed.delay("DEL_EXP_D")?;
+    let del_exp_exp = speed.delay("DEL_EXP_EXP")?;
+    let del_pt_clk = speed.delay("DEL_IMUX_PT_CLK")?;
+    let del_pt_oe = speed.delay("DEL_IMUX_PT_OE")?;
+    let del_pt_sr = is_xl.then(|| speed.delay("DEL_IMUX_PT_SR")).transpose()?;
+    let del_pt_ce = is_xl.then(|| speed.delay("DEL_IMUX_PT_CE")).transpose()?;
+    let del_pt_d_hp = speed.delay("DEL_IMUX_D_HP")?;
+    let del_pt_d_lp = speed.delay("DEL_IMUX_D_LP")?;
+
+    let del_ibuf_fclk = speed.delay("DEL_IBUF_FCLK")?;
+    for (idx, &pad) in cfg.fclk.iter().enumerate() {
+        let Some((crd, _)) = pad else { continue };
+        let Some(&ibuf) = ibufs.get(&crd) else {
+            continue;
+        };
+        for (fbc, fb_mcs) in cfg.fbs.iter().zip(&mcs) {
+            for (mcc, nodes) in fbc.mcs.iter().zip(fb_mcs) {
+                if mcc.clk == ClkSource::Fclk(idx) {
+                    mt.graph.add_edge(ibuf, nodes.clk, del_ibuf_fclk);
+                }
+            }
+        }
+    }
+    let del_ibuf_foe = speed.delay("DEL_IBUF_FOE")?;
+    for (idx, &pad) in cfg.foe.iter().enumerate() {
+        let Some((crd, _)) = pad else { continue };
+        let Some(&ibuf) = ibufs.get(&crd) else {
+            continue;
+        };
+        for (fbc, fb_mcs) in cfg.fbs.iter().zip(&mcs) {
+            for (mcc, nodes) in fbc.mcs.iter().zip(fb_mcs) {
+                if mcc.oe == OeSource::Foe(idx) {
+                    mt.graph.add_edge(ibuf, nodes.oe, del_ibuf_foe);
+                }
+            }
+        }
+    }
+    if let Some((crd, _)) = cfg.fsr
+        && let Some(&ibuf) = ibufs.get(&crd)
+    {
+        let del_ibuf_fsr = speed.delay("DEL_IBUF_FSR")?;
+        for (fbc, fb_mcs) in cfg.fbs.iter().zip(&mcs) {
+            for (mcc, nodes) in fbc.mcs.iter().zip(fb_mcs) {
+                if mcc.rst_fsr || mcc.set_fsr {
+                    mt.graph.add_edge(ibuf, nodes.sr, del_ibuf_fsr);
+                }
+            }
+        }
+    }
+
+    for (fb, fbc) in cfg.fbs.iter().enumerate() {
+        if !fbc.enable {
+            continue;
+        }
+        let imux: Vec<TimingNodeId> = (0..fbc.imux.len())
+            .map(|im| {
+                mt.graph
+                    .add_node(format!("FB{fb}.IM{im}"), TimingNodeKind::Internal)
+            })
+            .collect();
+        for (im, src) in fbc.imux.iter().enumerate() {
+            match *src {
+                ImuxSource::None => (),
+                ImuxSource::Uim(ref srcs) => {
+                    for &crd in srcs {
+                        mt.graph.add_edge(mc_nodes(crd).out, imux[im], del_uim_imux);
+                        if !is_xl
+                            && cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()].uim_oe
+                                == OeMux::Oe
+                        {
+                            mt.graph.add_edge(mc_nodes(crd).oe, imux[im], del_uim_imux);
+                        }
+                    }
+                }
+                ImuxSource::Mc(crd) => mt.graph.add_edge(mc_nodes(crd).out, imux[im], del_uim_imux),
+                ImuxSource::Iob(crd) => {
+                    if let Some(&ibuf) = ibufs.get(&crd) {
+                        mt.graph.add_edge(ibuf, imux[im], del_ibuf_imux);
+                    }
+                }
+            }
+        }
+
+        for (mc, mcc) in fbc.mcs.iter().enumerate() {
+            let nodes = mcs[fb][mc];
+            let del_pt = |pt: usize, sum_hp: bool| {
+                if mcc.pt_hp[pt] && sum_hp {
+                    del_pt_d_hp
+                } else {
+                    del_pt_d_lp
+                }
+            };
+            for pt in 0..PTS_PER_MC {
+                let mut dsts = vec![];
+                match mcc.pt_alloc[pt] {
+                    PtAlloc::None => (),
+                    PtAlloc::Sum => dsts.push((nodes.d, del_pt(pt, mcc.sum_hp))),
+                    PtAlloc::Export => {
+                        dsts.push((nodes.exp, del_pt(pt, mcc.sum_hp).then(del_exp_d)))
+                    }
+                    PtAlloc::Special => match pt {
+                        0 if mcc.clk == ClkSource::Pt => dsts.push((nodes.clk, del_pt_clk)),
+                        1 if mcc.oe == OeSource::Pt => dsts.push((nodes.oe, del_pt_oe)),
+                        2 | 3 if mcc.ce == Some(pt) => {
+                            dsts.push((nodes.ce, del_pt_ce.unwrap()));
+                        }
+                        2 if !mcc.rst_fsr => {
+                            dsts.push((nodes.sr, del_pt_sr.unwrap_or_else(|| del_pt(pt, true))))
+                        }
+                        3 if !mcc.set_fsr => {
+                            dsts.push((nodes.sr, del_pt_sr.unwrap_or_else(|| del_pt(pt, true))))
+                        }
+                        4 => dsts.push((nodes.d, del_pt(pt, true))),
+                        _ => (),
+                    },
+                }
+                for (dst, del) in dsts {
+                    for &(im, _) in &fbc.pts[mc][pt] {
+                        mt.graph.add_edge(imux[im], dst, del);
+                    }
+                }
+            }
+
+            // the export chain
+            let up = (mc + MCS_PER_FB - 1) % MCS_PER_FB;
+            let down = (mc + 1) % MCS_PER_FB;
+            if fbc.mcs[up].export_up && (fbc.export_enable || up != 0) {
+                if mcc.import_up_sum {
+                    mt.graph.add_edge(mcs[fb][up].exp, nodes.d, EdgeDelay::ZERO);
+                } else {
+                    mt.graph.add_edge(mcs[fb][up].exp, nodes.exp, del_exp_exp);
+                }
+            }
+            if !fbc.mcs[down].export_up {
+                if mcc.import_down_sum {
+                    mt.graph
+                        .add_edge(mcs[fb][down].exp, nodes.d, EdgeDelay::ZERO);
+                } else {
+                    mt.graph.add_edge(mcs[fb][down].exp, nodes.exp, del_exp_exp);
+                }
+            }
+
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            if mcc.out_ff {
+                mt.add_register(
+                    crd.to_string(),
+                    RegisterNodes {
+                        clk: nodes.clk,
+                        d: nodes.d,
+                        ce: mcc.ce.map(|_| nodes.ce),
+                        sr: nodes.sr,
+                        q: nodes.q,
+                    },
+                    if mcc.clk_inv {
+                        ClockEdge::Falling
+                    } else {
+                        ClockEdge::Rising
+                    },
+                    "SETUPHOLD_D_CLK",
+                    if mcc.clk == ClkSource::Pt {
+                        "WIDTH_CLK_PT"
+                    } else {
+                        "WIDTH_CLK"
+                    },
+                )?;
+                mt.graph.add_edge(nodes.q, nodes.out, EdgeDelay::ZERO);
+            } else {
+                mt.graph
+                    .add_edge(nodes.d, nodes.out, speed.delay("DEL_D_Q_COMB")?);
+            }
+
+            if mcc.iob_gnd || mcc.iob_oe == OeMux::Gnd || !cfg.done {
+                continue;
+            }
+            let Some(&pin) = pad_pins.get(&crd) else {
+                continue;
+            };
+            mt.add_output_pad(
+                format!("{crd}.O"),
+                pin,
+                nodes.out,
+                mcc.slew_fast,
+                "",
+                (mcc.iob_oe == OeMux::Oe).then_some((nodes.oe, EdgeDelay::ZERO)),
+            )?;
+        }
+    }
+    Ok(mt.graph)
+}
@@ -0,0 +1,17 @@
+#![cfg(feature = "serde")]
+
+use prjcombine_xc9500::Database;
+
+#[test]
+fn json_roundtrip() {
+    for target in ["xc9500", "xc9500xl", "xc9500xv"] {
+        let db = Database::from_file(format!(
+            "{dir}/../../databases/{target}.zstd",
+            dir = env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let json = serde_json::to_string(&db).unwrap();
+        let new: Database = serde_json::from_str(&json).unwrap();
+        assert!(new == db, "{target}");
+    }
+}
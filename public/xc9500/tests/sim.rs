@@ -0,0 +1,249 @@
+use prjcombine_jed::{JedFile, JedParserOptions, TestValue, TestVectorError};
+use prjcombine_types::cpld::design::{
+    Design, Input, Literal, Macrocell, Output, Placement, ProductTerm, Register, RegisterKind,
+    Signal,
+};
+use prjcombine_xc9500::{Database, bitstream::Bitstream, compile::compile, sim::Simulator};
+
+const PACKAGE: &str = "pc44";
+const INPUTS: [&str; 7] = ["clk", "d", "r", "s", "g", "h", "e"];
+const TARGETS: [(&str, &str); 2] = [("xc9500", "xc9536"), ("xc9500xl", "xc9536xl")];
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+fn term(lits: &[(&str, bool)]) -> ProductTerm {
+    ProductTerm::new(lits.iter().map(|&(name, inv)| Literal {
+        signal: Signal::Input(name.to_string()),
+        inv,
+    }))
+}
+
+fn reg(kind: RegisterKind, clk: ProductTerm, init: bool) -> Register {
+    Register {
+        kind,
+        clk,
+        clk_inv: false,
+        ce: None,
+        rst: None,
+        set: None,
+        init,
+    }
+}
+
+// `dff` has a single-literal reset and a product term set, `tff` starts out set,
+// `ptclk` is clocked by a product term, and `oe` / `oept` have a single-literal and
+// a product term output enable.
+fn register_design() -> Design {
+    let mut design = Design::default();
+    for name in INPUTS {
+        design.inputs.insert(name.to_string(), Input::default());
+    }
+    let mut add = |name: &str, reg: Option<Register>, oe: Option<ProductTerm>| {
+        design.macrocells.insert(
+            name.to_string(),
+            Macrocell {
+                sum: vec![term(&[("d", false)])],
+                reg,
+                output: Some(Output {
+                    oe,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+    };
+    add(
+        "dff",
+        Some(Register {
+            rst: Some(term(&[("r", false)])),
+            set: Some(term(&[("s", false), ("h", false)])),
+            ..reg(RegisterKind::Dff, term(&[("clk", false)]), false)
+        }),
+        None,
+    );
+    add(
+        "tff",
+        Some(reg(RegisterKind::Tff, term(&[("clk", false)]), true)),
+        None,
+    );
+    add(
+        "ptclk",
+        Some(reg(
+            RegisterKind::Dff,
+            term(&[("g", false), ("h", false)]),
+            false,
+        )),
+        None,
+    );
+    add("oe", None, Some(term(&[("e", false)])));
+    add("oept", None, Some(term(&[("e", false), ("h", true)])));
+    design
+}
+
+struct Bench {
+    sim: Simulator,
+    placement: Placement,
+}
+
+impl Bench {
+    fn set(&mut self, name: &str, val: bool) {
+        self.sim.set_pin(&self.placement.pins[name], Some(val));
+        self.sim.settle();
+    }
+
+    fn pulse(&mut self, name: &str) {
+        self.set(name, true);
+        self.set(name, false);
+    }
+
+    fn get(&self, name: &str) -> Option<bool> {
+        self.sim.get_pin(&self.placement.pins[name])
+    }
+}
+
+#[test]
+fn sim_registers() {
+    for (target, device) in TARGETS {
+        let db = Database::from_file(db_path(target)).unwrap();
+        let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+        let chip = &db.chips[dev.chip];
+        let bond = &db.bonds[dev.bonds[PACKAGE]];
+        let (jed, placement) = compile(&db, device, PACKAGE, &register_design()).unwrap();
+        let bs = Bitstream::from_jed(&jed, chip);
+        let mut b = Bench {
+            sim: Simulator::new(&db, chip, bond, &bs),
+            placement,
+        };
+        for name in INPUTS {
+            b.sim.set_pin(&b.placement.pins[name], Some(false));
+        }
+        b.sim.settle();
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        assert_eq!(b.get("tff"), Some(true), "{device}");
+        assert_eq!(b.get("ptclk"), Some(false), "{device}");
+
+        // D and T flip-flops on the global clock
+        b.set("d", true);
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        b.pulse("clk");
+        assert_eq!(b.get("dff"), Some(true), "{device}");
+        assert_eq!(b.get("tff"), Some(false), "{device}");
+        b.set("d", false);
+        b.pulse("clk");
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        assert_eq!(b.get("tff"), Some(false), "{device}");
+        b.set("d", true);
+        b.pulse("clk");
+        assert_eq!(b.get("tff"), Some(true), "{device}");
+
+        // asynchronous reset wins over the clock; the set needs its whole product term
+        b.set("r", true);
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        b.pulse("clk");
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        b.set("r", false);
+        b.set("s", true);
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+        b.set("h", true);
+        assert_eq!(b.get("dff"), Some(true), "{device}");
+        b.set("s", false);
+        b.set("h", false);
+        b.set("d", false);
+        b.pulse("clk");
+        assert_eq!(b.get("dff"), Some(false), "{device}");
+
+        // product term clock: only the rising edge of g & h captures d
+        b.set("d", true);
+        b.set("g", true);
+        assert_eq!(b.get("ptclk"), Some(false), "{device}");
+        b.set("h", true);
+        assert_eq!(b.get("ptclk"), Some(true), "{device}");
+        b.set("d", false);
+        assert_eq!(b.get("ptclk"), Some(true), "{device}");
+        b.set("h", false);
+        b.set("h", true);
+        assert_eq!(b.get("ptclk"), Some(false), "{device}");
+        b.set("g", false);
+        b.set("h", false);
+
+        // output enables
+        b.set("d", true);
+        assert_eq!(b.get("oe"), None, "{device}");
+        assert_eq!(b.get("oept"), None, "{device}");
+        b.set("e", true);
+        assert_eq!(b.get("oe"), Some(true), "{device}");
+        assert_eq!(b.get("oept"), Some(true), "{device}");
+        b.set("h", true);
+        assert_eq!(b.get("oe"), Some(true), "{device}");
+        assert_eq!(b.get("oept"), None, "{device}");
+        b.set("d", false);
+        assert_eq!(b.get("oe"), Some(false), "{device}");
+    }
+}
+
+fn pin_number(placement: &Placement, name: &str) -> u32 {
+    placement.pins[name]
+        .strip_prefix('P')
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+fn vector(values: &str) -> Vec<TestValue> {
+    values
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| TestValue::from_char(c).unwrap())
+        .collect()
+}
+
+#[test]
+fn sim_vectors() {
+    let (target, device) = TARGETS[0];
+    let db = Database::from_file(db_path(target)).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    let (jed, placement) = compile(&db, device, PACKAGE, &register_design()).unwrap();
+    // inputs clk d r s g h e, then outputs dff tff oe oept
+    let mut jed = jed
+        .with_vector(vector("0 0 0 0 0 0 0 L   H   Z  Z"))
+        .with_vector(vector("C 1 0 0 0 0 0 H   L   Z  Z"))
+        .with_vector(vector("C 1 1 0 0 0 1 L   H   H  H"))
+        .with_vector(vector("X 0 X 1 0 1 1 H   N   L  Z"))
+        .with_vector(vector("C 0 0 0 0 0 0 L   H   Z  Z"));
+    jed.pins = Some(
+        [
+            "clk", "d", "r", "s", "g", "h", "e", "dff", "tff", "oe", "oept",
+        ]
+        .map(|name| pin_number(&placement, name))
+        .to_vec(),
+    );
+    jed.default_test_condition = Some(false);
+
+    // the V fields survive the JED file round trip
+    let jed = JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap();
+    assert_eq!(jed.vectors.len(), 5);
+    let bs = Bitstream::from_jed(&jed, chip);
+    let mut sim = Simulator::new(&db, chip, bond, &bs);
+    jed.run_vectors(&mut sim).unwrap();
+
+    // a wrong expectation is reported with its vector and pin
+    let mut bad = jed.clone();
+    bad.vectors[1].values[8] = TestValue::ExpectHigh;
+    let mut sim = Simulator::new(&db, chip, bond, &bs);
+    assert_eq!(
+        bad.run_vectors(&mut sim),
+        Err(TestVectorError::Mismatch {
+            vector: 2,
+            pin: pin_number(&placement, "tff"),
+            expected: TestValue::ExpectHigh,
+            found: Some(false),
+        })
+    );
+}
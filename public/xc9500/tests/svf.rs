@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagOp, JtagProgram, ProgramError, ProgramOptions};
+use prjcombine_types::bitvec::BitVec;
+use prjcombine_xc9500::{
+    Chip, Database,
+    svf::{jed_fuse_count, program},
+};
+
+const IR_ISPEN: u64 = 0xe8;
+const IR_FPGM: u64 = 0xea;
+const IR_FBULK: u64 = 0xed;
+const IR_FVFY: u64 = 0xee;
+const IR_ISPEX: u64 = 0xf0;
+const IR_IDCODE: u64 = 0xfe;
+const IR_BYPASS: u64 = 0xff;
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+fn get_chip<'a>(db: &'a Database, device: &str) -> &'a Chip {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    &db.chips[dev.chip]
+}
+
+fn to_u64(bits: &BitVec) -> u64 {
+    bits.iter()
+        .enumerate()
+        .map(|(i, bit)| u64::from(bit) << i)
+        .sum()
+}
+
+fn test_fuses(len: usize) -> BitVec {
+    (0..len).map(|i| (i * 7 + i / 13) % 3 != 0).collect()
+}
+
+/// Returns every op along with the instruction in effect.
+fn ops_by_ir(prog: &JtagProgram) -> Vec<(u64, &JtagOp)> {
+    let mut ir = None;
+    let mut res = vec![];
+    for op in &prog.ops {
+        if let JtagOp::ShiftIr(val) = op {
+            ir = Some(to_u64(val));
+        }
+        res.push((ir.unwrap_or_default(), op));
+    }
+    res
+}
+
+fn ir_sequence(prog: &JtagProgram) -> Vec<u64> {
+    prog.ops
+        .iter()
+        .filter_map(|op| match op {
+            JtagOp::ShiftIr(val) => {
+                assert_eq!(val.len(), 8);
+                Some(to_u64(val))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the data last programmed at every address, as (control, data) pairs.
+fn programmed_words(prog: &JtagProgram, data_len: usize) -> BTreeMap<u32, (u64, BitVec)> {
+    let mut passes: Vec<Vec<&BitVec>> = vec![];
+    let mut in_pass = false;
+    for (ir, op) in ops_by_ir(prog) {
+        match op {
+            JtagOp::ShiftIr(_) => {
+                in_pass = ir == IR_FPGM;
+                if in_pass {
+                    passes.push(vec![]);
+                }
+            }
+            JtagOp::ShiftDr(shift) if in_pass => passes.last_mut().unwrap().push(&shift.tdi),
+            _ => (),
+        }
+    }
+    let mut res = BTreeMap::new();
+    for mut pass in passes {
+        // the final shift of a pass only collects the status of the last operation
+        pass.pop();
+        for tdi in pass {
+            let ctrl = to_u64(&tdi.slice(..2));
+            let data = tdi.slice(2..2 + data_len);
+            let addr = to_u64(&tdi.slice(2 + data_len..));
+            res.insert(addr.try_into().unwrap(), (ctrl, data));
+        }
+    }
+    res
+}
+
+#[test]
+fn svf_sequence() {
+    for (target, device, num_erase) in [("xc9500", "xc9536", 2), ("xc9500xl", "xc9536xl", 1)] {
+        let db = Database::from_file(db_path(target)).unwrap();
+        let chip = get_chip(&db, device);
+        let jed = JedFile::new().with_fuses(test_fuses(jed_fuse_count(chip)));
+        let prog = program(&db, device, &jed, &ProgramOptions::default()).unwrap();
+        assert_eq!(
+            ir_sequence(&prog),
+            [
+                IR_IDCODE, IR_ISPEN, IR_FBULK, IR_ISPEX, IR_ISPEN, IR_FPGM, IR_FVFY, IR_FPGM,
+                IR_ISPEX, IR_BYPASS
+            ],
+            "{device}"
+        );
+
+        let mut erases = 0;
+        let mut programs = 0;
+        for (ir, op) in ops_by_ir(&prog) {
+            let &JtagOp::RunTest { tck, usecs } = op else {
+                continue;
+            };
+            assert_eq!(tck, 1, "{device}");
+            match ir {
+                IR_FBULK => {
+                    assert_eq!(usecs, chip.erase_time, "{device}");
+                    erases += 1;
+                }
+                IR_FPGM => {
+                    assert_eq!(usecs, chip.program_time, "{device}");
+                    programs += 1;
+                }
+                IR_ISPEN | IR_FVFY => assert_eq!(usecs, 0, "{device}"),
+                IR_ISPEX => assert_eq!(usecs, 100, "{device}"),
+                _ => panic!("{device}: unexpected RUNTEST under IR {ir:02x}"),
+            }
+        }
+        assert_eq!(erases, num_erase, "{device}");
+        assert_ne!(programs, 0, "{device}");
+
+        let svf = prog.emit_svf();
+        let lines: Vec<_> = svf.lines().skip(5).take(6).collect();
+        assert_eq!(
+            lines,
+            [
+                format!("// device {device}"),
+                "// check IDCODE".to_string(),
+                "SIR 8 TDI (fe);".to_string(),
+                format!(
+                    "SDR 32 TDI (00000000) TDO ({idcode:08x}) MASK (0fffffff);",
+                    idcode = chip.idcode
+                ),
+                "// enter ISP mode".to_string(),
+                "SIR 8 TDI (e8);".to_string(),
+            ]
+        );
+        let erase_line = format!(
+            "RUNTEST 1 TCK {secs:.2E} SEC;",
+            secs = f64::from(chip.erase_time) / 1e6
+        );
+        assert!(svf.lines().any(|line| line == erase_line), "{device}");
+    }
+}
+
+#[test]
+fn xsvf_encoding() {
+    let db = Database::from_file(db_path("xc9500")).unwrap();
+    let chip = get_chip(&db, "xc9536");
+    assert_eq!(chip.blocks, 2);
+    let jed = JedFile::new().with_fuses(test_fuses(jed_fuse_count(chip)));
+    let xsvf = program(&db, "xc9536", &jed, &ProgramOptions::default())
+        .unwrap()
+        .emit_xsvf();
+    let mut expected = vec![
+        0x12, 0x00, 0x12, 0x01, 0x13, 0x00, 0x14, 0x00, 0x07, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+    ];
+    expected.push(0x16);
+    expected.extend(b"device xc9536\0");
+    expected.push(0x16);
+    expected.extend(b"check IDCODE\0");
+    expected.extend([0x02, 8, 0xfe]);
+    expected.extend([0x08, 0, 0, 0, 32]);
+    expected.extend([0x01, 0x0f, 0xff, 0xff, 0xff]);
+    expected.extend([0x09, 0, 0, 0, 0]);
+    expected.extend(chip.idcode.to_be_bytes());
+    expected.push(0x16);
+    expected.extend(b"enter ISP mode\0");
+    expected.extend([0x02, 8, 0xe8]);
+    // the ISPEN register has one enable bit per block, plus four
+    expected.extend([0x08, 0, 0, 0, 6]);
+    expected.extend([0x01, 0x00]);
+    expected.extend([0x09, 0x07, 0x00]);
+    expected.extend([0x17, 0x01, 0x01, 0, 0, 0, 1]);
+    assert_eq!(xsvf[..expected.len()], expected);
+    assert_eq!(xsvf.last(), Some(&0x00));
+}
+
+#[test]
+fn xc9500_words() {
+    let db = Database::from_file(db_path("xc9500")).unwrap();
+    let chip = get_chip(&db, "xc9536");
+    let fuses = test_fuses(jed_fuse_count(chip));
+    let jed = JedFile::new().with_fuses(fuses.clone());
+    let options = ProgramOptions {
+        erase: false,
+        verify: false,
+    };
+    let prog = program(&db, "xc9536", &jed, &options).unwrap();
+    let words = programmed_words(&prog, 8);
+
+    // main area: 72 rows of 15 columns per block, the last 6 columns 6 bits wide;
+    // then the UIM area: 18 rows of 5 columns per block pair, the last 4 columns 7 bits
+    // wide
+    let mut addrs = vec![];
+    for fb in 0..chip.blocks as u32 {
+        for row in 0..72 {
+            for col in 0..15 {
+                let sz = if col < 9 { 8 } else { 6 };
+                addrs.push((fb << 13 | row << 5 | col / 5 << 3 | col % 5, sz));
+            }
+        }
+        for sfb in 0..chip.blocks as u32 {
+            for row in 0..18 {
+                for col in 0..5 {
+                    let sz = if col == 0 { 8 } else { 7 };
+                    addrs.push((fb << 13 | 1 << 12 | sfb << 8 | row << 3 | col, sz));
+                }
+            }
+        }
+    }
+    assert_eq!(words.len(), addrs.len());
+    let mut unpacked = BitVec::new();
+    for (addr, sz) in addrs {
+        let (ctrl, ref data) = words[&addr];
+        assert_eq!(ctrl, 0b10, "{addr:05x}");
+        unpacked.extend(data.slice(..sz).iter());
+        assert!(data.slice(sz..).all(), "{addr:05x}");
+    }
+    assert_eq!(unpacked, fuses);
+}
+
+#[test]
+fn xc9500xl_words() {
+    let db = Database::from_file(db_path("xc9500xl")).unwrap();
+    let chip = get_chip(&db, "xc9536xl");
+    let fuses = test_fuses(jed_fuse_count(chip));
+    let jed = JedFile::new().with_fuses(fuses.clone());
+    let options = ProgramOptions {
+        erase: false,
+        verify: false,
+    };
+    let prog = program(&db, "xc9536xl", &jed, &options).unwrap();
+    let words = programmed_words(&prog, 8 * chip.blocks);
+
+    // 108 rows of 15 columns, each word holding one byte per block
+    assert_eq!(words.len(), 108 * 15);
+    let mut unpacked = BitVec::new();
+    for row in 0..108 {
+        for col in 0..15 {
+            let sz = if col < 9 { 8 } else { 6 };
+            let addr = row << 5 | col / 5 << 3 | col % 5;
+            let (ctrl, ref data) = words[&addr];
+            // the last word of a row triggers the programming of the whole row
+            assert_eq!(ctrl, if col == 14 { 0b11 } else { 0b01 }, "{addr:04x}");
+            for fb in 0..chip.blocks {
+                unpacked.extend(data.slice(fb * 8..fb * 8 + sz).iter());
+                assert!(!data.slice(fb * 8 + sz..fb * 8 + 8).any(), "{addr:04x}");
+            }
+        }
+    }
+    assert_eq!(unpacked, fuses);
+}
+
+#[test]
+fn svf_errors() {
+    let db = Database::from_file(db_path("xc9500")).unwrap();
+    let chip = get_chip(&db, "xc9536");
+    let options = ProgramOptions::default();
+    let count = jed_fuse_count(chip);
+    let jed = JedFile::new().with_fuses(test_fuses(count));
+    assert!(matches!(
+        program(&db, "xc9999", &jed, &options),
+        Err(ProgramError::UnknownDevice(ref device)) if device == "xc9999"
+    ));
+    assert!(matches!(
+        program(&db, "xc9536", &JedFile::new(), &options),
+        Err(ProgramError::MissingFuses)
+    ));
+    let jed = JedFile::new().with_fuses(test_fuses(count + 1));
+    assert!(matches!(
+        program(&db, "xc9536", &jed, &options),
+        Err(ProgramError::FuseCountMismatch { expected, got }) if expected == count && got == count + 1
+    ));
+}
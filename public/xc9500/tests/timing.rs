@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_types::{
+    cpld::design::{
+        Design, Input, Literal, Macrocell, Output, ProductTerm, Register, RegisterKind, Signal,
+        Slew,
+    },
+    speed::{EdgeArrival, Speed},
+    units::{Scalar, Time},
+};
+use prjcombine_xc9500::{Database, bitstream::Bitstream, compile::compile, timing::timing_graph};
+
+const PACKAGE: &str = "pc44";
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+fn term(lits: &[(Signal, bool)]) -> ProductTerm {
+    ProductTerm::new(lits.iter().map(|(signal, inv)| Literal {
+        signal: signal.clone(),
+        inv: *inv,
+    }))
+}
+
+fn input(name: &str) -> Signal {
+    Signal::Input(name.to_string())
+}
+
+// A two-input AND gate, and a D flip-flop on a global clock.  On the XL families, the
+// flip-flop also gets a product term clock enable and a two-literal product term reset,
+// which keeps the reset off the global set/reset network.
+fn design(xl: bool) -> Design {
+    let mut design = Design::default();
+    for name in ["a", "b", "c", "d", "clk"] {
+        design.inputs.insert(name.to_string(), Input::default());
+    }
+    design.macrocells.insert(
+        "comb".to_string(),
+        Macrocell {
+            sum: vec![term(&[(input("a"), false), (input("b"), false)])],
+            output: Some(Output {
+                slew: Slew::Fast,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    design.macrocells.insert(
+        "q".to_string(),
+        Macrocell {
+            sum: vec![term(&[(input("a"), false)])],
+            reg: Some(Register {
+                kind: RegisterKind::Dff,
+                clk: term(&[(input("clk"), false)]),
+                clk_inv: false,
+                ce: xl.then(|| term(&[(input("b"), false)])),
+                rst: xl.then(|| term(&[(input("c"), false), (input("d"), false)])),
+                set: None,
+                init: false,
+            }),
+            output: Some(Output {
+                slew: Slew::Fast,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    design
+}
+
+fn ns(val: f64) -> Time {
+    Time(Scalar(val * 1000.0))
+}
+
+fn delay(speed: &Speed, name: &str) -> Time {
+    speed.delay(name).unwrap().rise_to_rise.unwrap().max
+}
+
+// The rising and falling edges take the same time through all of these paths.
+fn arrival(report: &BTreeMap<(String, String), EdgeArrival>, src: &str, dst: &str) -> Time {
+    let arrival = report[&(src.to_string(), dst.to_string())];
+    assert_eq!(arrival.rise, arrival.fall, "{src} -> {dst}");
+    let range = arrival.rise.unwrap();
+    assert_eq!(range.min, range.max, "{src} -> {dst}");
+    range.max
+}
+
+// The data sheet tPD, tSU and tCO of the fastest speed grade.
+#[test]
+fn timing_data_sheet() {
+    for (target, device, tpd, tsu, tco) in [
+        ("xc9500", "xc9536", 5.0, 3.5, 4.0),
+        ("xc9500xl", "xc9536xl", 5.0, 3.7, 3.5),
+        ("xc9500xv", "xc9536xv", 5.0, 3.5, 3.5),
+    ] {
+        let db = Database::from_file(db_path(target)).unwrap();
+        let is_xl = target != "xc9500";
+        let design = design(is_xl);
+        let (jed, placement) = compile(&db, device, PACKAGE, &design).unwrap();
+        let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+        let chip = &db.chips[dev.chip];
+        let bond = &db.bonds[dev.bonds[PACKAGE]];
+        let speed = &db.speeds[dev.speeds["-5"]];
+        let jed = JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap();
+        let bs = Bitstream::from_jed(&jed, chip);
+        let report = timing_graph(&db, chip, bond, &bs, speed).unwrap().analyze();
+        let pin = |name: &str| placement.pins[name].as_str();
+
+        assert_eq!(
+            arrival(&report.pin_to_pin, pin("a"), pin("comb")),
+            ns(tpd),
+            "{device}"
+        );
+        assert_eq!(
+            arrival(&report.clock_to_out, pin("clk"), pin("q")),
+            ns(tco),
+            "{device}"
+        );
+        let sh = report.setup_hold[&(pin("a").to_string(), pin("clk").to_string())];
+        assert_eq!(sh.rise_setup, ns(tsu), "{device}");
+        assert!(report.fmax(pin("clk")).is_some(), "{device}");
+
+        if is_xl {
+            // the product term reset goes through DEL_IMUX_PT_SR
+            assert_eq!(
+                arrival(&report.pin_to_pin, pin("c"), pin("q")),
+                delay(speed, "DEL_IBUF_IMUX")
+                    + delay(speed, "DEL_IMUX_PT_SR")
+                    + delay(speed, "DEL_SR_Q")
+                    + delay(speed, "DEL_OBUF_FAST"),
+                "{device}"
+            );
+            // the product term clock enable goes through DEL_IMUX_PT_CE
+            let sh = report.setup_hold[&(pin("b").to_string(), pin("clk").to_string())];
+            assert_eq!(
+                sh.rise_setup,
+                delay(speed, "DEL_IBUF_IMUX")
+                    + delay(speed, "DEL_IMUX_PT_CE")
+                    + speed.setup_hold("SETUPHOLD_CE_CLK").unwrap().rise_setup
+                    - delay(speed, "DEL_IBUF_FCLK"),
+                "{device}"
+            );
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Frame ECC of Virtex 4 to Virtex 7.  Every frame stores an extended Hamming code of its
+//! contents in the middle word; bits masked on readback (see [`FrameMaskMode`]) are left
+//! out of it.
+
+use std::ops::Range;
+
+use prjcombine_interconnect::grid::DieId;
+
+use crate::error::BitstreamError;
+use crate::{Bitstream, DeviceKind, DieBitstream, FrameAddr, FrameMaskMode};
+
+fn addr_name(frame: FrameAddr) -> String {
+    format!(
+        "{typ}.{region}.{major}.{minor}",
+        typ = frame.typ,
+        region = frame.region,
+        major = frame.major,
+        minor = frame.minor
+    )
+}
+
+fn ecc_mismatch(die: DieId, frame: FrameAddr, expected: u32, actual: u32) -> BitstreamError {
+    BitstreamError::EccMismatch {
+        frame: format!("{die}.{addr}", addr = addr_name(frame)),
+        expected,
+        actual,
+    }
+}
+
+fn unsupported_mask(frame: FrameAddr, mask: FrameMaskMode) -> BitstreamError {
+    BitstreamError::Unsupported {
+        offset: 0,
+        reason: format!(
+            "frame mask mode {mask:?} in ECC computation of frame {addr}",
+            addr = addr_name(frame)
+        ),
+    }
+}
+
+fn virtex4_frame_ecc(dbs: &DieBitstream, fi: usize) -> Result<(u32, u32), BitstreamError> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    let mut recc: u32 = 0;
+    let flip = finfo.addr.region < 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x280 if !flip => finfo.mask_mode[idx / 0x140],
+            0..0x280 if flip => finfo.mask_mode[3 - idx / 0x140],
+            0x280..0x28c => {
+                recc ^= 1 << (idx - 0x280);
+                continue;
+            }
+            0x28c..0x2a0 => FrameMaskMode::None,
+            0x2a0..0x520 if !flip => finfo.mask_mode[(idx - 0x20) / 0x140],
+            0x2a0..0x520 if flip => finfo.mask_mode[3 - (idx - 0x20) / 0x140],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::BramV4 => {
+                let eidx = if flip { 0x520 - 1 - idx } else { idx };
+                let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
+                let eidx = eidx % 0x140;
+                if matches!(
+                    eidx,
+                    8 | 12
+                        | 14
+                        | 19
+                        | 21
+                        | 26
+                        | 27
+                        | 32
+                        | 35
+                        | 39
+                        | 41
+                        | 46
+                        | 48
+                        | 52
+                        | 55
+                        | 59
+                        | 61
+                        | 66
+                        | 68
+                        | 72
+                        | 74
+                        | 79
+                        | 81
+                        | 86
+                        | 88
+                        | 92
+                        | 95
+                        | 99
+                        | 101
+                        | 106
+                        | 108
+                        | 112
+                        | 114
+                        | 119
+                        | 121
+                        | 126
+                        | 200
+                        | 204
+                        | 207
+                        | 211
+                        | 213
+                        | 218
+                        | 220
+                        | 224
+                        | 227
+                        | 231
+                        | 233
+                        | 237
+                        | 240
+                        | 244
+                        | 247
+                        | 251
+                        | 253
+                        | 258
+                        | 260
+                        | 264
+                        | 266
+                        | 271
+                        | 273
+                        | 277
+                        | 280
+                        | 284
+                        | 287
+                        | 291
+                        | 293
+                        | 298
+                        | 300
+                        | 304
+                        | 306
+                        | 311
+                        | 313
+                        | 318
+                ) {
+                    continue;
+                }
+            }
+            FrameMaskMode::DrpV4 => {
+                let eidx = if flip { 0x520 - 1 - idx } else { idx };
+                let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
+                if matches!(eidx % 20, 1..17) {
+                    let midx = eidx / 20 * 20 + 18;
+                    let midx = if midx < 0x280 { midx } else { midx + 0x20 };
+                    let midx = if flip { 0x520 - 1 - midx } else { midx };
+                    if fdata[midx as usize] {
+                        continue;
+                    }
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return Err(unsupported_mask(finfo.addr, mask)),
+        }
+        let code = if idx < 0x140 {
+            0x2c0 + idx
+        } else {
+            0x420 + (idx - 0x140)
+        };
+        ecc ^= 0x800 | code;
+    }
+    for i in 0..11 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x800;
+        }
+    }
+    Ok((ecc, recc))
+}
+
+fn virtex5_frame_ecc(dbs: &DieBitstream, fi: usize) -> Result<(u32, u32), BitstreamError> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    let mut recc: u32 = 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x280 => finfo.mask_mode[0],
+            0x280..0x28c => {
+                recc ^= 1 << (idx - 0x280);
+                continue;
+            }
+            0x28c..0x2a0 => FrameMaskMode::None,
+            0x2a0..0x520 => finfo.mask_mode[1],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::DrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x280 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return Err(unsupported_mask(finfo.addr, mask)),
+        }
+        let code = if idx < 0x140 {
+            0x2c0 + idx
+        } else {
+            0x420 + (idx - 0x140)
+        };
+        ecc ^= 0x800 | code;
+    }
+    for i in 0..11 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x800;
+        }
+    }
+    Ok((ecc, recc))
+}
+
+fn virtex6_frame_ecc(dbs: &DieBitstream, fi: usize) -> Result<(u32, u32), BitstreamError> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    let mut recc: u32 = 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x500 => finfo.mask_mode[0],
+            0x500..0x50d => {
+                recc ^= 1 << (idx - 0x500);
+                continue;
+            }
+            0x50d..0x520 => FrameMaskMode::None,
+            0x520..0xa20 => finfo.mask_mode[1],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::DrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x500 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x500 + cbit]
+                    && !matches!(idx, 0..0x80 | 0x480..0x5a0 | 0x9a0..0xa20)
+                {
+                    continue;
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return Err(unsupported_mask(finfo.addr, mask)),
+        }
+        let code = if idx < 0x240 {
+            0x5c0 + idx
+        } else {
+            0x820 + (idx - 0x240)
+        };
+        ecc ^= 0x1000 | code;
+    }
+    for i in 0..12 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x1000;
+        }
+    }
+    Ok((ecc, recc))
+}
+
+fn virtex7_frame_ecc(dbs: &DieBitstream, fi: usize) -> Result<(u32, u32), BitstreamError> {
+    let fdata = dbs.frame(fi);
+    let finfo = &dbs.frame_info[fi];
+    let mut ecc: u32 = 0;
+    let mut recc: u32 = 0;
+    for (idx, bit) in fdata.iter().enumerate() {
+        if !*bit {
+            continue;
+        }
+        let mask = match idx {
+            0..0x640 => finfo.mask_mode[0],
+            0x640..0x64d => {
+                recc ^= 1 << (idx - 0x640);
+                continue;
+            }
+            0x64d..0x660 => FrameMaskMode::None,
+            0x660..0xca0 => finfo.mask_mode[1],
+            _ => unreachable!(),
+        };
+        let idx = idx as u32;
+        match mask {
+            FrameMaskMode::None => (),
+            FrameMaskMode::DrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x640 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::PcieLeftDrpHclk(cframe, cbit) => {
+                let cfi = (fi - (finfo.addr.minor as usize) + cframe)
+                    .checked_sub(28 + 2 * 36)
+                    .filter(|&cfi| dbs.frame_info[cfi].addr.minor == cframe as u32)
+                    .ok_or_else(|| BitstreamError::Malformed {
+                        offset: 0,
+                        reason: format!(
+                            "no PCIe HCLK frame for {addr}",
+                            addr = addr_name(finfo.addr)
+                        ),
+                    })?;
+                if dbs.frame(cfi)[0x640 + cbit] {
+                    continue;
+                }
+            }
+            FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
+                let cfi = fi - (finfo.addr.minor as usize) + cframe;
+                if dbs.frame(cfi)[0x640 + cbit] && matches!(idx, 0..0x600 | 0x6a0..0xca0) {
+                    continue;
+                }
+            }
+            FrameMaskMode::All => continue,
+            _ => return Err(unsupported_mask(finfo.addr, mask)),
+        }
+        let code = if idx < 0xe0 {
+            0x320 + idx
+        } else if idx < 0x4c0 {
+            0x420 + (idx - 0xe0)
+        } else {
+            0x820 + (idx - 0x4c0)
+        };
+        ecc ^= 0x1000 | code;
+    }
+    for i in 0..12 {
+        if (ecc & (1 << i)) != 0 {
+            ecc ^= 0x1000;
+        }
+    }
+    Ok((ecc, recc))
+}
+
+/// Returns the bits of a frame that hold its ECC word, for the families that have one.
+pub(crate) fn ecc_range(kind: DeviceKind) -> Option<Range<usize>> {
+    match kind {
+        DeviceKind::Virtex4 | DeviceKind::Virtex5 => Some(0x280..0x28c),
+        DeviceKind::Virtex6 => Some(0x500..0x50d),
+        DeviceKind::Virtex7 => Some(0x640..0x64d),
+        // The UltraScale ECC layout is not known.
+        _ => None,
+    }
+}
+
+// Returns the (computed, stored) ECC of a frame.
+fn frame_ecc(
+    kind: DeviceKind,
+    dbs: &DieBitstream,
+    fi: usize,
+) -> Result<(u32, u32), BitstreamError> {
+    match kind {
+        DeviceKind::Virtex4 => virtex4_frame_ecc(dbs, fi),
+        DeviceKind::Virtex5 => virtex5_frame_ecc(dbs, fi),
+        DeviceKind::Virtex6 => virtex6_frame_ecc(dbs, fi),
+        DeviceKind::Virtex7 => virtex7_frame_ecc(dbs, fi),
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the present frames whose stored ECC does not match their contents.
+pub(crate) fn check_ecc(bs: &Bitstream) -> Result<Vec<BitstreamError>, BitstreamError> {
+    let mut mismatches = vec![];
+    for (die, dbs) in &bs.die {
+        for (fi, present) in dbs.frame_present.iter().enumerate() {
+            if !*present {
+                continue;
+            }
+            let (ecc, recc) = frame_ecc(bs.kind, dbs, fi)?;
+            if ecc != recc {
+                mismatches.push(ecc_mismatch(die, dbs.frame_info[fi].addr, ecc, recc));
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Recomputes the stored ECC of every frame from its contents.
+pub(crate) fn fill_ecc(bs: &mut Bitstream) -> Result<(), BitstreamError> {
+    let Some(range) = ecc_range(bs.kind) else {
+        return Ok(());
+    };
+    let kind = bs.kind;
+    for dbs in bs.die.values_mut() {
+        for fi in 0..dbs.frame_info.len() {
+            let (ecc, _) = frame_ecc(kind, dbs, fi)?;
+            let frame = dbs.frame_mut(fi);
+            for (i, idx) in range.clone().enumerate() {
+                frame.set(idx, (ecc >> i & 1) != 0);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1131 @@
+use crate::ecc::fill_ecc;
+use crate::error::BitstreamError;
+use crate::packet::{Crc, Packet, PacketWriter};
+use crate::parse::{
+    Xc4000Crc, Xc5200Crc, spartan3a_far, spartan6_far, virtex_far, virtex4_far, virtex5_far,
+    virtex7_far,
+};
+use crate::{Bitstream, BitstreamGeom, BitstreamMode, DeviceKind, DieBitstream, GtzBitstream, Reg};
+use bitvec::prelude::*;
+use prjcombine_interconnect::dir::DirV;
+use std::collections::HashMap;
+
+fn emit_xc2000_bitstream(bs: &Bitstream) -> Vec<u8> {
+    let bs = bs.die.first().unwrap();
+    let mut data: BitVec<u8, Msb0> = BitVec::new();
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0]);
+    data.extend_from_bitslice(&BitArray::<[u8; 3], Msb0>::ZERO[..24]);
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1]);
+    for fi in 0..bs.frame_info.len() {
+        data.push(false);
+        data.extend(bs.frame(fi).iter().by_vals());
+        data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1]);
+    }
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1]);
+    while !data.len().is_multiple_of(8) {
+        data.push(true);
+    }
+    let bitlen = data.len() + 1;
+    set_bitlen(&mut data, bitlen);
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1, 1, 1, 1, 1]);
+    data.into_vec()
+}
+
+fn set_bitlen(data: &mut BitSlice<u8, Msb0>, bitlen: usize) {
+    for j in 0..24 {
+        data.set(35 - j, (bitlen >> j & 1) != 0);
+    }
+}
+
+// The check bits at the end of the final frame are computed by the emitter.  The parser
+// leaves them all set; a new bitstream has them all clear.  Anything else would be silently
+// replaced, so it is rejected.
+fn check_tail_bits(frame: &BitSlice, start: usize, offset: usize) -> Result<(), BitstreamError> {
+    let tail = &frame[start..];
+    if !tail.all() && !tail.not_any() {
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: "check bits at the end of the final frame are neither all set nor all clear"
+                .to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn emit_xc4000_bitstream(bs: &Bitstream) -> Result<Vec<u8>, BitstreamError> {
+    let bs = bs.die.first().unwrap();
+    let mut crc = Xc4000Crc::new();
+    let mut data: BitVec<u8, Msb0> = BitVec::new();
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0]);
+    data.extend_from_bitslice(&BitArray::<[u8; 3], Msb0>::ZERO[..24]);
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1]);
+    let frame_len = bs.frame_len;
+    let frames_num = bs.frame_info.len();
+    let crc_enable = !bs.frame(0)[1];
+    for fi in 0..frames_num {
+        data.push(false);
+        crc.feed_bit(fi == 0);
+        let frame = bs.frame(fi);
+        let is_last = crc_enable && fi == frames_num - 1;
+        let data_len = if is_last { frame_len - 7 } else { frame_len };
+        for i in 0..data_len {
+            data.push(frame[i]);
+            if fi == 0 && i < 2 {
+                // mirrors the parser: the first bit is fed twice.
+                crc.feed_bit(frame[0]);
+            } else {
+                crc.feed_bit(frame[i]);
+            }
+        }
+        if is_last {
+            // the last 7 bits of the final frame, together with the frame CRC,
+            // make up the final 11-bit check.
+            let (tail, raw_crc) = (0..0x80)
+                .flat_map(|tail| (0..0x10).map(move |raw_crc| (tail, raw_crc)))
+                .find(|&(tail, raw_crc)| {
+                    let mut crc = crc.clone();
+                    for i in 0..7 {
+                        crc.feed_bit((tail >> (6 - i) & 1) != 0);
+                    }
+                    for i in 0..4 {
+                        crc.feed_bit((raw_crc >> (3 - i) & 1) != 0);
+                    }
+                    (crc.crc & 0x7ff) == 0
+                })
+                .unwrap();
+            check_tail_bits(frame, data_len, data.len() / 8)?;
+            for i in 0..7 {
+                let bit = (tail >> (6 - i) & 1) != 0;
+                data.push(bit);
+                crc.feed_bit(bit);
+            }
+            for i in 0..4 {
+                let bit = (raw_crc >> (3 - i) & 1) != 0;
+                data.push(bit);
+                crc.feed_bit(bit);
+            }
+        } else if crc_enable {
+            let raw_crc = (0..0x10)
+                .find(|&raw_crc| {
+                    let mut crc = crc.clone();
+                    for i in 0..4 {
+                        crc.feed_bit((raw_crc >> (3 - i) & 1) != 0);
+                    }
+                    (crc.crc & 0xf) == 0
+                })
+                .unwrap();
+            for i in 0..4 {
+                let bit = (raw_crc >> (3 - i) & 1) != 0;
+                data.push(bit);
+                crc.feed_bit(bit);
+            }
+        } else {
+            data.extend_from_bitslice(bits![u8, Msb0; 0, 1, 1, 0]);
+        }
+    }
+    data.extend_from_bitslice(bits![u8, Msb0; 0, 1, 1, 1, 1, 1, 1, 1]);
+    while !data.len().is_multiple_of(8) {
+        data.push(true);
+    }
+    let bitlen = data.len() + 1;
+    set_bitlen(&mut data, bitlen);
+    data.extend_from_bitslice(bits![u8, Msb0; 1, 1, 1, 1, 1, 1, 1, 1]);
+    Ok(data.into_vec())
+}
+
+fn emit_xc5200_bitstream(bs: &Bitstream) -> Result<Vec<u8>, BitstreamError> {
+    let bs = bs.die.first().unwrap();
+    let mut crc = Xc5200Crc::new();
+    let mut data = vec![0xff, 0xf2, 0, 0, 0, 0xff];
+    let frame_len = bs.frame_len;
+    let frames_num = bs.frame_info.len();
+    let frame_bytes = frame_len.div_ceil(8);
+    let crc_enable = bs.frame(0)[0];
+    for fi in 0..frames_num {
+        let frame = bs.frame(fi);
+        let is_last = crc_enable && fi == frames_num - 1;
+        let mut fdata = vec![0; frame_bytes];
+        for j in 0..frame_len {
+            if is_last && j >= frame_len - 12 {
+                break;
+            }
+            if frame[j] {
+                fdata[j / 8] |= 0x80 >> (j % 8);
+            }
+        }
+        let compute_tail = |crc: &mut Xc5200Crc, fdata: &[u8]| {
+            for &b in fdata {
+                crc.feed_byte(b);
+            }
+            let fcrc = if crc_enable {
+                (!crc.crc >> 12) as u8 & 0xf
+            } else {
+                6
+            };
+            let tail = [fcrc << 4 | 0xf, 0xff, 0xff, 0xff];
+            for &b in &tail {
+                crc.feed_byte(b);
+            }
+            tail
+        };
+        crc.feed_byte(0xfe);
+        if is_last {
+            // the last 12 bits of the final frame are used to bring the CRC to 0.
+            let head = frame_bytes - 2;
+            for &b in &fdata[..head] {
+                crc.feed_byte(b);
+            }
+            let fixup = (0..0x1000)
+                .find(|&fixup: &u16| {
+                    let mut crc = crc.clone();
+                    let last = [fdata[head] | (fixup >> 8) as u8, fixup as u8];
+                    compute_tail(&mut crc, &last);
+                    crc.crc == 0
+                })
+                .unwrap();
+            fdata[head] |= (fixup >> 8) as u8;
+            fdata[head + 1] = fixup as u8;
+            check_tail_bits(frame, frame_len - 12, data.len() + 1 + head)?;
+            let tail = compute_tail(&mut crc, &fdata[head..]);
+            data.push(0xfe);
+            data.extend(&fdata);
+            data.extend(tail);
+        } else {
+            let tail = compute_tail(&mut crc, &fdata);
+            data.push(0xfe);
+            data.extend(&fdata);
+            data.extend(tail);
+        }
+    }
+    data.push(0xfe);
+    data.extend([0xff; 31]);
+    let bit_length = if bs.regs.contains_key(&Reg::FakeLcAlignmentDone) {
+        data.len() * 8 - 3
+    } else {
+        data.len() * 8 - 7
+    };
+    data[2] = (bit_length >> 16) as u8;
+    data[3] = (bit_length >> 8) as u8;
+    data[4] = bit_length as u8;
+    Ok(data)
+}
+
+fn get_reg(bs: &DieBitstream, reg: Reg) -> u32 {
+    bs.regs.get(&reg).copied().unwrap_or(0)
+}
+
+fn apply_fixups(bs: &DieBitstream, fi: usize) -> BitVec {
+    let mut frame = bs.frame(fi).to_bitvec();
+    for (&(ffi, bit), &val) in &bs.frame_fixups {
+        if ffi == fi {
+            frame.set(bit, val);
+        }
+    }
+    frame
+}
+
+fn frames_with_fixups(bs: &DieBitstream) -> Vec<usize> {
+    let mut res: Vec<_> = bs.frame_fixups.keys().map(|&(fi, _)| fi).collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+// consecutive runs of frame indices
+fn split_runs(frames: &[usize]) -> Vec<Vec<usize>> {
+    let mut res: Vec<Vec<usize>> = vec![];
+    for &fi in frames {
+        if let Some(run) = res.last_mut()
+            && *run.last().unwrap() + 1 == fi
+        {
+            run.push(fi);
+            continue;
+        }
+        res.push(vec![fi]);
+    }
+    res
+}
+
+fn encode_virtex_frame(kind: DeviceKind, frame: &BitSlice) -> Vec<u8> {
+    let frame_len = frame.len();
+    let frame_words = frame_len.div_ceil(32);
+    let mut res = vec![];
+    for i in 0..frame_words {
+        let mut bits: BitArray<u32, Lsb0> = BitArray::ZERO;
+        if i == frame_words - 1 {
+            let pad = frame_words * 32 - frame_len;
+            for j in pad..32 {
+                bits.set(j, frame[j - pad]);
+            }
+        } else {
+            let tgt = frame_len - (i + 1) * 32;
+            for j in 0..32 {
+                bits.set(j, frame[tgt + j]);
+            }
+        }
+        res.extend(bits.into_inner().to_be_bytes());
+    }
+    if kind == DeviceKind::Virtex {
+        res.extend([0; 4]);
+    }
+    res
+}
+
+fn encode_spartan3a_frame(frame: &BitSlice) -> Vec<u8> {
+    let frame_len = frame.len();
+    assert_eq!(frame_len % 16, 0);
+    let frame_words = frame_len / 16;
+    let mut res = vec![];
+    for i in 0..frame_words {
+        let mut bits: BitArray<u16, Lsb0> = BitArray::ZERO;
+        let tgt = frame_len - (i + 1) * 16;
+        for j in 0..16 {
+            bits.set(j, frame[tgt + j]);
+        }
+        res.extend(bits.into_inner().to_be_bytes());
+    }
+    res
+}
+
+fn encode_virtex4_frame(frame: &BitSlice) -> Vec<u8> {
+    let frame_words = frame.len().div_ceil(32);
+    let mut res = vec![];
+    for i in 0..frame_words {
+        let mut bits: BitArray<u32, Lsb0> = BitArray::ZERO;
+        for j in 0..32 {
+            bits.set(j, frame[i * 32 + j]);
+        }
+        res.extend(bits.into_inner().to_be_bytes());
+    }
+    res
+}
+
+fn emit_virtex_bitstream(bs: &Bitstream, mode: BitstreamMode) -> Vec<u8> {
+    let kind = bs.kind;
+    let bs = bs.die.first().unwrap();
+    let mut packets = PacketWriter::new(kind);
+    let frame_words = if kind == DeviceKind::Virtex {
+        bs.frame_len.div_ceil(32) + 1
+    } else {
+        bs.frame_len / 32
+    };
+    let frame_bytes = frame_words * 4;
+    let flr = (frame_words - 1) as u32;
+    let far = |fi: usize| virtex_far(bs.frame_info[fi].addr);
+    let encode = |fi: usize| encode_virtex_frame(kind, bs.frame(fi));
+    let early_dghigh = bs.regs.contains_key(&Reg::FakeEarlyGhigh);
+
+    packets.emit(&Packet::DummyWord);
+    packets.emit(&Packet::SyncWord);
+    if mode == BitstreamMode::Debug {
+        packets.emit(&Packet::LoutDebug(0));
+    }
+    packets.emit(&Packet::CmdRcrc);
+    if early_dghigh {
+        packets.emit(&Packet::CmdDGHigh);
+        for _ in 0..=flr {
+            packets.emit(&Packet::Nop);
+        }
+    }
+    packets.emit(&Packet::Flr(flr));
+    packets.emit(&Packet::Cor0(get_reg(bs, Reg::Cor0)));
+    if kind != DeviceKind::Virtex {
+        packets.emit(&Packet::Idcode(get_reg(bs, Reg::Idcode)));
+    }
+    packets.emit(&Packet::Mask(0xffffffff));
+    if bs.regs.contains_key(&Reg::FakeHasSwitch) {
+        packets.emit(&Packet::CmdSwitch);
+    } else {
+        packets.emit(&Packet::CmdNull);
+    }
+
+    // main loop
+    let num_frames = bs.frame_info.len();
+    match mode {
+        BitstreamMode::Debug => {
+            packets.emit(&Packet::Far(0));
+            packets.emit(&Packet::CmdWcfg);
+            for fi in 0..num_frames {
+                packets.emit(&Packet::Fdri(encode(fi)));
+                if kind == DeviceKind::Virtex {
+                    packets.emit(&Packet::Crc);
+                }
+                packets.emit(&Packet::LoutDebug(far(fi)));
+            }
+            if kind == DeviceKind::Virtex {
+                packets.emit(&Packet::Crc);
+            } else {
+                packets.emit(&Packet::Fdri(vec![0; frame_bytes]));
+            }
+        }
+        BitstreamMode::Plain => {
+            // one write per frame type; Virtex flushes the final frame separately below.
+            let mut runs: Vec<Vec<usize>> = vec![];
+            for fi in 0..num_frames {
+                if let Some(run) = runs.last_mut()
+                    && bs.frame_info[run[0]].addr.typ == bs.frame_info[fi].addr.typ
+                {
+                    run.push(fi);
+                    continue;
+                }
+                runs.push(vec![fi]);
+            }
+            for (i, run) in runs.iter().enumerate() {
+                if i == 0 {
+                    packets.emit(&Packet::CmdWcfg);
+                }
+                packets.emit(&Packet::Far(far(run[0])));
+                let mut data = vec![];
+                for &fi in run {
+                    data.extend(encode(fi));
+                }
+                if kind != DeviceKind::Virtex || i != runs.len() - 1 {
+                    data.extend(vec![0; frame_bytes]);
+                }
+                packets.emit(&Packet::Fdri(data));
+            }
+            if kind == DeviceKind::Virtex {
+                packets.emit(&Packet::Crc);
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    if kind != DeviceKind::Virtex {
+        packets.emit(&Packet::CmdGRestore);
+    }
+    if !early_dghigh {
+        packets.emit(&Packet::CmdDGHigh);
+    }
+    if kind == DeviceKind::Virtex {
+        packets.emit(&Packet::Fdri(vec![0; frame_bytes]));
+    } else {
+        if !early_dghigh {
+            if let Some(&nops) = bs.regs.get(&Reg::FakeFreezeDciNops) {
+                for _ in 0..nops {
+                    packets.emit(&Packet::Nop);
+                }
+                packets.emit(&Packet::CmdWcfg);
+                for run in split_runs(&frames_with_fixups(bs)) {
+                    packets.emit(&Packet::Far(far(run[0])));
+                    let mut data = vec![];
+                    for &fi in &run {
+                        data.extend(encode_virtex_frame(kind, &apply_fixups(bs, fi)));
+                    }
+                    data.extend(vec![0; frame_bytes]);
+                    packets.emit(&Packet::Fdri(data));
+                }
+            } else {
+                for _ in 0..=flr {
+                    packets.emit(&Packet::Nop);
+                }
+            }
+        }
+        if bs.regs.contains_key(&Reg::FakeDoubleGrestore) {
+            packets.emit(&Packet::CmdGRestore);
+        }
+    }
+
+    packets.emit(&Packet::CmdStart);
+    packets.emit(&Packet::Ctl0(get_reg(bs, Reg::Ctl0)));
+    packets.emit(&Packet::Crc);
+    if kind != DeviceKind::Virtex {
+        packets.emit(&Packet::CmdDesynch);
+    }
+    for _ in 0..4 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.finish()
+}
+
+fn emit_spartan3a_bitstream(bs: &Bitstream, mode: BitstreamMode) -> Vec<u8> {
+    let kind = bs.kind;
+    let bs = bs.die.first().unwrap();
+    let mut packets = PacketWriter::new(kind);
+    let far = |fi: usize| spartan3a_far(bs.frame_info[fi].addr);
+    let encode = |fi: usize| encode_spartan3a_frame(bs.frame(fi));
+    let frame_bytes = bs.frame_len / 8;
+
+    for _ in 0..16 {
+        packets.emit(&Packet::DummyWord);
+    }
+    packets.emit(&Packet::SyncWord);
+    if mode == BitstreamMode::Debug {
+        packets.emit(&Packet::LoutDebug(0));
+    }
+    packets.emit(&Packet::CmdRcrc);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::Cor2(get_reg(bs, Reg::Cor2)));
+    packets.emit(&Packet::CclkFrequency(get_reg(bs, Reg::CclkFrequency)));
+    packets.emit(&Packet::Flr((bs.frame_len / 16 - 1) as u32));
+    packets.emit(&Packet::Cor1(get_reg(bs, Reg::Cor1)));
+    packets.emit(&Packet::Idcode(get_reg(bs, Reg::Idcode)));
+    packets.emit(&Packet::Mask(0xffff));
+    packets.emit(&Packet::Ctl0(get_reg(bs, Reg::Ctl0)));
+    packets.emit(&Packet::Powerdown(get_reg(bs, Reg::Powerdown)));
+    packets.emit(&Packet::HcOpt(get_reg(bs, Reg::HcOpt)));
+    packets.emit(&Packet::PuGwe(get_reg(bs, Reg::PuGwe)));
+    packets.emit(&Packet::PuGts(get_reg(bs, Reg::PuGts)));
+    if bs.regs.contains_key(&Reg::Mode) {
+        packets.emit(&Packet::Mode(get_reg(bs, Reg::Mode)));
+        packets.emit(&Packet::General1(get_reg(bs, Reg::General1)));
+        packets.emit(&Packet::General2(get_reg(bs, Reg::General2)));
+    } else {
+        for _ in 0..6 {
+            packets.emit(&Packet::Nop);
+        }
+    }
+    packets.emit(&Packet::SeuOpt(get_reg(bs, Reg::SeuOpt)));
+    packets.emit(&Packet::RbCrcSw(get_reg(bs, Reg::RbCrcSw)));
+
+    // main loop
+    let num_frames = bs.frame_info.len();
+    match mode {
+        BitstreamMode::Debug => {
+            packets.emit(&Packet::Far(0));
+            packets.emit(&Packet::CmdWcfg);
+            for fi in 0..num_frames {
+                packets.emit(&Packet::Fdri(encode(fi)));
+                packets.emit(&Packet::LoutDebug(far(fi)));
+            }
+            packets.emit(&Packet::Fdri(vec![0; frame_bytes]));
+        }
+        BitstreamMode::Plain => {
+            let mut runs: Vec<Vec<usize>> = vec![];
+            for fi in 0..num_frames {
+                if let Some(run) = runs.last_mut()
+                    && bs.frame_info[run[0]].addr.typ == bs.frame_info[fi].addr.typ
+                {
+                    run.push(fi);
+                    continue;
+                }
+                runs.push(vec![fi]);
+            }
+            for (i, run) in runs.iter().enumerate() {
+                if i == 0 {
+                    packets.emit(&Packet::CmdWcfg);
+                }
+                packets.emit(&Packet::Far(far(run[0])));
+                let mut data = vec![];
+                for &fi in run {
+                    data.extend(encode(fi));
+                }
+                data.extend(vec![0; frame_bytes]);
+                packets.emit(&Packet::Fdri(data));
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    packets.emit(&Packet::Crc);
+    packets.emit(&Packet::CmdGRestore);
+    packets.emit(&Packet::CmdDGHigh);
+    for _ in 0..4 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.emit(&Packet::CmdStart);
+    packets.emit(&Packet::Mask(0xffff));
+    packets.emit(&Packet::Ctl0(get_reg(bs, Reg::Ctl0)));
+    packets.emit(&Packet::Crc);
+    packets.emit(&Packet::CmdDesynch);
+    for _ in 0..16 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.finish()
+}
+
+fn emit_spartan6_bitstream(bs: &Bitstream) -> Vec<u8> {
+    let kind = bs.kind;
+    let bs = bs.die.first().unwrap();
+    let mut packets = PacketWriter::new(kind);
+    let frame_bytes = bs.frame_len / 8;
+
+    for _ in 0..8 {
+        packets.emit(&Packet::DummyWord);
+    }
+    packets.emit(&Packet::SyncWord);
+    packets.emit(&Packet::CmdRcrc);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::Flr((bs.iob.len() / 16) as u32));
+    packets.emit(&Packet::Cor1(get_reg(bs, Reg::Cor1)));
+    packets.emit(&Packet::Cor2(get_reg(bs, Reg::Cor2)));
+    packets.emit(&Packet::Idcode(get_reg(bs, Reg::Idcode)));
+    packets.emit(&Packet::Mask(0xffff));
+    packets.emit(&Packet::Ctl0(get_reg(bs, Reg::Ctl0)));
+    for _ in 0..17 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.emit(&Packet::CclkFrequency(get_reg(bs, Reg::CclkFrequency)));
+    let double_cclk = bs.regs.contains_key(&Reg::FakeDoubleCclkFrequency);
+    if double_cclk {
+        packets.emit(&Packet::CclkFrequency(get_reg(bs, Reg::CclkFrequency)));
+    }
+    packets.emit(&Packet::Powerdown(get_reg(bs, Reg::Powerdown)));
+    packets.emit(&Packet::EyeMask(get_reg(bs, Reg::EyeMask)));
+    packets.emit(&Packet::HcOpt(get_reg(bs, Reg::HcOpt)));
+    packets.emit(&Packet::Timer(get_reg(bs, Reg::Timer)));
+    packets.emit(&Packet::PuGwe(get_reg(bs, Reg::PuGwe)));
+    packets.emit(&Packet::PuGts(get_reg(bs, Reg::PuGts)));
+    packets.emit(&Packet::Mode(get_reg(bs, Reg::Mode)));
+    packets.emit(&Packet::General1(get_reg(bs, Reg::General1)));
+    packets.emit(&Packet::General2(get_reg(bs, Reg::General2)));
+    packets.emit(&Packet::General3(get_reg(bs, Reg::General3)));
+    packets.emit(&Packet::General4(get_reg(bs, Reg::General4)));
+    packets.emit(&Packet::General5(get_reg(bs, Reg::General5)));
+    packets.emit(&Packet::SeuOpt(get_reg(bs, Reg::SeuOpt)));
+    packets.emit(&Packet::RbCrcSw(get_reg(bs, Reg::RbCrcSw)));
+    if let Some(&val) = bs.regs.get(&Reg::Testmode) {
+        packets.emit(&Packet::Testmode(val));
+    } else if !double_cclk {
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+    }
+
+    // main loop: main frames (with two pad frames at the end of every region),
+    // then BRAM frames, then the IOB frame, all in one write.
+    packets.emit(&Packet::Far(spartan6_far(bs.frame_info[0].addr)));
+    packets.emit(&Packet::CmdWcfg);
+    let mut data = vec![];
+    for fi in 0..bs.frame_info.len() {
+        data.extend(encode_spartan3a_frame(bs.frame(fi)));
+        if fi == bs.frame_info.len() - 1
+            || bs.frame_info[fi + 1].addr.region != bs.frame_info[fi].addr.region
+        {
+            data.extend(vec![0; frame_bytes * 2]);
+        }
+    }
+    for fi in 0..bs.bram_frame_info.len() {
+        data.extend(encode_spartan3a_frame(bs.bram_frame(fi)));
+    }
+    data.extend(encode_spartan3a_frame(&bs.iob));
+    data.extend([0; 2]);
+    packets.emit(&Packet::Fdri(data));
+    for _ in 0..24 {
+        packets.emit(&Packet::Nop);
+    }
+
+    for (i, run) in split_runs(&frames_with_fixups(bs)).into_iter().enumerate() {
+        packets.emit(&Packet::Far(spartan6_far(bs.frame_info[run[0]].addr)));
+        if i == 0 {
+            packets.emit(&Packet::CmdWcfg);
+        }
+        let mut data = vec![];
+        for &fi in &run {
+            data.extend(encode_spartan3a_frame(&apply_fixups(bs, fi)));
+        }
+        data.extend(vec![0; frame_bytes]);
+        packets.emit(&Packet::Fdri(data));
+    }
+
+    packets.emit(&Packet::CmdGRestore);
+    packets.emit(&Packet::CmdDGHigh);
+    for _ in 0..4 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.emit(&Packet::CmdGRestore);
+    packets.emit(&Packet::CmdStart);
+    packets.emit(&Packet::Mask(0xffff));
+    packets.emit(&Packet::Ctl0(get_reg(bs, Reg::Ctl0)));
+    packets.emit(&Packet::Crc);
+    packets.emit(&Packet::CmdDesynch);
+    for _ in 0..14 {
+        packets.emit(&Packet::Nop);
+    }
+    packets.finish()
+}
+
+fn virtex4_frame_far(kind: DeviceKind, bs: &DieBitstream, fi: usize) -> u32 {
+    let addr = bs.frame_info[fi].addr;
+    match kind {
+        DeviceKind::Virtex4 => virtex4_far(addr),
+        DeviceKind::Virtex5 | DeviceKind::Virtex6 => virtex5_far(addr),
+        DeviceKind::Virtex7 => virtex7_far(addr),
+        _ => unreachable!(),
+    }
+}
+
+// FDRI payload for a run of consecutive frames: two pad frames at the end of every
+// row, plus the final pipeline frame.
+fn virtex4_frame_run(bs: &DieBitstream, run: &[usize]) -> Vec<u8> {
+    let frame_bytes = bs.frame_len / 8;
+    let mut data = vec![];
+    for &fi in run {
+        data.extend(encode_virtex4_frame(bs.frame(fi)));
+        if fi + 1 >= bs.frame_info.len()
+            || bs.frame_info[fi + 1].addr.region != bs.frame_info[fi].addr.region
+            || bs.frame_info[fi + 1].addr.typ != bs.frame_info[fi].addr.typ
+        {
+            data.extend(vec![0; frame_bytes * 2]);
+        }
+    }
+    data.extend(vec![0; frame_bytes]);
+    data
+}
+
+fn emit_virtex4_frames_plain(packets: &mut PacketWriter, kind: DeviceKind, bs: &DieBitstream) {
+    let all: Vec<_> = (0..bs.frame_info.len()).collect();
+    packets.emit(&Packet::CmdWcfg);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::Far(virtex4_frame_far(kind, bs, 0)));
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::Fdri(virtex4_frame_run(bs, &all)));
+}
+
+fn emit_virtex4_frames_compress(packets: &mut PacketWriter, kind: DeviceKind, bs: &DieBitstream) {
+    let mut groups: HashMap<&BitSlice, Vec<usize>> = HashMap::new();
+    for fi in 0..bs.frame_info.len() {
+        groups.entry(bs.frame(fi)).or_default().push(fi);
+    }
+    let mut singles = vec![];
+    let mut multi = vec![];
+    for fi in 0..bs.frame_info.len() {
+        let group = &groups[bs.frame(fi)];
+        if group.len() == 1 {
+            singles.push(fi);
+        } else if group[0] == fi {
+            multi.push(group);
+        }
+    }
+    let far = |fi: usize| virtex4_frame_far(kind, bs, fi);
+    let mut in_wcfg = false;
+    for run in split_runs(&singles) {
+        if !in_wcfg {
+            packets.emit(&Packet::CmdWcfg);
+            packets.emit(&Packet::Nop);
+            packets.emit(&Packet::Far(far(run[0])));
+            packets.emit(&Packet::Nop);
+            in_wcfg = true;
+        } else {
+            packets.emit(&Packet::Far(far(run[0])));
+        }
+        packets.emit(&Packet::Fdri(virtex4_frame_run(bs, &run)));
+    }
+    for group in multi {
+        if !in_wcfg {
+            packets.emit(&Packet::CmdWcfg);
+            packets.emit(&Packet::Nop);
+            packets.emit(&Packet::Far(far(group[0])));
+            packets.emit(&Packet::Nop);
+        } else {
+            packets.emit(&Packet::Far(far(group[0])));
+        }
+        packets.emit(&Packet::Fdri(encode_virtex4_frame(bs.frame(group[0]))));
+        for (i, &fi) in group.iter().enumerate() {
+            packets.emit(&Packet::Far(far(fi)));
+            if i == 0 {
+                packets.emit(&Packet::CmdMfwr);
+                let num_nops = match kind {
+                    DeviceKind::Virtex4 | DeviceKind::Virtex5 => 1,
+                    DeviceKind::Virtex6 | DeviceKind::Virtex7 => 12,
+                    _ => unreachable!(),
+                };
+                for _ in 0..num_nops {
+                    packets.emit(&Packet::Nop);
+                }
+            }
+            let is_bram = bs.frame_info[fi].addr.typ == 1;
+            packets.emit(&Packet::Mfwr(match kind {
+                DeviceKind::Virtex4 => 2,
+                DeviceKind::Virtex5 | DeviceKind::Virtex6 => {
+                    if is_bram {
+                        6
+                    } else {
+                        2
+                    }
+                }
+                DeviceKind::Virtex7 => {
+                    if i == 0 {
+                        8
+                    } else {
+                        4
+                    }
+                }
+                _ => unreachable!(),
+            }));
+            if kind == DeviceKind::Virtex7 && is_bram {
+                for _ in 0..8 {
+                    packets.emit(&Packet::Nop);
+                }
+            }
+        }
+        in_wcfg = false;
+    }
+}
+
+fn emit_virtex4_bitstream(
+    bs: &Bitstream,
+    geom: &BitstreamGeom,
+    mode: BitstreamMode,
+    die_index: usize,
+) -> Vec<u8> {
+    let kind = bs.kind;
+    let die = geom.die_order[die_index];
+    let diebs = &bs.die[die];
+    let mut packets = PacketWriter::new(kind);
+    let ctl0 = get_reg(diebs, Reg::Ctl0);
+    let mut trim_regs = 0;
+
+    if kind == DeviceKind::Virtex4 {
+        packets.emit(&Packet::DummyWord);
+        packets.emit(&Packet::SyncWord);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdRcrc);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Cor0(get_reg(diebs, Reg::Cor0)));
+        packets.emit(&Packet::Idcode(get_reg(diebs, Reg::Idcode)));
+        packets.emit(&Packet::CmdSwitch);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdNull);
+        packets.emit(&Packet::Nop);
+    } else {
+        for _ in 0..8 {
+            packets.emit(&Packet::DummyWord);
+        }
+        packets.emit(&Packet::WidthDetect);
+        packets.emit(&Packet::DummyWord);
+        packets.emit(&Packet::DummyWord);
+        packets.emit(&Packet::SyncWord);
+        packets.emit(&Packet::Nop);
+        if let Some(&val) = diebs.regs.get(&Reg::Bspi) {
+            packets.emit(&Packet::Bspi(val));
+            packets.emit(&Packet::CmdBspiRead);
+            packets.emit(&Packet::Nop);
+        }
+        if kind == DeviceKind::Virtex7 {
+            packets.emit(&Packet::Timer(get_reg(diebs, Reg::Timer)));
+        }
+        packets.emit(&Packet::WBStar(get_reg(diebs, Reg::WbStar)));
+        packets.emit(&Packet::CmdNull);
+        packets.emit(&Packet::Nop);
+        if diebs.regs.contains_key(&Reg::Trim2) {
+            for (reg, cor1) in [
+                (Reg::Trim0, 0x1000),
+                (Reg::Trim1, 0x1400),
+                (Reg::Trim2, 0x1800),
+            ] {
+                if let Some(&val) = diebs.regs.get(&reg) {
+                    packets.emit(&Packet::Cor1(cor1));
+                    packets.emit(&Packet::Mask(0xffffffff));
+                    packets.emit(&Packet::Trim(val));
+                    trim_regs += 1;
+                }
+            }
+        } else {
+            if let Some(&val) = diebs.regs.get(&Reg::Trim1) {
+                packets.emit(&Packet::Mask(0xffffffff));
+                packets.emit(&Packet::Trim1(val));
+            }
+            if let Some(&val) = diebs.regs.get(&Reg::Trim0) {
+                packets.emit(&Packet::Mask(0xffffffff));
+                packets.emit(&Packet::Trim(val));
+            }
+        }
+        if kind != DeviceKind::Virtex5
+            && let Some(&val) = diebs.regs.get(&Reg::Testmode)
+        {
+            packets.emit(&Packet::Testmode(val));
+        }
+        packets.emit(&Packet::CmdRcrc);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+        if kind != DeviceKind::Virtex7 {
+            packets.emit(&Packet::Timer(get_reg(diebs, Reg::Timer)));
+        }
+        packets.emit(&Packet::RbCrcSw(get_reg(diebs, Reg::RbCrcSw)));
+        if kind == DeviceKind::Virtex5
+            && let Some(&val) = diebs.regs.get(&Reg::Testmode)
+        {
+            packets.emit(&Packet::Testmode(val));
+        }
+        packets.emit(&Packet::Cor0(get_reg(diebs, Reg::Cor0)));
+        packets.emit(&Packet::Cor1(get_reg(diebs, Reg::Cor1)));
+        packets.emit(&Packet::Idcode(get_reg(diebs, Reg::Idcode)));
+        if diebs.regs.contains_key(&Reg::FakeFallEdge) {
+            packets.emit(&Packet::CmdFallEdge);
+        }
+        packets.emit(&Packet::CmdSwitch);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Mask(0xffffffff));
+        packets.emit(&Packet::Ctl0(ctl0));
+        packets.emit(&Packet::Mask(0xffffffff));
+        packets.emit(&Packet::Ctl1(get_reg(diebs, Reg::Ctl1)));
+        for _ in 0..8 {
+            packets.emit(&Packet::Nop);
+        }
+    }
+
+    // main loop
+    match mode {
+        BitstreamMode::Plain => emit_virtex4_frames_plain(&mut packets, kind, diebs),
+        BitstreamMode::Compress => emit_virtex4_frames_compress(&mut packets, kind, diebs),
+        _ => unreachable!(),
+    }
+
+    let ignore_crc = diebs.regs.contains_key(&Reg::FakeIgnoreCrc);
+    let crc_packet = if ignore_crc {
+        Packet::CmdRcrc
+    } else {
+        Packet::Crc
+    };
+    packets.emit(&crc_packet);
+    if matches!(kind, DeviceKind::Virtex6 | DeviceKind::Virtex7) {
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+    }
+    packets.emit(&Packet::CmdGRestore);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::CmdDGHigh);
+    if kind != DeviceKind::Virtex4 && mode == BitstreamMode::Compress {
+        packets.emit(&Packet::Mask(0xffffffff));
+        packets.emit(&Packet::Ctl1(get_reg(diebs, Reg::Ctl1)));
+    }
+    for _ in 0..100 {
+        packets.emit(&Packet::Nop);
+    }
+    if matches!(kind, DeviceKind::Virtex4 | DeviceKind::Virtex5) {
+        packets.emit(&Packet::CmdGRestore);
+    }
+    let final_far = virtex4_frame_far(kind, diebs, 0);
+    if kind == DeviceKind::Virtex4 {
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdNull);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Far(final_far));
+        packets.emit(&Packet::CmdStart);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Mask(0xffffffff));
+        packets.emit(&Packet::Ctl0(ctl0));
+        packets.emit(&Packet::Crc);
+        packets.emit(&Packet::CmdDesynch);
+        for _ in 0..16 {
+            packets.emit(&Packet::Nop);
+        }
+    } else {
+        if kind == DeviceKind::Virtex5 {
+            for _ in 0..30 {
+                packets.emit(&Packet::Nop);
+            }
+        }
+        packets.emit(&Packet::CmdStart);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Far(final_far));
+        packets.emit(&Packet::Mask(0xffffffff));
+        packets.emit(&Packet::Ctl0(ctl0));
+        packets.emit(&crc_packet);
+        if matches!(kind, DeviceKind::Virtex6 | DeviceKind::Virtex7) {
+            packets.emit(&Packet::Nop);
+            packets.emit(&Packet::Nop);
+        }
+        packets.emit(&Packet::CmdDesynch);
+        let mut num_nops = match kind {
+            DeviceKind::Virtex5 => 61,
+            DeviceKind::Virtex6 | DeviceKind::Virtex7 => 400,
+            _ => unreachable!(),
+        };
+        if diebs.regs.contains_key(&Reg::Trim1) {
+            num_nops -= 4;
+        }
+        if trim_regs == 0 && diebs.regs.contains_key(&Reg::Trim0) {
+            num_nops -= 4;
+        }
+        if diebs.regs.contains_key(&Reg::Testmode) {
+            num_nops -= 2;
+        }
+        if diebs.regs.contains_key(&Reg::Bspi) {
+            num_nops -= 5;
+        }
+        if diebs.regs.contains_key(&Reg::FakeFallEdge) {
+            num_nops -= 2;
+        }
+        num_nops -= 6 * trim_regs;
+        for _ in 0..num_nops {
+            packets.emit(&Packet::Nop);
+        }
+    }
+    if die_index != geom.die_order.len() - 1 {
+        packets.emit(&Packet::SyncWord);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdShutdown);
+        packets.emit(&Packet::CmdRcrc);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Bout(emit_virtex4_bitstream(
+            bs,
+            geom,
+            mode,
+            die_index + 1,
+        )));
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdStart);
+        packets.emit(&Packet::CmdDesynch);
+    }
+    packets.finish()
+}
+
+fn emit_gtz_bitstream(gtz: &GtzBitstream) -> Vec<u32> {
+    let mut res = vec![];
+    for (idcode, seg) in [(gtz.idcode, &gtz.data), (gtz.idcode | 1 << 28, &gtz.code)] {
+        let start = res.len();
+        res.push(idcode);
+        res.push(0x00010001);
+        res.push((seg.len() + 1 + 0x20) as u32);
+        res.extend(seg);
+        let mut crc = Crc::new(DeviceKind::Virtex7);
+        for &w in &res[start..] {
+            crc.update(0, w);
+        }
+        res.push(crc.get());
+        res.extend([0; 0x20]);
+    }
+    res
+}
+
+fn emit_virtex4_shutdown(geom: &BitstreamGeom, die_index: usize, aghigh: bool) -> Vec<u8> {
+    let mut packets = PacketWriter::new(geom.kind);
+    for _ in 0..8 {
+        packets.emit(&Packet::DummyWord);
+    }
+    packets.emit(&Packet::WidthDetect);
+    packets.emit(&Packet::DummyWord);
+    packets.emit(&Packet::DummyWord);
+    packets.emit(&Packet::SyncWord);
+    packets.emit(&Packet::Nop);
+    if aghigh {
+        packets.emit(&Packet::CmdAGHigh);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+    } else {
+        packets.emit(&Packet::CmdShutdown);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdRcrc);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+    }
+    packets.emit(&Packet::CmdDesynch);
+    for _ in 0..8 {
+        packets.emit(&Packet::Nop);
+    }
+    if die_index != geom.die_order.len() - 1 {
+        packets.emit(&Packet::SyncWord);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Bout(emit_virtex4_shutdown(
+            geom,
+            die_index + 1,
+            aghigh,
+        )));
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::Nop);
+        packets.emit(&Packet::CmdDesynch);
+        for _ in 0..8 {
+            packets.emit(&Packet::Nop);
+        }
+    }
+    packets.finish()
+}
+
+fn emit_virtex7_gtz_bitstream(
+    bs: &Bitstream,
+    geom: &BitstreamGeom,
+    mode: BitstreamMode,
+) -> Vec<u8> {
+    let loader = bs.gtz_loader.as_ref().unwrap();
+    let mut res = emit_virtex4_bitstream(loader, geom, mode, 0);
+    let mut packets = PacketWriter::new(bs.kind);
+    for _ in 0..8 {
+        packets.emit(&Packet::DummyWord);
+    }
+    packets.emit(&Packet::WidthDetect);
+    packets.emit(&Packet::DummyWord);
+    packets.emit(&Packet::DummyWord);
+    packets.emit(&Packet::SyncWord);
+    packets.emit(&Packet::Nop);
+    for dir in [DirV::S, DirV::N] {
+        if let Some(gtz) = bs.gtz.get(&dir) {
+            packets.emit(&Packet::Axss(emit_gtz_bitstream(gtz)));
+        }
+    }
+    packets.emit(&Packet::Crc);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::Nop);
+    packets.emit(&Packet::CmdDesynch);
+    for _ in 0..8 {
+        packets.emit(&Packet::Nop);
+    }
+    res.extend(packets.finish());
+    res.extend(emit_virtex4_shutdown(geom, 0, false));
+    res.extend(emit_virtex4_shutdown(geom, 0, true));
+    let mut packets = PacketWriter::new(bs.kind);
+    for _ in 0..100 {
+        packets.emit(&Packet::Nop);
+    }
+    res.extend(packets.finish());
+    res.extend(emit_virtex4_bitstream(bs, geom, mode, 0));
+    res
+}
+
+/// Serializes a bitstream into the raw configuration data (without the `.bit` file header).
+///
+/// This is the inverse of [`crate::parse`]: parsing the result yields back the same
+/// frames and registers.  On Virtex 4 and later, the frame ECC words are recomputed from
+/// the frame contents.  On XC4000 and XC5200 with CRC checking enabled, the check bits at
+/// the end of the final frame are computed by the emitter; they must be all set (as left
+/// by the parser) or all clear.  Encrypted bitstreams are not supported.
+pub fn emit(
+    geom: &BitstreamGeom,
+    bs: &Bitstream,
+    mode: BitstreamMode,
+) -> Result<Vec<u8>, BitstreamError> {
+    if geom.kind != bs.kind {
+        return Err(BitstreamError::Malformed {
+            offset: 0,
+            reason: format!(
+                "{kind:?} bitstream emitted with a {geom_kind:?} geometry",
+                kind = bs.kind,
+                geom_kind = geom.kind
+            ),
+        });
+    }
+    let modes: &[BitstreamMode] = match bs.kind {
+        DeviceKind::Virtex | DeviceKind::Virtex2 | DeviceKind::Spartan3A => {
+            &[BitstreamMode::Plain, BitstreamMode::Debug]
+        }
+        DeviceKind::Virtex4 | DeviceKind::Virtex5 | DeviceKind::Virtex6 | DeviceKind::Virtex7 => {
+            &[BitstreamMode::Plain, BitstreamMode::Compress]
+        }
+        _ => &[BitstreamMode::Plain],
+    };
+    if !modes.contains(&mode) {
+        return Err(BitstreamError::Unsupported {
+            offset: 0,
+            reason: format!("bitstream mode {mode:?} for {kind:?}", kind = bs.kind),
+        });
+    }
+    Ok(match bs.kind {
+        DeviceKind::Xc2000 => emit_xc2000_bitstream(bs),
+        DeviceKind::Xc4000 | DeviceKind::S40Xl => emit_xc4000_bitstream(bs)?,
+        DeviceKind::Xc5200 => emit_xc5200_bitstream(bs)?,
+        DeviceKind::Virtex | DeviceKind::Virtex2 => emit_virtex_bitstream(bs, mode),
+        DeviceKind::Spartan3A => emit_spartan3a_bitstream(bs, mode),
+        DeviceKind::Spartan6 => emit_spartan6_bitstream(bs),
+        DeviceKind::Virtex4 | DeviceKind::Virtex5 | DeviceKind::Virtex6 | DeviceKind::Virtex7 => {
+            let mut bs = bs.clone();
+            fill_ecc(&mut bs)?;
+            if let Some(loader) = &mut bs.gtz_loader {
+                fill_ecc(loader)?;
+            }
+            if bs.gtz_loader.is_some() {
+                emit_virtex7_gtz_bitstream(&bs, geom, mode)
+            } else {
+                emit_virtex4_bitstream(&bs, geom, mode, 0)
+            }
+        }
+        DeviceKind::Ultrascale | DeviceKind::UltrascalePlus | DeviceKind::Versal => {
+            return Err(BitstreamError::Unsupported {
+                offset: 0,
+                reason: format!("emitting {kind:?} bitstreams", kind = bs.kind),
+            });
+        }
+    })
+}
@@ -0,0 +1,34 @@
+pub use prjcombine_types::bitstream::BitstreamError;
+pub(crate) use prjcombine_types::bitstream::check_len;
+
+macro_rules! ensure {
+    ($cond:expr, $offset:expr) => {
+        if !$cond {
+            return Err($crate::error::BitstreamError::Malformed {
+                offset: $offset,
+                reason: concat!("check failed: ", stringify!($cond)).to_string(),
+            });
+        }
+    };
+}
+
+macro_rules! ensure_eq {
+    ($a:expr, $b:expr, $offset:expr) => {
+        match (&$a, &$b) {
+            (a, b) => {
+                if *a != *b {
+                    return Err($crate::error::BitstreamError::Malformed {
+                        offset: $offset,
+                        reason: format!(
+                            "check failed: {} == {} ({a:?} vs {b:?})",
+                            stringify!($a),
+                            stringify!($b)
+                        ),
+                    });
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use {ensure, ensure_eq};
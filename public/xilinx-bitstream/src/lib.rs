@@ -5,9 +5,16 @@ use prjcombine_interconnect::{dir::DirV, grid::DieId};
 use prjcombine_types::bsdata::{RectBitId, RectFrameId};
 use std::collections::{BTreeMap, HashMap};
 
+mod ecc;
+mod emit;
+mod error;
 mod packet;
 mod parse;
-pub use parse::parse;
+mod pdi;
+pub use emit::emit;
+pub use error::BitstreamError;
+pub use parse::{parse, parse_strict};
+pub use pdi::{Pdi, PdiBootHeader, PdiImage, PdiPartition, PdiPartitionKind, parse_cfi, parse_pdi};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub enum Reg {
@@ -141,6 +148,30 @@ pub struct Bitstream {
 }
 
 impl Bitstream {
+    pub fn new(geom: &BitstreamGeom) -> Self {
+        Bitstream {
+            kind: geom.kind,
+            die: geom.die.map_values(|dg| DieBitstream {
+                regs: Default::default(),
+                mode: BitstreamMode::Plain,
+                iv: vec![],
+                frame_len: dg.frame_len,
+                frame_data: BitVec::repeat(false, dg.frame_len * dg.frame_info.len()),
+                frame_info: dg.frame_info.clone(),
+                frame_present: BitVec::repeat(false, dg.frame_info.len()),
+                bram_data: BitVec::repeat(false, dg.bram_frame_len * dg.bram_frame_info.len()),
+                bram_frame_present: BitVec::repeat(false, dg.bram_frame_info.len()),
+                bram_frame_len: dg.bram_frame_len,
+                bram_frame_info: dg.bram_frame_info.clone(),
+                iob: BitVec::repeat(false, dg.iob_frame_len),
+                iob_present: false,
+                frame_fixups: HashMap::new(),
+            }),
+            gtz: Default::default(),
+            gtz_loader: None,
+        }
+    }
+
     pub fn diff(a: &Bitstream, b: &Bitstream) -> HashMap<BitPos, bool> {
         assert_eq!(a.kind, b.kind);
         assert_eq!(a.die.len(), b.die.len());
@@ -197,12 +228,7 @@ impl Bitstream {
                 }
                 for j in 0..da.frame_len {
                     if fa[j] != fb[j] {
-                        let is_ecc = match a.kind {
-                            DeviceKind::Virtex4 | DeviceKind::Virtex5 => (640..652).contains(&j),
-                            DeviceKind::Virtex6 => (1280..1293).contains(&j),
-                            DeviceKind::Virtex7 => (1600..1613).contains(&j),
-                            _ => false,
-                        };
+                        let is_ecc = ecc::ecc_range(a.kind).is_some_and(|r| r.contains(&j));
                         if !is_ecc {
                             res.insert(BitPos::Main(die, i, j), fb[j]);
                         }
@@ -257,6 +283,15 @@ impl Bitstream {
         res
     }
 
+    /// Returns true for the bits of a frame ECC word.  They are derived from the rest of the
+    /// frame, and recomputed by [`emit`].
+    pub fn is_ecc_bit(&self, bit: BitPos) -> bool {
+        match bit {
+            BitPos::Main(_, _, bit) => ecc::ecc_range(self.kind).is_some_and(|r| r.contains(&bit)),
+            _ => false,
+        }
+    }
+
     pub fn get_bit(&self, bit: BitPos) -> bool {
         match bit {
             BitPos::Reg(die, reg, bit) => match self.die[die].regs.get(&reg) {
@@ -273,6 +308,44 @@ impl Bitstream {
             BitPos::Gtz(dir, frame, bit) => (self.gtz[&dir].data[frame] >> bit & 1) != 0,
         }
     }
+
+    pub fn set_bit(&mut self, bit: BitPos, val: bool) {
+        match bit {
+            BitPos::Reg(die, reg, bit) => {
+                let reg = self.die[die].regs.entry(reg).or_default();
+                if val {
+                    *reg |= 1 << bit;
+                } else {
+                    *reg &= !(1 << bit);
+                }
+            }
+            BitPos::RegPresent(die, reg) => {
+                if val {
+                    self.die[die].regs.entry(reg).or_default();
+                } else {
+                    self.die[die].regs.remove(&reg);
+                }
+            }
+            BitPos::Main(die, frame, bit) => self.die[die].frame_mut(frame).set(bit, val),
+            BitPos::Fixup(die, frame, bit) => {
+                if val {
+                    self.die[die].frame_fixups.insert((frame, bit), true);
+                } else {
+                    self.die[die].frame_fixups.remove(&(frame, bit));
+                }
+            }
+            BitPos::Bram(die, frame, bit) => self.die[die].bram_frame_mut(frame).set(bit, val),
+            BitPos::Iob(die, bit) => self.die[die].iob.set(bit, val),
+            BitPos::Gtz(dir, frame, bit) => {
+                let word = &mut self.gtz.get_mut(&dir).unwrap().data[frame];
+                if val {
+                    *word |= 1 << bit;
+                } else {
+                    *word &= !(1 << bit);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -1,3 +1,4 @@
+use crate::error::{BitstreamError, check_len, ensure, ensure_eq};
 use crate::{DeviceKind, KeyData, KeySeq};
 use aes::cipher::{KeyIvInit, inout::InOutBuf};
 use arrayref::{array_mut_ref, array_ref};
@@ -20,6 +21,10 @@ pub struct PacketParser<'a> {
     bypass_crc: bool,
     decrypted: Option<Vec<u8>>,
     orig_pos: usize,
+    start: usize,
+    strict: bool,
+    // CRC mismatches tolerated in non-strict mode
+    warnings: Vec<BitstreamError>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -85,6 +90,24 @@ pub enum Packet {
     BugFdri(Vec<u8>),
 }
 
+impl Packet {
+    pub fn describe(&self) -> String {
+        match self {
+            Packet::Cbc(data)
+            | Packet::Fdri(data)
+            | Packet::Bout(data)
+            | Packet::EncFdri(data)
+            | Packet::BugFdri(data) => {
+                let name = format!("{self:?}");
+                let name = name.split('(').next().unwrap();
+                format!("{name}({l} bytes)", l = data.len())
+            }
+            Packet::Axss(data) => format!("Axss({l} words)", l = data.len()),
+            _ => format!("{self:x?}"),
+        }
+    }
+}
+
 fn bitswap32(data: &mut [u8]) {
     for i in (0..data.len()).step_by(4) {
         let a = data[i].reverse_bits();
@@ -161,8 +184,30 @@ impl Crc {
     }
 }
 
+// In non-strict mode, mismatches are recorded as warnings instead of failing the parse.
+fn check_crc(
+    strict: bool,
+    offset: usize,
+    warnings: &mut Vec<BitstreamError>,
+    actual: u32,
+    expected: u32,
+) -> Result<(), BitstreamError> {
+    if actual != expected {
+        let err = BitstreamError::CrcMismatch {
+            offset,
+            expected,
+            actual,
+        };
+        if strict {
+            return Err(err);
+        }
+        warnings.push(err);
+    }
+    Ok(())
+}
+
 impl<'a> PacketParser<'a> {
-    pub fn new(kind: DeviceKind, data: &'a [u8], key: &'a KeyData) -> Self {
+    pub fn new(kind: DeviceKind, data: &'a [u8], key: &'a KeyData, strict: bool) -> Self {
         Self {
             kind,
             key,
@@ -178,30 +223,95 @@ impl<'a> PacketParser<'a> {
             ctl0: 0,
             decrypted: None,
             orig_pos: 0,
+            start: 0,
+            strict,
+            warnings: vec![],
         }
     }
 
-    fn decrypt_v4(&self, data: &[u8]) -> Vec<u8> {
+    fn decrypt_v4(&self, data: &[u8]) -> Result<Vec<u8>, BitstreamError> {
         let mut data = data.to_vec();
-        assert_eq!(data.len() % 16, 0);
+        ensure_eq!(data.len() % 16, 0, self.start);
         bitswap32(&mut data);
-        let mut iv: [u8; 16] = *array_ref!(self.iv.as_ref().unwrap(), 0, 16);
+        let Some(iv) = self.iv.as_ref() else {
+            return Err(self.malformed("encrypted data without IV"));
+        };
+        let mut iv: [u8; 16] = *array_ref!(iv, 0, 16);
         bitswap32(&mut iv);
         let KeyData::Aes(key) = self.key else {
-            unreachable!();
+            return Err(self.unsupported("AES-encrypted bitstream without AES key"));
         };
         let mut cipher: cbc::Decryptor<aes::Aes256> =
             cbc::Decryptor::new((&key.key).into(), (&iv).into());
         let iodata: InOutBuf<_> = (&mut data[..]).into();
         let (mut blocks, tail) = iodata.into_chunks();
-        assert!(tail.is_empty());
+        ensure!(tail.is_empty(), self.start);
         cipher.decrypt_blocks_inout_mut(blocks.reborrow());
         bitswap32(&mut data);
-        data
+        Ok(data)
+    }
+
+    pub fn malformed(&self, reason: impl Into<String>) -> BitstreamError {
+        BitstreamError::Malformed {
+            offset: self.start,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn unsupported(&self, reason: impl Into<String>) -> BitstreamError {
+        BitstreamError::Unsupported {
+            offset: self.start,
+            reason: reason.into(),
+        }
+    }
+
+    /// Takes the errors tolerated so far in non-strict mode.
+    pub fn take_warnings(&mut self) -> Vec<BitstreamError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Records errors tolerated by a nested parser.
+    pub fn add_warnings(&mut self, warnings: Vec<BitstreamError>) {
+        self.warnings.extend(warnings);
     }
 
     pub fn peek(&self) -> Option<Packet> {
-        self.clone().next()
+        self.clone().next_packet().ok().flatten()
+    }
+
+    /// Byte offset of the most recently decoded packet.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn unexpected(
+        &self,
+        expected: impl Into<String>,
+        packet: Option<Packet>,
+    ) -> BitstreamError {
+        BitstreamError::UnexpectedPacket {
+            offset: self.start,
+            expected: expected.into(),
+            packet: packet.map(|p| p.describe()),
+        }
+    }
+
+    pub fn expect(&mut self, packet: Packet) -> Result<(), BitstreamError> {
+        match self.next_packet()? {
+            Some(p) if p == packet => Ok(()),
+            p => Err(self.unexpected(packet.describe(), p)),
+        }
+    }
+
+    pub fn expect_end(&mut self) -> Result<(), BitstreamError> {
+        match self.next_packet()? {
+            None => Ok(()),
+            p => Err(self.unexpected("end of bitstream", p)),
+        }
     }
 
     pub fn pos(&self) -> usize {
@@ -213,10 +323,8 @@ impl<'a> PacketParser<'a> {
     }
 }
 
-impl Iterator for PacketParser<'_> {
-    type Item = Packet;
-
-    fn next(&mut self) -> Option<Packet> {
+impl PacketParser<'_> {
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, BitstreamError> {
         let src_data = match self.decrypted {
             Some(ref data) => {
                 if self.pos == data.len() {
@@ -229,306 +337,56 @@ impl Iterator for PacketParser<'_> {
             }
             None => self.data,
         };
-        if matches!(self.kind, DeviceKind::Spartan3A | DeviceKind::Spartan6) {
-            let is_s6 = self.kind == DeviceKind::Spartan6;
-            if self.pos + 2 > src_data.len() {
-                None
-            } else {
-                let ph = u16::from_be_bytes(*array_ref!(src_data, self.pos, 2));
-                self.pos += 2;
-                if self.sync {
-                    let prev_crc = self.crc.get();
-                    if ph == 0x2000 {
-                        Some(Packet::Nop)
-                    } else if (ph >> 11) == 6 {
-                        let reg = ph >> 5 & 0x3f;
-                        let num = (ph & 0x1f) as usize;
-                        let dpos = self.pos;
-                        self.pos += num * 2;
-                        let epos = self.pos;
-                        let get_val = |i: usize| {
-                            u16::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 2)) as u32
-                        };
-                        let get_val32 =
-                            |i: usize| u32::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 4));
-                        if !matches!(reg, 0 | 9 | 0x12) {
-                            for i in 0..num {
-                                self.crc.update(reg as u32, get_val(i));
-                            }
-                        }
-                        match (reg, num) {
-                            (0, 2) => {
-                                let val = get_val32(0);
-                                let ecrc = if self.bypass_crc {
-                                    0x9876defc
-                                } else {
-                                    self.crc.get()
-                                };
-                                if val != ecrc {
-                                    println!("CRC MISMATCH {val:08x} {ecrc:08x}");
-                                }
-                                Some(Packet::Crc)
-                            }
-                            (1, 2) => Some(Packet::Far(get_val32(0))),
-                            (5, 1) => match get_val(0) {
-                                0 => Some(Packet::CmdNull),
-                                1 => Some(Packet::CmdWcfg),
-                                2 => Some(Packet::CmdMfwr),
-                                3 => Some(Packet::CmdDGHigh),
-                                5 => Some(Packet::CmdStart),
-                                7 => {
-                                    self.crc.reset();
-                                    Some(Packet::CmdRcrc)
-                                }
-                                8 => Some(Packet::CmdAGHigh),
-                                9 => Some(Packet::CmdSwitch),
-                                10 => Some(Packet::CmdGRestore),
-                                11 => Some(Packet::CmdShutdown),
-                                13 => Some(Packet::CmdDesynch),
-                                val => panic!("unk cmd: {val}"),
-                            },
-                            (6, 1) => {
-                                let val = get_val(0);
-                                self.ctl0 = (self.ctl0 & !self.mask) | (val & self.mask);
-                                Some(Packet::Ctl0(val))
-                            }
-                            (7, 1) => {
-                                let val = get_val(0);
-                                self.mask = val;
-                                Some(Packet::Mask(val))
-                            }
-                            (9, 2) => Some(Packet::LoutDebug(get_val32(0))),
-                            (0xa, 1) => {
-                                let val = get_val(0);
-                                self.bypass_crc = (val & 0x10) != 0;
-                                Some(Packet::Cor1(val))
-                            }
-                            (0xb, 1) => Some(Packet::Cor2(get_val(0))),
-                            (0xc, 1) => Some(Packet::Powerdown(get_val(0))),
-                            (0xd, 1) => Some(Packet::Flr(get_val(0))),
-                            (0xe, 2) => Some(Packet::Idcode(get_val32(0))),
-                            (0xf, 1) if is_s6 => Some(Packet::Timer(get_val(0))),
-                            (0x10, 1) => Some(Packet::HcOpt(get_val(0))),
-                            (0x11, 1) => Some(Packet::Testmode(get_val(0))),
-                            (0x13, 1) => Some(Packet::General1(get_val(0))),
-                            (0x14, 1) => Some(Packet::General2(get_val(0))),
-                            (0x15, 1) if !is_s6 => Some(Packet::Mode(get_val(0))),
-                            (0x16, 1) if !is_s6 => Some(Packet::PuGwe(get_val(0))),
-                            (0x17, 1) if !is_s6 => Some(Packet::PuGts(get_val(0))),
-                            (0x18, _) if !is_s6 => {
-                                assert!(src_data[dpos..epos].iter().all(|&x| x == 0));
-                                Some(Packet::Mfwr(num))
-                            }
-                            (0x19, 1) if !is_s6 => Some(Packet::CclkFrequency(get_val(0))),
-                            (0x1a, 1) if !is_s6 => Some(Packet::SeuOpt(get_val(0))),
-                            (0x1b, 2) if !is_s6 => Some(Packet::RbCrcSw(get_val32(0))),
-                            (0x15, 1) if is_s6 => Some(Packet::General3(get_val(0))),
-                            (0x16, 1) if is_s6 => Some(Packet::General4(get_val(0))),
-                            (0x17, 1) if is_s6 => Some(Packet::General5(get_val(0))),
-                            (0x18, 1) if is_s6 => Some(Packet::Mode(get_val(0))),
-                            (0x19, 1) if is_s6 => Some(Packet::PuGwe(get_val(0))),
-                            (0x1a, 1) if is_s6 => Some(Packet::PuGts(get_val(0))),
-                            (0x1b, _) if is_s6 => {
-                                assert!(src_data[dpos..epos].iter().all(|&x| x == 0));
-                                Some(Packet::Mfwr(num))
-                            }
-                            (0x1c, 1) if is_s6 => Some(Packet::CclkFrequency(get_val(0))),
-                            (0x1d, 1) if is_s6 => Some(Packet::SeuOpt(get_val(0))),
-                            (0x1e, 2) if is_s6 => Some(Packet::RbCrcSw(get_val32(0))),
-                            (0x21, 1) if is_s6 => Some(Packet::EyeMask(get_val(0))),
-                            (0x22, 8) if is_s6 => {
-                                let data = src_data[dpos..epos].to_vec();
-                                self.iv = Some(data.clone());
-                                Some(Packet::Cbc(data))
-                            }
-                            _ => panic!("unk write: {reg} times {num}"),
-                        }
-                    } else if (ph >> 11) == 0xa {
-                        let reg = ph >> 5 & 0x3f;
-                        let num = u32::from_be_bytes(*array_ref!(src_data, self.pos, 4)) as usize;
-                        self.pos += 4;
-                        let dpos = self.pos;
-                        self.pos += num * 2;
-                        let epos = self.pos;
-                        let get_val = |i: usize| {
-                            u16::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 2)) as u32
-                        };
-                        for i in 0..num {
-                            self.crc.update(reg as u32, get_val(i));
-                        }
-                        match reg {
-                            3 => {
-                                if is_s6 && (self.ctl0 & 0x40) != 0 {
-                                    // rollback CRC changes.
-                                    self.crc.set(prev_crc);
-                                    let mut real_num = num + 2;
-                                    while !real_num.is_multiple_of(8) {
-                                        real_num += 1;
-                                    }
-                                    let epos = dpos + real_num * 2;
-                                    self.pos = epos;
-                                    let data = self.decrypt_v4(&src_data[dpos..epos]);
-                                    for i in 0..num {
-                                        self.crc.update(
-                                            reg as u32,
-                                            u16::from_be_bytes(*array_ref!(data, i * 2, 2)) as u32,
-                                        );
-                                    }
-                                    let crc = u32::from_be_bytes(*array_ref!(data, num * 2, 4));
-                                    let ecrc = if self.bypass_crc {
-                                        0x9876defc
-                                    } else {
-                                        self.crc.get()
-                                    };
-                                    if crc != ecrc {
-                                        println!("AUTOCRC MISMATCH {crc:08x} {ecrc:08x}");
-                                    }
-                                    for i in (num + 2)..real_num {
-                                        assert_eq!(
-                                            u16::from_be_bytes(*array_ref!(data, i * 2, 2)),
-                                            0x2000
-                                        );
-                                    }
-                                    Some(Packet::EncFdri(data[..num * 2].to_vec()))
-                                } else {
-                                    if self.kind == DeviceKind::Spartan6 {
-                                        let crc =
-                                            u32::from_be_bytes(*array_ref!(src_data, epos, 4));
-                                        let ecrc = if self.bypass_crc {
-                                            0x9876defc
-                                        } else {
-                                            self.crc.get()
-                                        };
-                                        if crc != ecrc {
-                                            println!("AUTOCRC MISMATCH {crc:08x} {ecrc:08x}");
-                                        }
-                                        self.pos += 4;
-                                    }
-                                    Some(Packet::Fdri(src_data[dpos..epos].to_vec()))
-                                }
-                            }
-                            _ => panic!("unk long write: {reg} times {num}"),
-                        }
-                    } else {
-                        panic!("unk word: {ph:04x}")
-                    }
+        self.start = self.pos;
+        Ok(
+            if matches!(self.kind, DeviceKind::Spartan3A | DeviceKind::Spartan6) {
+                let is_s6 = self.kind == DeviceKind::Spartan6;
+                if self.pos + 2 > src_data.len() {
+                    None
                 } else {
-                    match ph {
-                        0xffff => Some(Packet::DummyWord),
-                        0xaa99 => {
-                            if self.kind == DeviceKind::Spartan6 {
-                                assert_eq!(
-                                    u32::from_be_bytes(*array_ref!(src_data, self.pos - 2, 4)),
-                                    0xaa995566
-                                );
-                                self.pos += 2;
-                            }
-                            self.sync = true;
-                            Some(Packet::SyncWord)
-                        }
-                        _ => panic!("unk word while desyncd: {ph:04x}"),
-                    }
-                }
-            }
-        } else {
-            let is_v4 = !matches!(self.kind, DeviceKind::Virtex | DeviceKind::Virtex2);
-            if self.pos + 4 > src_data.len() {
-                None
-            } else {
-                loop {
-                    let ph = u32::from_be_bytes(*array_ref!(src_data, self.pos, 4));
-                    self.pos += 4;
-                    break if self.sync {
+                    let ph = u16::from_be_bytes(*array_ref!(src_data, self.pos, 2));
+                    self.pos += 2;
+                    if self.sync {
                         let prev_crc = self.crc.get();
-                        if ph == 0x00000000 && self.kind == DeviceKind::Virtex {
-                            Some(Packet::Nop)
-                        } else if ph == 0xffffffff {
-                            // welp.
-                            self.sync = false;
-                            Some(Packet::DummyWord)
-                        } else if ph == 0x00000000 && is_v4 {
-                            // Not a valid packet, but a manifestation of a bitgen bug
-                            // that emits broken debug bitstreams for Virtex4+.
-                            // The packet header is missing, should be a long write to FDRI.
-                            self.pos -= 4;
-                            let dpos = self.pos;
-                            let get_val = |i: usize| {
-                                u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
-                            };
-                            let mut i = 0;
-                            while get_val(i) == 0 {
-                                i += 1;
-                            }
-                            let num = i;
-                            self.pos += num * 4;
-                            let epos = self.pos;
-                            for i in 0..num {
-                                self.crc.update(2, get_val(i));
-                            }
-                            Some(Packet::BugFdri(src_data[dpos..epos].to_vec()))
-                        } else if ph == 0x20000000 {
+                        if ph == 0x2000 {
                             Some(Packet::Nop)
-                        } else if (ph >> 27) == 6 {
-                            let reg = ph >> 13 & 0x3fff;
-                            let num = (ph & 0x1fff) as usize;
+                        } else if (ph >> 11) == 6 {
+                            let reg = ph >> 5 & 0x3f;
+                            let num = (ph & 0x1f) as usize;
                             let dpos = self.pos;
-                            self.last_reg = Some(reg);
-                            self.pos += num * 4;
+                            self.pos += num * 2;
                             let epos = self.pos;
+                            check_len(src_data.len(), epos, self.start)?;
                             let get_val = |i: usize| {
-                                u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
+                                u16::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 2)) as u32
+                            };
+                            let get_val32 = |i: usize| {
+                                u32::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 4))
                             };
-                            if !matches!(reg, 8 | 0xf | 0x1e) {
+                            if !matches!(reg, 0 | 9 | 0x12) {
                                 for i in 0..num {
-                                    self.crc.update(reg, get_val(i));
+                                    self.crc.update(reg as u32, get_val(i));
                                 }
                             }
                             match (reg, num) {
-                                (0, 1) => {
-                                    let val = get_val(0);
-                                    let ecrc = if self.bypass_crc { 0xdefc } else { prev_crc };
-                                    if val != ecrc {
-                                        println!("CRC MISMATCH {val:08x} {ecrc:08x}");
-                                    }
-                                    Some(Packet::Crc)
-                                }
-                                (1, 1) => Some(Packet::Far(get_val(0))),
-                                (2, 0) => continue,
-                                (2, _) => {
-                                    if self.kind == DeviceKind::Virtex2 {
-                                        let crc =
-                                            u32::from_be_bytes(*array_ref!(src_data, epos, 4));
-                                        let ecrc = if self.bypass_crc {
-                                            0xdefc
-                                        } else {
-                                            self.crc.get()
-                                        };
-                                        if crc != ecrc {
-                                            println!("AUTOCRC MISMATCH {crc:08x} {ecrc:08x}",);
-                                        }
-                                        self.pos += 4;
-                                        self.crc.reset();
-                                    }
-                                    if matches!(
-                                        self.kind,
-                                        DeviceKind::Virtex4 | DeviceKind::Virtex5
-                                    ) && (self.ctl0 & 0x40) != 0
-                                    {
-                                        // rollback CRC changes.
-                                        self.crc.set(prev_crc);
-                                        let data = self.decrypt_v4(&src_data[dpos..epos]);
-                                        for i in 0..num {
-                                            self.crc.update(
-                                                reg,
-                                                u32::from_be_bytes(*array_ref!(data, i * 4, 4)),
-                                            );
-                                        }
-                                        Some(Packet::EncFdri(data))
+                                (0, 2) => {
+                                    let val = get_val32(0);
+                                    let ecrc = if self.bypass_crc {
+                                        0x9876defc
                                     } else {
-                                        Some(Packet::Fdri(src_data[dpos..epos].to_vec()))
-                                    }
+                                        self.crc.get()
+                                    };
+                                    check_crc(
+                                        self.strict,
+                                        self.start,
+                                        &mut self.warnings,
+                                        val,
+                                        ecrc,
+                                    )?;
+                                    Some(Packet::Crc)
                                 }
-                                (4, 1) => match get_val(0) {
+                                (1, 2) => Some(Packet::Far(get_val32(0))),
+                                (5, 1) => match get_val(0) {
                                     0 => Some(Packet::CmdNull),
                                     1 => Some(Packet::CmdWcfg),
                                     2 => Some(Packet::CmdMfwr),
@@ -543,285 +401,1026 @@ impl Iterator for PacketParser<'_> {
                                     10 => Some(Packet::CmdGRestore),
                                     11 => Some(Packet::CmdShutdown),
                                     13 => Some(Packet::CmdDesynch),
-                                    18 => Some(Packet::CmdBspiRead),
-                                    19 => Some(Packet::CmdFallEdge),
-                                    val => panic!("unk cmd: {val}"),
+                                    val => {
+                                        return Err(
+                                            self.malformed(format!("unknown command {val}"))
+                                        );
+                                    }
                                 },
-                                (5, 1) => {
+                                (6, 1) => {
                                     let val = get_val(0);
                                     self.ctl0 = (self.ctl0 & !self.mask) | (val & self.mask);
                                     Some(Packet::Ctl0(val))
                                 }
-                                (6, 1) => {
+                                (7, 1) => {
                                     let val = get_val(0);
                                     self.mask = val;
                                     Some(Packet::Mask(val))
                                 }
-                                (8, 1) => Some(Packet::LoutDebug(get_val(0))),
-                                (9, 1) => {
+                                (9, 2) => Some(Packet::LoutDebug(get_val32(0))),
+                                (0xa, 1) => {
                                     let val = get_val(0);
-                                    match self.kind {
-                                        DeviceKind::Virtex2 => {
-                                            self.bypass_crc = (val & 1 << 29) != 0;
-                                        }
-                                        DeviceKind::Virtex4 | DeviceKind::Virtex5 => {
-                                            self.bypass_crc = (val & 1 << 28) != 0;
-                                        }
-                                        _ => (),
-                                    }
-                                    Some(Packet::Cor0(val))
+                                    self.bypass_crc = (val & 0x10) != 0;
+                                    Some(Packet::Cor1(val))
                                 }
-                                (0xa, _) => {
-                                    assert!(src_data[dpos..epos].iter().all(|&x| x == 0));
+                                (0xb, 1) => Some(Packet::Cor2(get_val(0))),
+                                (0xc, 1) => Some(Packet::Powerdown(get_val(0))),
+                                (0xd, 1) => Some(Packet::Flr(get_val(0))),
+                                (0xe, 2) => Some(Packet::Idcode(get_val32(0))),
+                                (0xf, 1) if is_s6 => Some(Packet::Timer(get_val(0))),
+                                (0x10, 1) => Some(Packet::HcOpt(get_val(0))),
+                                (0x11, 1) => Some(Packet::Testmode(get_val(0))),
+                                (0x13, 1) => Some(Packet::General1(get_val(0))),
+                                (0x14, 1) => Some(Packet::General2(get_val(0))),
+                                (0x15, 1) if !is_s6 => Some(Packet::Mode(get_val(0))),
+                                (0x16, 1) if !is_s6 => Some(Packet::PuGwe(get_val(0))),
+                                (0x17, 1) if !is_s6 => Some(Packet::PuGts(get_val(0))),
+                                (0x18, _) if !is_s6 => {
+                                    ensure!(
+                                        src_data[dpos..epos].iter().all(|&x| x == 0),
+                                        self.start
+                                    );
                                     Some(Packet::Mfwr(num))
                                 }
-                                (0xb, 1) if !is_v4 => Some(Packet::Flr(get_val(0))),
-                                (0xc, 1) if !is_v4 => {
-                                    let val = get_val(0);
-                                    self.start_key = Some(val as usize);
-                                    Some(Packet::Key(val))
-                                }
-                                (0xd, 2) if !is_v4 => {
-                                    let mut data = vec![];
-                                    data.extend(&src_data[dpos + 4..dpos + 8]);
-                                    data.extend(&src_data[dpos..dpos + 4]);
-                                    self.iv = Some(data.clone());
-                                    Some(Packet::Cbc(data))
+                                (0x19, 1) if !is_s6 => Some(Packet::CclkFrequency(get_val(0))),
+                                (0x1a, 1) if !is_s6 => Some(Packet::SeuOpt(get_val(0))),
+                                (0x1b, 2) if !is_s6 => Some(Packet::RbCrcSw(get_val32(0))),
+                                (0x15, 1) if is_s6 => Some(Packet::General3(get_val(0))),
+                                (0x16, 1) if is_s6 => Some(Packet::General4(get_val(0))),
+                                (0x17, 1) if is_s6 => Some(Packet::General5(get_val(0))),
+                                (0x18, 1) if is_s6 => Some(Packet::Mode(get_val(0))),
+                                (0x19, 1) if is_s6 => Some(Packet::PuGwe(get_val(0))),
+                                (0x1a, 1) if is_s6 => Some(Packet::PuGts(get_val(0))),
+                                (0x1b, _) if is_s6 => {
+                                    ensure!(
+                                        src_data[dpos..epos].iter().all(|&x| x == 0),
+                                        self.start
+                                    );
+                                    Some(Packet::Mfwr(num))
                                 }
-                                (0xe, 1) if !is_v4 => Some(Packet::Idcode(get_val(0))),
-                                (0xb, 4) if is_v4 => {
+                                (0x1c, 1) if is_s6 => Some(Packet::CclkFrequency(get_val(0))),
+                                (0x1d, 1) if is_s6 => Some(Packet::SeuOpt(get_val(0))),
+                                (0x1e, 2) if is_s6 => Some(Packet::RbCrcSw(get_val32(0))),
+                                (0x21, 1) if is_s6 => Some(Packet::EyeMask(get_val(0))),
+                                (0x22, 8) if is_s6 => {
                                     let data = src_data[dpos..epos].to_vec();
                                     self.iv = Some(data.clone());
                                     Some(Packet::Cbc(data))
                                 }
-                                (0xc, 1) if is_v4 => Some(Packet::Idcode(get_val(0))),
-                                (0xd, 0) if is_v4 => continue,
-                                (0xe, 1) if is_v4 => Some(Packet::Cor1(get_val(0))),
-                                (0x10, 1) if is_v4 => Some(Packet::WBStar(get_val(0))),
-                                (0x11, 1) if is_v4 => Some(Packet::Timer(get_val(0))),
-                                (0x13, 1) if is_v4 => Some(Packet::RbCrcSw(get_val(0))),
-                                (0x17, 1) if is_v4 => Some(Packet::Testmode(get_val(0))),
-                                (0x18, 1) if is_v4 => Some(Packet::Ctl1(get_val(0))),
-                                (0x1a, 1) if is_v4 => {
-                                    let val = get_val(0);
-                                    assert!(self.decrypted.is_none());
-                                    assert_eq!(val % 4, 0);
-                                    let cspos = self.pos;
-                                    self.pos += (val as usize) * 4;
-                                    let cepos = self.pos;
-                                    let decrypted = self.decrypt_v4(&src_data[cspos..cepos]);
-                                    let ipad = *array_ref!(decrypted, 0, 0x40);
-                                    let trailer_pos = decrypted.len() - 0x1e0;
-                                    let trailer = *array_ref!(decrypted, trailer_pos, 0x1e0);
-                                    assert_eq!(ipad[..0x20], [0x36; 0x20]);
-                                    let hmac_key: [_; 0x20] =
-                                        core::array::from_fn(|i| ipad[0x20 + i] ^ 0x36);
-                                    let mut sha_pad = [0; 0x40];
-                                    sha_pad[0] = 0x80;
-                                    *array_mut_ref!(sha_pad, 0x38, 8) =
-                                        ((trailer_pos as u64) * 8).to_be_bytes();
-                                    assert_eq!(trailer[..0x40], sha_pad);
-                                    assert_eq!(trailer[0x40..0x140], [0; 0x100]);
-                                    let opad = *array_ref!(trailer, 0x140, 0x40);
-                                    assert_eq!(opad[..0x20], [0x5c; 0x20]);
-                                    let hmac_key_tail: [_; 0x20] =
-                                        core::array::from_fn(|i| opad[0x20 + i] ^ 0x5c);
-                                    assert_eq!(hmac_key, hmac_key_tail);
-                                    assert_eq!(*array_ref!(trailer, 0x180, 0x20), [0; 0x20]);
-                                    let mut outer_sha_pad = [0; 0x20];
-                                    outer_sha_pad[0] = 0x80;
-                                    outer_sha_pad[0x1e] = 0x3;
-                                    assert_eq!(*array_ref!(trailer, 0x1a0, 0x20), outer_sha_pad);
-                                    let mut hasher_inner = sha2::Sha256::new();
-                                    hasher_inner.update(&decrypted[..trailer_pos]);
-                                    let sha_inner: [u8; 0x20] = hasher_inner.finalize().into();
-                                    let mut hasher_outer = sha2::Sha256::new();
-                                    hasher_outer.update(opad);
-                                    hasher_outer.update(sha_inner);
-                                    let sha_outer: [u8; 0x20] = hasher_outer.finalize().into();
-                                    assert_eq!(*array_ref!(trailer, 0x1c0, 0x20), sha_outer);
-                                    self.decrypted = Some(decrypted[0x40..trailer_pos].to_vec());
-                                    self.orig_pos = self.pos;
-                                    self.pos = 0;
-                                    Some(Packet::Dwc(val))
+                                _ => {
+                                    return Err(
+                                        self.malformed(format!("unknown write: {reg} times {num}"))
+                                    );
                                 }
-                                (0x1b, 1) if is_v4 => Some(Packet::Trim(get_val(0))),
-                                (0x1c, 1) if is_v4 => Some(Packet::Trim1(get_val(0))),
-                                (0x1e, 0) => continue,
-                                (0x1f, 1) if is_v4 => Some(Packet::Bspi(get_val(0))),
-                                _ => panic!("unk write: {reg} times {num}"),
                             }
-                        } else if (ph >> 27) == 7 {
-                            let reg = ph >> 13 & 0x3fff;
-                            let num = (ph & 0x1fff) as usize;
-                            self.last_reg = Some(reg);
-                            assert_eq!(reg, 2);
-                            assert_eq!(num, 0);
-                            continue;
-                        } else if (ph >> 27) == 0xa {
-                            let reg = self.last_reg.unwrap();
-                            let num = (ph & 0x7ffffff) as usize;
+                        } else if (ph >> 11) == 0xa {
+                            let reg = ph >> 5 & 0x3f;
+                            check_len(src_data.len(), self.pos + 4, self.start)?;
+                            let num =
+                                u32::from_be_bytes(*array_ref!(src_data, self.pos, 4)) as usize;
+                            self.pos += 4;
                             let dpos = self.pos;
-                            self.pos += num * 4;
+                            self.pos += num * 2;
                             let epos = self.pos;
+                            check_len(src_data.len(), epos, self.start)?;
                             let get_val = |i: usize| {
-                                u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
+                                u16::from_be_bytes(*array_ref!(src_data, dpos + i * 2, 2)) as u32
                             };
                             for i in 0..num {
-                                self.crc.update(reg, get_val(i));
+                                self.crc.update(reg as u32, get_val(i));
                             }
                             match reg {
-                                2 => {
-                                    if self.kind == DeviceKind::Virtex2 {
-                                        let crc =
-                                            u32::from_be_bytes(*array_ref!(src_data, epos, 4));
-                                        let ecrc = if self.bypass_crc {
-                                            0xdefc
-                                        } else {
-                                            self.crc.get()
-                                        };
-                                        if crc != ecrc {
-                                            println!("AUTOCRC MISMATCH {crc:08x} {ecrc:08x}");
-                                        }
-                                        self.pos += 4;
-                                        self.crc.reset();
-                                    }
-                                    if matches!(
-                                        self.kind,
-                                        DeviceKind::Virtex4 | DeviceKind::Virtex5
-                                    ) && (self.ctl0 & 0x40) != 0
-                                    {
+                                3 => {
+                                    if is_s6 && (self.ctl0 & 0x40) != 0 {
                                         // rollback CRC changes.
                                         self.crc.set(prev_crc);
-                                        let data = self.decrypt_v4(&src_data[dpos..epos]);
+                                        let mut real_num = num + 2;
+                                        while !real_num.is_multiple_of(8) {
+                                            real_num += 1;
+                                        }
+                                        let epos = dpos + real_num * 2;
+                                        check_len(src_data.len(), epos, self.start)?;
+                                        self.pos = epos;
+                                        let data = self.decrypt_v4(&src_data[dpos..epos])?;
                                         for i in 0..num {
                                             self.crc.update(
-                                                reg,
-                                                u32::from_be_bytes(*array_ref!(data, i * 4, 4)),
+                                                reg as u32,
+                                                u16::from_be_bytes(*array_ref!(data, i * 2, 2))
+                                                    as u32,
+                                            );
+                                        }
+                                        let crc = u32::from_be_bytes(*array_ref!(data, num * 2, 4));
+                                        let ecrc = if self.bypass_crc {
+                                            0x9876defc
+                                        } else {
+                                            self.crc.get()
+                                        };
+                                        check_crc(
+                                            self.strict,
+                                            self.start,
+                                            &mut self.warnings,
+                                            crc,
+                                            ecrc,
+                                        )?;
+                                        for i in (num + 2)..real_num {
+                                            ensure_eq!(
+                                                u16::from_be_bytes(*array_ref!(data, i * 2, 2)),
+                                                0x2000,
+                                                self.start
                                             );
                                         }
-                                        Some(Packet::EncFdri(data))
+                                        Some(Packet::EncFdri(data[..num * 2].to_vec()))
                                     } else {
+                                        if self.kind == DeviceKind::Spartan6 {
+                                            check_len(src_data.len(), epos + 4, self.start)?;
+                                            let crc =
+                                                u32::from_be_bytes(*array_ref!(src_data, epos, 4));
+                                            let ecrc = if self.bypass_crc {
+                                                0x9876defc
+                                            } else {
+                                                self.crc.get()
+                                            };
+                                            check_crc(
+                                                self.strict,
+                                                self.start,
+                                                &mut self.warnings,
+                                                crc,
+                                                ecrc,
+                                            )?;
+                                            self.pos += 4;
+                                        }
                                         Some(Packet::Fdri(src_data[dpos..epos].to_vec()))
                                     }
                                 }
-                                0xd if is_v4 => Some(Packet::Axss((0..num).map(get_val).collect())),
-                                0x1e => {
-                                    self.crc.set(prev_crc);
-                                    Some(Packet::Bout(src_data[dpos..epos].to_vec()))
+                                _ => {
+                                    return Err(self.malformed(format!(
+                                        "unknown long write: {reg} times {num}"
+                                    )));
                                 }
-                                _ => panic!("unk long write: {reg} times {num}"),
                             }
-                        } else if (ph >> 27) == 0xb {
-                            let reg = self.last_reg.unwrap();
-                            let num = (ph & 0x7ffffff) as usize;
-                            let dpos = self.pos;
-                            self.pos += num * 4;
-                            let epos = self.pos;
-                            let ct = &src_data[dpos..epos];
-                            assert_eq!(num % 2, 0);
-                            let mut pt = vec![];
-
-                            let KeyData::Des(key) = self.key else {
-                                unreachable!()
-                            };
-                            let start_key = self.start_key.unwrap();
-                            let iv = self.iv.as_ref().unwrap();
-                            let mut chain: [u8; 8] = *array_ref!(iv, 0, 8);
-                            // what in the name of fuck.
-                            chain[5] &= 0xfc;
-                            chain[6] = 0;
-                            chain[7] = 0;
-                            let cipher = key.key.map(|k| {
-                                let mut val: u64 = 0;
-                                for bi in 0..7 {
-                                    val |= (k[6 - bi] as u64) << (8 * bi);
+                        } else {
+                            return Err(BitstreamError::InvalidWord {
+                                offset: self.start,
+                                word: ph as u32,
+                            });
+                        }
+                    } else {
+                        match ph {
+                            0xffff => Some(Packet::DummyWord),
+                            0xaa99 => {
+                                if self.kind == DeviceKind::Spartan6 {
+                                    check_len(src_data.len(), self.pos + 2, self.start)?;
+                                    ensure_eq!(
+                                        u32::from_be_bytes(*array_ref!(src_data, self.pos - 2, 4)),
+                                        0xaa995566,
+                                        self.start
+                                    );
+                                    self.pos += 2;
                                 }
-                                let mut ek: [u8; 8] = [0; 8];
-                                for bi in 0..8 {
-                                    ek[7 - bi] = (((val >> (7 * bi)) & 0x7f) as u8) << 1;
+                                self.sync = true;
+                                Some(Packet::SyncWord)
+                            }
+                            _ => {
+                                return Err(BitstreamError::InvalidWord {
+                                    offset: self.start,
+                                    word: ph as u32,
+                                });
+                            }
+                        }
+                    }
+                }
+            } else {
+                let is_v4 = !matches!(self.kind, DeviceKind::Virtex | DeviceKind::Virtex2);
+                if self.pos + 4 > src_data.len() {
+                    None
+                } else {
+                    loop {
+                        self.start = self.pos;
+                        check_len(src_data.len(), self.pos + 4, self.start)?;
+                        let ph = u32::from_be_bytes(*array_ref!(src_data, self.pos, 4));
+                        self.pos += 4;
+                        break if self.sync {
+                            let prev_crc = self.crc.get();
+                            if ph == 0x00000000 && self.kind == DeviceKind::Virtex {
+                                Some(Packet::Nop)
+                            } else if ph == 0xffffffff {
+                                // welp.
+                                self.sync = false;
+                                Some(Packet::DummyWord)
+                            } else if ph == 0x00000000 && is_v4 {
+                                // Not a valid packet, but a manifestation of a bitgen bug
+                                // that emits broken debug bitstreams for Virtex4+.
+                                // The packet header is missing, should be a long write to FDRI.
+                                self.pos -= 4;
+                                let dpos = self.pos;
+                                let get_val = |i: usize| {
+                                    u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
+                                };
+                                let mut i = 0;
+                                while dpos + i * 4 + 4 <= src_data.len() && get_val(i) == 0 {
+                                    i += 1;
                                 }
-                                des::Des::new(&ek.into())
-                            });
-                            let mut last_key = start_key;
-                            loop {
-                                if last_key == start_key {
-                                    assert!(matches!(
-                                        key.keyseq[last_key],
-                                        KeySeq::First | KeySeq::Single
-                                    ));
-                                } else {
-                                    assert!(matches!(
-                                        key.keyseq[last_key],
-                                        KeySeq::Middle | KeySeq::Last
-                                    ));
+                                let num = i;
+                                self.pos += num * 4;
+                                let epos = self.pos;
+                                check_len(src_data.len(), epos, self.start)?;
+                                for i in 0..num {
+                                    self.crc.update(2, get_val(i));
                                 }
-                                if matches!(key.keyseq[last_key], KeySeq::Last | KeySeq::Single) {
-                                    break;
+                                Some(Packet::BugFdri(src_data[dpos..epos].to_vec()))
+                            } else if ph == 0x20000000 {
+                                Some(Packet::Nop)
+                            } else if (ph >> 27) == 6 {
+                                let reg = ph >> 13 & 0x3fff;
+                                let num = (ph & 0x1fff) as usize;
+                                let dpos = self.pos;
+                                self.last_reg = Some(reg);
+                                self.pos += num * 4;
+                                let epos = self.pos;
+                                check_len(src_data.len(), epos, self.start)?;
+                                let get_val = |i: usize| {
+                                    u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
+                                };
+                                if !matches!(reg, 8 | 0xf | 0x1e) {
+                                    for i in 0..num {
+                                        self.crc.update(reg, get_val(i));
+                                    }
                                 }
-                                last_key += 1;
-                            }
-                            for i in 0..(num / 2) {
-                                let ctb: [u8; 8] = core::array::from_fn(|j| ct[i * 8 + (j ^ 4)]);
-                                let mut ptb = ctb;
-                                for kidx in start_key..=last_key {
-                                    if (kidx - start_key).is_multiple_of(2) {
-                                        cipher[kidx].decrypt_block((&mut ptb).into());
-                                    } else {
-                                        cipher[kidx].encrypt_block((&mut ptb).into());
+                                match (reg, num) {
+                                    (0, 1) => {
+                                        let val = get_val(0);
+                                        let ecrc = if self.bypass_crc { 0xdefc } else { prev_crc };
+                                        check_crc(
+                                            self.strict,
+                                            self.start,
+                                            &mut self.warnings,
+                                            val,
+                                            ecrc,
+                                        )?;
+                                        Some(Packet::Crc)
+                                    }
+                                    (1, 1) => Some(Packet::Far(get_val(0))),
+                                    (2, 0) => continue,
+                                    (2, _) => {
+                                        if self.kind == DeviceKind::Virtex2 {
+                                            check_len(src_data.len(), epos + 4, self.start)?;
+                                            let crc =
+                                                u32::from_be_bytes(*array_ref!(src_data, epos, 4));
+                                            let ecrc = if self.bypass_crc {
+                                                0xdefc
+                                            } else {
+                                                self.crc.get()
+                                            };
+                                            check_crc(
+                                                self.strict,
+                                                self.start,
+                                                &mut self.warnings,
+                                                crc,
+                                                ecrc,
+                                            )?;
+                                            self.pos += 4;
+                                            self.crc.reset();
+                                        }
+                                        if matches!(
+                                            self.kind,
+                                            DeviceKind::Virtex4 | DeviceKind::Virtex5
+                                        ) && (self.ctl0 & 0x40) != 0
+                                        {
+                                            // rollback CRC changes.
+                                            self.crc.set(prev_crc);
+                                            let data = self.decrypt_v4(&src_data[dpos..epos])?;
+                                            for i in 0..num {
+                                                self.crc.update(
+                                                    reg,
+                                                    u32::from_be_bytes(*array_ref!(data, i * 4, 4)),
+                                                );
+                                            }
+                                            Some(Packet::EncFdri(data))
+                                        } else {
+                                            Some(Packet::Fdri(src_data[dpos..epos].to_vec()))
+                                        }
+                                    }
+                                    (4, 1) => match get_val(0) {
+                                        0 => Some(Packet::CmdNull),
+                                        1 => Some(Packet::CmdWcfg),
+                                        2 => Some(Packet::CmdMfwr),
+                                        3 => Some(Packet::CmdDGHigh),
+                                        5 => Some(Packet::CmdStart),
+                                        7 => {
+                                            self.crc.reset();
+                                            Some(Packet::CmdRcrc)
+                                        }
+                                        8 => Some(Packet::CmdAGHigh),
+                                        9 => Some(Packet::CmdSwitch),
+                                        10 => Some(Packet::CmdGRestore),
+                                        11 => Some(Packet::CmdShutdown),
+                                        13 => Some(Packet::CmdDesynch),
+                                        18 => Some(Packet::CmdBspiRead),
+                                        19 => Some(Packet::CmdFallEdge),
+                                        val => {
+                                            return Err(
+                                                self.malformed(format!("unknown command {val}"))
+                                            );
+                                        }
+                                    },
+                                    (5, 1) => {
+                                        let val = get_val(0);
+                                        self.ctl0 = (self.ctl0 & !self.mask) | (val & self.mask);
+                                        Some(Packet::Ctl0(val))
+                                    }
+                                    (6, 1) => {
+                                        let val = get_val(0);
+                                        self.mask = val;
+                                        Some(Packet::Mask(val))
+                                    }
+                                    (8, 1) => Some(Packet::LoutDebug(get_val(0))),
+                                    (9, 1) => {
+                                        let val = get_val(0);
+                                        match self.kind {
+                                            DeviceKind::Virtex2 => {
+                                                self.bypass_crc = (val & 1 << 29) != 0;
+                                            }
+                                            DeviceKind::Virtex4 | DeviceKind::Virtex5 => {
+                                                self.bypass_crc = (val & 1 << 28) != 0;
+                                            }
+                                            _ => (),
+                                        }
+                                        Some(Packet::Cor0(val))
+                                    }
+                                    (0xa, _) => {
+                                        ensure!(
+                                            src_data[dpos..epos].iter().all(|&x| x == 0),
+                                            self.start
+                                        );
+                                        Some(Packet::Mfwr(num))
+                                    }
+                                    (0xb, 1) if !is_v4 => Some(Packet::Flr(get_val(0))),
+                                    (0xc, 1) if !is_v4 => {
+                                        let val = get_val(0);
+                                        self.start_key = Some(val as usize);
+                                        Some(Packet::Key(val))
+                                    }
+                                    (0xd, 2) if !is_v4 => {
+                                        let mut data = vec![];
+                                        data.extend(&src_data[dpos + 4..dpos + 8]);
+                                        data.extend(&src_data[dpos..dpos + 4]);
+                                        self.iv = Some(data.clone());
+                                        Some(Packet::Cbc(data))
+                                    }
+                                    (0xe, 1) if !is_v4 => Some(Packet::Idcode(get_val(0))),
+                                    (0xb, 4) if is_v4 => {
+                                        let data = src_data[dpos..epos].to_vec();
+                                        self.iv = Some(data.clone());
+                                        Some(Packet::Cbc(data))
+                                    }
+                                    (0xc, 1) if is_v4 => Some(Packet::Idcode(get_val(0))),
+                                    (0xd, 0) if is_v4 => continue,
+                                    (0xe, 1) if is_v4 => Some(Packet::Cor1(get_val(0))),
+                                    (0x10, 1) if is_v4 => Some(Packet::WBStar(get_val(0))),
+                                    (0x11, 1) if is_v4 => Some(Packet::Timer(get_val(0))),
+                                    (0x13, 1) if is_v4 => Some(Packet::RbCrcSw(get_val(0))),
+                                    (0x17, 1) if is_v4 => Some(Packet::Testmode(get_val(0))),
+                                    (0x18, 1) if is_v4 => Some(Packet::Ctl1(get_val(0))),
+                                    (0x1a, 1) if is_v4 => {
+                                        let val = get_val(0);
+                                        ensure!(self.decrypted.is_none(), self.start);
+                                        ensure_eq!(val % 4, 0, self.start);
+                                        let cspos = self.pos;
+                                        self.pos += (val as usize) * 4;
+                                        let cepos = self.pos;
+                                        check_len(src_data.len(), cepos, self.start)?;
+                                        ensure!(cepos - cspos >= 0x220, self.start);
+                                        let decrypted = self.decrypt_v4(&src_data[cspos..cepos])?;
+                                        let ipad = *array_ref!(decrypted, 0, 0x40);
+                                        let trailer_pos = decrypted.len() - 0x1e0;
+                                        let trailer = *array_ref!(decrypted, trailer_pos, 0x1e0);
+                                        ensure_eq!(ipad[..0x20], [0x36; 0x20], self.start);
+                                        let hmac_key: [_; 0x20] =
+                                            core::array::from_fn(|i| ipad[0x20 + i] ^ 0x36);
+                                        let mut sha_pad = [0; 0x40];
+                                        sha_pad[0] = 0x80;
+                                        *array_mut_ref!(sha_pad, 0x38, 8) =
+                                            ((trailer_pos as u64) * 8).to_be_bytes();
+                                        ensure_eq!(trailer[..0x40], sha_pad, self.start);
+                                        ensure_eq!(trailer[0x40..0x140], [0; 0x100], self.start);
+                                        let opad = *array_ref!(trailer, 0x140, 0x40);
+                                        ensure_eq!(opad[..0x20], [0x5c; 0x20], self.start);
+                                        let hmac_key_tail: [_; 0x20] =
+                                            core::array::from_fn(|i| opad[0x20 + i] ^ 0x5c);
+                                        ensure_eq!(hmac_key, hmac_key_tail, self.start);
+                                        ensure_eq!(
+                                            *array_ref!(trailer, 0x180, 0x20),
+                                            [0; 0x20],
+                                            self.start
+                                        );
+                                        let mut outer_sha_pad = [0; 0x20];
+                                        outer_sha_pad[0] = 0x80;
+                                        outer_sha_pad[0x1e] = 0x3;
+                                        ensure_eq!(
+                                            *array_ref!(trailer, 0x1a0, 0x20),
+                                            outer_sha_pad,
+                                            self.start
+                                        );
+                                        let mut hasher_inner = sha2::Sha256::new();
+                                        hasher_inner.update(&decrypted[..trailer_pos]);
+                                        let sha_inner: [u8; 0x20] = hasher_inner.finalize().into();
+                                        let mut hasher_outer = sha2::Sha256::new();
+                                        hasher_outer.update(opad);
+                                        hasher_outer.update(sha_inner);
+                                        let sha_outer: [u8; 0x20] = hasher_outer.finalize().into();
+                                        ensure_eq!(
+                                            *array_ref!(trailer, 0x1c0, 0x20),
+                                            sha_outer,
+                                            self.start
+                                        );
+                                        self.decrypted =
+                                            Some(decrypted[0x40..trailer_pos].to_vec());
+                                        self.orig_pos = self.pos;
+                                        self.pos = 0;
+                                        Some(Packet::Dwc(val))
+                                    }
+                                    (0x1b, 1) if is_v4 => Some(Packet::Trim(get_val(0))),
+                                    (0x1c, 1) if is_v4 => Some(Packet::Trim1(get_val(0))),
+                                    (0x1e, 0) => continue,
+                                    (0x1f, 1) if is_v4 => Some(Packet::Bspi(get_val(0))),
+                                    _ => {
+                                        return Err(self.malformed(format!(
+                                            "unknown write: {reg} times {num}"
+                                        )));
                                     }
                                 }
-                                for j in 0..8 {
-                                    pt.push(ptb[j ^ 4] ^ chain[j ^ 4]);
+                            } else if (ph >> 27) == 7 {
+                                let reg = ph >> 13 & 0x3fff;
+                                let num = (ph & 0x1fff) as usize;
+                                self.last_reg = Some(reg);
+                                ensure_eq!(reg, 2, self.start);
+                                ensure_eq!(num, 0, self.start);
+                                continue;
+                            } else if (ph >> 27) == 0xa {
+                                let Some(reg) = self.last_reg else {
+                                    return Err(
+                                        self.malformed("type 2 packet without preceding type 1")
+                                    );
+                                };
+                                let num = (ph & 0x7ffffff) as usize;
+                                let dpos = self.pos;
+                                self.pos += num * 4;
+                                let epos = self.pos;
+                                check_len(src_data.len(), epos, self.start)?;
+                                let get_val = |i: usize| {
+                                    u32::from_be_bytes(*array_ref!(src_data, dpos + i * 4, 4))
+                                };
+                                for i in 0..num {
+                                    self.crc.update(reg, get_val(i));
                                 }
-                                chain = ctb;
-                            }
-                            let get_val = |i: usize| u32::from_be_bytes(*array_ref!(pt, i * 4, 4));
-                            for i in 0..num {
-                                self.crc.update(reg, get_val(i));
-                            }
-                            match reg {
-                                2 => {
-                                    if self.kind == DeviceKind::Virtex2 {
-                                        let crc =
-                                            u32::from_be_bytes(*array_ref!(src_data, epos, 4));
-                                        let ecrc = if self.bypass_crc {
-                                            0xdefc
+                                match reg {
+                                    2 => {
+                                        if self.kind == DeviceKind::Virtex2 {
+                                            check_len(src_data.len(), epos + 4, self.start)?;
+                                            let crc =
+                                                u32::from_be_bytes(*array_ref!(src_data, epos, 4));
+                                            let ecrc = if self.bypass_crc {
+                                                0xdefc
+                                            } else {
+                                                self.crc.get()
+                                            };
+                                            check_crc(
+                                                self.strict,
+                                                self.start,
+                                                &mut self.warnings,
+                                                crc,
+                                                ecrc,
+                                            )?;
+                                            self.pos += 4;
+                                            self.crc.reset();
+                                        }
+                                        if matches!(
+                                            self.kind,
+                                            DeviceKind::Virtex4 | DeviceKind::Virtex5
+                                        ) && (self.ctl0 & 0x40) != 0
+                                        {
+                                            // rollback CRC changes.
+                                            self.crc.set(prev_crc);
+                                            let data = self.decrypt_v4(&src_data[dpos..epos])?;
+                                            for i in 0..num {
+                                                self.crc.update(
+                                                    reg,
+                                                    u32::from_be_bytes(*array_ref!(data, i * 4, 4)),
+                                                );
+                                            }
+                                            Some(Packet::EncFdri(data))
                                         } else {
-                                            self.crc.get()
-                                        };
-                                        if crc != ecrc {
-                                            println!("AUTOCRC MISMATCH {crc:08x} {ecrc:08x}");
+                                            Some(Packet::Fdri(src_data[dpos..epos].to_vec()))
                                         }
-                                        self.pos += 4;
-                                        self.crc.reset();
                                     }
-                                    Some(Packet::EncFdri(pt))
+                                    0xd if is_v4 => {
+                                        Some(Packet::Axss((0..num).map(get_val).collect()))
+                                    }
+                                    0x1e => {
+                                        self.crc.set(prev_crc);
+                                        Some(Packet::Bout(src_data[dpos..epos].to_vec()))
+                                    }
+                                    _ => {
+                                        return Err(self.malformed(format!(
+                                            "unknown long write: {reg} times {num}"
+                                        )));
+                                    }
                                 }
-                                _ => panic!("unk encrypted long write: {reg} times {num}"),
+                            } else if (ph >> 27) == 0xb {
+                                let Some(reg) = self.last_reg else {
+                                    return Err(
+                                        self.malformed("type 2 packet without preceding type 1")
+                                    );
+                                };
+                                let num = (ph & 0x7ffffff) as usize;
+                                let dpos = self.pos;
+                                self.pos += num * 4;
+                                let epos = self.pos;
+                                check_len(src_data.len(), epos, self.start)?;
+                                let ct = &src_data[dpos..epos];
+                                ensure_eq!(num % 2, 0, self.start);
+                                let mut pt = vec![];
+
+                                let KeyData::Des(key) = self.key else {
+                                    return Err(
+                                        self.unsupported("DES-encrypted bitstream without DES key")
+                                    );
+                                };
+                                let (Some(start_key), Some(iv)) =
+                                    (self.start_key, self.iv.as_ref())
+                                else {
+                                    return Err(self.malformed("encrypted data without key or IV"));
+                                };
+                                let mut chain: [u8; 8] = *array_ref!(iv, 0, 8);
+                                // what in the name of fuck.
+                                chain[5] &= 0xfc;
+                                chain[6] = 0;
+                                chain[7] = 0;
+                                let cipher = key.key.map(|k| {
+                                    let mut val: u64 = 0;
+                                    for bi in 0..7 {
+                                        val |= (k[6 - bi] as u64) << (8 * bi);
+                                    }
+                                    let mut ek: [u8; 8] = [0; 8];
+                                    for bi in 0..8 {
+                                        ek[7 - bi] = (((val >> (7 * bi)) & 0x7f) as u8) << 1;
+                                    }
+                                    des::Des::new(&ek.into())
+                                });
+                                let mut last_key = start_key;
+                                loop {
+                                    if last_key == start_key {
+                                        ensure!(
+                                            matches!(
+                                                key.keyseq[last_key],
+                                                KeySeq::First | KeySeq::Single
+                                            ),
+                                            self.start
+                                        );
+                                    } else {
+                                        ensure!(
+                                            matches!(
+                                                key.keyseq[last_key],
+                                                KeySeq::Middle | KeySeq::Last
+                                            ),
+                                            self.start
+                                        );
+                                    }
+                                    if matches!(key.keyseq[last_key], KeySeq::Last | KeySeq::Single)
+                                    {
+                                        break;
+                                    }
+                                    last_key += 1;
+                                }
+                                for i in 0..(num / 2) {
+                                    let ctb: [u8; 8] =
+                                        core::array::from_fn(|j| ct[i * 8 + (j ^ 4)]);
+                                    let mut ptb = ctb;
+                                    for kidx in start_key..=last_key {
+                                        if (kidx - start_key).is_multiple_of(2) {
+                                            cipher[kidx].decrypt_block((&mut ptb).into());
+                                        } else {
+                                            cipher[kidx].encrypt_block((&mut ptb).into());
+                                        }
+                                    }
+                                    for j in 0..8 {
+                                        pt.push(ptb[j ^ 4] ^ chain[j ^ 4]);
+                                    }
+                                    chain = ctb;
+                                }
+                                let get_val =
+                                    |i: usize| u32::from_be_bytes(*array_ref!(pt, i * 4, 4));
+                                for i in 0..num {
+                                    self.crc.update(reg, get_val(i));
+                                }
+                                match reg {
+                                    2 => {
+                                        if self.kind == DeviceKind::Virtex2 {
+                                            check_len(src_data.len(), epos + 4, self.start)?;
+                                            let crc =
+                                                u32::from_be_bytes(*array_ref!(src_data, epos, 4));
+                                            let ecrc = if self.bypass_crc {
+                                                0xdefc
+                                            } else {
+                                                self.crc.get()
+                                            };
+                                            check_crc(
+                                                self.strict,
+                                                self.start,
+                                                &mut self.warnings,
+                                                crc,
+                                                ecrc,
+                                            )?;
+                                            self.pos += 4;
+                                            self.crc.reset();
+                                        }
+                                        Some(Packet::EncFdri(pt))
+                                    }
+                                    _ => {
+                                        return Err(self.malformed(format!(
+                                            "unknown encrypted long write: {reg} times {num}"
+                                        )));
+                                    }
+                                }
+                            } else {
+                                return Err(BitstreamError::InvalidWord {
+                                    offset: self.start,
+                                    word: ph,
+                                });
                             }
                         } else {
-                            panic!("unk word: {ph:08x}")
-                        }
-                    } else {
-                        match ph {
-                            0xffffffff => Some(Packet::DummyWord),
-                            0x000000bb => {
-                                let w2 = u32::from_be_bytes(*array_ref!(src_data, self.pos, 4));
-                                assert_eq!(w2, 0x11220044);
-                                self.pos += 4;
-                                Some(Packet::WidthDetect)
-                            }
-                            0xaa995566 => {
-                                self.sync = true;
-                                self.crc.reset();
-                                Some(Packet::SyncWord)
+                            match ph {
+                                0xffffffff => Some(Packet::DummyWord),
+                                0x000000bb => {
+                                    check_len(src_data.len(), self.pos + 4, self.start)?;
+                                    let w2 = u32::from_be_bytes(*array_ref!(src_data, self.pos, 4));
+                                    ensure_eq!(w2, 0x11220044, self.start);
+                                    self.pos += 4;
+                                    Some(Packet::WidthDetect)
+                                }
+                                0xaa995566 => {
+                                    self.sync = true;
+                                    self.crc.reset();
+                                    Some(Packet::SyncWord)
+                                }
+                                _ => {
+                                    return Err(BitstreamError::InvalidWord {
+                                        offset: self.start,
+                                        word: ph,
+                                    });
+                                }
                             }
-                            _ => panic!("unk word while desyncd: {ph:08x}"),
-                        }
-                    };
+                        };
+                    }
+                }
+            },
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PacketWriter {
+    kind: DeviceKind,
+    data: Vec<u8>,
+    crc: Crc,
+    bypass_crc: bool,
+}
+
+impl PacketWriter {
+    pub fn new(kind: DeviceKind) -> Self {
+        Self {
+            kind,
+            data: vec![],
+            crc: Crc::new(kind),
+            bypass_crc: false,
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn is_16bit(&self) -> bool {
+        matches!(self.kind, DeviceKind::Spartan3A | DeviceKind::Spartan6)
+    }
+
+    fn expected_crc(&self) -> u32 {
+        if self.bypass_crc {
+            if self.is_16bit() { 0x9876defc } else { 0xdefc }
+        } else {
+            self.crc.get()
+        }
+    }
+
+    fn push16(&mut self, val: u32) {
+        self.data.extend((val as u16).to_be_bytes());
+    }
+
+    fn push32(&mut self, val: u32) {
+        self.data.extend(val.to_be_bytes());
+    }
+
+    fn write16(&mut self, reg: u32, vals: &[u32]) {
+        self.push16(0x3000 | reg << 5 | vals.len() as u32);
+        for &val in vals {
+            self.push16(val);
+            if !matches!(reg, 0 | 9 | 0x12) {
+                self.crc.update(reg, val);
+            }
+        }
+    }
+
+    fn write16_32(&mut self, reg: u32, val: u32) {
+        self.write16(reg, &[val >> 16, val & 0xffff]);
+    }
+
+    fn write32(&mut self, reg: u32, vals: &[u32]) {
+        self.push32(0x30000000 | reg << 13 | vals.len() as u32);
+        for &val in vals {
+            self.push32(val);
+            if !matches!(reg, 8 | 0xf | 0x1e) {
+                self.crc.update(reg, val);
+            }
+        }
+    }
+
+    fn write32_long(&mut self, reg: u32, vals: &[u32]) {
+        self.write32(reg, &[]);
+        self.push32(0x50000000 | vals.len() as u32);
+        for &val in vals {
+            self.push32(val);
+            if reg != 0x1e {
+                self.crc.update(reg, val);
+            }
+        }
+    }
+
+    fn write_reg(&mut self, reg16: u32, reg32: u32, val: u32) {
+        if self.is_16bit() {
+            self.write16(reg16, &[val]);
+        } else {
+            self.write32(reg32, &[val]);
+        }
+    }
+
+    fn write_cmd(&mut self, cmd: u32) {
+        self.write_reg(5, 4, cmd);
+        if cmd == 7 {
+            self.crc.reset();
+        }
+    }
+
+    pub fn emit(&mut self, packet: &Packet) {
+        let is_s6 = self.kind == DeviceKind::Spartan6;
+        let is_v4 = !matches!(
+            self.kind,
+            DeviceKind::Virtex | DeviceKind::Virtex2 | DeviceKind::Spartan3A | DeviceKind::Spartan6
+        );
+        match *packet {
+            Packet::DummyWord => {
+                if self.is_16bit() {
+                    self.push16(0xffff);
+                } else {
+                    self.push32(0xffffffff);
+                }
+            }
+            Packet::WidthDetect => {
+                self.push32(0x000000bb);
+                self.push32(0x11220044);
+            }
+            Packet::SyncWord => {
+                if self.kind == DeviceKind::Spartan3A {
+                    self.push16(0xaa99);
+                } else {
+                    self.push32(0xaa995566);
+                    if !self.is_16bit() {
+                        self.crc.reset();
+                    }
                 }
             }
+            Packet::Nop => {
+                if self.is_16bit() {
+                    self.push16(0x2000);
+                } else {
+                    self.push32(0x20000000);
+                }
+            }
+            Packet::CmdNull => self.write_cmd(0),
+            Packet::CmdWcfg => self.write_cmd(1),
+            Packet::CmdMfwr => self.write_cmd(2),
+            Packet::CmdDGHigh => self.write_cmd(3),
+            Packet::CmdStart => self.write_cmd(5),
+            Packet::CmdRcrc => self.write_cmd(7),
+            Packet::CmdAGHigh => self.write_cmd(8),
+            Packet::CmdSwitch => self.write_cmd(9),
+            Packet::CmdGRestore => self.write_cmd(10),
+            Packet::CmdShutdown => self.write_cmd(11),
+            Packet::CmdDesynch => self.write_cmd(13),
+            Packet::CmdBspiRead => self.write_cmd(18),
+            Packet::CmdFallEdge => self.write_cmd(19),
+            Packet::Crc => {
+                let val = self.expected_crc();
+                if self.is_16bit() {
+                    self.write16_32(0, val);
+                } else {
+                    self.write32(0, &[val]);
+                }
+            }
+            Packet::Far(val) => {
+                if self.is_16bit() {
+                    self.write16_32(1, val);
+                } else {
+                    self.write32(1, &[val]);
+                }
+            }
+            Packet::Ctl0(val) => self.write_reg(6, 5, val),
+            Packet::Mask(val) => self.write_reg(7, 6, val),
+            Packet::LoutDebug(val) => {
+                if self.is_16bit() {
+                    self.write16_32(9, val);
+                } else {
+                    self.write32(8, &[val]);
+                }
+            }
+            Packet::Cor0(val) => {
+                assert!(!self.is_16bit());
+                match self.kind {
+                    DeviceKind::Virtex2 => {
+                        self.bypass_crc = (val & 1 << 29) != 0;
+                    }
+                    DeviceKind::Virtex4 | DeviceKind::Virtex5 => {
+                        self.bypass_crc = (val & 1 << 28) != 0;
+                    }
+                    _ => (),
+                }
+                self.write32(9, &[val]);
+            }
+            Packet::Cor1(val) => {
+                if self.is_16bit() {
+                    self.bypass_crc = (val & 0x10) != 0;
+                    self.write16(0xa, &[val]);
+                } else {
+                    assert!(is_v4);
+                    self.write32(0xe, &[val]);
+                }
+            }
+            Packet::Mfwr(num) => {
+                let zeros = vec![0; num];
+                if self.is_16bit() {
+                    self.write16(if is_s6 { 0x1b } else { 0x18 }, &zeros);
+                } else {
+                    self.write32(0xa, &zeros);
+                }
+            }
+            Packet::Flr(val) => {
+                assert!(!is_v4);
+                self.write_reg(0xd, 0xb, val);
+            }
+            Packet::Idcode(val) => {
+                if self.is_16bit() {
+                    self.write16_32(0xe, val);
+                } else {
+                    self.write32(if is_v4 { 0xc } else { 0xe }, &[val]);
+                }
+            }
+            Packet::Cor2(val) => {
+                assert!(self.is_16bit());
+                self.write16(0xb, &[val]);
+            }
+            Packet::Powerdown(val) => {
+                assert!(self.is_16bit());
+                self.write16(0xc, &[val]);
+            }
+            Packet::Timer(val) => {
+                assert!(is_s6 || is_v4);
+                self.write_reg(0xf, 0x11, val);
+            }
+            Packet::HcOpt(val) => {
+                assert!(self.is_16bit());
+                self.write16(0x10, &[val]);
+            }
+            Packet::Testmode(val) => {
+                assert!(self.is_16bit() || is_v4);
+                self.write_reg(0x11, 0x17, val);
+            }
+            Packet::General1(val) => {
+                assert!(self.is_16bit());
+                self.write16(0x13, &[val]);
+            }
+            Packet::General2(val) => {
+                assert!(self.is_16bit());
+                self.write16(0x14, &[val]);
+            }
+            Packet::General3(val) => {
+                assert!(is_s6);
+                self.write16(0x15, &[val]);
+            }
+            Packet::General4(val) => {
+                assert!(is_s6);
+                self.write16(0x16, &[val]);
+            }
+            Packet::General5(val) => {
+                assert!(is_s6);
+                self.write16(0x17, &[val]);
+            }
+            Packet::Mode(val) => {
+                assert!(self.is_16bit());
+                self.write16(if is_s6 { 0x18 } else { 0x15 }, &[val]);
+            }
+            Packet::PuGwe(val) => {
+                assert!(self.is_16bit());
+                self.write16(if is_s6 { 0x19 } else { 0x16 }, &[val]);
+            }
+            Packet::PuGts(val) => {
+                assert!(self.is_16bit());
+                self.write16(if is_s6 { 0x1a } else { 0x17 }, &[val]);
+            }
+            Packet::CclkFrequency(val) => {
+                assert!(self.is_16bit());
+                self.write16(if is_s6 { 0x1c } else { 0x19 }, &[val]);
+            }
+            Packet::SeuOpt(val) => {
+                assert!(self.is_16bit());
+                self.write16(if is_s6 { 0x1d } else { 0x1a }, &[val]);
+            }
+            Packet::RbCrcSw(val) => {
+                if self.is_16bit() {
+                    self.write16_32(if is_s6 { 0x1e } else { 0x1b }, val);
+                } else {
+                    assert!(is_v4);
+                    self.write32(0x13, &[val]);
+                }
+            }
+            Packet::EyeMask(val) => {
+                assert!(is_s6);
+                self.write16(0x21, &[val]);
+            }
+            Packet::WBStar(val) => {
+                assert!(is_v4);
+                self.write32(0x10, &[val]);
+            }
+            Packet::Ctl1(val) => {
+                assert!(is_v4);
+                self.write32(0x18, &[val]);
+            }
+            Packet::Trim(val) => {
+                assert!(is_v4);
+                self.write32(0x1b, &[val]);
+            }
+            Packet::Trim1(val) => {
+                assert!(is_v4);
+                self.write32(0x1c, &[val]);
+            }
+            Packet::Bspi(val) => {
+                assert!(is_v4);
+                self.write32(0x1f, &[val]);
+            }
+            Packet::Axss(ref vals) => {
+                assert!(is_v4);
+                self.write32_long(0xd, vals);
+            }
+            Packet::Bout(ref data) => {
+                assert!(is_v4);
+                assert_eq!(data.len() % 4, 0);
+                self.write32(0x1e, &[]);
+                self.push32(0x50000000 | (data.len() / 4) as u32);
+                self.data.extend(data);
+            }
+            Packet::Fdri(ref data) => {
+                if self.is_16bit() {
+                    assert_eq!(data.len() % 2, 0);
+                    let num = data.len() / 2;
+                    self.push16(0x5000 | 3 << 5);
+                    self.push32(num as u32);
+                    for i in 0..num {
+                        let val = u16::from_be_bytes(*array_ref!(data, i * 2, 2)) as u32;
+                        self.push16(val);
+                        self.crc.update(3, val);
+                    }
+                    if is_s6 {
+                        let crc = self.expected_crc();
+                        self.push32(crc);
+                    }
+                } else {
+                    assert_eq!(data.len() % 4, 0);
+                    let vals: Vec<u32> = (0..data.len() / 4)
+                        .map(|i| u32::from_be_bytes(*array_ref!(data, i * 4, 4)))
+                        .collect();
+                    self.write32_long(2, &vals);
+                    if self.kind == DeviceKind::Virtex2 {
+                        let crc = self.expected_crc();
+                        self.push32(crc);
+                        self.crc.reset();
+                    }
+                }
+            }
+            Packet::Key(_)
+            | Packet::Cbc(_)
+            | Packet::Dwc(_)
+            | Packet::EncFdri(_)
+            | Packet::BugFdri(_) => {
+                panic!("cannot emit packet {packet:?}")
+            }
         }
     }
 }
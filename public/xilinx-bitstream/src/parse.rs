@@ -1,29 +1,32 @@
+use crate::ecc::check_ecc;
+use crate::error::{BitstreamError, check_len, ensure, ensure_eq};
 use crate::packet::{Crc, Packet, PacketParser};
 use crate::{
-    Bitstream, BitstreamGeom, BitstreamMode, DeviceKind, DieBitstream, FrameAddr, FrameMaskMode,
-    GtzBitstream, KeyData, Reg,
+    Bitstream, BitstreamGeom, BitstreamMode, DeviceKind, DieBitstream, FrameAddr, GtzBitstream,
+    KeyData, Reg,
 };
 use arrayref::array_ref;
 use bitvec::prelude::*;
 use prjcombine_interconnect::dir::DirV;
 use std::collections::HashMap;
 
-fn parse_xc2000_bitstream(bs: &mut Bitstream, data: &[u8]) {
+fn parse_xc2000_bitstream(bs: &mut Bitstream, data: &[u8]) -> Result<(), BitstreamError> {
     let bs = bs.die.first_mut().unwrap();
     let data: &BitSlice<u8, Msb0> = BitSlice::from_slice(data);
-    assert_eq!(data[..12], bits![1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0]);
+    let frame_len = bs.frame_len;
+    let frames_num = bs.frame_info.len();
+    check_len(data.len(), 40 + frames_num * (frame_len + 4) + 4, 0)?;
+    ensure_eq!(data[..12], bits![1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0], 0);
     let mut bitlen = 0;
     for j in 0..24 {
         if data[35 - j] {
             bitlen |= 1 << j;
         }
     }
-    assert_eq!(data[36..40], bits![1, 1, 1, 1]);
+    ensure_eq!(data[36..40], bits![1, 1, 1, 1], 4);
     let mut pos = 40;
-    let frame_len = bs.frame_len;
-    let frames_num = bs.frame_info.len();
     for fi in 0..frames_num {
-        assert!(!data[pos]);
+        ensure!(!data[pos], pos / 8);
         pos += 1;
         let fdata = &data[pos..(pos + frame_len)];
         let frame = bs.frame_mut(fi);
@@ -32,33 +35,36 @@ fn parse_xc2000_bitstream(bs: &mut Bitstream, data: &[u8]) {
         }
         pos += frame_len;
         let stop = &data[pos..(pos + 3)];
-        assert_eq!(stop, &bits![1, 1, 1]);
+        ensure_eq!(stop, &bits![1, 1, 1], pos / 8);
         pos += 3;
     }
     let post = &data[pos..(pos + 4)];
-    assert_eq!(post, &bits![1, 1, 1, 1]);
+    ensure_eq!(post, &bits![1, 1, 1, 1], pos / 8);
     pos += 4;
     while !pos.is_multiple_of(8) {
-        assert!(data[pos]);
+        ensure!(data[pos], pos / 8);
         pos += 1;
     }
-    assert_eq!(bitlen, pos + 1);
+    ensure_eq!(bitlen, pos + 1, 0);
+    check_len(data.len(), pos + 8, pos / 8)?;
     let pad = &data[pos..(pos + 8)];
-    assert_eq!(pad, &bits![1, 1, 1, 1, 1, 1, 1, 1]);
+    ensure_eq!(pad, &bits![1, 1, 1, 1, 1, 1, 1, 1], pos / 8);
     pos += 8;
-    assert_eq!(pos, data.len());
+    ensure_eq!(pos, data.len(), pos / 8);
+    Ok(())
 }
 
-struct Xc4000Crc {
-    crc: u16,
+#[derive(Clone)]
+pub(crate) struct Xc4000Crc {
+    pub crc: u16,
 }
 
 impl Xc4000Crc {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self { crc: 0 }
     }
 
-    fn feed_bit(&mut self, b: bool) {
+    pub fn feed_bit(&mut self, b: bool) {
         if !b {
             self.crc ^= 0x8000;
         }
@@ -71,24 +77,25 @@ impl Xc4000Crc {
     }
 }
 
-fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) {
+fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) -> Result<(), BitstreamError> {
     let kind = bs.kind;
     let bs = bs.die.first_mut().unwrap();
     if data.starts_with(&[0xff, 0xff, 0xf2]) {
-        let bitlen = (data[3] as u32) << 16 | (data[4] as u32) << 8 | (data[5] as u32);
-        assert_eq!(data[6], 0xd2);
-        let mut pos = 7;
         let frame_len = bs.frame_len;
         let frames_num = bs.frame_info.len();
+        let flen = frame_len.div_ceil(8);
+        check_len(data.len(), 7 + frames_num * (flen + 7) + 10, 0)?;
+        let bitlen = (data[3] as u32) << 16 | (data[4] as u32) << 8 | (data[5] as u32);
+        ensure_eq!(data[6], 0xd2, 6);
+        let mut pos = 7;
         let start = if kind == DeviceKind::S40Xl {
             0xff
         } else {
             0xfe
         };
-        let flen = frame_len.div_ceil(8);
         let pad = flen * 8 - frame_len;
         for fi in 0..frames_num {
-            assert_eq!(data[pos], start);
+            ensure_eq!(data[pos], start, pos);
             pos += 1;
             let fdata = &data[pos..pos + flen];
             pos += flen;
@@ -100,21 +107,21 @@ fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) {
                         let bi = match bit {
                             0 => {
                                 if i == 0 {
-                                    assert!(!bv);
+                                    ensure!(!bv, pos);
                                     continue;
                                 }
                                 bit * flen + i - 1
                             }
                             7 => {
                                 if i == 0 {
-                                    assert!(bv);
+                                    ensure!(bv, pos);
                                     continue;
                                 }
                                 bit * flen + i - 2
                             }
                             _ => bit * flen + i - 1,
                         };
-                        assert!(bi < frame_len);
+                        ensure!(bi < frame_len, pos);
                         frame.set(bi, bv);
                     } else {
                         if bit < (8 - pad) {
@@ -122,7 +129,7 @@ fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) {
                             frame.set(bi, bv);
                         } else {
                             if i == 0 {
-                                assert!(bv);
+                                ensure!(bv, pos);
                             } else {
                                 let bi = bit * (flen - 1) + (8 - pad) + (i - 1);
                                 frame.set(bi, bv);
@@ -131,30 +138,35 @@ fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) {
                     }
                 }
             }
-            assert_eq!(data[pos..pos + 6], [0xd2, 0xff, 0xd2, 0xff, 0xff, 0xff]);
+            ensure_eq!(
+                data[pos..pos + 6],
+                [0xd2, 0xff, 0xd2, 0xff, 0xff, 0xff],
+                pos
+            );
             pos += 6;
         }
-        assert_eq!(data[pos..pos + 10], [0xff; 10]);
+        ensure_eq!(data[pos..pos + 10], [0xff; 10], pos);
         pos += 10;
-        assert_eq!(pos, data.len());
-        assert_eq!(bitlen as usize, pos * 8 - 7);
+        ensure_eq!(pos, data.len(), pos);
+        ensure_eq!(bitlen as usize, pos * 8 - 7, 3);
     } else {
         let mut crc = Xc4000Crc::new();
         let data: &BitSlice<u8, Msb0> = BitSlice::from_slice(data);
-        assert_eq!(data[..12], bits![1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0]);
+        let frame_len = bs.frame_len;
+        let frames_num = bs.frame_info.len();
+        check_len(data.len(), 40 + frames_num * (frame_len + 5) + 8, 0)?;
+        ensure_eq!(data[..12], bits![1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 1, 0], 0);
         let mut bitlen = 0;
         for j in 0..24 {
             if data[35 - j] {
                 bitlen |= 1 << j;
             }
         }
-        assert_eq!(data[36..40], bits![1, 1, 1, 1]);
+        ensure_eq!(data[36..40], bits![1, 1, 1, 1], 4);
         let mut pos = 40;
-        let frame_len = bs.frame_len;
-        let frames_num = bs.frame_info.len();
         let mut crc_enable = false;
         for fi in 0..frames_num {
-            assert!(!data[pos]);
+            ensure!(!data[pos], pos / 8);
             if fi == 0 {
                 crc.feed_bit(true);
             } else {
@@ -182,42 +194,56 @@ fn parse_xc4000_bitstream(bs: &mut Bitstream, data: &[u8]) {
                 for bit in raw_crc {
                     crc.feed_bit(*bit);
                 }
-                assert_eq!(crc.crc & 0xf, 0);
+                check_frame_crc(crc.crc & 0xf, pos / 8)?;
             } else {
-                assert_eq!(raw_crc, bits![0, 1, 1, 0]);
+                ensure_eq!(raw_crc, bits![0, 1, 1, 0], pos / 8);
             }
             if crc_enable && fi == frames_num - 1 {
                 for i in (frame_len - 7)..frame_len {
                     frame.set(i, true);
                 }
-                assert_eq!(crc.crc & 0x7ff, 0);
+                check_frame_crc(crc.crc & 0x7ff, pos / 8)?;
             }
         }
         let post = &data[pos..(pos + 8)];
-        assert_eq!(post, &bits![0, 1, 1, 1, 1, 1, 1, 1]);
+        ensure_eq!(post, &bits![0, 1, 1, 1, 1, 1, 1, 1], pos / 8);
         pos += 8;
         while !pos.is_multiple_of(8) {
-            assert!(data[pos]);
+            ensure!(data[pos], pos / 8);
             pos += 1;
         }
-        assert_eq!(bitlen, pos + 1);
+        ensure_eq!(bitlen, pos + 1, 0);
+        check_len(data.len(), pos + 8, pos / 8)?;
         let pad = &data[pos..(pos + 8)];
-        assert_eq!(pad, &bits![1, 1, 1, 1, 1, 1, 1, 1]);
+        ensure_eq!(pad, &bits![1, 1, 1, 1, 1, 1, 1, 1], pos / 8);
         pos += 8;
-        assert_eq!(pos, data.len());
+        ensure_eq!(pos, data.len(), pos / 8);
     }
+    Ok(())
+}
+
+fn check_frame_crc(residue: u16, offset: usize) -> Result<(), BitstreamError> {
+    if residue != 0 {
+        return Err(BitstreamError::CrcMismatch {
+            offset,
+            expected: 0,
+            actual: residue.into(),
+        });
+    }
+    Ok(())
 }
 
-struct Xc5200Crc {
-    crc: u16,
+#[derive(Clone)]
+pub(crate) struct Xc5200Crc {
+    pub crc: u16,
 }
 
 impl Xc5200Crc {
-    fn new() -> Self {
+    pub fn new() -> Self {
         Self { crc: 0 }
     }
 
-    fn feed_byte(&mut self, b: u8) {
+    pub fn feed_byte(&mut self, b: u8) {
         for i in (0..8).rev() {
             if ((b >> i) & 1) == 0 {
                 self.crc ^= 0x8000;
@@ -232,30 +258,34 @@ impl Xc5200Crc {
     }
 }
 
-fn parse_xc5200_bitstream(bs: &mut Bitstream, data: &[u8]) {
+fn parse_xc5200_bitstream(bs: &mut Bitstream, data: &[u8]) -> Result<(), BitstreamError> {
     let bs = bs.die.first_mut().unwrap();
     let mut crc = Xc5200Crc::new();
-    assert_eq!(data[0], 0xff);
-    assert_eq!(data[1], 0xf2);
+    let frame_len = bs.frame_len;
+    let frames_num = bs.frame_info.len();
+    let frame_bytes = frame_len.div_ceil(8);
+    check_len(data.len(), 6 + frames_num * (frame_bytes + 5) + 32, 0)?;
+    ensure_eq!(data[0], 0xff, 0);
+    ensure_eq!(data[1], 0xf2, 1);
     let bit_length = (data[2] as usize) << 16 | (data[3] as usize) << 8 | (data[4] as usize);
-    assert_eq!(data[5], 0xff);
+    ensure_eq!(data[5], 0xff, 5);
     if bit_length == data.len() * 8 - 7 {
         // OK
     } else if bit_length == data.len() * 8 - 3 {
         bs.regs.insert(Reg::FakeLcAlignmentDone, 1);
     } else {
-        panic!(
-            "weird length {bit_length} [total {total}]",
-            total = data.len() * 8
-        );
+        return Err(BitstreamError::Malformed {
+            offset: 2,
+            reason: format!(
+                "weird length {bit_length} [total {total}]",
+                total = data.len() * 8
+            ),
+        });
     }
     let mut pos = 6;
-    let frame_len = bs.frame_len;
     let mut crc_enable = false;
-    let frames_num = bs.frame_info.len();
-    let frame_bytes = frame_len.div_ceil(8);
     for fi in 0..frames_num {
-        assert_eq!(data[pos], 0xfe);
+        ensure_eq!(data[pos], 0xfe, pos);
         crc.feed_byte(data[pos]);
         pos += 1;
         let frame = bs.frame_mut(fi);
@@ -269,12 +299,12 @@ fn parse_xc5200_bitstream(bs: &mut Bitstream, data: &[u8]) {
                     frame.set(j, true);
                 }
                 if j < frame_bytes * 8 - 12 {
-                    assert!(!bit);
+                    ensure!(!bit, pos);
                 }
             } else if j < frame_len {
                 frame.set(j, bit);
             } else {
-                assert!(!bit);
+                ensure!(!bit, pos);
             }
         }
         for &b in &data[pos..(pos + frame_bytes)] {
@@ -283,29 +313,37 @@ fn parse_xc5200_bitstream(bs: &mut Bitstream, data: &[u8]) {
         pos += frame_bytes;
         let fcrc = data[pos] >> 4;
         if !crc_enable {
-            assert_eq!(fcrc, 6);
+            ensure_eq!(fcrc, 6, pos);
         } else {
-            assert_eq!(fcrc, (!crc.crc >> 12) as u8);
-        }
-        assert_eq!(data[pos] & 0xf, 0xf);
-        assert_eq!(data[pos + 1], 0xff);
-        assert_eq!(data[pos + 2], 0xff);
-        assert_eq!(data[pos + 3], 0xff);
+            let expected = (!crc.crc >> 12) as u8;
+            if fcrc != expected {
+                return Err(BitstreamError::CrcMismatch {
+                    offset: pos,
+                    expected: expected.into(),
+                    actual: fcrc.into(),
+                });
+            }
+        }
+        ensure_eq!(data[pos] & 0xf, 0xf, pos);
+        ensure_eq!(data[pos + 1], 0xff, pos);
+        ensure_eq!(data[pos + 2], 0xff, pos);
+        ensure_eq!(data[pos + 3], 0xff, pos);
         for &b in &data[pos..(pos + 4)] {
             crc.feed_byte(b);
         }
         pos += 4;
     }
     if crc_enable {
-        assert_eq!(crc.crc, 0);
+        check_frame_crc(crc.crc, pos)?;
     }
-    assert_eq!(data[pos], 0xfe);
+    ensure_eq!(data[pos], 0xfe, pos);
     pos += 1;
     for _ in 0..31 {
-        assert_eq!(data[pos], 0xff);
+        ensure_eq!(data[pos], 0xff, pos);
         pos += 1;
     }
-    assert_eq!(data.len(), pos);
+    ensure_eq!(data.len(), pos, pos);
+    Ok(())
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
@@ -315,15 +353,26 @@ enum State {
     Mfwr,
 }
 
-fn virtex_far(addr: FrameAddr) -> u32 {
+fn lookup_far(
+    far_dict: &HashMap<u32, usize>,
+    far: u32,
+    offset: usize,
+) -> Result<usize, BitstreamError> {
+    far_dict
+        .get(&far)
+        .copied()
+        .ok_or(BitstreamError::InvalidFrameAddress { offset, far })
+}
+
+pub(crate) fn virtex_far(addr: FrameAddr) -> u32 {
     addr.minor << 9 | addr.major << 17 | addr.typ << 25
 }
 
-fn spartan3a_far(addr: FrameAddr) -> u32 {
+pub(crate) fn spartan3a_far(addr: FrameAddr) -> u32 {
     addr.minor | addr.major << 16 | addr.typ << 26
 }
 
-fn spartan6_far(addr: FrameAddr) -> u32 {
+pub(crate) fn spartan6_far(addr: FrameAddr) -> u32 {
     if addr.typ == 1 {
         // BRAM
         addr.minor << 14 | addr.major << 16 | (addr.region as u32) << 24 | addr.typ << 28
@@ -332,7 +381,7 @@ fn spartan6_far(addr: FrameAddr) -> u32 {
     }
 }
 
-fn virtex4_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex4_far(addr: FrameAddr) -> u32 {
     let (row, bt) = if addr.region < 0 {
         ((-1 - addr.region) as u32, 1)
     } else {
@@ -341,7 +390,7 @@ fn virtex4_far(addr: FrameAddr) -> u32 {
     addr.minor | addr.major << 6 | row << 14 | addr.typ << 19 | bt << 22
 }
 
-fn virtex5_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex5_far(addr: FrameAddr) -> u32 {
     let (row, bt) = if addr.region < 0 {
         ((-1 - addr.region) as u32, 1)
     } else {
@@ -350,7 +399,7 @@ fn virtex5_far(addr: FrameAddr) -> u32 {
     addr.minor | addr.major << 7 | row << 15 | bt << 20 | addr.typ << 21
 }
 
-fn virtex7_far(addr: FrameAddr) -> u32 {
+pub(crate) fn virtex7_far(addr: FrameAddr) -> u32 {
     let (row, bt) = if addr.region < 0 {
         ((-1 - addr.region) as u32, 1)
     } else {
@@ -359,14 +408,29 @@ fn virtex7_far(addr: FrameAddr) -> u32 {
     addr.minor | addr.major << 7 | row << 17 | bt << 22 | addr.typ << 23
 }
 
-fn insert_virtex_frame(kind: DeviceKind, bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+pub(crate) fn ultrascale_far(addr: FrameAddr) -> u32 {
+    addr.minor | addr.major << 7 | (addr.region as u32) << 17 | addr.typ << 23
+}
+
+pub(crate) fn ultrascaleplus_far(addr: FrameAddr) -> u32 {
+    addr.minor | addr.major << 8 | (addr.region as u32) << 18 | addr.typ << 24
+}
+
+fn insert_virtex_frame(
+    kind: DeviceKind,
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
     let frame_len = bs.frame_len;
     let frame_words = frame_len.div_ceil(32);
     if kind == DeviceKind::Virtex {
-        assert_eq!(data.len(), (frame_words + 1) * 4);
+        ensure_eq!(data.len(), (frame_words + 1) * 4, offset);
     } else {
-        assert_eq!(data.len(), frame_words * 4);
+        ensure_eq!(data.len(), frame_words * 4, offset);
     }
+    ensure!(fi < bs.frame_present.len(), offset);
     let frame = bs.frame_mut(fi);
     for i in 0..frame_words {
         let word = u32::from_be_bytes(*array_ref!(data, i * 4, 4));
@@ -384,21 +448,35 @@ fn insert_virtex_frame(kind: DeviceKind, bs: &mut DieBitstream, fi: usize, data:
         }
     }
     if bs.frame_present[fi] {
-        panic!("FRAME {fi} SET TWICE");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("frame {fi} set twice"),
+        });
     }
     bs.frame_present.set(fi, true);
+    Ok(())
 }
 
-fn fixup_virtex_frame(kind: DeviceKind, bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+fn fixup_virtex_frame(
+    kind: DeviceKind,
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
+    ensure!(fi < bs.frame_present.len(), offset);
     if !bs.frame_present[fi] {
-        panic!("FIXUP ON NOT PRESENT FRAME {fi}");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("fixup on not present frame {fi}"),
+        });
     }
     let frame_len = bs.frame_len;
     let frame_words = frame_len.div_ceil(32);
     if kind == DeviceKind::Virtex {
-        assert_eq!(data.len(), (frame_words + 1) * 4);
+        ensure_eq!(data.len(), (frame_words + 1) * 4, offset);
     } else {
-        assert_eq!(data.len(), frame_words * 4);
+        ensure_eq!(data.len(), frame_words * 4, offset);
     }
     let pos = fi * bs.frame_len;
     let frame = &bs.frame_data[pos..pos + bs.frame_len];
@@ -421,13 +499,20 @@ fn fixup_virtex_frame(kind: DeviceKind, bs: &mut DieBitstream, fi: usize, data:
             }
         }
     }
+    Ok(())
 }
 
-fn insert_spartan3a_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+fn insert_spartan3a_frame(
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
     let frame_len = bs.frame_len;
-    assert_eq!(frame_len % 16, 0);
+    ensure_eq!(frame_len % 16, 0, offset);
     let frame_words = frame_len / 16;
-    assert_eq!(data.len(), frame_words * 2);
+    ensure_eq!(data.len(), frame_words * 2, offset);
+    ensure!(fi < bs.frame_present.len(), offset);
     let frame = bs.frame_mut(fi);
     for i in 0..frame_words {
         let word = u16::from_be_bytes(*array_ref!(data, i * 2, 2));
@@ -438,16 +523,26 @@ fn insert_spartan3a_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
         }
     }
     if bs.frame_present[fi] {
-        panic!("FRAME {fi} SET TWICE");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("frame {fi} set twice"),
+        });
     }
     bs.frame_present.set(fi, true);
+    Ok(())
 }
 
-fn insert_spartan6_bram_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+fn insert_spartan6_bram_frame(
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
     let frame_len = bs.bram_frame_len;
-    assert_eq!(frame_len % 16, 0);
+    ensure_eq!(frame_len % 16, 0, offset);
     let frame_words = frame_len / 16;
-    assert_eq!(data.len(), frame_words * 2);
+    ensure_eq!(data.len(), frame_words * 2, offset);
+    ensure!(fi < bs.bram_frame_present.len(), offset);
     let frame = bs.bram_frame_mut(fi);
     for i in 0..frame_words {
         let word = u16::from_be_bytes(*array_ref!(data, i * 2, 2));
@@ -458,16 +553,24 @@ fn insert_spartan6_bram_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
         }
     }
     if bs.bram_frame_present[fi] {
-        panic!("BRAM FRAME {fi} SET TWICE");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("bram frame {fi} set twice"),
+        });
     }
     bs.bram_frame_present.set(fi, true);
+    Ok(())
 }
 
-fn insert_spartan6_iob_frame(bs: &mut DieBitstream, data: &[u8]) {
+fn insert_spartan6_iob_frame(
+    bs: &mut DieBitstream,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
     let frame_len = bs.iob.len();
-    assert_eq!(frame_len % 16, 0);
+    ensure_eq!(frame_len % 16, 0, offset);
     let frame_words = frame_len / 16;
-    assert_eq!(data.len(), frame_words * 2);
+    ensure_eq!(data.len(), frame_words * 2, offset);
     for i in 0..frame_words {
         let word = u16::from_be_bytes(*array_ref!(data, i * 2, 2));
         let bits: BitArray<u16, Lsb0> = BitArray::new(word);
@@ -477,18 +580,31 @@ fn insert_spartan6_iob_frame(bs: &mut DieBitstream, data: &[u8]) {
         }
     }
     if bs.iob_present {
-        panic!("IOB FRAME SET TWICE");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: "iob frame set twice".to_string(),
+        });
     }
     bs.iob_present = true;
+    Ok(())
 }
 
-fn fixup_spartan6_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+fn fixup_spartan6_frame(
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
+    ensure!(fi < bs.frame_present.len(), offset);
     if !bs.frame_present[fi] {
-        panic!("FIXUP ON NOT PRESENT FRAME {fi}");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("fixup on not present frame {fi}"),
+        });
     }
     let frame_len = bs.frame_len;
     let frame_words = frame_len / 16;
-    assert_eq!(data.len(), frame_words * 2);
+    ensure_eq!(data.len(), frame_words * 2, offset);
     let pos = fi * bs.frame_len;
     let frame = &bs.frame_data[pos..pos + bs.frame_len];
     for i in 0..frame_words {
@@ -501,12 +617,19 @@ fn fixup_spartan6_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
             }
         }
     }
+    Ok(())
 }
 
-fn insert_virtex4_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
+fn insert_virtex4_frame(
+    bs: &mut DieBitstream,
+    fi: usize,
+    data: &[u8],
+    offset: usize,
+) -> Result<(), BitstreamError> {
     let frame_len = bs.frame_len;
     let frame_words = frame_len.div_ceil(32);
-    assert_eq!(data.len(), frame_words * 4);
+    ensure_eq!(data.len(), frame_words * 4, offset);
+    ensure!(fi < bs.frame_present.len(), offset);
     let frame = bs.frame_mut(fi);
     for i in 0..frame_words {
         let word = u32::from_be_bytes(*array_ref!(data, i * 4, 4));
@@ -517,13 +640,23 @@ fn insert_virtex4_frame(bs: &mut DieBitstream, fi: usize, data: &[u8]) {
         }
     }
     if bs.frame_present[fi] {
-        panic!("FRAME {fi} SET TWICE");
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: format!("frame {fi} set twice"),
+        });
     }
     bs.frame_present.set(fi, true);
+    Ok(())
 }
 
-fn parse_virtex_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
-    let mut packets = PacketParser::new(bs.kind, data, key);
+fn parse_virtex_bitstream(
+    bs: &mut Bitstream,
+    data: &[u8],
+    key: &KeyData,
+    strict: bool,
+    warnings: &mut Vec<BitstreamError>,
+) -> Result<(), BitstreamError> {
+    let mut packets = PacketParser::new(bs.kind, data, key, strict);
     let kind = bs.kind;
     let bs = bs.die.first_mut().unwrap();
     let far_dict: HashMap<_, _> = bs
@@ -533,13 +666,13 @@ fn parse_virtex_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         .map(|(i, f)| (virtex_far(f.addr), i))
         .collect();
 
-    assert_eq!(packets.next(), Some(Packet::DummyWord));
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
+    packets.expect(Packet::DummyWord)?;
+    packets.expect(Packet::SyncWord)?;
     if packets.peek() == Some(Packet::LoutDebug(0)) {
-        packets.next();
+        packets.next_packet()?;
         bs.mode = BitstreamMode::Debug;
     }
-    assert_eq!(packets.next(), Some(Packet::CmdRcrc));
+    packets.expect(Packet::CmdRcrc)?;
     let frame_words = if kind == DeviceKind::Virtex {
         bs.frame_len.div_ceil(32) + 1
     } else {
@@ -549,80 +682,90 @@ fn parse_virtex_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
     let flr = (frame_words - 1) as u32;
     let mut early_dghigh = false;
     if packets.peek() == Some(Packet::CmdDGHigh) {
-        packets.next();
+        packets.next_packet()?;
         early_dghigh = true;
         bs.regs.insert(Reg::FakeEarlyGhigh, 1);
         let mut nops = 0;
         while let Some(Packet::Nop) = packets.peek() {
-            packets.next();
+            packets.next_packet()?;
             nops += 1;
         }
-        assert_eq!(nops, flr + 1);
+        ensure_eq!(nops, flr + 1, packets.start());
     }
-    assert_eq!(packets.next(), Some(Packet::Flr(flr)));
-    match packets.next() {
+    packets.expect(Packet::Flr(flr))?;
+    match packets.next_packet()? {
         Some(Packet::Cor0(val)) => {
             bs.regs.insert(Reg::Cor0, val);
         }
-        p => panic!("expected cor0 got {p:?}"),
+        p => return Err(packets.unexpected("cor0", p)),
     }
     if kind != DeviceKind::Virtex {
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Idcode(val)) => {
                 bs.regs.insert(Reg::Idcode, val);
             }
-            p => panic!("expected idcode got {p:?}"),
+            p => return Err(packets.unexpected("idcode", p)),
         }
     }
     // TODO: validate?
-    let _mask = match packets.next() {
+    let _mask = match packets.next_packet()? {
         Some(Packet::Mask(val)) => val,
-        p => panic!("expected mask got {p:?}"),
+        p => return Err(packets.unexpected("mask", p)),
     };
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::CmdSwitch) => {
             bs.regs.insert(Reg::FakeHasSwitch, 1);
         }
         Some(Packet::CmdNull) => (),
-        p => panic!("expected switch or null got {p:?}"),
+        p => return Err(packets.unexpected("switch or null", p)),
     }
 
     // main loop
     let mut fi = 0;
     let mut last_frame = None;
     if bs.mode == BitstreamMode::Debug {
-        assert_eq!(packets.next(), Some(Packet::Far(0)));
-        assert_eq!(packets.next(), Some(Packet::CmdWcfg));
+        packets.expect(Packet::Far(0))?;
+        packets.expect(Packet::CmdWcfg)?;
         loop {
-            let val = match packets.next() {
+            let val = match packets.next_packet()? {
                 Some(Packet::Fdri(val)) => {
-                    assert_eq!(val.len(), frame_bytes);
+                    ensure_eq!(val.len(), frame_bytes, packets.start());
                     val
                 }
                 Some(Packet::Crc) if kind == DeviceKind::Virtex => {
+                    ensure!(fi != 0, packets.start());
                     fi -= 1;
                     break;
                 }
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
             if kind == DeviceKind::Virtex {
-                match packets.next() {
+                match packets.next_packet()? {
                     Some(Packet::Crc) => (),
                     Some(Packet::Far(far)) => {
-                        assert_eq!(far_dict[&far], fi);
+                        ensure_eq!(
+                            lookup_far(&far_dict, far, packets.start())?,
+                            fi,
+                            packets.start()
+                        );
                         continue;
                     }
-                    p => panic!("expected crc/far got {p:?}"),
+                    p => return Err(packets.unexpected("crc/far", p)),
                 }
             }
             if let Some(Packet::LoutDebug(far)) = packets.peek() {
-                assert_eq!(far_dict[&far], fi);
-                packets.next();
-                insert_virtex_frame(kind, bs, fi, &val);
+                packets.next_packet()?;
+                ensure_eq!(
+                    lookup_far(&far_dict, far, packets.start())?,
+                    fi,
+                    packets.start()
+                );
+                insert_virtex_frame(kind, bs, fi, &val, packets.start())?;
                 fi += 1;
             } else {
                 if kind == DeviceKind::Virtex {
-                    panic!("expected loutdebug got {p:?}", p = packets.next());
+                    let p = packets.next_packet()?;
+                    return Err(packets.unexpected("loutdebug", p));
                 }
                 break;
             }
@@ -632,169 +775,215 @@ fn parse_virtex_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         loop {
             match packets.peek() {
                 Some(Packet::Far(far)) => {
-                    packets.next();
-                    fi = far_dict[&far];
+                    packets.next_packet()?;
+                    fi = lookup_far(&far_dict, far, packets.start())?;
                 }
                 Some(Packet::CmdWcfg) => {
-                    packets.next();
-                    assert_ne!(state, State::Wcfg);
+                    packets.next_packet()?;
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
-                    match packets.next() {
+                    match packets.next_packet()? {
                         Some(Packet::Far(far)) => {
-                            fi = far_dict[&far];
+                            fi = lookup_far(&far_dict, far, packets.start())?;
                         }
-                        p => panic!("expected far got {p:?}"),
+                        p => return Err(packets.unexpected("far", p)),
                     }
                 }
                 _ => break,
             }
             match packets.peek() {
                 Some(Packet::CmdWcfg) => {
-                    assert_ne!(state, State::Wcfg);
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
-                    packets.next();
+                    packets.next_packet()?;
                 }
                 Some(Packet::CmdMfwr) => {
-                    assert_ne!(state, State::Mfwr);
+                    ensure!(state != State::Mfwr, packets.start());
                     bs.mode = BitstreamMode::Compress;
                     state = State::Mfwr;
-                    packets.next();
+                    packets.next_packet()?;
                 }
                 _ => (),
             }
             match (packets.peek(), state) {
                 (Some(Packet::Fdri(val)), State::Wcfg) => {
-                    packets.next();
+                    packets.next_packet()?;
                     let frames = val.len() / frame_bytes;
-                    assert_eq!(val.len() % frame_bytes, 0);
+                    ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                    ensure!(!val.is_empty(), packets.start());
                     for i in 0..(frames - 1) {
                         let pos = i * frame_bytes;
-                        insert_virtex_frame(kind, bs, fi, &val[pos..pos + frame_bytes]);
+                        insert_virtex_frame(
+                            kind,
+                            bs,
+                            fi,
+                            &val[pos..pos + frame_bytes],
+                            packets.start(),
+                        )?;
                         fi += 1;
                     }
                     let last = val[(frames - 1) * frame_bytes..].to_vec();
                     last_frame = Some(last);
                 }
                 (Some(Packet::Mfwr(2)), State::Mfwr) => {
-                    packets.next();
-                    insert_virtex_frame(kind, bs, fi, last_frame.as_ref().unwrap());
+                    packets.next_packet()?;
+                    insert_virtex_frame(
+                        kind,
+                        bs,
+                        fi,
+                        last_frame
+                            .as_deref()
+                            .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                        packets.start(),
+                    )?;
                 }
                 (Some(Packet::Far(_)), _) => continue,
                 (Some(Packet::Key(key)), State::Wcfg) => {
                     bs.mode = BitstreamMode::Encrypt;
                     bs.regs.insert(Reg::Key, key);
-                    packets.next();
-                    assert_eq!(packets.next(), Some(Packet::Nop));
-                    match packets.next() {
+                    packets.next_packet()?;
+                    packets.expect(Packet::Nop)?;
+                    match packets.next_packet()? {
                         Some(Packet::Cbc(iv)) => {
                             bs.iv = iv;
                         }
-                        p => panic!("expected iv got {p:?}"),
+                        p => return Err(packets.unexpected("iv", p)),
                     }
-                    match packets.next() {
+                    match packets.next_packet()? {
                         Some(Packet::EncFdri(val)) => {
                             let frames = val.len() / frame_bytes;
-                            assert_eq!(val.len() % frame_bytes, 0);
+                            ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                            ensure!(!val.is_empty(), packets.start());
                             for i in 0..(frames - 1) {
                                 let pos = i * frame_bytes;
-                                insert_virtex_frame(kind, bs, fi, &val[pos..pos + frame_bytes]);
+                                insert_virtex_frame(
+                                    kind,
+                                    bs,
+                                    fi,
+                                    &val[pos..pos + frame_bytes],
+                                    packets.start(),
+                                )?;
                                 fi += 1;
                             }
                             let last = val[(frames - 1) * frame_bytes..].to_vec();
                             last_frame = Some(last);
                         }
-                        p => panic!("expected fdri got {p:?}"),
+                        p => return Err(packets.unexpected("fdri", p)),
                     }
                     break;
                 }
-                (p, _) => {
-                    panic!("UNEXPECTED PACKET IN STATE {state:?}: {p:#x?}");
+                _ => {
+                    let p = packets.next_packet()?;
+                    return Err(packets.unexpected(format!("packet valid in {state:?} state"), p));
                 }
             }
         }
         if kind == DeviceKind::Virtex {
-            assert_eq!(packets.next(), Some(Packet::Crc));
+            packets.expect(Packet::Crc)?;
         }
     }
 
     if kind != DeviceKind::Virtex {
-        assert_eq!(packets.next(), Some(Packet::CmdGRestore));
+        packets.expect(Packet::CmdGRestore)?;
     }
     if !early_dghigh {
-        assert_eq!(packets.next(), Some(Packet::CmdDGHigh));
+        packets.expect(Packet::CmdDGHigh)?;
     }
 
     if kind == DeviceKind::Virtex {
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Fdri(val)) => {
-                assert_eq!(val.len(), frame_bytes);
-                assert!(val.iter().all(|&x| x == 0));
+                ensure_eq!(val.len(), frame_bytes, packets.start());
+                ensure!(val.iter().all(|&x| x == 0), packets.start());
             }
-            p => panic!("expected fdri got {p:?}"),
+            p => return Err(packets.unexpected("fdri", p)),
         }
         if !bs.frame_present[fi] {
-            insert_virtex_frame(kind, bs, fi, &last_frame.unwrap());
-        }
-        assert!(bs.frame_present.all());
+            insert_virtex_frame(
+                kind,
+                bs,
+                fi,
+                last_frame
+                    .as_deref()
+                    .ok_or_else(|| packets.malformed("missing final frame"))?,
+                packets.start(),
+            )?;
+        }
+        ensure!(bs.frame_present.all(), packets.start());
     } else {
-        assert!(bs.frame_present.all());
+        ensure!(bs.frame_present.all(), packets.start());
 
         if !early_dghigh {
             let mut nops = 0;
             while let Some(Packet::Nop) = packets.peek() {
-                packets.next();
+                packets.next_packet()?;
                 nops += 1;
             }
 
             if packets.peek() == Some(Packet::CmdWcfg) {
                 bs.regs.insert(Reg::FakeFreezeDciNops, nops);
-                packets.next();
+                packets.next_packet()?;
                 while let Some(Packet::Far(far)) = packets.peek() {
-                    packets.next();
-                    match packets.next() {
+                    packets.next_packet()?;
+                    match packets.next_packet()? {
                         Some(Packet::Fdri(val)) => {
                             let frames = val.len() / frame_bytes;
-                            assert_eq!(val.len() % frame_bytes, 0);
-                            fi = far_dict[&far];
+                            ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                            ensure!(!val.is_empty(), packets.start());
+                            fi = lookup_far(&far_dict, far, packets.start())?;
                             for i in 0..(frames - 1) {
                                 let pos = i * frame_bytes;
-                                fixup_virtex_frame(kind, bs, fi, &val[pos..pos + frame_bytes]);
+                                fixup_virtex_frame(
+                                    kind,
+                                    bs,
+                                    fi,
+                                    &val[pos..pos + frame_bytes],
+                                    packets.start(),
+                                )?;
                                 fi += 1;
                             }
                         }
-                        p => panic!("expected fdri got {p:?}"),
+                        p => return Err(packets.unexpected("fdri", p)),
                     }
                 }
             } else {
-                assert_eq!(nops, flr + 1);
+                ensure_eq!(nops, flr + 1, packets.start());
             }
         }
 
         if packets.peek() == Some(Packet::CmdGRestore) {
             bs.regs.insert(Reg::FakeDoubleGrestore, 1);
-            packets.next();
+            packets.next_packet()?;
         }
     }
 
-    assert_eq!(packets.next(), Some(Packet::CmdStart));
-    match packets.next() {
+    packets.expect(Packet::CmdStart)?;
+    match packets.next_packet()? {
         Some(Packet::Ctl0(val)) => {
             bs.regs.insert(Reg::Ctl0, val);
         }
-        p => panic!("expected ctl0 got {p:?}"),
+        p => return Err(packets.unexpected("ctl0", p)),
     }
-    assert_eq!(packets.next(), Some(Packet::Crc));
+    packets.expect(Packet::Crc)?;
     if kind != DeviceKind::Virtex {
-        assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        packets.expect(Packet::CmdDesynch)?;
     }
     for _ in 0..4 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    assert_eq!(packets.next(), None);
+    packets.expect_end()?;
+    warnings.extend(packets.take_warnings());
+    Ok(())
 }
 
-fn parse_spartan3a_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
-    let mut packets = PacketParser::new(bs.kind, data, key);
+fn parse_spartan3a_bitstream(
+    bs: &mut Bitstream,
+    data: &[u8],
+    key: &KeyData,
+    strict: bool,
+    warnings: &mut Vec<BitstreamError>,
+) -> Result<(), BitstreamError> {
+    let mut packets = PacketParser::new(bs.kind, data, key, strict);
     let bs = bs.die.first_mut().unwrap();
     let far_dict: HashMap<_, _> = bs
         .frame_info
@@ -804,132 +993,136 @@ fn parse_spartan3a_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         .collect();
 
     for _ in 0..16 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
+    packets.expect(Packet::SyncWord)?;
     if packets.peek() == Some(Packet::LoutDebug(0)) {
-        packets.next();
+        packets.next_packet()?;
         bs.mode = BitstreamMode::Debug;
     }
-    assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    match packets.next() {
+    packets.expect(Packet::CmdRcrc)?;
+    packets.expect(Packet::Nop)?;
+    match packets.next_packet()? {
         Some(Packet::Cor2(val)) => {
             bs.regs.insert(Reg::Cor2, val);
         }
-        p => panic!("expected cor2 got {p:?}"),
+        p => return Err(packets.unexpected("cor2", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::CclkFrequency(val)) => {
             bs.regs.insert(Reg::CclkFrequency, val);
         }
-        p => panic!("expected cclk frequency got {p:?}"),
+        p => return Err(packets.unexpected("cclk frequency", p)),
     }
     let frame_bytes = bs.frame_len / 8;
     let flr = (bs.frame_len / 16 - 1) as u32;
-    assert_eq!(packets.next(), Some(Packet::Flr(flr)));
-    match packets.next() {
+    packets.expect(Packet::Flr(flr))?;
+    match packets.next_packet()? {
         Some(Packet::Cor1(val)) => {
             bs.regs.insert(Reg::Cor1, val);
         }
-        p => panic!("expected cor1 got {p:?}"),
+        p => return Err(packets.unexpected("cor1", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Idcode(val)) => {
             bs.regs.insert(Reg::Idcode, val);
         }
-        p => panic!("expected idcode got {p:?}"),
+        p => return Err(packets.unexpected("idcode", p)),
     }
-    let _mask = match packets.next() {
+    let _mask = match packets.next_packet()? {
         Some(Packet::Mask(val)) => val,
-        p => panic!("expected mask got {p:?}"),
+        p => return Err(packets.unexpected("mask", p)),
     };
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Ctl0(val)) => {
             bs.regs.insert(Reg::Ctl0, val);
         }
-        p => panic!("expected ctl0 got {p:?}"),
+        p => return Err(packets.unexpected("ctl0", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Powerdown(val)) => {
             bs.regs.insert(Reg::Powerdown, val);
         }
-        p => panic!("expected powerdown got {p:?}"),
+        p => return Err(packets.unexpected("powerdown", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::HcOpt(val)) => {
             bs.regs.insert(Reg::HcOpt, val);
         }
-        p => panic!("expected hc opt got {p:?}"),
+        p => return Err(packets.unexpected("hc opt", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::PuGwe(val)) => {
             bs.regs.insert(Reg::PuGwe, val);
         }
-        p => panic!("expected pu gwe got {p:?}"),
+        p => return Err(packets.unexpected("pu gwe", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::PuGts(val)) => {
             bs.regs.insert(Reg::PuGts, val);
         }
-        p => panic!("expected pu gts got {p:?}"),
+        p => return Err(packets.unexpected("pu gts", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Mode(val)) => {
             bs.regs.insert(Reg::Mode, val);
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::General1(val)) => {
                     bs.regs.insert(Reg::General1, val);
                 }
-                p => panic!("expected general1 got {p:?}"),
+                p => return Err(packets.unexpected("general1", p)),
             }
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::General2(val)) => {
                     bs.regs.insert(Reg::General2, val);
                 }
-                p => panic!("expected general2 got {p:?}"),
+                p => return Err(packets.unexpected("general2", p)),
             }
         }
         Some(Packet::Nop) => {
             for _ in 0..5 {
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
             }
         }
-        p => panic!("expected mode got {p:?}"),
+        p => return Err(packets.unexpected("mode", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::SeuOpt(val)) => {
             bs.regs.insert(Reg::SeuOpt, val);
         }
-        p => panic!("expected seuopt got {p:?}"),
+        p => return Err(packets.unexpected("seuopt", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::RbCrcSw(val)) => {
             bs.regs.insert(Reg::RbCrcSw, val);
         }
-        p => panic!("expected rbcrcsw got {p:?}"),
+        p => return Err(packets.unexpected("rbcrcsw", p)),
     }
 
     // main loop
     if bs.mode == BitstreamMode::Debug {
-        assert_eq!(packets.next(), Some(Packet::Far(0)));
-        assert_eq!(packets.next(), Some(Packet::CmdWcfg));
+        packets.expect(Packet::Far(0))?;
+        packets.expect(Packet::CmdWcfg)?;
         let mut fi = 0;
         loop {
-            let val = match packets.next() {
+            let val = match packets.next_packet()? {
                 Some(Packet::Fdri(val)) => {
-                    assert_eq!(val.len(), frame_bytes);
+                    ensure_eq!(val.len(), frame_bytes, packets.start());
                     val
                 }
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
             if let Some(Packet::LoutDebug(far)) = packets.peek() {
-                assert_eq!(far_dict[&far], fi);
-                packets.next();
-                insert_spartan3a_frame(bs, fi, &val);
+                packets.next_packet()?;
+                ensure_eq!(
+                    lookup_far(&far_dict, far, packets.start())?,
+                    fi,
+                    packets.start()
+                );
+                insert_spartan3a_frame(bs, fi, &val, packets.start())?;
                 fi += 1;
             } else {
-                assert!(val.iter().all(|&x| x == 0));
+                ensure!(val.iter().all(|&x| x == 0), packets.start());
                 break;
             }
         }
@@ -939,91 +1132,119 @@ fn parse_spartan3a_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         loop {
             let far = match packets.peek() {
                 Some(Packet::Far(far)) => {
-                    packets.next();
+                    packets.next_packet()?;
                     far
                 }
                 Some(Packet::CmdWcfg) => {
-                    packets.next();
-                    assert_ne!(state, State::Wcfg);
+                    packets.next_packet()?;
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
-                    match packets.next() {
+                    match packets.next_packet()? {
                         Some(Packet::Far(far)) => far,
-                        p => panic!("expected far got {p:?}"),
+                        p => return Err(packets.unexpected("far", p)),
                     }
                 }
                 _ => break,
             };
             match packets.peek() {
                 Some(Packet::CmdWcfg) => {
-                    assert_ne!(state, State::Wcfg);
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
-                    packets.next();
+                    packets.next_packet()?;
                 }
                 Some(Packet::CmdMfwr) => {
-                    assert_ne!(state, State::Mfwr);
+                    ensure!(state != State::Mfwr, packets.start());
                     bs.mode = BitstreamMode::Compress;
                     state = State::Mfwr;
-                    packets.next();
+                    packets.next_packet()?;
                 }
                 _ => (),
             }
-            match (packets.next(), state) {
+            match (packets.next_packet()?, state) {
                 (Some(Packet::Fdri(val)), State::Wcfg) => {
                     let frames = val.len() / frame_bytes;
-                    assert_eq!(val.len() % frame_bytes, 0);
+                    ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                    ensure!(!val.is_empty(), packets.start());
                     if frames > 1 {
-                        let fi = far_dict[&far];
+                        let fi = lookup_far(&far_dict, far, packets.start())?;
                         for i in 0..(frames - 1) {
                             let pos = i * frame_bytes;
-                            insert_spartan3a_frame(bs, fi + i, &val[pos..pos + frame_bytes]);
+                            insert_spartan3a_frame(
+                                bs,
+                                fi + i,
+                                &val[pos..pos + frame_bytes],
+                                packets.start(),
+                            )?;
                         }
                     }
                     mfwr_frame = Some(val[(frames - 1) * frame_bytes..].to_vec());
                 }
                 (Some(Packet::Mfwr(4)), State::Mfwr) => {
-                    let fi = far_dict[&far];
-                    assert_ne!(bs.frame_info[fi].addr.typ, 1);
-                    insert_spartan3a_frame(bs, fi, mfwr_frame.as_ref().unwrap());
+                    let fi = lookup_far(&far_dict, far, packets.start())?;
+                    ensure!(bs.frame_info[fi].addr.typ != 1, packets.start());
+                    insert_spartan3a_frame(
+                        bs,
+                        fi,
+                        mfwr_frame
+                            .as_deref()
+                            .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                        packets.start(),
+                    )?;
                 }
                 (Some(Packet::Mfwr(14)), State::Mfwr) => {
-                    let fi = far_dict[&far];
-                    assert_eq!(bs.frame_info[fi].addr.typ, 1);
-                    insert_spartan3a_frame(bs, fi, mfwr_frame.as_ref().unwrap());
+                    let fi = lookup_far(&far_dict, far, packets.start())?;
+                    ensure_eq!(bs.frame_info[fi].addr.typ, 1, packets.start());
+                    insert_spartan3a_frame(
+                        bs,
+                        fi,
+                        mfwr_frame
+                            .as_deref()
+                            .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                        packets.start(),
+                    )?;
                 }
-                p => {
-                    panic!("UNEXPECTED PACKET IN STATE {state:?}: {p:#x?}");
+                (p, _) => {
+                    return Err(packets.unexpected(format!("packet valid in {state:?} state"), p));
                 }
             }
         }
     }
 
-    assert!(bs.frame_present.all());
+    ensure!(bs.frame_present.all(), packets.start());
 
-    assert_eq!(packets.next(), Some(Packet::Crc));
-    assert_eq!(packets.next(), Some(Packet::CmdGRestore));
-    assert_eq!(packets.next(), Some(Packet::CmdDGHigh));
+    packets.expect(Packet::Crc)?;
+    packets.expect(Packet::CmdGRestore)?;
+    packets.expect(Packet::CmdDGHigh)?;
     for _ in 0..4 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    assert_eq!(packets.next(), Some(Packet::CmdStart));
-    let _mask2 = match packets.next() {
+    packets.expect(Packet::CmdStart)?;
+    let _mask2 = match packets.next_packet()? {
         Some(Packet::Mask(val)) => val,
-        p => panic!("expected mask got {p:?}"),
+        p => return Err(packets.unexpected("mask", p)),
     };
-    let _ctl0_2 = match packets.next() {
+    let _ctl0_2 = match packets.next_packet()? {
         Some(Packet::Ctl0(val)) => val,
-        p => panic!("expected ctl0 got {p:?}"),
+        p => return Err(packets.unexpected("ctl0", p)),
     };
-    assert_eq!(packets.next(), Some(Packet::Crc));
-    assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+    packets.expect(Packet::Crc)?;
+    packets.expect(Packet::CmdDesynch)?;
     for _ in 0..16 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    assert_eq!(packets.next(), None);
+    packets.expect_end()?;
+    warnings.extend(packets.take_warnings());
+    Ok(())
 }
 
-fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
-    let mut packets = PacketParser::new(bs.kind, data, key);
+fn parse_spartan6_bitstream(
+    bs: &mut Bitstream,
+    data: &[u8],
+    key: &KeyData,
+    strict: bool,
+    warnings: &mut Vec<BitstreamError>,
+) -> Result<(), BitstreamError> {
+    let mut packets = PacketParser::new(bs.kind, data, key, strict);
     let bs = bs.die.first_mut().unwrap();
     let far_dict: HashMap<_, _> = bs
         .frame_info
@@ -1039,158 +1260,158 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         .collect();
 
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
-    assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-    assert_eq!(packets.next(), Some(Packet::Nop));
+    packets.expect(Packet::SyncWord)?;
+    packets.expect(Packet::CmdRcrc)?;
+    packets.expect(Packet::Nop)?;
     let flr = (bs.iob.len() / 16) as u32;
-    assert_eq!(packets.next(), Some(Packet::Flr(flr)));
-    match packets.next() {
+    packets.expect(Packet::Flr(flr))?;
+    match packets.next_packet()? {
         Some(Packet::Cor1(val)) => {
             bs.regs.insert(Reg::Cor1, val);
         }
-        p => panic!("expected cor1 got {p:?}"),
+        p => return Err(packets.unexpected("cor1", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Cor2(val)) => {
             bs.regs.insert(Reg::Cor2, val);
         }
-        p => panic!("expected cor2 got {p:?}"),
+        p => return Err(packets.unexpected("cor2", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Idcode(val)) => {
             bs.regs.insert(Reg::Idcode, val);
         }
-        p => panic!("expected idcode got {p:?}"),
+        p => return Err(packets.unexpected("idcode", p)),
     }
-    let _mask = match packets.next() {
+    let _mask = match packets.next_packet()? {
         Some(Packet::Mask(val)) => val,
-        p => panic!("expected mask got {p:?}"),
+        p => return Err(packets.unexpected("mask", p)),
     };
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Ctl0(val)) => {
             bs.regs.insert(Reg::Ctl0, val);
         }
-        p => panic!("expected ctl0 got {p:?}"),
+        p => return Err(packets.unexpected("ctl0", p)),
     }
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Cbc(_)) => {
-            assert_eq!(bs.regs[&Reg::Ctl0] & 0x40, 0x40);
+            ensure_eq!(bs.regs[&Reg::Ctl0] & 0x40, 0x40, packets.start());
             bs.mode = BitstreamMode::Encrypt;
         }
         Some(Packet::Nop) => {
-            assert_eq!(bs.regs[&Reg::Ctl0] & 0x40, 0);
+            ensure_eq!(bs.regs[&Reg::Ctl0] & 0x40, 0, packets.start());
             for _ in 0..8 {
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
             }
         }
-        p => panic!("expected cbc or nop got {p:?}"),
+        p => return Err(packets.unexpected("cbc or nop", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::CclkFrequency(val)) => {
             bs.regs.insert(Reg::CclkFrequency, val);
         }
-        p => panic!("expected cclk frequency got {p:?}"),
+        p => return Err(packets.unexpected("cclk frequency", p)),
     }
     if let Some(Packet::CclkFrequency(val)) = packets.peek() {
-        packets.next();
+        packets.next_packet()?;
         bs.regs.insert(Reg::CclkFrequency, val);
         bs.regs.insert(Reg::FakeDoubleCclkFrequency, 1);
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Powerdown(val)) => {
             bs.regs.insert(Reg::Powerdown, val);
         }
-        p => panic!("expected powerdown got {p:?}"),
+        p => return Err(packets.unexpected("powerdown", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::EyeMask(val)) => {
             bs.regs.insert(Reg::EyeMask, val);
         }
-        p => panic!("expected eyemask got {p:?}"),
+        p => return Err(packets.unexpected("eyemask", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::HcOpt(val)) => {
             bs.regs.insert(Reg::HcOpt, val);
         }
-        p => panic!("expected hc opt got {p:?}"),
+        p => return Err(packets.unexpected("hc opt", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Timer(val)) => {
             bs.regs.insert(Reg::Timer, val);
         }
-        p => panic!("expected timer got {p:?}"),
+        p => return Err(packets.unexpected("timer", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::PuGwe(val)) => {
             bs.regs.insert(Reg::PuGwe, val);
         }
-        p => panic!("expected pu gwe got {p:?}"),
+        p => return Err(packets.unexpected("pu gwe", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::PuGts(val)) => {
             bs.regs.insert(Reg::PuGts, val);
         }
-        p => panic!("expected pu gts got {p:?}"),
+        p => return Err(packets.unexpected("pu gts", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Mode(val)) => {
             bs.regs.insert(Reg::Mode, val);
         }
-        p => panic!("expected mode got {p:?}"),
+        p => return Err(packets.unexpected("mode", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::General1(val)) => {
             bs.regs.insert(Reg::General1, val);
         }
-        p => panic!("expected general1 got {p:?}"),
+        p => return Err(packets.unexpected("general1", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::General2(val)) => {
             bs.regs.insert(Reg::General2, val);
         }
-        p => panic!("expected general2 got {p:?}"),
+        p => return Err(packets.unexpected("general2", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::General3(val)) => {
             bs.regs.insert(Reg::General3, val);
         }
-        p => panic!("expected general3 got {p:?}"),
+        p => return Err(packets.unexpected("general3", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::General4(val)) => {
             bs.regs.insert(Reg::General4, val);
         }
-        p => panic!("expected general4 got {p:?}"),
+        p => return Err(packets.unexpected("general4", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::General5(val)) => {
             bs.regs.insert(Reg::General5, val);
         }
-        p => panic!("expected general5 got {p:?}"),
+        p => return Err(packets.unexpected("general5", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::SeuOpt(val)) => {
             bs.regs.insert(Reg::SeuOpt, val);
         }
-        p => panic!("expected seuopt got {p:?}"),
+        p => return Err(packets.unexpected("seuopt", p)),
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::RbCrcSw(val)) => {
             bs.regs.insert(Reg::RbCrcSw, val);
         }
-        p => panic!("expected rbcrcsw got {p:?}"),
+        p => return Err(packets.unexpected("rbcrcsw", p)),
     }
     if let Some(Packet::Testmode(val)) = packets.peek() {
-        packets.next();
+        packets.next_packet()?;
         bs.regs.insert(Reg::Testmode, val);
     } else if !bs.regs.contains_key(&Reg::FakeDoubleCclkFrequency) {
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
     }
 
     // main loop
@@ -1222,39 +1443,51 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                     } else if far == 0x20000000 {
                         Frame::Iob
                     } else {
-                        panic!("weird FAR {far:08x}")
+                        packets.next_packet()?;
+                        return Err(BitstreamError::InvalidFrameAddress {
+                            offset: packets.start(),
+                            far,
+                        });
                     };
-                    packets.next();
+                    packets.next_packet()?;
                     skip = 1;
                 }
                 Some(Packet::CmdWcfg) => {
-                    packets.next();
-                    assert_ne!(state, State::Wcfg);
+                    packets.next_packet()?;
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
                 }
                 Some(Packet::CmdMfwr) => {
-                    packets.next();
-                    assert_ne!(state, State::Mfwr);
+                    packets.next_packet()?;
+                    ensure!(state != State::Mfwr, packets.start());
                     state = State::Mfwr;
                     for _ in 0..8 {
-                        assert_eq!(packets.next(), Some(Packet::Nop));
+                        packets.expect(Packet::Nop)?;
                     }
                 }
                 Some(Packet::Mfwr(4)) => {
-                    packets.next();
+                    packets.next_packet()?;
                     bs.mode = BitstreamMode::Compress;
-                    assert_eq!(state, State::Mfwr);
+                    ensure_eq!(state, State::Mfwr, packets.start());
                     let Frame::Main(fi) = frame else {
-                        panic!("mfwr in weird frame {frame:?}");
+                        return Err(packets.malformed(format!("mfwr in weird frame {frame:?}")));
                     };
-                    insert_spartan3a_frame(bs, fi, last_frame.as_ref().unwrap());
+                    insert_spartan3a_frame(
+                        bs,
+                        fi,
+                        last_frame
+                            .as_deref()
+                            .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                        packets.start(),
+                    )?;
                 }
                 Some(Packet::Fdri(orig_data)) => {
                     let mut data = &orig_data[..];
-                    packets.next();
+                    packets.next_packet()?;
                     while !data.is_empty() {
                         match frame {
                             Frame::Main(fi) => {
+                                ensure!(data.len() >= frame_bytes, packets.start());
                                 let cur = &data[..frame_bytes];
                                 data = &data[frame_bytes..];
                                 if skip != 0 {
@@ -1263,7 +1496,14 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                                         frame = Frame::Bram(0);
                                     }
                                 } else {
-                                    insert_spartan3a_frame(bs, fi, last_frame.as_ref().unwrap());
+                                    insert_spartan3a_frame(
+                                        bs,
+                                        fi,
+                                        last_frame.as_deref().ok_or_else(|| {
+                                            packets.malformed("no preceding frame")
+                                        })?,
+                                        packets.start(),
+                                    )?;
                                     frame = Frame::Main(fi + 1);
                                     if fi == bs.frame_info.len() - 1
                                         || bs.frame_info[fi + 1].addr.region
@@ -1275,9 +1515,10 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                                 last_frame = Some(cur.to_vec());
                             }
                             Frame::Bram(fi) => {
+                                ensure!(data.len() >= bram_frame_bytes, packets.start());
                                 let cur = &data[..bram_frame_bytes];
                                 data = &data[bram_frame_bytes..];
-                                insert_spartan6_bram_frame(bs, fi, cur);
+                                insert_spartan6_bram_frame(bs, fi, cur, packets.start())?;
                                 if fi == bs.bram_frame_info.len() - 1 {
                                     frame = Frame::Iob;
                                 } else {
@@ -1285,12 +1526,19 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                                 }
                             }
                             Frame::Iob => {
-                                assert_eq!(data.len(), iob_frame_bytes + 2);
-                                insert_spartan6_iob_frame(bs, &data[..iob_frame_bytes]);
+                                ensure_eq!(data.len(), iob_frame_bytes + 2, packets.start());
+                                insert_spartan6_iob_frame(
+                                    bs,
+                                    &data[..iob_frame_bytes],
+                                    packets.start(),
+                                )?;
                                 data = &[];
                                 frame = Frame::None;
                             }
-                            _ => panic!("fdri {l} with no frame", l = data.len()),
+                            Frame::None => {
+                                return Err(packets
+                                    .malformed(format!("fdri {l} with no frame", l = data.len())));
+                            }
                         }
                     }
                 }
@@ -1299,13 +1547,13 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
         }
         if bs.mode != BitstreamMode::Compress {
             for _ in 0..24 {
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
             }
         }
     } else {
-        assert_eq!(packets.next(), Some(Packet::Far(0)));
-        assert_eq!(packets.next(), Some(Packet::CmdWcfg));
-        match packets.next() {
+        packets.expect(Packet::Far(0))?;
+        packets.expect(Packet::CmdWcfg)?;
+        match packets.next_packet()? {
             Some(Packet::EncFdri(orig_data)) => {
                 let num = orig_data.len() / 2;
                 let mut data = &orig_data[..];
@@ -1314,6 +1562,7 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                 while !data.is_empty() {
                     match frame {
                         Frame::Main(fi) => {
+                            ensure!(data.len() >= frame_bytes, packets.start());
                             let cur = &data[..frame_bytes];
                             data = &data[frame_bytes..];
                             if skip != 0 {
@@ -1322,7 +1571,14 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                                     frame = Frame::Bram(0);
                                 }
                             } else {
-                                insert_spartan3a_frame(bs, fi, last_frame.as_ref().unwrap());
+                                insert_spartan3a_frame(
+                                    bs,
+                                    fi,
+                                    last_frame
+                                        .as_deref()
+                                        .ok_or_else(|| packets.malformed("no preceding frame"))?,
+                                    packets.start(),
+                                )?;
                                 frame = Frame::Main(fi + 1);
                                 if fi == bs.frame_info.len() - 1
                                     || bs.frame_info[fi + 1].addr.region
@@ -1334,9 +1590,10 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                             last_frame = Some(cur.to_vec());
                         }
                         Frame::Bram(fi) => {
+                            ensure!(data.len() >= bram_frame_bytes, packets.start());
                             let cur = &data[..bram_frame_bytes];
                             data = &data[bram_frame_bytes..];
-                            insert_spartan6_bram_frame(bs, fi, cur);
+                            insert_spartan6_bram_frame(bs, fi, cur, packets.start())?;
                             if fi == bs.bram_frame_info.len() - 1 {
                                 frame = Frame::Iob;
                             } else {
@@ -1344,12 +1601,19 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                             }
                         }
                         Frame::Iob => {
-                            assert_eq!(data.len(), iob_frame_bytes + 2);
-                            insert_spartan6_iob_frame(bs, &data[..iob_frame_bytes]);
+                            ensure_eq!(data.len(), iob_frame_bytes + 2, packets.start());
+                            insert_spartan6_iob_frame(
+                                bs,
+                                &data[..iob_frame_bytes],
+                                packets.start(),
+                            )?;
                             data = &[];
                             frame = Frame::None;
                         }
-                        _ => panic!("fdri {l} with no frame", l = data.len()),
+                        Frame::None => {
+                            return Err(packets
+                                .malformed(format!("fdri {l} with no frame", l = data.len())));
+                        }
                     }
                 }
                 let mut real_num = num + 2;
@@ -1358,60 +1622,61 @@ fn parse_spartan6_bitstream(bs: &mut Bitstream, data: &[u8], key: &KeyData) {
                 }
                 let exp_nops = 24 - (real_num - num - 2);
                 for _ in 0..exp_nops {
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
             }
-            p => panic!("expected enc fdri got {p:?}"),
+            p => return Err(packets.unexpected("enc fdri", p)),
         }
     }
 
     let mut first = true;
     while let Some(Packet::Far(far)) = packets.peek() {
-        packets.next();
-        let Some(&fi) = far_dict.get(&far) else {
-            panic!("weird fixup FAR {far:08x}");
-        };
+        packets.next_packet()?;
+        let fi = lookup_far(&far_dict, far, packets.start())?;
         if first {
-            assert_eq!(packets.next(), Some(Packet::CmdWcfg));
+            packets.expect(Packet::CmdWcfg)?;
             first = false;
         }
-        let data = match packets.next() {
+        let data = match packets.next_packet()? {
             Some(Packet::Fdri(val)) => val,
-            p => panic!("expected fdri got {p:?}"),
+            p => return Err(packets.unexpected("fdri", p)),
         };
         let mut fi = fi;
         let frames = data.len() / frame_bytes;
-        assert_eq!(data.len() % frame_bytes, 0);
+        ensure_eq!(data.len() % frame_bytes, 0, packets.start());
+        ensure!(!data.is_empty(), packets.start());
         for i in 0..(frames - 1) {
             let pos = i * frame_bytes;
-            fixup_spartan6_frame(bs, fi, &data[pos..pos + frame_bytes]);
+            fixup_spartan6_frame(bs, fi, &data[pos..pos + frame_bytes], packets.start())?;
             fi += 1;
         }
     }
 
-    assert_eq!(packets.next(), Some(Packet::CmdGRestore));
-    assert_eq!(packets.next(), Some(Packet::CmdDGHigh));
+    packets.expect(Packet::CmdGRestore)?;
+    packets.expect(Packet::CmdDGHigh)?;
     for _ in 0..4 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    assert_eq!(packets.next(), Some(Packet::CmdGRestore));
-    assert_eq!(packets.next(), Some(Packet::CmdStart));
-    let _mask = match packets.next() {
+    packets.expect(Packet::CmdGRestore)?;
+    packets.expect(Packet::CmdStart)?;
+    let _mask = match packets.next_packet()? {
         Some(Packet::Mask(val)) => val,
-        p => panic!("expected mask got {p:?}"),
+        p => return Err(packets.unexpected("mask", p)),
     };
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Ctl0(val)) => {
             bs.regs.insert(Reg::Ctl0, val);
         }
-        p => panic!("expected ctl0 got {p:?}"),
+        p => return Err(packets.unexpected("ctl0", p)),
     }
-    assert_eq!(packets.next(), Some(Packet::Crc));
-    assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+    packets.expect(Packet::Crc)?;
+    packets.expect(Packet::CmdDesynch)?;
     for _ in 0..14 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    assert_eq!(packets.next(), None);
+    packets.expect_end()?;
+    warnings.extend(packets.take_warnings());
+    Ok(())
 }
 
 fn parse_virtex4_bitstream(
@@ -1420,9 +1685,11 @@ fn parse_virtex4_bitstream(
     key: &KeyData,
     geom: &BitstreamGeom,
     die_index: usize,
-) {
+    strict: bool,
+    warnings: &mut Vec<BitstreamError>,
+) -> Result<(), BitstreamError> {
     let die = geom.die_order[die_index];
-    let mut packets = PacketParser::new(bs.kind, data, key);
+    let mut packets = PacketParser::new(bs.kind, data, key, strict);
     let kind = bs.kind;
     let diebs = &mut bs.die[die];
     let far_dict: HashMap<_, _> = diebs
@@ -1446,259 +1713,259 @@ fn parse_virtex4_bitstream(
     let mut trim_regs = 0;
 
     if kind == DeviceKind::Virtex4 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
-        assert_eq!(packets.next(), Some(Packet::SyncWord));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        match packets.next() {
+        packets.expect(Packet::DummyWord)?;
+        packets.expect(Packet::SyncWord)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::CmdRcrc)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+        match packets.next_packet()? {
             Some(Packet::Cor0(val)) => {
                 diebs.regs.insert(Reg::Cor0, val);
             }
-            p => panic!("expected cor0 got {p:?}"),
+            p => return Err(packets.unexpected("cor0", p)),
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Idcode(val)) => {
                 diebs.regs.insert(Reg::Idcode, val);
             }
-            p => panic!("expected idcode got {p:?}"),
+            p => return Err(packets.unexpected("idcode", p)),
         }
         if matches!(packets.peek(), Some(Packet::Mask(_))) {
-            let mask = match packets.next() {
+            let mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Ctl0(val)) => {
-                    assert_eq!(mask & 0x40, 0x40);
-                    assert_eq!(val & 0x40, 0x40);
+                    ensure_eq!(mask & 0x40, 0x40, packets.start());
+                    ensure_eq!(val & 0x40, 0x40, packets.start());
                     ctl0 = (ctl0 & !mask) | (val & mask);
                 }
-                p => panic!("expected ctl0 got {p:?}"),
+                p => return Err(packets.unexpected("ctl0", p)),
             }
             diebs.mode = BitstreamMode::Encrypt;
         }
-        assert_eq!(packets.next(), Some(Packet::CmdSwitch));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        match packets.next() {
+        packets.expect(Packet::CmdSwitch)?;
+        packets.expect(Packet::Nop)?;
+        match packets.next_packet()? {
             Some(Packet::CmdNull) => {
                 // the following block is just missing in xqr4v?!?
             }
             Some(Packet::Mask(mask)) => {
-                match packets.next() {
+                match packets.next_packet()? {
                     Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-                    p => panic!("expected ctl0 got {p:?}"),
+                    p => return Err(packets.unexpected("ctl0", p)),
                 }
                 for _ in 0..1150 {
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
-                let mask = match packets.next() {
+                let mask = match packets.next_packet()? {
                     Some(Packet::Mask(val)) => val,
-                    p => panic!("expected mask got {p:?}"),
+                    p => return Err(packets.unexpected("mask", p)),
                 };
-                match packets.next() {
+                match packets.next_packet()? {
                     Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-                    p => panic!("expected ctl0 got {p:?}"),
+                    p => return Err(packets.unexpected("ctl0", p)),
                 }
-                assert_eq!(packets.next(), Some(Packet::CmdNull));
+                packets.expect(Packet::CmdNull)?;
             }
-            p => panic!("expected mask got {p:?}"),
+            p => return Err(packets.unexpected("mask", p)),
         };
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     } else {
         for _ in 0..8 {
-            assert_eq!(packets.next(), Some(Packet::DummyWord));
+            packets.expect(Packet::DummyWord)?;
         }
-        assert_eq!(packets.next(), Some(Packet::WidthDetect));
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
-        assert_eq!(packets.next(), Some(Packet::SyncWord));
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::WidthDetect)?;
+        packets.expect(Packet::DummyWord)?;
+        packets.expect(Packet::DummyWord)?;
+        packets.expect(Packet::SyncWord)?;
+        packets.expect(Packet::Nop)?;
         if matches!(packets.peek(), Some(Packet::Bspi(_))) {
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Bspi(val)) => {
                     diebs.regs.insert(Reg::Bspi, val);
-                    assert_eq!(packets.next(), Some(Packet::CmdBspiRead));
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::CmdBspiRead)?;
+                    packets.expect(Packet::Nop)?;
                 }
-                p => panic!("expected bspi got {p:?}"),
+                p => return Err(packets.unexpected("bspi", p)),
             }
         }
         if matches!(packets.peek(), Some(Packet::Mask(_))) {
-            let mask = match packets.next() {
+            let mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-                p => panic!("expected ctl0 got {p:?}"),
+                p => return Err(packets.unexpected("ctl0", p)),
             }
-            assert_eq!(ctl0 & 0x40, 0x40);
+            ensure_eq!(ctl0 & 0x40, 0x40, packets.start());
             if kind == DeviceKind::Virtex7 {
-                match packets.next() {
+                match packets.next_packet()? {
                     Some(Packet::Cor1(val)) => {
                         diebs.regs.insert(Reg::Cor1, val);
                     }
-                    p => panic!("expected cor1 got {p:?}"),
+                    p => return Err(packets.unexpected("cor1", p)),
                 }
                 for _ in 0..14 {
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
             } else {
                 for _ in 0..16 {
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
             }
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Cbc(val)) => diebs.iv = val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
 
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Dwc(_)) => (),
-                p => panic!("expected dwc got {p:?}"),
+                p => return Err(packets.unexpected("dwc", p)),
             };
 
             diebs.regs.insert(Reg::FakeEncrypted, 1);
         }
         if kind == DeviceKind::Virtex7 {
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Timer(val)) => {
                     diebs.regs.insert(Reg::Timer, val);
                 }
-                p => panic!("expected timer got {p:?}"),
+                p => return Err(packets.unexpected("timer", p)),
             }
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::WBStar(val)) => {
                 diebs.regs.insert(Reg::WbStar, val);
             }
-            p => panic!("expected wbstar got {p:?}"),
+            p => return Err(packets.unexpected("wbstar", p)),
         }
-        assert_eq!(packets.next(), Some(Packet::CmdNull));
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::CmdNull)?;
+        packets.expect(Packet::Nop)?;
         while matches!(packets.peek(), Some(Packet::Mask(_))) {
-            let _mask = match packets.next() {
+            let _mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Trim1(val)) => {
                     diebs.regs.insert(Reg::Trim1, val);
                 }
                 Some(Packet::Trim(val)) => {
                     diebs.regs.insert(Reg::Trim0, val);
                 }
-                p => panic!("expected ctl2 or trim got {p:?}"),
+                p => return Err(packets.unexpected("ctl2 or trim", p)),
             }
         }
         while matches!(packets.peek(), Some(Packet::Cor1(_))) {
-            let cor1 = match packets.next() {
+            let cor1 = match packets.next_packet()? {
                 Some(Packet::Cor1(val)) => val,
-                p => panic!("expected cor1 got {p:?}"),
+                p => return Err(packets.unexpected("cor1", p)),
             };
-            let _mask = match packets.next() {
+            let _mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
             let reg = match cor1 {
                 0x1000 => Reg::Trim0,
                 0x1400 => Reg::Trim1,
                 0x1800 => Reg::Trim2,
-                _ => panic!("unexpected cor1 val {cor1:#x}"),
+                _ => return Err(packets.malformed(format!("unexpected cor1 val {cor1:#x}"))),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Trim(val)) => {
                     diebs.regs.insert(reg, val);
                 }
-                p => panic!("expected trim got {p:?}"),
+                p => return Err(packets.unexpected("trim", p)),
             }
             trim_regs += 1;
         }
         if kind != DeviceKind::Virtex5 && matches!(packets.peek(), Some(Packet::Testmode(_))) {
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Testmode(val)) => {
                     diebs.regs.insert(Reg::Testmode, val);
                 }
-                p => panic!("expected testmode got {p:?}"),
+                p => return Err(packets.unexpected("testmode", p)),
             }
         }
-        assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::CmdRcrc)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
         if kind != DeviceKind::Virtex7 {
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Timer(val)) => {
                     diebs.regs.insert(Reg::Timer, val);
                 }
-                p => panic!("expected timer got {p:?}"),
+                p => return Err(packets.unexpected("timer", p)),
             }
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::RbCrcSw(val)) => {
                 diebs.regs.insert(Reg::RbCrcSw, val);
             }
-            p => panic!("expected rbcrcsw got {p:?}"),
+            p => return Err(packets.unexpected("rbcrcsw", p)),
         }
         if kind == DeviceKind::Virtex5 && matches!(packets.peek(), Some(Packet::Testmode(_))) {
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Testmode(val)) => {
                     diebs.regs.insert(Reg::Testmode, val);
                 }
-                p => panic!("expected testmode got {p:?}"),
+                p => return Err(packets.unexpected("testmode", p)),
             }
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Cor0(val)) => {
                 diebs.regs.insert(Reg::Cor0, val);
             }
-            p => panic!("expected cor0 got {p:?}"),
+            p => return Err(packets.unexpected("cor0", p)),
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Cor1(val)) => {
                 diebs.regs.insert(Reg::Cor1, val);
             }
-            p => panic!("expected cor1 got {p:?}"),
+            p => return Err(packets.unexpected("cor1", p)),
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Idcode(val)) => {
                 diebs.regs.insert(Reg::Idcode, val);
             }
-            p => panic!("expected idcode got {p:?}"),
+            p => return Err(packets.unexpected("idcode", p)),
         }
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::CmdSwitch) => (),
             Some(Packet::CmdFallEdge) => {
                 diebs.regs.insert(Reg::FakeFallEdge, 0);
-                assert_eq!(packets.next(), Some(Packet::CmdSwitch));
+                packets.expect(Packet::CmdSwitch)?;
             }
-            p => panic!("expected switch got {p:?}"),
+            p => return Err(packets.unexpected("switch", p)),
         }
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        let mask = match packets.next() {
+        packets.expect(Packet::Nop)?;
+        let mask = match packets.next_packet()? {
             Some(Packet::Mask(val)) => val,
-            p => panic!("expected mask got {p:?}"),
+            p => return Err(packets.unexpected("mask", p)),
         };
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-            p => panic!("expected ctl0 got {p:?}"),
+            p => return Err(packets.unexpected("ctl0", p)),
         }
         if kind == DeviceKind::Virtex5 && (ctl0 & 0x40) != 0 {
             diebs.mode = BitstreamMode::Encrypt;
         }
-        let _mask = match packets.next() {
+        let _mask = match packets.next_packet()? {
             Some(Packet::Mask(val)) => val,
-            p => panic!("expected mask got {p:?}"),
+            p => return Err(packets.unexpected("mask", p)),
         };
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Ctl1(val)) => {
                 diebs.regs.insert(Reg::Ctl1, val);
             }
-            p => panic!("expected ctl1 got {p:?}"),
+            p => return Err(packets.unexpected("ctl1", p)),
         }
         for _ in 0..8 {
-            assert_eq!(packets.next(), Some(Packet::Nop));
+            packets.expect(Packet::Nop)?;
         }
     }
 
@@ -1707,52 +1974,53 @@ fn parse_virtex4_bitstream(
     if diebs.mode == BitstreamMode::Encrypt {
         let data;
         if kind == DeviceKind::Virtex4 {
-            assert_eq!(packets.next(), Some(Packet::Far(0)));
-            assert_eq!(packets.next(), Some(Packet::CmdWcfg));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            let init_iv = match packets.next() {
+            packets.expect(Packet::Far(0))?;
+            packets.expect(Packet::CmdWcfg)?;
+            packets.expect(Packet::Nop)?;
+            let init_iv = match packets.next_packet()? {
                 Some(Packet::Cbc(val)) => val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
             diebs.iv = init_iv.clone();
-            let init_data = match packets.next() {
+            let init_data = match packets.next_packet()? {
                 Some(Packet::EncFdri(val)) => val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
             for _ in 0..9 {
-                assert_eq!(packets.next(), Some(Packet::Nop));
-            }
-            assert_eq!(packets.next(), Some(Packet::Crc));
-            assert_eq!(packets.next(), Some(Packet::CmdNull));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            assert_eq!(packets.next(), Some(Packet::Far(0)));
-            assert_eq!(packets.next(), Some(Packet::CmdWcfg));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            assert_eq!(packets.next(), Some(Packet::Cbc(init_iv)));
-            data = match packets.next() {
+                packets.expect(Packet::Nop)?;
+            }
+            packets.expect(Packet::Crc)?;
+            packets.expect(Packet::CmdNull)?;
+            packets.expect(Packet::Nop)?;
+            packets.expect(Packet::Far(0))?;
+            packets.expect(Packet::CmdWcfg)?;
+            packets.expect(Packet::Nop)?;
+            packets.expect(Packet::Cbc(init_iv))?;
+            data = match packets.next_packet()? {
                 Some(Packet::EncFdri(val)) => val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
-            assert_eq!(init_data.len(), 12 * 41 * 4);
-            assert!(data.starts_with(&init_data));
+            ensure_eq!(init_data.len(), 12 * 41 * 4, packets.start());
+            ensure!(data.starts_with(&init_data), packets.start());
         } else {
-            assert_eq!(packets.next(), Some(Packet::Far(0)));
-            assert_eq!(packets.next(), Some(Packet::CmdWcfg));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            match packets.next() {
+            packets.expect(Packet::Far(0))?;
+            packets.expect(Packet::CmdWcfg)?;
+            packets.expect(Packet::Nop)?;
+            match packets.next_packet()? {
                 Some(Packet::Cbc(val)) => diebs.iv = val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
-            data = match packets.next() {
+            data = match packets.next_packet()? {
                 Some(Packet::EncFdri(val)) => val,
-                p => panic!("expected fdri got {p:?}"),
+                p => return Err(packets.unexpected("fdri", p)),
             };
         }
         let frames = data.len() / frame_bytes;
         let tail = data.len() % frame_bytes;
-        assert!(tail < 16);
+        ensure!(frames != 0, packets.start());
+        ensure!(tail < 16, packets.start());
         let tail = &data[(data.len() - tail)..];
-        assert!(tail.iter().all(|&x| x == 0));
+        ensure!(tail.iter().all(|&x| x == 0), packets.start());
         let mut skip = 0;
         let mut fi = 0;
         for i in 0..(frames - 1) {
@@ -1761,7 +2029,7 @@ fn parse_virtex4_bitstream(
                 continue;
             }
             let pos = i * frame_bytes;
-            insert_virtex4_frame(diebs, fi, &data[pos..pos + frame_bytes]);
+            insert_virtex4_frame(diebs, fi, &data[pos..pos + frame_bytes], packets.start())?;
             let cur_reg = diebs.frame_info[fi].addr.region;
             let cur_typ = diebs.frame_info[fi].addr.typ;
             fi += 1;
@@ -1773,9 +2041,9 @@ fn parse_virtex4_bitstream(
             }
         }
         let last = data[(frames - 1) * frame_bytes..].to_vec();
-        assert!(last.iter().all(|&x| x == 0));
+        ensure!(last.iter().all(|&x| x == 0), packets.start());
         for _ in 0..9 {
-            assert_eq!(packets.next(), Some(Packet::Nop));
+            packets.expect(Packet::Nop)?;
         }
     } else {
         let mut fi = 0;
@@ -1786,72 +2054,78 @@ fn parse_virtex4_bitstream(
         loop {
             match packets.peek() {
                 Some(Packet::Far(far)) => {
-                    packets.next();
-                    fi = far_dict[&far];
+                    packets.next_packet()?;
+                    fi = lookup_far(&far_dict, far, packets.start())?;
                     skip = 0;
                 }
                 Some(Packet::CmdWcfg) => {
-                    packets.next();
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.next_packet()?;
+                    packets.expect(Packet::Nop)?;
                     if kind != DeviceKind::Virtex7 {
-                        assert_ne!(state, State::Wcfg);
+                        ensure!(state != State::Wcfg, packets.start());
                     }
                     state = State::Wcfg;
-                    match packets.next() {
+                    match packets.next_packet()? {
                         Some(Packet::Far(far)) => {
-                            fi = far_dict[&far];
+                            fi = lookup_far(&far_dict, far, packets.start())?;
                             skip = 0;
                         }
-                        p => panic!("expected far got {p:?}"),
+                        p => return Err(packets.unexpected("far", p)),
                     }
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
                 Some(Packet::CmdMfwr) => (),
                 _ => break,
             }
             match packets.peek() {
                 Some(Packet::CmdWcfg) => {
-                    assert_ne!(state, State::Wcfg);
+                    ensure!(state != State::Wcfg, packets.start());
                     state = State::Wcfg;
-                    packets.next();
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.next_packet()?;
+                    packets.expect(Packet::Nop)?;
                 }
                 Some(Packet::CmdMfwr) => {
-                    assert_ne!(state, State::Mfwr);
+                    ensure!(state != State::Mfwr, packets.start());
                     diebs.mode = BitstreamMode::Compress;
                     state = State::Mfwr;
-                    packets.next();
+                    packets.next_packet()?;
                     let num_nops = match kind {
                         DeviceKind::Virtex4 | DeviceKind::Virtex5 => 1,
                         DeviceKind::Virtex6 | DeviceKind::Virtex7 => 12,
                         _ => unreachable!(),
                     };
                     for _ in 0..num_nops {
-                        assert_eq!(packets.next(), Some(Packet::Nop));
+                        packets.expect(Packet::Nop)?;
                     }
                     first_mf = true;
                 }
                 Some(Packet::Nop) => {
-                    packets.next();
+                    packets.next_packet()?;
                 }
                 _ => (),
             }
             match (packets.peek(), state) {
                 (Some(Packet::CmdWcfg), State::Wcfg) => {
-                    packets.next();
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.next_packet()?;
+                    packets.expect(Packet::Nop)?;
                 }
                 (Some(Packet::Fdri(val)), State::Wcfg) => {
-                    packets.next();
+                    packets.next_packet()?;
                     let frames = val.len() / frame_bytes;
-                    assert_eq!(val.len() % frame_bytes, 0);
+                    ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                    ensure!(!val.is_empty(), packets.start());
                     for i in 0..(frames - 1) {
                         if skip != 0 {
                             skip -= 1;
                             continue;
                         }
                         let pos = i * frame_bytes;
-                        insert_virtex4_frame(diebs, fi, &val[pos..pos + frame_bytes]);
+                        insert_virtex4_frame(
+                            diebs,
+                            fi,
+                            &val[pos..pos + frame_bytes],
+                            packets.start(),
+                        )?;
                         let cur_reg = diebs.frame_info[fi].addr.region;
                         let cur_typ = diebs.frame_info[fi].addr.typ;
                         fi += 1;
@@ -1866,7 +2140,7 @@ fn parse_virtex4_bitstream(
                     last_frame = Some(last);
                 }
                 (Some(Packet::Mfwr(mf)), State::Mfwr) => {
-                    assert_eq!(
+                    ensure_eq!(
                         mf,
                         match kind {
                             DeviceKind::Virtex4 => 2,
@@ -1884,110 +2158,125 @@ fn parse_virtex4_bitstream(
                                 },
                             _ => unreachable!(),
                         },
+                        packets.start()
                     );
                     first_mf = false;
-                    packets.next();
-                    insert_virtex4_frame(diebs, fi, last_frame.as_ref().unwrap());
+                    packets.next_packet()?;
+                    insert_virtex4_frame(
+                        diebs,
+                        fi,
+                        last_frame
+                            .as_deref()
+                            .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                        packets.start(),
+                    )?;
                     if kind == DeviceKind::Virtex7 && diebs.frame_info[fi].addr.typ == 1 {
                         for _ in 0..8 {
-                            assert_eq!(packets.next(), Some(Packet::Nop));
+                            packets.expect(Packet::Nop)?;
                         }
                     }
                 }
-                (p, _) => {
-                    panic!("UNEXPECTED PACKET IN STATE {state:?}: {p:#x?}");
+                _ => {
+                    let p = packets.next_packet()?;
+                    return Err(packets.unexpected(format!("packet valid in {state:?} state"), p));
                 }
             }
         }
     }
-    match packets.next() {
+    match packets.next_packet()? {
         Some(Packet::Crc) => (),
         Some(Packet::CmdRcrc) => {
             diebs.regs.insert(Reg::FakeIgnoreCrc, 1);
         }
-        p => panic!("expected CRC or RCRC got {p:?}"),
+        p => return Err(packets.unexpected("CRC or RCRC", p)),
     }
     if matches!(kind, DeviceKind::Virtex6 | DeviceKind::Virtex7) {
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-    }
-    assert_eq!(packets.next(), Some(Packet::CmdGRestore));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdDGHigh));
-    if kind != DeviceKind::Virtex4 && diebs.mode == BitstreamMode::Compress {
-        let _mask = match packets.next() {
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+    }
+    packets.expect(Packet::CmdGRestore)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdDGHigh)?;
+    // a compressed bitstream without any repeated frames has no MFWR, and can only be
+    // recognized by this trailer.
+    if kind != DeviceKind::Virtex4
+        && (diebs.mode == BitstreamMode::Compress
+            || matches!(packets.peek(), Some(Packet::Mask(_))))
+    {
+        diebs.mode = BitstreamMode::Compress;
+        let _mask = match packets.next_packet()? {
             Some(Packet::Mask(val)) => val,
-            p => panic!("expected mask got {p:?}"),
+            p => return Err(packets.unexpected("mask", p)),
         };
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Ctl1(val)) => {
                 diebs.regs.insert(Reg::Ctl1, val);
             }
-            p => panic!("expected ctl1 got {p:?}"),
+            p => return Err(packets.unexpected("ctl1", p)),
         }
     }
     for _ in 0..100 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
     if matches!(kind, DeviceKind::Virtex4 | DeviceKind::Virtex5) {
-        assert_eq!(packets.next(), Some(Packet::CmdGRestore));
+        packets.expect(Packet::CmdGRestore)?;
     }
     match kind {
         DeviceKind::Virtex4 => {
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            assert_eq!(packets.next(), Some(Packet::CmdNull));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            let _final_far = match packets.next() {
+            packets.expect(Packet::Nop)?;
+            packets.expect(Packet::CmdNull)?;
+            packets.expect(Packet::Nop)?;
+            let _final_far = match packets.next_packet()? {
                 Some(Packet::Far(val)) => val,
-                p => panic!("expected far got {p:?}"),
+                p => return Err(packets.unexpected("far", p)),
             };
-            assert_eq!(packets.next(), Some(Packet::CmdStart));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            let mask = match packets.next() {
+            packets.expect(Packet::CmdStart)?;
+            packets.expect(Packet::Nop)?;
+            let mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-                p => panic!("expected ctl0 got {p:?}"),
+                p => return Err(packets.unexpected("ctl0", p)),
             }
-            assert_eq!(packets.next(), Some(Packet::Crc));
-            assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+            packets.expect(Packet::Crc)?;
+            packets.expect(Packet::CmdDesynch)?;
             for _ in 0..16 {
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
             }
         }
         DeviceKind::Virtex5 | DeviceKind::Virtex6 | DeviceKind::Virtex7 => {
             if kind == DeviceKind::Virtex5 {
                 for _ in 0..30 {
-                    assert_eq!(packets.next(), Some(Packet::Nop));
+                    packets.expect(Packet::Nop)?;
                 }
             }
-            assert_eq!(packets.next(), Some(Packet::CmdStart));
-            assert_eq!(packets.next(), Some(Packet::Nop));
-            let _final_far = match packets.next() {
+            packets.expect(Packet::CmdStart)?;
+            packets.expect(Packet::Nop)?;
+            let _final_far = match packets.next_packet()? {
                 Some(Packet::Far(val)) => val,
-                p => panic!("expected far got {p:?}"),
+                p => return Err(packets.unexpected("far", p)),
             };
-            let mask = match packets.next() {
+            let mask = match packets.next_packet()? {
                 Some(Packet::Mask(val)) => val,
-                p => panic!("expected mask got {p:?}"),
+                p => return Err(packets.unexpected("mask", p)),
             };
-            match packets.next() {
+            match packets.next_packet()? {
                 Some(Packet::Ctl0(val)) => ctl0 = (ctl0 & !mask) | (val & mask),
-                p => panic!("expected ctl0 got {p:?}"),
+                p => return Err(packets.unexpected("ctl0", p)),
             }
             if diebs.regs.contains_key(&Reg::FakeIgnoreCrc) {
-                assert_eq!(packets.next(), Some(Packet::CmdRcrc));
+                packets.expect(Packet::CmdRcrc)?;
             } else {
-                assert_eq!(packets.next(), Some(Packet::Crc));
+                packets.expect(Packet::Crc)?;
             }
             if matches!(kind, DeviceKind::Virtex6 | DeviceKind::Virtex7) {
-                assert_eq!(packets.next(), Some(Packet::Nop));
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
+                packets.expect(Packet::Nop)?;
             }
             if !diebs.regs.contains_key(&Reg::FakeEncrypted) {
-                assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+                packets.expect(Packet::CmdDesynch)?;
             }
             let mut num_nops = match kind {
                 DeviceKind::Virtex5 => 61,
@@ -2017,7 +2306,7 @@ fn parse_virtex4_bitstream(
             }
             num_nops -= 6 * trim_regs;
             for _ in 0..num_nops {
-                assert_eq!(packets.next(), Some(Packet::Nop));
+                packets.expect(Packet::Nop)?;
             }
         }
         _ => unreachable!(),
@@ -2025,606 +2314,486 @@ fn parse_virtex4_bitstream(
     diebs.regs.insert(Reg::Ctl0, ctl0);
     if die_index != geom.die_order.len() - 1 {
         packets.desync();
-        assert_eq!(packets.next(), Some(Packet::SyncWord));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::CmdShutdown));
-        assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        let subdata = match packets.next() {
+        packets.expect(Packet::SyncWord)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::CmdShutdown)?;
+        packets.expect(Packet::CmdRcrc)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+        let subdata = match packets.next_packet()? {
             Some(Packet::Bout(data)) => data,
-            p => panic!("expected bout got {p:?}"),
+            p => return Err(packets.unexpected("bout", p)),
         };
-        parse_virtex4_bitstream(bs, &subdata, key, geom, die_index + 1);
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::CmdStart));
-        assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        parse_virtex4_bitstream(bs, &subdata, key, geom, die_index + 1, strict, warnings)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::CmdStart)?;
+        packets.expect(Packet::CmdDesynch)?;
     }
     loop {
-        match packets.next() {
+        match packets.next_packet()? {
             Some(Packet::Nop) => (),
             Some(Packet::DummyWord) => break,
-            None => return,
-            p => panic!("expected end got {p:?}"),
+            None => {
+                warnings.extend(packets.take_warnings());
+                return Ok(());
+            }
+            p => return Err(packets.unexpected("end", p)),
         }
     }
-    assert_eq!(die_index, 0);
-    assert!(geom.has_gtz_bot || geom.has_gtz_top);
-    let loader = core::mem::replace(bs, empty(geom));
+    ensure_eq!(die_index, 0, packets.start());
+    ensure!(geom.has_gtz_bot || geom.has_gtz_top, packets.start());
+    let loader = core::mem::replace(bs, Bitstream::new(geom));
     bs.gtz_loader = Some(Box::new(loader));
     for _ in 0..7 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::WidthDetect));
+    packets.expect(Packet::WidthDetect)?;
     for _ in 0..2 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
-    assert_eq!(packets.next(), Some(Packet::Nop));
+    packets.expect(Packet::SyncWord)?;
+    packets.expect(Packet::Nop)?;
     loop {
-        let pkt = packets.next().unwrap();
-        if let Packet::Axss(data) = pkt {
-            let gtz = parse_gtz_bitstream(&data);
-            if geom.has_gtz_bot && !bs.gtz.contains_key(&DirV::S) {
-                bs.gtz.insert(DirV::S, gtz);
-            } else if geom.has_gtz_top && !bs.gtz.contains_key(&DirV::N) {
-                bs.gtz.insert(DirV::N, gtz);
-            } else {
-                panic!("ummm too many GTZ?");
+        match packets.next_packet()? {
+            Some(Packet::Axss(data)) => {
+                let gtz = parse_gtz_bitstream(&data, packets.start())?;
+                if geom.has_gtz_bot && !bs.gtz.contains_key(&DirV::S) {
+                    bs.gtz.insert(DirV::S, gtz);
+                } else if geom.has_gtz_top && !bs.gtz.contains_key(&DirV::N) {
+                    bs.gtz.insert(DirV::N, gtz);
+                } else {
+                    return Err(packets.malformed("too many GTZ bitstreams"));
+                }
             }
-        } else {
-            assert_eq!(pkt, Packet::Crc);
-            break;
+            Some(Packet::Crc) => break,
+            p => return Err(packets.unexpected("axss or crc", p)),
         }
     }
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdDesynch)?;
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
-    consume_virtex4_shutdown(&mut packets, geom, die_index);
-    consume_virtex4_aghigh(&mut packets, geom, die_index);
+    consume_virtex4_shutdown(&mut packets, geom, die_index)?;
+    consume_virtex4_aghigh(&mut packets, geom, die_index)?;
     for _ in 0..100 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
-    }
-    parse_virtex4_bitstream(bs, &data[packets.pos()..], key, geom, die_index)
+        packets.expect(Packet::Nop)?;
+    }
+    warnings.extend(packets.take_warnings());
+    parse_virtex4_bitstream(
+        bs,
+        &data[packets.pos()..],
+        key,
+        geom,
+        die_index,
+        strict,
+        warnings,
+    )
 }
 
-fn parse_gtz_bitstream(data: &[u32]) -> GtzBitstream {
+fn parse_gtz_bitstream(data: &[u32], offset: usize) -> Result<GtzBitstream, BitstreamError> {
+    ensure!(data.len() >= 3, offset);
     let idcode = data[0];
-    assert_eq!(idcode >> 28, 0);
-    assert_eq!(data[1], 0x00010001);
-    let data_len: usize = data[2].try_into().unwrap();
+    ensure_eq!(idcode >> 28, 0, offset);
+    ensure_eq!(data[1], 0x00010001, offset);
+    let data_len = data[2] as usize;
+    ensure!(data_len > 0x21 && data.len() >= 3 + data_len + 3, offset);
     let data_seg = &data[3..(data_len + 3)];
-    let data_seg = data_seg.strip_suffix(&[0; 0x20]).unwrap();
+    let Some(data_seg) = data_seg.strip_suffix(&[0; 0x20]) else {
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: "GTZ data segment missing padding".to_string(),
+        });
+    };
     let data_crc = data_seg[data_seg.len() - 1];
     let data_seg = &data_seg[..data_seg.len() - 1];
     let mut crc = Crc::new(DeviceKind::Virtex7);
     for &w in &data[..3 + data_len - 0x21] {
         crc.update(0, w);
     }
-    assert_eq!(data_crc, crc.get());
+    ensure_eq!(data_crc, crc.get(), offset);
 
-    assert_eq!(data[3 + data_len], idcode | 1 << 28);
-    assert_eq!(data[3 + data_len + 1], 0x00010001);
-    let code_len: usize = data[3 + data_len + 2].try_into().unwrap();
+    ensure_eq!(data[3 + data_len], idcode | 1 << 28, offset);
+    ensure_eq!(data[3 + data_len + 1], 0x00010001, offset);
+    let code_len = data[3 + data_len + 2] as usize;
+    ensure_eq!(3 + data_len + 3 + code_len, data.len(), offset);
+    ensure!(code_len > 0x21, offset);
     let code_seg = &data[3 + data_len + 3..3 + data_len + 3 + code_len];
-    assert_eq!(3 + data_len + 3 + code_len, data.len());
-    let code_seg = code_seg.strip_suffix(&[0; 0x20]).unwrap();
+    let Some(code_seg) = code_seg.strip_suffix(&[0; 0x20]) else {
+        return Err(BitstreamError::Malformed {
+            offset,
+            reason: "GTZ code segment missing padding".to_string(),
+        });
+    };
     let code_crc = code_seg[code_seg.len() - 1];
     let code_seg = &code_seg[..code_seg.len() - 1];
     let mut crc = Crc::new(DeviceKind::Virtex7);
     for &w in &data[3 + data_len..3 + data_len + 3 + code_len - 0x21] {
         crc.update(0, w);
     }
-    assert_eq!(code_crc, crc.get());
-    GtzBitstream {
+    ensure_eq!(code_crc, crc.get(), offset);
+    Ok(GtzBitstream {
         idcode,
         data: data_seg.to_vec(),
         code: code_seg.to_vec(),
-    }
+    })
 }
 
-fn consume_virtex4_shutdown(packets: &mut PacketParser, geom: &BitstreamGeom, die_index: usize) {
+fn consume_virtex4_shutdown(
+    packets: &mut PacketParser,
+    geom: &BitstreamGeom,
+    die_index: usize,
+) -> Result<(), BitstreamError> {
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::WidthDetect));
+    packets.expect(Packet::WidthDetect)?;
     for _ in 0..2 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
-    }
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdShutdown));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdRcrc));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        packets.expect(Packet::DummyWord)?;
+    }
+    packets.expect(Packet::SyncWord)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdShutdown)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdRcrc)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdDesynch)?;
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
     packets.desync();
     if die_index != geom.die_order.len() - 1 {
-        assert_eq!(packets.next(), Some(Packet::SyncWord));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        let subdata = match packets.next() {
+        packets.expect(Packet::SyncWord)?;
+        packets.expect(Packet::Nop)?;
+        let subdata = match packets.next_packet()? {
             Some(Packet::Bout(data)) => data,
-            p => panic!("expected bout got {p:?}"),
+            p => return Err(packets.unexpected("bout", p)),
         };
-        let mut subpackets = PacketParser::new(geom.kind, &subdata, &KeyData::None);
-        consume_virtex4_shutdown(&mut subpackets, geom, die_index + 1);
-        assert_eq!(subpackets.next(), None);
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        let mut subpackets =
+            PacketParser::new(geom.kind, &subdata, &KeyData::None, packets.strict());
+        consume_virtex4_shutdown(&mut subpackets, geom, die_index + 1)?;
+        subpackets.expect_end()?;
+        packets.add_warnings(subpackets.take_warnings());
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::CmdDesynch)?;
         for _ in 0..8 {
-            assert_eq!(packets.next(), Some(Packet::Nop));
+            packets.expect(Packet::Nop)?;
         }
     }
+    Ok(())
 }
 
-fn consume_virtex4_aghigh(packets: &mut PacketParser, geom: &BitstreamGeom, die_index: usize) {
+fn consume_virtex4_aghigh(
+    packets: &mut PacketParser,
+    geom: &BitstreamGeom,
+    die_index: usize,
+) -> Result<(), BitstreamError> {
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
+        packets.expect(Packet::DummyWord)?;
     }
-    assert_eq!(packets.next(), Some(Packet::WidthDetect));
+    packets.expect(Packet::WidthDetect)?;
     for _ in 0..2 {
-        assert_eq!(packets.next(), Some(Packet::DummyWord));
-    }
-    assert_eq!(packets.next(), Some(Packet::SyncWord));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdAGHigh));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::Nop));
-    assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        packets.expect(Packet::DummyWord)?;
+    }
+    packets.expect(Packet::SyncWord)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdAGHigh)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::Nop)?;
+    packets.expect(Packet::CmdDesynch)?;
     for _ in 0..8 {
-        assert_eq!(packets.next(), Some(Packet::Nop));
+        packets.expect(Packet::Nop)?;
     }
     packets.desync();
     if die_index != geom.die_order.len() - 1 {
-        assert_eq!(packets.next(), Some(Packet::SyncWord));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        let subdata = match packets.next() {
+        packets.expect(Packet::SyncWord)?;
+        packets.expect(Packet::Nop)?;
+        let subdata = match packets.next_packet()? {
             Some(Packet::Bout(data)) => data,
-            p => panic!("expected bout got {p:?}"),
+            p => return Err(packets.unexpected("bout", p)),
         };
-        let mut subpackets = PacketParser::new(geom.kind, &subdata, &KeyData::None);
-        consume_virtex4_aghigh(&mut subpackets, geom, die_index + 1);
-        assert_eq!(subpackets.next(), None);
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::Nop));
-        assert_eq!(packets.next(), Some(Packet::CmdDesynch));
+        let mut subpackets =
+            PacketParser::new(geom.kind, &subdata, &KeyData::None, packets.strict());
+        consume_virtex4_aghigh(&mut subpackets, geom, die_index + 1)?;
+        subpackets.expect_end()?;
+        packets.add_warnings(subpackets.take_warnings());
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::Nop)?;
+        packets.expect(Packet::CmdDesynch)?;
         for _ in 0..8 {
-            assert_eq!(packets.next(), Some(Packet::Nop));
+            packets.expect(Packet::Nop)?;
         }
     }
+    Ok(())
 }
 
-fn check_virtex4_ecc(bs: &Bitstream) {
-    for (die, dbs) in &bs.die {
-        for (fi, present) in dbs.frame_present.iter().enumerate() {
-            if !*present {
-                continue;
-            }
-            let fdata = dbs.frame(fi);
-            let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            let flip = finfo.addr.region < 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x280 if !flip => finfo.mask_mode[idx / 0x140],
-                    0..0x280 if flip => finfo.mask_mode[3 - idx / 0x140],
-                    0x280..0x28c => {
-                        recc ^= 1 << (idx - 0x280);
-                        continue;
-                    }
-                    0x28c..0x2a0 => FrameMaskMode::None,
-                    0x2a0..0x520 if !flip => finfo.mask_mode[(idx - 0x20) / 0x140],
-                    0x2a0..0x520 if flip => finfo.mask_mode[3 - (idx - 0x20) / 0x140],
+fn parse_ultrascale_bitstream(
+    bs: &mut Bitstream,
+    data: &[u8],
+    key: &KeyData,
+    geom: &BitstreamGeom,
+    die_index: usize,
+    strict: bool,
+    warnings: &mut Vec<BitstreamError>,
+) -> Result<(), BitstreamError> {
+    let die = geom.die_order[die_index];
+    let mut packets = PacketParser::new(bs.kind, data, key, strict);
+    let kind = bs.kind;
+    let far_dict: HashMap<_, _> = bs.die[die]
+        .frame_info
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            (
+                match kind {
+                    DeviceKind::Ultrascale => ultrascale_far(f.addr),
+                    DeviceKind::UltrascalePlus => ultrascaleplus_far(f.addr),
                     _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::BramV4 => {
-                        let eidx = if flip { 0x520 - 1 - idx } else { idx };
-                        let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
-                        let eidx = eidx % 0x140;
-                        if matches!(
-                            eidx,
-                            8 | 12
-                                | 14
-                                | 19
-                                | 21
-                                | 26
-                                | 27
-                                | 32
-                                | 35
-                                | 39
-                                | 41
-                                | 46
-                                | 48
-                                | 52
-                                | 55
-                                | 59
-                                | 61
-                                | 66
-                                | 68
-                                | 72
-                                | 74
-                                | 79
-                                | 81
-                                | 86
-                                | 88
-                                | 92
-                                | 95
-                                | 99
-                                | 101
-                                | 106
-                                | 108
-                                | 112
-                                | 114
-                                | 119
-                                | 121
-                                | 126
-                                | 200
-                                | 204
-                                | 207
-                                | 211
-                                | 213
-                                | 218
-                                | 220
-                                | 224
-                                | 227
-                                | 231
-                                | 233
-                                | 237
-                                | 240
-                                | 244
-                                | 247
-                                | 251
-                                | 253
-                                | 258
-                                | 260
-                                | 264
-                                | 266
-                                | 271
-                                | 273
-                                | 277
-                                | 280
-                                | 284
-                                | 287
-                                | 291
-                                | 293
-                                | 298
-                                | 300
-                                | 304
-                                | 306
-                                | 311
-                                | 313
-                                | 318
-                        ) {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::DrpV4 => {
-                        let eidx = if flip { 0x520 - 1 - idx } else { idx };
-                        let eidx = if eidx < 0x280 { eidx } else { eidx - 0x20 };
-                        if matches!(eidx % 20, 1..17) {
-                            let midx = eidx / 20 * 20 + 18;
-                            let midx = if midx < 0x280 { midx } else { midx + 0x20 };
-                            let midx = if flip { 0x520 - 1 - midx } else { midx };
-                            if fdata[midx as usize] {
-                                continue;
-                            }
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0x140 {
-                    0x2c0 + idx
-                } else {
-                    0x420 + (idx - 0x140)
-                };
-                ecc ^= 0x800 | code;
+                },
+                i,
+            )
+        })
+        .collect();
+    let frame_bytes = bs.die[die].frame_len / 8;
+
+    let mut ctl0 = 0;
+    let mut mask = 0;
+    let mut fi = None;
+    let mut skip = 0;
+    let mut state = State::None;
+    let mut last_frame: Option<Vec<u8>> = None;
+    while let Some(packet) = packets.next_packet()? {
+        let diebs = &mut bs.die[die];
+        match packet {
+            Packet::DummyWord | Packet::WidthDetect | Packet::SyncWord | Packet::Nop => (),
+            Packet::CmdWcfg => state = State::Wcfg,
+            Packet::CmdMfwr => state = State::Mfwr,
+            Packet::CmdNull
+            | Packet::CmdRcrc
+            | Packet::CmdSwitch
+            | Packet::CmdGRestore
+            | Packet::CmdDGHigh
+            | Packet::CmdAGHigh
+            | Packet::CmdShutdown
+            | Packet::CmdStart
+            | Packet::CmdBspiRead
+            | Packet::Crc => (),
+            Packet::CmdFallEdge => {
+                diebs.regs.insert(Reg::FakeFallEdge, 0);
             }
-            for i in 0..11 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x800;
+            Packet::CmdDesynch => {
+                while packets.peek() == Some(Packet::Nop) {
+                    packets.next_packet()?;
                 }
+                packets.desync();
             }
-            if ecc != recc {
-                eprintln!(
-                    "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
-                    ft = finfo.addr.typ,
-                    fr = finfo.addr.region,
-                    fmaj = finfo.addr.major,
-                    fmin = finfo.addr.minor
-                );
+            Packet::Far(far) => {
+                fi = far_dict.get(&far).copied();
+                skip = 0;
             }
-        }
-    }
-}
-
-fn check_virtex5_ecc(bs: &Bitstream) {
-    for (die, dbs) in &bs.die {
-        for (fi, present) in dbs.frame_present.iter().enumerate() {
-            if !*present {
-                continue;
-            }
-            let fdata = dbs.frame(fi);
-            let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x280 => finfo.mask_mode[0],
-                    0x280..0x28c => {
-                        recc ^= 1 << (idx - 0x280);
+            Packet::Fdri(val) => {
+                ensure_eq!(state, State::Wcfg, packets.start());
+                let Some(mut cfi) = fi else {
+                    return Err(packets.malformed("fdri without valid far"));
+                };
+                ensure_eq!(val.len() % frame_bytes, 0, packets.start());
+                let frames = val.len() / frame_bytes;
+                let Some(last) = frames.checked_sub(1) else {
+                    return Err(packets.malformed("empty fdri"));
+                };
+                for i in 0..last {
+                    if skip != 0 {
+                        skip -= 1;
                         continue;
                     }
-                    0x28c..0x2a0 => FrameMaskMode::None,
-                    0x2a0..0x520 => finfo.mask_mode[1],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::DrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x280 + cbit] {
-                            continue;
-                        }
+                    let pos = i * frame_bytes;
+                    insert_virtex4_frame(
+                        diebs,
+                        cfi,
+                        &val[pos..pos + frame_bytes],
+                        packets.start(),
+                    )?;
+                    let cur_reg = diebs.frame_info[cfi].addr.region;
+                    let cur_typ = diebs.frame_info[cfi].addr.typ;
+                    cfi += 1;
+                    if cfi >= diebs.frame_info.len()
+                        || diebs.frame_info[cfi].addr.region != cur_reg
+                        || diebs.frame_info[cfi].addr.typ != cur_typ
+                    {
+                        skip = 2;
                     }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
                 }
-                let code = if idx < 0x140 {
-                    0x2c0 + idx
-                } else {
-                    0x420 + (idx - 0x140)
+                fi = Some(cfi);
+                last_frame = Some(val[last * frame_bytes..].to_vec());
+            }
+            Packet::Mfwr(_) => {
+                ensure_eq!(state, State::Mfwr, packets.start());
+                diebs.mode = BitstreamMode::Compress;
+                let Some(cfi) = fi else {
+                    return Err(packets.malformed("mfwr without valid far"));
                 };
-                ecc ^= 0x800 | code;
+                insert_virtex4_frame(
+                    diebs,
+                    cfi,
+                    last_frame
+                        .as_deref()
+                        .ok_or_else(|| packets.malformed("mfwr without preceding frame"))?,
+                    packets.start(),
+                )?;
+            }
+            Packet::Mask(val) => mask = val,
+            Packet::Ctl0(val) => {
+                ctl0 = (ctl0 & !mask) | (val & mask);
+                if ctl0 & 0x40 != 0 {
+                    return Err(packets.unsupported("encrypted bitstreams not supported"));
+                }
+            }
+            Packet::Ctl1(val) => {
+                diebs.regs.insert(Reg::Ctl1, val);
             }
-            for i in 0..11 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x800;
-                }
+            Packet::Cor0(val) => {
+                diebs.regs.insert(Reg::Cor0, val);
             }
-            if ecc != recc {
-                eprintln!(
-                    "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
-                    ft = finfo.addr.typ,
-                    fr = finfo.addr.region,
-                    fmaj = finfo.addr.major,
-                    fmin = finfo.addr.minor
-                );
+            Packet::Cor1(val) => {
+                diebs.regs.insert(Reg::Cor1, val);
             }
-        }
-    }
-}
-
-fn check_virtex6_ecc(bs: &Bitstream) {
-    for (die, dbs) in &bs.die {
-        for (fi, present) in dbs.frame_present.iter().enumerate() {
-            if !*present {
-                continue;
+            Packet::Idcode(val) => {
+                diebs.regs.insert(Reg::Idcode, val);
             }
-            let fdata = dbs.frame(fi);
-            let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x500 => finfo.mask_mode[0],
-                    0x500..0x50d => {
-                        recc ^= 1 << (idx - 0x500);
-                        continue;
-                    }
-                    0x50d..0x520 => FrameMaskMode::None,
-                    0x520..0xa20 => finfo.mask_mode[1],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::DrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x500 + cbit] {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x500 + cbit]
-                            && !matches!(idx, 0..0x80 | 0x480..0x5a0 | 0x9a0..0xa20)
-                        {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0x240 {
-                    0x5c0 + idx
-                } else {
-                    0x820 + (idx - 0x240)
-                };
-                ecc ^= 0x1000 | code;
+            Packet::WBStar(val) => {
+                diebs.regs.insert(Reg::WbStar, val);
             }
-            for i in 0..12 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x1000;
-                }
+            Packet::Timer(val) => {
+                diebs.regs.insert(Reg::Timer, val);
             }
-            if ecc != recc {
-                eprintln!(
-                    "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
-                    ft = finfo.addr.typ,
-                    fr = finfo.addr.region,
-                    fmaj = finfo.addr.major,
-                    fmin = finfo.addr.minor
-                );
+            Packet::RbCrcSw(val) => {
+                diebs.regs.insert(Reg::RbCrcSw, val);
             }
-        }
-    }
-}
-
-fn check_virtex7_ecc(bs: &Bitstream) {
-    for (die, dbs) in &bs.die {
-        for (fi, present) in dbs.frame_present.iter().enumerate() {
-            if !*present {
-                continue;
+            Packet::Testmode(val) => {
+                diebs.regs.insert(Reg::Testmode, val);
             }
-            let fdata = dbs.frame(fi);
-            let finfo = &dbs.frame_info[fi];
-            let mut ecc: u32 = 0;
-            let mut recc: u32 = 0;
-            for (idx, bit) in fdata.iter().enumerate() {
-                if !*bit {
-                    continue;
-                }
-                let mask = match idx {
-                    0..0x640 => finfo.mask_mode[0],
-                    0x640..0x64d => {
-                        recc ^= 1 << (idx - 0x640);
-                        continue;
-                    }
-                    0x64d..0x660 => FrameMaskMode::None,
-                    0x660..0xca0 => finfo.mask_mode[1],
-                    _ => unreachable!(),
-                };
-                let idx = idx as u32;
-                match mask {
-                    FrameMaskMode::None => (),
-                    FrameMaskMode::DrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x640 + cbit] {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::PcieLeftDrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) - 28 - 2 * 36 + cframe;
-                        assert_eq!(dbs.frame_info[cfi].addr.minor, cframe as u32);
-                        if dbs.frame(cfi)[0x640 + cbit] {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::CmtDrpHclk(cframe, cbit) => {
-                        let cfi = fi - (finfo.addr.minor as usize) + cframe;
-                        if dbs.frame(cfi)[0x640 + cbit] && matches!(idx, 0..0x600 | 0x6a0..0xca0) {
-                            continue;
-                        }
-                    }
-                    FrameMaskMode::All => continue,
-                    _ => todo!(),
-                }
-                let code = if idx < 0xe0 {
-                    0x320 + idx
-                } else if idx < 0x4c0 {
-                    0x420 + (idx - 0xe0)
-                } else {
-                    0x820 + (idx - 0x4c0)
-                };
-                ecc ^= 0x1000 | code;
+            Packet::Bspi(val) => {
+                diebs.regs.insert(Reg::Bspi, val);
             }
-            for i in 0..12 {
-                if (ecc & (1 << i)) != 0 {
-                    ecc ^= 0x1000;
-                }
+            Packet::Trim(val) => {
+                diebs.regs.insert(Reg::Trim0, val);
             }
-            if ecc != recc {
-                eprintln!(
-                    "ECC MISMATCH at frame {die}.{ft}.{fr}.{fmaj}.{fmin}: computed {ecc:04x} found {recc:04x}",
-                    ft = finfo.addr.typ,
-                    fr = finfo.addr.region,
-                    fmaj = finfo.addr.major,
-                    fmin = finfo.addr.minor
-                );
+            Packet::Trim1(val) => {
+                diebs.regs.insert(Reg::Trim1, val);
+            }
+            Packet::Bout(subdata) => {
+                ensure!(die_index != geom.die_order.len() - 1, packets.start());
+                parse_ultrascale_bitstream(
+                    bs,
+                    &subdata,
+                    key,
+                    geom,
+                    die_index + 1,
+                    strict,
+                    warnings,
+                )?;
             }
+            p => return Err(packets.unexpected("configuration packet", Some(p))),
         }
     }
+    bs.die[die].regs.insert(Reg::Ctl0, ctl0);
+    warnings.extend(packets.take_warnings());
+    Ok(())
 }
 
-fn parse_ultrascale_bitstream(bs: &Bitstream, data: &[u8], key: &KeyData) {
-    let packets = PacketParser::new(bs.kind, data, key);
-    for packet in packets {
-        if let Packet::Fdri(data) = packet {
-            println!("PACKET FDRI {l}", l = data.len());
-        } else {
-            println!("PACKET {packet:x?}");
+fn parse_impl(
+    geom: &BitstreamGeom,
+    data: &[u8],
+    key: &KeyData,
+    strict: bool,
+) -> Result<(Bitstream, Vec<BitstreamError>), BitstreamError> {
+    let mut res = Bitstream::new(geom);
+    let mut warnings = vec![];
+    let ecc_mismatches = match res.kind {
+        DeviceKind::Xc2000 => {
+            parse_xc2000_bitstream(&mut res, data)?;
+            vec![]
+        }
+        DeviceKind::Xc4000 | DeviceKind::S40Xl => {
+            parse_xc4000_bitstream(&mut res, data)?;
+            vec![]
+        }
+        DeviceKind::Xc5200 => {
+            parse_xc5200_bitstream(&mut res, data)?;
+            vec![]
+        }
+        DeviceKind::Virtex | DeviceKind::Virtex2 => {
+            parse_virtex_bitstream(&mut res, data, key, strict, &mut warnings)?;
+            vec![]
+        }
+        DeviceKind::Spartan3A => {
+            parse_spartan3a_bitstream(&mut res, data, key, strict, &mut warnings)?;
+            vec![]
+        }
+        DeviceKind::Spartan6 => {
+            parse_spartan6_bitstream(&mut res, data, key, strict, &mut warnings)?;
+            vec![]
         }
-    }
-    todo!()
-}
-
-fn empty(geom: &BitstreamGeom) -> Bitstream {
-    Bitstream {
-        kind: geom.kind,
-        die: geom.die.map_values(|dg| DieBitstream {
-            regs: Default::default(),
-            mode: BitstreamMode::Plain,
-            iv: vec![],
-            frame_len: dg.frame_len,
-            frame_data: BitVec::repeat(false, dg.frame_len * dg.frame_info.len()),
-            frame_info: dg.frame_info.clone(),
-            frame_present: BitVec::repeat(false, dg.frame_info.len()),
-            bram_data: BitVec::repeat(false, dg.bram_frame_len * dg.bram_frame_info.len()),
-            bram_frame_present: BitVec::repeat(false, dg.bram_frame_info.len()),
-            bram_frame_len: dg.bram_frame_len,
-            bram_frame_info: dg.bram_frame_info.clone(),
-            iob: BitVec::repeat(false, dg.iob_frame_len),
-            iob_present: false,
-            frame_fixups: HashMap::new(),
-        }),
-        gtz: Default::default(),
-        gtz_loader: None,
-    }
-}
-
-pub fn parse(geom: &BitstreamGeom, data: &[u8], key: &KeyData) -> Bitstream {
-    let mut res = empty(geom);
-    match res.kind {
-        DeviceKind::Xc2000 => parse_xc2000_bitstream(&mut res, data),
-        DeviceKind::Xc4000 | DeviceKind::S40Xl => parse_xc4000_bitstream(&mut res, data),
-        DeviceKind::Xc5200 => parse_xc5200_bitstream(&mut res, data),
-        DeviceKind::Virtex | DeviceKind::Virtex2 => parse_virtex_bitstream(&mut res, data, key),
-        DeviceKind::Spartan3A => parse_spartan3a_bitstream(&mut res, data, key),
-        DeviceKind::Spartan6 => parse_spartan6_bitstream(&mut res, data, key),
         DeviceKind::Virtex4 => {
-            parse_virtex4_bitstream(&mut res, data, key, geom, 0);
-            check_virtex4_ecc(&res);
+            parse_virtex4_bitstream(&mut res, data, key, geom, 0, strict, &mut warnings)?;
+            check_ecc(&res)?
         }
         DeviceKind::Virtex5 => {
-            parse_virtex4_bitstream(&mut res, data, key, geom, 0);
-            check_virtex5_ecc(&res);
+            parse_virtex4_bitstream(&mut res, data, key, geom, 0, strict, &mut warnings)?;
+            check_ecc(&res)?
         }
         DeviceKind::Virtex6 => {
-            parse_virtex4_bitstream(&mut res, data, key, geom, 0);
-            check_virtex6_ecc(&res);
+            parse_virtex4_bitstream(&mut res, data, key, geom, 0, strict, &mut warnings)?;
+            check_ecc(&res)?
         }
         DeviceKind::Virtex7 => {
-            parse_virtex4_bitstream(&mut res, data, key, geom, 0);
-            check_virtex7_ecc(&res);
+            parse_virtex4_bitstream(&mut res, data, key, geom, 0, strict, &mut warnings)?;
+            check_ecc(&res)?
         }
         DeviceKind::Ultrascale | DeviceKind::UltrascalePlus => {
-            parse_ultrascale_bitstream(&res, data, key)
+            parse_ultrascale_bitstream(&mut res, data, key, geom, 0, strict, &mut warnings)?;
+            // The frame ECC layout of these families is not known, so it is not checked.
+            vec![]
         }
-        DeviceKind::Versal => panic!("versal bitstreams not supported through generic code"),
+        DeviceKind::Versal => {
+            return Err(BitstreamError::Unsupported {
+                offset: 0,
+                reason: "versal bitstreams not supported through generic code; use parse_pdi and parse_cfi"
+                    .to_string(),
+            });
+        }
+    };
+    let mut ecc_mismatches = ecc_mismatches.into_iter();
+    if strict && let Some(err) = ecc_mismatches.next() {
+        return Err(err);
     }
-    res
+    warnings.extend(ecc_mismatches);
+    Ok((res, warnings))
+}
+
+/// Parses a bitstream, tolerating CRC and ECC mismatches.  The tolerated mismatches are
+/// returned alongside the bitstream, in the order they were found.
+///
+/// Frame ECC is checked for Virtex 4 to Virtex 7 only; UltraScale and UltraScale+ frames
+/// are taken as they are.
+pub fn parse(
+    geom: &BitstreamGeom,
+    data: &[u8],
+    key: &KeyData,
+) -> Result<(Bitstream, Vec<BitstreamError>), BitstreamError> {
+    parse_impl(geom, data, key, false)
+}
+
+/// Parses a bitstream like [`parse`], but returns the first CRC or ECC mismatch as an error
+/// instead of tolerating it.
+pub fn parse_strict(
+    geom: &BitstreamGeom,
+    data: &[u8],
+    key: &KeyData,
+) -> Result<Bitstream, BitstreamError> {
+    parse_impl(geom, data, key, true).map(|(res, _)| res)
 }
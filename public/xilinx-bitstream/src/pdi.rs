@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+
+use arrayref::array_ref;
+
+use crate::error::{BitstreamError, check_len, ensure, ensure_eq};
+use crate::{Bitstream, BitstreamGeom, DeviceKind, FrameAddr};
+
+#[derive(Clone, Debug)]
+pub struct Pdi {
+    pub boot_header: Option<PdiBootHeader>,
+    pub version: u32,
+    pub idcode: u32,
+    pub attributes: u32,
+    pub pdi_id: u32,
+    pub parent_id: u32,
+    pub images: Vec<PdiImage>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PdiBootHeader {
+    pub key_source: u32,
+    pub pmc_data_load_addr: u32,
+    pub attributes: u32,
+    pub puf_shutter: u32,
+    // (address, value) pairs
+    pub reg_init: Vec<(u32, u32)>,
+    pub plm: Vec<u8>,
+    pub pmc_data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PdiImage {
+    pub name: String,
+    pub id: u32,
+    pub unique_id: u32,
+    pub parent_unique_id: u32,
+    pub function_id: u32,
+    pub attributes: u32,
+    pub partitions: Vec<PdiPartition>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum PdiPartitionKind {
+    Elf,
+    Cdo,
+    Cfi,
+    Raw,
+    RawElf,
+    CfiGsc,
+    CfiGscUnmask,
+    Unknown(u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct PdiPartition {
+    pub id: u32,
+    pub kind: PdiPartitionKind,
+    pub attributes: u32,
+    pub exec_addr: u64,
+    pub load_addr: u64,
+    // byte offset of the data within the PDI, for error reporting
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+const BOOT_HEADER_SYNC: u32 = 0xaa995566;
+
+fn get_word(data: &[u8], pos: usize) -> Result<u32, BitstreamError> {
+    check_len(data.len(), pos + 4, pos)?;
+    Ok(u32::from_le_bytes(*array_ref!(data, pos, 4)))
+}
+
+fn get_bytes(data: &[u8], pos: usize, len: usize) -> Result<&[u8], BitstreamError> {
+    check_len(data.len(), pos + len, pos)?;
+    Ok(&data[pos..pos + len])
+}
+
+fn check_header_checksum(data: &[u8], pos: usize, words: usize) -> Result<(), BitstreamError> {
+    let mut sum: u32 = 0;
+    for i in 0..words - 1 {
+        sum = sum.wrapping_add(get_word(data, pos + i * 4)?);
+    }
+    let offset = pos + (words - 1) * 4;
+    let checksum = get_word(data, offset)?;
+    if checksum != !sum {
+        return Err(BitstreamError::CrcMismatch {
+            offset,
+            expected: !sum,
+            actual: checksum,
+        });
+    }
+    Ok(())
+}
+
+fn parse_boot_header(data: &[u8]) -> Result<(PdiBootHeader, usize), BitstreamError> {
+    ensure_eq!(get_bytes(data, 0x14, 4)?, b"XNLX", 0x14);
+    let key_source = get_word(data, 0x18)?;
+    if key_source != 0 {
+        return Err(BitstreamError::Unsupported {
+            offset: 0x18,
+            reason: "encrypted PDIs".to_string(),
+        });
+    }
+    let plm_offset = get_word(data, 0x1c)? as usize;
+    let pmc_data_load_addr = get_word(data, 0x20)?;
+    let pmc_data_len = get_word(data, 0x24)? as usize;
+    let plm_len = get_word(data, 0x2c)? as usize;
+    let attributes = get_word(data, 0x34)?;
+    let puf_shutter = get_word(data, 0x70)?;
+    let meta_header_offset = get_word(data, 0xc4)? as usize;
+    let mut reg_init = vec![];
+    for i in 0..0x100 {
+        let addr = get_word(data, 0x128 + i * 8)?;
+        let val = get_word(data, 0x128 + i * 8 + 4)?;
+        if addr != 0xffffffff {
+            reg_init.push((addr, val));
+        }
+    }
+    check_header_checksum(data, 0x10, (0xf34 - 0x10) / 4)?;
+    let plm = get_bytes(data, plm_offset, plm_len)?.to_vec();
+    let pmc_data = get_bytes(data, plm_offset + plm_len, pmc_data_len)?.to_vec();
+    Ok((
+        PdiBootHeader {
+            key_source,
+            pmc_data_load_addr,
+            attributes,
+            puf_shutter,
+            reg_init,
+            plm,
+            pmc_data,
+        },
+        meta_header_offset,
+    ))
+}
+
+fn parse_partition(data: &[u8], pos: usize) -> Result<PdiPartition, BitstreamError> {
+    check_header_checksum(data, pos, 0x20)?;
+    let enc_len = get_word(data, pos)? as usize;
+    let len = get_word(data, pos + 4)? as usize;
+    let exec_addr =
+        (get_word(data, pos + 0x10)? as u64) | (get_word(data, pos + 0x14)? as u64) << 32;
+    let load_addr =
+        (get_word(data, pos + 0x18)? as u64) | (get_word(data, pos + 0x1c)? as u64) << 32;
+    let offset = get_word(data, pos + 0x20)? as usize * 4;
+    let attributes = get_word(data, pos + 0x24)?;
+    let id = get_word(data, pos + 0x30)?;
+    if enc_len != len || get_word(data, pos + 0x44)? != 0 {
+        return Err(BitstreamError::Unsupported {
+            offset: pos,
+            reason: "encrypted PDI partitions".to_string(),
+        });
+    }
+    let kind = match attributes >> 4 & 7 {
+        1 => PdiPartitionKind::Elf,
+        2 => PdiPartitionKind::Cdo,
+        3 => PdiPartitionKind::Cfi,
+        4 => PdiPartitionKind::Raw,
+        5 => PdiPartitionKind::RawElf,
+        6 => PdiPartitionKind::CfiGsc,
+        7 => PdiPartitionKind::CfiGscUnmask,
+        val => PdiPartitionKind::Unknown(val),
+    };
+    Ok(PdiPartition {
+        id,
+        kind,
+        attributes,
+        exec_addr,
+        load_addr,
+        offset,
+        data: get_bytes(data, offset, len * 4)?.to_vec(),
+    })
+}
+
+fn parse_image(data: &[u8], pos: usize) -> Result<PdiImage, BitstreamError> {
+    check_header_checksum(data, pos, 0x10)?;
+    let first_partition = get_word(data, pos)? as usize * 4;
+    let num_partitions = get_word(data, pos + 4)? as usize;
+    let attributes = get_word(data, pos + 0xc)?;
+    let name = get_bytes(data, pos + 0x10, 0x10)?;
+    // the name is stored as a sequence of byte-swapped words
+    let name: Vec<u8> = name
+        .chunks(4)
+        .flat_map(|w| w.iter().rev().copied())
+        .take_while(|&c| c != 0)
+        .collect();
+    let name = String::from_utf8_lossy(&name).into_owned();
+    Ok(PdiImage {
+        name,
+        id: get_word(data, pos + 0x20)?,
+        unique_id: get_word(data, pos + 0x24)?,
+        parent_unique_id: get_word(data, pos + 0x28)?,
+        function_id: get_word(data, pos + 0x2c)?,
+        attributes,
+        partitions: (0..num_partitions)
+            .map(|i| parse_partition(data, first_partition + i * 0x80))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Parses a Versal programmable device image container.  Accepts both boot PDIs (starting
+/// with a boot header) and partial PDIs (starting directly with the image header table).
+pub fn parse_pdi(data: &[u8]) -> Result<Pdi, BitstreamError> {
+    let (boot_header, iht) = if data.len() >= 0x18 && get_word(data, 0x10)? == BOOT_HEADER_SYNC {
+        let (bh, iht) = parse_boot_header(data)?;
+        (Some(bh), iht)
+    } else {
+        (None, 0)
+    };
+    check_header_checksum(data, iht, 0x10)?;
+    let version = get_word(data, iht)?;
+    let num_images = get_word(data, iht + 4)? as usize;
+    let first_image = get_word(data, iht + 8)? as usize * 4;
+    let idcode = get_word(data, iht + 0x18)?;
+    let attributes = get_word(data, iht + 0x1c)?;
+    let pdi_id = get_word(data, iht + 0x20)?;
+    let parent_id = get_word(data, iht + 0x24)?;
+    let images = (0..num_images)
+        .map(|i| parse_image(data, first_image + i * 0x40))
+        .collect::<Result<_, _>>()?;
+    Ok(Pdi {
+        boot_header,
+        version,
+        idcode,
+        attributes,
+        pdi_id,
+        parent_id,
+        images,
+    })
+}
+
+// CFI packet stream registers and commands.
+const CFI_SYNC: u32 = 0xaa995566;
+const CFI_REG_FAR: u32 = 1;
+const CFI_REG_FDRI: u32 = 2;
+const CFI_REG_CMD: u32 = 4;
+const CFI_CMD_WCFG: u32 = 1;
+const CFI_CMD_DESYNC: u32 = 0xd;
+const CFI_OP_NOP: u32 = 0;
+const CFI_OP_WRITE: u32 = 2;
+
+/// The frame address register value of a CFRAME frame: block type in bits 24-26, CFRAME
+/// row in bits 18-23, column in bits 8-17 and minor in bits 0-7.
+pub(crate) fn versal_far(addr: FrameAddr) -> u32 {
+    addr.minor | addr.major << 8 | (addr.region as u32) << 18 | addr.typ << 24
+}
+
+fn parse_cfi_partition(
+    bs: &mut Bitstream,
+    far_dict: &HashMap<u32, usize>,
+    part: &PdiPartition,
+) -> Result<(), BitstreamError> {
+    let data = &part.data;
+    let base = part.offset;
+    let diebs = bs.die.first_mut().unwrap();
+    let frame_bytes = diebs.frame_len / 8;
+    ensure_eq!(data.len() % 4, 0, base);
+    let mut pos = 0;
+    let mut synced = false;
+    let mut wcfg = false;
+    let mut last_reg = None;
+    let mut fi = None;
+    while pos < data.len() {
+        let offset = base + pos;
+        let header = get_word(data, pos)?;
+        pos += 4;
+        if !synced {
+            // anything before the sync word is padding
+            synced = header == CFI_SYNC;
+            continue;
+        }
+        let (op, reg, count) = match header >> 29 {
+            1 => {
+                let reg = header >> 13 & 0x3fff;
+                last_reg = Some(reg);
+                (header >> 27 & 3, reg, (header & 0x7ff) as usize)
+            }
+            2 => {
+                let Some(reg) = last_reg else {
+                    return Err(BitstreamError::Malformed {
+                        offset,
+                        reason: "type 2 packet without preceding type 1 packet".to_string(),
+                    });
+                };
+                (header >> 27 & 3, reg, (header & 0x7ffffff) as usize)
+            }
+            _ => {
+                return Err(BitstreamError::InvalidWord {
+                    offset,
+                    word: header,
+                });
+            }
+        };
+        let payload =
+            get_bytes(data, pos, count * 4).map_err(|_| BitstreamError::Truncated { offset })?;
+        pos += count * 4;
+        match op {
+            // a zero-length write only selects the register for a following type 2 packet
+            CFI_OP_NOP => continue,
+            CFI_OP_WRITE if count == 0 => continue,
+            CFI_OP_WRITE => (),
+            _ => {
+                return Err(BitstreamError::Unsupported {
+                    offset,
+                    reason: format!("CFI packet opcode {op}"),
+                });
+            }
+        }
+        match reg {
+            CFI_REG_FAR => {
+                ensure_eq!(count, 1, offset);
+                let far = get_word(payload, 0)?;
+                let Some(&idx) = far_dict.get(&far) else {
+                    return Err(BitstreamError::InvalidFrameAddress { offset, far });
+                };
+                fi = Some(idx);
+            }
+            CFI_REG_FDRI => {
+                ensure!(wcfg, offset);
+                ensure!(!payload.is_empty(), offset);
+                ensure_eq!(payload.len() % frame_bytes, 0, offset);
+                let Some(mut cfi) = fi else {
+                    return Err(BitstreamError::Malformed {
+                        offset,
+                        reason: "FDRI write without valid FAR".to_string(),
+                    });
+                };
+                for frame in payload.chunks(frame_bytes) {
+                    if cfi >= diebs.frame_info.len() {
+                        return Err(BitstreamError::Malformed {
+                            offset,
+                            reason: "FDRI write past the last frame".to_string(),
+                        });
+                    }
+                    let bits = diebs.frame_mut(cfi);
+                    for (i, word) in frame.chunks(4).enumerate() {
+                        let word = u32::from_le_bytes(*array_ref!(word, 0, 4));
+                        for j in 0..32 {
+                            bits.set(i * 32 + j, (word >> j & 1) != 0);
+                        }
+                    }
+                    diebs.frame_present.set(cfi, true);
+                    cfi += 1;
+                }
+                fi = Some(cfi);
+            }
+            CFI_REG_CMD => {
+                ensure_eq!(count, 1, offset);
+                match get_word(payload, 0)? {
+                    CFI_CMD_WCFG => wcfg = true,
+                    CFI_CMD_DESYNC => {
+                        synced = false;
+                        wcfg = false;
+                    }
+                    _ => (),
+                }
+            }
+            // other CFU and CFRAME registers do not affect the frame contents
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes the CFI partitions of a PDI into the configuration frames described by `geom`
+/// (see `prjcombine_versal::expanded::ExpandedDevice::bitstream_geom`).
+///
+/// A CFI partition is a stream of little-endian configuration packets with the UltraScale+
+/// header layout.  FDRI writes fill consecutive frames of the geometry, starting at the last
+/// FAR write, without the pad frames of earlier families.  This layout has not been checked
+/// against vendor images yet.  Only single-die devices are supported.
+pub fn parse_cfi(geom: &BitstreamGeom, pdi: &Pdi) -> Result<Bitstream, BitstreamError> {
+    assert_eq!(geom.kind, DeviceKind::Versal);
+    if geom.die.len() != 1 {
+        return Err(BitstreamError::Unsupported {
+            offset: 0,
+            reason: "multi-die PDIs".to_string(),
+        });
+    }
+    let mut bs = Bitstream::new(geom);
+    let far_dict: HashMap<_, _> = bs
+        .die
+        .first()
+        .unwrap()
+        .frame_info
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (versal_far(f.addr), i))
+        .collect();
+    for image in &pdi.images {
+        for part in &image.partitions {
+            if matches!(
+                part.kind,
+                PdiPartitionKind::Cfi | PdiPartitionKind::CfiGsc | PdiPartitionKind::CfiGscUnmask
+            ) {
+                parse_cfi_partition(&mut bs, &far_dict, part)?;
+            }
+        }
+    }
+    Ok(bs)
+}
+
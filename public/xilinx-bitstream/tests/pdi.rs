@@ -0,0 +1,188 @@
+use arrayvec::ArrayVec;
+use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_xilinx_bitstream::{
+    Bitstream, BitstreamError, BitstreamGeom, DeviceKind, DieBitstreamGeom, FrameAddr, FrameInfo,
+    PdiPartitionKind, parse_cfi, parse_pdi,
+};
+
+const FRAME_WORDS: usize = 4;
+
+// CFI packet stream registers and commands.
+const CFI_SYNC: u32 = 0xaa995566;
+const CFI_REG_FAR: u32 = 1;
+const CFI_REG_FDRI: u32 = 2;
+const CFI_REG_CMD: u32 = 4;
+const CFI_CMD_WCFG: u32 = 1;
+const CFI_CMD_DESYNC: u32 = 0xd;
+const CFI_OP_WRITE: u32 = 2;
+
+fn push_header(out: &mut Vec<u32>, words: &[u32]) {
+    let sum = words.iter().fold(0u32, |a, &b| a.wrapping_add(b));
+    out.extend(words);
+    out.push(!sum);
+}
+
+// A partial PDI: image header table at 0, one image at 0x40, one CFI partition header at
+// 0x80, and the partition data at 0x100.
+fn partial_pdi(payload: &[u32]) -> Vec<u8> {
+    let mut words = vec![];
+    let mut iht = vec![0; 15];
+    iht[0] = 0x00040000;
+    iht[1] = 1;
+    iht[2] = 0x40 / 4;
+    iht[6] = 0x14ca8093;
+    push_header(&mut words, &iht);
+    let mut image = vec![0; 15];
+    image[0] = 0x80 / 4;
+    image[1] = 1;
+    // the name is stored as byte-swapped words
+    image[4] = u32::from_be_bytes(*b"pl_c");
+    image[5] = u32::from_be_bytes(*b"fi\0\0");
+    image[8] = 0x18700000;
+    push_header(&mut words, &image);
+    let mut part = vec![0; 31];
+    part[0] = payload.len() as u32;
+    part[1] = payload.len() as u32;
+    part[6] = 0xf6000000;
+    part[8] = 0x100 / 4;
+    part[9] = 3 << 4;
+    part[12] = 0x1c000000;
+    push_header(&mut words, &part);
+    words.extend(payload);
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+// Two CFRAME rows of three columns of two frames each.
+fn geom() -> BitstreamGeom {
+    let mut frame_info = vec![];
+    for region in 0..2 {
+        for major in 0..3 {
+            for minor in 0..2 {
+                frame_info.push(FrameInfo {
+                    addr: FrameAddr {
+                        typ: 0,
+                        region,
+                        major,
+                        minor,
+                    },
+                    mask_mode: ArrayVec::new(),
+                });
+            }
+        }
+    }
+    BitstreamGeom {
+        kind: DeviceKind::Versal,
+        die: EntityVec::from_iter([DieBitstreamGeom {
+            frame_len: FRAME_WORDS * 32,
+            frame_info,
+            bram_frame_len: 0,
+            bram_frame_info: vec![],
+            iob_frame_len: 0,
+        }]),
+        die_order: vec![DieId::from_idx(0)],
+        has_gtz_bot: false,
+        has_gtz_top: false,
+    }
+}
+
+fn far(region: i32, major: u32, minor: u32) -> u32 {
+    minor | major << 8 | (region as u32) << 18
+}
+
+fn type1_write(reg: u32, data: &[u32]) -> Vec<u32> {
+    let mut res = vec![1 << 29 | CFI_OP_WRITE << 27 | reg << 13 | data.len() as u32];
+    res.extend(data);
+    res
+}
+
+fn cfi_stream(far: u32, frames: &[u32]) -> Vec<u32> {
+    let mut res = vec![0xffffffff, CFI_SYNC, 0x20000000];
+    res.extend(type1_write(CFI_REG_CMD, &[CFI_CMD_WCFG]));
+    res.extend(type1_write(CFI_REG_FAR, &[far]));
+    res.push(1 << 29 | CFI_OP_WRITE << 27 | CFI_REG_FDRI << 13);
+    res.push(2 << 29 | CFI_OP_WRITE << 27 | frames.len() as u32);
+    res.extend(frames);
+    res.extend(type1_write(CFI_REG_CMD, &[CFI_CMD_DESYNC]));
+    res
+}
+
+#[test]
+fn pdi_partitions() {
+    let payload: Vec<u32> = (0..16).map(|i| 0x01010101 * i).collect();
+    let data = partial_pdi(&payload);
+    let pdi = parse_pdi(&data).unwrap();
+    assert!(pdi.boot_header.is_none());
+    assert_eq!(pdi.version, 0x00040000);
+    assert_eq!(pdi.idcode, 0x14ca8093);
+    assert_eq!(pdi.images.len(), 1);
+    let image = &pdi.images[0];
+    assert_eq!(image.name, "pl_cfi");
+    assert_eq!(image.id, 0x18700000);
+    assert_eq!(image.partitions.len(), 1);
+    let part = &image.partitions[0];
+    assert_eq!(part.kind, PdiPartitionKind::Cfi);
+    assert_eq!(part.id, 0x1c000000);
+    assert_eq!(part.load_addr, 0xf6000000);
+    assert_eq!(part.offset, 0x100);
+    assert_eq!(part.data, data[0x100..]);
+}
+
+#[test]
+fn pdi_errors() {
+    let mut data = partial_pdi(&[0; 8]);
+    assert!(matches!(
+        parse_pdi(&data[..0x90]),
+        Err(BitstreamError::Truncated { .. })
+    ));
+    data[0x84] ^= 1;
+    assert!(matches!(
+        parse_pdi(&data),
+        Err(BitstreamError::CrcMismatch { offset: 0xfc, .. })
+    ));
+}
+
+#[test]
+fn cfi_frames() {
+    let geom = geom();
+    let frames: Vec<u32> = (0..2 * FRAME_WORDS as u32).map(|i| 1 << i).collect();
+    let data = partial_pdi(&cfi_stream(far(1, 2, 0), &frames));
+    let pdi = parse_pdi(&data).unwrap();
+    let bs = parse_cfi(&geom, &pdi).unwrap();
+    let diebs = bs.die.first().unwrap();
+    for (fi, finfo) in diebs.frame_info.iter().enumerate() {
+        let written = finfo.addr.region == 1 && finfo.addr.major == 2;
+        assert_eq!(diebs.frame_present[fi], written);
+        if written {
+            let base = finfo.addr.minor as usize * FRAME_WORDS;
+            for (i, bit) in diebs.frame(fi).iter().enumerate() {
+                assert_eq!(*bit, frames[base + i / 32] >> (i % 32) & 1 != 0);
+            }
+        } else {
+            assert!(diebs.frame(fi).not_any());
+        }
+    }
+    let empty = Bitstream::new(&geom);
+    assert_eq!(Bitstream::diff(&empty, &bs).len(), 2 * FRAME_WORDS);
+}
+
+#[test]
+fn cfi_errors() {
+    let geom = geom();
+    let data = partial_pdi(&cfi_stream(0x3fffffff, &[0; FRAME_WORDS]));
+    let pdi = parse_pdi(&data).unwrap();
+    assert!(matches!(
+        parse_cfi(&geom, &pdi),
+        Err(BitstreamError::InvalidFrameAddress {
+            far: 0x3fffffff,
+            ..
+        })
+    ));
+    // last frame of the device, followed by one frame too many
+    let data = partial_pdi(&cfi_stream(far(1, 2, 1), &[0; 2 * FRAME_WORDS]));
+    let pdi = parse_pdi(&data).unwrap();
+    assert!(matches!(
+        parse_cfi(&geom, &pdi),
+        Err(BitstreamError::Malformed { .. })
+    ));
+}
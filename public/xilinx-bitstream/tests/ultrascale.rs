@@ -0,0 +1,296 @@
+use arrayvec::ArrayVec;
+use bitvec::slice::BitSlice;
+use prjcombine_entity::{EntityId, EntityVec};
+use prjcombine_interconnect::grid::DieId;
+use prjcombine_ultrascale::db::Database;
+use prjcombine_xilinx_bitstream::{
+    Bitstream, BitstreamGeom, DeviceKind, DieBitstreamGeom, FrameAddr, FrameInfo, FrameMaskMode,
+    KeyData, Reg, parse_strict,
+};
+
+const DB_ULTRASCALE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascale.zstd"
+);
+const DB_ULTRASCALEPLUS: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../databases/ultrascaleplus.zstd"
+);
+
+// (type, major, minor count) of the frames: two columns of main frames and one of BRAM
+// frames, all in one region.  The FDRI write below covers all of them.
+const BLOCKS: [(u32, u32, u32); 3] = [(0, 0, 4), (0, 1, 3), (1, 0, 3)];
+
+fn geom(kind: DeviceKind, frame_words: usize) -> BitstreamGeom {
+    let mut frame_info = vec![];
+    for (typ, major, minors) in BLOCKS {
+        for minor in 0..minors {
+            frame_info.push(FrameInfo {
+                addr: FrameAddr {
+                    typ,
+                    region: 0,
+                    major,
+                    minor,
+                },
+                mask_mode: ArrayVec::from_iter([FrameMaskMode::None]),
+            });
+        }
+    }
+    let mut die = EntityVec::new();
+    die.push(DieBitstreamGeom {
+        frame_len: frame_words * 32,
+        frame_info,
+        bram_frame_len: 0,
+        bram_frame_info: vec![],
+        iob_frame_len: 0,
+    });
+    BitstreamGeom {
+        kind,
+        die,
+        die_order: vec![DieId::from_idx(0)],
+        has_gtz_bot: false,
+        has_gtz_top: false,
+    }
+}
+
+fn type1_write(data: &mut Vec<u8>, reg: u32, vals: &[u32]) {
+    data.extend((0x30000000 | reg << 13 | vals.len() as u32).to_be_bytes());
+    for val in vals {
+        data.extend(val.to_be_bytes());
+    }
+}
+
+fn frame_word(fi: usize, wi: usize) -> u32 {
+    (fi as u32) << 24 | (wi as u32) * 0x10101 ^ 0x5a5a5a
+}
+
+// A synthetic configuration stream with the packet sequence of a vendor bitstream: bus
+// width detection and sync, the IDCODE check, a single FDRI write starting at the first
+// frame, the streams of the following SLRs if any, and desync.
+fn stream(
+    frame_words: usize,
+    frame_info: &[FrameInfo],
+    idcode: u32,
+    word: impl Fn(usize, usize) -> u32,
+    bout: Option<Vec<u8>>,
+) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend([0xff; 32]);
+    data.extend(0x000000bbu32.to_be_bytes());
+    data.extend(0x11220044u32.to_be_bytes());
+    data.extend([0xff; 8]);
+    data.extend(0xaa995566u32.to_be_bytes());
+    data.extend(0x20000000u32.to_be_bytes());
+    type1_write(&mut data, 0xc, &[idcode]);
+    type1_write(&mut data, 4, &[1]);
+    type1_write(&mut data, 1, &[0]);
+    type1_write(&mut data, 2, &[]);
+    let mut fdri = vec![];
+    for (fi, finfo) in frame_info.iter().enumerate() {
+        for wi in 0..frame_words {
+            fdri.push(word(fi, wi));
+        }
+        // Two pad frames follow the end of a block; the write ends with one more.
+        let pad = match frame_info.get(fi + 1) {
+            Some(next)
+                if next.addr.typ == finfo.addr.typ && next.addr.region == finfo.addr.region =>
+            {
+                0
+            }
+            Some(_) => 2,
+            None => 1,
+        };
+        fdri.extend(std::iter::repeat_n(0xdeadbeef, pad * frame_words));
+    }
+    data.extend((0x50000000 | fdri.len() as u32).to_be_bytes());
+    for word in fdri {
+        data.extend(word.to_be_bytes());
+    }
+    if let Some(bout) = bout {
+        type1_write(&mut data, 0x1e, &[]);
+        data.extend((0x50000000 | (bout.len() / 4) as u32).to_be_bytes());
+        data.extend(bout);
+    }
+    type1_write(&mut data, 4, &[13]);
+    data.extend(std::iter::repeat_n(0x20000000u32.to_be_bytes(), 4).flatten());
+    data
+}
+
+fn check_frames(
+    kind: DeviceKind,
+    frame: &BitSlice,
+    frame_words: usize,
+    word: impl Fn(usize) -> u32,
+) {
+    for wi in 0..frame_words {
+        let word = word(wi);
+        for j in 0..32 {
+            assert_eq!(
+                frame[wi * 32 + j],
+                (word >> j & 1) != 0,
+                "{kind:?}: word {wi} bit {j}"
+            );
+        }
+    }
+}
+
+fn check_parse(kind: DeviceKind, frame_words: usize) {
+    let geom = geom(kind, frame_words);
+    let num_frames = geom.die.first().unwrap().frame_info.len();
+    let frame_info = &geom.die.first().unwrap().frame_info;
+    let data = stream(frame_words, frame_info, 0x03822093, frame_word, None);
+    let bitstream = parse_strict(&geom, &data, &KeyData::None).unwrap();
+    let die = bitstream.die.first().unwrap();
+    assert_eq!(die.regs.get(&Reg::Idcode), Some(&0x03822093));
+    assert!(die.frame_present.all(), "{kind:?}: missing frames");
+    for fi in 0..num_frames {
+        check_frames(kind, die.frame(fi), frame_words, |wi| frame_word(fi, wi));
+    }
+    // The frame ECC layout is not known for these families, so no bit is left out of the
+    // comparison.
+    let mut changed = bitstream.clone();
+    let die = changed.die.first_mut().unwrap();
+    for bit in 0..die.frame_len {
+        let val = die.frame(0)[bit];
+        die.frame_mut(0).set(bit, !val);
+    }
+    assert_eq!(
+        Bitstream::diff(&bitstream, &changed).len(),
+        frame_words * 32,
+        "{kind:?}"
+    );
+}
+
+#[test]
+fn parse_ultrascale() {
+    check_parse(DeviceKind::Ultrascale, 123);
+}
+
+#[test]
+fn parse_ultrascaleplus() {
+    check_parse(DeviceKind::UltrascalePlus, 93);
+}
+
+// The geometry of a multi-SLR device from the database, with two frames per column and
+// clock region: the frame counts of the column kinds are not in the database.  As in
+// vendor bitstreams, the primary SLR comes first, followed by the others in order.
+fn multi_slr_geom(
+    kind: DeviceKind,
+    db: &Database,
+    device: &str,
+    frame_words: usize,
+) -> BitstreamGeom {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let primary = db.interposers[dev.interposer].primary;
+    let die = dev.chips.map_values(|&chip| {
+        let chip = &db.chips[chip];
+        let mut frame_info = vec![];
+        for region in 0..chip.regs {
+            for major in 0..chip.columns.len() {
+                for minor in 0..2 {
+                    frame_info.push(FrameInfo {
+                        addr: FrameAddr {
+                            typ: 0,
+                            region: region as i32,
+                            major: major as u32,
+                            minor,
+                        },
+                        mask_mode: ArrayVec::from_iter([FrameMaskMode::None]),
+                    });
+                }
+            }
+        }
+        DieBitstreamGeom {
+            frame_len: frame_words * 32,
+            frame_info,
+            bram_frame_len: 0,
+            bram_frame_info: vec![],
+            iob_frame_len: 0,
+        }
+    });
+    let mut die_order = vec![primary];
+    die_order.extend(dev.chips.ids().filter(|&die| die != primary));
+    BitstreamGeom {
+        kind,
+        die,
+        die_order,
+        has_gtz_bot: false,
+        has_gtz_top: false,
+    }
+}
+
+fn slr_idcode(die: DieId) -> u32 {
+    0x04a00093 | (die.to_idx() as u32) << 12
+}
+
+fn slr_frame_word(die: DieId, fi: usize, wi: usize) -> u32 {
+    frame_word(fi, wi) ^ (die.to_idx() as u32 + 1) << 20
+}
+
+fn check_multi_slr(
+    kind: DeviceKind,
+    db: &str,
+    device: &str,
+    frame_words: usize,
+    die_order: &[usize],
+) {
+    let db = Database::from_file(db).unwrap();
+    let geom = multi_slr_geom(kind, &db, device, frame_words);
+    assert_eq!(
+        geom.die_order,
+        die_order
+            .iter()
+            .map(|&die| DieId::from_idx(die))
+            .collect::<Vec<_>>()
+    );
+    // Every SLR stream carries the streams of the SLRs after it.
+    let mut data = None;
+    for &die in geom.die_order.iter().rev() {
+        data = Some(stream(
+            frame_words,
+            &geom.die[die].frame_info,
+            slr_idcode(die),
+            |fi, wi| slr_frame_word(die, fi, wi),
+            data,
+        ));
+    }
+    let bitstream = parse_strict(&geom, &data.unwrap(), &KeyData::None).unwrap();
+    for (die, diebs) in &bitstream.die {
+        assert_eq!(
+            diebs.regs.get(&Reg::Idcode),
+            Some(&slr_idcode(die)),
+            "{kind:?}"
+        );
+        assert!(
+            diebs.frame_present.all(),
+            "{kind:?}: missing frames in {die}"
+        );
+        for fi in 0..geom.die[die].frame_info.len() {
+            check_frames(kind, diebs.frame(fi), frame_words, |wi| {
+                slr_frame_word(die, fi, wi)
+            });
+        }
+    }
+}
+
+#[test]
+fn parse_ultrascale_multi_slr() {
+    check_multi_slr(
+        DeviceKind::Ultrascale,
+        DB_ULTRASCALE,
+        "xcvu190",
+        123,
+        &[1, 0, 2],
+    );
+}
+
+#[test]
+fn parse_ultrascaleplus_multi_slr() {
+    check_multi_slr(
+        DeviceKind::UltrascalePlus,
+        DB_ULTRASCALEPLUS,
+        "xcvu9p",
+        93,
+        &[1, 0, 2],
+    );
+}
@@ -1,156 +1,10 @@
-use std::{collections::BTreeMap, error::Error, fs::read_to_string, path::PathBuf};
+use std::{error::Error, fs::read_to_string, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
-use prjcombine_entity::EntityId;
-use prjcombine_jed::JedFile;
-use prjcombine_types::{
-    bitvec::BitVec,
-    bsdata::{Tile, TileItemKind},
-    cpld::MacrocellId,
+use prjcombine_xpla3::{
+    Database,
+    bitstream::{Bitstream, set_tile_item},
 };
-use prjcombine_xpla3::{Chip, Database};
-
-struct Bitstream {
-    fbs: Vec<FbData>,
-    globals: BTreeMap<String, BitVec>,
-}
-
-struct FbData {
-    misc: BTreeMap<String, BitVec>,
-    mcs: [BTreeMap<String, BitVec>; 16],
-    pla_and: [PTermData; 48],
-    pla_or: [BitVec; 16],
-}
-
-struct PTermData {
-    im_t: BitVec,
-    im_f: BitVec,
-    fbn: BitVec,
-}
-
-fn init_tile(tile: &Tile) -> BTreeMap<String, BitVec> {
-    tile.items
-        .iter()
-        .map(|(k, v)| (k.clone(), BitVec::repeat(true, v.bits.len())))
-        .collect()
-}
-
-impl Bitstream {
-    fn new(chip: &Chip, db: &Database) -> Self {
-        let fbs = (0..(chip.block_rows * chip.block_cols.len() * 2))
-            .map(|_| {
-                let mut misc = init_tile(&db.block_bits);
-                for i in 0..40 {
-                    misc.insert(
-                        format!("IM[{i}].MUX"),
-                        BitVec::repeat(true, chip.imux_width),
-                    );
-                }
-                FbData {
-                    misc,
-                    mcs: core::array::from_fn(|_| init_tile(&db.mc_bits)),
-                    pla_and: core::array::from_fn(|_| PTermData {
-                        im_t: BitVec::repeat(false, 40),
-                        im_f: BitVec::repeat(false, 40),
-                        fbn: BitVec::repeat(false, 8),
-                    }),
-                    pla_or: core::array::from_fn(|_| BitVec::repeat(false, 48)),
-                }
-            })
-            .collect();
-        Bitstream {
-            fbs,
-            globals: init_tile(&chip.global_bits),
-        }
-    }
-
-    fn to_jed(&self, chip: &Chip, db: &Database, device: &str) -> JedFile {
-        let mut res = BitVec::new();
-        for fbd in &self.fbs {
-            for i in 0..40 {
-                let n = format!("IM[{i}].MUX");
-                let val = &fbd.misc[&n];
-                res.extend(val);
-            }
-            for i in 0..48 {
-                let pt = &fbd.pla_and[i];
-                for j in 0..40 {
-                    res.push(!pt.im_t[j]);
-                    res.push(!pt.im_f[j]);
-                }
-                for j in 0..8 {
-                    res.push(!pt.fbn[j]);
-                }
-            }
-            for i in 0..48 {
-                for j in 0..16 {
-                    res.push(!fbd.pla_or[j][i]);
-                }
-            }
-            for (bn, bi) in &db.jed_block_bits {
-                res.push(fbd.misc[bn][*bi]);
-            }
-            for iobful in [true, false] {
-                for mc in 0..16 {
-                    if chip.io_mcs.contains(&MacrocellId::from_idx(mc)) != iobful {
-                        continue;
-                    }
-                    let mcd = &fbd.mcs[mc];
-                    let jed_bits = if iobful {
-                        &db.jed_mc_bits_iob
-                    } else {
-                        &db.jed_mc_bits_buried
-                    };
-                    for (bn, bi) in jed_bits {
-                        res.push(mcd[bn][*bi]);
-                    }
-                }
-            }
-        }
-        for (bn, bi) in &chip.jed_global_bits {
-            res.push(self.globals[bn][*bi]);
-        }
-        JedFile::new()
-            .with_fuses(res)
-            .with_note(format!(" DEVICE {device}"))
-    }
-}
-
-fn set_tile_item(data: &mut BTreeMap<String, BitVec>, tile: &Tile, item: &str) {
-    if let Some((name, val)) = item.split_once('=') {
-        let item = &tile.items[name];
-        let val = match &item.kind {
-            TileItemKind::Enum { values } => values[val].clone(),
-            TileItemKind::BitVec { invert } => {
-                assert_eq!(val.len(), item.bits.len());
-                val.chars()
-                    .rev()
-                    .enumerate()
-                    .map(|(i, x)| match x {
-                        '0' => invert[i],
-                        '1' => !invert[i],
-                        _ => unreachable!(),
-                    })
-                    .collect()
-            }
-        };
-        data.insert(name.to_string(), val);
-    } else {
-        let (name, val) = if let Some(name) = item.strip_prefix('!') {
-            (name, false)
-        } else {
-            (item, true)
-        };
-        let item = &tile.items[name];
-        match &item.kind {
-            TileItemKind::Enum { .. } => unreachable!(),
-            TileItemKind::BitVec { invert } => {
-                assert_eq!(item.bits.len(), 1);
-                data.insert(name.to_string(), BitVec::repeat(val ^ invert[0], 1));
-            }
-        }
-    }
-}
 
 pub fn main() -> Result<(), Box<dyn Error>> {
     let m = Command::new("xcpla3_as")
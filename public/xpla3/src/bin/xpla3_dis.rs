@@ -1,116 +1,12 @@
 use std::{collections::BTreeMap, error::Error, path::PathBuf};
 
 use clap::{Arg, Command, value_parser};
-use prjcombine_entity::EntityId;
 use prjcombine_jed::{JedFile, JedParserOptions};
 use prjcombine_types::{
     bitvec::BitVec,
     bsdata::{Tile, TileItemKind},
-    cpld::MacrocellId,
 };
-use prjcombine_xpla3::{Chip, Database};
-
-struct Bitstream {
-    fbs: Vec<FbData>,
-    globals: BTreeMap<String, BitVec>,
-}
-
-struct FbData {
-    misc: BTreeMap<String, BitVec>,
-    mcs: [BTreeMap<String, BitVec>; 16],
-    pla_and: [PTermData; 48],
-    pla_or: [BitVec; 16],
-}
-
-struct PTermData {
-    im_t: BitVec,
-    im_f: BitVec,
-    fbn: BitVec,
-}
-
-impl Bitstream {
-    fn from_jed(jed: &JedFile, chip: &Chip, db: &Database) -> Self {
-        let fuses = jed.fuses.as_ref().unwrap();
-        let mut fbs = vec![];
-        let mut pos = 0;
-        for _ in 0..(chip.block_cols.len() * chip.block_rows * 2) {
-            let mut fbd = FbData {
-                misc: BTreeMap::new(),
-                mcs: core::array::from_fn(|_| BTreeMap::new()),
-                pla_and: core::array::from_fn(|_| PTermData {
-                    im_t: BitVec::new(),
-                    im_f: BitVec::new(),
-                    fbn: BitVec::new(),
-                }),
-                pla_or: core::array::from_fn(|_| BitVec::new()),
-            };
-            for i in 0..40 {
-                let n = format!("IM[{i}].MUX");
-                let data = fuses.slice(pos..(pos + chip.imux_width));
-                pos += chip.imux_width;
-                fbd.misc.insert(n, data);
-            }
-            for i in 0..48 {
-                let pt = &mut fbd.pla_and[i];
-                for _ in 0..40 {
-                    pt.im_t.push(!fuses[pos]);
-                    pos += 1;
-                    pt.im_f.push(!fuses[pos]);
-                    pos += 1;
-                }
-                for _ in 0..8 {
-                    pt.fbn.push(!fuses[pos]);
-                    pos += 1;
-                }
-            }
-            for _ in 0..48 {
-                for j in 0..16 {
-                    fbd.pla_or[j].push(!fuses[pos]);
-                    pos += 1;
-                }
-            }
-            for (bn, bi) in &db.jed_block_bits {
-                let bits = fbd
-                    .misc
-                    .entry(bn.clone())
-                    .or_insert_with(|| BitVec::repeat(false, db.block_bits.items[bn].bits.len()));
-                bits.set(*bi, fuses[pos]);
-                pos += 1;
-            }
-            for iobful in [true, false] {
-                for mc in 0..16 {
-                    if chip.io_mcs.contains(&MacrocellId::from_idx(mc)) != iobful {
-                        continue;
-                    }
-                    let mcd = &mut fbd.mcs[mc];
-                    let jed_bits = if iobful {
-                        &db.jed_mc_bits_iob
-                    } else {
-                        &db.jed_mc_bits_buried
-                    };
-                    for (bn, bi) in jed_bits {
-                        let bits = mcd.entry(bn.clone()).or_insert_with(|| {
-                            BitVec::repeat(false, db.mc_bits.items[bn].bits.len())
-                        });
-                        bits.set(*bi, fuses[pos]);
-                        pos += 1;
-                    }
-                }
-            }
-            fbs.push(fbd);
-        }
-        let mut globals = BTreeMap::new();
-        for (bn, bi) in &chip.jed_global_bits {
-            let bits = globals
-                .entry(bn.clone())
-                .or_insert_with(|| BitVec::repeat(false, chip.global_bits.items[bn].bits.len()));
-            bits.set(*bi, fuses[pos]);
-            pos += 1;
-        }
-        assert_eq!(pos, fuses.len());
-        Bitstream { fbs, globals }
-    }
-}
+use prjcombine_xpla3::{Database, bitstream::Bitstream};
 
 fn print_tile(data: &BTreeMap<String, BitVec>, tile: &Tile) {
     for (k, v) in data {
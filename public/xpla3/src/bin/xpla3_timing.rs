@@ -0,0 +1,64 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{Arg, Command, value_parser};
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_xpla3::{Database, bitstream::Bitstream, timing::timing_graph};
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let m = Command::new("xpla3_timing")
+        .arg(
+            Arg::new("db")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("jed")
+                .required(true)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(Arg::new("package").long("package"))
+        .arg(Arg::new("speed").long("speed"))
+        .get_matches();
+    let arg_db = m.get_one::<PathBuf>("db").unwrap();
+    let arg_jed = m.get_one::<PathBuf>("jed").unwrap();
+    let jed = JedFile::parse_from_file(arg_jed, &JedParserOptions::new().skip_design_spec())?;
+    let mut device = None;
+    for note in &jed.notes {
+        if let Some(dev) = note.strip_prefix(" DEVICE ") {
+            device = Some(dev.to_ascii_lowercase());
+        }
+    }
+    let device = device.unwrap();
+    let parts: Vec<&str> = device.split('-').collect();
+    let dev = parts[0];
+    let package = match m.get_one::<String>("package") {
+        Some(package) => package.to_ascii_lowercase(),
+        None => parts.last().unwrap().to_string(),
+    };
+    let speed = match m.get_one::<String>("speed") {
+        Some(speed) => speed.clone(),
+        None if parts.len() == 3 => format!("-{}", parts[1]),
+        None => {
+            eprintln!("No speed grade given");
+            return Ok(());
+        }
+    };
+    let db = Database::from_file(arg_db)?;
+    let Some(part) = db.devices.iter().find(|p| p.name == dev) else {
+        eprintln!("Unknown device {dev}");
+        return Ok(());
+    };
+    let Some(&bond) = part.bonds.get(&package) else {
+        eprintln!("Unknown package {package}");
+        return Ok(());
+    };
+    let Some(&speed) = part.speeds.get(&speed) else {
+        eprintln!("Unknown speed grade {speed}");
+        return Ok(());
+    };
+    let chip = &db.chips[part.chip];
+    let bs = Bitstream::from_jed(&jed, chip, &db);
+    let graph = timing_graph(&db, chip, &db.bonds[bond], &bs, &db.speeds[speed])?;
+    print!("{}", graph.analyze());
+    Ok(())
+}
@@ -0,0 +1,611 @@
+//! Compiles a [`Design`] into a JED file.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_types::{
+    bsdata::TileItemKind,
+    cpld::{
+        BlockId, MacrocellCoord, MacrocellId,
+        design::{
+            CompileError, ControlKind, Design, Placement, ProductTerm, RegisterKind, Signal, Slew,
+            assign_slots,
+        },
+    },
+};
+
+use crate::{
+    BondPad, Chip, Database,
+    bitstream::{Bitstream, set_tile_item},
+};
+
+const MCS_PER_FB: usize = 16;
+const PTS_PER_FB: usize = 48;
+const IMUX_PER_FB: usize = 40;
+const LCTS_PER_FB: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Pad {
+    Iob(MacrocellCoord),
+    Gclk(usize),
+}
+
+/// A control product term: either the dedicated per-macrocell PT or a shared LCT.
+#[derive(Debug)]
+struct ControlReq {
+    term: ProductTerm,
+    choices: &'static [usize],
+    mux: &'static str,
+}
+
+#[derive(Debug, Default)]
+struct McPlan {
+    items: Vec<String>,
+    sum: Vec<ProductTerm>,
+    // the clock or CE term using the dedicated product term of this macrocell
+    own_pt: Option<ProductTerm>,
+    lcts: Vec<ControlReq>,
+    // the GCLK wanted on FCLK, with the CLK_INV value
+    fclk: Option<(usize, bool)>,
+}
+
+struct Compiler<'a> {
+    db: &'a Database,
+    chip: &'a Chip,
+    design: &'a Design,
+    pins: &'a BTreeMap<String, BondPad>,
+    pad_pins: BTreeMap<Pad, &'a str>,
+    used_pads: BTreeSet<Pad>,
+    input_pads: BTreeMap<&'a str, Pad>,
+    mc_place: BTreeMap<&'a str, MacrocellCoord>,
+    mc_names: BTreeMap<MacrocellCoord, &'a str>,
+    fb_signals: Vec<BTreeSet<&'a Signal>>,
+    fb_terms: Vec<BTreeSet<&'a ProductTerm>>,
+    bs: Bitstream,
+}
+
+impl<'a> Compiler<'a> {
+    fn pad_of(&self, signal: &Signal) -> Option<Pad> {
+        match signal {
+            Signal::Input(name) => self.input_pads.get(name.as_str()).copied(),
+            Signal::Pad(name) => self.mc_place.get(name.as_str()).map(|&crd| Pad::Iob(crd)),
+            Signal::Macrocell(_) => None,
+        }
+    }
+
+    fn gclk_source(&self, term: &ProductTerm) -> Option<(usize, bool)> {
+        let lit = term.single()?;
+        match self.pad_of(&lit.signal)? {
+            Pad::Gclk(gclk) => Some((gclk, lit.inv)),
+            Pad::Iob(_) => None,
+        }
+    }
+
+    fn set_global(&mut self, item: &str) {
+        set_tile_item(&mut self.bs.globals, &self.chip.global_bits, item);
+    }
+
+    fn set_fb(&mut self, fb: usize, item: &str) {
+        let tile = if item.starts_with("IM[") {
+            &self.chip.imux_bits
+        } else {
+            &self.db.block_bits
+        };
+        set_tile_item(&mut self.bs.fbs[fb].misc, tile, item);
+    }
+
+    fn set_mc(&mut self, crd: MacrocellCoord, item: &str) {
+        set_tile_item(
+            &mut self.bs.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()],
+            &self.db.mc_bits,
+            item,
+        );
+    }
+
+    fn resolve_pin(&mut self, pin: &str, output: bool) -> Result<Pad, CompileError> {
+        let Some(&bpad) = self.pins.get(pin) else {
+            return Err(CompileError::UnknownPin(pin.to_string()));
+        };
+        let pad = match bpad {
+            BondPad::Iob(crd) => Pad::Iob(crd),
+            BondPad::Gclk(gclk) if !output => Pad::Gclk(gclk.to_idx()),
+            _ => return Err(CompileError::PinNotUsable(pin.to_string())),
+        };
+        if !self.pad_pins.contains_key(&pad) {
+            // JTAG pins
+            return Err(CompileError::PinNotUsable(pin.to_string()));
+        }
+        if !self.used_pads.insert(pad) {
+            return Err(CompileError::PinConflict(pin.to_string()));
+        }
+        Ok(pad)
+    }
+
+    fn mc_terms(&self, name: &str) -> Vec<&'a ProductTerm> {
+        let mc = &self.design.macrocells[name];
+        let mut terms: Vec<&ProductTerm> = mc.sum.iter().collect();
+        if let Some(ref reg) = mc.reg {
+            if self.gclk_source(&reg.clk).is_none() {
+                terms.push(&reg.clk);
+            }
+            terms.extend([&reg.ce, &reg.rst, &reg.set].into_iter().flatten());
+        }
+        terms.extend(mc.output.iter().filter_map(|out| out.oe.as_ref()));
+        terms
+    }
+
+    fn place_mc(&mut self, name: &'a str, crd: MacrocellCoord) {
+        self.mc_place.insert(name, crd);
+        self.mc_names.insert(crd, name);
+        self.account_mc(name, crd);
+    }
+
+    fn account_mc(&mut self, name: &str, crd: MacrocellCoord) {
+        let terms = self.mc_terms(name);
+        let fb = crd.block.to_idx();
+        self.fb_signals[fb].extend(terms.iter().flat_map(|term| term.signals()));
+        self.fb_terms[fb].extend(terms);
+    }
+
+    fn place(&mut self) -> Result<(), CompileError> {
+        let design = self.design;
+        for (name, inp) in &design.inputs {
+            if let Some(ref pin) = inp.pin {
+                let pad = self.resolve_pin(pin, false)?;
+                self.input_pads.insert(name, pad);
+            }
+        }
+        for (name, mc) in &design.macrocells {
+            if let Some(pin) = mc.output.as_ref().and_then(|out| out.pin.as_ref()) {
+                let Pad::Iob(crd) = self.resolve_pin(pin, true)? else {
+                    unreachable!()
+                };
+                self.mc_place.insert(name, crd);
+                self.mc_names.insert(crd, name);
+            }
+        }
+
+        let clocks = design.control_signals(ControlKind::Clock);
+        for (name, inp) in &design.inputs {
+            if inp.pin.is_some() {
+                continue;
+            }
+            let is_clock = clocks.contains(&Signal::Input(name.clone()));
+            let free: Vec<Pad> = self
+                .pad_pins
+                .keys()
+                .copied()
+                .filter(|pad| {
+                    !self.used_pads.contains(pad)
+                        && !matches!(pad, Pad::Iob(crd) if self.mc_names.contains_key(crd))
+                })
+                .collect();
+            let pad = free
+                .iter()
+                .copied()
+                .find(|pad| matches!(pad, Pad::Gclk(_)) == is_clock)
+                .or_else(|| free.first().copied())
+                .ok_or_else(|| CompileError::NoFreePin(name.clone()))?;
+            self.used_pads.insert(pad);
+            self.input_pads.insert(name, pad);
+        }
+
+        for (&crd, &name) in &self.mc_names.clone() {
+            self.account_mc(name, crd);
+        }
+
+        for with_output in [true, false] {
+            for (name, mc) in &design.macrocells {
+                if self.mc_place.contains_key(name.as_str()) || mc.output.is_some() != with_output {
+                    continue;
+                }
+                let terms = self.mc_terms(name);
+                let signals: BTreeSet<&Signal> =
+                    terms.iter().flat_map(|term| term.signals()).collect();
+                let mut candidates = vec![];
+                for fb in self.chip.blocks() {
+                    for mc in 0..MCS_PER_FB {
+                        let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                        if self.mc_names.contains_key(&crd) {
+                            continue;
+                        }
+                        let has_pad = self.pad_pins.contains_key(&Pad::Iob(crd))
+                            && !self.used_pads.contains(&Pad::Iob(crd));
+                        if with_output && !has_pad {
+                            continue;
+                        }
+                        candidates.push((usize::from(!with_output && has_pad), crd));
+                    }
+                }
+                candidates.sort();
+                let fits = |crd: MacrocellCoord| {
+                    let fb = crd.block.to_idx();
+                    self.fb_signals[fb].union(&signals).count() <= IMUX_PER_FB
+                        && self.fb_terms[fb]
+                            .union(&terms.iter().copied().collect())
+                            .count()
+                            <= PTS_PER_FB
+                };
+                let crd = candidates
+                    .iter()
+                    .map(|&(_, crd)| crd)
+                    .find(|&crd| fits(crd))
+                    .or_else(|| candidates.first().map(|&(_, crd)| crd))
+                    .ok_or_else(|| {
+                        if with_output {
+                            CompileError::NoFreePin(name.clone())
+                        } else {
+                            CompileError::NoFreeMacrocell(name.clone())
+                        }
+                    })?;
+                if with_output {
+                    self.used_pads.insert(Pad::Iob(crd));
+                }
+                self.place_mc(name, crd);
+            }
+        }
+        Ok(())
+    }
+
+    fn plan_mc(&self, name: &str) -> Result<McPlan, CompileError> {
+        let mc = &self.design.macrocells[name];
+        let unsupported = |reason| CompileError::Unsupported {
+            signal: name.to_string(),
+            reason,
+        };
+        let mut plan = McPlan {
+            sum: mc.sum.clone(),
+            ..Default::default()
+        };
+        // the LUT selects on SUM | D1 << 1; D1 is not used
+        plan.items
+            .push(if mc.invert { "LUT=0101" } else { "LUT=1010" }.into());
+        plan.items.push("!REG_D_IREG".into());
+        plan.items.push("!REG_D_SHIFT".into());
+        plan.items.push("REG_D_SHIFT_DIR=UP".into());
+        match mc.reg {
+            None => {
+                plan.items.push("MC_ZIA_MUX=LUT".into());
+                plan.items.push("MC_IOB_MUX=LUT".into());
+                plan.items.push("REG_MODE=DFF".into());
+                plan.items.push("RST_MUX=GND".into());
+                plan.items.push("SET_MUX=GND".into());
+            }
+            Some(ref reg) => {
+                plan.items.push("MC_ZIA_MUX=REG".into());
+                plan.items.push("MC_IOB_MUX=REG".into());
+                if reg.init {
+                    return Err(unsupported("registers always initialize to 0 on XPLA3"));
+                }
+                let mode = match (reg.kind, reg.ce.is_some()) {
+                    (RegisterKind::Dff, false) => "DFF",
+                    (RegisterKind::Dff, true) => "DFFCE",
+                    (RegisterKind::Tff, false) => "TFF",
+                    (RegisterKind::Latch, false) => "LATCH",
+                    (_, true) => {
+                        return Err(unsupported(
+                            "clock enable is only supported on D flip-flops on XPLA3",
+                        ));
+                    }
+                };
+                plan.items.push(format!("REG_MODE={mode}"));
+
+                if let Some((gclk, inv)) = self.gclk_source(&reg.clk) {
+                    plan.fclk = Some((gclk, inv ^ reg.clk_inv));
+                } else {
+                    plan.items
+                        .push(if reg.clk_inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                    plan.items.push("CLK_MUX=PT".into());
+                    plan.own_pt = Some(reg.clk.clone());
+                }
+                if let Some(ref ce) = reg.ce {
+                    if plan.own_pt.is_none() {
+                        plan.items.push("CE_MUX=PT".into());
+                        plan.own_pt = Some(ce.clone());
+                    } else {
+                        plan.lcts.push(ControlReq {
+                            term: ce.clone(),
+                            choices: &[4],
+                            mux: "CE_MUX",
+                        });
+                    }
+                }
+                for (term, mux) in [(&reg.rst, "RST_MUX"), (&reg.set, "SET_MUX")] {
+                    match term {
+                        None => plan.items.push(format!("{mux}=GND")),
+                        Some(term) => plan.lcts.push(ControlReq {
+                            term: term.clone(),
+                            choices: &[0, 1, 2, 3, 5, 4],
+                            mux,
+                        }),
+                    }
+                }
+            }
+        }
+        if let Some(ref out) = mc.output {
+            plan.items.push(
+                match out.slew {
+                    Slew::Slow => "IOB_SLEW=SLOW",
+                    Slew::Fast => "IOB_SLEW=FAST",
+                }
+                .into(),
+            );
+            match out.oe {
+                None => plan.items.push("OE_MUX=VCC".into()),
+                Some(ref oe) => plan.lcts.push(ControlReq {
+                    term: oe.clone(),
+                    choices: &[6, 0, 1, 2],
+                    mux: "OE_MUX",
+                }),
+            }
+        }
+        Ok(plan)
+    }
+
+    fn emit_fb(
+        &mut self,
+        fb: usize,
+        plans: BTreeMap<usize, McPlan>,
+        names: &BTreeMap<usize, &str>,
+    ) -> Result<(), CompileError> {
+        let fbid = BlockId::from_idx(fb);
+        let mc_crd = |mc: usize| MacrocellCoord::simple(fbid, MacrocellId::from_idx(mc));
+        let mut pts: [Option<ProductTerm>; PTS_PER_FB] = core::array::from_fn(|_| None);
+        // pick the FCLK pair covering the most of the wanted global clocks
+        let wanted: BTreeSet<usize> = plans
+            .values()
+            .filter_map(|plan| plan.fclk.map(|(gclk, _)| gclk))
+            .collect();
+        let TileItemKind::Enum { ref values } = self.db.block_bits.items["FCLK_MUX"].kind else {
+            unreachable!()
+        };
+        let (fclk_value, fclk) = values
+            .keys()
+            .map(|val| {
+                let (a, b) = val.split_once('_').unwrap_or((val, val));
+                let fclk: [Option<usize>; 2] =
+                    [a, b].map(|x| x.strip_prefix("GCLK").map(|x| x.parse().unwrap()));
+                (val, fclk)
+            })
+            .max_by_key(|(val, fclk)| {
+                (
+                    wanted
+                        .iter()
+                        .filter(|&&gclk| fclk.contains(&Some(gclk)))
+                        .count(),
+                    *val == "NONE",
+                )
+            })
+            .unwrap();
+        self.set_fb(fb, &format!("FCLK_MUX={fclk_value}"));
+        let mut lcts_used = false;
+        let mut sums = BTreeMap::new();
+        for (mc, mut plan) in plans {
+            let crd = mc_crd(mc);
+            if let Some(term) = plan.own_pt.take() {
+                pts[9 + 2 * mc] = Some(term);
+            }
+            if let Some((gclk, inv)) = plan.fclk {
+                match fclk.iter().position(|&x| x == Some(gclk)) {
+                    Some(slot) => {
+                        plan.items.push(format!("CLK_MUX=FCLK{slot}"));
+                        plan.items
+                            .push(if inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                    }
+                    None => {
+                        // no FCLK left, route the clock pin through the ZIA
+                        if pts[9 + 2 * mc].is_some() {
+                            return Err(CompileError::ControlTermsExhausted(
+                                names[&mc].to_string(),
+                            ));
+                        }
+                        let reg = self.design.macrocells[names[&mc]].reg.as_ref().unwrap();
+                        plan.items
+                            .push(if reg.clk_inv { "CLK_INV" } else { "!CLK_INV" }.into());
+                        plan.items.push("CLK_MUX=PT".into());
+                        pts[9 + 2 * mc] = Some(reg.clk.clone());
+                    }
+                }
+            }
+            for req in plan.lcts {
+                let lct = req
+                    .choices
+                    .iter()
+                    .copied()
+                    .find(|&lct| pts[lct].as_ref() == Some(&req.term))
+                    .or_else(|| req.choices.iter().copied().find(|&lct| pts[lct].is_none()))
+                    .ok_or_else(|| CompileError::ControlTermsExhausted(names[&mc].to_string()))?;
+                pts[lct] = Some(req.term);
+                lcts_used = true;
+                plan.items.push(format!("{mux}=LCT{lct}", mux = req.mux));
+            }
+            for item in &plan.items {
+                self.set_mc(crd, item);
+            }
+            sums.insert(mc, plan.sum);
+        }
+
+        // sum terms can use any product term not taken by a control function, and are
+        // shared between macrocells
+        let mut sum_pts: HashMap<ProductTerm, usize> = HashMap::new();
+        for (mc, sum) in sums {
+            for term in sum {
+                let pt = match sum_pts.get(&term) {
+                    Some(&pt) => pt,
+                    None => {
+                        let Some(pt) = (LCTS_PER_FB..PTS_PER_FB)
+                            .chain(0..LCTS_PER_FB)
+                            .find(|&pt| pts[pt].is_none())
+                        else {
+                            return Err(CompileError::ProductTermsExhausted(
+                                names[&mc].to_string(),
+                            ));
+                        };
+                        pts[pt] = Some(term.clone());
+                        sum_pts.insert(term, pt);
+                        pt
+                    }
+                };
+                self.bs.fbs[fb].pla_or[mc].set(pt, true);
+            }
+        }
+
+        let signals: Vec<&Signal> = pts
+            .iter()
+            .flatten()
+            .flat_map(|term| term.signals())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut candidates = vec![];
+        let mut sources = vec![];
+        for signal in &signals {
+            let src = match signal {
+                Signal::Macrocell(name) => format!("MC_{}", self.mc_place[name.as_str()]),
+                _ => match self.pad_of(signal).unwrap() {
+                    Pad::Iob(crd) => format!("IOB_{crd}"),
+                    Pad::Gclk(gclk) => format!("GCLK{gclk}"),
+                },
+            };
+            let mut cands = vec![];
+            for im in 0..IMUX_PER_FB {
+                if let TileItemKind::Enum { ref values } =
+                    self.chip.imux_bits.items[&format!("IM[{im}].MUX")].kind
+                    && values.contains_key(&src)
+                {
+                    cands.push(im);
+                }
+            }
+            candidates.push(cands);
+            sources.push(src);
+        }
+        let Some(assignment) = assign_slots(&candidates, IMUX_PER_FB) else {
+            return Err(CompileError::RoutingFailed(format!("FB {fb}")));
+        };
+        let mut signal_im = HashMap::new();
+        for (i, &im) in assignment.iter().enumerate() {
+            self.set_fb(fb, &format!("IM[{im}].MUX={src}", src = sources[i]));
+            if let Some(gclk) = sources[i].strip_prefix("GCLK") {
+                let col = fb / 2 / self.chip.block_rows;
+                self.set_global(&format!("FB_COL[{col}].ZIA_GCLK{gclk}_ENABLE"));
+            }
+            signal_im.insert(signals[i], im);
+        }
+        for (pt, term) in pts.iter().enumerate() {
+            let Some(term) = term else { continue };
+            let ptd = &mut self.bs.fbs[fb].pla_and[pt];
+            for lit in &term.literals {
+                let im = signal_im[&lit.signal];
+                if lit.inv {
+                    ptd.im_f.set(im, true);
+                } else {
+                    ptd.im_t.set(im, true);
+                }
+            }
+        }
+
+        if lcts_used || pts[..LCTS_PER_FB].iter().any(|pt| pt.is_some()) {
+            for lct in 0..LCTS_PER_FB {
+                self.set_fb(fb, &format!("!LCT{lct}_INV"));
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self) -> Result<(), CompileError> {
+        let nfb = self.chip.blocks().len();
+        let mut plans: Vec<BTreeMap<usize, McPlan>> = (0..nfb).map(|_| BTreeMap::new()).collect();
+        let mut names: Vec<BTreeMap<usize, &str>> = vec![BTreeMap::new(); nfb];
+        for (&crd, &name) in &self.mc_names {
+            let plan = self.plan_mc(name)?;
+            plans[crd.block.to_idx()].insert(crd.macrocell.to_idx(), plan);
+            names[crd.block.to_idx()].insert(crd.macrocell.to_idx(), name);
+        }
+        // all used pads feed the ZIA; input-only pads also need their output disabled
+        let mut ibuf_mcs = BTreeSet::new();
+        for pad in self.input_pads.clone().into_values() {
+            if let Pad::Iob(crd) = pad {
+                ibuf_mcs.insert(crd);
+                self.set_mc(crd, "OE_MUX=GND");
+            }
+        }
+        for (&name, &crd) in &self.mc_place {
+            if self.design.macrocells[name].output.is_some() {
+                ibuf_mcs.insert(crd);
+            }
+        }
+        for crd in ibuf_mcs {
+            self.set_mc(crd, "IOB_ZIA_MUX=IBUF");
+        }
+        for (fb, plans) in plans.into_iter().enumerate() {
+            if !plans.is_empty() {
+                self.emit_fb(fb, plans, &names[fb])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a design for the given device and package.  Returns the JED file and the
+/// chosen placement.
+pub fn compile(
+    db: &Database,
+    device: &str,
+    package: &str,
+    design: &Design,
+) -> Result<(JedFile, Placement), CompileError> {
+    design.check()?;
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(CompileError::UnknownDevice(device.to_string()));
+    };
+    let Some(&bond) = dev.bonds.get(package) else {
+        return Err(CompileError::UnknownPackage(package.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[bond];
+    let mut pad_pins = BTreeMap::new();
+    for (pin, &pad) in &bond.pins {
+        let pad = match pad {
+            // the JTAG pins are only usable with ISP disabled
+            BondPad::Iob(crd) if !chip.io_special.values().any(|&x| x == crd) => Pad::Iob(crd),
+            BondPad::Gclk(gclk) => Pad::Gclk(gclk.to_idx()),
+            _ => continue,
+        };
+        pad_pins.entry(pad).or_insert(pin.as_str());
+    }
+    let nfb = chip.blocks().len();
+    let mut compiler = Compiler {
+        db,
+        chip,
+        design,
+        pins: &bond.pins,
+        pad_pins,
+        used_pads: BTreeSet::new(),
+        input_pads: BTreeMap::new(),
+        mc_place: BTreeMap::new(),
+        mc_names: BTreeMap::new(),
+        fb_signals: vec![BTreeSet::new(); nfb],
+        fb_terms: vec![BTreeSet::new(); nfb],
+        bs: Bitstream::new(chip, db),
+    };
+    compiler.place()?;
+    compiler.emit()?;
+
+    let mut placement = Placement::default();
+    for (&name, &pad) in &compiler.input_pads {
+        placement
+            .pins
+            .insert(name.to_string(), compiler.pad_pins[&pad].to_string());
+    }
+    for (&name, &crd) in &compiler.mc_place {
+        placement.macrocells.insert(name.to_string(), crd);
+        if design.macrocells[name].output.is_some() {
+            placement.pins.insert(
+                name.to_string(),
+                compiler.pad_pins[&Pad::Iob(crd)].to_string(),
+            );
+        }
+    }
+    Ok((compiler.bs.to_jed(chip, db, device), placement))
+}
@@ -13,11 +13,19 @@ use prjcombine_entity::{
 };
 use prjcombine_types::{
     bsdata::Tile,
-    cpld::{BlockId, MacrocellCoord, MacrocellId},
-    db::{BondId, ChipId, DumpFlags, SpeedId},
+    cpld::{BlockId, MacrocellCoord, MacrocellId, MacrocellTag, jed_bits_diff_node},
+    db::{BondId, BondTag, ChipId, ChipTag, DumpFlags, SpeedId, SpeedTag},
+    diff::{DbDiff, DiffNode},
     speed::Speed,
+    text::{TextError, TextParser, expect_next_id, parse_hex, parse_id, parse_num, split_assign},
 };
 
+pub mod bitstream;
+pub mod compile;
+pub mod sim;
+pub mod svf;
+pub mod timing;
+
 pub struct GclkTag;
 impl EntityTag for GclkTag {
     const PREFIX: &'static str = "GCLK";
@@ -25,6 +33,7 @@ impl EntityTag for GclkTag {
 pub type GclkId = EntityIdU8<GclkTag>;
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chip {
     pub idcode_part: u32,
     pub bs_cols: usize,
@@ -83,9 +92,110 @@ impl Chip {
 
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("idcode_part", format!("0x{:04x}", self.idcode_part));
+        res.insert_leaf("bs_cols", self.bs_cols);
+        res.insert_leaf("imux_width", self.imux_width);
+        res.insert_leaf("block_rows", self.block_rows);
+        for (idx, col) in self.block_cols.iter().enumerate() {
+            res.insert_leaf(
+                format!("block_col {idx}"),
+                format!(
+                    "pt {}, imux {}, mc {}",
+                    col.pt_col, col.imux_col, col.mc_col
+                ),
+            );
+        }
+        for mc in &self.io_mcs {
+            res.insert(format!("io_mc {mc}"), DiffNode::new());
+        }
+        for (k, v) in &self.io_special {
+            res.insert_leaf(format!("io_special {k}"), v);
+        }
+        res.insert("bstile GLOBAL_BITS", self.global_bits.diff_node());
+        res.insert(
+            "jedtile GLOBAL_BITS",
+            jed_bits_diff_node(&self.jed_global_bits),
+        );
+        res.insert("bstile IMUX_BITS", self.imux_bits.diff_node());
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut idcode_part = None;
+        let mut bs_cols = None;
+        let mut imux_width = None;
+        let mut block_rows = None;
+        let mut block_cols = vec![];
+        let mut io_mcs = None;
+        let mut io_special = BTreeMap::new();
+        let mut global_bits = None;
+        let mut jed_global_bits = None;
+        let mut imux_bits = None;
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            match line.block_start() {
+                Some(("bstile", "GLOBAL_BITS")) => global_bits = Some(Tile::parse(p)?),
+                Some(("jedtile", "GLOBAL_BITS")) => jed_global_bits = Some(p.parse_jed_bits()?),
+                Some(("bstile", "IMUX_BITS")) => imux_bits = Some(Tile::parse(p)?),
+                Some(_) => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+                None => {
+                    let (kw, val) = line.keyword_statement()?;
+                    match kw {
+                        "idcode_part" => idcode_part = Some(line.check(parse_hex(val))?),
+                        "bs_cols" => bs_cols = Some(line.check(parse_num(val))?),
+                        "imux_width" => imux_width = Some(line.check(parse_num(val))?),
+                        "block_rows" => block_rows = Some(line.check(parse_num(val))?),
+                        "block_col" => {
+                            let Some((pt_col, imux_col, mc_col)) = val
+                                .strip_prefix("pt ")
+                                .and_then(|v| v.split_once(", imux "))
+                                .and_then(|(pt, v)| Some((pt, v.split_once(", mc ")?)))
+                                .map(|(pt, (imux, mc))| (pt, imux, mc))
+                            else {
+                                return Err(line.error("invalid block column"));
+                            };
+                            block_cols.push(FbColumn {
+                                pt_col: line.check(parse_num(pt_col))?,
+                                imux_col: line.check(parse_num(imux_col))?,
+                                mc_col: line.check(parse_num(mc_col))?,
+                            });
+                        }
+                        "io_mcs" => {
+                            let mut mcs = BTreeSet::new();
+                            for mc in val.split(", ").filter(|mc| !mc.is_empty()) {
+                                mcs.insert(line.check(parse_id(mc, MacrocellTag::PREFIX))?);
+                            }
+                            io_mcs = Some(mcs);
+                        }
+                        "io_special" => {
+                            let (name, mc) = line.check(split_assign(val))?;
+                            io_special.insert(name.to_string(), line.check(mc.parse())?);
+                        }
+                        _ => return Err(line.error(format!("unknown chip statement {kw}"))),
+                    }
+                }
+            }
+        }
+        Ok(Chip {
+            idcode_part: idcode_part.ok_or_else(|| p.missing("idcode_part"))?,
+            bs_cols: bs_cols.ok_or_else(|| p.missing("bs_cols"))?,
+            imux_width: imux_width.ok_or_else(|| p.missing("imux_width"))?,
+            block_rows: block_rows.ok_or_else(|| p.missing("block_rows"))?,
+            block_cols,
+            io_mcs: io_mcs.ok_or_else(|| p.missing("io_mcs"))?,
+            io_special,
+            global_bits: global_bits.ok_or_else(|| p.missing("GLOBAL_BITS"))?,
+            jed_global_bits: jed_global_bits.ok_or_else(|| p.missing("GLOBAL_BITS jedtile"))?,
+            imux_bits: imux_bits.ok_or_else(|| p.missing("IMUX_BITS"))?,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FbColumn {
     pub pt_col: usize,
     pub imux_col: usize,
@@ -93,6 +203,7 @@ pub struct FbColumn {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BondPad {
     Nc,
     Gnd,
@@ -115,7 +226,30 @@ impl std::fmt::Display for BondPad {
     }
 }
 
+impl std::str::FromStr for BondPad {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NC" => BondPad::Nc,
+            "GND" => BondPad::Gnd,
+            "VCC" => BondPad::Vcc,
+            "PORT_EN" => BondPad::PortEn,
+            _ => {
+                if let Some(mc) = s.strip_prefix("IOB_") {
+                    BondPad::Iob(mc.parse()?)
+                } else if s.starts_with(GclkTag::PREFIX) {
+                    BondPad::Gclk(parse_id(s, GclkTag::PREFIX)?)
+                } else {
+                    return Err(format!("unknown bond pad {s}"));
+                }
+            }
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bond {
     pub idcode_part: u32,
     pub pins: BTreeMap<String, BondPad>,
@@ -134,9 +268,76 @@ impl Bond {
         }
         Ok(())
     }
+
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        res.insert_leaf("idcode_part", format!("0x{:04x}", self.idcode_part));
+        for (pin, pad) in &self.pins {
+            res.insert_leaf(format!("pin {pin}"), pad);
+        }
+        res
+    }
+
+    pub fn parse(p: &mut TextParser) -> Result<Self, TextError> {
+        let mut idcode_part = None;
+        let mut pins = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "idcode_part" => idcode_part = Some(line.check(parse_hex(val))?),
+                "pin" => {
+                    let (name, pad) = line.check(split_assign(val))?;
+                    pins.insert(name.to_string(), line.check(pad.parse())?);
+                }
+                _ => return Err(line.error(format!("unknown bond statement {kw}"))),
+            }
+        }
+        Ok(Bond {
+            idcode_part: idcode_part.ok_or_else(|| p.missing("idcode_part"))?,
+            pins,
+        })
+    }
+}
+
+impl Device {
+    pub fn parse(p: &mut TextParser, name: &str) -> Result<Self, TextError> {
+        let mut chip = None;
+        let mut bonds = BTreeMap::new();
+        let mut speeds = BTreeMap::new();
+        while !p.try_block_end() {
+            let line = p.expect_line()?;
+            let (kw, val) = line.keyword_statement()?;
+            match kw {
+                "chip" => chip = Some(line.check(parse_id(val, ChipTag::PREFIX))?),
+                "bond" => {
+                    let (pkg, bond) = line.check(split_assign(val))?;
+                    bonds.insert(
+                        pkg.to_string(),
+                        line.check(parse_id(bond, BondTag::PREFIX))?,
+                    );
+                }
+                "speed" => {
+                    let (sname, speed) = line.check(split_assign(val))?;
+                    speeds.insert(
+                        sname.to_string(),
+                        line.check(parse_id(speed, SpeedTag::PREFIX))?,
+                    );
+                }
+                _ => return Err(line.error(format!("unknown device statement {kw}"))),
+            }
+        }
+        Ok(Device {
+            name: name.to_string(),
+            chip: chip.ok_or_else(|| p.missing("chip"))?,
+            bonds,
+            speeds,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub name: String,
     pub chip: ChipId,
@@ -145,6 +346,7 @@ pub struct Device {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Database {
     pub chips: EntityVec<ChipId, Chip>,
     pub bonds: EntityVec<BondId, Bond>,
@@ -174,6 +376,100 @@ impl Database {
         Ok(bincode::decode_from_std_read(&mut cf, config)?)
     }
 
+    /// Reads back a database from the full text dump written by [`Database::dump`].
+    pub fn from_text(text: &str) -> Result<Self, TextError> {
+        let mut p = TextParser::new(text);
+        let mut chips = EntityVec::new();
+        let mut bonds = EntityVec::new();
+        let mut speeds = EntityVec::new();
+        let mut devices = vec![];
+        let mut mc_bits = None;
+        let mut block_bits = None;
+        let mut jed_mc_bits_iob = None;
+        let mut jed_mc_bits_buried = None;
+        let mut jed_block_bits = None;
+        while let Some(line) = p.peek() {
+            p.expect_line()?;
+            let Some((kw, name)) = line.block_start() else {
+                return Err(line.error(format!("unexpected \"{}\"", line.text())));
+            };
+            match (kw, name) {
+                ("chip", _) => {
+                    line.check(expect_next_id(name, chips.next_id()))?;
+                    chips.push(Chip::parse(&mut p)?);
+                }
+                ("bond", _) => {
+                    line.check(expect_next_id(name, bonds.next_id()))?;
+                    bonds.push(Bond::parse(&mut p)?);
+                }
+                ("speed", _) => {
+                    line.check(expect_next_id(name, speeds.next_id()))?;
+                    speeds.push(Speed::parse(&mut p)?);
+                }
+                ("device", _) => devices.push(Device::parse(&mut p, name)?),
+                ("bstile", "MC_BITS") => mc_bits = Some(Tile::parse(&mut p)?),
+                ("bstile", "BLOCK_BITS") => block_bits = Some(Tile::parse(&mut p)?),
+                ("jedtile", "MC_BITS_IOB") => jed_mc_bits_iob = Some(p.parse_jed_bits()?),
+                ("jedtile", "MC_BITS_BURIED") => jed_mc_bits_buried = Some(p.parse_jed_bits()?),
+                ("jedtile", "BLOCK_BITS") => jed_block_bits = Some(p.parse_jed_bits()?),
+                _ => return Err(line.error(format!("unknown block \"{}\"", line.text()))),
+            }
+        }
+        Ok(Database {
+            chips,
+            bonds,
+            speeds,
+            devices,
+            mc_bits: mc_bits.ok_or_else(|| p.missing("MC_BITS"))?,
+            block_bits: block_bits.ok_or_else(|| p.missing("BLOCK_BITS"))?,
+            jed_mc_bits_iob: jed_mc_bits_iob.ok_or_else(|| p.missing("MC_BITS_IOB jedtile"))?,
+            jed_mc_bits_buried: jed_mc_bits_buried
+                .ok_or_else(|| p.missing("MC_BITS_BURIED jedtile"))?,
+            jed_block_bits: jed_block_bits.ok_or_else(|| p.missing("BLOCK_BITS jedtile"))?,
+        })
+    }
+
+    pub fn from_text_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::from_text(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Compares this database against a newer version by name.
+    pub fn diff(&self, new: &Self) -> DbDiff {
+        DbDiff::new(&self.diff_node(), &new.diff_node())
+    }
+
+    /// Builds the diff tree of the database.  Chips, bonds and speeds are compared through
+    /// the devices and packages that use them, so renumbering them is not a change.
+    pub fn diff_node(&self) -> DiffNode {
+        let mut res = DiffNode::new();
+        for dev in &self.devices {
+            let mut node = DiffNode::new();
+            node.insert("chip", self.chips[dev.chip].diff_node());
+            for (pkg, &bond) in &dev.bonds {
+                node.insert(format!("bond {pkg}"), self.bonds[bond].diff_node());
+            }
+            for (sname, &speed) in &dev.speeds {
+                node.insert(format!("speed {sname}"), self.speeds[speed].diff_node());
+            }
+            res.insert(format!("device {name}", name = dev.name), node);
+        }
+        res.insert("bstile MC_BITS", self.mc_bits.diff_node());
+        res.insert("bstile BLOCK_BITS", self.block_bits.diff_node());
+        res.insert(
+            "jedtile MC_BITS_IOB",
+            jed_bits_diff_node(&self.jed_mc_bits_iob),
+        );
+        res.insert(
+            "jedtile MC_BITS_BURIED",
+            jed_bits_diff_node(&self.jed_mc_bits_buried),
+        );
+        res.insert(
+            "jedtile BLOCK_BITS",
+            jed_bits_diff_node(&self.jed_block_bits),
+        );
+        res
+    }
+
     pub fn dump(&self, o: &mut dyn std::io::Write, flags: DumpFlags) -> std::io::Result<()> {
         if flags.chip || flags.device {
             for (cid, chip) in &self.chips {
@@ -255,8 +551,8 @@ impl Database {
                 for (pkg, bond) in &dev.bonds {
                     writeln!(o, "\tbond {pkg} = {bond};")?;
                 }
-                for speed in dev.speeds.values() {
-                    writeln!(o, "\tspeed {speed};")?;
+                for (sname, speed) in &dev.speeds {
+                    writeln!(o, "\tspeed {sname} = {speed};")?;
                 }
                 writeln!(o, "}}")?;
                 writeln!(o)?;
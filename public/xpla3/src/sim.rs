@@ -0,0 +1,554 @@
+//! Functional simulation of a configured device.
+//!
+//! The simulator evaluates the configuration as decoded from the bitstream, following the
+//! semantics described in the device structure documentation.  It is cycle-based: all
+//! combinatorial logic is re-evaluated until it is stable, and registers are updated when
+//! their clock rises during that process.  Timing is not modelled, so inputs that change
+//! together with a clock are assumed to meet setup time.  Pins that are not driven by
+//! either side keep their last value, like with a bus keeper, unless the pull-up is enabled.
+//!
+//! The `STARTUP` net is high while the simulator is constructed and low afterwards.  The JTAG
+//! pins are treated as unconnected unless `ISP_DISABLE` is programmed.
+
+use std::collections::BTreeMap;
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::TestTarget;
+use prjcombine_types::cpld::{BlockId, MacrocellCoord, MacrocellId};
+
+use crate::{
+    Bond, BondPad, Chip, Database,
+    bitstream::{Bitstream, get_tile_bitvec, get_tile_enum},
+};
+
+pub(crate) const MCS_PER_FB: usize = 16;
+pub(crate) const PTS_PER_FB: usize = 48;
+const IMUX_PER_FB: usize = 40;
+pub(crate) const LCTS_PER_FB: usize = 8;
+pub(crate) const FBNS_PER_FB: usize = 8;
+const NUM_GCLK: usize = 4;
+const MAX_SETTLE_STEPS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImuxSource {
+    Const(bool),
+    Mc(MacrocellCoord),
+    Iob(MacrocellCoord),
+    Gclk(usize),
+    Startup,
+}
+
+/// A control input of a macrocell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CtlSource {
+    Const(bool),
+    Pt(usize),
+    Lct(usize),
+    Uct(usize),
+    Fclk(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegD {
+    Lut,
+    Ireg,
+    ShiftUp,
+    ShiftDown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegMode {
+    Dff,
+    Tff,
+    Latch,
+    Dffce,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Pad {
+    Iob(MacrocellCoord),
+    Gclk(usize),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct McConfig {
+    pub(crate) lut: [bool; 4],
+    pub(crate) reg_d: RegD,
+    pub(crate) mode: RegMode,
+    pub(crate) clk: CtlSource,
+    pub(crate) clk_inv: bool,
+    pub(crate) ce: CtlSource,
+    pub(crate) rst: CtlSource,
+    pub(crate) set: CtlSource,
+    pub(crate) oe: CtlSource,
+    pub(crate) pullup: bool,
+    pub(crate) mc_zia_reg: bool,
+    pub(crate) iob_zia_reg: bool,
+    pub(crate) mc_iob_reg: bool,
+    pub(crate) slew_fast: bool,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PtConfig {
+    // the (FB input, true polarity) pairs included in the product term
+    pub(crate) inputs: Vec<(usize, bool)>,
+    pub(crate) fbns: Vec<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct FbConfig {
+    pub(crate) imux: Vec<ImuxSource>,
+    pub(crate) fclk: [Option<usize>; 2],
+    pub(crate) lct_inv: [bool; LCTS_PER_FB],
+    pub(crate) pts: Vec<PtConfig>,
+    pub(crate) sums: Vec<Vec<usize>>,
+    pub(crate) mcs: Vec<McConfig>,
+    pub(crate) group: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct McState {
+    reg: bool,
+    clk: bool,
+    lut: bool,
+    mc_zia: bool,
+    mc_iob: bool,
+    oe: bool,
+}
+
+/// The configuration of a device, as decoded from the bitstream.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) pins: BTreeMap<String, Pad>,
+    // per FB group, the (FB, LCT) driving each UCT
+    pub(crate) ucts: Vec<[Option<(usize, usize)>; 4]>,
+    pub(crate) fbs: Vec<FbConfig>,
+    pub(crate) jtag: Vec<MacrocellCoord>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    cfg: Config,
+    startup: bool,
+    ext: BTreeMap<Pad, Option<bool>>,
+    ibuf: BTreeMap<Pad, bool>,
+    pts: Vec<[bool; PTS_PER_FB]>,
+    mcs: Vec<[McState; MCS_PER_FB]>,
+}
+
+fn parse_ctl(val: Option<&str>, pt: usize) -> CtlSource {
+    let Some(val) = val else {
+        return CtlSource::Const(false);
+    };
+    if val == "PT" {
+        CtlSource::Pt(pt)
+    } else if val == "VCC" {
+        CtlSource::Const(true)
+    } else if let Some(idx) = val.strip_prefix("LCT") {
+        CtlSource::Lct(idx.parse().unwrap())
+    } else if let Some(idx) = val.strip_prefix("UCT") {
+        CtlSource::Uct(idx.parse().unwrap())
+    } else if let Some(idx) = val.strip_prefix("FCLK") {
+        CtlSource::Fclk(idx.parse().unwrap())
+    } else {
+        CtlSource::Const(false)
+    }
+}
+
+impl Config {
+    pub(crate) fn new(db: &Database, chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let num_fbs = chip.blocks().len();
+        let mut mc_names = BTreeMap::new();
+        for fb in chip.blocks() {
+            for mc in 0..MCS_PER_FB {
+                let crd = MacrocellCoord::simple(fb, MacrocellId::from_idx(mc));
+                mc_names.insert(crd.to_string(), crd);
+            }
+        }
+
+        let mut ucts = vec![];
+        for group in 0.. {
+            if !chip
+                .global_bits
+                .items
+                .contains_key(&format!("FB_GROUP[{group}].UCT0"))
+            {
+                break;
+            }
+            ucts.push(core::array::from_fn(|uct| {
+                let val = get_tile_enum(
+                    &bs.globals,
+                    &chip.global_bits,
+                    &format!("FB_GROUP[{group}].UCT{uct}"),
+                )?;
+                let (fb, lct) = val.strip_prefix("FB")?.split_once("_LCT")?;
+                Some((fb.parse().unwrap(), lct.parse().unwrap()))
+            }));
+        }
+        let isp_disable = get_tile_bitvec(&bs.globals, &chip.global_bits, "ISP_DISABLE")
+            .is_some_and(|val| val[0]);
+
+        let mut fbs = vec![];
+        for (fb, fbd) in bs.fbs.iter().enumerate() {
+            let mut imux = vec![];
+            for im in 0..IMUX_PER_FB {
+                let val = get_tile_enum(&fbd.misc, &chip.imux_bits, &format!("IM[{im}].MUX"));
+                imux.push(match val {
+                    Some("VCC") => ImuxSource::Const(true),
+                    Some("STARTUP") => ImuxSource::Startup,
+                    Some(val) => {
+                        if let Some(crd) = val.strip_prefix("MC_") {
+                            ImuxSource::Mc(mc_names[crd])
+                        } else if let Some(crd) = val.strip_prefix("IOB_") {
+                            ImuxSource::Iob(mc_names[crd])
+                        } else if let Some(idx) = val.strip_prefix("GCLK") {
+                            ImuxSource::Gclk(idx.parse().unwrap())
+                        } else {
+                            ImuxSource::Const(false)
+                        }
+                    }
+                    None => ImuxSource::Const(false),
+                });
+            }
+            let fclk = match get_tile_enum(&fbd.misc, &db.block_bits, "FCLK_MUX") {
+                Some(val) => {
+                    let (a, b) = val.split_once('_').unwrap_or((val, val));
+                    [a, b].map(|x| x.strip_prefix("GCLK").map(|x| x.parse().unwrap()))
+                }
+                None => [None, None],
+            };
+            let lct_inv = core::array::from_fn(|lct| {
+                get_tile_bitvec(&fbd.misc, &db.block_bits, &format!("LCT{lct}_INV"))
+                    .is_some_and(|val| val[0])
+            });
+            let pts = fbd
+                .pla_and
+                .iter()
+                .map(|ptd| {
+                    let mut inputs = vec![];
+                    for im in 0..IMUX_PER_FB {
+                        if ptd.im_t[im] {
+                            inputs.push((im, true));
+                        }
+                        if ptd.im_f[im] {
+                            inputs.push((im, false));
+                        }
+                    }
+                    PtConfig {
+                        inputs,
+                        fbns: (0..FBNS_PER_FB).filter(|&i| ptd.fbn[i]).collect(),
+                    }
+                })
+                .collect();
+            let sums = fbd
+                .pla_or
+                .iter()
+                .map(|st| (0..PTS_PER_FB).filter(|&pt| st[pt]).collect())
+                .collect();
+            let mut mcs = vec![];
+            for (mc, mcd) in fbd.mcs.iter().enumerate() {
+                let get = |name: &str| get_tile_enum(mcd, &db.mc_bits, name);
+                let get_bool =
+                    |name: &str| get_tile_bitvec(mcd, &db.mc_bits, name).is_some_and(|val| val[0]);
+                let lut = get_tile_bitvec(mcd, &db.mc_bits, "LUT");
+                mcs.push(McConfig {
+                    lut: core::array::from_fn(|i| lut.as_ref().is_some_and(|lut| lut[i])),
+                    reg_d: if get_bool("REG_D_SHIFT") {
+                        if get("REG_D_SHIFT_DIR") == Some("DOWN") {
+                            RegD::ShiftDown
+                        } else {
+                            RegD::ShiftUp
+                        }
+                    } else if get_bool("REG_D_IREG") {
+                        RegD::Ireg
+                    } else {
+                        RegD::Lut
+                    },
+                    mode: match get("REG_MODE") {
+                        Some("TFF") => RegMode::Tff,
+                        Some("LATCH") => RegMode::Latch,
+                        Some("DFFCE") => RegMode::Dffce,
+                        _ => RegMode::Dff,
+                    },
+                    clk: parse_ctl(get("CLK_MUX"), 9 + 2 * mc),
+                    clk_inv: get_bool("CLK_INV"),
+                    ce: parse_ctl(get("CE_MUX"), 9 + 2 * mc),
+                    rst: parse_ctl(get("RST_MUX"), 9 + 2 * mc),
+                    set: parse_ctl(get("SET_MUX"), 9 + 2 * mc),
+                    oe: parse_ctl(get("OE_MUX"), 9 + 2 * mc),
+                    pullup: get("OE_MUX") == Some("PULLUP"),
+                    mc_zia_reg: get("MC_ZIA_MUX") == Some("REG"),
+                    iob_zia_reg: get("IOB_ZIA_MUX") == Some("REG"),
+                    mc_iob_reg: get("MC_IOB_MUX") == Some("REG"),
+                    slew_fast: get("IOB_SLEW") == Some("FAST"),
+                });
+            }
+            fbs.push(FbConfig {
+                imux,
+                fclk,
+                lct_inv,
+                pts,
+                sums,
+                mcs,
+                // TODO: exact FB assignment to groups on XCR3512XL; assume halves
+                group: fb * ucts.len() / num_fbs,
+            });
+        }
+
+        let mut pins = BTreeMap::new();
+        for (pin, &pad) in &bond.pins {
+            match pad {
+                BondPad::Iob(crd) => {
+                    pins.insert(pin.clone(), Pad::Iob(crd));
+                }
+                BondPad::Gclk(idx) => {
+                    pins.insert(pin.clone(), Pad::Gclk(idx.to_idx()));
+                }
+                _ => (),
+            }
+        }
+        let jtag = if isp_disable {
+            vec![]
+        } else {
+            chip.io_special.values().copied().collect()
+        };
+        Config {
+            pins,
+            ucts,
+            fbs,
+            jtag,
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new(db: &Database, chip: &Chip, bond: &Bond, bs: &Bitstream) -> Self {
+        let cfg = Config::new(db, chip, bond, bs);
+        let num_fbs = cfg.fbs.len();
+        let mut pads = vec![];
+        for fb in chip.blocks() {
+            for &mc in &chip.io_mcs {
+                pads.push(Pad::Iob(MacrocellCoord::simple(fb, mc)));
+            }
+        }
+        pads.extend((0..NUM_GCLK).map(Pad::Gclk));
+        let mut res = Simulator {
+            cfg,
+            startup: true,
+            ext: pads.iter().map(|&pad| (pad, None)).collect(),
+            ibuf: pads.iter().map(|&pad| (pad, false)).collect(),
+            pts: vec![[false; PTS_PER_FB]; num_fbs],
+            mcs: vec![[McState::default(); MCS_PER_FB]; num_fbs],
+        };
+        res.settle_inner(false);
+        res.startup = false;
+        res.settle_inner(false);
+        res
+    }
+
+    /// Drives a package pin from the outside, or leaves it undriven if `None`.
+    pub fn set_pin(&mut self, pin: &str, val: Option<bool>) {
+        if let Some(&pad) = self.cfg.pins.get(pin) {
+            self.ext.insert(pad, val);
+        }
+    }
+
+    /// Returns the value driven by the device on a package pin, or `None` if the output
+    /// buffer is disabled.
+    pub fn get_pin(&self, pin: &str) -> Option<bool> {
+        self.cfg.pins.get(pin).and_then(|&pad| self.pad_output(pad))
+    }
+
+    /// Returns the ZIA output of a macrocell.
+    pub fn get_mc(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].mc_zia
+    }
+
+    /// Returns the state of a macrocell's register.
+    pub fn get_reg(&self, crd: MacrocellCoord) -> bool {
+        self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()].reg
+    }
+
+    /// Re-evaluates the device until it is stable.
+    pub fn settle(&mut self) {
+        self.settle_inner(true);
+    }
+
+    fn settle_inner(&mut self, clocked: bool) {
+        for _ in 0..MAX_SETTLE_STEPS {
+            if !self.step(clocked) {
+                break;
+            }
+        }
+    }
+
+    fn pad_output(&self, pad: Pad) -> Option<bool> {
+        let Pad::Iob(crd) = pad else {
+            return None;
+        };
+        if self.cfg.jtag.contains(&crd) {
+            return None;
+        }
+        let state = &self.mcs[crd.block.to_idx()][crd.macrocell.to_idx()];
+        state.oe.then_some(state.mc_iob)
+    }
+
+    fn pad_pullup(&self, pad: Pad) -> bool {
+        match pad {
+            Pad::Iob(crd) => {
+                !self.cfg.jtag.contains(&crd)
+                    && self.cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()].pullup
+            }
+            Pad::Gclk(_) => false,
+        }
+    }
+
+    /// Performs a single evaluation step.  Returns true if anything changed.
+    fn step(&mut self, clocked: bool) -> bool {
+        let mut changed = false;
+        let pads: Vec<Pad> = self.ibuf.keys().copied().collect();
+        for pad in pads {
+            let val = self
+                .pad_output(pad)
+                .or(self.ext[&pad])
+                .or(self.pad_pullup(pad).then_some(true));
+            if let Some(val) = val
+                && self.ibuf[&pad] != val
+            {
+                self.ibuf.insert(pad, val);
+                changed = true;
+            }
+        }
+
+        let new_pts: Vec<[bool; PTS_PER_FB]> = self
+            .cfg
+            .fbs
+            .iter()
+            .enumerate()
+            .map(|(fb, fbc)| {
+                // the ZIA GCLK enable fuses are not modelled, as they don't appear to gate
+                // anything on real hardware
+                let inputs: Vec<bool> = fbc
+                    .imux
+                    .iter()
+                    .map(|src| match *src {
+                        ImuxSource::Const(val) => val,
+                        ImuxSource::Mc(crd) => self.get_mc(crd),
+                        ImuxSource::Iob(crd) => {
+                            if self.cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()]
+                                .iob_zia_reg
+                            {
+                                self.get_reg(crd)
+                            } else {
+                                self.ibuf[&Pad::Iob(crd)]
+                            }
+                        }
+                        ImuxSource::Gclk(idx) => self.ibuf[&Pad::Gclk(idx)],
+                        ImuxSource::Startup => self.startup,
+                    })
+                    .collect();
+                let old_pts = &self.pts[fb];
+                core::array::from_fn(|pt| {
+                    let ptc = &fbc.pts[pt];
+                    ptc.inputs.iter().all(|&(im, pol)| inputs[im] == pol)
+                        && ptc
+                            .fbns
+                            .iter()
+                            .all(|&i| old_pts[PTS_PER_FB - FBNS_PER_FB + i])
+                })
+            })
+            .collect();
+        let lct = |fb: usize, lct: usize| new_pts[fb][lct] ^ self.cfg.fbs[fb].lct_inv[lct];
+
+        let mut new_mcs = self.mcs.clone();
+        for (fb, fbc) in self.cfg.fbs.iter().enumerate() {
+            let pts = &new_pts[fb];
+            let ctl = |src: CtlSource| match src {
+                CtlSource::Const(val) => val,
+                CtlSource::Pt(pt) => pts[pt],
+                CtlSource::Lct(idx) => lct(fb, idx),
+                CtlSource::Uct(idx) => self
+                    .cfg
+                    .ucts
+                    .get(fbc.group)
+                    .and_then(|ucts| ucts[idx])
+                    .is_some_and(|(sfb, idx)| lct(sfb, idx)),
+                CtlSource::Fclk(idx) => {
+                    fbc.fclk[idx].is_some_and(|gclk| self.ibuf[&Pad::Gclk(gclk)])
+                }
+            };
+            for mc in 0..MCS_PER_FB {
+                let mcc = &fbc.mcs[mc];
+                let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+                let sum = fbc.sums[mc].iter().any(|&pt| pts[pt]);
+                let select = usize::from(sum) | usize::from(pts[8 + 2 * mc]) << 1;
+                let lut = mcc.lut[select];
+                let ibuf = self.ibuf.get(&Pad::Iob(crd)).copied().unwrap_or(false);
+                let d = match mcc.reg_d {
+                    RegD::Lut => lut,
+                    RegD::Ireg => ibuf,
+                    RegD::ShiftUp => self.mcs[fb][(mc + MCS_PER_FB - 1) % MCS_PER_FB].reg,
+                    RegD::ShiftDown => self.mcs[fb][(mc + 1) % MCS_PER_FB].reg,
+                };
+                let clk = ctl(mcc.clk) ^ mcc.clk_inv;
+
+                let state = &mut new_mcs[fb][mc];
+                if ctl(mcc.rst) {
+                    state.reg = false;
+                } else if ctl(mcc.set) {
+                    state.reg = true;
+                } else if mcc.mode == RegMode::Latch {
+                    if clk {
+                        state.reg = d;
+                    }
+                } else if clocked && clk && !state.clk {
+                    match mcc.mode {
+                        RegMode::Dff => state.reg = d,
+                        RegMode::Tff => state.reg ^= d,
+                        RegMode::Dffce => {
+                            if ctl(mcc.ce) {
+                                state.reg = d;
+                            }
+                        }
+                        RegMode::Latch => unreachable!(),
+                    }
+                }
+                state.clk = clk;
+                state.lut = lut;
+                state.mc_zia = if mcc.mc_zia_reg { state.reg } else { lut };
+                state.mc_iob = if mcc.mc_iob_reg { state.reg } else { lut };
+                state.oe = ctl(mcc.oe);
+            }
+        }
+        if new_pts != self.pts {
+            changed = true;
+        }
+        for (old, new) in self.mcs.iter().zip(&new_mcs) {
+            for (old, new) in old.iter().zip(new) {
+                if (old.reg, old.lut, old.mc_zia, old.mc_iob, old.oe)
+                    != (new.reg, new.lut, new.mc_zia, new.mc_iob, new.oe)
+                {
+                    changed = true;
+                }
+            }
+        }
+        self.pts = new_pts;
+        self.mcs = new_mcs;
+        changed
+    }
+}
+
+/// JEDEC pin numbers refer to the `P<n>` pins of the package.
+impl TestTarget for Simulator {
+    fn set_pin(&mut self, pin: u32, val: Option<bool>) {
+        Simulator::set_pin(self, &format!("P{pin}"), val);
+    }
+
+    fn get_pin(&self, pin: u32) -> Option<bool> {
+        Simulator::get_pin(self, &format!("P{pin}"))
+    }
+
+    fn settle(&mut self) {
+        Simulator::settle(self);
+    }
+}
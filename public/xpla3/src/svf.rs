@@ -0,0 +1,338 @@
+//! Generates JTAG programming sequences (SVF / XSVF) from JED files.
+//!
+//! The fuse array is made of two planes, each programmed one row at a time with the
+//! `ISP_*` instructions.  The row holding the read protection and ISP disable fuses is
+//! programmed in a separate final pass, after the (optional) verification.
+
+use prjcombine_entity::EntityId;
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagProgram, ProgramError, ProgramOptions, bits_from_u64};
+use prjcombine_types::{
+    bitvec::BitVec,
+    bsdata::{TileBit, TileItemKind},
+    cpld::MacrocellId,
+};
+
+use crate::{Chip, Database};
+
+const IR_LEN: usize = 5;
+const IR_IDCODE: u64 = 0x01;
+const IR_ISP_ENABLE: u64 = 0x09;
+const IR_ISP_ERASE: u64 = 0x0a;
+const IR_ISP_PROGRAM: u64 = 0x0b;
+const IR_ISP_VERIFY: u64 = 0x0c;
+const IR_ISP_INIT: u64 = 0x0d;
+const IR_ISP_DISABLE: u64 = 0x10;
+const IR_BYPASS: u64 = 0x1f;
+
+const ENABLE_TIME: u32 = 800;
+const DISABLE_TIME: u32 = 100;
+const ERASE_TIME: u32 = 100000;
+const PROGRAM_TIME: u32 = 10000;
+const INIT_TIME: u32 = 800;
+
+const FINAL_ITEMS: [&str; 2] = ["READ_PROT", "ISP_DISABLE"];
+
+/// Returns the number of rows in each plane of the fuse array.
+pub fn num_rows(chip: &Chip) -> usize {
+    chip.block_rows * 52 + 2
+}
+
+/// Returns the width of the word address in the ISP data register.
+pub fn addr_len(chip: &Chip) -> usize {
+    1 + (usize::BITS - (num_rows(chip) - 1).leading_zeros()) as usize
+}
+
+/// Returns the word address as shifted into the ISP data register: the plane, followed
+/// by the gray-coded row, MSB first.
+fn word_addr(chip: &Chip, plane: usize, row: usize) -> BitVec {
+    let rlen = addr_len(chip) - 1;
+    let gray = row ^ row >> 1;
+    let mut res = BitVec::new();
+    res.push(plane != 0);
+    for i in 0..rlen {
+        res.push((gray >> (rlen - 1 - i) & 1) != 0);
+    }
+    res
+}
+
+/// Converts a JED fuse list into physical fuse array words, indexed by plane and row,
+/// in shift order.
+fn jed_to_words(chip: &Chip, db: &Database, fuses: &BitVec) -> [Vec<BitVec>; 2] {
+    let mut words: [Vec<BitVec>; 2] =
+        core::array::from_fn(|_| vec![BitVec::repeat(true, chip.bs_cols); num_rows(chip)]);
+    let mut pos = 0;
+    let mut put = |col: usize, row: usize, plane: usize| {
+        words[plane][row].set(chip.bs_cols - 1 - col, fuses[pos]);
+        pos += 1;
+    };
+    let pt_row = |fbr: usize, idx: usize| fbr * 52 + if idx < 20 { 2 + idx } else { 10 + idx };
+    let mc_row = |fbr: usize, mc: usize| fbr * 52 + if mc < 8 { mc * 3 } else { 4 + mc * 3 };
+    for fb in chip.blocks() {
+        let fbc = &chip.block_cols[fb.to_idx() / (chip.block_rows * 2)];
+        let fbr = fb.to_idx() / 2 % chip.block_rows;
+        let fb_odd = fb.to_idx() % 2 == 1;
+        let mc_col = |bit: usize| fbc.mc_col + if fb_odd { 9 - bit } else { bit };
+        for i in 0..40 {
+            for j in 0..chip.imux_width {
+                put(
+                    fbc.imux_col + (chip.imux_width - 1 - j),
+                    pt_row(fbr, i),
+                    usize::from(!fb_odd),
+                );
+            }
+        }
+        for pt in 0..48 {
+            let col = fbc.pt_col + if fb_odd { 95 - pt } else { pt };
+            for imux in 0..40 {
+                put(col, pt_row(fbr, imux), 0);
+                put(col, pt_row(fbr, imux), 1);
+            }
+            for (row, plane) in [
+                (0, 1),
+                (0, 0),
+                (1, 1),
+                (1, 0),
+                (50, 0),
+                (50, 1),
+                (51, 0),
+                (51, 1),
+            ] {
+                put(col, fbr * 52 + row, plane);
+            }
+        }
+        for pt in 0..48 {
+            let col = fbc.pt_col + if fb_odd { 95 - pt } else { pt };
+            for mc in 0..16 {
+                put(col, fbr * 52 + 22 + mc / 2, 1 - mc % 2);
+            }
+        }
+        for (name, bit) in &db.jed_block_bits {
+            let crd = db.block_bits.items[name].bits[*bit];
+            put(
+                mc_col(crd.bit.to_idx()),
+                fbr * 52 + 24 + crd.frame.to_idx(),
+                crd.rect.to_idx(),
+            );
+        }
+        for iobful in [true, false] {
+            for mc in 0..16 {
+                if chip.io_mcs.contains(&MacrocellId::from_idx(mc)) != iobful {
+                    continue;
+                }
+                let jed_bits = if iobful {
+                    &db.jed_mc_bits_iob
+                } else {
+                    &db.jed_mc_bits_buried
+                };
+                for (name, bit) in jed_bits {
+                    let crd = db.mc_bits.items[name].bits[*bit];
+                    put(
+                        mc_col(crd.bit.to_idx()),
+                        mc_row(fbr, mc) + crd.frame.to_idx(),
+                        crd.rect.to_idx(),
+                    );
+                }
+            }
+        }
+    }
+    for (name, bit) in &chip.jed_global_bits {
+        let crd = chip.global_bits.items[name].bits[*bit];
+        put(crd.bit.to_idx(), crd.frame.to_idx(), crd.rect.to_idx());
+    }
+    words
+}
+
+/// Returns the (plane, row, shift position) of a global fuse.
+fn global_pos(chip: &Chip, crd: TileBit) -> (usize, usize, usize) {
+    (
+        crd.rect.to_idx(),
+        crd.frame.to_idx(),
+        chip.bs_cols - 1 - crd.bit.to_idx(),
+    )
+}
+
+struct Word {
+    plane: usize,
+    row: usize,
+    data: BitVec,
+}
+
+struct Programmer<'a> {
+    chip: &'a Chip,
+    prog: JtagProgram,
+}
+
+impl Programmer<'_> {
+    fn check_idcode(&mut self) {
+        self.prog.comment("check IDCODE");
+        self.prog.sir(IR_LEN, IR_IDCODE);
+        // the vendor part of IDCODE differs between Philips and Xilinx devices
+        self.prog.sdr_check(
+            BitVec::repeat(false, 32),
+            bits_from_u64(u64::from(self.chip.idcode_part) << 12, 32),
+            bits_from_u64(0x0fff_8000, 32),
+        );
+    }
+
+    fn isp_enable(&mut self) {
+        self.prog.comment("enter ISP mode");
+        self.prog.sir(IR_LEN, IR_ISP_ENABLE);
+        self.prog.run_test(1, ENABLE_TIME);
+    }
+
+    fn isp_disable(&mut self) {
+        self.prog.comment("exit ISP mode");
+        self.prog.sir(IR_LEN, IR_ISP_DISABLE);
+        self.prog.run_test(1, DISABLE_TIME);
+    }
+
+    fn program(&mut self, words: &[Word]) {
+        self.prog.sir(IR_LEN, IR_ISP_PROGRAM);
+        for word in words {
+            let mut tdi = word.data.clone();
+            tdi.extend(word_addr(self.chip, word.plane, word.row).iter());
+            self.prog.sdr(tdi);
+            self.prog.run_test(1, PROGRAM_TIME);
+        }
+    }
+
+    fn verify(&mut self, words: &[Word]) {
+        self.prog.sir(IR_LEN, IR_ISP_VERIFY);
+        for word in words {
+            self.prog.sdr(word_addr(self.chip, word.plane, word.row));
+            self.prog.run_test(20, 0);
+            self.prog.sdr_check(
+                BitVec::repeat(false, self.chip.bs_cols),
+                word.data.clone(),
+                BitVec::repeat(true, self.chip.bs_cols),
+            );
+        }
+    }
+}
+
+/// Generates a JTAG sequence programming the given JED file into a device.
+///
+/// The read protection fuse is programmed if the JED file has the security fuse set,
+/// and the UES is taken from the JED user field, if present.
+pub fn program(
+    db: &Database,
+    device: &str,
+    jed: &JedFile,
+    options: &ProgramOptions,
+) -> Result<JtagProgram, ProgramError> {
+    let Some(dev) = db.devices.iter().find(|dev| dev.name == device) else {
+        return Err(ProgramError::UnknownDevice(device.to_string()));
+    };
+    let chip = &db.chips[dev.chip];
+    let Some(ref fuses) = jed.fuses else {
+        return Err(ProgramError::MissingFuses);
+    };
+    let expected = jed_fuse_count(chip, db);
+    if fuses.len() != expected {
+        return Err(ProgramError::FuseCountMismatch {
+            expected,
+            got: fuses.len(),
+        });
+    }
+    let mut words = jed_to_words(chip, db, fuses);
+
+    let ues = &chip.global_bits.items["UES"];
+    if !jed.user.is_empty() {
+        if jed.user.len() != ues.bits.len() {
+            return Err(ProgramError::UserCodeLengthMismatch {
+                expected: ues.bits.len(),
+                got: jed.user.len(),
+            });
+        }
+        let TileItemKind::BitVec { ref invert } = ues.kind else {
+            unreachable!()
+        };
+        for (i, &crd) in ues.bits.iter().enumerate() {
+            let (plane, row, bit) = global_pos(chip, crd);
+            words[plane][row].set(bit, jed.user[i] ^ invert[i]);
+        }
+    }
+    let read_prot = &chip.global_bits.items["READ_PROT"];
+    if jed.security == Some(true) {
+        let TileItemKind::BitVec { ref invert } = read_prot.kind else {
+            unreachable!()
+        };
+        for (i, &crd) in read_prot.bits.iter().enumerate() {
+            let (plane, row, bit) = global_pos(chip, crd);
+            words[plane][row].set(bit, !invert[i]);
+        }
+    }
+
+    let mut final_words = vec![];
+    for name in FINAL_ITEMS {
+        for &crd in &chip.global_bits.items[name].bits {
+            let (plane, row, _) = global_pos(chip, crd);
+            final_words.push((plane, row));
+        }
+    }
+    final_words.sort_unstable();
+    final_words.dedup();
+    let mut main_words = vec![];
+    for (plane, rows) in words.iter().enumerate() {
+        for (row, data) in rows.iter().enumerate() {
+            if !final_words.contains(&(plane, row)) {
+                main_words.push(Word {
+                    plane,
+                    row,
+                    data: data.clone(),
+                });
+            }
+        }
+    }
+    let final_words: Vec<_> = final_words
+        .into_iter()
+        .map(|(plane, row)| Word {
+            plane,
+            row,
+            data: words[plane][row].clone(),
+        })
+        .collect();
+
+    let mut p = Programmer {
+        chip,
+        prog: JtagProgram::new(),
+    };
+    p.prog.comment(format!("device {device}"));
+    p.check_idcode();
+    p.isp_enable();
+    if options.erase {
+        p.prog.comment("erase the device");
+        p.prog.sir(IR_LEN, IR_ISP_ERASE);
+        p.prog.run_test(1, ERASE_TIME);
+        // read protection is only reevaluated when entering ISP mode
+        p.isp_disable();
+        p.isp_enable();
+    }
+    p.prog.comment("program main fuses");
+    p.program(&main_words);
+    if options.verify {
+        p.prog.comment("verify main fuses");
+        p.verify(&main_words);
+    }
+    p.prog.comment("program protection fuses");
+    p.program(&final_words);
+    p.prog.comment("load the configuration");
+    p.prog.sir(IR_LEN, IR_ISP_INIT);
+    p.prog.run_test(1, INIT_TIME);
+    p.isp_disable();
+    p.prog.sir(IR_LEN, IR_BYPASS);
+    Ok(p.prog)
+}
+
+/// Returns the number of fuses in a JED file for the given chip.
+pub fn jed_fuse_count(chip: &Chip, db: &Database) -> usize {
+    let num_iob = chip.io_mcs.len();
+    let per_fb = 40 * chip.imux_width
+        + 48 * (80 + 8)
+        + 48 * 16
+        + db.jed_block_bits.len()
+        + num_iob * db.jed_mc_bits_iob.len()
+        + (16 - num_iob) * db.jed_mc_bits_buried.len();
+    chip.blocks().len() * per_fb + chip.jed_global_bits.len()
+}
@@ -0,0 +1,288 @@
+//! Static timing analysis of a configured device.
+//!
+//! The timing graph is built from the configuration as decoded for the simulator, using
+//! the delays of the chosen speed grade.  Product terms feeding the LUT directly pay
+//! `DEL_IMUX_PT`, while those going through the sum term pay `DEL_IMUX_OR`; foldback NANDs
+//! add `DEL_IMUX_FBN` in front of the product terms that use them.  Universal control terms
+//! add `DEL_PT_UT` on top of the local control term driving them.
+
+use prjcombine_entity::EntityId;
+use prjcombine_types::{
+    cpld::{
+        BlockId, MacrocellCoord, MacrocellId,
+        timing::{MacrocellTiming, RegisterNodes},
+    },
+    speed::{EdgeDelay, Speed, SpeedError},
+    timing::{ClockEdge, TimingGraph, TimingNodeId, TimingNodeKind},
+};
+
+use crate::{
+    Bond, Chip, Database,
+    bitstream::Bitstream,
+    sim::{
+        Config, CtlSource, FBNS_PER_FB, FbConfig, ImuxSource, LCTS_PER_FB, MCS_PER_FB, PTS_PER_FB,
+        Pad, RegD, RegMode,
+    },
+};
+
+#[derive(Clone, Copy)]
+struct McNodes {
+    lut: TimingNodeId,
+    d: TimingNodeId,
+    clk: TimingNodeId,
+    ce: TimingNodeId,
+    sr: TimingNodeId,
+    oe: TimingNodeId,
+    q: TimingNodeId,
+    zia: TimingNodeId,
+}
+
+/// Returns the nodes feeding a product term: FB inputs and foldback NANDs.
+fn pt_inputs(
+    fbc: &FbConfig,
+    imux: &[TimingNodeId],
+    fbns: &[TimingNodeId],
+    pt: usize,
+) -> Vec<TimingNodeId> {
+    let ptc = &fbc.pts[pt];
+    ptc.inputs
+        .iter()
+        .map(|&(im, _)| imux[im])
+        .chain(ptc.fbns.iter().map(|&i| fbns[i]))
+        .collect()
+}
+
+/// Builds the timing graph of a configured device for the given speed grade.
+pub fn timing_graph(
+    db: &Database,
+    chip: &Chip,
+    bond: &Bond,
+    bs: &Bitstream,
+    speed: &Speed,
+) -> Result<TimingGraph, SpeedError> {
+    let cfg = Config::new(db, chip, bond, bs);
+    let mut mt = MacrocellTiming::new(speed);
+    let (ibufs, pad_pins) = mt.add_input_pads(
+        cfg.pins
+            .iter()
+            .filter(|&(_, &pad)| !matches!(pad, Pad::Iob(crd) if cfg.jtag.contains(&crd)))
+            .map(|(pin, &pad)| (pin.as_str(), pad)),
+        |pad| match pad {
+            Pad::Iob(crd) => format!("{crd}.I"),
+            Pad::Gclk(idx) => format!("GCLK{idx}.I"),
+        },
+    );
+
+    let mut mcs: Vec<Vec<McNodes>> = vec![];
+    let mut lcts: Vec<Vec<TimingNodeId>> = vec![];
+    for fb in 0..cfg.fbs.len() {
+        let mut fb_mcs = vec![];
+        for mc in 0..MCS_PER_FB {
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            let mut node = |name: &str| {
+                mt.graph
+                    .add_node(format!("{crd}.{name}"), TimingNodeKind::Internal)
+            };
+            fb_mcs.push(McNodes {
+                lut: node("LUT"),
+                d: node("D"),
+                clk: node("CLK"),
+                ce: node("CE"),
+                sr: node("SR"),
+                oe: node("OE"),
+                q: node("Q"),
+                zia: node("ZIA"),
+            });
+        }
+        mcs.push(fb_mcs);
+        lcts.push(
+            (0..LCTS_PER_FB)
+                .map(|lct| {
+                    mt.graph
+                        .add_node(format!("FB{fb}.LCT{lct}"), TimingNodeKind::Internal)
+                })
+                .collect(),
+        );
+    }
+    let mut ucts: Vec<Vec<Option<TimingNodeId>>> = vec![];
+    let del_pt_ut = speed.delay("DEL_PT_UT")?;
+    for (group, group_ucts) in cfg.ucts.iter().enumerate() {
+        ucts.push(
+            group_ucts
+                .iter()
+                .enumerate()
+                .map(|(uct, &src)| {
+                    let (fb, lct) = src?;
+                    let node = mt
+                        .graph
+                        .add_node(format!("GROUP{group}.UCT{uct}"), TimingNodeKind::Internal);
+                    mt.graph.add_edge(lcts[fb][lct], node, del_pt_ut);
+                    Some(node)
+                })
+                .collect(),
+        );
+    }
+
+    let del_ibuf_imux = speed.delay("DEL_IBUF_IMUX")?;
+    let del_uim_imux = speed.delay("DEL_UIM_IMUX")?;
+    let del_ibuf_fclk = speed.delay("DEL_IBUF_FCLK")?;
+    let del_ibuf_d = speed.delay("DEL_IBUF_D")?;
+    let del_imux_pt = speed.delay("DEL_IMUX_PT")?;
+    let del_imux_pt_clk = speed.delay("DEL_IMUX_PT_CLK")?;
+    let del_imux_or = speed.delay("DEL_IMUX_OR")?;
+    let del_imux_fbn = speed.delay("DEL_IMUX_FBN")?;
+
+    for (fb, fbc) in cfg.fbs.iter().enumerate() {
+        let imux: Vec<TimingNodeId> = (0..fbc.imux.len())
+            .map(|im| {
+                mt.graph
+                    .add_node(format!("FB{fb}.IM{im}"), TimingNodeKind::Internal)
+            })
+            .collect();
+        for (im, &src) in fbc.imux.iter().enumerate() {
+            match src {
+                ImuxSource::Const(_) | ImuxSource::Startup => (),
+                ImuxSource::Mc(crd) => {
+                    let nodes = mcs[crd.block.to_idx()][crd.macrocell.to_idx()];
+                    mt.graph.add_edge(nodes.zia, imux[im], del_uim_imux);
+                }
+                ImuxSource::Iob(crd) => {
+                    if cfg.fbs[crd.block.to_idx()].mcs[crd.macrocell.to_idx()].iob_zia_reg {
+                        let nodes = mcs[crd.block.to_idx()][crd.macrocell.to_idx()];
+                        mt.graph.add_edge(nodes.q, imux[im], del_uim_imux);
+                    } else if let Some(&ibuf) = ibufs.get(&Pad::Iob(crd)) {
+                        mt.graph.add_edge(ibuf, imux[im], del_ibuf_imux);
+                    }
+                }
+                ImuxSource::Gclk(idx) => {
+                    if let Some(&ibuf) = ibufs.get(&Pad::Gclk(idx)) {
+                        mt.graph.add_edge(ibuf, imux[im], del_ibuf_imux);
+                    }
+                }
+            }
+        }
+        let fbns: Vec<TimingNodeId> = (0..FBNS_PER_FB)
+            .map(|i| {
+                mt.graph
+                    .add_node(format!("FB{fb}.FBN{i}"), TimingNodeKind::Internal)
+            })
+            .collect();
+        for (i, &fbn) in fbns.iter().enumerate() {
+            // foldback NANDs feeding each other are not modelled
+            for &(im, _) in &fbc.pts[PTS_PER_FB - FBNS_PER_FB + i].inputs {
+                mt.graph.add_edge(imux[im], fbn, del_imux_fbn);
+            }
+        }
+        for (lct, &node) in lcts[fb].iter().enumerate() {
+            for src in pt_inputs(fbc, &imux, &fbns, lct) {
+                mt.graph.add_edge(src, node, del_imux_pt);
+            }
+        }
+
+        let ctl = |graph: &mut TimingGraph, src: CtlSource, dst: TimingNodeId, pt_del| match src {
+            CtlSource::Const(_) => (),
+            CtlSource::Pt(pt) => {
+                for src in pt_inputs(fbc, &imux, &fbns, pt) {
+                    graph.add_edge(src, dst, pt_del);
+                }
+            }
+            CtlSource::Lct(idx) => graph.add_edge(lcts[fb][idx], dst, EdgeDelay::ZERO),
+            CtlSource::Uct(idx) => {
+                if let Some(&Some(uct)) = ucts.get(fbc.group).map(|ucts| &ucts[idx]) {
+                    graph.add_edge(uct, dst, EdgeDelay::ZERO);
+                }
+            }
+            CtlSource::Fclk(idx) => {
+                if let Some(gclk) = fbc.fclk[idx]
+                    && let Some(&ibuf) = ibufs.get(&Pad::Gclk(gclk))
+                {
+                    graph.add_edge(ibuf, dst, del_ibuf_fclk);
+                }
+            }
+        };
+
+        for (mc, mcc) in fbc.mcs.iter().enumerate() {
+            let nodes = mcs[fb][mc];
+            let crd = MacrocellCoord::simple(BlockId::from_idx(fb), MacrocellId::from_idx(mc));
+            for &pt in &fbc.sums[mc] {
+                for src in pt_inputs(fbc, &imux, &fbns, pt) {
+                    mt.graph.add_edge(src, nodes.lut, del_imux_or);
+                }
+            }
+            for src in pt_inputs(fbc, &imux, &fbns, 8 + 2 * mc) {
+                mt.graph.add_edge(src, nodes.lut, del_imux_pt);
+            }
+            match mcc.reg_d {
+                RegD::Lut => mt.graph.add_edge(nodes.lut, nodes.d, EdgeDelay::ZERO),
+                RegD::Ireg => {
+                    if let Some(&ibuf) = ibufs.get(&Pad::Iob(crd)) {
+                        mt.graph.add_edge(ibuf, nodes.d, del_ibuf_d);
+                    }
+                }
+                RegD::ShiftUp => mt.graph.add_edge(
+                    mcs[fb][(mc + MCS_PER_FB - 1) % MCS_PER_FB].q,
+                    nodes.d,
+                    EdgeDelay::ZERO,
+                ),
+                RegD::ShiftDown => {
+                    mt.graph
+                        .add_edge(mcs[fb][(mc + 1) % MCS_PER_FB].q, nodes.d, EdgeDelay::ZERO)
+                }
+            }
+            ctl(&mut mt.graph, mcc.clk, nodes.clk, del_imux_pt_clk);
+            ctl(&mut mt.graph, mcc.rst, nodes.sr, del_imux_pt);
+            ctl(&mut mt.graph, mcc.set, nodes.sr, del_imux_pt);
+            ctl(&mut mt.graph, mcc.oe, nodes.oe, del_imux_pt);
+
+            if mcc.mode == RegMode::Dffce {
+                ctl(&mut mt.graph, mcc.ce, nodes.ce, del_imux_pt);
+            }
+            mt.add_register(
+                crd.to_string(),
+                RegisterNodes {
+                    clk: nodes.clk,
+                    d: nodes.d,
+                    ce: (mcc.mode == RegMode::Dffce).then_some(nodes.ce),
+                    sr: nodes.sr,
+                    q: nodes.q,
+                },
+                if mcc.clk_inv {
+                    ClockEdge::Falling
+                } else {
+                    ClockEdge::Rising
+                },
+                "SETUPHOLD_D_CLK",
+                if matches!(mcc.clk, CtlSource::Fclk(_)) {
+                    "WIDTH_CLK"
+                } else {
+                    "WIDTH_CLK_PT"
+                },
+            )?;
+            if mcc.mode == RegMode::Latch {
+                mt.graph
+                    .add_edge(nodes.d, nodes.q, speed.delay("DEL_D_Q_LATCH")?);
+            }
+            mt.graph.add_edge(
+                if mcc.mc_zia_reg { nodes.q } else { nodes.lut },
+                nodes.zia,
+                EdgeDelay::ZERO,
+            );
+
+            if mcc.oe == CtlSource::Const(false) {
+                continue;
+            }
+            let Some(&pin) = pad_pins.get(&Pad::Iob(crd)) else {
+                continue;
+            };
+            mt.add_output_pad(
+                format!("{crd}.O"),
+                pin,
+                if mcc.mc_iob_reg { nodes.q } else { nodes.lut },
+                mcc.slew_fast,
+                "",
+                Some((nodes.oe, speed.delay("DEL_OBUF_OE")?)),
+            )?;
+        }
+    }
+    Ok(mt.graph)
+}
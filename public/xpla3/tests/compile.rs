@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_types::cpld::design::{
+    CompileError, Design, Input, Literal, Macrocell, Output, Placement, ProductTerm, Register,
+    RegisterKind, Signal,
+};
+use prjcombine_xpla3::{BondPad, Database, bitstream::Bitstream, compile::compile, sim::Simulator};
+
+const DEVICE: &str = "xcr3032xl";
+const PACKAGE: &str = "pc44";
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+fn term(lits: &[(Signal, bool)]) -> ProductTerm {
+    ProductTerm::new(lits.iter().map(|(signal, inv)| Literal {
+        signal: signal.clone(),
+        inv: *inv,
+    }))
+}
+
+fn input(name: &str) -> Signal {
+    Signal::Input(name.to_string())
+}
+
+fn mc(name: &str) -> Signal {
+    Signal::Macrocell(name.to_string())
+}
+
+// A 3-bit counter of T flip-flops on a global clock, its outputs enabled by a global OE,
+// and a bidirectional pin that drives q0 while the counter outputs are disabled.  `echo`
+// copies whatever is on the bidirectional pin.
+fn counter_design(bidi_pin: &str) -> Design {
+    let mut design = Design::default();
+    for name in ["clk", "oe"] {
+        design.inputs.insert(name.to_string(), Input::default());
+    }
+    let toggles = [
+        vec![],
+        vec![(mc("q0"), false)],
+        vec![(mc("q0"), false), (mc("q1"), false)],
+    ];
+    for (i, toggle) in toggles.into_iter().enumerate() {
+        design.macrocells.insert(
+            format!("q{i}"),
+            Macrocell {
+                sum: vec![term(&toggle)],
+                reg: Some(Register {
+                    kind: RegisterKind::Tff,
+                    clk: term(&[(input("clk"), false)]),
+                    clk_inv: false,
+                    ce: None,
+                    rst: None,
+                    set: None,
+                    init: false,
+                }),
+                output: Some(Output {
+                    oe: Some(term(&[(input("oe"), false)])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+    }
+    design.macrocells.insert(
+        "bidi".to_string(),
+        Macrocell {
+            sum: vec![term(&[(mc("q0"), false)])],
+            output: Some(Output {
+                pin: Some(bidi_pin.to_string()),
+                oe: Some(term(&[(input("oe"), true)])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    design.macrocells.insert(
+        "echo".to_string(),
+        Macrocell {
+            sum: vec![term(&[(Signal::Pad("bidi".to_string()), false)])],
+            output: Some(Output::default()),
+            ..Default::default()
+        },
+    );
+    design
+}
+
+fn counter_value(sim: &Simulator, placement: &Placement) -> Option<u8> {
+    let mut res = 0;
+    for i in 0..3 {
+        if sim.get_pin(&placement.pins[&format!("q{i}")])? {
+            res |= 1 << i;
+        }
+    }
+    Some(res)
+}
+
+#[test]
+fn compile_counter() {
+    let db = Database::from_file(db_path("xpla3")).unwrap();
+    let design = counter_design("P4");
+    let (jed, placement) = compile(&db, DEVICE, PACKAGE, &design).unwrap();
+
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    assert_eq!(placement.pins["bidi"], "P4");
+    assert_eq!(placement.macrocells["bidi"], "C0B0MC0".parse().unwrap());
+    let pads: BTreeMap<&str, BondPad> = placement
+        .pins
+        .iter()
+        .map(|(name, pin)| (name.as_str(), bond.pins[pin]))
+        .collect();
+    for (name, &crd) in &placement.macrocells {
+        if design.macrocells[name].output.is_some() {
+            assert_eq!(pads[name.as_str()], BondPad::Iob(crd), "{name}");
+        }
+    }
+    assert!(
+        matches!(pads["clk"], BondPad::Gclk(_)),
+        "clk not on a GCLK pin"
+    );
+    assert!(matches!(pads["oe"], BondPad::Iob(_)));
+
+    // feed the JED file, as written out, to the simulator
+    let jed = JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap();
+    let bs = Bitstream::from_jed(&jed, chip, &db);
+    let mut sim = Simulator::new(&db, chip, bond, &bs);
+    let pin = |name: &str| placement.pins[name].clone();
+    sim.set_pin(&pin("oe"), Some(true));
+    sim.set_pin(&pin("clk"), Some(false));
+    sim.settle();
+    assert_eq!(counter_value(&sim, &placement), Some(0));
+    for i in 1..=9 {
+        sim.set_pin(&pin("clk"), Some(true));
+        sim.settle();
+        sim.set_pin(&pin("clk"), Some(false));
+        sim.settle();
+        assert_eq!(counter_value(&sim, &placement), Some(i % 8));
+    }
+
+    // counter outputs enabled: the bidirectional pin is an input
+    assert_eq!(sim.get_pin(&pin("bidi")), None);
+    for val in [true, false, true] {
+        sim.set_pin(&pin("bidi"), Some(val));
+        sim.settle();
+        assert_eq!(sim.get_pin(&pin("echo")), Some(val));
+    }
+
+    // counter outputs disabled: the bidirectional pin drives q0
+    sim.set_pin(&pin("bidi"), None);
+    sim.set_pin(&pin("oe"), Some(false));
+    sim.settle();
+    assert_eq!(counter_value(&sim, &placement), None);
+    assert_eq!(sim.get_pin(&pin("bidi")), Some(true));
+    assert_eq!(sim.get_pin(&pin("echo")), Some(true));
+    sim.set_pin(&pin("clk"), Some(true));
+    sim.settle();
+    assert_eq!(sim.get_pin(&pin("bidi")), Some(false));
+    assert_eq!(sim.get_pin(&pin("echo")), Some(false));
+}
+
+#[test]
+fn compile_errors() {
+    let db = Database::from_file(db_path("xpla3")).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    let chip = &db.chips[dev.chip];
+    // the JTAG pins are not available to the design
+    let num_pads = bond
+        .pins
+        .values()
+        .filter(
+            |pad| matches!(pad, BondPad::Iob(crd) if !chip.io_special.values().any(|x| x == crd)),
+        )
+        .count();
+
+    let mut design = Design::default();
+    for i in 0..=num_pads {
+        design.macrocells.insert(
+            format!("o{i:02}"),
+            Macrocell {
+                output: Some(Output::default()),
+                ..Default::default()
+            },
+        );
+    }
+    assert_eq!(
+        compile(&db, DEVICE, PACKAGE, &design).unwrap_err(),
+        CompileError::NoFreePin(format!("o{num_pads:02}"))
+    );
+
+    // every minterm of 6 inputs: more product terms than a whole function block has
+    let mut design = Design::default();
+    for i in 0..6 {
+        design.inputs.insert(format!("a{i}"), Input::default());
+    }
+    let sum = (0..64)
+        .map(|m: u32| {
+            let lits: Vec<_> = (0..6)
+                .map(|i| (input(&format!("a{i}")), (m >> i & 1) == 0))
+                .collect();
+            term(&lits)
+        })
+        .collect();
+    design.macrocells.insert(
+        "wide".to_string(),
+        Macrocell {
+            sum,
+            output: Some(Output::default()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        compile(&db, DEVICE, PACKAGE, &design).unwrap_err(),
+        CompileError::ProductTermsExhausted("wide".to_string())
+    );
+
+    assert_eq!(
+        compile(&db, "xcr9999", PACKAGE, &design).unwrap_err(),
+        CompileError::UnknownDevice("xcr9999".to_string())
+    );
+}
@@ -0,0 +1,264 @@
+use prjcombine_jed::{JedFile, JedParserOptions, TestValue, TestVectorError};
+use prjcombine_types::cpld::design::{
+    Design, Input, Literal, Macrocell, Output, Placement, ProductTerm, Register, RegisterKind,
+    Signal,
+};
+use prjcombine_xpla3::{Database, bitstream::Bitstream, compile::compile, sim::Simulator};
+
+const PACKAGE: &str = "pc44";
+const INPUTS: [&str; 7] = ["clk", "d", "r", "s", "g", "h", "e"];
+const DEVICE: &str = "xcr3032xl";
+
+fn db() -> Database {
+    Database::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../databases/xpla3.zstd"
+    ))
+    .unwrap()
+}
+
+fn term(lits: &[(&str, bool)]) -> ProductTerm {
+    ProductTerm::new(lits.iter().map(|&(name, inv)| Literal {
+        signal: Signal::Input(name.to_string()),
+        inv,
+    }))
+}
+
+fn reg(kind: RegisterKind, clk: ProductTerm, init: bool) -> Register {
+    Register {
+        kind,
+        clk,
+        clk_inv: false,
+        ce: None,
+        rst: None,
+        set: None,
+        init,
+    }
+}
+
+// `dff` has a single-literal reset and a product term set, `tff` toggles,
+// `ptclk` is clocked by a product term, `lat` is a latch gated by `g`, and `oe` /
+// `oept` have a single-literal and a product term output enable.
+fn register_design() -> Design {
+    let mut design = Design::default();
+    for name in INPUTS {
+        design.inputs.insert(name.to_string(), Input::default());
+    }
+    let mut add = |name: &str, reg: Option<Register>, oe: Option<ProductTerm>| {
+        design.macrocells.insert(
+            name.to_string(),
+            Macrocell {
+                sum: vec![term(&[("d", false)])],
+                reg,
+                output: Some(Output {
+                    oe,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+    };
+    add(
+        "dff",
+        Some(Register {
+            rst: Some(term(&[("r", false)])),
+            set: Some(term(&[("s", false), ("h", false)])),
+            ..reg(RegisterKind::Dff, term(&[("clk", false)]), false)
+        }),
+        None,
+    );
+    add(
+        "tff",
+        Some(reg(RegisterKind::Tff, term(&[("clk", false)]), false)),
+        None,
+    );
+    add(
+        "ptclk",
+        Some(reg(
+            RegisterKind::Dff,
+            term(&[("g", false), ("h", false)]),
+            false,
+        )),
+        None,
+    );
+    add(
+        "lat",
+        Some(reg(RegisterKind::Latch, term(&[("g", false)]), false)),
+        None,
+    );
+    add("oe", None, Some(term(&[("e", false)])));
+    add("oept", None, Some(term(&[("e", false), ("h", true)])));
+    design
+}
+
+struct Bench {
+    sim: Simulator,
+    placement: Placement,
+}
+
+impl Bench {
+    fn set(&mut self, name: &str, val: bool) {
+        self.sim.set_pin(&self.placement.pins[name], Some(val));
+        self.sim.settle();
+    }
+
+    fn pulse(&mut self, name: &str) {
+        self.set(name, true);
+        self.set(name, false);
+    }
+
+    fn get(&self, name: &str) -> Option<bool> {
+        self.sim.get_pin(&self.placement.pins[name])
+    }
+}
+
+#[test]
+fn sim_registers() {
+    let db = db();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    let (jed, placement) = compile(&db, DEVICE, PACKAGE, &register_design()).unwrap();
+    let bs = Bitstream::from_jed(&jed, chip, &db);
+    let mut b = Bench {
+        sim: Simulator::new(&db, chip, bond, &bs),
+        placement,
+    };
+    for name in INPUTS {
+        b.sim.set_pin(&b.placement.pins[name], Some(false));
+    }
+    b.sim.settle();
+    assert_eq!(b.get("dff"), Some(false));
+    assert_eq!(b.get("tff"), Some(false));
+    assert_eq!(b.get("ptclk"), Some(false));
+
+    // D and T flip-flops on the global clock
+    b.set("d", true);
+    assert_eq!(b.get("dff"), Some(false));
+    b.pulse("clk");
+    assert_eq!(b.get("dff"), Some(true));
+    assert_eq!(b.get("tff"), Some(true));
+    b.set("d", false);
+    b.pulse("clk");
+    assert_eq!(b.get("dff"), Some(false));
+    assert_eq!(b.get("tff"), Some(true));
+    b.set("d", true);
+    b.pulse("clk");
+    assert_eq!(b.get("tff"), Some(false));
+
+    // asynchronous reset wins over the clock; the set needs its whole product term
+    b.set("r", true);
+    assert_eq!(b.get("dff"), Some(false));
+    b.pulse("clk");
+    assert_eq!(b.get("dff"), Some(false));
+    b.set("r", false);
+    b.set("s", true);
+    assert_eq!(b.get("dff"), Some(false));
+    b.set("h", true);
+    assert_eq!(b.get("dff"), Some(true));
+    b.set("s", false);
+    b.set("h", false);
+    b.set("d", false);
+    b.pulse("clk");
+    assert_eq!(b.get("dff"), Some(false));
+
+    // product term clock: only the rising edge of g & h captures d
+    b.set("d", true);
+    b.set("g", true);
+    assert_eq!(b.get("ptclk"), Some(false));
+    b.set("h", true);
+    assert_eq!(b.get("ptclk"), Some(true));
+    b.set("d", false);
+    assert_eq!(b.get("ptclk"), Some(true));
+    b.set("h", false);
+    b.set("h", true);
+    assert_eq!(b.get("ptclk"), Some(false));
+    b.set("g", false);
+    b.set("h", false);
+
+    // the latch is transparent while g is high
+    b.set("d", true);
+    assert_eq!(b.get("lat"), Some(false));
+    b.set("g", true);
+    assert_eq!(b.get("lat"), Some(true));
+    b.set("d", false);
+    assert_eq!(b.get("lat"), Some(false));
+    b.set("d", true);
+    b.set("g", false);
+    b.set("d", false);
+    assert_eq!(b.get("lat"), Some(true));
+
+    // output enables
+    b.set("d", true);
+    assert_eq!(b.get("oe"), None);
+    assert_eq!(b.get("oept"), None);
+    b.set("e", true);
+    assert_eq!(b.get("oe"), Some(true));
+    assert_eq!(b.get("oept"), Some(true));
+    b.set("h", true);
+    assert_eq!(b.get("oe"), Some(true));
+    assert_eq!(b.get("oept"), None);
+    b.set("d", false);
+    assert_eq!(b.get("oe"), Some(false));
+}
+
+fn pin_number(placement: &Placement, name: &str) -> u32 {
+    placement.pins[name]
+        .strip_prefix('P')
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+fn vector(values: &str) -> Vec<TestValue> {
+    values
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| TestValue::from_char(c).unwrap())
+        .collect()
+}
+
+#[test]
+fn sim_vectors() {
+    let db = db();
+    let dev = db.devices.iter().find(|dev| dev.name == DEVICE).unwrap();
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    let (jed, placement) = compile(&db, DEVICE, PACKAGE, &register_design()).unwrap();
+    // inputs clk d r s g h e, then outputs dff tff oe oept
+    let mut jed = jed
+        .with_vector(vector("0 0 0 0 0 0 0 L   L   Z  Z"))
+        .with_vector(vector("C 1 0 0 0 0 0 H   H   Z  Z"))
+        .with_vector(vector("C 1 1 0 0 0 1 L   L   H  H"))
+        .with_vector(vector("X 0 X 1 0 1 1 H   N   L  Z"))
+        .with_vector(vector("C 0 0 0 0 0 0 L   L   Z  Z"));
+    jed.pins = Some(
+        [
+            "clk", "d", "r", "s", "g", "h", "e", "dff", "tff", "oe", "oept",
+        ]
+        .map(|name| pin_number(&placement, name))
+        .to_vec(),
+    );
+    jed.default_test_condition = Some(false);
+
+    // the V fields survive the JED file round trip
+    let jed = JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap();
+    assert_eq!(jed.vectors.len(), 5);
+    let bs = Bitstream::from_jed(&jed, chip, &db);
+    let mut sim = Simulator::new(&db, chip, bond, &bs);
+    jed.run_vectors(&mut sim).unwrap();
+
+    // a wrong expectation is reported with its vector and pin
+    let mut bad = jed.clone();
+    bad.vectors[1].values[8] = TestValue::ExpectLow;
+    let mut sim = Simulator::new(&db, chip, bond, &bs);
+    assert_eq!(
+        bad.run_vectors(&mut sim),
+        Err(TestVectorError::Mismatch {
+            vector: 2,
+            pin: pin_number(&placement, "tff"),
+            expected: TestValue::ExpectLow,
+            found: Some(true),
+        })
+    );
+}
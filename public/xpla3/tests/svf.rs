@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use prjcombine_jed::JedFile;
+use prjcombine_svf::{JtagOp, JtagProgram, ProgramError, ProgramOptions};
+use prjcombine_types::bitvec::BitVec;
+use prjcombine_xpla3::{
+    Chip, Database,
+    svf::{addr_len, jed_fuse_count, num_rows, program},
+};
+
+const IR_IDCODE: u64 = 0x01;
+const IR_ISP_ENABLE: u64 = 0x09;
+const IR_ISP_ERASE: u64 = 0x0a;
+const IR_ISP_PROGRAM: u64 = 0x0b;
+const IR_ISP_VERIFY: u64 = 0x0c;
+const IR_ISP_INIT: u64 = 0x0d;
+const IR_ISP_DISABLE: u64 = 0x10;
+const IR_BYPASS: u64 = 0x1f;
+
+const DEVICE: &str = "xcr3032xl";
+
+fn db() -> Database {
+    Database::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../databases/xpla3.zstd"
+    ))
+    .unwrap()
+}
+
+fn get_chip<'a>(db: &'a Database, device: &str) -> &'a Chip {
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    &db.chips[dev.chip]
+}
+
+fn to_u64(bits: &BitVec) -> u64 {
+    bits.iter()
+        .enumerate()
+        .map(|(i, bit)| u64::from(bit) << i)
+        .sum()
+}
+
+fn ir_sequence(prog: &JtagProgram) -> Vec<u64> {
+    prog.ops
+        .iter()
+        .filter_map(|op| match op {
+            JtagOp::ShiftIr(val) => {
+                assert_eq!(val.len(), 5);
+                Some(to_u64(val))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Decodes a word address: the plane, followed by the gray-coded row, MSB first.
+fn decode_addr(addr: &BitVec) -> (usize, usize) {
+    let mut gray = 0;
+    for bit in addr.slice(1..).iter() {
+        gray = gray << 1 | usize::from(bit);
+    }
+    let mut row = 0;
+    while gray != 0 {
+        row ^= gray;
+        gray >>= 1;
+    }
+    (usize::from(addr[0]), row)
+}
+
+/// Returns the data last programmed into every word, and the data checked by the
+/// verification pass.
+#[allow(clippy::type_complexity)]
+fn programmed_words(
+    prog: &JtagProgram,
+    chip: &Chip,
+) -> (
+    BTreeMap<(usize, usize), BitVec>,
+    BTreeMap<(usize, usize), BitVec>,
+) {
+    let mut programmed = BTreeMap::new();
+    let mut verified = BTreeMap::new();
+    let mut ir = 0;
+    let mut addr = None;
+    for op in &prog.ops {
+        match op {
+            JtagOp::ShiftIr(val) => ir = to_u64(val),
+            JtagOp::ShiftDr(shift) if ir == IR_ISP_PROGRAM => {
+                assert_eq!(shift.tdi.len(), chip.bs_cols + addr_len(chip));
+                let word = decode_addr(&shift.tdi.slice(chip.bs_cols..));
+                programmed.insert(word, shift.tdi.slice(..chip.bs_cols));
+            }
+            JtagOp::ShiftDr(shift) if ir == IR_ISP_VERIFY => match shift.check {
+                None => addr = Some(decode_addr(&shift.tdi)),
+                Some((ref tdo, ref mask)) => {
+                    assert!(mask.all());
+                    verified.insert(addr.take().unwrap(), tdo.clone());
+                }
+            },
+            _ => (),
+        }
+    }
+    (programmed, verified)
+}
+
+#[test]
+fn svf_sequence() {
+    let db = db();
+    let chip = get_chip(&db, DEVICE);
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, jed_fuse_count(chip, &db)));
+    let prog = program(&db, DEVICE, &jed, &ProgramOptions::default()).unwrap();
+    assert_eq!(
+        ir_sequence(&prog),
+        [
+            IR_IDCODE,
+            IR_ISP_ENABLE,
+            IR_ISP_ERASE,
+            IR_ISP_DISABLE,
+            IR_ISP_ENABLE,
+            IR_ISP_PROGRAM,
+            IR_ISP_VERIFY,
+            IR_ISP_PROGRAM,
+            IR_ISP_INIT,
+            IR_ISP_DISABLE,
+            IR_BYPASS
+        ]
+    );
+
+    let mut ir = 0;
+    for op in &prog.ops {
+        match *op {
+            JtagOp::ShiftIr(ref val) => ir = to_u64(val),
+            JtagOp::RunTest { tck, usecs } => {
+                let expected = match ir {
+                    IR_ISP_ENABLE => (1, 800),
+                    IR_ISP_ERASE => (1, 100000),
+                    IR_ISP_PROGRAM => (1, 10000),
+                    IR_ISP_VERIFY => (20, 0),
+                    IR_ISP_INIT => (1, 800),
+                    IR_ISP_DISABLE => (1, 100),
+                    _ => panic!("unexpected RUNTEST under IR {ir:02x}"),
+                };
+                assert_eq!((tck, usecs), expected);
+            }
+            _ => (),
+        }
+    }
+
+    let svf = prog.emit_svf();
+    let lines: Vec<_> = svf.lines().skip(5).take(5).collect();
+    assert_eq!(
+        lines,
+        [
+            format!("// device {DEVICE}"),
+            "// check IDCODE".to_string(),
+            "SIR 5 TDI (01);".to_string(),
+            format!(
+                "SDR 32 TDI (00000000) TDO ({idcode:08x}) MASK (0fff8000);",
+                idcode = chip.idcode_part << 12
+            ),
+            "// enter ISP mode".to_string(),
+        ]
+    );
+    assert!(svf.lines().any(|line| line == "RUNTEST 1 TCK 1.00E-1 SEC;"));
+
+    let xsvf = prog.emit_xsvf();
+    let mut expected = vec![
+        0x12, 0x00, 0x12, 0x01, 0x13, 0x00, 0x14, 0x00, 0x07, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+    ];
+    expected.push(0x16);
+    expected.extend(format!("device {DEVICE}\0").bytes());
+    expected.push(0x16);
+    expected.extend(b"check IDCODE\0");
+    expected.extend([0x02, 5, 0x01]);
+    expected.extend([0x08, 0, 0, 0, 32]);
+    expected.extend([0x01, 0x0f, 0xff, 0x80, 0x00]);
+    expected.extend([0x09, 0, 0, 0, 0]);
+    expected.extend((chip.idcode_part << 12).to_be_bytes());
+    expected.push(0x16);
+    expected.extend(b"enter ISP mode\0");
+    expected.extend([0x02, 5, 0x09]);
+    expected.extend([0x17, 0x01, 0x01, 0x00, 0x00, 0x03, 0x20]);
+    assert_eq!(xsvf[..expected.len()], expected);
+}
+
+#[test]
+fn svf_words() {
+    let db = db();
+    for device in [DEVICE, "xcr3256xl"] {
+        let chip = get_chip(&db, device);
+        let count = jed_fuse_count(chip, &db);
+        let mut words = vec![];
+        for val in [false, true] {
+            let jed = JedFile::new().with_fuses(BitVec::repeat(val, count));
+            let prog = program(&db, device, &jed, &ProgramOptions::default()).unwrap();
+            let (programmed, verified) = programmed_words(&prog, chip);
+            // every word of both planes is programmed exactly once
+            assert_eq!(programmed.len(), 2 * num_rows(chip), "{device}");
+            assert_eq!(
+                programmed.keys().copied().collect::<Vec<_>>(),
+                (0..2)
+                    .flat_map(|plane| (0..num_rows(chip)).map(move |row| (plane, row)))
+                    .collect::<Vec<_>>(),
+                "{device}"
+            );
+            // everything but the final pass words is verified against the programmed data
+            assert_eq!(verified.len(), programmed.len() - 1, "{device}");
+            for (word, data) in &verified {
+                assert_eq!(data, &programmed[word], "{device}");
+            }
+            words.push(programmed);
+        }
+        // every fuse lands in its own bit of the array
+        let diff: usize = words[0]
+            .iter()
+            .map(|(word, data)| (data ^ &words[1][word]).iter().filter(|&bit| bit).count())
+            .sum();
+        assert_eq!(diff, count, "{device}");
+    }
+}
+
+#[test]
+fn svf_errors() {
+    let db = db();
+    let chip = get_chip(&db, DEVICE);
+    let options = ProgramOptions::default();
+    let count = jed_fuse_count(chip, &db);
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, count));
+    assert!(matches!(
+        program(&db, "xcr9999xl", &jed, &options),
+        Err(ProgramError::UnknownDevice(ref device)) if device == "xcr9999xl"
+    ));
+    assert!(matches!(
+        program(&db, DEVICE, &JedFile::new(), &options),
+        Err(ProgramError::MissingFuses)
+    ));
+    let mut jed_user = jed.clone();
+    jed_user.user = BitVec::repeat(true, 3);
+    assert!(matches!(
+        program(&db, DEVICE, &jed_user, &options),
+        Err(ProgramError::UserCodeLengthMismatch { got: 3, .. })
+    ));
+    let jed = JedFile::new().with_fuses(BitVec::repeat(true, count - 1));
+    assert!(matches!(
+        program(&db, DEVICE, &jed, &options),
+        Err(ProgramError::FuseCountMismatch { expected, got }) if expected == count && got == count - 1
+    ));
+}
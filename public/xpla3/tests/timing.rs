@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use prjcombine_jed::{JedFile, JedParserOptions};
+use prjcombine_types::{
+    cpld::design::{
+        Design, Input, Literal, Macrocell, Output, ProductTerm, Register, RegisterKind, Signal,
+        Slew,
+    },
+    speed::EdgeArrival,
+    units::{Scalar, Time},
+};
+use prjcombine_xpla3::{Database, bitstream::Bitstream, compile::compile, timing::timing_graph};
+
+const PACKAGE: &str = "pc44";
+
+fn db_path(target: &str) -> String {
+    format!(
+        "{dir}/../../databases/{target}.zstd",
+        dir = env!("CARGO_MANIFEST_DIR")
+    )
+}
+
+fn term(lits: &[(Signal, bool)]) -> ProductTerm {
+    ProductTerm::new(lits.iter().map(|(signal, inv)| Literal {
+        signal: signal.clone(),
+        inv: *inv,
+    }))
+}
+
+fn input(name: &str) -> Signal {
+    Signal::Input(name.to_string())
+}
+
+// A two-input AND gate, and a D flip-flop on a global clock.
+fn design() -> Design {
+    let mut design = Design::default();
+    for name in ["a", "b", "clk"] {
+        design.inputs.insert(name.to_string(), Input::default());
+    }
+    design.macrocells.insert(
+        "comb".to_string(),
+        Macrocell {
+            sum: vec![term(&[(input("a"), false), (input("b"), false)])],
+            output: Some(Output {
+                slew: Slew::Fast,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    design.macrocells.insert(
+        "q".to_string(),
+        Macrocell {
+            sum: vec![term(&[(input("a"), false)])],
+            reg: Some(Register {
+                kind: RegisterKind::Dff,
+                clk: term(&[(input("clk"), false)]),
+                clk_inv: false,
+                ce: None,
+                rst: None,
+                set: None,
+                init: false,
+            }),
+            output: Some(Output {
+                slew: Slew::Fast,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    design
+}
+
+fn ns(val: f64) -> Time {
+    Time(Scalar(val * 1000.0))
+}
+
+// The rising and falling edges take the same time through all of these paths.
+fn arrival(report: &BTreeMap<(String, String), EdgeArrival>, src: &str, dst: &str) -> Time {
+    let arrival = report[&(src.to_string(), dst.to_string())];
+    assert_eq!(arrival.rise, arrival.fall, "{src} -> {dst}");
+    let range = arrival.rise.unwrap();
+    assert_eq!(range.min, range.max, "{src} -> {dst}");
+    range.max
+}
+
+// The data sheet tPD and tCO of the fastest speed grade.
+#[test]
+fn timing_data_sheet() {
+    let db = Database::from_file(db_path("xpla3")).unwrap();
+    let device = "xcr3032xl";
+    let (jed, placement) = compile(&db, device, PACKAGE, &design()).unwrap();
+    let dev = db.devices.iter().find(|dev| dev.name == device).unwrap();
+    let chip = &db.chips[dev.chip];
+    let bond = &db.bonds[dev.bonds[PACKAGE]];
+    let speed = &db.speeds[dev.speeds["-5"]];
+    let jed = JedFile::parse(&jed.emit(), &JedParserOptions::new()).unwrap();
+    let bs = Bitstream::from_jed(&jed, chip, &db);
+    let report = timing_graph(&db, chip, bond, &bs, speed).unwrap().analyze();
+    let pin = |name: &str| placement.pins[name].as_str();
+
+    assert_eq!(arrival(&report.pin_to_pin, pin("a"), pin("comb")), ns(5.0));
+    assert_eq!(arrival(&report.pin_to_pin, pin("b"), pin("comb")), ns(5.0));
+    assert_eq!(arrival(&report.clock_to_out, pin("clk"), pin("q")), ns(3.5));
+    assert!(
+        report
+            .setup_hold
+            .contains_key(&(pin("a").to_string(), pin("clk").to_string()))
+    );
+    assert!(report.fmax(pin("clk")).is_some());
+}
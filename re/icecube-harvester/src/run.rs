@@ -684,7 +684,9 @@ fn parse_dedio(placer_log: String) -> BTreeSet<(InstId, InstPin)> {
 
 */
 
-fn get_result<R: std::io::Read + std::io::Seek>(zip: &mut ZipArchive<R>) -> RunResult {
+fn get_result<R: std::io::Read + std::io::Seek>(
+    zip: &mut ZipArchive<R>,
+) -> Result<RunResult, RunError> {
     let mut read_to_string = |name| {
         let mut res = String::new();
         let mut f = zip.by_name(name).unwrap();
@@ -709,9 +711,12 @@ fn get_result<R: std::io::Read + std::io::Seek>(zip: &mut ZipArchive<R>) -> RunR
         .unwrap()
         .read_to_end(&mut bsdata)
         .unwrap();
-    let bitstream = Bitstream::parse(&bsdata);
+    let bitstream = Bitstream::parse(&bsdata).map_err(|err| RunError {
+        stdout: String::new(),
+        stderr: format!("cannot parse the icecube bitstream: {err}"),
+    })?;
 
-    RunResult {
+    Ok(RunResult {
         pin_table,
         loc_map,
         io_map,
@@ -719,7 +724,7 @@ fn get_result<R: std::io::Read + std::io::Seek>(zip: &mut ZipArchive<R>) -> RunR
         bitstream,
         dedio,
         sdf,
-    }
+    })
 }
 
 pub fn run(toolchain: &Toolchain, design: &Design, key: &str) -> Result<RunResult, RunError> {
@@ -735,7 +740,7 @@ pub fn run(toolchain: &Toolchain, design: &Design, key: &str) -> Result<RunResul
         let cur_design: Design = bincode::decode_from_std_read(&mut design_file, config).unwrap();
         core::mem::drop(design_file);
         if cur_design == *design {
-            return Ok(get_result(&mut ok_zip));
+            return get_result(&mut ok_zip);
         }
     }
     let fail_path = cache_dir.join("fail").join(format!("{key}.zip"));
@@ -909,7 +914,7 @@ pub fn run(toolchain: &Toolchain, design: &Design, key: &str) -> Result<RunResul
             stderr: String::from_utf8_lossy(&status.stderr).to_string(),
         })
     } else {
-        let result = get_result(&mut zip);
+        let result = get_result(&mut zip)?;
         std::fs::create_dir_all(cache_dir.join("ok")).unwrap();
         let _ = std::fs::remove_file(&ok_path);
         std::fs::rename(&work_path, &ok_path).unwrap();
@@ -937,14 +942,16 @@ pub fn get_cached_designs(
             }
         }
     }
-    keys.into_par_iter().map(move |key| {
+    keys.into_par_iter().filter_map(move |key| {
         let zip = ok_dir.join(format!("{key}.zip"));
         let mut zip = ZipArchive::new(File::open(zip).unwrap()).unwrap();
         let mut design_file = zip.by_name("design").unwrap();
         let config = bincode::config::standard();
         let design: Design = bincode::decode_from_std_read(&mut design_file, config).unwrap();
         core::mem::drop(design_file);
-        (key, design, get_result(&mut zip))
+        // runs whose bitstream does not parse are not usable
+        let result = get_result(&mut zip).ok()?;
+        Some((key, design, result))
     })
 }
 
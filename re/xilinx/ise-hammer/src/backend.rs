@@ -688,7 +688,11 @@ impl<'a> Backend for IseBackend<'a> {
             gopts.insert("COMPRESS".to_owned(), "".to_owned());
         }
         let bitdata = run_bitgen(self.tc, &xdl, &gopts, &pcf, altvr).unwrap();
-        parse(self.bs_geom, &bitdata, &key)
+        let (bitstream, warnings) = parse(self.bs_geom, &bitdata, &key).unwrap();
+        for warning in warnings {
+            eprintln!("{warning}");
+        }
+        bitstream
     }
 
     fn return_fuzzer(
@@ -52,7 +52,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     };
     let gedev = db.expand_grid(device);
     let bs_geom = gedev.bs_geom();
-    let bitstream = prjcombine_xilinx_bitstream::parse(bs_geom, &bitdata, &KeyData::None);
+    let (bitstream, warnings) =
+        prjcombine_xilinx_bitstream::parse(bs_geom, &bitdata, &KeyData::None)?;
+    for warning in warnings {
+        eprintln!("{warning}");
+    }
     for (die, dbs) in &bitstream.die {
         if let Some(&val) = dbs.regs.get(&Reg::Idcode) {
             println!("DIE {die} IDCODE {val:08x}");
@@ -344,7 +344,11 @@ impl<'a> Backend for XactBackend<'a> {
             }
         }
         assert_eq!(bitpos, 7);
-        parse(self.bs_geom, &data, &KeyData::None)
+        let (bitstream, warnings) = parse(self.bs_geom, &data, &KeyData::None).unwrap();
+        for warning in warnings {
+            eprintln!("{warning}");
+        }
+        bitstream
     }
 
     fn diff(bs1: &Bitstream, bs2: &Bitstream) -> HashMap<BitPos, bool> {